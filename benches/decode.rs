@@ -0,0 +1,27 @@
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use simple_redis::{BulkString, RespDecode};
+
+const LEN: usize = 16 * 1024 * 1024;
+
+// a client sending a 16 MB value, already in the read buffer
+fn request() -> BytesMut {
+    let mut buf = BytesMut::with_capacity(LEN + 32);
+    buf.extend_from_slice(format!("${}\r\n", LEN).as_bytes());
+    buf.resize(buf.len() + LEN, b'x');
+    buf.extend_from_slice(b"\r\n");
+    buf
+}
+
+fn decode_big_bulk_string(c: &mut Criterion) {
+    c.bench_function("decode a 16 MB bulk string", |b| {
+        b.iter_batched(
+            request,
+            |mut buf| BulkString::decode(&mut buf).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, decode_big_bulk_string);
+criterion_main!(benches);
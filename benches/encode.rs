@@ -0,0 +1,65 @@
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, Criterion};
+use simple_redis::{BulkString, RespArray, RespEncode, RespFrame};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// counts the allocations, to report how many encoding a frame takes
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn big_array() -> RespFrame {
+    RespArray::new(
+        (0..10_000)
+            .map(|i| BulkString::from(format!("member{i}")).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+fn encode_big_array(c: &mut Criterion) {
+    let frame = big_array();
+    let mut buf = BytesMut::new();
+    let mut encode_into = || {
+        buf.clear();
+        buf.reserve(frame.encoded_len());
+        frame.encode_into(&mut buf);
+    };
+    println!(
+        "encode: {} allocations, encode_into: {} allocations, {} once the buffer is reused",
+        allocations(|| drop(frame.clone().encode())) - allocations(|| drop(frame.clone())),
+        allocations(&mut encode_into),
+        allocations(&mut encode_into),
+    );
+
+    c.bench_function("encode a 10k element array", |b| {
+        b.iter(|| frame.clone().encode())
+    });
+    c.bench_function("encode_into a reused buffer a 10k element array", |b| {
+        b.iter(&mut encode_into)
+    });
+}
+
+criterion_group!(benches, encode_big_array);
+criterion_main!(benches);
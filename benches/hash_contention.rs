@@ -0,0 +1,60 @@
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion};
+use simple_redis::{Backend, BulkString};
+
+const TASKS: usize = 16;
+const OPS_PER_TASK: usize = 100;
+const FIELDS: usize = 1000;
+
+// every task writes a field of the same hash and reads all of it back, in turns
+fn mixed_hset_hgetall(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(TASKS)
+        .build()
+        .unwrap();
+    let backend = Backend::new();
+    let fields = (0..FIELDS)
+        .map(|i| {
+            (
+                Bytes::from(format!("field{i}")),
+                BulkString::from("value").into(),
+            )
+        })
+        .collect();
+    backend.hset_multi(Bytes::from("hot"), fields).unwrap();
+
+    c.bench_function("mixed hset/hgetall on one key from 16 tasks", |b| {
+        b.iter(|| {
+            rt.block_on(async {
+                let tasks = (0..TASKS)
+                    .map(|task| {
+                        let backend = backend.clone();
+                        tokio::spawn(async move {
+                            for op in 0..OPS_PER_TASK {
+                                if op % 2 == 0 {
+                                    let field =
+                                        format!("field{}", (task * OPS_PER_TASK + op) % FIELDS);
+                                    backend
+                                        .hset(
+                                            Bytes::from("hot"),
+                                            field.into(),
+                                            BulkString::from("new").into(),
+                                        )
+                                        .unwrap();
+                                } else {
+                                    assert_eq!(backend.hentries(b"hot").unwrap().len(), FIELDS);
+                                }
+                            }
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                for task in tasks {
+                    task.await.unwrap();
+                }
+            })
+        })
+    });
+}
+
+criterion_group!(benches, mixed_hset_hgetall);
+criterion_main!(benches);
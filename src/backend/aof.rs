@@ -0,0 +1,687 @@
+use super::{expire::unix_millis, format_score, snapshot::dump_value, AppendFsync, Backend, Value};
+use crate::cmd::{Command, CommandError, CommandExecutor};
+use crate::{BulkString, RespArray, RespDecode, RespEncode, RespError, RespFrame};
+use bytes::BytesMut;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// what the writer thread of the append only file is sent
+#[derive(Debug)]
+enum AofMessage {
+    // the encoding of a command that ran
+    Append(Vec<u8>),
+    // `appendfsync` changed
+    Fsync(AppendFsync),
+    // flush and fsync what was sent so far, then reply
+    Sync(mpsc::Sender<()>),
+    // keep what is appended from now on for the file being rewritten
+    StartRewrite,
+    // append what was kept to the rewritten file at the path, and swap it in for the file
+    FinishRewrite(PathBuf, mpsc::Sender<io::Result<()>>),
+    // the rewrite failed, stop keeping what is appended
+    AbortRewrite,
+}
+
+// like redis, the elements of a collection are added back by batches of this many
+const REWRITE_BATCH: usize = 64;
+
+/// The end of the channel to the writer thread of the append only file, which owns the file so
+/// that commands never wait on the disk.
+#[derive(Debug)]
+pub(super) struct AofSender {
+    sender: mpsc::Sender<AofMessage>,
+    path: PathBuf,
+    // the database of the last command sent, a SELECT goes first when the next is for another
+    db: Option<usize>,
+}
+
+impl Backend {
+    /// Where the append only file is: `appendfilename` in `dir`.
+    pub fn aof_path(&self) -> PathBuf {
+        let config = self.config();
+        Path::new(&config.dir).join(&config.appendfilename)
+    }
+
+    /// Append every write command that runs from now on to the file at `path`, on a thread of its
+    /// own which syncs it to the disk as `fsync` says. One started already is stopped first.
+    pub fn start_aof(&self, path: &Path, fsync: AppendFsync) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (sender, receiver) = mpsc::channel();
+        let writer_path = path.to_path_buf();
+        std::thread::Builder::new()
+            .name("aof-writer".to_string())
+            .spawn(move || write_aof(file, writer_path, receiver, fsync))?;
+        let aof = AofSender {
+            sender,
+            path: path.to_path_buf(),
+            db: None,
+        };
+        let previous = self.server.aof.lock().unwrap().replace(aof);
+        if let Some(previous) = previous {
+            sync(&previous.sender);
+        }
+        Ok(())
+    }
+
+    /// Stop appending to the file, once what was appended is on the disk.
+    pub fn stop_aof(&self) {
+        let aof = self.server.aof.lock().unwrap().take();
+        if let Some(aof) = aof {
+            sync(&aof.sender);
+        }
+    }
+
+    /// Wait for the commands appended so far to be on the disk, whatever `appendfsync` is.
+    pub fn sync_aof(&self) {
+        // the lock is not held while waiting, for the commands to go on meanwhile
+        let sender = match self.server.aof.lock().unwrap().as_ref() {
+            Some(aof) => aof.sender.clone(),
+            None => return,
+        };
+        sync(&sender);
+    }
+
+    // tell the writer that `appendfsync` changed
+    pub(super) fn set_aof_fsync(&self, fsync: AppendFsync) {
+        if let Some(aof) = self.server.aof.lock().unwrap().as_ref() {
+            let _ = aof.sender.send(AofMessage::Fsync(fsync));
+        }
+    }
+
+    // append the encoded write `command`, run on the database of the handle
+    pub(super) fn append_aof(&self, command: &[u8]) {
+        let mut aof = self.server.aof.lock().unwrap();
+        let Some(aof) = aof.as_mut() else {
+            return;
+        };
+        if aof.db != Some(self.index) {
+            let select = RespArray::new(vec![
+                BulkString::from("SELECT").into(),
+                BulkString::from(self.index.to_string()).into(),
+            ]);
+            let _ = aof.sender.send(AofMessage::Append(select.encode()));
+            aof.db = Some(self.index);
+        }
+        let _ = aof.sender.send(AofMessage::Append(command.to_vec()));
+    }
+
+    /// Rewrite the append only file in the background, like `BGREWRITEAOF`: the commands that
+    /// make the data as it is replace the commands that made it. What runs in the meantime is
+    /// appended to both files. With appendonly off, the file is written all the same.
+    pub fn bgrewriteaof(&self) -> Result<(), CommandError> {
+        if self.server.aof_rewriting.swap(true, Ordering::SeqCst) {
+            return Err(CommandError::AofRewriteInProgress);
+        }
+        let backend = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let (commands, writer) = {
+                let _exclusive = backend.execution_lock().blocking_write();
+                (backend.rewrite_commands(), backend.start_rewrite())
+            };
+            let path = match &writer {
+                Some((_, path)) => path.clone(),
+                None => backend.aof_path(),
+            };
+            let result = finish_rewrite(&path, &commands, writer.as_ref().map(|(s, _)| s));
+            match &result {
+                Ok(()) => info!("Background AOF rewrite finished successfully"),
+                Err(e) => warn!("Background AOF rewrite of {} failed: {}", path.display(), e),
+            }
+            backend.server.aof_rewriting.store(false, Ordering::SeqCst);
+        });
+        Ok(())
+    }
+
+    /// Whether a `BGREWRITEAOF` is rewriting the file.
+    pub fn aof_rewrite_in_progress(&self) -> bool {
+        self.server.aof_rewriting.load(Ordering::SeqCst)
+    }
+
+    /// Whether commands are appended to the append only file.
+    pub fn aof_enabled(&self) -> bool {
+        self.server.aof.lock().unwrap().is_some()
+    }
+
+    // have the writer keep what is appended from now on, returning where to send the rewritten
+    // file and the file it replaces if appendonly is on
+    fn start_rewrite(&self) -> Option<(mpsc::Sender<AofMessage>, PathBuf)> {
+        let mut aof = self.server.aof.lock().unwrap();
+        let aof = aof.as_mut()?;
+        aof.sender.send(AofMessage::StartRewrite).ok()?;
+        // the rewritten file ends on its own database, what comes after must say its own
+        aof.db = None;
+        Some((aof.sender.clone(), aof.path.clone()))
+    }
+
+    // the commands that make every database as it is: a few per key, and one more to expire it
+    fn rewrite_commands(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut emit = |args: Vec<BulkString>| {
+            let args = args.into_iter().map(RespFrame::from).collect::<Vec<_>>();
+            out.extend(RespArray::new(args).encode());
+        };
+        let (now, now_ms) = (Instant::now(), unix_millis());
+        for db in self.databases() {
+            if db.dbsize() == 0 {
+                continue;
+            }
+            emit(vec!["SELECT".into(), db.index().to_string().into()]);
+            for item in db.entries.iter() {
+                let Some(expiry) = item.unix_expiry(now, now_ms) else {
+                    continue;
+                };
+                let key = item.key();
+                let commands = match &item.value {
+                    Value::Str(s) => vec![vec!["SET".into(), key.into(), s[..].into()]],
+                    Value::List(list) => {
+                        let values = list.iter().map(argument).collect::<Vec<_>>();
+                        batches("RPUSH", key, values, 1)
+                    }
+                    Value::Set(set) => {
+                        let members = set.iter().map(|member| member.key().into()).collect();
+                        batches("SADD", key, members, 1)
+                    }
+                    Value::Hash(hash) => {
+                        let fields = hash
+                            .iter()
+                            .flat_map(|field| [field.key().into(), argument(field.value())])
+                            .collect();
+                        batches("HSET", key, fields, 2)
+                    }
+                    Value::ZSet(zset) => {
+                        let members = zset
+                            .iter()
+                            .flat_map(|(member, score)| [format_score(score).into(), member.into()])
+                            .collect();
+                        batches("ZADD", key, members, 2)
+                    }
+                    // the groups, their consumers and pending entries can't be made by commands
+                    // the way they are, the stream is restored whole
+                    Value::Stream(_) => vec![vec![
+                        "RESTORE".into(),
+                        key.into(),
+                        "0".into(),
+                        BulkString::new(dump_value(&item.value)),
+                        "REPLACE".into(),
+                    ]],
+                };
+                commands.into_iter().for_each(&mut emit);
+                if let Some(expiry) = expiry {
+                    emit(vec![
+                        "PEXPIREAT".into(),
+                        key.into(),
+                        expiry.to_string().into(),
+                    ]);
+                }
+            }
+        }
+        out
+    }
+
+    /// Run the commands of the append only file at `path`, returning how many ran. A command cut
+    /// short at the end, as a crash while writing it leaves it, is truncated away with a warning.
+    pub fn load_aof(&self, path: &Path) -> io::Result<usize> {
+        let mut buf = BytesMut::from(std::fs::read(path)?.as_slice());
+        let len = buf.len();
+        let (mut db, mut commands) = (self.clone(), 0);
+        loop {
+            let offset = len - buf.len();
+            let frame = match RespFrame::decode(&mut buf) {
+                Ok(frame) => frame,
+                Err(RespError::NotComplete) if buf.is_empty() => break,
+                Err(RespError::NotComplete) => {
+                    warn!(
+                        "{} ends with an incomplete command, truncated to {} bytes",
+                        path.display(),
+                        offset
+                    );
+                    OpenOptions::new()
+                        .write(true)
+                        .open(path)?
+                        .set_len(offset as u64)?;
+                    break;
+                }
+                Err(e) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("bad command at byte {}: {}", offset, e),
+                    ))
+                }
+            };
+            let written = Command::written_keys(&frame);
+            let cmd = Command::try_from(frame).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("bad command at byte {}: {}", offset, e),
+                )
+            })?;
+            // the file only has the commands that succeeded, they succeed again
+            match cmd {
+                Command::Select(select) => {
+                    db = self
+                        .select(select.index)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?
+                }
+                cmd => {
+                    db.mark_written(&written);
+                    if let Err(e) = cmd.execute(&db) {
+                        warn!("command at byte {} failed again: {}", offset, e);
+                    }
+                }
+            }
+            commands += 1;
+        }
+        info!("Replayed {} commands of {}", commands, path.display());
+        Ok(commands)
+    }
+}
+
+// the `name key` commands adding `elements`, `per_element` arguments each, by batches
+fn batches(
+    name: &str,
+    key: &[u8],
+    elements: Vec<BulkString>,
+    per_element: usize,
+) -> Vec<Vec<BulkString>> {
+    elements
+        .chunks(REWRITE_BATCH * per_element)
+        .map(|batch| {
+            let mut args = vec![name.into(), key.into()];
+            args.extend_from_slice(batch);
+            args
+        })
+        .collect()
+}
+
+// a value as the argument of a command, which is what any value stored by a command was
+fn argument(value: &RespFrame) -> BulkString {
+    match value {
+        RespFrame::BulkString(s) => s.clone(),
+        RespFrame::SimpleString(s) => s.as_str().into(),
+        RespFrame::Integer(n) => n.to_string().into(),
+        frame => BulkString::new(frame.clone().encode()),
+    }
+}
+
+// write the rewritten `commands` to a temporary file next to `path`, and have `writer` append to
+// it what ran since and swap it in. Without a writer it is swapped in right away
+fn finish_rewrite(
+    path: &Path,
+    commands: &[u8],
+    writer: Option<&mpsc::Sender<AofMessage>>,
+) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    let temp = path.with_file_name(format!(
+        "temp-rewriteaof-{}-{}",
+        std::process::id(),
+        name.to_string_lossy()
+    ));
+    let written = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(commands)?;
+        file.sync_data()
+    })();
+    let result = match (written, writer) {
+        (Err(e), Some(writer)) => {
+            let _ = writer.send(AofMessage::AbortRewrite);
+            Err(e)
+        }
+        (Err(e), None) => Err(e),
+        (Ok(()), Some(writer)) => {
+            let (done, wait) = mpsc::channel();
+            match writer.send(AofMessage::FinishRewrite(temp.clone(), done)) {
+                Ok(()) => wait.recv().unwrap_or_else(|_| {
+                    Err(io::Error::other("the writer stopped during the rewrite"))
+                }),
+                // appendonly was turned off meanwhile, nothing else needs appending
+                Err(_) => std::fs::rename(&temp, path),
+            }
+        }
+        (Ok(()), None) => std::fs::rename(&temp, path),
+    };
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+// wait for the writer to have flushed and synced what it was sent
+fn sync(sender: &mpsc::Sender<AofMessage>) {
+    let (done, wait) = mpsc::channel();
+    if sender.send(AofMessage::Sync(done)).is_ok() {
+        let _ = wait.recv();
+    }
+}
+
+// the writer thread: it appends what it is sent, flushing once there is nothing more to append
+// for now, and syncs to the disk after every command, every second or never. It stops once the
+// sender is dropped
+fn write_aof(
+    file: File,
+    path: PathBuf,
+    receiver: mpsc::Receiver<AofMessage>,
+    mut fsync: AppendFsync,
+) {
+    const SECOND: Duration = Duration::from_secs(1);
+    let mut file = BufWriter::new(file);
+    let mut synced = Instant::now();
+    // what was appended since a rewrite started, for the rewritten file
+    let mut rewrite: Option<Vec<u8>> = None;
+    // what failed last, logged once rather than for every command
+    let mut failing = false;
+    loop {
+        let message = match receiver.recv_timeout(SECOND) {
+            Ok(message) => Some(message),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let mut replies = Vec::new();
+        let mut appended = false;
+        for message in message.into_iter().chain(receiver.try_iter()) {
+            match message {
+                AofMessage::Append(command) => {
+                    appended = true;
+                    report(&mut failing, file.write_all(&command));
+                    if let Some(rewrite) = rewrite.as_mut() {
+                        rewrite.extend_from_slice(&command);
+                    }
+                    if fsync == AppendFsync::Always {
+                        report(&mut failing, flush_and_sync(&mut file));
+                    }
+                }
+                AofMessage::Fsync(policy) => fsync = policy,
+                AofMessage::Sync(reply) => replies.push(reply),
+                AofMessage::StartRewrite => rewrite = Some(Vec::new()),
+                AofMessage::FinishRewrite(temp, done) => {
+                    let appended = rewrite.take().unwrap_or_default();
+                    let _ = done.send(swap_in(&mut file, &path, &temp, &appended));
+                }
+                AofMessage::AbortRewrite => rewrite = None,
+            }
+        }
+        if !replies.is_empty() || (fsync == AppendFsync::EverySec && synced.elapsed() >= SECOND) {
+            report(&mut failing, flush_and_sync(&mut file));
+            synced = Instant::now();
+        } else if appended {
+            report(&mut failing, file.flush());
+        }
+        for reply in replies {
+            let _ = reply.send(());
+        }
+    }
+    report(&mut failing, flush_and_sync(&mut file));
+}
+
+// append `appended` to the rewritten file at `temp`, then rename it over the file at `path` and
+// append to it from now on. On failure the file is left as it was
+fn swap_in(
+    file: &mut BufWriter<File>,
+    path: &Path,
+    temp: &Path,
+    appended: &[u8],
+) -> io::Result<()> {
+    file.flush()?;
+    let mut rewritten = OpenOptions::new().append(true).open(temp)?;
+    rewritten.write_all(appended)?;
+    rewritten.sync_data()?;
+    std::fs::rename(temp, path)?;
+    *file = BufWriter::new(rewritten);
+    Ok(())
+}
+
+fn flush_and_sync(file: &mut BufWriter<File>) -> io::Result<()> {
+    file.flush()?;
+    file.get_ref().sync_data()
+}
+
+fn report(failing: &mut bool, result: io::Result<()>) {
+    match result {
+        Err(e) if !*failing => {
+            warn!("Writing the append only file failed: {}", e);
+            *failing = true;
+        }
+        Err(_) => {}
+        Ok(()) => *failing = false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use anyhow::Result;
+
+    fn aof_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("simple-redis-{}-{}.aof", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_replay_restores_the_data() -> Result<()> {
+        let path = aof_file("replay");
+        let _ = std::fs::remove_file(&path);
+        let backend = Backend::new();
+        backend.start_aof(&path, AppendFsync::EverySec)?;
+        run_args(&backend, &["set", "key", "value"])?;
+        run_args(&backend, &["rpush", "list", "a", "b", "c"])?;
+        run_args(&backend, &["lpop", "list"])?;
+        run_args(&backend, &["get", "key"])?;
+        // a failed write is not appended, and would fail again
+        assert!(run_args(&backend, &["lpush", "key", "x"]).is_err());
+        run_args(&backend.select(2)?, &["hset", "hash", "field", "1"])?;
+        run_args(&backend.select(2)?, &["hincrby", "hash", "field", "2"])?;
+        run_args(&backend, &["incr", "counter"])?;
+        backend.stop_aof();
+
+        let restored = Backend::new();
+        // the SELECTs before switching databases count too
+        assert_eq!(restored.load_aof(&path)?, 9);
+        assert_eq!(restored.get(b"key"), Some(BulkString::from("value").into()));
+        assert_eq!(restored.llen(b"list")?, 2);
+        assert_eq!(
+            restored.select(2)?.hget(b"hash", b"field"),
+            Some(BulkString::from("3").into())
+        );
+        assert_eq!(restored.get(b"counter"), Some(BulkString::from("1").into()));
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_is_deterministic() -> Result<()> {
+        let path = aof_file("deterministic");
+        let _ = std::fs::remove_file(&path);
+        let backend = Backend::new();
+        backend.start_aof(&path, AppendFsync::EverySec)?;
+        run_args(&backend, &["set", "short", "value", "px", "200"])?;
+        run_args(&backend, &["set", "long", "value", "nx", "ex", "100"])?;
+        run_args(&backend, &["rpush", "list", "a"])?;
+        run_args(&backend, &["pexpire", "list", "200"])?;
+        let id = run_args(&backend, &["xadd", "stream", "*", "field", "1"])?;
+        run_args(&backend, &["sadd", "set", "a", "b", "c", "d"])?;
+        run_args(&backend, &["spop", "set", "2"])?;
+        backend.stop_aof();
+        std::thread::sleep(Duration::from_millis(300));
+
+        // the keys expire when they did, not as long after the replay
+        let restored = Backend::new();
+        restored.load_aof(&path)?;
+        assert_eq!(restored.get(b"short"), None);
+        assert_eq!(restored.key_type(b"list"), None);
+        assert!((99_000..=100_000).contains(&restored.pttl(b"long")));
+        assert_eq!(
+            run_args(&restored, &["xrange", "stream", "-", "+"])?,
+            RespArray::new(vec![RespArray::new(vec![
+                id,
+                RespArray::new(vec![
+                    BulkString::from("field").into(),
+                    BulkString::from("1").into()
+                ])
+                .into()
+            ])
+            .into()])
+            .into()
+        );
+        let members = |backend: &Backend| -> Result<Vec<bytes::Bytes>> {
+            let mut members = backend.smembers(b"set")?;
+            members.sort();
+            Ok(members)
+        };
+        assert_eq!(members(&restored)?, members(&backend)?);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_torn_tail_is_truncated() -> Result<()> {
+        let path = aof_file("torn");
+        let set = RespArray::new(vec![
+            BulkString::from("set").into(),
+            BulkString::from("key").into(),
+            BulkString::from("value").into(),
+        ])
+        .encode();
+        let mut bytes = set.clone();
+        bytes.extend_from_slice(&set[..set.len() - 4]);
+        std::fs::write(&path, &bytes)?;
+
+        let restored = Backend::new();
+        assert_eq!(restored.load_aof(&path)?, 1);
+        assert_eq!(restored.get(b"key"), Some(BulkString::from("value").into()));
+        assert_eq!(std::fs::read(&path)?, set);
+
+        // what is not a command at all is an error rather than a torn write
+        std::fs::write(&path, b"*1\r\n$4\r\nnope\r\n")?;
+        assert!(restored.load_aof(&path).is_err());
+        std::fs::write(&path, b"garbage\r\n")?;
+        assert!(restored.load_aof(&path).is_err());
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rewrite_compacts_the_file() -> Result<()> {
+        let path = aof_file("rewrite");
+        let _ = std::fs::remove_file(&path);
+        let backend = Backend::new();
+        backend.start_aof(&path, AppendFsync::No)?;
+        for i in 0..1000 {
+            run_args(&backend, &["set", "key", &format!("value-{}", i)])?;
+        }
+        run_args(&backend, &["rpush", "list", "a", "b", "c"])?;
+        run_args(&backend, &["sadd", "set", "x", "y"])?;
+        run_args(&backend, &["zadd", "zset", "1.5", "one", "-inf", "low"])?;
+        run_args(&backend, &["pexpire", "list", "100000"])?;
+        run_args(&backend.select(3)?, &["hset", "hash", "field", "1"])?;
+        run_args(
+            &backend.select(3)?,
+            &["xadd", "stream", "1-1", "field", "1"],
+        )?;
+        backend.sync_aof();
+        let before = std::fs::metadata(&path)?.len();
+
+        backend.bgrewriteaof()?;
+        assert!(matches!(
+            backend.bgrewriteaof(),
+            Err(CommandError::AofRewriteInProgress)
+        ));
+        while backend.aof_rewrite_in_progress() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // what runs after the rewrite is appended to the new file
+        run_args(&backend, &["incr", "counter"])?;
+        backend.stop_aof();
+        let after = std::fs::metadata(&path)?.len();
+        assert!(
+            after * 20 < before,
+            "{} bytes rewritten from {}",
+            after,
+            before
+        );
+
+        let restored = Backend::new();
+        restored.load_aof(&path)?;
+        assert_eq!(restored.dbsize(), 5);
+        assert_eq!(
+            restored.get(b"key"),
+            Some(BulkString::from("value-999").into())
+        );
+        assert_eq!(restored.get(b"counter"), Some(BulkString::from("1").into()));
+        assert_eq!(
+            restored.lrange(b"list", 0, -1)?,
+            backend.lrange(b"list", 0, -1)?
+        );
+        assert!(restored.pttl(b"list") > 90000);
+        let mut members = restored.smembers(b"set")?;
+        members.sort();
+        assert_eq!(members, ["x", "y"]);
+        let zset = |backend: &Backend| {
+            run_args(backend, &["zrange", "zset", "0", "-1", "withscores"])
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(zset(&restored), zset(&backend));
+        let restored = restored.select(3)?;
+        assert_eq!(
+            restored.hget(b"hash", b"field"),
+            Some(BulkString::from("1").into())
+        );
+        assert_eq!(
+            run_args(&restored, &["xrange", "stream", "-", "+"])?,
+            run_args(&backend.select(3)?, &["xrange", "stream", "-", "+"])?
+        );
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rewrite_keeps_pending_entries() -> Result<()> {
+        let path = aof_file("rewrite-pending");
+        let _ = std::fs::remove_file(&path);
+        let backend = Backend::new();
+        backend.start_aof(&path, AppendFsync::No)?;
+        run_args(&backend, &["xadd", "stream", "1-1", "field", "1"])?;
+        run_args(&backend, &["xadd", "stream", "2-1", "field", "2"])?;
+        run_args(&backend, &["xgroup", "create", "stream", "group", "0"])?;
+        run_args(
+            &backend,
+            &[
+                "xreadgroup",
+                "group",
+                "group",
+                "alice",
+                "streams",
+                "stream",
+                ">",
+            ],
+        )?;
+        run_args(&backend, &["xack", "stream", "group", "1-1"])?;
+
+        backend.bgrewriteaof()?;
+        while backend.aof_rewrite_in_progress() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        backend.stop_aof();
+
+        // the entry delivered to alice is still hers to acknowledge
+        let restored = Backend::new();
+        restored.load_aof(&path)?;
+        let pending = |backend: &Backend| {
+            run_args(backend, &["xpending", "stream", "group"])
+                .map(|reply| reply.encode())
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(pending(&restored), pending(&backend));
+        assert_eq!(
+            run_args(&restored, &["xack", "stream", "group", "2-1"])?,
+            RespFrame::Integer(1)
+        );
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
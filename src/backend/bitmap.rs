@@ -0,0 +1,471 @@
+use super::{edit_bytes, list::normalize_range, Backend, KeyType, MAX_STRING_LEN};
+use crate::{cmd::CommandError, BulkString};
+use bytes::Bytes;
+
+/// One past the highest bit offset of a string, the bits of the largest string value.
+const MAX_BIT_OFFSET: usize = MAX_STRING_LEN * 8;
+
+/// Whether the range of `BITCOUNT` is in bytes or in bits.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum BitUnit {
+    #[default]
+    Byte,
+    Bit,
+}
+
+/// How `BITOP` combines its sources.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+/// An integer field of `BITFIELD`: signed from 1 to 64 bits or unsigned from 1 to 63 bits, so
+/// that every value fits in an `i64`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitFieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+/// What `BITFIELD` does with a value that doesn't fit in its field.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    /// Keep the low bits, wrapping around like two's complement arithmetic.
+    #[default]
+    Wrap,
+    /// Clamp to the lowest or highest value of the field.
+    Sat,
+    /// Leave the field unchanged and reply with nil.
+    Fail,
+}
+
+/// One subcommand of `BITFIELD`, at a bit offset from the start of the string.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitFieldOp {
+    Get(BitFieldType, usize),
+    Set(BitFieldType, usize, i64, Overflow),
+    IncrBy(BitFieldType, usize, i64, Overflow),
+}
+
+impl Backend {
+    /// Set the bit at `offset` of the string at `key` to `bit`, growing the string with zero bytes
+    /// as needed. Returns the previous bit. Bit 0 is the most significant bit of the first byte.
+    pub fn setbit(&self, key: &[u8], offset: usize, bit: bool) -> Result<bool, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        if offset >= MAX_BIT_OFFSET {
+            return Err(CommandError::BitOffsetOutOfRange);
+        }
+
+        let mut value = self.strings().or_default(Bytes::copy_from_slice(key))?;
+        let (byte, mask) = (offset / 8, 0x80 >> (offset % 8));
+        Ok(edit_bytes(&mut value, |s| {
+            if s.len() <= byte {
+                s.resize(byte + 1, 0);
+            }
+            let old = s[byte] & mask != 0;
+            if bit {
+                s[byte] |= mask;
+            } else {
+                s[byte] &= !mask;
+            }
+            old
+        }))
+    }
+
+    /// The bit at `offset` of the string at `key`, unset past its end.
+    pub fn getbit(&self, key: &[u8], offset: usize) -> Result<bool, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        Ok(self.strings().get(key).is_some_and(|s| {
+            s.get(offset / 8)
+                .is_some_and(|byte| byte & (0x80 >> (offset % 8)) != 0)
+        }))
+    }
+
+    /// The number of set bits of the string at `key`, only between `start` and `end` inclusive if
+    /// a range is given. Like `GETRANGE`, negative offsets count from the end.
+    pub fn bitcount(
+        &self,
+        key: &[u8],
+        range: Option<(i64, i64, BitUnit)>,
+    ) -> Result<i64, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        let Some(s) = self.strings().get(key) else {
+            return Ok(0);
+        };
+
+        let count = match range {
+            None => count_ones(&s),
+            Some((start, end, BitUnit::Byte)) => {
+                normalize_range(s.len(), start, end).map_or(0, |range| count_ones(&s[range]))
+            }
+            Some((start, end, BitUnit::Bit)) => match normalize_range(s.len() * 8, start, end) {
+                Some(range) => count_bits(&s, range.start, range.end - 1),
+                None => 0,
+            },
+        };
+        Ok(count as i64)
+    }
+}
+
+impl Backend {
+    /// Store at `dest` the bytewise `op` of the strings at `keys`, the shorter ones padded with
+    /// zero bytes to the longest. Missing keys are empty strings. Returns the length of the
+    /// result, an empty result deletes `dest`. `Not` takes a single key.
+    pub fn bitop(
+        &self,
+        op: BitOperation,
+        dest: &[u8],
+        keys: &[Bytes],
+    ) -> Result<i64, CommandError> {
+        let sources = keys
+            .iter()
+            .map(|key| self.string_bytes(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let len = sources.iter().map(Vec::len).max().unwrap_or(0);
+        let mut result = vec![0; len];
+        for (i, byte) in result.iter_mut().enumerate() {
+            let mut bytes = sources.iter().map(|s| s.get(i).copied().unwrap_or(0));
+            let first = bytes.next().unwrap_or(0);
+            *byte = match op {
+                BitOperation::And => bytes.fold(first, |a, b| a & b),
+                BitOperation::Or => bytes.fold(first, |a, b| a | b),
+                BitOperation::Xor => bytes.fold(first, |a, b| a ^ b),
+                BitOperation::Not => !first,
+            };
+        }
+
+        if result.is_empty() {
+            self.remove_key(dest);
+        } else {
+            self.set(Bytes::copy_from_slice(dest), BulkString::new(result).into());
+        }
+        Ok(len as i64)
+    }
+
+    /// The position of the first bit set to `bit` in the string at `key`, from `start` to `end`
+    /// inclusive, in bytes or in bits as for `bitcount`, or -1 if there is none. Without an
+    /// `end` the string is seen as padded with zero bits, so looking for a 0 in a string of ones
+    /// finds the bit right after its end.
+    pub fn bitpos(
+        &self,
+        key: &[u8],
+        bit: bool,
+        start: i64,
+        end: Option<i64>,
+        unit: BitUnit,
+    ) -> Result<i64, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        let Some(s) = self.strings().get(key) else {
+            return Ok(if bit { -1 } else { 0 });
+        };
+
+        let range = match unit {
+            BitUnit::Byte => normalize_range(s.len(), start, end.unwrap_or(-1))
+                .map(|range| range.start * 8..range.end * 8),
+            BitUnit::Bit => normalize_range(s.len() * 8, start, end.unwrap_or(-1)),
+        };
+        let Some(range) = range else {
+            return Ok(-1);
+        };
+        Ok(match find_bit(&s, range.start, range.end - 1, bit) {
+            Some(position) => position as i64,
+            None if !bit && end.is_none() => range.end as i64,
+            None => -1,
+        })
+    }
+
+    /// Run the `ops` of a `BITFIELD` in order on the string at `key`: the value read by a `Get`,
+    /// the previous value replaced by a `Set` and the new value of an `IncrBy`, `None` for a write
+    /// that failed on overflow. Writes create the key and grow it to the highest field written,
+    /// reads see zero bits past the end.
+    pub fn bitfield(
+        &self,
+        key: &[u8],
+        ops: &[BitFieldOp],
+    ) -> Result<Vec<Option<i64>>, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        if ops.iter().any(|op| op.end() > MAX_BIT_OFFSET) {
+            return Err(CommandError::BitOffsetOutOfRange);
+        }
+
+        let written = ops.iter().filter(|op| !matches!(op, BitFieldOp::Get(..)));
+        let Some(write_end) = written.map(BitFieldOp::end).max() else {
+            // reading alone never creates the key
+            let value = self.strings().get(key);
+            let bytes = value.as_deref().map_or(&[][..], |s| &s[..]);
+            return Ok(ops
+                .iter()
+                .map(|op| Some(read_field(bytes, op.offset(), op.field_type())))
+                .collect());
+        };
+
+        let mut value = self.strings().or_default(Bytes::copy_from_slice(key))?;
+        Ok(edit_bytes(&mut value, |s| {
+            if s.len() * 8 < write_end {
+                s.resize(write_end.div_ceil(8), 0);
+            }
+            ops.iter()
+                .map(|op| match *op {
+                    BitFieldOp::Get(ty, offset) => Some(read_field(s, offset, ty)),
+                    BitFieldOp::Set(ty, offset, value, overflow) => {
+                        let old = read_field(s, offset, ty);
+                        ty.fit(value as i128, overflow).map(|value| {
+                            write_field(s, offset, ty, value);
+                            old
+                        })
+                    }
+                    BitFieldOp::IncrBy(ty, offset, increment, overflow) => {
+                        let old = read_field(s, offset, ty);
+                        ty.fit(old as i128 + increment as i128, overflow)
+                            .inspect(|&value| write_field(s, offset, ty, value))
+                    }
+                })
+                .collect()
+        }))
+    }
+
+    // a copy of the string at `key`, empty if it is missing
+    fn string_bytes(&self, key: &[u8]) -> Result<Vec<u8>, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        Ok(self
+            .strings()
+            .get(key)
+            .map_or_else(Vec::new, |s| s.to_vec()))
+    }
+}
+
+impl BitFieldType {
+    // `value` as a value of the field, or `None` if it doesn't fit and `overflow` fails
+    fn fit(self, value: i128, overflow: Overflow) -> Option<i64> {
+        let (min, max) = match self.signed {
+            true => (-(1 << (self.bits - 1)), (1 << (self.bits - 1)) - 1),
+            false => (0, (1 << self.bits) - 1),
+        };
+        if (min..=max).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            Overflow::Wrap => Some(((value - min).rem_euclid(1 << self.bits) + min) as i64),
+            Overflow::Sat => Some(value.clamp(min, max) as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+impl BitFieldOp {
+    fn field_type(&self) -> BitFieldType {
+        match *self {
+            BitFieldOp::Get(ty, _) | BitFieldOp::Set(ty, ..) | BitFieldOp::IncrBy(ty, ..) => ty,
+        }
+    }
+
+    fn offset(&self) -> usize {
+        match *self {
+            BitFieldOp::Get(_, offset)
+            | BitFieldOp::Set(_, offset, ..)
+            | BitFieldOp::IncrBy(_, offset, ..) => offset,
+        }
+    }
+
+    // one past the last bit of the field
+    fn end(&self) -> usize {
+        self.offset()
+            .saturating_add(self.field_type().bits as usize)
+    }
+}
+
+// the field of type `ty` at bit `offset`, most significant bit first. Bits past the end of the
+// string are 0.
+fn read_field(bytes: &[u8], offset: usize, ty: BitFieldType) -> i64 {
+    let raw = (offset..offset + ty.bits as usize).fold(0u64, |raw, bit| {
+        let byte = bytes.get(bit / 8).copied().unwrap_or(0);
+        raw << 1 | ((byte >> (7 - bit % 8)) & 1) as u64
+    });
+    // sign extend a negative value
+    if ty.signed && ty.bits < 64 && raw >> (ty.bits - 1) == 1 {
+        (raw | u64::MAX << ty.bits) as i64
+    } else {
+        raw as i64
+    }
+}
+
+// write the low bits of `value` to the field of type `ty` at bit `offset`, which must be within
+// `bytes`
+fn write_field(bytes: &mut [u8], offset: usize, ty: BitFieldType, value: i64) {
+    for i in 0..ty.bits as usize {
+        let bit = offset + i;
+        let mask = 0x80 >> (bit % 8);
+        if (value as u64 >> (ty.bits as usize - 1 - i)) & 1 == 1 {
+            bytes[bit / 8] |= mask;
+        } else {
+            bytes[bit / 8] &= !mask;
+        }
+    }
+}
+
+// the first bit equal to `bit` from bit `first` to bit `last` inclusive. Whole bytes are skipped
+// at once, only the first and last bytes are masked to the range.
+fn find_bit(bytes: &[u8], first: usize, last: usize, bit: bool) -> Option<usize> {
+    let (first_byte, last_byte) = (first / 8, last / 8);
+    (first_byte..=last_byte).find_map(|i| {
+        // looking for a 0 is looking for a 1 in the inverted byte
+        let mut byte = if bit { bytes[i] } else { !bytes[i] };
+        if i == first_byte {
+            byte &= 0xff >> (first % 8);
+        }
+        if i == last_byte {
+            byte &= !0xffu8.checked_shr(last as u32 % 8 + 1).unwrap_or(0);
+        }
+        (byte != 0).then(|| i * 8 + byte.leading_zeros() as usize)
+    })
+}
+
+fn count_ones(bytes: &[u8]) -> u32 {
+    bytes.iter().map(|byte| byte.count_ones()).sum()
+}
+
+// the set bits from bit `first` to bit `last` inclusive: whole bytes, less the bits of the first
+// and last bytes outside of the range
+fn count_bits(bytes: &[u8], first: usize, last: usize) -> u32 {
+    let (first_byte, last_byte) = (first / 8, last / 8);
+    let before = bytes[first_byte] & !(0xff >> (first % 8));
+    let after = bytes[last_byte] & 0xffu8.checked_shr(last as u32 % 8 + 1).unwrap_or(0);
+    count_ones(&bytes[first_byte..=last_byte]) - before.count_ones() - after.count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_bits() {
+        let bytes = [0b1010_1010, 0xff, 0b0000_0001];
+        let cases = [
+            (0, 23, 13),
+            (0, 0, 1),
+            (1, 1, 0),
+            (1, 7, 3),
+            (7, 8, 1),
+            (6, 9, 3),
+            (8, 15, 8),
+            (15, 23, 2),
+            (23, 23, 1),
+            (16, 22, 0),
+        ];
+        for (first, last, expected) in cases {
+            assert_eq!(
+                count_bits(&bytes, first, last),
+                expected,
+                "{}..={}",
+                first,
+                last
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_write_field() {
+        let u = |bits| BitFieldType {
+            signed: false,
+            bits,
+        };
+        let i = |bits| BitFieldType { signed: true, bits };
+
+        // a field spanning three bytes
+        let mut bytes = [0u8; 3];
+        write_field(&mut bytes, 4, u(16), 0xabcd);
+        assert_eq!(bytes, [0x0a, 0xbc, 0xd0]);
+        assert_eq!(read_field(&bytes, 4, u(16)), 0xabcd);
+        assert_eq!(read_field(&bytes, 8, u(8)), 0xbc);
+        assert_eq!(read_field(&bytes, 4, i(4)), -6);
+        assert_eq!(read_field(&bytes, 20, u(8)), 0);
+        // only the low bits are written
+        write_field(&mut bytes, 0, i(4), -1);
+        assert_eq!(bytes, [0xfa, 0xbc, 0xd0]);
+
+        let mut bytes = [0u8; 8];
+        write_field(&mut bytes, 0, i(64), i64::MIN);
+        assert_eq!(read_field(&bytes, 0, i(64)), i64::MIN);
+        write_field(&mut bytes, 1, u(63), i64::MAX);
+        assert_eq!(read_field(&bytes, 1, u(63)), i64::MAX);
+        assert_eq!(read_field(&bytes, 0, i(64)), i64::MIN + i64::MAX);
+    }
+
+    #[test]
+    fn test_fit() {
+        let u8 = BitFieldType {
+            signed: false,
+            bits: 8,
+        };
+        let i8 = BitFieldType {
+            signed: true,
+            bits: 8,
+        };
+        let i64 = BitFieldType {
+            signed: true,
+            bits: 64,
+        };
+        let cases = [
+            (u8, 255, Overflow::Wrap, Some(255)),
+            (u8, 256, Overflow::Wrap, Some(0)),
+            (u8, 265, Overflow::Wrap, Some(9)),
+            (u8, -1, Overflow::Wrap, Some(255)),
+            (u8, 300, Overflow::Sat, Some(255)),
+            (u8, -5, Overflow::Sat, Some(0)),
+            (u8, 256, Overflow::Fail, None),
+            (i8, 128, Overflow::Wrap, Some(-128)),
+            (i8, -129, Overflow::Wrap, Some(127)),
+            (i8, 200, Overflow::Sat, Some(127)),
+            (i8, -300, Overflow::Sat, Some(-128)),
+            (i8, -128, Overflow::Fail, Some(-128)),
+            (i8, -129, Overflow::Fail, None),
+            (i64, i64::MAX as i128 + 1, Overflow::Wrap, Some(i64::MIN)),
+            (i64, i64::MIN as i128 - 1, Overflow::Sat, Some(i64::MIN)),
+        ];
+        for (ty, value, overflow, expected) in cases {
+            assert_eq!(
+                ty.fit(value, overflow),
+                expected,
+                "{:?} {} {:?}",
+                ty,
+                value,
+                overflow
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_bit() {
+        let bytes = [0b0000_0000, 0b0001_0000, 0xff, 0b1111_1110];
+        let cases = [
+            (0, 31, true, Some(11)),
+            (0, 10, true, None),
+            (11, 11, true, Some(11)),
+            (12, 20, true, Some(16)),
+            (0, 31, false, Some(0)),
+            (16, 30, false, None),
+            (16, 31, false, Some(31)),
+            (9, 15, false, Some(9)),
+            (11, 11, false, None),
+        ];
+        for (first, last, bit, expected) in cases {
+            assert_eq!(
+                find_bit(&bytes, first, last, bit),
+                expected,
+                "{}..={} {}",
+                first,
+                last,
+                bit
+            );
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use super::{Backend, KeyType, ListEnd, StreamEntry, StreamId, Value};
+use crate::{cmd::CommandError, BulkString, RespArray, RespEncode, RespFrame};
+use bytes::Bytes;
+use dashmap::mapref::entry::Entry as MapEntry;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{oneshot, Notify},
+    time::Instant,
+};
+
+// where a pushed element is handed to a blocked client. A client blocked on several keys is queued
+// on each of them with the same handoff, whoever takes the sender first serves it.
+type Handoff = Arc<Mutex<Option<oneshot::Sender<(Bytes, RespFrame)>>>>;
+
+/// A client waiting in `BLPOP` or `BRPOP` for one of its keys to be pushed to.
+#[derive(Debug)]
+pub(crate) struct Waiter {
+    end: ListEnd,
+    handoff: Handoff,
+}
+
+// removes the waiters of a client from every key it was queued on, however the wait ended
+struct Registration<'a> {
+    backend: &'a Backend,
+    keys: Vec<Bytes>,
+    handoff: Handoff,
+}
+
+// the same for a client blocked in XREAD
+struct ReadRegistration<'a> {
+    backend: &'a Backend,
+    keys: Vec<Bytes>,
+    notify: Arc<Notify>,
+}
+
+impl Backend {
+    /// Pop an element from the `end` of the first non-empty list of `keys`, waiting up to
+    /// `timeout` for one to be pushed if they are all empty, or forever with `None`. Returns the
+    /// key and the element, `None` once the timeout expired.
+    ///
+    /// Clients waiting on the same key are served in the order they started waiting.
+    pub async fn blocking_pop(
+        &self,
+        keys: &[Bytes],
+        end: ListEnd,
+        timeout: Option<Duration>,
+    ) -> Result<Option<(Bytes, RespFrame)>, CommandError> {
+        let (tx, mut rx) = oneshot::channel();
+        let handoff: Handoff = Arc::new(Mutex::new(Some(tx)));
+        self.metrics().client_blocked();
+        let mut registration = Registration {
+            backend: self,
+            keys: Vec::with_capacity(keys.len()),
+            handoff: handoff.clone(),
+        };
+
+        // a pop right away is propagated like a push serving the client is, in the order of the
+        // writes around it
+        let sequence = self.sequence_lock().lock().await;
+        for key in keys {
+            self.check_type(key, KeyType::List)?;
+            // the list entry stays locked while queueing, a push to this key can only come after
+            // and will find the waiter
+            match self.entries.entry(key.clone()) {
+                MapEntry::Occupied(mut entry) if matches!(&entry.get().value, Value::List(list) if !list.is_empty()) =>
+                {
+                    // a push to a key queued on earlier may have served the client already
+                    if handoff.lock().unwrap().take().is_none() {
+                        break;
+                    }
+                    let Value::List(list) = &mut entry.get_mut().value else {
+                        unreachable!("checked to be a list");
+                    };
+                    let element = pop_end(list, end).expect("the list is not empty");
+                    drop(entry);
+                    self.remove_if_empty(key);
+                    self.propagate(&pop_command(key, end));
+                    return Ok(Some((key.clone(), element)));
+                }
+                _ => {
+                    self.blocked
+                        .entry(key.clone())
+                        .or_default()
+                        .push_back(Waiter {
+                            end,
+                            handoff: handoff.clone(),
+                        });
+                    registration.keys.push(key.clone());
+                }
+            }
+        }
+        drop(sequence);
+
+        let served = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, &mut rx).await.ok(),
+            None => Some((&mut rx).await),
+        };
+        match served {
+            Some(served) => Ok(served.ok()),
+            // the element is sent while the sender is taken, if it is gone it has arrived
+            None if handoff.lock().unwrap().take().is_none() => Ok(rx.try_recv().ok()),
+            None => Ok(None),
+        }
+    }
+
+    /// Read the entries after the given ids of `streams` like `xread`, waiting up to `timeout` for
+    /// one to be added if there are none, or forever with `None`. An id of `None` stands for the
+    /// last entry of the stream when the read starts. Returns `None` once the timeout expired.
+    pub async fn blocking_read(
+        &self,
+        streams: &[(Bytes, Option<StreamId>)],
+        count: Option<usize>,
+        timeout: Option<Duration>,
+    ) -> Result<Option<Vec<(Bytes, Vec<StreamEntry>)>>, CommandError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let notify = Arc::new(Notify::new());
+        self.metrics().client_blocked();
+        let mut registration = ReadRegistration {
+            backend: self,
+            keys: Vec::with_capacity(streams.len()),
+            notify: notify.clone(),
+        };
+        // queued before looking at the streams, an entry added from now on wakes the client
+        for (key, _) in streams {
+            self.readers
+                .entry(key.clone())
+                .or_default()
+                .push(notify.clone());
+            registration.keys.push(key.clone());
+        }
+        let streams = streams
+            .iter()
+            .map(|(key, id)| {
+                let id = match id {
+                    Some(id) => *id,
+                    None => self.last_stream_id(key)?,
+                };
+                Ok((key.clone(), id))
+            })
+            .collect::<Result<Vec<_>, CommandError>>()?;
+
+        loop {
+            let read = self.xread(&streams, count)?;
+            if !read.is_empty() {
+                return Ok(Some(read));
+            }
+            match deadline {
+                Some(deadline) => {
+                    if tokio::time::timeout_at(deadline, notify.notified())
+                        .await
+                        .is_err()
+                    {
+                        return Ok(None);
+                    }
+                }
+                None => notify.notified().await,
+            }
+        }
+    }
+
+    // wake the clients blocked in XREAD on `key`, after an entry was added to it
+    pub(crate) fn wake_readers(&self, key: &[u8]) {
+        if let Some(readers) = self.readers.get(key) {
+            // a client not waiting yet keeps the wakeup for when it does
+            readers.iter().for_each(|notify| notify.notify_one());
+        }
+    }
+
+    // hand elements of the list just pushed to at `key` to the clients blocked on it, longest
+    // waiting first. Called with the entry of the list locked.
+    pub(crate) fn serve_blocked(&self, key: &[u8], list: &mut VecDeque<RespFrame>) {
+        let Some(mut waiters) = self.blocked.get_mut(key) else {
+            return;
+        };
+        while !list.is_empty() {
+            let Some(waiter) = waiters.pop_front() else {
+                break;
+            };
+            let mut handoff = waiter.handoff.lock().unwrap();
+            // already served through another key, or timed out
+            let Some(tx) = handoff.take() else {
+                continue;
+            };
+            let element = pop_end(list, waiter.end).expect("the list is not empty");
+            match tx.send((Bytes::copy_from_slice(key), element)) {
+                Ok(()) => self
+                    .served
+                    .lock()
+                    .unwrap()
+                    .push((Bytes::copy_from_slice(key), waiter.end)),
+                // the client went away without deregistering, keep the element
+                Err((_, element)) => match waiter.end {
+                    ListEnd::Left => list.push_front(element),
+                    ListEnd::Right => list.push_back(element),
+                },
+            }
+        }
+        let empty = waiters.is_empty();
+        drop(waiters);
+        if empty {
+            self.blocked.remove_if(key, |_, waiters| waiters.is_empty());
+        }
+    }
+
+    /// The pops the pushes since the last time handed to blocked clients, as the commands doing
+    /// the same, to be propagated right after the push.
+    pub(crate) fn take_served(&self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut *self.served.lock().unwrap())
+            .into_iter()
+            .map(|(key, end)| pop_command(&key, end))
+            .collect()
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.backend.metrics().client_unblocked();
+        for key in &self.keys {
+            if let Some(mut waiters) = self.backend.blocked.get_mut(key) {
+                waiters.retain(|waiter| !Arc::ptr_eq(&waiter.handoff, &self.handoff));
+            }
+            self.backend
+                .blocked
+                .remove_if(key, |_, waiters| waiters.is_empty());
+        }
+    }
+}
+
+impl Drop for ReadRegistration<'_> {
+    fn drop(&mut self) {
+        self.backend.metrics().client_unblocked();
+        for key in &self.keys {
+            if let Some(mut readers) = self.backend.readers.get_mut(key) {
+                readers.retain(|notify| !Arc::ptr_eq(notify, &self.notify));
+            }
+            self.backend
+                .readers
+                .remove_if(key, |_, readers| readers.is_empty());
+        }
+    }
+}
+
+// the `LPOP` or `RPOP` doing what a blocking pop of `key` did, for the replicas and the append
+// only file: as a pop right away, or a push serving a blocked client
+fn pop_command(key: &[u8], end: ListEnd) -> Vec<u8> {
+    let name = match end {
+        ListEnd::Left => "LPOP",
+        ListEnd::Right => "RPOP",
+    };
+    RespArray::new(vec![
+        BulkString::from(name).into(),
+        BulkString::new(key).into(),
+    ])
+    .encode()
+}
+
+fn pop_end(list: &mut VecDeque<RespFrame>, end: ListEnd) -> Option<RespFrame> {
+    match end {
+        ListEnd::Left => list.pop_front(),
+        ListEnd::Right => list.pop_back(),
+    }
+}
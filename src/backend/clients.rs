@@ -0,0 +1,225 @@
+use super::Backend;
+use crate::cmd::CommandError;
+use std::fmt::Write;
+use std::sync::{atomic::Ordering, Arc};
+use std::time::Instant;
+use tokio::sync::Notify;
+
+/// What the server knows of a connected client, as listed by `CLIENT LIST`.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub id: u64,
+    /// The address of the client.
+    pub addr: String,
+    /// The address of the server the client connected to.
+    pub laddr: String,
+    pub name: String,
+    /// The database selected when the client sent its last command.
+    pub db: usize,
+    /// The name of the last command the client sent, `NULL` before the first one.
+    pub last_command: String,
+    /// Whether the client may send commands other than `AUTH`, it must first if there is a
+    /// `requirepass`.
+    pub authenticated: bool,
+    /// The version of RESP the replies are in, 2 until a `HELLO 3`.
+    pub protocol: u8,
+    connected: Instant,
+    last_interaction: Instant,
+    // notified to make the connection close
+    kill: Arc<Notify>,
+}
+
+/// Which clients `CLIENT KILL` closes: those matching every filter that is set.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KillFilter {
+    pub id: Option<u64>,
+    pub addr: Option<String>,
+    pub laddr: Option<String>,
+    /// Leave out the client asking.
+    pub skip_me: bool,
+}
+
+impl Backend {
+    /// Register a connection from `addr` to `laddr`. Returns the handle serving it and what is
+    /// notified when the connection is to be closed.
+    pub fn connect_client(&self, addr: String, laddr: String) -> (Backend, Arc<Notify>) {
+        let id = self.server.next_client_id.fetch_add(1, Ordering::Relaxed);
+        let kill = Arc::new(Notify::new());
+        let now = Instant::now();
+        self.server.clients.insert(
+            id,
+            ClientInfo {
+                id,
+                addr,
+                laddr,
+                name: String::new(),
+                db: self.index,
+                last_command: "NULL".to_string(),
+                // a client connected before a password is set stays authenticated
+                authenticated: self.config().requirepass.is_empty(),
+                protocol: 2,
+                connected: now,
+                last_interaction: now,
+                kill: kill.clone(),
+            },
+        );
+        let backend = Backend {
+            client: Some(id),
+            ..self.clone()
+        };
+        (backend, kill)
+    }
+
+    /// Forget the connection the handle serves, once it is closed.
+    pub fn disconnect_client(&self) {
+        self.drop_subscriber();
+        if let Some(id) = self.client {
+            self.server.clients.remove(&id);
+        }
+    }
+
+    /// The id of the connection the handle serves, if it serves one.
+    pub fn client_id(&self) -> Option<u64> {
+        self.client
+    }
+
+    /// What the server knows of the connection the handle serves.
+    pub fn client_info(&self) -> Option<ClientInfo> {
+        let id = self.client?;
+        self.server.clients.get(&id).map(|info| info.clone())
+    }
+
+    /// Name the connection the handle serves, an empty name removes it.
+    pub fn set_client_name(&self, name: String) {
+        if let Some(mut info) = self.client.and_then(|id| self.server.clients.get_mut(&id)) {
+            info.name = name;
+        }
+    }
+
+    /// The version of RESP to reply to the connection the handle serves in, 2 for a handle
+    /// serving no connection.
+    pub fn client_protocol(&self) -> u8 {
+        match self.client.and_then(|id| self.server.clients.get(&id)) {
+            Some(info) => info.protocol,
+            None => 2,
+        }
+    }
+
+    /// Reply to the connection the handle serves in the version `protocol` of RESP from now on.
+    pub fn set_client_protocol(&self, protocol: u8) {
+        if let Some(mut info) = self.client.and_then(|id| self.server.clients.get_mut(&id)) {
+            info.protocol = protocol;
+        }
+    }
+
+    /// Forget what `RESET` forgets of the connection the handle serves: its name, its
+    /// subscriptions, its protocol, and its authentication if `requirepass` is set.
+    pub fn reset_client(&self) {
+        self.drop_subscriber();
+        let authenticated = self.config().requirepass.is_empty();
+        if let Some(mut info) = self.client.and_then(|id| self.server.clients.get_mut(&id)) {
+            info.name.clear();
+            info.authenticated = authenticated;
+            info.protocol = 2;
+        }
+    }
+
+    /// Whether the handle may run any command. Handles serving no connection always may.
+    pub fn is_authenticated(&self) -> bool {
+        match self.client.and_then(|id| self.server.clients.get(&id)) {
+            Some(info) => info.authenticated,
+            None => true,
+        }
+    }
+
+    /// Authenticate the connection the handle serves with the `requirepass`. The only user is
+    /// `default`. A wrong password leaves the connection as it was.
+    pub fn authenticate(&self, username: Option<&str>, password: &str) -> Result<(), CommandError> {
+        let requirepass = self.config().requirepass.clone();
+        if requirepass.is_empty() && username.is_none() {
+            return Err(CommandError::NoPassword);
+        }
+        let user_ok = username.is_none_or(|user| user == "default");
+        // the user is not secret, the password is compared in constant time
+        let password_ok =
+            requirepass.is_empty() || constant_time_eq(password.as_bytes(), requirepass.as_bytes());
+        if !(user_ok && password_ok) {
+            return Err(CommandError::WrongPass);
+        }
+        if let Some(mut info) = self.client.and_then(|id| self.server.clients.get_mut(&id)) {
+            info.authenticated = true;
+        }
+        Ok(())
+    }
+
+    /// Note that the connection the handle serves sent the command `name`.
+    pub(crate) fn record_command(&self, name: &str) {
+        if let Some(mut info) = self.client.and_then(|id| self.server.clients.get_mut(&id)) {
+            info.db = self.index;
+            info.last_command = name.to_string();
+            info.last_interaction = Instant::now();
+        }
+    }
+
+    /// The reply of `CLIENT LIST`: a line per connected client, by id.
+    pub fn client_list(&self) -> String {
+        let mut clients = self
+            .server
+            .clients
+            .iter()
+            .map(|info| info.clone())
+            .collect::<Vec<_>>();
+        clients.sort_unstable_by_key(|info| info.id);
+
+        let mut list = String::new();
+        for info in clients {
+            // writing to a string can't fail
+            let _ = writeln!(
+                list,
+                "id={} addr={} laddr={} name={} age={} idle={} flags=N db={} cmd={} resp={}",
+                info.id,
+                info.addr,
+                info.laddr,
+                info.name,
+                info.connected.elapsed().as_secs(),
+                info.last_interaction.elapsed().as_secs(),
+                info.db,
+                info.last_command,
+                info.protocol
+            );
+        }
+        list
+    }
+
+    /// Close the connections matching `filter`, returning how many there were. A connection
+    /// closes once it is done with the command at hand, so killing its own one still replies.
+    pub fn kill_clients(&self, filter: &KillFilter) -> usize {
+        let mut killed = 0;
+        for info in self.server.clients.iter() {
+            let matches = filter.id.is_none_or(|id| id == info.id)
+                && filter.addr.as_ref().is_none_or(|addr| *addr == info.addr)
+                && filter
+                    .laddr
+                    .as_ref()
+                    .is_none_or(|laddr| *laddr == info.laddr)
+                && !(filter.skip_me && self.client == Some(info.id));
+            if matches {
+                // `notify_one` keeps the wakeup for a connection not waiting on it yet
+                info.kill.notify_one();
+                killed += 1;
+            }
+        }
+        killed
+    }
+}
+
+// compare without returning early, so that how long it takes says nothing of where `a` and `b`
+// differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = a.len() ^ b.len();
+    for i in 0..a.len().max(b.len()) {
+        let (x, y) = (a.get(i).unwrap_or(&0), b.get(i).unwrap_or(&0));
+        diff |= (x ^ y) as usize;
+    }
+    diff == 0
+}
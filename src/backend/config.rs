@@ -0,0 +1,669 @@
+use super::{glob::glob_match, Backend, KeyspaceEvents};
+use crate::{cmd::CommandError, ProtoLimits};
+use anyhow::{anyhow, bail, Context};
+use std::path::Path;
+use std::sync::RwLockReadGuard;
+use std::time::Duration;
+use tracing::warn;
+
+/// What to do when a write would take the data over `maxmemory`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MaxMemoryPolicy {
+    /// Refuse the write with an error.
+    #[default]
+    NoEviction,
+    AllKeysLru,
+    AllKeysLfu,
+    AllKeysRandom,
+    VolatileLru,
+    VolatileLfu,
+    VolatileRandom,
+    VolatileTtl,
+}
+
+/// When the append only file is synced to the disk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AppendFsync {
+    /// After every write command.
+    Always,
+    /// Once a second, losing at most a second of writes on a crash.
+    #[default]
+    EverySec,
+    /// Whenever the operating system sees fit.
+    No,
+}
+
+impl AppendFsync {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AppendFsync::Always => "always",
+            AppendFsync::EverySec => "everysec",
+            AppendFsync::No => "no",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        [AppendFsync::Always, AppendFsync::EverySec, AppendFsync::No]
+            .into_iter()
+            .find(|fsync| fsync.as_str().eq_ignore_ascii_case(name))
+    }
+}
+
+/// The settings of the server, as read by `CONFIG GET` and changed by `CONFIG SET`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    pub bind: String,
+    pub port: u16,
+    /// Where the snapshot and the append only file are written.
+    pub dir: String,
+    pub dbfilename: String,
+    pub appendonly: bool,
+    pub appendfilename: String,
+    pub appendfsync: AppendFsync,
+    /// Snapshot after `.1` changes within `.0` seconds, for each pair. Never if empty.
+    pub save: Vec<(u64, u64)>,
+    /// The password clients must `AUTH` with, none if empty.
+    pub requirepass: String,
+    /// The most bytes the data may take, no limit if 0.
+    pub maxmemory: u64,
+    pub maxmemory_policy: MaxMemoryPolicy,
+    /// How many keys of each database the policy picks the key to evict among.
+    pub maxmemory_samples: usize,
+    pub databases: usize,
+    /// Close connections idle for this many seconds, never if 0.
+    pub timeout: u64,
+    /// Log the commands taking longer than this many microseconds, none if negative.
+    pub slowlog_log_slower_than: i64,
+    /// How many entries the slow log keeps.
+    pub slowlog_max_len: usize,
+    /// The keyspace events published over pub/sub, none by default.
+    pub notify_keyspace_events: KeyspaceEvents,
+    /// The most bytes of a bulk string a client may send.
+    pub proto_max_bulk_len: u64,
+    /// The most arguments of a command a client may send.
+    pub proto_max_multibulk_len: u64,
+    /// Abort the scripts running for longer than this many milliseconds, never if 0.
+    pub busy_reply_threshold: u64,
+}
+
+// every parameter, in the order `CONFIG GET` lists them, and whether `CONFIG SET` may change it
+const PARAMETERS: [(&str, bool); 20] = [
+    ("bind", false),
+    ("port", false),
+    ("dir", true),
+    ("dbfilename", true),
+    ("appendonly", true),
+    ("appendfilename", false),
+    ("appendfsync", true),
+    ("save", true),
+    ("requirepass", true),
+    ("maxmemory", true),
+    ("maxmemory-policy", true),
+    ("maxmemory-samples", true),
+    ("databases", false),
+    ("timeout", true),
+    ("slowlog-log-slower-than", true),
+    ("slowlog-max-len", true),
+    ("notify-keyspace-events", true),
+    ("proto-max-bulk-len", true),
+    ("proto-max-multibulk-len", true),
+    ("busy-reply-threshold", true),
+];
+
+const POLICY_ERROR: &str = "argument(s) must be one of the following: noeviction, allkeys-lru, \
+    allkeys-lfu, allkeys-random, volatile-lru, volatile-lfu, volatile-random, volatile-ttl";
+
+impl MaxMemoryPolicy {
+    const ALL: [MaxMemoryPolicy; 8] = [
+        MaxMemoryPolicy::NoEviction,
+        MaxMemoryPolicy::AllKeysLru,
+        MaxMemoryPolicy::AllKeysLfu,
+        MaxMemoryPolicy::AllKeysRandom,
+        MaxMemoryPolicy::VolatileLru,
+        MaxMemoryPolicy::VolatileLfu,
+        MaxMemoryPolicy::VolatileRandom,
+        MaxMemoryPolicy::VolatileTtl,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaxMemoryPolicy::NoEviction => "noeviction",
+            MaxMemoryPolicy::AllKeysLru => "allkeys-lru",
+            MaxMemoryPolicy::AllKeysLfu => "allkeys-lfu",
+            MaxMemoryPolicy::AllKeysRandom => "allkeys-random",
+            MaxMemoryPolicy::VolatileLru => "volatile-lru",
+            MaxMemoryPolicy::VolatileLfu => "volatile-lfu",
+            MaxMemoryPolicy::VolatileRandom => "volatile-random",
+            MaxMemoryPolicy::VolatileTtl => "volatile-ttl",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.as_str().eq_ignore_ascii_case(name))
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0".to_string(),
+            port: 6379,
+            dir: ".".to_string(),
+            dbfilename: "dump.rdb".to_string(),
+            appendonly: false,
+            appendfilename: "appendonly.aof".to_string(),
+            appendfsync: AppendFsync::EverySec,
+            save: vec![(3600, 1), (300, 100), (60, 10000)],
+            requirepass: String::new(),
+            maxmemory: 0,
+            maxmemory_policy: MaxMemoryPolicy::NoEviction,
+            maxmemory_samples: 5,
+            databases: 16,
+            timeout: 0,
+            slowlog_log_slower_than: 10_000,
+            slowlog_max_len: 128,
+            notify_keyspace_events: KeyspaceEvents::default(),
+            proto_max_bulk_len: ProtoLimits::default().max_bulk_len as u64,
+            proto_max_multibulk_len: ProtoLimits::default().max_multibulk_len as u64,
+            busy_reply_threshold: 5000,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// The value of parameter `name` as `CONFIG GET` shows it, `None` for an unknown parameter.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "bind" => self.bind.clone(),
+            "port" => self.port.to_string(),
+            "dir" => self.dir.clone(),
+            "dbfilename" => self.dbfilename.clone(),
+            "appendonly" => yes_no(self.appendonly),
+            "appendfilename" => self.appendfilename.clone(),
+            "save" => self
+                .save
+                .iter()
+                .map(|(seconds, changes)| format!("{} {}", seconds, changes))
+                .collect::<Vec<_>>()
+                .join(" "),
+            "requirepass" => self.requirepass.clone(),
+            "maxmemory" => self.maxmemory.to_string(),
+            "maxmemory-policy" => self.maxmemory_policy.as_str().to_string(),
+            "maxmemory-samples" => self.maxmemory_samples.to_string(),
+            "databases" => self.databases.to_string(),
+            "timeout" => self.timeout.to_string(),
+            "slowlog-log-slower-than" => self.slowlog_log_slower_than.to_string(),
+            "slowlog-max-len" => self.slowlog_max_len.to_string(),
+            "notify-keyspace-events" => self.notify_keyspace_events.to_string(),
+            "proto-max-bulk-len" => self.proto_max_bulk_len.to_string(),
+            "proto-max-multibulk-len" => self.proto_max_multibulk_len.to_string(),
+            "busy-reply-threshold" => self.busy_reply_threshold.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Change parameter `name` to `value`, parsed the way `CONFIG SET` and the config file
+    /// write it. Unlike `CONFIG SET` this may change the parameters only read at startup.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), CommandError> {
+        let invalid = |reason: &'static str| CommandError::InvalidConfig(name.to_string(), reason);
+        match name {
+            "bind" => self.bind = value.to_string(),
+            "port" => {
+                self.port = value
+                    .parse()
+                    .map_err(|_| invalid("argument must be a port"))?
+            }
+            "dir" => {
+                if !std::path::Path::new(value).is_dir() {
+                    return Err(invalid("No such file or directory"));
+                }
+                self.dir = value.to_string();
+            }
+            "dbfilename" | "appendfilename" if value.contains(['/', '\\']) => {
+                return Err(invalid("file name can't be a path, just a filename"));
+            }
+            "dbfilename" => self.dbfilename = value.to_string(),
+            "appendfilename" => self.appendfilename = value.to_string(),
+            "appendfsync" => {
+                self.appendfsync = AppendFsync::parse(value).ok_or(invalid(
+                    "argument(s) must be one of the following: always, everysec, no",
+                ))?
+            }
+            "appendonly" => {
+                self.appendonly =
+                    parse_yes_no(value).ok_or(invalid("argument must be 'yes' or 'no'"))?
+            }
+            "save" => self.save = parse_save(value).ok_or(invalid("Invalid save parameters"))?,
+            "requirepass" => self.requirepass = value.to_string(),
+            "maxmemory" => {
+                self.maxmemory =
+                    parse_memory(value).ok_or(invalid("argument must be a memory value"))?
+            }
+            "maxmemory-policy" => {
+                self.maxmemory_policy =
+                    MaxMemoryPolicy::parse(value).ok_or(invalid(POLICY_ERROR))?
+            }
+            "maxmemory-samples" => {
+                self.maxmemory_samples = value
+                    .parse()
+                    .ok()
+                    .filter(|samples| (1..=64).contains(samples))
+                    .ok_or(invalid("argument must be between 1 and 64 inclusive"))?
+            }
+            "databases" => {
+                self.databases = value
+                    .parse()
+                    .ok()
+                    .filter(|databases| *databases > 0)
+                    .ok_or(invalid(
+                        "argument must be between 1 and 2147483647 inclusive",
+                    ))?
+            }
+            "timeout" => {
+                self.timeout = value
+                    .parse()
+                    .map_err(|_| invalid("argument couldn't be parsed into an integer"))?
+            }
+            "slowlog-log-slower-than" => {
+                self.slowlog_log_slower_than = value
+                    .parse()
+                    .map_err(|_| invalid("argument couldn't be parsed into an integer"))?
+            }
+            "slowlog-max-len" => {
+                self.slowlog_max_len = value
+                    .parse()
+                    .map_err(|_| invalid("argument couldn't be parsed into an integer"))?
+            }
+            "notify-keyspace-events" => {
+                self.notify_keyspace_events = KeyspaceEvents::parse(value).ok_or(invalid(
+                    "Invalid event class character. Use 'Ag$lshzxeKEtm'.",
+                ))?
+            }
+            // like redis, not so small that a big value can't be sent at all
+            "proto-max-bulk-len" => {
+                self.proto_max_bulk_len = parse_memory(value)
+                    .filter(|len| *len >= 1024 * 1024)
+                    .ok_or(invalid("argument must be a memory value of at least 1mb"))?
+            }
+            "proto-max-multibulk-len" => {
+                self.proto_max_multibulk_len = value
+                    .parse()
+                    .ok()
+                    .filter(|len| *len > 0)
+                    .ok_or(invalid("argument must be a positive integer"))?
+            }
+            "busy-reply-threshold" => {
+                self.busy_reply_threshold = value
+                    .parse()
+                    .map_err(|_| invalid("argument couldn't be parsed into an integer"))?
+            }
+            _ => return Err(CommandError::UnknownConfig(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+impl ServerConfig {
+    /// Read a `redis.conf` style file: a directive and its arguments per line, which may be
+    /// quoted, and `#` comments. Unknown directives are skipped with a warning.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("can't read config file {}", path.display()))?;
+        let mut config = Self::default();
+        config
+            .load(&text)
+            .with_context(|| format!("bad config file {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Apply the directives of the text of a config file on top of this config.
+    pub fn load(&mut self, text: &str) -> anyhow::Result<()> {
+        let mut saved = false;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let args = split_args(line)
+                .ok_or_else(|| anyhow!("line {}: unbalanced quotes in '{}'", number + 1, line))?;
+            let name = args[0].to_ascii_lowercase();
+            let mut value = args[1..].join(" ");
+            // the first `save` line replaces the default, the others add to it
+            if name == "save" && std::mem::replace(&mut saved, true) {
+                value = format!("{} {}", self.get("save").unwrap_or_default(), value)
+                    .trim()
+                    .to_string();
+            }
+            self.apply(&name, &value)
+                .map_err(|e| anyhow!("line {}: {}", number + 1, e))?;
+        }
+        Ok(())
+    }
+
+    /// Apply command line flags on top of this config, each a `--` and the name of a directive
+    /// followed by its arguments, as in `--port 6380 --save 900 1`.
+    pub fn apply_args(&mut self, args: &[String]) -> anyhow::Result<()> {
+        let mut args = args.iter().peekable();
+        while let Some(flag) = args.next() {
+            let Some(name) = flag.strip_prefix("--") else {
+                bail!("unexpected argument '{}'", flag);
+            };
+            let mut values = Vec::new();
+            while let Some(value) = args.next_if(|arg| !arg.starts_with("--")) {
+                values.push(value.as_str());
+            }
+            self.apply(&name.to_ascii_lowercase(), &values.join(" "))
+                .map_err(|e| anyhow!("flag {}: {}", flag, e))?;
+        }
+        Ok(())
+    }
+
+    // set a directive read at startup, where an unknown one is only a warning
+    fn apply(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        match self.set(name, value) {
+            Ok(()) => Ok(()),
+            Err(CommandError::UnknownConfig(_)) => {
+                warn!("unknown config directive '{}', ignored", name);
+                Ok(())
+            }
+            Err(CommandError::InvalidConfig(name, reason)) => {
+                bail!("invalid value '{}' for '{}': {}", value, name, reason)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Backend {
+    /// The settings of the server. They may change as soon as the guard is dropped.
+    pub fn config(&self) -> RwLockReadGuard<'_, ServerConfig> {
+        self.server.settings.read().unwrap()
+    }
+
+    /// The largest frames the clients may send, as of `proto-max-bulk-len` and
+    /// `proto-max-multibulk-len`.
+    pub fn proto_limits(&self) -> ProtoLimits {
+        let config = self.config();
+        let len = |len: u64| usize::try_from(len).unwrap_or(usize::MAX);
+        ProtoLimits {
+            max_bulk_len: len(config.proto_max_bulk_len),
+            max_multibulk_len: len(config.proto_max_multibulk_len),
+            ..ProtoLimits::default()
+        }
+    }
+
+    /// How long a client may stay idle before its connection is closed, as of `timeout`.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        match self.config().timeout {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// How long a script may run before it is aborted, as of `busy-reply-threshold`.
+    pub fn script_time_limit(&self) -> Option<Duration> {
+        match self.config().busy_reply_threshold {
+            0 => None,
+            millis => Some(Duration::from_millis(millis)),
+        }
+    }
+
+    /// The parameters matching any of the glob `patterns` and their values, each once.
+    pub fn config_get(&self, patterns: &[String]) -> Vec<(String, String)> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| pattern.to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        let config = self.config();
+        PARAMETERS
+            .iter()
+            .filter(|(name, _)| {
+                patterns
+                    .iter()
+                    .any(|pattern| glob_match(pattern.as_bytes(), name.as_bytes()))
+            })
+            .filter_map(|(name, _)| Some((name.to_string(), config.get(name)?)))
+            .collect()
+    }
+
+    /// Change the parameters of `pairs` all at once, or none of them if any is unknown, can't be
+    /// changed while running or has an invalid value.
+    pub fn config_set(&self, pairs: &[(String, String)]) -> Result<(), CommandError> {
+        let mut settings = self.server.settings.write().unwrap();
+        let mut config = settings.clone();
+        for (name, value) in pairs {
+            let name = name.to_ascii_lowercase();
+            match PARAMETERS.iter().find(|(parameter, _)| *parameter == name) {
+                Some((_, true)) => config.set(&name, value)?,
+                Some((_, false)) => return Err(CommandError::ImmutableConfig(name)),
+                None => return Err(CommandError::UnknownConfig(name)),
+            }
+        }
+        // like redis, appending starts with the command after, and stops once it is on the disk
+        let aof = Path::new(&config.dir).join(&config.appendfilename);
+        match (settings.appendonly, config.appendonly) {
+            (false, true) => self.start_aof(&aof, config.appendfsync).map_err(|_| {
+                CommandError::InvalidConfig(
+                    "appendonly".to_string(),
+                    "can't open the append only file",
+                )
+            })?,
+            (true, false) => self.stop_aof(),
+            _ if settings.appendfsync != config.appendfsync => {
+                self.set_aof_fsync(config.appendfsync)
+            }
+            _ => {}
+        }
+        self.store_keyspace_events(config.notify_keyspace_events);
+        *settings = config;
+        Ok(())
+    }
+
+    /// Whether the data takes more than `maxmemory`, so that writes which grow it are refused.
+    pub fn over_maxmemory(&self) -> bool {
+        let maxmemory = self.config().maxmemory;
+        maxmemory > 0 && self.used_memory() as u64 > maxmemory
+    }
+}
+
+// the arguments of a line of a config file, split like an inline command
+fn split_args(line: &str) -> Option<Vec<String>> {
+    let args = crate::resp::split_args(line.as_bytes())?;
+    Some(
+        args.iter()
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect(),
+    )
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+fn parse_yes_no(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+// pairs of seconds and changes, `""` for no snapshots at all
+fn parse_save(value: &str) -> Option<Vec<(u64, u64)>> {
+    let numbers = value
+        .split_whitespace()
+        .map(|n| n.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if !numbers.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(numbers.chunks(2).map(|pair| (pair[0], pair[1])).collect())
+}
+
+// a number of bytes with an optional unit, `k`/`m`/`g` are powers of 1000 and `kb`/`mb`/`gb`
+// powers of 1024, like redis
+fn parse_memory(value: &str) -> Option<u64> {
+    let value = value.to_ascii_lowercase();
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let unit = match &value[digits..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    value[..digits].parse::<u64>().ok()?.checked_mul(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_set_is_atomic() {
+        let backend = Backend::new();
+        let pairs = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        backend
+            .config_set(&pairs(&[
+                ("MAXMEMORY", "2mb"),
+                ("maxmemory-policy", "allkeys-lru"),
+            ]))
+            .unwrap();
+        assert_eq!(backend.config().maxmemory, 2 * 1024 * 1024);
+        assert_eq!(
+            backend.config().maxmemory_policy,
+            MaxMemoryPolicy::AllKeysLru
+        );
+
+        // the valid first pair is not applied either
+        assert!(backend
+            .config_set(&pairs(&[("maxmemory", "10"), ("timeout", "soon")]))
+            .is_err());
+        assert_eq!(backend.config().maxmemory, 2 * 1024 * 1024);
+        assert!(matches!(
+            backend.config_set(&pairs(&[("databases", "4")])),
+            Err(CommandError::ImmutableConfig(_))
+        ));
+        assert!(matches!(
+            backend.config_set(&pairs(&[("nope", "4")])),
+            Err(CommandError::UnknownConfig(_))
+        ));
+
+        assert_eq!(backend.proto_limits(), ProtoLimits::default());
+        assert!(backend
+            .config_set(&pairs(&[("proto-max-bulk-len", "1kb")]))
+            .is_err());
+        backend
+            .config_set(&pairs(&[
+                ("proto-max-bulk-len", "1mb"),
+                ("proto-max-multibulk-len", "100"),
+            ]))
+            .unwrap();
+        assert_eq!(
+            backend.proto_limits(),
+            ProtoLimits {
+                max_bulk_len: 1024 * 1024,
+                max_multibulk_len: 100,
+                ..ProtoLimits::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(parse_memory("100"), Some(100));
+        assert_eq!(parse_memory("1k"), Some(1000));
+        assert_eq!(parse_memory("1KB"), Some(1024));
+        assert_eq!(parse_memory("3gb"), Some(3 * 1024 * 1024 * 1024));
+        assert_eq!(parse_memory("1tb"), None);
+        assert_eq!(parse_memory("-1"), None);
+        assert_eq!(parse_save(""), Some(vec![]));
+        assert_eq!(parse_save("900 1 300 10"), Some(vec![(900, 1), (300, 10)]));
+        assert_eq!(parse_save("900"), None);
+    }
+
+    #[test]
+    fn test_load_config_file() {
+        let mut config = ServerConfig::default();
+        config
+            .load(
+                "# a comment\n\
+                 \n\
+                 port 6380\n\
+                 requirepass \"with spaces \\\"and quotes\\\"\"\n\
+                 dir '/'\n\
+                 save 900 1\n\
+                 save 300 10\n\
+                 MAXMEMORY 100mb\n\
+                 some-directive we don't know\n\
+                 \tappendonly yes\n",
+            )
+            .unwrap();
+        assert_eq!(config.port, 6380);
+        assert_eq!(config.requirepass, "with spaces \"and quotes\"");
+        assert_eq!(config.dir, "/");
+        assert_eq!(config.save, vec![(900, 1), (300, 10)]);
+        assert_eq!(config.maxmemory, 100 * 1024 * 1024);
+        assert!(config.appendonly);
+
+        let mut config = ServerConfig::default();
+        config.load("save \"\"\n").unwrap();
+        assert_eq!(config.save, vec![]);
+        let error = config.load("port 1\nport many\n").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "line 2: invalid value 'many' for 'port': argument must be a port"
+        );
+        assert!(config.load("requirepass \"unterminated\n").is_err());
+        assert!(config.load("requirepass \"a\"b\n").is_err());
+    }
+
+    #[test]
+    fn test_from_file() {
+        let path = std::env::temp_dir().join(format!("simple-redis-{}.conf", std::process::id()));
+        std::fs::write(&path, "databases 4\nmaxmemory-policy allkeys-lru\n").unwrap();
+        let config = ServerConfig::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.databases, 4);
+        assert_eq!(config.maxmemory_policy, MaxMemoryPolicy::AllKeysLru);
+        assert_eq!(Backend::new_with_server_config(config).databases().len(), 4);
+        assert!(ServerConfig::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_flags_override_the_file() {
+        let mut config = ServerConfig::default();
+        config.load("port 6380\nbind 127.0.0.1\n").unwrap();
+        let args = ["--port", "7000", "--save", "60", "5", "--requirepass", "pw"].map(String::from);
+        config.apply_args(&args).unwrap();
+        assert_eq!(config.port, 7000);
+        assert_eq!(config.bind, "127.0.0.1");
+        assert_eq!(config.save, vec![(60, 5)]);
+        assert_eq!(config.requirepass, "pw");
+        assert!(config.apply_args(&["port".into()]).is_err());
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            split_args("  set  \"a\\tb\\x41\"  'it\\'s' plain ").unwrap(),
+            ["set", "a\tbA", "it's", "plain"]
+        );
+        assert_eq!(split_args("").unwrap(), Vec::<String>::new());
+        assert_eq!(split_args("'unterminated"), None);
+    }
+}
@@ -0,0 +1,307 @@
+use super::{random_key_of, Backend, Database, Entry, KeyspaceEvents, MaxMemoryPolicy};
+use crate::{cmd::CommandError, BulkString, RespArray, RespEncode};
+use bytes::Bytes;
+use rand::Rng;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+// the most keys looked at per key sampled, for the policies only evicting keys with a ttl
+const MAX_VISITS_PER_SAMPLE: usize = 20;
+
+impl Backend {
+    /// Note that `keys` were just read, so that the LRU policies evict them last.
+    pub fn record_access(&self, keys: &[Bytes]) {
+        for key in keys {
+            if let Some(mut entry) = self.entries.get_mut(key) {
+                entry.last_access = self.tick();
+            }
+        }
+    }
+
+    // `keys` are written, they are sized again the next time the used memory is asked for, or
+    // by the next active expire cycle at the latest
+    pub(crate) fn mark_written(&self, keys: &[Bytes]) {
+        for key in keys {
+            self.unaccounted.insert(key.clone(), self.tick());
+        }
+    }
+
+    // size the keys written since the last time: a value is only sized once per write, however
+    // many times the used memory is asked for
+    pub(super) fn account_written(&self) {
+        let keys = self
+            .unaccounted
+            .iter()
+            .map(|key| key.key().clone())
+            .collect::<Vec<_>>();
+        for key in keys {
+            let Some((_, written)) = self.unaccounted.remove(&key) else {
+                continue;
+            };
+            let Some(bytes) = self.memory_usage(&key) else {
+                continue;
+            };
+            // grow the counter before shrinking it, so that it never wraps around meanwhile
+            self.used_memory.fetch_add(bytes, Ordering::Relaxed);
+            let previous = match self.entries.get_mut(&key) {
+                Some(mut entry) => {
+                    entry.last_access = entry.last_access.max(written);
+                    std::mem::replace(&mut entry.size, bytes)
+                }
+                // removed in the meantime
+                None => bytes,
+            };
+            self.used_memory.fetch_sub(previous, Ordering::Relaxed);
+        }
+    }
+
+    /// The approximate bytes of every key and value in every database, the sum of their
+    /// `MEMORY USAGE` as of their last write.
+    pub fn used_memory(&self) -> usize {
+        self.databases()
+            .iter()
+            .map(|db| {
+                db.account_written();
+                db.used_memory.load(Ordering::Relaxed)
+            })
+            .sum()
+    }
+
+    /// Make room for a write under `maxmemory`, evicting keys the way `maxmemory-policy` says
+    /// until the data fits. Fails if the policy evicts nothing or there is nothing left to evict.
+    pub fn free_memory(&self) -> Result<(), CommandError> {
+        let policy = self.config().maxmemory_policy;
+        while self.over_maxmemory() {
+            let (db, key) = self
+                .eviction_candidate(policy)
+                .ok_or(CommandError::OutOfMemory)?;
+            db.evict(&key);
+        }
+        Ok(())
+    }
+
+    // the key `policy` evicts first among `maxmemory-samples` keys of each database, like redis
+    // an approximation that never looks at every key
+    fn eviction_candidate(&self, policy: MaxMemoryPolicy) -> Option<(Backend, Bytes)> {
+        if policy == MaxMemoryPolicy::NoEviction {
+            return None;
+        }
+        let samples = self.config().maxmemory_samples.max(1);
+        let now = Instant::now();
+        let mut rng = rand::thread_rng();
+        // the lowest goes first, `None` for a key the policy never evicts
+        let mut rank = |entry: &Entry| -> Option<u128> {
+            match policy {
+                MaxMemoryPolicy::NoEviction => None,
+                MaxMemoryPolicy::AllKeysRandom => Some(rng.gen::<u64>().into()),
+                MaxMemoryPolicy::VolatileRandom => {
+                    entry.expires_at.map(|_| rng.gen::<u64>().into())
+                }
+                // no access frequency is kept, the least recently used key goes instead
+                MaxMemoryPolicy::AllKeysLru | MaxMemoryPolicy::AllKeysLfu => {
+                    Some(entry.last_access.into())
+                }
+                MaxMemoryPolicy::VolatileLru | MaxMemoryPolicy::VolatileLfu => {
+                    entry.expires_at.map(|_| entry.last_access.into())
+                }
+                MaxMemoryPolicy::VolatileTtl => entry
+                    .expires_at
+                    .map(|deadline| deadline.saturating_duration_since(now).as_nanos()),
+            }
+        };
+        self.databases()
+            .into_iter()
+            .flat_map(|db| {
+                db.sample_keys(samples, &mut rank)
+                    .into_iter()
+                    .map(move |(key, rank)| (db.clone(), key, rank))
+                    .collect::<Vec<_>>()
+            })
+            .min_by_key(|(_, _, rank)| *rank)
+            .map(|(db, key, _)| (db, key))
+    }
+
+    // up to `count` keys picked at random with their `rank`, only among those it ranks. Gives up
+    // after looking at `MAX_VISITS_PER_SAMPLE` times as many keys, a database with no more keys
+    // than that has all of them looked at instead.
+    fn sample_keys(
+        &self,
+        count: usize,
+        rank: &mut impl FnMut(&Entry) -> Option<u128>,
+    ) -> Vec<(Bytes, u128)> {
+        let visits = count * MAX_VISITS_PER_SAMPLE;
+        if self.entries.len() <= visits {
+            return self
+                .entries
+                .iter()
+                .filter_map(|entry| Some((entry.key().clone(), rank(entry.value())?)))
+                .collect();
+        }
+        let mut rng = rand::thread_rng();
+        let mut sampled = Vec::with_capacity(count);
+        for _ in 0..visits {
+            if sampled.len() >= count {
+                break;
+            }
+            let Some(key) = random_key_of(&self.entries, &mut rng) else {
+                continue;
+            };
+            if let Some(rank) = self.entries.get(&key).and_then(|entry| rank(&entry)) {
+                sampled.push((key, rank));
+            }
+        }
+        sampled
+    }
+
+    // drop `key` to make room, for the replicas and the append only file a DEL
+    fn evict(&self, key: &[u8]) {
+        self.remove_key(key);
+        self.server.metrics.key_evicted();
+        self.notify_keyspace_event(KeyspaceEvents::EVICTED, "evicted", key);
+        let del = RespArray::new(vec![
+            BulkString::from("DEL").into(),
+            BulkString::from(key).into(),
+        ]);
+        self.propagate(&del.encode());
+    }
+}
+
+impl Database {
+    // every key is gone at once, as by a flush
+    pub(super) fn forget_all_usage(&self) {
+        self.unaccounted.clear();
+        self.used_memory.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::{RespFrame, SimpleError, SimpleString};
+    use anyhow::Result;
+
+    // room for five keys of the same size and a bit, filled up to it
+    fn filled(policy: &str) -> Result<Backend> {
+        let backend = Backend::new();
+        for key in ["k0", "k1", "k2", "k3", "k4"] {
+            run_args(&backend, &["set", key, "value"])?;
+        }
+        let size = backend.memory_usage(b"k0").unwrap();
+        assert_eq!(backend.used_memory(), 5 * size);
+        let maxmemory = (5 * size + size / 2).to_string();
+        run_args(&backend, &["config", "set", "maxmemory", &maxmemory])?;
+        run_args(&backend, &["config", "set", "maxmemory-policy", policy])?;
+        Ok(backend)
+    }
+
+    #[test]
+    fn test_allkeys_lru_evicts_the_oldest_keys() -> Result<()> {
+        let backend = filled("allkeys-lru")?;
+        run_args(&backend, &["get", "k0"])?;
+        // the first write goes over the limit, the next ones make room first
+        for key in ["k5", "k6", "k7"] {
+            assert_eq!(
+                run_args(&backend, &["set", key, "value"])?,
+                SimpleString::new("OK").into()
+            );
+        }
+        let exists = |key: &str| backend.exists(&[Bytes::copy_from_slice(key.as_bytes())]) == 1;
+        assert!(exists("k0"));
+        assert!(!exists("k1") && !exists("k2"));
+        assert!(["k3", "k4", "k5", "k6", "k7"].into_iter().all(exists));
+        assert_eq!(backend.metrics().evicted_keys(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_volatile_lru_only_evicts_keys_with_a_ttl() -> Result<()> {
+        let backend = filled("volatile-lru")?;
+        run_args(&backend, &["expire", "k3", "100"])?;
+        run_args(&backend, &["set", "k5", "value"])?;
+        run_args(&backend, &["set", "k6", "value"])?;
+        assert_eq!(backend.exists(&["k3".into()]), 0);
+        assert_eq!(backend.dbsize(), 6);
+        // with no ttl left, nothing can go
+        assert!(matches!(
+            run_args(&backend, &["set", "k7", "value"]).unwrap_err(),
+            CommandError::OutOfMemory
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_noeviction_refuses_the_write() -> Result<()> {
+        let backend = filled("noeviction")?;
+        run_args(&backend, &["set", "k5", "value"])?;
+        let refused = run_args(&backend, &["set", "k6", "value"]).unwrap_err();
+        assert_eq!(
+            RespFrame::from(refused),
+            SimpleError::new("OOM command not allowed when used memory > 'maxmemory'.").into()
+        );
+        assert_eq!(backend.dbsize(), 6);
+
+        // deleting frees the memory right away
+        run_args(&backend, &["del", "k0", "k1"])?;
+        run_args(&backend, &["set", "k6", "value"])?;
+        run_args(&backend, &["flushall"])?;
+        assert_eq!(backend.used_memory(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_allkeys_random_keeps_the_data_under_the_limit() -> Result<()> {
+        let backend = filled("allkeys-random")?;
+        for key in ["k5", "k6", "k7", "k8", "k9"] {
+            run_args(&backend, &["set", key, "value"])?;
+        }
+        assert_eq!(backend.dbsize(), 6);
+        assert_eq!(backend.metrics().evicted_keys(), 4);
+        assert!(backend.exists(&["k9".into()]) == 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_removed_keys_are_not_left_to_size() -> Result<()> {
+        let backend = Backend::new();
+        for i in 0..100 {
+            let key = format!("key{}", i);
+            run_args(&backend, &["set", &key, "value"])?;
+            run_args(&backend, &["del", &key])?;
+        }
+        // nothing asked for the used memory, yet nothing is left to size
+        assert!(backend.unaccounted.is_empty());
+        run_args(&backend, &["set", "kept", "value"])?;
+        assert_eq!(backend.unaccounted.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_eviction_samples_large_databases() -> Result<()> {
+        let backend = Backend::new();
+        for i in 0..1000 {
+            run_args(&backend, &["set", &format!("key{}", i), "value"])?;
+        }
+        let size = backend.memory_usage(b"key0").unwrap();
+        let maxmemory = (500 * size).to_string();
+        run_args(&backend, &["config", "set", "maxmemory", &maxmemory])?;
+        run_args(
+            &backend,
+            &["config", "set", "maxmemory-policy", "allkeys-lru"],
+        )?;
+        assert!(run_args(&backend, &["config", "set", "maxmemory-samples", "0"]).is_err());
+        run_args(&backend, &["config", "set", "maxmemory-samples", "10"])?;
+        run_args(&backend, &["set", "new", "value"])?;
+        assert!(backend.dbsize() <= 501);
+        assert_eq!(backend.exists(&["new".into()]), 1);
+        // once the samples have no key with a ttl left, the write is refused
+        run_args(
+            &backend,
+            &["config", "set", "maxmemory-policy", "volatile-ttl"],
+        )?;
+        run_args(&backend, &["config", "set", "maxmemory", "1"])?;
+        run_args(&backend, &["expire", "key999", "100"])?;
+        assert!(run_args(&backend, &["set", "more", "value"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+use super::{shard_bucket, Backend, Entry, KeyspaceEvents, Server};
+use std::{
+    sync::{atomic::Ordering, Arc, Weak},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::JoinHandle;
+
+// like redis, keep sweeping while more than a quarter of the sampled keys turned out to be expired,
+// but never for more than this many rounds per cycle
+const MAX_ROUNDS_PER_CYCLE: usize = 16;
+
+// keys without a ttl and empty buckets are skipped over while sampling, up to this many times the
+// sample size per round so that a database with few of them is not walked whole every cycle
+const MAX_VISITS_PER_SAMPLE: usize = 10;
+
+// where the active expire cycle stopped last time, the bucket of a shard to go on from, so that
+// consecutive cycles walk the whole database instead of looking at the same keys over and over
+#[derive(Debug, Default)]
+struct ExpireCursor {
+    shard: usize,
+    bucket: usize,
+}
+
+/// The `NX`, `XX`, `GT` and `LT` flags of the expire commands. A key without a time to live is
+/// treated as one that expires infinitely late.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ExpireCondition {
+    /// Only set the expiry if the key has none.
+    pub nx: bool,
+    /// Only set the expiry if the key already has one.
+    pub xx: bool,
+    /// Only set the expiry if it is later than the current one.
+    pub gt: bool,
+    /// Only set the expiry if it is earlier than the current one.
+    pub lt: bool,
+}
+
+impl Backend {
+    /// Set a time to live in milliseconds on an existing key. A non-positive ttl deletes the key
+    /// right away. Returns false if the key does not exist.
+    pub fn expire(&self, key: &[u8], ttl_ms: i64) -> bool {
+        self.expire_with(key, ttl_ms, ExpireCondition::default())
+    }
+
+    /// Like `expire`, but only if `condition` holds. Returns whether the expiry was changed.
+    pub fn expire_with(&self, key: &[u8], ttl_ms: i64, condition: ExpireCondition) -> bool {
+        if !self.contains_key(key) {
+            return false;
+        }
+        let now = Instant::now();
+        let current = self.deadline(key);
+        // `None` is a deadline too far in the future to represent, which is the same as never
+        let deadline = match ttl_ms {
+            ..=0 => Some(now),
+            ttl_ms => now.checked_add(Duration::from_millis(ttl_ms as u64)),
+        };
+        if (condition.nx && current.is_some())
+            || (condition.xx && current.is_none())
+            || (condition.gt && !later(deadline, current))
+            || (condition.lt && !later(current, deadline))
+        {
+            return false;
+        }
+
+        match deadline {
+            _ if ttl_ms <= 0 => self.remove_key(key),
+            deadline => {
+                if let Some(mut entry) = self.entries.get_mut(key) {
+                    entry.expires_at = deadline;
+                }
+            }
+        }
+        true
+    }
+
+    /// Like `expire_with`, with the expiry given as a unix timestamp in milliseconds. A timestamp
+    /// in the past deletes the key right away.
+    pub fn expire_at(&self, key: &[u8], unix_ms: i64, condition: ExpireCondition) -> bool {
+        // deadlines are kept on the monotonic clock, only the distance to the wall clock matters
+        self.expire_with(key, unix_ms.saturating_sub(unix_millis()), condition)
+    }
+
+    /// Remaining time to live in milliseconds: -2 if the key does not exist, -1 if it has no ttl.
+    pub fn pttl(&self, key: &[u8]) -> i64 {
+        if !self.contains_key(key) {
+            return -2;
+        }
+        match self.deadline(key) {
+            Some(deadline) => deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis() as i64,
+            None => -1,
+        }
+    }
+
+    /// Remove the time to live of a key, returning whether a ttl was removed.
+    pub fn persist(&self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        self.entries
+            .get_mut(key)
+            .is_some_and(|mut entry| entry.expires_at.take().is_some())
+    }
+
+    // when `key` expires, `None` if it does not exist or has no ttl
+    pub(crate) fn deadline(&self, key: &[u8]) -> Option<Instant> {
+        self.entries.get(key).and_then(|entry| entry.expires_at)
+    }
+
+    // how many keys have a ttl
+    pub(crate) fn volatile_keys(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.expires_at.is_some())
+            .count()
+    }
+
+    /// Turn the active expire cycle on or off, expired keys are still removed when read.
+    pub fn set_active_expire(&self, enabled: bool) {
+        self.server.active_expire.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Spawn the active expire cycle, which periodically evicts expired keys that are never read
+    /// again, in every database. The task only holds a weak reference and stops once the last
+    /// `Backend` is dropped.
+    pub fn spawn_active_expire(&self) -> JoinHandle<()> {
+        let server: Weak<Server> = Arc::downgrade(&self.server);
+        let interval = self.server.config.expire_interval;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            let mut cursors = Vec::new();
+            loop {
+                ticker.tick().await;
+                let Some(server) = server.upgrade() else {
+                    break;
+                };
+                let active = server.active_expire.load(Ordering::Relaxed);
+                let databases = Backend::open(server, 0).databases();
+                // the sizes of the keys written meanwhile are kept up to date along the way
+                for db in &databases {
+                    db.account_written();
+                }
+                if !active {
+                    continue;
+                }
+                cursors.resize_with(databases.len(), ExpireCursor::default);
+                for (db, cursor) in databases.iter().zip(&mut cursors) {
+                    db.active_expire_cycle(cursor);
+                }
+            }
+        })
+    }
+
+    // sample the keys with a ttl and evict those whose deadline has passed, returning how many
+    // keys were evicted
+    fn active_expire_cycle(&self, cursor: &mut ExpireCursor) -> usize {
+        let shards = self.entries.shards();
+        let sample_size = self.server.config.expire_sample_size.max(1);
+        let mut evicted = 0;
+
+        for _ in 0..MAX_ROUNDS_PER_CYCLE {
+            let now = Instant::now();
+            let mut sampled = 0;
+            let mut skipped = 0;
+            let mut expired = Vec::new();
+            let mut visited = 0;
+            while sampled < sample_size
+                && skipped < sample_size * MAX_VISITS_PER_SAMPLE
+                && visited < shards.len()
+            {
+                cursor.shard %= shards.len();
+                // only collect under the read lock, evicting needs to write to the same shard
+                let shard = shards[cursor.shard].read();
+                let buckets = shard.raw_table().buckets();
+                while cursor.bucket < buckets
+                    && sampled < sample_size
+                    && skipped < sample_size * MAX_VISITS_PER_SAMPLE
+                {
+                    let bucket = shard_bucket(&shard, cursor.bucket);
+                    cursor.bucket += 1;
+                    match bucket.and_then(|(key, entry)| Some((key, entry.get().expires_at?))) {
+                        Some((key, deadline)) => {
+                            sampled += 1;
+                            if deadline <= now {
+                                expired.push(key.clone());
+                            }
+                        }
+                        None => skipped += 1,
+                    }
+                }
+                // the table may have grown or shrunk since, past its end is the next shard
+                if cursor.bucket >= buckets {
+                    cursor.shard += 1;
+                    cursor.bucket = 0;
+                    visited += 1;
+                }
+            }
+
+            // evicting leaves the buckets where they are, unless the table is resized, in which
+            // case the next lap through it picks up the keys that moved behind the cursor
+            let round = expired
+                .iter()
+                .filter(|key| self.expire_if_needed(key))
+                .count();
+            evicted += round;
+            if sampled == 0 || round * 4 <= sampled {
+                break;
+            }
+        }
+        evicted
+    }
+
+    // lazy expiration: evict the key if its deadline has passed, returning whether it was evicted
+    pub(crate) fn expire_if_needed(&self, key: &[u8]) -> bool {
+        let now = Instant::now();
+        // looked at under the read lock first, most keys are not expired
+        if !self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.expired(now))
+        {
+            return false;
+        }
+        if self
+            .remove_entry_if(key, |entry| entry.expired(now))
+            .is_some()
+        {
+            self.server.metrics.key_expired();
+            self.notify_keyspace_event(KeyspaceEvents::EXPIRED, "expired", key);
+            return true;
+        }
+        false
+    }
+}
+
+impl Entry {
+    // when the key expires as a unix time in milliseconds, `Some(None)` if it never does and
+    // `None` if it is expired already, at `now` which is `now_ms` on the wall clock
+    pub(super) fn unix_expiry(&self, now: Instant, now_ms: i64) -> Option<Option<i64>> {
+        match self.expires_at {
+            Some(deadline) if deadline <= now => None,
+            Some(deadline) => {
+                let ttl = deadline.duration_since(now).as_millis() as i64;
+                Some(Some(now_ms.saturating_add(ttl)))
+            }
+            None => Some(None),
+        }
+    }
+}
+
+/// The current wall clock time as milliseconds since the unix epoch.
+pub(crate) fn unix_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+// whether deadline `a` comes after `b`, `None` being never
+fn later(a: Option<Instant>, b: Option<Instant>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a > b,
+        (None, Some(_)) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackendConfig, BulkString};
+
+    #[test]
+    fn test_active_expire_cycle() {
+        let backend = Backend::new_with_config(BackendConfig {
+            expire_interval: Duration::from_millis(10),
+            expire_sample_size: 5,
+            ..Default::default()
+        });
+        for i in 0..100 {
+            let key = format!("key{}", i);
+            backend.set(key.clone().into(), BulkString::from("value").into());
+            // half of the keys expire soon, the other half much later
+            let ttl = if i % 2 == 0 { 1 } else { 100_000 };
+            backend.expire(key.as_bytes(), ttl);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+
+        let mut cursor = ExpireCursor::default();
+        let mut evicted = 0;
+        for _ in 0..100 {
+            evicted += backend.active_expire_cycle(&mut cursor);
+        }
+        assert_eq!(evicted, 50);
+        assert_eq!(backend.entries.len(), 50);
+        assert_eq!(backend.volatile_keys(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_active_expire_evicts_unread_keys() {
+        let backend = Backend::new_with_config(BackendConfig {
+            expire_interval: Duration::from_millis(10),
+            expire_sample_size: 20,
+            ..Default::default()
+        });
+        let handle = backend.spawn_active_expire();
+
+        backend.set("hello".into(), BulkString::from("world").into());
+        backend.expire(b"hello", 20);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // look at the map directly, going through `get` would evict the key lazily
+        assert!(!backend.entries.contains_key(b"hello".as_slice()));
+
+        // the task stops once the last handle is gone
+        drop(backend);
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("active expire task did not stop")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_expire_at_converts_wall_clock() {
+        let backend = Backend::new();
+        backend.set("key".into(), BulkString::from("value").into());
+
+        assert!(backend.expire_at(b"key", unix_millis() + 10_000, ExpireCondition::default()));
+        let ttl = backend.pttl(b"key");
+        assert!(ttl > 9_900 && ttl <= 10_000, "ttl {}", ttl);
+
+        // a timestamp in the past deletes the key
+        assert!(backend.expire_at(b"key", unix_millis() - 1000, ExpireCondition::default()));
+        assert_eq!(backend.pttl(b"key"), -2);
+        assert!(!backend.expire_at(b"key", unix_millis() + 10_000, ExpireCondition::default()));
+    }
+
+    #[test]
+    fn test_expire_conditions() {
+        let backend = Backend::new();
+        backend.set("key".into(), BulkString::from("value").into());
+        let nx = ExpireCondition {
+            nx: true,
+            ..Default::default()
+        };
+        let xx = ExpireCondition {
+            xx: true,
+            ..Default::default()
+        };
+        let gt = ExpireCondition {
+            gt: true,
+            ..Default::default()
+        };
+        let lt = ExpireCondition {
+            lt: true,
+            ..Default::default()
+        };
+
+        // without a ttl the key expires infinitely late: XX and GT fail, LT succeeds
+        assert!(!backend.expire_with(b"key", 10_000, xx));
+        assert!(!backend.expire_with(b"key", 10_000, gt));
+        assert!(backend.expire_with(b"key", 10_000, lt));
+        assert!(!backend.expire_with(b"key", 20_000, nx));
+        assert!(backend.persist(b"key"));
+        assert!(backend.expire_with(b"key", 10_000, nx));
+
+        assert!(!backend.expire_with(b"key", 5_000, gt));
+        assert!(backend.expire_with(b"key", 20_000, gt));
+        assert!(backend.pttl(b"key") > 19_000);
+        assert!(!backend.expire_with(b"key", 30_000, lt));
+        assert!(backend.expire_with(b"key", 5_000, lt));
+        assert!(backend.pttl(b"key") <= 5_000);
+        assert!(backend.expire_with(b"key", 50_000, xx));
+        assert!(backend.pttl(b"key") > 49_000);
+    }
+}
@@ -0,0 +1,331 @@
+use super::{Backend, KeyType, ScoreBound, ZAddOptions};
+use crate::cmd::CommandError;
+use bytes::Bytes;
+use std::collections::BTreeSet;
+
+// the latitudes that can be projected with EPSG:3857, like redis
+const LAT_MIN: f64 = -85.05112878;
+const LAT_MAX: f64 = 85.05112878;
+const LON_MIN: f64 = -180.0;
+const LON_MAX: f64 = 180.0;
+/// The bits of each coordinate in a geohash. Both together take 52 bits, which a score holds
+/// exactly.
+const GEO_STEP: u32 = 26;
+/// The radius of the earth in meters, the same as redis so distances agree.
+const EARTH_RADIUS: f64 = 6372797.560856;
+
+/// The unit of a distance given to or returned by the geo commands.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum GeoUnit {
+    #[default]
+    Meters,
+    Kilometers,
+    Miles,
+    Feet,
+}
+
+/// Where `GEOSEARCH` looks from: a member of the set or a position.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeoOrigin {
+    Member(Bytes),
+    LonLat(f64, f64),
+}
+
+/// The area `GEOSEARCH` looks in around its origin, sizes in meters: a circle of the given radius
+/// or a box of the given width and height.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeoShape {
+    Radius(f64),
+    Box(f64, f64),
+}
+
+/// A member found by `GEOSEARCH`, with its distance to the origin in meters, its geohash and its
+/// position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoMatch {
+    pub member: Bytes,
+    pub distance: f64,
+    pub hash: u64,
+    pub lon: f64,
+    pub lat: f64,
+}
+
+impl GeoUnit {
+    /// The meters in one of this unit.
+    pub fn meters(self) -> f64 {
+        match self {
+            GeoUnit::Meters => 1.0,
+            GeoUnit::Kilometers => 1000.0,
+            GeoUnit::Miles => 1609.34,
+            GeoUnit::Feet => 0.3048,
+        }
+    }
+}
+
+impl Backend {
+    /// Add the `(lon, lat, member)` points to the sorted set at `key`, each scored with its
+    /// geohash, creating it if needed. Returns the number of members added, see `zadd`.
+    pub fn geoadd(
+        &self,
+        key: Bytes,
+        points: Vec<(f64, f64, Bytes)>,
+        options: ZAddOptions,
+    ) -> Result<i64, CommandError> {
+        let pairs = points
+            .into_iter()
+            .map(|(lon, lat, member)| {
+                check_position(lon, lat)?;
+                Ok((encode(lon, lat) as f64, member))
+            })
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        self.zadd(key, pairs, options)
+    }
+
+    /// The `(lon, lat)` position of each of `members` of the sorted set at `key`, `None` for those
+    /// missing. Positions are the center of the geohash cell of the member, close to what was
+    /// added.
+    pub fn geopos(
+        &self,
+        key: &[u8],
+        members: &[Bytes],
+    ) -> Result<Vec<Option<(f64, f64)>>, CommandError> {
+        Ok(self
+            .zmscore(key, members)?
+            .into_iter()
+            .map(|score| score.map(|score| decode(score as u64)))
+            .collect())
+    }
+
+    /// The distance in meters between the members `a` and `b` of the sorted set at `key`, `None`
+    /// if either is missing.
+    pub fn geodist(&self, key: &[u8], a: &[u8], b: &[u8]) -> Result<Option<f64>, CommandError> {
+        let members = [Bytes::copy_from_slice(a), Bytes::copy_from_slice(b)];
+        let positions = self.geopos(key, &members)?;
+        Ok(match positions[..] {
+            [Some((lon1, lat1)), Some((lon2, lat2))] => Some(distance(lon1, lat1, lon2, lat2)),
+            _ => None,
+        })
+    }
+
+    /// The members of the sorted set at `key` within `shape` around `origin`, nearest first or
+    /// farthest first with `desc`, and at most `count` of them. Only the geohash cells covering
+    /// the shape are visited, as ranges of scores.
+    pub fn geosearch(
+        &self,
+        key: &[u8],
+        origin: &GeoOrigin,
+        shape: GeoShape,
+        desc: bool,
+        count: Option<usize>,
+    ) -> Result<Vec<GeoMatch>, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        let Some(zset) = self.zsets().get(key) else {
+            return Ok(Vec::new());
+        };
+        let (lon, lat) = match origin {
+            GeoOrigin::LonLat(lon, lat) => {
+                check_position(*lon, *lat)?;
+                (*lon, *lat)
+            }
+            GeoOrigin::Member(member) => match zset.score(member) {
+                Some(score) => decode(score as u64),
+                None => return Err(CommandError::GeoMemberNotFound),
+            },
+        };
+
+        let mut matches = Vec::new();
+        for (start, end) in covering_ranges(lon, lat, shape) {
+            let members = zset.range_by_score(
+                ScoreBound::Inclusive(start as f64),
+                ScoreBound::Exclusive(end as f64),
+            );
+            for (member, score) in members {
+                let hash = score as u64;
+                let (member_lon, member_lat) = decode(hash);
+                if let Some(distance) = distance_within(lon, lat, member_lon, member_lat, shape) {
+                    matches.push(GeoMatch {
+                        member: member.clone(),
+                        distance,
+                        hash,
+                        lon: member_lon,
+                        lat: member_lat,
+                    });
+                }
+            }
+        }
+        matches.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        if desc {
+            matches.reverse();
+        }
+        matches.truncate(count.unwrap_or(usize::MAX));
+        Ok(matches)
+    }
+}
+
+fn check_position(lon: f64, lat: f64) -> Result<(), CommandError> {
+    if (LON_MIN..=LON_MAX).contains(&lon) && (LAT_MIN..=LAT_MAX).contains(&lat) {
+        Ok(())
+    } else {
+        Err(CommandError::InvalidLonLat(lon, lat))
+    }
+}
+
+/// The 52 bit geohash of a position: the cell of each coordinate in 26 bits, interleaved with the
+/// longitude in the odd bits, so that nearby positions tend to have nearby hashes.
+fn encode(lon: f64, lat: f64) -> u64 {
+    interleave(
+        cell(lat, LAT_MIN, LAT_MAX, GEO_STEP),
+        cell(lon, LON_MIN, LON_MAX, GEO_STEP),
+    )
+}
+
+/// The `(lon, lat)` center of the cell of a geohash.
+fn decode(hash: u64) -> (f64, f64) {
+    let center = |index: u32, min: f64, max: f64| {
+        let size = (max - min) / (1u64 << GEO_STEP) as f64;
+        (min + (index as f64 + 0.5) * size).clamp(min, max)
+    };
+    (
+        center(squash(hash >> 1), LON_MIN, LON_MAX),
+        center(squash(hash), LAT_MIN, LAT_MAX),
+    )
+}
+
+// the index of the cell of `value` when splitting `min..=max` in `2^step` cells
+fn cell(value: f64, min: f64, max: f64, step: u32) -> u32 {
+    let cells = 1u64 << step;
+    let index = ((value - min) / (max - min) * cells as f64) as u64;
+    // the maximum belongs to the last cell
+    index.min(cells - 1) as u32
+}
+
+fn interleave(lat: u32, lon: u32) -> u64 {
+    spread(lat) | spread(lon) << 1
+}
+
+// the bits of `value` in the even bits
+fn spread(value: u32) -> u64 {
+    let mut x = value as u64;
+    x = (x | x << 16) & 0x0000_ffff_0000_ffff;
+    x = (x | x << 8) & 0x00ff_00ff_00ff_00ff;
+    x = (x | x << 4) & 0x0f0f_0f0f_0f0f_0f0f;
+    x = (x | x << 2) & 0x3333_3333_3333_3333;
+    (x | x << 1) & 0x5555_5555_5555_5555
+}
+
+// the even bits of `value` packed together, the reverse of `spread`
+fn squash(value: u64) -> u32 {
+    let mut x = value & 0x5555_5555_5555_5555;
+    x = (x | x >> 1) & 0x3333_3333_3333_3333;
+    x = (x | x >> 2) & 0x0f0f_0f0f_0f0f_0f0f;
+    x = (x | x >> 4) & 0x00ff_00ff_00ff_00ff;
+    x = (x | x >> 8) & 0x0000_ffff_0000_ffff;
+    (x | x >> 16) as u32
+}
+
+/// The great-circle distance in meters between two positions, with the haversine formula.
+fn distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let u = ((lat2 - lat1) / 2.0).sin();
+    let v = ((lon2 - lon1).to_radians() / 2.0).sin();
+    let a = u * u + lat1.cos() * lat2.cos() * v * v;
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+// the distance from the origin to a position if it is within `shape` around the origin. In a box
+// the height is measured along the meridian and the width along the parallel of the position,
+// like redis.
+fn distance_within(lon: f64, lat: f64, to_lon: f64, to_lat: f64, shape: GeoShape) -> Option<f64> {
+    match shape {
+        GeoShape::Radius(radius) => {
+            Some(distance(lon, lat, to_lon, to_lat)).filter(|d| *d <= radius)
+        }
+        GeoShape::Box(width, height) => {
+            let lat_distance = EARTH_RADIUS * (to_lat - lat).to_radians().abs();
+            if lat_distance > height / 2.0 || distance(lon, to_lat, to_lon, to_lat) > width / 2.0 {
+                return None;
+            }
+            Some(distance(lon, lat, to_lon, to_lat))
+        }
+    }
+}
+
+// the ranges of scores of the geohash cells covering `shape` around a position, as `start..end`.
+// The cells are the finest for which the shape spans at most three of them each way.
+fn covering_ranges(lon: f64, lat: f64, shape: GeoShape) -> Vec<(u64, u64)> {
+    let (half_width, half_height) = match shape {
+        GeoShape::Radius(radius) => (radius, radius),
+        GeoShape::Box(width, height) => (width / 2.0, height / 2.0),
+    };
+    let lat_delta = (half_height / EARTH_RADIUS).to_degrees();
+    let lat_lo = (lat - lat_delta).max(LAT_MIN);
+    let lat_hi = (lat + lat_delta).min(LAT_MAX);
+    // a degree of longitude is the shortest on the parallel farthest from the equator
+    let parallel = EARTH_RADIUS * lat_lo.abs().max(lat_hi.abs()).to_radians().cos();
+    let lon_delta = (half_width / parallel).to_degrees().min(LON_MAX);
+
+    let mut step = GEO_STEP;
+    let cell_size = |range: f64, step: u32| range / (1u64 << step) as f64;
+    while step > 0
+        && (cell_size(LAT_MAX - LAT_MIN, step) < lat_delta
+            || cell_size(LON_MAX - LON_MIN, step) < lon_delta)
+    {
+        step -= 1;
+    }
+
+    let cells = 1i64 << step;
+    let lon_cell = cell_size(LON_MAX - LON_MIN, step);
+    let lon_first = ((lon - lon_delta - LON_MIN) / lon_cell).floor() as i64;
+    let lon_last = ((lon + lon_delta - LON_MIN) / lon_cell).floor() as i64;
+    let mut hashes = BTreeSet::new();
+    for lat_index in cell(lat_lo, LAT_MIN, LAT_MAX, step)..=cell(lat_hi, LAT_MIN, LAT_MAX, step) {
+        // the cells past the antimeridian wrap around
+        for lon_index in lon_first..=lon_last.min(lon_first + cells - 1) {
+            hashes.insert(interleave(lat_index, lon_index.rem_euclid(cells) as u32));
+        }
+    }
+    let shift = 2 * (GEO_STEP - step);
+    hashes
+        .into_iter()
+        .map(|hash| (hash << shift, (hash + 1) << shift))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geohash_round_trip() {
+        for (lon, lat) in [
+            (13.361389, 38.115556),
+            (-122.4194, 37.7749),
+            (0.0, 0.0),
+            (180.0, LAT_MAX),
+        ] {
+            let hash = encode(lon, lat);
+            assert!(hash < 1 << 52);
+            let (decoded_lon, decoded_lat) = decode(hash);
+            assert!((decoded_lon - lon).abs() < 1e-5, "{} {}", decoded_lon, lon);
+            assert!((decoded_lat - lat).abs() < 1e-5, "{} {}", decoded_lat, lat);
+        }
+        // the same hash as redis for Palermo
+        assert_eq!(encode(13.361389, 38.115556), 3479099956230698);
+        assert_eq!(squash(spread(0x2aa_aaaa)), 0x2aa_aaaa);
+    }
+
+    #[test]
+    fn test_covering_ranges() {
+        // a small radius is covered by a few fine cells, the whole world by coarse ones
+        let ranges = covering_ranges(13.361389, 38.115556, GeoShape::Radius(1000.0));
+        assert!(ranges.len() <= 9);
+        assert!(ranges.iter().all(|(start, end)| end - start < 1 << 30));
+        let hash = encode(13.361389, 38.115556);
+        assert!(ranges
+            .iter()
+            .any(|(start, end)| (*start..*end).contains(&hash)));
+
+        let ranges = covering_ranges(0.0, 0.0, GeoShape::Radius(30_000_000.0));
+        assert_eq!(ranges, vec![(0, 1 << 52)]);
+    }
+}
@@ -0,0 +1,133 @@
+/// Redis style glob matching: `*` matches any run of bytes, `?` any single byte, `[abc]` and
+/// `[a-z]` a byte in the class (`[^...]` negates it) and `\` escapes the next byte.
+pub(crate) fn glob_match(pattern: &[u8], key: &[u8]) -> bool {
+    let (mut p, mut k) = (0, 0);
+    while p < pattern.len() {
+        match pattern[p] {
+            b'*' => {
+                // consecutive stars are the same as a single one
+                while p + 1 < pattern.len() && pattern[p + 1] == b'*' {
+                    p += 1;
+                }
+                if p + 1 == pattern.len() {
+                    return true;
+                }
+                return (k..=key.len()).any(|start| glob_match(&pattern[p + 1..], &key[start..]));
+            }
+            b'?' => {
+                if k == key.len() {
+                    return false;
+                }
+                k += 1;
+            }
+            b'[' => {
+                if k == key.len() {
+                    return false;
+                }
+                let (matched, next) = match_class(pattern, p + 1, key[k]);
+                if !matched {
+                    return false;
+                }
+                p = next;
+                k += 1;
+                continue;
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                p += 1;
+                if k == key.len() || pattern[p] != key[k] {
+                    return false;
+                }
+                k += 1;
+            }
+            c => {
+                if k == key.len() || c != key[k] {
+                    return false;
+                }
+                k += 1;
+            }
+        }
+        p += 1;
+    }
+    k == key.len()
+}
+
+// match `c` against the class starting right after the `[` at `start`, returning whether it
+// matched and the position right after the closing `]`
+fn match_class(pattern: &[u8], start: usize, c: u8) -> (bool, usize) {
+    let mut p = start;
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+    let mut matched = false;
+    // like redis an unterminated class simply ends with the pattern
+    while p < pattern.len() && pattern[p] != b']' {
+        if pattern[p] == b'\\' && p + 1 < pattern.len() {
+            p += 1;
+            matched |= pattern[p] == c;
+        } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' && pattern[p + 2] != b']' {
+            let (lo, hi) = (
+                pattern[p].min(pattern[p + 2]),
+                pattern[p].max(pattern[p + 2]),
+            );
+            matched |= (lo..=hi).contains(&c);
+            p += 2;
+        } else {
+            matched |= pattern[p] == c;
+        }
+        p += 1;
+    }
+    (matched != negate, p + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        let cases: &[(&str, &str, bool)] = &[
+            ("*", "", true),
+            ("*", "anything", true),
+            ("hello", "hello", true),
+            ("hello", "hello!", false),
+            ("h?llo", "hello", true),
+            ("h?llo", "hallo", true),
+            ("h?llo", "hllo", false),
+            ("h*llo", "hllo", true),
+            ("h*llo", "heeeello", true),
+            ("h*llo", "heeeelo", false),
+            ("h**o", "hello", true),
+            ("*o*", "foo", true),
+            ("a*b*c", "aXbYc", true),
+            ("a*b*c", "aXbY", false),
+            ("h[ae]llo", "hello", true),
+            ("h[ae]llo", "hallo", true),
+            ("h[ae]llo", "hillo", false),
+            ("h[^e]llo", "hallo", true),
+            ("h[^e]llo", "hello", false),
+            ("h[a-b]llo", "hbllo", true),
+            ("h[a-b]llo", "hcllo", false),
+            ("h[b-a]llo", "hallo", true),
+            ("key[0-9]", "key7", true),
+            ("key[0-9]", "keyx", false),
+            ("[a-]", "-", true),
+            ("[\\]]", "]", true),
+            ("h\\*llo", "h*llo", true),
+            ("h\\*llo", "hello", false),
+            ("h\\?llo", "hello", false),
+            ("\\[abc\\]", "[abc]", true),
+            ("trailing\\", "trailing\\", true),
+            ("[abc", "b", true),
+        ];
+        for (pattern, key, expected) in cases {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), key.as_bytes()),
+                *expected,
+                "{} against {}",
+                pattern,
+                key
+            );
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use super::{edit_bytes, Backend, KeyType};
+use crate::cmd::CommandError;
+use bytes::Bytes;
+
+// a HyperLogLog is a string value: this header and then one byte per register, so that it can be
+// read and written back with GET and SET like any other string
+const MAGIC: &[u8] = b"HYLL";
+const REGISTER_BITS: u32 = 14;
+const REGISTERS: usize = 1 << REGISTER_BITS;
+const HLL_LEN: usize = MAGIC.len() + REGISTERS;
+// the seed redis uses, so elements land in the same registers as in redis
+const SEED: u64 = 0xadc8_3b19;
+
+impl Backend {
+    /// Add `elements` to the HyperLogLog at `key`, creating it if needed. Returns whether the
+    /// estimated cardinality may have changed: a register was updated or the key was created.
+    pub fn pfadd(&self, key: &[u8], elements: &[Bytes]) -> Result<bool, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        let mut created = false;
+        let mut value = self
+            .strings()
+            .or_insert_with(Bytes::copy_from_slice(key), || {
+                created = true;
+                new_hll()
+            })?;
+        let changed = update_registers(&mut value, |registers| {
+            let mut changed = false;
+            for element in elements {
+                let (index, count) = register_of(element);
+                if registers[index] < count {
+                    registers[index] = count;
+                    changed = true;
+                }
+            }
+            changed
+        })?;
+        Ok(created || changed)
+    }
+
+    /// The estimated number of distinct elements added to the HyperLogLogs at `keys`, counting
+    /// their union without changing any of them. Missing keys are empty.
+    pub fn pfcount(&self, keys: &[Bytes]) -> Result<i64, CommandError> {
+        Ok(estimate(&self.merged_registers(keys)?))
+    }
+
+    /// Store at `dest` the union of the HyperLogLogs at `sources` and the one already at `dest`,
+    /// if any.
+    pub fn pfmerge(&self, dest: &[u8], sources: &[Bytes]) -> Result<(), CommandError> {
+        let merged = self.merged_registers(sources)?;
+        self.expire_if_needed(dest);
+        self.check_type(dest, KeyType::String)?;
+        let mut value = self
+            .strings()
+            .or_insert_with(Bytes::copy_from_slice(dest), new_hll)?;
+        update_registers(&mut value, |registers| {
+            for (register, merged) in registers.iter_mut().zip(merged) {
+                *register = (*register).max(merged);
+            }
+        })
+    }
+
+    // the register-wise max of the HyperLogLogs at `keys`, each read on its own
+    fn merged_registers(&self, keys: &[Bytes]) -> Result<Vec<u8>, CommandError> {
+        let mut merged = vec![0; REGISTERS];
+        for key in keys {
+            self.expire_if_needed(key);
+            self.check_type(key, KeyType::String)?;
+            let Some(value) = self.strings().get(key) else {
+                continue;
+            };
+            for (register, value) in merged.iter_mut().zip(registers(&value)?) {
+                *register = (*register).max(*value);
+            }
+        }
+        Ok(merged)
+    }
+}
+
+fn new_hll() -> Bytes {
+    let mut bytes = Vec::with_capacity(HLL_LEN);
+    bytes.extend_from_slice(MAGIC);
+    bytes.resize(HLL_LEN, 0);
+    bytes.into()
+}
+
+// the registers of a string holding a HyperLogLog
+fn registers(value: &[u8]) -> Result<&[u8], CommandError> {
+    match value.len() == HLL_LEN && value.starts_with(MAGIC) {
+        true => Ok(&value[MAGIC.len()..]),
+        false => Err(CommandError::InvalidHyperLogLog),
+    }
+}
+
+// change the registers of a string holding a HyperLogLog in place
+fn update_registers<R>(
+    value: &mut Bytes,
+    update: impl FnOnce(&mut [u8]) -> R,
+) -> Result<R, CommandError> {
+    registers(value)?;
+    Ok(edit_bytes(value, |s| update(&mut s[MAGIC.len()..])))
+}
+
+// the register `element` falls into, given by the low bits of its hash, and the position of the
+// first set bit in the others
+fn register_of(element: &[u8]) -> (usize, u8) {
+    let hash = murmur_hash64a(element, SEED);
+    let index = hash as usize & (REGISTERS - 1);
+    // the sentinel bit caps the count when all the remaining bits are 0
+    let rest = hash >> REGISTER_BITS | 1 << (64 - REGISTER_BITS);
+    (index, rest.trailing_zeros() as u8 + 1)
+}
+
+// the raw HyperLogLog estimate, a harmonic mean of the registers, with linear counting for small
+// cardinalities where it is more accurate
+fn estimate(registers: &[u8]) -> i64 {
+    let m = REGISTERS as f64;
+    let alpha = 0.7213 / (1.0 + 1.079 / m);
+    let sum = registers
+        .iter()
+        .map(|&r| 2f64.powi(-(r as i32)))
+        .sum::<f64>();
+    let estimate = alpha * m * m / sum;
+    let zeros = registers.iter().filter(|&&r| r == 0).count();
+    let estimate = if estimate <= 2.5 * m && zeros > 0 {
+        m * (m / zeros as f64).ln()
+    } else {
+        estimate
+    };
+    estimate.round() as i64
+}
+
+// MurmurHash64A, the hash redis uses for HyperLogLogs
+fn murmur_hash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().expect("chunks of 8 bytes"));
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_error() {
+        let mut registers = vec![0; REGISTERS];
+        for (i, n) in (1..=100_000).enumerate() {
+            let (index, count) = register_of(format!("element:{}", i).as_bytes());
+            registers[index] = registers[index].max(count);
+            if [10, 100, 1000, 10_000, 100_000].contains(&n) {
+                let error = (estimate(&registers) - n).abs() as f64 / n as f64;
+                assert!(
+                    error < 0.02,
+                    "{} elements estimated with {} error",
+                    n,
+                    error
+                );
+            }
+        }
+        assert_eq!(estimate(&vec![0; REGISTERS]), 0);
+    }
+
+    #[test]
+    fn test_register_of() {
+        // the same register and count for the same element, counts never past the sentinel
+        assert_eq!(register_of(b"foo"), register_of(b"foo"));
+        for i in 0..1000 {
+            let (index, count) = register_of(i.to_string().as_bytes());
+            assert!(index < REGISTERS);
+            assert!((1..=51).contains(&count));
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use super::{Backend, KeyType};
+use crate::{cmd::CommandError, RespFrame};
+use bytes::Bytes;
+use std::{collections::VecDeque, ops::Range};
+
+/// The end of a list a command works on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEnd {
+    /// The head of the list, `LEFT` in redis.
+    Left,
+    /// The tail of the list, `RIGHT` in redis.
+    Right,
+}
+
+/// The `RANK`, `COUNT` and `MAXLEN` options of `LPOS`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LPosOptions {
+    /// Skip the first `rank - 1` matches, counting from the tail if negative. Never 0.
+    pub rank: i64,
+    /// Return up to this many matches, 0 for all of them.
+    pub count: usize,
+    /// Only look at this many elements, 0 for the whole list.
+    pub maxlen: usize,
+}
+
+impl Default for LPosOptions {
+    fn default() -> Self {
+        LPosOptions {
+            rank: 1,
+            count: 1,
+            maxlen: 0,
+        }
+    }
+}
+
+impl Backend {
+    /// Push `values` one after the other onto the head of the list at `key`, creating it if
+    /// needed, so the last value ends up first. Returns the new length.
+    pub fn lpush(&self, key: Bytes, values: Vec<RespFrame>) -> Result<i64, CommandError> {
+        self.push(key, values, ListEnd::Left, false)
+    }
+
+    /// Append `values` to the list at `key`, creating it if needed. Returns the new length.
+    pub fn rpush(&self, key: Bytes, values: Vec<RespFrame>) -> Result<i64, CommandError> {
+        self.push(key, values, ListEnd::Right, false)
+    }
+
+    /// Like `lpush`, only if the list at `key` already exists. Returns 0 otherwise.
+    pub fn lpushx(&self, key: Bytes, values: Vec<RespFrame>) -> Result<i64, CommandError> {
+        self.push(key, values, ListEnd::Left, true)
+    }
+
+    /// Like `rpush`, only if the list at `key` already exists. Returns 0 otherwise.
+    pub fn rpushx(&self, key: Bytes, values: Vec<RespFrame>) -> Result<i64, CommandError> {
+        self.push(key, values, ListEnd::Right, true)
+    }
+
+    /// Remove and return up to `count` elements from the head of the list at `key`, `None` if the
+    /// key does not exist. The key is removed along with its last element.
+    pub fn lpop(&self, key: &[u8], count: usize) -> Result<Option<Vec<RespFrame>>, CommandError> {
+        self.pop(key, count, ListEnd::Left)
+    }
+
+    /// Like `lpop`, from the tail of the list.
+    pub fn rpop(&self, key: &[u8], count: usize) -> Result<Option<Vec<RespFrame>>, CommandError> {
+        self.pop(key, count, ListEnd::Right)
+    }
+
+    /// The length of the list at `key`, 0 if the key does not exist.
+    pub fn llen(&self, key: &[u8]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::List)?;
+        Ok(self.lists().get(key).map_or(0, |list| list.len() as i64))
+    }
+
+    /// The elements of the list at `key` from `start` to `stop` inclusive, see `normalize_range`.
+    pub fn lrange(
+        &self,
+        key: &[u8],
+        start: i64,
+        stop: i64,
+    ) -> Result<Vec<RespFrame>, CommandError> {
+        self.check_type(key, KeyType::List)?;
+        let Some(list) = self.lists().get(key) else {
+            return Ok(Vec::new());
+        };
+        Ok(match normalize_range(list.len(), start, stop) {
+            Some(range) => list.range(range).cloned().collect(),
+            None => Vec::new(),
+        })
+    }
+
+    /// The element at `index` of the list at `key`, negative indexes counting from the tail.
+    pub fn lindex(&self, key: &[u8], index: i64) -> Result<Option<RespFrame>, CommandError> {
+        self.check_type(key, KeyType::List)?;
+        Ok(self.lists().get(key).and_then(|list| {
+            let index = normalize_index(list.len(), index)?;
+            list.get(index).cloned()
+        }))
+    }
+
+    /// Replace the element at `index` of the list at `key`.
+    pub fn lset(&self, key: &[u8], index: i64, value: RespFrame) -> Result<(), CommandError> {
+        self.check_type(key, KeyType::List)?;
+        let Some(mut list) = self.lists().get_mut(key) else {
+            return Err(CommandError::NoSuchKey);
+        };
+        let index = normalize_index(list.len(), index).ok_or(CommandError::IndexOutOfRange)?;
+        list[index] = value;
+        Ok(())
+    }
+
+    /// Remove up to `count` elements equal to `element` from the list at `key`, starting from the
+    /// head for a positive count and from the tail for a negative one. A count of 0 removes all of
+    /// them. Returns how many were removed.
+    pub fn lrem(&self, key: &[u8], count: i64, element: &RespFrame) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::List)?;
+        let Some(mut list) = self.lists().get_mut(key) else {
+            return Ok(0);
+        };
+        let limit = match count {
+            0 => usize::MAX,
+            count => count.unsigned_abs() as usize,
+        };
+        let mut removed = 0;
+        if count >= 0 {
+            list.retain(|e| {
+                let remove = removed < limit && e == element;
+                removed += remove as usize;
+                !remove
+            });
+        } else {
+            // `retain` only walks forward, so drop the matches found from the tail one by one
+            let mut i = list.len();
+            while i > 0 && removed < limit {
+                i -= 1;
+                if list[i] == *element {
+                    list.remove(i);
+                    removed += 1;
+                }
+            }
+        }
+        drop(list);
+        self.remove_if_empty(key);
+        Ok(removed as i64)
+    }
+
+    /// Keep only the elements of the list at `key` from `start` to `stop` inclusive, see
+    /// `normalize_range`. The key is removed if nothing is left.
+    pub fn ltrim(&self, key: &[u8], start: i64, stop: i64) -> Result<(), CommandError> {
+        self.check_type(key, KeyType::List)?;
+        let Some(mut list) = self.lists().get_mut(key) else {
+            return Ok(());
+        };
+        match normalize_range(list.len(), start, stop) {
+            Some(range) => {
+                list.truncate(range.end);
+                list.drain(..range.start);
+            }
+            None => list.clear(),
+        }
+        drop(list);
+        self.remove_if_empty(key);
+        Ok(())
+    }
+
+    /// Insert `element` next to the first occurrence of `pivot` in the list at `key`, on the side
+    /// given by `end`: `Left` inserts before it. Returns the new length, -1 if `pivot` was not
+    /// found and 0 if the key does not exist.
+    pub fn linsert(
+        &self,
+        key: &[u8],
+        end: ListEnd,
+        pivot: &RespFrame,
+        element: RespFrame,
+    ) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::List)?;
+        let Some(mut list) = self.lists().get_mut(key) else {
+            return Ok(0);
+        };
+        let Some(index) = list.iter().position(|e| e == pivot) else {
+            return Ok(-1);
+        };
+        match end {
+            ListEnd::Left => list.insert(index, element),
+            ListEnd::Right => list.insert(index + 1, element),
+        }
+        Ok(list.len() as i64)
+    }
+
+    /// Pop an element from the `from` end of the list at `src` and push it onto the `to` end of the
+    /// list at `dst`, creating it if needed. Returns the element, `None` if `src` does not exist.
+    ///
+    /// With two different keys the element is popped before it is pushed, as both entries can't
+    /// be held at once without risking a deadlock on a shared shard. A concurrent reader may see
+    /// the element in neither list for a moment, but never in both.
+    pub fn lmove(
+        &self,
+        src: &[u8],
+        dst: &[u8],
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<RespFrame>, CommandError> {
+        self.check_type(src, KeyType::List)?;
+        self.check_type(dst, KeyType::List)?;
+        if src == dst {
+            // rotating a single list only needs its one entry
+            let Some(mut list) = self.lists().get_mut(src) else {
+                return Ok(None);
+            };
+            let element = match from {
+                ListEnd::Left => list.pop_front(),
+                ListEnd::Right => list.pop_back(),
+            };
+            if let Some(element) = &element {
+                match to {
+                    ListEnd::Left => list.push_front(element.clone()),
+                    ListEnd::Right => list.push_back(element.clone()),
+                }
+            }
+            return Ok(element);
+        }
+
+        let Some(element) = self.pop(src, 1, from)?.and_then(|mut popped| popped.pop()) else {
+            return Ok(None);
+        };
+        self.push(
+            Bytes::copy_from_slice(dst),
+            vec![element.clone()],
+            to,
+            false,
+        )?;
+        Ok(Some(element))
+    }
+
+    /// The indexes of the elements equal to `element` in the list at `key`, see `positions`. A
+    /// missing key has no matches.
+    pub fn lpos(
+        &self,
+        key: &[u8],
+        element: &RespFrame,
+        options: LPosOptions,
+    ) -> Result<Vec<usize>, CommandError> {
+        self.check_type(key, KeyType::List)?;
+        Ok(match self.lists().get(key) {
+            Some(list) => positions(list.iter(), element, options),
+            None => Vec::new(),
+        })
+    }
+
+    // with `require_existing` the existence check and the push share the entry, a concurrent
+    // delete can't come in between and the key is never created again
+    fn push(
+        &self,
+        key: Bytes,
+        values: Vec<RespFrame>,
+        end: ListEnd,
+        require_existing: bool,
+    ) -> Result<i64, CommandError> {
+        self.check_type(&key, KeyType::List)?;
+        let mut list = match self.lists().get_mut(&key) {
+            Some(list) => list,
+            None if require_existing => return Ok(0),
+            None => self.lists().or_default(key.clone())?,
+        };
+        for value in values {
+            match end {
+                ListEnd::Left => list.push_front(value),
+                ListEnd::Right => list.push_back(value),
+            }
+        }
+        // like redis, the reply is the length before any blocked client takes its element
+        let len = list.len() as i64;
+        self.serve_blocked(&key, &mut list);
+        drop(list);
+        self.remove_if_empty(&key);
+        Ok(len)
+    }
+
+    fn pop(
+        &self,
+        key: &[u8],
+        count: usize,
+        end: ListEnd,
+    ) -> Result<Option<Vec<RespFrame>>, CommandError> {
+        self.check_type(key, KeyType::List)?;
+        let Some(mut list) = self.lists().get_mut(key) else {
+            return Ok(None);
+        };
+        let len = list.len();
+        let count = count.min(len);
+        let popped = match end {
+            ListEnd::Left => list.drain(..count).collect(),
+            ListEnd::Right => list.drain(len - count..).rev().collect(),
+        };
+        drop(list);
+        self.remove_if_empty(key);
+        Ok(Some(popped))
+    }
+
+    // a list left empty is removed, unless a push got to it in the meantime
+    pub(crate) fn remove_if_empty(&self, key: &[u8]) {
+        self.lists().remove_if(key, VecDeque::is_empty);
+    }
+}
+
+/// The positions of a list of `len` elements from `start` to `stop` inclusive, the way redis reads
+/// them: negative offsets count from the tail and out of range offsets are clamped to the list.
+/// `None` if the range is empty.
+pub(crate) fn normalize_range(len: usize, start: i64, stop: i64) -> Option<Range<usize>> {
+    let len = len as i64;
+    let start = if start < 0 { len + start } else { start }.max(0);
+    let stop = if stop < 0 { len + stop } else { stop }.min(len - 1);
+    if start > stop {
+        return None;
+    }
+    Some(start as usize..stop as usize + 1)
+}
+
+/// The indexes, counted from the head, of the elements of `list` equal to `element`, in the order
+/// they are found: from the head for a positive rank and from the tail for a negative one.
+pub(crate) fn positions<'a, I>(list: I, element: &RespFrame, options: LPosOptions) -> Vec<usize>
+where
+    I: DoubleEndedIterator<Item = &'a RespFrame> + ExactSizeIterator,
+{
+    let len = list.len();
+    let maxlen = match options.maxlen {
+        0 => len,
+        maxlen => maxlen,
+    };
+    let count = match options.count {
+        0 => usize::MAX,
+        count => count,
+    };
+    let skip = options.rank.unsigned_abs() as usize - 1;
+    let matches = |(_, e): &(usize, &RespFrame)| *e == element;
+    if options.rank > 0 {
+        list.enumerate()
+            .take(maxlen)
+            .filter(matches)
+            .skip(skip)
+            .take(count)
+            .map(|(i, _)| i)
+            .collect()
+    } else {
+        list.enumerate()
+            .rev()
+            .take(maxlen)
+            .filter(matches)
+            .skip(skip)
+            .take(count)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+// the position of `index` in a list of `len` elements, negative indexes counting from the tail
+fn normalize_index(len: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    #[test]
+    fn test_normalize_range() {
+        let cases: &[(usize, i64, i64, Option<Range<usize>>)] = &[
+            (5, 0, -1, Some(0..5)),
+            (5, 1, 3, Some(1..4)),
+            (5, 2, 2, Some(2..3)),
+            // start after stop
+            (5, 3, 1, None),
+            (5, -1, -3, None),
+            // both negative
+            (5, -3, -1, Some(2..5)),
+            (5, -100, -4, Some(0..2)),
+            (5, -100, -6, None),
+            // stop beyond the end
+            (5, 0, 100, Some(0..5)),
+            (5, 4, 100, Some(4..5)),
+            (5, 5, 100, None),
+            (5, 100, 200, None),
+            // empty list
+            (0, 0, -1, None),
+            (0, 0, 0, None),
+            (0, -1, 10, None),
+        ];
+        for (len, start, stop, expected) in cases {
+            assert_eq!(
+                normalize_range(*len, *start, *stop),
+                *expected,
+                "{} {} in {}",
+                start,
+                stop,
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn test_positions() {
+        let list = ["a", "b", "c", "a", "b", "a"]
+            .map(|e| RespFrame::from(BulkString::from(e)))
+            .to_vec();
+        let a = BulkString::from("a").into();
+        let cases: &[(i64, usize, usize, &[usize])] = &[
+            (1, 1, 0, &[0]),
+            (2, 1, 0, &[3]),
+            (4, 1, 0, &[]),
+            (1, 0, 0, &[0, 3, 5]),
+            (2, 0, 0, &[3, 5]),
+            (1, 2, 0, &[0, 3]),
+            // from the tail
+            (-1, 1, 0, &[5]),
+            (-1, 0, 0, &[5, 3, 0]),
+            (-2, 2, 0, &[3, 0]),
+            (-4, 0, 0, &[]),
+            // only the first elements, or the last ones with a negative rank
+            (1, 0, 3, &[0]),
+            (1, 0, 4, &[0, 3]),
+            (-1, 0, 2, &[5]),
+            (-1, 0, 100, &[5, 3, 0]),
+            (2, 0, 3, &[]),
+        ];
+        for (rank, count, maxlen, expected) in cases {
+            let options = LPosOptions {
+                rank: *rank,
+                count: *count,
+                maxlen: *maxlen,
+            };
+            assert_eq!(
+                positions(list.iter(), &a, options),
+                *expected,
+                "{:?}",
+                options
+            );
+        }
+        assert_eq!(
+            positions(
+                list.iter(),
+                &BulkString::from("x").into(),
+                LPosOptions::default()
+            ),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn test_normalize_index() {
+        assert_eq!(normalize_index(3, 0), Some(0));
+        assert_eq!(normalize_index(3, 2), Some(2));
+        assert_eq!(normalize_index(3, 3), None);
+        assert_eq!(normalize_index(3, -1), Some(2));
+        assert_eq!(normalize_index(3, -3), Some(0));
+        assert_eq!(normalize_index(3, -4), None);
+        assert_eq!(normalize_index(0, 0), None);
+    }
+}
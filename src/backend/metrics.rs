@@ -0,0 +1,502 @@
+use super::Backend;
+use bytes::Bytes;
+use dashmap::{mapref::one::Ref, DashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// the redis version we answer like, clients such as redis-cli turn features on by it
+pub(crate) const REDIS_VERSION: &str = "7.0.0";
+// the sections of `INFO`, their headers and whether they are in the default ones, in order
+const SECTIONS: [(&str, &str, bool); 8] = [
+    ("server", "Server", true),
+    ("clients", "Clients", true),
+    ("memory", "Memory", true),
+    ("persistence", "Persistence", true),
+    ("stats", "Stats", true),
+    ("replication", "Replication", true),
+    ("commandstats", "Commandstats", false),
+    ("keyspace", "Keyspace", true),
+];
+
+/// Counters of a command, reported by `INFO commandstats`.
+#[derive(Debug, Default)]
+pub struct CommandStat {
+    calls: AtomicU64,
+    usec: AtomicU64,
+    rejected_calls: AtomicU64,
+}
+
+impl CommandStat {
+    /// How many times the command ran.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// The microseconds the command spent running, over all its calls.
+    pub fn usec(&self) -> u64 {
+        self.usec.load(Ordering::Relaxed)
+    }
+
+    /// How many times the command was refused before running, e.g. for a wrong arity.
+    pub fn rejected_calls(&self) -> u64 {
+        self.rejected_calls.load(Ordering::Relaxed)
+    }
+}
+
+/// Counters of the server as a whole, reported by `INFO`.
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    connected_clients: AtomicUsize,
+    blocked_clients: AtomicUsize,
+    total_connections: AtomicU64,
+    commands_processed: AtomicU64,
+    keyspace_hits: AtomicU64,
+    keyspace_misses: AtomicU64,
+    expired_keys: AtomicU64,
+    evicted_keys: AtomicU64,
+    // by command name, only those called or rejected since the start or the last reset
+    commands: DashMap<&'static str, CommandStat>,
+    // unix seconds of the last successful save, or of the start before any
+    last_save: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            connected_clients: AtomicUsize::new(0),
+            blocked_clients: AtomicUsize::new(0),
+            total_connections: AtomicU64::new(0),
+            commands_processed: AtomicU64::new(0),
+            keyspace_hits: AtomicU64::new(0),
+            keyspace_misses: AtomicU64::new(0),
+            expired_keys: AtomicU64::new(0),
+            evicted_keys: AtomicU64::new(0),
+            commands: DashMap::new(),
+            last_save: AtomicU64::new(unix_seconds()),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn connection_opened(&self) {
+        self.connected_clients.fetch_add(1, Ordering::Relaxed);
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.connected_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn command_processed(&self) {
+        self.commands_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn client_blocked(&self) {
+        self.blocked_clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn client_unblocked(&self) {
+        self.blocked_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Zero the counters that only ever grow, like `CONFIG RESETSTAT`.
+    pub fn reset(&self) {
+        for counter in [
+            &self.total_connections,
+            &self.commands_processed,
+            &self.keyspace_hits,
+            &self.keyspace_misses,
+            &self.expired_keys,
+            &self.evicted_keys,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.commands.clear();
+    }
+
+    /// Count a call of the command `name` that ran for `duration`.
+    pub fn command_called(&self, name: &'static str, duration: Duration) {
+        self.with_command(name, |stat| {
+            stat.calls.fetch_add(1, Ordering::Relaxed);
+            stat.usec
+                .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        });
+    }
+
+    /// Count a call of the command `name` refused before it ran.
+    pub fn command_rejected(&self, name: &'static str) {
+        self.with_command(name, |stat| {
+            stat.rejected_calls.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    /// The counters of the command `name`, `None` if it was neither called nor rejected.
+    pub fn command_stat(&self, name: &str) -> Option<Ref<'_, &'static str, CommandStat>> {
+        self.commands.get(name)
+    }
+
+    pub(crate) fn key_expired(&self) {
+        self.expired_keys.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn key_evicted(&self) {
+        self.evicted_keys.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // only the first call of a command takes the write lock of its shard
+    fn with_command(&self, name: &'static str, update: impl FnOnce(&CommandStat)) {
+        match self.commands.get(name) {
+            Some(stat) => update(&stat),
+            None => update(&self.commands.entry(name).or_default()),
+        }
+    }
+
+    /// Note a successful save of the data, as reported by `LASTSAVE`.
+    pub fn saved(&self) {
+        self.last_save.store(unix_seconds(), Ordering::Relaxed);
+    }
+
+    /// When the data was last saved, in unix seconds, or when the server started if it never was.
+    pub fn last_save(&self) -> u64 {
+        self.last_save.load(Ordering::Relaxed)
+    }
+
+    pub fn keyspace_hits(&self) -> u64 {
+        self.keyspace_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn keyspace_misses(&self) -> u64 {
+        self.keyspace_misses.load(Ordering::Relaxed)
+    }
+
+    pub fn expired_keys(&self) -> u64 {
+        self.expired_keys.load(Ordering::Relaxed)
+    }
+
+    pub fn evicted_keys(&self) -> u64 {
+        self.evicted_keys.load(Ordering::Relaxed)
+    }
+}
+
+impl Backend {
+    pub fn metrics(&self) -> &Metrics {
+        &self.server.metrics
+    }
+
+    /// Count a hit or a miss for each of `keys`, as looked up by a command reading them.
+    pub fn record_reads(&self, keys: &[Bytes]) {
+        for key in keys {
+            let counter = if self.contains_key(key) {
+                &self.server.metrics.keyspace_hits
+            } else {
+                &self.server.metrics.keyspace_misses
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The reply of `INFO`: the given sections, or the default ones if there are none or it is
+    /// `default`. `all` and `everything` are every section, unknown sections are left out.
+    pub fn info(&self, sections: &[String]) -> String {
+        let mut wanted = sections
+            .iter()
+            .map(|section| section.to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        let all = wanted
+            .iter()
+            .any(|s| matches!(s.as_str(), "all" | "everything"));
+        if all || wanted.is_empty() || wanted.iter().any(|s| s == "default") {
+            wanted.extend(
+                SECTIONS
+                    .iter()
+                    .filter(|(_, _, default)| all || *default)
+                    .map(|(s, _, _)| s.to_string()),
+            );
+        }
+
+        let mut info = String::new();
+        for (section, header, _) in SECTIONS {
+            if !wanted.iter().any(|s| s == section) {
+                continue;
+            }
+            if !info.is_empty() {
+                info.push_str("\r\n");
+            }
+            // writing to a string can't fail
+            let _ = write!(info, "# {}\r\n", header);
+            match section {
+                "keyspace" => self.write_keyspace(&mut info),
+                "commandstats" => self.write_commandstats(&mut info),
+                "replication" => self.write_replication(&mut info),
+                _ => {}
+            }
+            for (name, value) in self.info_fields(section) {
+                let _ = write!(info, "{}:{}\r\n", name, value);
+            }
+        }
+        info
+    }
+
+    fn info_fields(&self, section: &str) -> Vec<(&'static str, String)> {
+        let metrics = &self.server.metrics;
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed).to_string();
+        match section {
+            "server" => {
+                let uptime = metrics.started.elapsed().as_secs();
+                vec![
+                    ("redis_version", REDIS_VERSION.to_string()),
+                    (
+                        "simple_redis_version",
+                        env!("CARGO_PKG_VERSION").to_string(),
+                    ),
+                    ("redis_mode", "standalone".to_string()),
+                    ("arch_bits", usize::BITS.to_string()),
+                    ("process_id", std::process::id().to_string()),
+                    ("uptime_in_seconds", uptime.to_string()),
+                    ("uptime_in_days", (uptime / 86400).to_string()),
+                ]
+            }
+            "clients" => vec![
+                (
+                    "connected_clients",
+                    metrics
+                        .connected_clients
+                        .load(Ordering::Relaxed)
+                        .to_string(),
+                ),
+                (
+                    "blocked_clients",
+                    metrics.blocked_clients.load(Ordering::Relaxed).to_string(),
+                ),
+            ],
+            "memory" => {
+                let used = self.used_memory();
+                vec![
+                    ("used_memory", used.to_string()),
+                    ("used_memory_human", human_bytes(used)),
+                ]
+            }
+            "persistence" => {
+                let status = if self.last_bgsave_failed() {
+                    "err"
+                } else {
+                    "ok"
+                };
+                vec![
+                    ("loading", "0".to_string()),
+                    ("aof_enabled", (self.aof_enabled() as u8).to_string()),
+                    (
+                        "aof_rewrite_in_progress",
+                        (self.aof_rewrite_in_progress() as u8).to_string(),
+                    ),
+                    (
+                        "rdb_bgsave_in_progress",
+                        (self.bgsave_in_progress() as u8).to_string(),
+                    ),
+                    ("rdb_last_save_time", load(&metrics.last_save)),
+                    ("rdb_last_bgsave_status", status.to_string()),
+                ]
+            }
+            "stats" => vec![
+                (
+                    "total_connections_received",
+                    load(&metrics.total_connections),
+                ),
+                (
+                    "total_commands_processed",
+                    load(&metrics.commands_processed),
+                ),
+                ("keyspace_hits", load(&metrics.keyspace_hits)),
+                ("keyspace_misses", load(&metrics.keyspace_misses)),
+                ("expired_keys", load(&metrics.expired_keys)),
+                ("evicted_keys", load(&metrics.evicted_keys)),
+            ],
+            // the keyspace, the commandstats and the replicas have a field per database,
+            // command or replica, see `write_keyspace`, `write_commandstats` and
+            // `write_replication`
+            _ => Vec::new(),
+        }
+    }
+
+    fn write_keyspace(&self, info: &mut String) {
+        for db in self.databases() {
+            let keys = db.dbsize();
+            // like redis, empty databases are not listed
+            if keys > 0 {
+                let expires = db.volatile_keys();
+                let _ = write!(
+                    info,
+                    "db{}:keys={},expires={},avg_ttl=0\r\n",
+                    db.index(),
+                    keys,
+                    expires
+                );
+            }
+        }
+    }
+
+    fn write_commandstats(&self, info: &mut String) {
+        let mut commands = self
+            .server
+            .metrics
+            .commands
+            .iter()
+            .map(|stat| {
+                (
+                    *stat.key(),
+                    stat.calls(),
+                    stat.usec(),
+                    stat.rejected_calls(),
+                )
+            })
+            .collect::<Vec<_>>();
+        commands.sort_unstable();
+        for (name, calls, usec, rejected_calls) in commands {
+            let per_call = if calls == 0 {
+                0.0
+            } else {
+                usec as f64 / calls as f64
+            };
+            let _ = write!(
+                info,
+                "cmdstat_{}:calls={},usec={},usec_per_call={:.2},rejected_calls={}\r\n",
+                name, calls, usec, per_call, rejected_calls
+            );
+        }
+    }
+}
+
+/// The banner of `LOLWUT`.
+pub fn version_banner() -> String {
+    format!(
+        "Simple-Redis ver. {}, answering like Redis ver. {}\n",
+        env!("CARGO_PKG_VERSION"),
+        REDIS_VERSION
+    )
+}
+
+fn unix_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// like the `*_human` fields of redis, e.g. 1.50K
+fn human_bytes(bytes: usize) -> String {
+    let units = ["B", "K", "M", "G", "T"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.2}{}", value, units[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::BulkString;
+
+    #[test]
+    fn test_info_sections() {
+        let backend = Backend::new();
+        let info = backend.info(&[]);
+        let headers = info
+            .lines()
+            .filter(|line| line.starts_with('#'))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            [
+                "# Server",
+                "# Clients",
+                "# Memory",
+                "# Persistence",
+                "# Stats",
+                "# Replication",
+                "# Keyspace"
+            ]
+        );
+        assert!(info.ends_with("# Keyspace\r\n"));
+        // sections are separated by an empty line
+        assert!(info.contains("\r\n\r\n# Clients\r\n"));
+
+        backend.set("a".into(), BulkString::from("1").into());
+        backend.set("b".into(), BulkString::from("2").into());
+        backend.expire_with(b"b", 10_000, Default::default());
+        assert_eq!(
+            backend.info(&["KEYSPACE".into()]),
+            "# Keyspace\r\ndb0:keys=2,expires=1,avg_ttl=0\r\n"
+        );
+        let info = backend.info(&["memory".into(), "clients".into()]);
+        assert!(info.starts_with("# Clients\r\nconnected_clients:0\r\n"));
+        assert!(info.contains("# Memory\r\nused_memory:"));
+        assert_eq!(backend.info(&["nope".into()]), "");
+    }
+
+    #[test]
+    fn test_keyspace_hits_and_misses() -> anyhow::Result<()> {
+        let backend = Backend::new();
+        let get = |key: &str| run_args(&backend, &["GET", key]);
+        get("present")?;
+        backend.set("present".into(), BulkString::from("1").into());
+        get("present")?;
+        get("present")?;
+        get("absent")?;
+        assert_eq!(backend.metrics().keyspace_hits(), 2);
+        assert_eq!(backend.metrics().keyspace_misses(), 2);
+
+        // a key read after its deadline is a miss, and counts as expired
+        backend.expire(b"present", 1);
+        std::thread::sleep(Duration::from_millis(5));
+        get("present")?;
+        assert_eq!(backend.metrics().keyspace_misses(), 3);
+        assert_eq!(backend.metrics().expired_keys(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_commandstats() {
+        let backend = Backend::new();
+        let metrics = backend.metrics();
+        // only asked for, or with everything
+        assert!(!backend.info(&[]).contains("# Commandstats"));
+        assert_eq!(backend.info(&["commandstats".into()]), "# Commandstats\r\n");
+
+        metrics.command_called("get", Duration::from_micros(10));
+        metrics.command_called("get", Duration::from_micros(5));
+        metrics.command_rejected("get");
+        metrics.command_called("del", Duration::from_micros(1));
+        assert_eq!(
+            metrics.command_stat("get").map(|stat| stat.calls()),
+            Some(2)
+        );
+        assert_eq!(
+            backend.info(&["commandstats".into()]),
+            "# Commandstats\r\n\
+             cmdstat_del:calls=1,usec=1,usec_per_call=1.00,rejected_calls=0\r\n\
+             cmdstat_get:calls=2,usec=15,usec_per_call=7.50,rejected_calls=1\r\n"
+        );
+        assert!(backend.info(&["all".into()]).contains("cmdstat_get:"));
+
+        metrics.reset();
+        assert!(metrics.command_stat("get").is_none());
+    }
+
+    #[test]
+    fn test_human_bytes() {
+        assert_eq!(human_bytes(100), "100B");
+        assert_eq!(human_bytes(1536), "1.50K");
+        assert_eq!(human_bytes(3 * 1024 * 1024), "3.00M");
+    }
+}
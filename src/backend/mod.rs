@@ -1,38 +1,247 @@
-use crate::RespFrame;
-use dashmap::{DashMap, DashSet};
+mod aof;
+mod bitmap;
+mod blocking;
+mod clients;
+mod config;
+mod evict;
+mod expire;
+mod geo;
+mod glob;
+mod hyperloglog;
+mod list;
+mod metrics;
+mod monitor;
+mod notify;
+mod object;
+mod pubsub;
+mod replication;
+mod sampling;
+mod scan;
+mod scripts;
+mod slowlog;
+mod snapshot;
+mod stream;
+mod value;
+mod watch;
+mod zset;
+
+pub use bitmap::{BitFieldOp, BitFieldType, BitOperation, BitUnit, Overflow};
+pub use clients::{ClientInfo, KillFilter};
+pub use config::{AppendFsync, MaxMemoryPolicy, ServerConfig};
+pub(crate) use expire::unix_millis;
+pub use expire::ExpireCondition;
+pub use geo::{GeoMatch, GeoOrigin, GeoShape, GeoUnit};
+pub use list::{LPosOptions, ListEnd};
+pub(crate) use metrics::REDIS_VERSION;
+pub use metrics::{version_banner, CommandStat, Metrics};
+pub use monitor::MonitorEvent;
+pub use notify::KeyspaceEvents;
+pub use replication::FullResync;
+pub use slowlog::SlowLogEntry;
+pub use stream::{
+    PendingEntry, PendingSummary, Stream, StreamEntry, StreamFields, StreamId, StreamTrim,
+    TrimThreshold,
+};
+pub(crate) use value::{edit_bytes, string_bytes, string_frame};
+pub use value::{Entry, Hash, Value};
+pub(crate) use zset::format_score;
+pub use zset::{Aggregate, LexBound, Limit, ScoreBound, ZAddOptions, ZRangeBy, ZSet};
+
+use crate::{cmd::CommandError, BulkString, RespFrame};
+use bytes::Bytes;
+use dashmap::mapref::{entry::Entry as MapEntry, one::MappedRef};
+use dashmap::DashMap;
+use glob::glob_match;
+use rand::Rng;
+use sampling::sample;
+use std::collections::{HashSet, VecDeque};
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Notify};
+use tokio_util::sync::CancellationToken;
 
+// the largest string value, same as the redis default of proto-max-bulk-len
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+/// A handle to one of the databases of the server. Cloning it is cheap, and every clone shares
+/// the same data.
 #[derive(Debug, Clone)]
-pub struct Backend(Arc<BackendInner>);
+pub struct Backend {
+    db: Arc<Database>,
+    // where `db` was when the handle was taken, SWAPDB may have moved it since
+    index: usize,
+    // the connection the handle serves, if any
+    client: Option<u64>,
+    server: Arc<Server>,
+}
 
+/// The keys and values of one of the numbered databases.
 #[derive(Debug)]
-pub struct BackendInner {
-    pub(crate) map: DashMap<String, RespFrame>,
-    pub(crate) hmap: DashMap<String, DashMap<String, RespFrame>>,
-    pub(crate) hset: DashMap<String, DashSet<String>>,
+pub struct Database {
+    // every key with its value, its time to live and the rest, see `Entry`
+    pub(crate) entries: DashMap<Bytes, Entry>,
+    // clients blocked in BLPOP and friends, per key in the order they started waiting
+    pub(crate) blocked: DashMap<Bytes, VecDeque<blocking::Waiter>>,
+    // the pops a push just served to blocked clients, propagated right after it, see
+    // `serve_blocked`
+    pub(crate) served: Mutex<Vec<(Bytes, ListEnd)>>,
+    // clients blocked in XREAD, per key, woken whenever an entry is added to it
+    pub(crate) readers: DashMap<Bytes, Vec<Arc<Notify>>>,
+    // the tick a key was last removed at, the version of every key that does not exist, see
+    // `key_version`
+    pub(crate) removed_at: AtomicU64,
+    // the keys written since they were last sized with the tick they were written at, and the
+    // sum of the sizes of every key, see `used_memory`
+    pub(crate) unaccounted: DashMap<Bytes, u64>,
+    pub(crate) used_memory: AtomicUsize,
+}
+
+// what the databases of a server share
+#[derive(Debug)]
+struct Server {
+    databases: RwLock<Vec<Arc<Database>>>,
+    metrics: Metrics,
+    config: BackendConfig,
+    settings: RwLock<ServerConfig>,
+    // the connected clients by id
+    clients: DashMap<u64, ClientInfo>,
+    next_client_id: AtomicU64,
+    // cancelled to stop the server, see `shutdown`
+    shutdown: CancellationToken,
+    save_on_shutdown: AtomicBool,
+    // whether a BGSAVE is running, and whether the last one failed
+    bgsave_running: AtomicBool,
+    bgsave_failed: AtomicBool,
+    // whether a BGREWRITEAOF is running
+    aof_rewriting: AtomicBool,
+    // where every command goes for `MONITOR`
+    monitors: broadcast::Sender<MonitorEvent>,
+    slowlog: Mutex<slowlog::SlowLog>,
+    pubsub: pubsub::PubSub,
+    // the scripts of `SCRIPT LOAD` and `EVAL` by their SHA1 digest
+    scripts: DashMap<String, Arc<str>>,
+    // the `notify-keyspace-events` of the settings, read on every write
+    notify_events: AtomicU32,
+    // the stream of the write commands run so far and the replicas it goes to, see `propagate`
+    replication: Mutex<replication::Replication>,
+    // where the write commands go once appendonly is on
+    aof: Mutex<Option<aof::AofSender>>,
+    // shared by every command, held exclusively while a transaction runs
+    execution: tokio::sync::RwLock<()>,
+    // held by a write from when it runs until it is propagated, see `sequence_lock`
+    sequence: tokio::sync::Mutex<()>,
+    // whether the active expire cycle runs, `DEBUG SET-ACTIVE-EXPIRE` turns it off
+    active_expire: AtomicBool,
+    // ticks on every change and access of a key, which the versions of `WATCH` and the stamps of
+    // the LRU eviction are taken from
+    clock: AtomicU64,
+    // values whose drop is deferred to a background thread, started on first use
+    drop_worker: OnceLock<mpsc::Sender<Box<dyn Send>>>,
+}
+
+/// Modifiers of the `SET` command.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SetOptions {
+    pub condition: SetCondition,
+    pub expiry: SetExpiry,
+    /// Return the previous value of the key.
+    pub get: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SetCondition {
+    #[default]
+    Always,
+    /// `NX`: only set the key if it does not exist.
+    IfNotExists,
+    /// `XX`: only set the key if it already exists.
+    IfExists,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SetExpiry {
+    /// Discard any previous time to live.
+    #[default]
+    Clear,
+    /// `KEEPTTL`: retain the time to live of the previous value.
+    Keep,
+    /// `EX`/`PX`: expire after the given number of milliseconds.
+    After(i64),
+    /// `EXAT`/`PXAT`: expire at the given unix time in milliseconds, right away if it is past.
+    At(i64),
+}
+
+/// The kind of value stored at a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    String,
+    Hash,
+    Set,
+    List,
+    ZSet,
+    Stream,
+}
+
+#[derive(Debug, Clone)]
+pub struct BackendConfig {
+    /// How often the active expire cycle runs.
+    pub expire_interval: Duration,
+    /// How many keys with a ttl are examined per round of the active expire cycle.
+    pub expire_sample_size: usize,
+    /// How many numbered databases there are to `SELECT`.
+    pub databases: usize,
 }
 
 impl Deref for Backend {
-    type Target = BackendInner;
+    type Target = Database;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.db
     }
 }
 
 impl Default for Backend {
     fn default() -> Self {
-        Self(Arc::new(BackendInner::default()))
+        Self::new_with_config(BackendConfig::default())
     }
 }
 
-impl Default for BackendInner {
+impl Default for BackendConfig {
     fn default() -> Self {
         Self {
-            map: DashMap::new(),
-            hmap: DashMap::new(),
-            hset: DashMap::new(),
+            expire_interval: Duration::from_millis(100),
+            expire_sample_size: 20,
+            databases: 16,
+        }
+    }
+}
+
+impl KeyType {
+    /// The name reported by the `TYPE` command.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyType::String => "string",
+            KeyType::Hash => "hash",
+            KeyType::Set => "set",
+            KeyType::List => "list",
+            KeyType::ZSet => "zset",
+            KeyType::Stream => "stream",
+        }
+    }
+}
+
+impl Database {
+    fn new() -> Self {
+        Self {
+            entries: DashMap::new(),
+            blocked: DashMap::new(),
+            served: Mutex::new(Vec::new()),
+            readers: DashMap::new(),
+            removed_at: AtomicU64::new(0),
+            unaccounted: DashMap::new(),
+            used_memory: AtomicUsize::new(0),
         }
     }
 }
@@ -42,37 +251,1068 @@ impl Backend {
         Self::default()
     }
 
-    pub fn get(&self, key: &str) -> Option<RespFrame> {
-        self.map.get(key).map(|v| v.value().clone())
+    /// A server with the `databases` of `config`, all empty, and a handle to database 0.
+    pub fn new_with_config(config: BackendConfig) -> Self {
+        let settings = ServerConfig {
+            databases: config.databases.max(1),
+            ..Default::default()
+        };
+        Self::new_with_settings(config, settings)
+    }
+
+    /// A server with the settings of `config`, as loaded at startup.
+    pub fn new_with_server_config(config: ServerConfig) -> Self {
+        let backend_config = BackendConfig {
+            databases: config.databases,
+            ..Default::default()
+        };
+        Self::new_with_settings(backend_config, config)
+    }
+
+    fn new_with_settings(config: BackendConfig, settings: ServerConfig) -> Self {
+        let databases = (0..config.databases.max(1))
+            .map(|_| Arc::new(Database::new()))
+            .collect();
+        let events = settings.notify_keyspace_events;
+        let server = Server {
+            databases: RwLock::new(databases),
+            metrics: Metrics::default(),
+            settings: RwLock::new(settings),
+            clients: DashMap::new(),
+            next_client_id: AtomicU64::new(1),
+            shutdown: CancellationToken::new(),
+            save_on_shutdown: AtomicBool::new(false),
+            bgsave_running: AtomicBool::new(false),
+            bgsave_failed: AtomicBool::new(false),
+            aof_rewriting: AtomicBool::new(false),
+            active_expire: AtomicBool::new(true),
+            clock: AtomicU64::new(0),
+            slowlog: Mutex::new(Default::default()),
+            pubsub: Default::default(),
+            scripts: DashMap::new(),
+            notify_events: AtomicU32::new(0),
+            replication: Mutex::default(),
+            aof: Mutex::new(None),
+            execution: tokio::sync::RwLock::new(()),
+            sequence: tokio::sync::Mutex::new(()),
+            monitors: broadcast::channel(monitor::MONITOR_CAPACITY).0,
+            config,
+            drop_worker: OnceLock::new(),
+        };
+        let backend = Self::open(Arc::new(server), 0);
+        backend.store_keyspace_events(events);
+        backend
+    }
+
+    fn open(server: Arc<Server>, index: usize) -> Self {
+        let db = server.databases.read().unwrap()[index].clone();
+        Self {
+            db,
+            index,
+            client: None,
+            server,
+        }
+    }
+
+    /// The number of the database this handle is on.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// A handle to database `index` of the same server.
+    pub fn select(&self, index: i64) -> Result<Backend, CommandError> {
+        let count = self.server.databases.read().unwrap().len();
+        match usize::try_from(index) {
+            Ok(index) if index < count => Ok(Self {
+                client: self.client,
+                ..Self::open(self.server.clone(), index)
+            }),
+            _ => Err(CommandError::DbIndexOutOfRange),
+        }
+    }
+
+    /// The handle again, to whatever database is at its number now. A handle kept across
+    /// commands should be refreshed like this, in case a `SWAPDB` moved its database.
+    pub fn current(&self) -> Backend {
+        Self {
+            client: self.client,
+            ..Self::open(self.server.clone(), self.index)
+        }
+    }
+
+    /// A handle to each database of the server, in order.
+    pub fn databases(&self) -> Vec<Backend> {
+        let count = self.server.databases.read().unwrap().len();
+        (0..count)
+            .map(|index| Self::open(self.server.clone(), index))
+            .collect()
+    }
+
+    /// Exchange the contents of two databases, for every client at once.
+    pub fn swapdb(&self, a: i64, b: i64) -> Result<(), CommandError> {
+        let mut databases = self.server.databases.write().unwrap();
+        let index = |i: i64| {
+            usize::try_from(i)
+                .ok()
+                .filter(|i| *i < databases.len())
+                .ok_or(CommandError::DbIndexOutOfRange)
+        };
+        let (a, b) = (index(a)?, index(b)?);
+        databases.swap(a, b);
+        // a key watched in either database may now have another value
+        let tick = self.tick();
+        databases[a].removed_at.store(tick, Ordering::Relaxed);
+        databases[b].removed_at.store(tick, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Ask the server to stop: to accept no more connections and close the open ones once
+    /// they are done with the command at hand. `save` is whether to save the data first, by
+    /// default only if there are `save` points configured.
+    pub fn shutdown(&self, save: Option<bool>) {
+        let save = save.unwrap_or_else(|| !self.config().save.is_empty());
+        self.server.save_on_shutdown.store(save, Ordering::Relaxed);
+        self.server.shutdown.cancel();
+    }
+
+    /// What is cancelled when the server is to stop. Cancelling it is a `shutdown` without
+    /// saving.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.server.shutdown.clone()
+    }
+
+    /// Whether the data should be saved before the server stops, as asked by `shutdown`.
+    pub fn save_on_shutdown(&self) -> bool {
+        self.server.save_on_shutdown.load(Ordering::Relaxed)
+    }
+
+    /// Taken shared to run a command, and exclusively to run a transaction so that no command of
+    /// another connection runs in between.
+    pub fn execution_lock(&self) -> &tokio::sync::RwLock<()> {
+        &self.server.execution
+    }
+
+    /// Held by a write from when it runs until it is propagated, for the replicas and the append
+    /// only file to get the writes in the order they changed the data. Taken after the
+    /// execution lock.
+    pub fn sequence_lock(&self) -> &tokio::sync::Mutex<()> {
+        &self.server.sequence
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<RespFrame> {
+        self.expire_if_needed(key);
+        self.strings().get(key).map(|value| string_frame(&value))
+    }
+
+    pub fn set(&self, key: Bytes, value: RespFrame) {
+        // overwriting a key discards its previous time to live and value of any type
+        let entry = Entry::new(Value::Str(string_bytes(value)), self.tick());
+        self.store_entry(key, entry);
+    }
+
+    /// Get the string values of several keys, `None` for missing keys, in the order of `keys`.
+    pub fn mget(&self, keys: &[Bytes]) -> Vec<Option<RespFrame>> {
+        keys.iter().map(|key| self.get(key)).collect()
     }
 
-    pub fn set(&self, key: String, value: RespFrame) {
-        self.map.insert(key, value);
+    pub fn mset(&self, pairs: Vec<(Bytes, RespFrame)>) {
+        for (key, value) in pairs {
+            self.set(key, value);
+        }
     }
 
-    pub fn hget(&self, key: &str, field: &str) -> Option<RespFrame> {
-        self.hmap
+    /// Set all the pairs only if none of the keys exist, returning whether they were written.
+    /// Like the rest of the backend this is not atomic across keys living in different shards: a
+    /// concurrent writer may create one of the keys between the check and the write.
+    pub fn msetnx(&self, pairs: Vec<(Bytes, RespFrame)>) -> bool {
+        if pairs.iter().any(|(key, _)| self.contains_key(key)) {
+            return false;
+        }
+        self.mset(pairs);
+        true
+    }
+
+    /// Set a string value honoring the `SET` modifiers. Returns whether the value was written and
+    /// the previous string value of the key.
+    pub fn set_with_options(
+        &self,
+        key: Bytes,
+        value: RespFrame,
+        options: &SetOptions,
+    ) -> Result<(bool, Option<RespFrame>), CommandError> {
+        self.expire_if_needed(&key);
+        let value = Value::Str(string_bytes(value));
+        let tick = self.tick();
+        let (written, old) = match self.entries.entry(key.clone()) {
+            MapEntry::Occupied(mut entry) => {
+                let old = match &entry.get().value {
+                    Value::Str(old) => Some(string_frame(old)),
+                    _ if options.get => return Err(CommandError::WrongType),
+                    _ => None,
+                };
+                match options.condition {
+                    SetCondition::IfNotExists => (false, old),
+                    // SET overwrites a key of any type
+                    _ => {
+                        let entry = entry.get_mut();
+                        entry.value = value;
+                        entry.version = tick;
+                        if options.expiry != SetExpiry::Keep {
+                            entry.expires_at = None;
+                        }
+                        (true, old)
+                    }
+                }
+            }
+            MapEntry::Vacant(entry) => match options.condition {
+                SetCondition::IfExists => (false, None),
+                _ => {
+                    entry.insert(Entry::new(value, tick));
+                    (true, None)
+                }
+            },
+        };
+
+        match (written, options.expiry) {
+            (true, SetExpiry::After(ttl_ms)) => {
+                self.expire(&key, ttl_ms);
+            }
+            (true, SetExpiry::At(unix_ms)) => {
+                self.expire_at(&key, unix_ms, ExpireCondition::default());
+            }
+            _ => {}
+        }
+        if written {
+            self.mark_written(std::slice::from_ref(&key));
+        }
+        Ok((written, old))
+    }
+
+    /// Atomically replace the string at `key`, returning the previous value. Like `set` this
+    /// discards the time to live.
+    pub fn getset(&self, key: Bytes, value: RespFrame) -> Result<Option<RespFrame>, CommandError> {
+        self.expire_if_needed(&key);
+        self.check_type(&key, KeyType::String)?;
+        let entry = Entry::new(Value::Str(string_bytes(value)), self.tick());
+        Ok(self
+            .store_entry(key, entry)
+            .and_then(|old| match old.value {
+                Value::Str(old) => Some(string_frame(&old)),
+                _ => None,
+            }))
+    }
+
+    /// Remove the string at `key`, returning it.
+    pub fn getdel(&self, key: &[u8]) -> Result<Option<RespFrame>, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        Ok(self.strings().remove(key).map(|value| string_frame(&value)))
+    }
+
+    /// Get the string at `key` and update its time to live: `Keep` leaves it untouched, `Clear`
+    /// persists the key, and a time to live that is already over deletes it.
+    pub fn getex(&self, key: &[u8], expiry: SetExpiry) -> Result<Option<RespFrame>, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        // hold the value while changing the ttl so a concurrent write can't slip in between
+        let Some(mut entry) = self.entries.get_mut(key) else {
+            return Ok(None);
+        };
+        let Value::Str(value) = &entry.value else {
+            return Err(CommandError::WrongType);
+        };
+        let value = string_frame(value);
+        let ttl_ms = match expiry {
+            SetExpiry::Keep => return Ok(Some(value)),
+            SetExpiry::Clear => {
+                entry.expires_at = None;
+                return Ok(Some(value));
+            }
+            SetExpiry::After(ttl_ms) => ttl_ms,
+            SetExpiry::At(unix_ms) => unix_ms.saturating_sub(unix_millis()),
+        };
+        if ttl_ms > 0 {
+            if let Some(deadline) = Instant::now().checked_add(Duration::from_millis(ttl_ms as u64))
+            {
+                entry.expires_at = Some(deadline);
+            }
+        } else {
+            // like an expiry in the past, it deletes the key once the value is read
+            drop(entry);
+            self.remove_key(key);
+        }
+        Ok(Some(value))
+    }
+
+    /// Atomically add `delta` to the integer stored at `key`, starting from 0 for a missing key.
+    pub fn incr_by(&self, key: &[u8], delta: i64) -> Result<i64, CommandError> {
+        self.expire_if_needed(key);
+        let mut entry = self
+            .strings()
+            .or_insert_with(Bytes::copy_from_slice(key), || Bytes::from_static(b"0"))?;
+        let current = std::str::from_utf8(&entry)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or(CommandError::NotAnInteger)?;
+        let value = current
+            .checked_add(delta)
+            .ok_or(CommandError::NotAnInteger)?;
+        *entry = Bytes::from(value.to_string());
+        Ok(value)
+    }
+
+    /// Atomically add `delta` to the float stored at `key`, returning the new value formatted the
+    /// way it is stored.
+    pub fn incr_by_float(&self, key: &[u8], delta: f64) -> Result<String, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        let mut entry = self
+            .strings()
+            .or_insert_with(Bytes::copy_from_slice(key), || Bytes::from_static(b"0"))?;
+        let current = std::str::from_utf8(&entry)
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .filter(|f| f.is_finite())
+            .ok_or(CommandError::NotAFloat)?;
+        let value = current + delta;
+        if !value.is_finite() {
+            return Err(CommandError::NanOrInfinity);
+        }
+        let value = format_float(value);
+        *entry = Bytes::from(value.clone());
+        Ok(value)
+    }
+
+    /// Append raw bytes to the string at `key`, creating it if missing. Returns the new length.
+    pub fn append(&self, key: &[u8], value: &[u8]) -> Result<i64, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        let mut entry = self.strings().or_default(Bytes::copy_from_slice(key))?;
+        let len = edit_bytes(&mut entry, |s| {
+            s.extend_from_slice(value);
+            s.len()
+        });
+        Ok(len as i64)
+    }
+
+    /// Byte length of the string at `key`, 0 if the key does not exist.
+    pub fn strlen(&self, key: &[u8]) -> Result<i64, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        Ok(self.strings().get(key).map_or(0, |s| s.len() as i64))
+    }
+
+    /// The bytes of the string at `key` between `start` and `end` inclusive. Negative offsets
+    /// count from the end of the string and out of range offsets are clamped.
+    pub fn getrange(&self, key: &[u8], start: i64, end: i64) -> Result<Vec<u8>, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        let Some(s) = self.strings().get(key) else {
+            return Ok(Vec::new());
+        };
+
+        let len = s.len() as i64;
+        let start = if start < 0 { len + start } else { start }.max(0);
+        let end = if end < 0 { len + end } else { end }.max(0).min(len - 1);
+        if start > end || len == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(s[start as usize..=end as usize].to_vec())
+    }
+
+    /// Overwrite the string at `key` starting at `offset`, padding with zero bytes if the string
+    /// is shorter than `offset`. Returns the new length.
+    pub fn setrange(&self, key: &[u8], offset: usize, value: &[u8]) -> Result<i64, CommandError> {
+        self.expire_if_needed(key);
+        self.check_type(key, KeyType::String)?;
+        if offset + value.len() > MAX_STRING_LEN {
+            return Err(CommandError::StringTooLong);
+        }
+        // an empty value never creates the key
+        if value.is_empty() {
+            return self.strlen(key);
+        }
+
+        let mut entry = self.strings().or_default(Bytes::copy_from_slice(key))?;
+        let len = edit_bytes(&mut entry, |s| {
+            let end = offset + value.len();
+            if s.len() < end {
+                s.resize(end, 0);
+            }
+            s[offset..end].copy_from_slice(value);
+            s.len()
+        });
+        Ok(len as i64)
+    }
+
+    pub fn hget(&self, key: &[u8], field: &[u8]) -> Option<RespFrame> {
+        self.expire_if_needed(key);
+        self.hashes()
             .get(key)
             .and_then(|v| v.get(field).map(|v| v.value().clone()))
     }
 
-    pub fn hset(&self, key: String, field: String, value: RespFrame) {
-        let hmap = self.hmap.entry(key).or_default();
-        hmap.insert(field, value);
+    pub fn hset(&self, key: Bytes, field: Bytes, value: RespFrame) -> Result<(), CommandError> {
+        self.hset_multi(key, vec![(field, value)]).map(|_| ())
+    }
+
+    /// Set several fields of the hash at `key`, creating it if needed. Returns how many of the
+    /// fields are new.
+    pub fn hset_multi(
+        &self,
+        key: Bytes,
+        fields: Vec<(Bytes, RespFrame)>,
+    ) -> Result<i64, CommandError> {
+        self.check_type(&key, KeyType::Hash)?;
+        let hash = self.hash_for_write(key)?;
+        let mut created = 0;
+        for (field, value) in fields {
+            if hash.insert(field, value).is_none() {
+                created += 1;
+            }
+        }
+        Ok(created)
+    }
+
+    /// Set `field` of the hash at `key` only if it does not exist yet, returning whether it was
+    /// set. The check and the insert happen under the same lock of the inner map.
+    pub fn hsetnx(&self, key: Bytes, field: Bytes, value: RespFrame) -> Result<bool, CommandError> {
+        self.check_type(&key, KeyType::Hash)?;
+        let hash = self.hash_for_write(key)?;
+        let set = match hash.entry(field) {
+            MapEntry::Occupied(_) => false,
+            MapEntry::Vacant(entry) => {
+                entry.insert(value);
+                true
+            }
+        };
+        Ok(set)
+    }
+
+    /// Atomically add `delta` to the integer in `field` of the hash at `key`, creating both as
+    /// needed starting from 0.
+    pub fn hincr_by(&self, key: &[u8], field: &[u8], delta: i64) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::Hash)?;
+        let hash = self.hash_for_write(Bytes::copy_from_slice(key))?;
+        let mut entry = hash
+            .entry(Bytes::copy_from_slice(field))
+            .or_insert_with(|| BulkString::from("0").into());
+        let current = match entry.value() {
+            RespFrame::BulkString(s) => std::str::from_utf8(s)
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .ok_or(CommandError::HashNotAnInteger)?,
+            RespFrame::Integer(n) => *n,
+            _ => return Err(CommandError::HashNotAnInteger),
+        };
+        let value = current.checked_add(delta).ok_or(CommandError::Overflow)?;
+        *entry = BulkString::from(value.to_string()).into();
+        Ok(value)
+    }
+
+    /// Atomically add `delta` to the float in `field` of the hash at `key`, returning the new value
+    /// formatted the way it is stored.
+    pub fn hincr_by_float(
+        &self,
+        key: &[u8],
+        field: &[u8],
+        delta: f64,
+    ) -> Result<String, CommandError> {
+        self.check_type(key, KeyType::Hash)?;
+        let hash = self.hash_for_write(Bytes::copy_from_slice(key))?;
+        let mut entry = hash
+            .entry(Bytes::copy_from_slice(field))
+            .or_insert_with(|| BulkString::from("0").into());
+        let current = match entry.value() {
+            RespFrame::BulkString(s) => std::str::from_utf8(s)
+                .ok()
+                .and_then(|s| s.parse::<f64>().ok())
+                .filter(|f| f.is_finite())
+                .ok_or(CommandError::HashNotAFloat)?,
+            RespFrame::Integer(n) => *n as f64,
+            RespFrame::Double(f) => *f,
+            _ => return Err(CommandError::HashNotAFloat),
+        };
+        let value = current + delta;
+        if !value.is_finite() {
+            return Err(CommandError::NanOrInfinity);
+        }
+        let value = format_float(value);
+        *entry = BulkString::from(value.as_str()).into();
+        Ok(value)
+    }
+
+    /// The fields and values of the hash at `key`, collected in a single pass so that they are
+    /// consistent with each other. Empty if the key does not exist.
+    pub fn hentries(&self, key: &[u8]) -> Result<Vec<(Bytes, RespFrame)>, CommandError> {
+        self.check_type(key, KeyType::Hash)?;
+        Ok(self.shared_hash(key).map_or_else(Vec::new, |hash| {
+            hash.iter()
+                .map(|e| (e.key().clone(), e.value().clone()))
+                .collect()
+        }))
+    }
+
+    // the hash at `key` to change, under a read lock of the key so that it can't be removed
+    // meanwhile. Only a new key takes the write lock, to create it.
+    fn hash_for_write(
+        &self,
+        key: Bytes,
+    ) -> Result<MappedRef<'_, Bytes, Entry, Hash>, CommandError> {
+        match self.hashes().get(&key) {
+            Some(hash) => Ok(hash),
+            None => self.hashes().or_default_shared(key),
+        }
+    }
+
+    // the fields of the hash at `key` for a long read, which doesn't hold up the other keys of
+    // the database nor the writers of this one
+    fn shared_hash(&self, key: &[u8]) -> Option<Arc<DashMap<Bytes, RespFrame>>> {
+        self.hashes().get(key).map(|hash| hash.shared())
+    }
+
+    /// Remove fields from the hash at `key`, returning how many existed. The key itself is removed
+    /// along with its last field.
+    pub fn hdel(&self, key: &[u8], fields: &[Bytes]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::Hash)?;
+        let removed = match self.hashes().get(key) {
+            Some(hash) => fields.iter().filter(|f| hash.remove(*f).is_some()).count(),
+            None => return Ok(0),
+        };
+        // checked again under the write lock, a concurrent HSET may have refilled the hash
+        self.hashes().remove_if(key, |hash| hash.is_empty());
+        Ok(removed as i64)
+    }
+
+    /// Up to `count` random fields of the hash at `key` with their values. The fields are
+    /// distinct if `distinct` is set, otherwise exactly `count` are picked and may repeat.
+    pub fn hrandfield(
+        &self,
+        key: &[u8],
+        count: usize,
+        distinct: bool,
+    ) -> Result<Vec<(Bytes, RespFrame)>, CommandError> {
+        self.check_type(key, KeyType::Hash)?;
+        Ok(self.shared_hash(key).map_or_else(Vec::new, |hash| {
+            sample(hash.shards(), count, distinct, |field, value| {
+                (field.clone(), value.get().clone())
+            })
+        }))
+    }
+
+    /// Byte length of the value of `field` in the hash at `key`, 0 if either does not exist.
+    pub fn hstrlen(&self, key: &[u8], field: &[u8]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::Hash)?;
+        let len = match self
+            .hashes()
+            .get(key)
+            .as_deref()
+            .and_then(|hash| hash.get(field))
+        {
+            Some(value) => match value.value() {
+                RespFrame::BulkString(s) => s.len(),
+                RespFrame::SimpleString(s) => s.len(),
+                RespFrame::Integer(i) => i.to_string().len(),
+                _ => 0,
+            },
+            None => 0,
+        };
+        Ok(len as i64)
+    }
+
+    pub fn hexists(&self, key: &[u8], field: &[u8]) -> Result<bool, CommandError> {
+        self.check_type(key, KeyType::Hash)?;
+        Ok(self
+            .hashes()
+            .get(key)
+            .is_some_and(|hash| hash.contains_key(field)))
+    }
+
+    /// The number of fields of the hash at `key`, 0 if the key does not exist.
+    pub fn hlen(&self, key: &[u8]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::Hash)?;
+        Ok(self.hashes().get(key).map_or(0, |hash| hash.len() as i64))
+    }
+
+    pub fn sadd(
+        &self,
+        key: impl Into<Bytes>,
+        field: impl Into<Bytes>,
+    ) -> Result<bool, CommandError> {
+        let key = key.into();
+        self.expire_if_needed(&key);
+        self.check_type(&key, KeyType::Set)?;
+        Ok(self.sets().or_default_shared(key)?.insert(field.into()))
+    }
+
+    pub fn sismember(&self, key: &[u8], member: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        self.sets().get(key).is_some_and(|v| v.contains(member))
+    }
+
+    /// Remove members from the set at `key`, returning how many existed. The key itself is removed
+    /// along with its last member.
+    pub fn srem(&self, key: &[u8], members: &[Bytes]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::Set)?;
+        let removed = match self.sets().get(key) {
+            Some(set) => members.iter().filter(|m| set.remove(*m).is_some()).count(),
+            None => return Ok(0),
+        };
+        // checked again under the write lock, a concurrent SADD may have refilled the set
+        self.sets().remove_if(key, |set| set.is_empty());
+        Ok(removed as i64)
+    }
+
+    /// The number of members of the set at `key`, 0 if the key does not exist.
+    pub fn scard(&self, key: &[u8]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::Set)?;
+        Ok(self.sets().get(key).map_or(0, |set| set.len() as i64))
+    }
+
+    /// All members of the set at `key`, empty if the key does not exist.
+    pub fn smembers(&self, key: &[u8]) -> Result<Vec<Bytes>, CommandError> {
+        self.check_type(key, KeyType::Set)?;
+        Ok(self
+            .sets()
+            .get(key)
+            .map_or_else(Vec::new, |set| set.iter().map(|m| m.clone()).collect()))
+    }
+
+    /// Up to `count` random members of the set at `key`. The members are distinct if `distinct`
+    /// is set, otherwise exactly `count` are picked and may repeat.
+    pub fn srandom(
+        &self,
+        key: &[u8],
+        count: usize,
+        distinct: bool,
+    ) -> Result<Vec<Bytes>, CommandError> {
+        self.check_type(key, KeyType::Set)?;
+        Ok(self.sets().get(key).map_or_else(Vec::new, |set| {
+            sample(set.shards(), count, distinct, |member, _| member.clone())
+        }))
+    }
+
+    /// Remove and return up to `count` random members of the set at `key`. The key itself is
+    /// removed along with its last member.
+    pub fn spop(&self, key: &[u8], count: usize) -> Result<Vec<Bytes>, CommandError> {
+        self.check_type(key, KeyType::Set)?;
+        // asking for the whole set takes it over at once
+        if self.scard(key)? as usize <= count {
+            return Ok(self
+                .sets()
+                .remove(key)
+                .map_or_else(Vec::new, |set| set.into_iter().collect()));
+        }
+
+        let popped = match self.sets().get(key) {
+            // a member popped by someone else in the meantime is not returned twice
+            Some(set) => sample(set.shards(), count, true, |member, _| member.clone())
+                .into_iter()
+                .filter(|member| set.remove(member).is_some())
+                .collect(),
+            None => return Ok(Vec::new()),
+        };
+        self.sets().remove_if(key, |set| set.is_empty());
+        Ok(popped)
+    }
+
+    /// Move `member` from the set at `src` to the one at `dst`, creating `dst` if needed. Returns
+    /// false if `member` was not in `src`.
+    pub fn smove(&self, src: &[u8], dst: &[u8], member: &[u8]) -> Result<bool, CommandError> {
+        self.check_set_types(&[Bytes::copy_from_slice(src), Bytes::copy_from_slice(dst)])?;
+        if src == dst {
+            return Ok(self.sismember(src, member));
+        }
+        // only the caller that actually removed the member moves it, so concurrent moves of the
+        // same member can't duplicate it
+        let removed = self
+            .sets()
+            .get(src)
+            .and_then(|set| set.remove(member))
+            .is_some();
+        if !removed {
+            return Ok(false);
+        }
+        self.sets().remove_if(src, |set| set.is_empty());
+        self.sets()
+            .or_default_shared(Bytes::copy_from_slice(dst))?
+            .insert(Bytes::copy_from_slice(member));
+        Ok(true)
+    }
+
+    /// Whether each of `members` is in the set at `key`, in the same order.
+    pub fn smismember(&self, key: &[u8], members: &[Bytes]) -> Result<Vec<bool>, CommandError> {
+        self.check_type(key, KeyType::Set)?;
+        Ok(match self.sets().get(key) {
+            Some(set) => members.iter().map(|m| set.contains(m)).collect(),
+            None => vec![false; members.len()],
+        })
+    }
+
+    /// The members present in every set at `keys`. A missing key is an empty set, which makes the
+    /// whole intersection empty.
+    pub fn sinter(&self, keys: &[Bytes]) -> Result<HashSet<Bytes>, CommandError> {
+        self.check_set_types(keys)?;
+        // start from the smallest set, the intersection can only shrink from there
+        let mut sizes = keys
+            .iter()
+            .map(|key| (self.sets().get(key).map_or(0, |set| set.len()), key))
+            .collect::<Vec<_>>();
+        sizes.sort_unstable();
+        let Some(((_, smallest), rest)) = sizes.split_first() else {
+            return Ok(HashSet::new());
+        };
+
+        let mut members = self.set_members(smallest);
+        for (_, key) in rest {
+            if members.is_empty() {
+                break;
+            }
+            match self.sets().get(key) {
+                Some(set) => members.retain(|member| set.contains(member)),
+                None => members.clear(),
+            }
+        }
+        Ok(members)
+    }
+
+    /// The size of the intersection of the sets at `keys`, counting no further than `limit` when
+    /// it is not 0. Nothing is copied, the members of the smallest set are looked up in the
+    /// others.
+    pub fn sintercard(&self, keys: &[Bytes], limit: usize) -> Result<i64, CommandError> {
+        self.check_set_types(keys)?;
+        // the shard locks only keep out writers, so holding several read guards at once is fine
+        let Some(mut sets) = keys
+            .iter()
+            .map(|key| self.sets().get(key))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Ok(0);
+        };
+        sets.sort_unstable_by_key(|set| set.len());
+        let Some((smallest, rest)) = sets.split_first() else {
+            return Ok(0);
+        };
+
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        let count = smallest
+            .iter()
+            .filter(|member| rest.iter().all(|set| set.contains(member.key())))
+            .take(limit)
+            .count();
+        Ok(count as i64)
+    }
+
+    /// The members present in any of the sets at `keys`.
+    pub fn sunion(&self, keys: &[Bytes]) -> Result<HashSet<Bytes>, CommandError> {
+        self.check_set_types(keys)?;
+        let mut members = HashSet::new();
+        for key in keys {
+            members.extend(self.set_members(key));
+        }
+        Ok(members)
+    }
+
+    /// The members of the first set at `keys` that are in none of the others.
+    pub fn sdiff(&self, keys: &[Bytes]) -> Result<HashSet<Bytes>, CommandError> {
+        self.check_set_types(keys)?;
+        let Some((first, rest)) = keys.split_first() else {
+            return Ok(HashSet::new());
+        };
+
+        let mut members = self.set_members(first);
+        for key in rest {
+            if members.is_empty() {
+                break;
+            }
+            if let Some(set) = self.sets().get(key) {
+                members.retain(|member| !set.contains(member));
+            }
+        }
+        Ok(members)
+    }
+
+    /// Replace whatever is stored at `dest` with a set of `members`, returning its cardinality.
+    /// An empty set deletes `dest` instead.
+    pub fn sstore(&self, dest: &[u8], members: HashSet<Bytes>) -> i64 {
+        let len = members.len();
+        self.remove_key(dest);
+        if len > 0 {
+            self.sets()
+                .insert(Bytes::copy_from_slice(dest), members.into_iter().collect());
+        }
+        len as i64
+    }
+
+    /// Remove the given keys from every keyspace, returning how many keys were actually removed.
+    pub fn del(&self, keys: &[Bytes]) -> i64 {
+        keys.iter()
+            .filter(|key| {
+                self.expire_if_needed(key);
+                self.remove_entry(key).is_some()
+            })
+            .count() as i64
+    }
+
+    /// Like `del`, but freeing the values is left to a background thread so that removing a big
+    /// hash or set returns right away.
+    pub fn unlink(&self, keys: &[Bytes]) -> i64 {
+        let mut removed = 0;
+        for key in keys {
+            self.expire_if_needed(key);
+            if let Some(entry) = self.remove_entry(key) {
+                self.drop_in_background(entry);
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    /// Remove every key. With `lazy` the contents are taken out a shard at a time and freed on
+    /// the background thread, so that flushing a big database returns right away.
+    pub fn flush(&self, lazy: bool) {
+        self.flush_map(&self.entries, lazy);
+        self.forget_all_usage();
+        self.removed_at.store(self.tick(), Ordering::Relaxed);
+    }
+
+    /// `flush` every database of the server.
+    pub fn flush_all(&self, lazy: bool) {
+        for db in self.databases() {
+            db.flush(lazy);
+        }
+    }
+
+    fn flush_map<V: Send + Sync + 'static>(&self, map: &DashMap<Bytes, V>, lazy: bool) {
+        if !lazy {
+            map.clear();
+            return;
+        }
+        for shard in map.shards() {
+            let contents = std::mem::take(&mut *shard.write());
+            self.drop_in_background(contents);
+        }
+    }
+
+    /// Count how many of the given keys exist, like `exists`.
+    pub fn touch(&self, keys: &[Bytes]) -> i64 {
+        self.record_access(keys);
+        self.exists(keys)
+    }
+
+    pub(crate) fn drop_in_background<T: Send + 'static>(&self, value: T) {
+        let worker = self.server.drop_worker.get_or_init(|| {
+            let (tx, rx) = mpsc::channel::<Box<dyn Send>>();
+            // the thread ends once the backend, and with it the sender, is gone
+            std::thread::spawn(move || rx.into_iter().for_each(drop));
+            tx
+        });
+        if let Err(mpsc::SendError(value)) = worker.send(Box::new(value)) {
+            drop(value);
+        }
     }
 
-    pub fn hgetall(&self, key: &str) -> Option<DashMap<String, RespFrame>> {
-        self.hmap.get(key).map(|v| v.clone())
+    /// Count how many of the given keys exist. A key given multiple times is counted multiple times.
+    pub fn exists(&self, keys: &[Bytes]) -> i64 {
+        keys.iter().filter(|key| self.contains_key(key)).count() as i64
     }
 
-    pub fn sadd(&self, key: impl Into<String>, field: impl Into<String>) -> bool {
-        self.hset
-            .entry(key.into())
-            .or_default()
-            .insert(field.into())
+    /// All the keys matching the glob `pattern`, each reported once.
+    pub fn keys(&self, pattern: &[u8]) -> Vec<Bytes> {
+        let mut keys = self
+            .entries
+            .iter()
+            .filter(|e| glob_match(pattern, e.key()))
+            .map(|e| e.key().clone())
+            .collect::<Vec<_>>();
+        // evict expired keys only once no iterator holds a shard lock
+        keys.retain(|key| !self.expire_if_needed(key));
+        keys
     }
 
-    pub fn sismember(&self, key: &str, member: &str) -> bool {
-        self.hset.get(key).map_or(false, |v| v.contains(member))
+    /// Move the value and time to live of `src` to `dst`, overwriting `dst` unless `nx` is set
+    /// in which case nothing happens if it exists. Returns whether the key was renamed.
+    ///
+    /// The value is removed from `src` before being stored under `dst`, so it never lives under
+    /// both names. The two keys can live in different shards though, so a concurrent reader may
+    /// briefly see neither of them.
+    pub fn rename(&self, src: &[u8], dst: &[u8], nx: bool) -> Result<bool, CommandError> {
+        if !self.contains_key(src) {
+            return Err(CommandError::NoSuchKey);
+        }
+        if nx && self.contains_key(dst) {
+            return Ok(false);
+        }
+        if src == dst {
+            return Ok(true);
+        }
+        let Some(entry) = self.remove_entry(src) else {
+            return Err(CommandError::NoSuchKey);
+        };
+        self.store_entry(Bytes::copy_from_slice(dst), entry);
+        Ok(true)
+    }
+
+    /// Store a copy of the value and time to live of `src` under `dst`. An existing `dst` is only
+    /// overwritten with `replace`. Returns whether the key was copied.
+    pub fn copy(&self, src: &[u8], dst: &[u8], replace: bool) -> Result<bool, CommandError> {
+        if src == dst {
+            return Err(CommandError::SameObject);
+        }
+        if !replace && self.contains_key(dst) {
+            return Ok(false);
+        }
+        self.expire_if_needed(src);
+        // cloning a `DashMap` or `DashSet` clones every shard, so the copy shares nothing with the
+        // original
+        let Some((value, expires_at)) = self
+            .entries
+            .get(src)
+            .map(|entry| (entry.value.clone(), entry.expires_at))
+        else {
+            return Ok(false);
+        };
+        let mut entry = Entry::new(value, 0);
+        entry.expires_at = expires_at;
+        self.store_entry(Bytes::copy_from_slice(dst), entry);
+        Ok(true)
+    }
+
+    /// The number of live keys.
+    pub fn dbsize(&self) -> i64 {
+        let now = Instant::now();
+        let expired = self
+            .entries
+            .iter()
+            .filter(|e| e.expired(now))
+            .map(|e| e.key().clone())
+            .collect::<Vec<_>>();
+        for key in expired {
+            self.expire_if_needed(&key);
+        }
+        self.entries.len() as i64
+    }
+
+    /// A random live key, `None` if there are none. It is picked from a random bucket, the keys
+    /// themselves are never collected.
+    pub fn random_key(&self) -> Option<Bytes> {
+        let mut rng = rand::thread_rng();
+        loop {
+            if self.entries.is_empty() {
+                return None;
+            }
+            match random_key_of(&self.entries, &mut rng) {
+                // expired keys are evicted, which makes sure the loop ends once only those are left
+                Some(key) if !self.expire_if_needed(&key) => return Some(key),
+                // expired, or a shard without keys
+                _ => continue,
+            }
+        }
+    }
+
+    /// The kind of value stored at `key`, `None` if the key does not exist.
+    pub fn key_type(&self, key: &[u8]) -> Option<KeyType> {
+        self.expire_if_needed(key);
+        self.entries.get(key).map(|entry| entry.value.key_type())
+    }
+
+    /// Fail with WRONGTYPE if `key` exists and holds something other than `expected`.
+    pub fn check_type(&self, key: &[u8], expected: KeyType) -> Result<(), CommandError> {
+        match self.key_type(key) {
+            Some(actual) if actual != expected => Err(CommandError::WrongType),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_set_types(&self, keys: &[Bytes]) -> Result<(), CommandError> {
+        keys.iter()
+            .try_for_each(|key| self.check_type(key, KeyType::Set))
+    }
+
+    // a copy of the members of the set at `key`, empty if the key does not exist
+    fn set_members(&self, key: &[u8]) -> HashSet<Bytes> {
+        self.sets()
+            .get(key)
+            .map_or_else(HashSet::new, |set| set.iter().map(|m| m.clone()).collect())
+    }
+
+    fn contains_key(&self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        self.entries.contains_key(key)
+    }
+
+    // store `value` at `key` in place of whatever is there, keeping its time to live
+    fn put_value(&self, key: Bytes, value: Value) {
+        let tick = self.tick();
+        match self.entries.entry(key.clone()) {
+            MapEntry::Occupied(mut entry) => {
+                let entry = entry.get_mut();
+                entry.value = value;
+                entry.version = tick;
+            }
+            MapEntry::Vacant(entry) => {
+                entry.insert(Entry::new(value, tick));
+            }
+        }
+        self.mark_written(std::slice::from_ref(&key));
+    }
+
+    // store `entry` at `key` in place of whatever is there, returning what was
+    fn store_entry(&self, key: Bytes, mut entry: Entry) -> Option<Entry> {
+        entry.version = self.tick();
+        entry.size = 0;
+        self.mark_written(std::slice::from_ref(&key));
+        let old = self.entries.insert(key, entry)?;
+        self.used_memory.fetch_sub(old.size, Ordering::Relaxed);
+        Some(old)
+    }
+
+    fn remove_key(&self, key: &[u8]) {
+        self.remove_entry(key);
+    }
+}
+
+// a key of `map` picked at random: the one in a random bucket of a random shard, or the first
+// one after it when the bucket is empty. Like redis, that is not quite uniform but never looks at
+// more than the buckets of one shard. `None` if the shard has no keys
+fn random_key_of<V>(map: &DashMap<Bytes, V>, rng: &mut impl Rng) -> Option<Bytes> {
+    let shards = map.shards();
+    let shard = shards[rng.gen_range(0..shards.len())].read();
+    if shard.is_empty() {
+        return None;
+    }
+    let buckets = shard.raw_table().buckets();
+    let start = rng.gen_range(0..buckets);
+    (0..buckets)
+        .find_map(|i| shard_bucket(&shard, (start + i) % buckets).map(|(key, _)| key.clone()))
+}
+
+// what is in the bucket at `index` of the table of a shard, `None` if it is empty or past the end
+fn shard_bucket<K, V, S>(shard: &hashbrown::HashMap<K, V, S>, index: usize) -> Option<&(K, V)> {
+    let table = shard.raw_table();
+    if index >= table.buckets() {
+        return None;
+    }
+    // SAFETY: the index is one of the buckets, only read once it is known to be full, and the
+    // borrow of the shard keeps the table from changing for as long as the entry is borrowed
+    unsafe {
+        table
+            .is_bucket_full(index)
+            .then(|| table.bucket(index).as_ref())
+    }
+}
+
+/// Format a float the way redis stores it: no trailing zeros and never in exponent notation,
+/// e.g. `3.1` or `5`.
+pub(crate) fn format_float(value: f64) -> String {
+    // redis does its arithmetic in long double, which hides the rounding noise of results such as
+    // `3.1 - 5.1`. Rounding to the 15 significant digits a f64 can hold does the same for us.
+    let value: f64 = format!("{:.14e}", value).parse().unwrap_or(value);
+    // `Display` for f64 is the shortest round-trip form and never uses an exponent
+    let s = value.to_string();
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s
     }
 }
@@ -0,0 +1,107 @@
+use super::Backend;
+use crate::RespArray;
+use crate::RespFrame;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+// how many lines a slow monitor may fall behind before it misses some
+pub(super) const MONITOR_CAPACITY: usize = 1024;
+
+/// A command as seen by `MONITOR`: the client that sent it, if any, and its line.
+pub type MonitorEvent = (Option<u64>, String);
+
+impl Backend {
+    /// Receive a line for every command run from now on, by any client.
+    pub fn monitor(&self) -> broadcast::Receiver<MonitorEvent> {
+        self.server.monitors.subscribe()
+    }
+
+    /// Show the command in `args` to the monitors, if there are any.
+    pub(crate) fn feed_monitors(&self, args: &RespArray) {
+        if self.server.monitors.receiver_count() == 0 {
+            return;
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let addr = self
+            .client_info()
+            .map_or_else(|| "internal".to_string(), |info| info.addr);
+        // writing to a string can't fail
+        let mut line = String::new();
+        let _ = write!(
+            line,
+            "{}.{:06} [{} {}]",
+            now.as_secs(),
+            now.subsec_micros(),
+            self.index,
+            addr
+        );
+        let redacted = matches!(
+            args.first(),
+            Some(RespFrame::BulkString(name)) if name.eq_ignore_ascii_case(b"auth")
+        );
+        for (i, arg) in args.iter().enumerate() {
+            line.push(' ');
+            match arg {
+                // the password is not for the monitors to see
+                _ if redacted && i > 0 => line.push_str("\"(redacted)\""),
+                RespFrame::BulkString(arg) => quote(&mut line, arg),
+                frame => quote(&mut line, format!("{:?}", frame).as_bytes()),
+            }
+        }
+        // nobody may be listening anymore, which is fine
+        let _ = self.server.monitors.send((self.client, line));
+    }
+}
+
+// like the `sdscatrepr` of redis: in double quotes, escaping what is not printable
+fn quote(line: &mut String, arg: &[u8]) {
+    line.push('"');
+    for &byte in arg {
+        match byte {
+            b'\\' => line.push_str("\\\\"),
+            b'"' => line.push_str("\\\""),
+            b'\n' => line.push_str("\\n"),
+            b'\r' => line.push_str("\\r"),
+            b'\t' => line.push_str("\\t"),
+            0x07 => line.push_str("\\a"),
+            0x08 => line.push_str("\\b"),
+            b' '..=b'~' => line.push(byte as char),
+            _ => {
+                let _ = write!(line, "\\x{:02x}", byte);
+            }
+        }
+    }
+    line.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::request_args;
+    use crate::BulkString;
+
+    #[test]
+    fn test_monitor_lines() {
+        let backend = Backend::new();
+        // without monitors nothing is sent
+        backend.feed_monitors(&RespArray::new(vec![BulkString::from("ping").into()]));
+
+        let mut monitor = backend.monitor();
+        backend.feed_monitors(&request_args(&["SET", "k", "a \"b\"\n\x01"]));
+        backend.feed_monitors(&RespArray::new(vec![
+            BulkString::from("auth").into(),
+            BulkString::from("secret").into(),
+        ]));
+
+        let (client, line) = monitor.try_recv().unwrap();
+        assert_eq!(client, None);
+        let (_, line) = line.split_once(' ').unwrap();
+        assert_eq!(line, r#"[0 internal] "SET" "k" "a \"b\"\n\x01""#);
+        let (_, line) = monitor.try_recv().unwrap();
+        assert!(line.ends_with(r#"[0 internal] "auth" "(redacted)""#));
+        assert!(monitor.try_recv().is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use super::Backend;
+use std::sync::atomic::Ordering;
+
+/// The classes of keyspace events to publish, as `notify-keyspace-events` sets them with the
+/// flag letters of redis: `K` and `E` pick the channels, the other letters the events.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KeyspaceEvents(u32);
+
+// every letter after `A`, in the order `CONFIG GET` lists them
+const LETTERS: [(char, KeyspaceEvents); 12] = [
+    ('g', KeyspaceEvents::GENERIC),
+    ('$', KeyspaceEvents::STRING),
+    ('l', KeyspaceEvents::LIST),
+    ('s', KeyspaceEvents::SET),
+    ('h', KeyspaceEvents::HASH),
+    ('z', KeyspaceEvents::ZSET),
+    ('x', KeyspaceEvents::EXPIRED),
+    ('e', KeyspaceEvents::EVICTED),
+    ('t', KeyspaceEvents::STREAM),
+    ('K', KeyspaceEvents::KEYSPACE),
+    ('E', KeyspaceEvents::KEYEVENT),
+    ('m', KeyspaceEvents::KEY_MISS),
+];
+
+impl KeyspaceEvents {
+    /// `K`: publish to `__keyspace@<db>__:<key>`, with the event as the message.
+    pub const KEYSPACE: Self = Self(1);
+    /// `E`: publish to `__keyevent@<db>__:<event>`, with the key as the message.
+    pub const KEYEVENT: Self = Self(1 << 1);
+    /// `g`: commands of any type, like `DEL`, `EXPIRE` and `RENAME`.
+    pub const GENERIC: Self = Self(1 << 2);
+    pub const STRING: Self = Self(1 << 3);
+    pub const LIST: Self = Self(1 << 4);
+    pub const SET: Self = Self(1 << 5);
+    pub const HASH: Self = Self(1 << 6);
+    pub const ZSET: Self = Self(1 << 7);
+    /// `x`: a key reached the end of its time to live.
+    pub const EXPIRED: Self = Self(1 << 8);
+    /// `e`: a key was evicted for `maxmemory`.
+    pub const EVICTED: Self = Self(1 << 9);
+    pub const STREAM: Self = Self(1 << 10);
+    /// `m`: a key was read but missing, not part of `A`.
+    pub const KEY_MISS: Self = Self(1 << 11);
+    /// `A`: every class of events but key misses.
+    pub const ALL: Self = Self(
+        Self::GENERIC.0
+            | Self::STRING.0
+            | Self::LIST.0
+            | Self::SET.0
+            | Self::HASH.0
+            | Self::ZSET.0
+            | Self::EXPIRED.0
+            | Self::EVICTED.0
+            | Self::STREAM.0,
+    );
+
+    /// The classes of the flag letters of `flags`, `None` if one is not a flag.
+    pub fn parse(flags: &str) -> Option<Self> {
+        flags.chars().try_fold(Self::default(), |events, letter| {
+            let class = match letter {
+                'A' => Self::ALL,
+                letter => LETTERS.iter().find(|(l, _)| *l == letter)?.1,
+            };
+            Some(Self(events.0 | class.0))
+        })
+    }
+
+    /// Whether every class of `other` is included.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::fmt::Display for KeyspaceEvents {
+    // like redis, `A` stands for all the classes it includes
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let all = self.contains(Self::ALL);
+        if all {
+            write!(f, "A")?;
+        }
+        for (letter, class) in LETTERS {
+            if self.contains(class) && !(all && Self::ALL.contains(class)) {
+                write!(f, "{}", letter)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Backend {
+    /// Publish that `event` of `class` happened to `key`, on the channels `notify-keyspace-events`
+    /// asks for. Only an atomic load when notifications are off.
+    pub fn notify_keyspace_event(&self, class: KeyspaceEvents, event: &str, key: &[u8]) {
+        let events = KeyspaceEvents(self.server.notify_events.load(Ordering::Relaxed));
+        if !events.contains(class) {
+            return;
+        }
+        if events.contains(KeyspaceEvents::KEYSPACE) {
+            let mut channel = format!("__keyspace@{}__:", self.index).into_bytes();
+            channel.extend_from_slice(key);
+            self.publish(&channel, event.as_bytes());
+        }
+        if events.contains(KeyspaceEvents::KEYEVENT) {
+            let channel = format!("__keyevent@{}__:{}", self.index, event);
+            self.publish(channel.as_bytes(), key);
+        }
+    }
+
+    // keep the flags read by `notify_keyspace_event` in step with the settings
+    pub(super) fn store_keyspace_events(&self, events: KeyspaceEvents) {
+        self.server.notify_events.store(events.0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespFrame};
+    use anyhow::Result;
+
+    #[test]
+    fn test_parse_flags() {
+        let events = KeyspaceEvents::parse("KEA").unwrap();
+        assert!(events.contains(KeyspaceEvents::KEYSPACE));
+        assert!(events.contains(KeyspaceEvents::EXPIRED));
+        assert!(!events.contains(KeyspaceEvents::KEY_MISS));
+        assert_eq!(events.to_string(), "AKE");
+        assert_eq!(KeyspaceEvents::parse("El$").unwrap().to_string(), "$lE");
+        assert_eq!(KeyspaceEvents::parse("").unwrap().to_string(), "");
+        assert_eq!(KeyspaceEvents::parse("Kq"), None);
+    }
+
+    #[tokio::test]
+    async fn test_notifications() -> Result<()> {
+        let server = Backend::new();
+        let (client, _) = server.connect_client("a".into(), "l".into());
+        let mut messages = client.subscriber()?;
+        client.subscribe(&["__keyspace@0__:key".into()])?;
+        client.subscribe(&["__keyevent@0__:lpush".into()])?;
+
+        // nothing is published until asked for
+        server.notify_keyspace_event(KeyspaceEvents::LIST, "lpush", b"key");
+        assert!(messages.try_recv().is_err());
+
+        server.config_set(&[("notify-keyspace-events".into(), "Kl".into())])?;
+        server.notify_keyspace_event(KeyspaceEvents::HASH, "hset", b"key");
+        server.notify_keyspace_event(KeyspaceEvents::LIST, "lpush", b"key");
+        let message = |channel: &[u8], payload: &str| -> RespFrame {
+            crate::RespPush::new(vec![
+                BulkString::from("message").into(),
+                BulkString::from(channel).into(),
+                BulkString::from(payload).into(),
+            ])
+            .into()
+        };
+        assert_eq!(
+            messages.try_recv()?,
+            message(b"__keyspace@0__:key", "lpush")
+        );
+        assert!(messages.try_recv().is_err());
+
+        server.config_set(&[("notify-keyspace-events".into(), "El".into())])?;
+        server
+            .select(1)?
+            .notify_keyspace_event(KeyspaceEvents::LIST, "lpush", b"key");
+        server.notify_keyspace_event(KeyspaceEvents::LIST, "lpush", b"key");
+        assert_eq!(
+            messages.try_recv()?,
+            message(b"__keyevent@0__:lpush", "key")
+        );
+        assert!(messages.try_recv().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+use super::{Backend, StreamId, Value};
+use crate::RespFrame;
+use bytes::Bytes;
+use std::mem::size_of;
+
+// rough per-entry costs of the maps: the key or field `String`, the value and a slot of the hash
+// table, whose control byte and spare capacity is approximated by a pointer
+const ENTRY_OVERHEAD: usize = size_of::<String>() + size_of::<usize>();
+// redis switches small hashes, sets and sorted sets to listpacks below these limits
+const LISTPACK_MAX_ENTRIES: usize = 128;
+const LISTPACK_MAX_VALUE: usize = 64;
+const INTSET_MAX_ENTRIES: usize = 512;
+// lists longer than this are split into a quicklist of listpacks
+const LIST_LISTPACK_MAX_ENTRIES: usize = 128;
+// strings up to this length are allocated together with their object header
+const EMBSTR_MAX_LEN: usize = 44;
+
+impl Backend {
+    /// Approximate number of bytes used by the key and its value, `None` if the key does not
+    /// exist.
+    pub fn memory_usage(&self, key: &[u8]) -> Option<usize> {
+        self.expire_if_needed(key);
+        let entry = self.entries.get(key)?;
+        let value = match &entry.value {
+            Value::Str(s) => size_of::<Bytes>() + s.len(),
+            Value::Hash(hash) => hash
+                .iter()
+                .map(|e| ENTRY_OVERHEAD + e.key().len() + frame_size(e.value()))
+                .sum(),
+            Value::Set(set) => set.iter().map(|m| ENTRY_OVERHEAD + m.len()).sum(),
+            Value::List(list) => list.iter().map(frame_size).sum(),
+            // every member is both in the map of scores and in the ordered index
+            Value::ZSet(zset) => zset
+                .iter()
+                .map(|(member, _)| 2 * (ENTRY_OVERHEAD + member.len() + size_of::<f64>()))
+                .sum(),
+            Value::Stream(stream) => stream
+                .iter()
+                .map(|(_, fields)| {
+                    ENTRY_OVERHEAD
+                        + size_of::<StreamId>()
+                        + fields
+                            .iter()
+                            .map(|(field, value)| field.len() + value.len())
+                            .sum::<usize>()
+                })
+                .sum(),
+        };
+        Some(ENTRY_OVERHEAD + key.len() + value)
+    }
+
+    /// The name of the encoding redis would use for the value at `key`, `None` if the key does
+    /// not exist.
+    pub fn object_encoding(&self, key: &[u8]) -> Option<&'static str> {
+        self.expire_if_needed(key);
+        let entry = self.entries.get(key)?;
+        Some(match &entry.value {
+            Value::Str(s) if is_integer(s) => "int",
+            Value::Str(s) if s.len() <= EMBSTR_MAX_LEN => "embstr",
+            Value::Str(_) => "raw",
+            Value::Hash(hash) => {
+                let small = hash.len() <= LISTPACK_MAX_ENTRIES
+                    && hash.iter().all(|e| {
+                        e.key().len() <= LISTPACK_MAX_VALUE
+                            && frame_size(e.value()) - size_of::<RespFrame>() <= LISTPACK_MAX_VALUE
+                    });
+                if small {
+                    "listpack"
+                } else {
+                    "hashtable"
+                }
+            }
+            Value::Set(set) => {
+                if set.len() <= INTSET_MAX_ENTRIES && set.iter().all(|m| is_integer(m.key())) {
+                    "intset"
+                } else if set.len() <= LISTPACK_MAX_ENTRIES
+                    && set.iter().all(|m| m.len() <= LISTPACK_MAX_VALUE)
+                {
+                    "listpack"
+                } else {
+                    "hashtable"
+                }
+            }
+            Value::List(list) => {
+                let small = list.len() <= LIST_LISTPACK_MAX_ENTRIES
+                    && list
+                        .iter()
+                        .all(|e| frame_size(e) - size_of::<RespFrame>() <= LISTPACK_MAX_VALUE);
+                if small {
+                    "listpack"
+                } else {
+                    "quicklist"
+                }
+            }
+            Value::ZSet(zset) => {
+                let small = zset.len() <= LISTPACK_MAX_ENTRIES
+                    && zset
+                        .iter()
+                        .all(|(member, _)| member.len() <= LISTPACK_MAX_VALUE);
+                if small {
+                    "listpack"
+                } else {
+                    "skiplist"
+                }
+            }
+            Value::Stream(_) => "stream",
+        })
+    }
+}
+
+// the size of a frame including the bytes it owns
+fn frame_size(frame: &RespFrame) -> usize {
+    size_of::<RespFrame>()
+        + match frame {
+            RespFrame::BulkString(s) => s.len(),
+            RespFrame::SimpleString(s) => s.len(),
+            _ => 0,
+        }
+}
+
+fn is_integer(value: &[u8]) -> bool {
+    std::str::from_utf8(value).is_ok_and(|s| s.parse::<i64>().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    #[test]
+    fn test_memory_usage_is_ordered() {
+        let backend = Backend::new();
+        backend.set("string".into(), BulkString::from("0123456789").into());
+        for i in 0..1000 {
+            backend
+                .hset(
+                    "hash".into(),
+                    format!("field{}", i).into(),
+                    BulkString::from("value").into(),
+                )
+                .unwrap();
+        }
+
+        let string = backend.memory_usage(b"string").unwrap();
+        let hash = backend.memory_usage(b"hash").unwrap();
+        assert!(string >= "string".len() + 10);
+        assert!(hash > string * 100);
+        assert_eq!(backend.memory_usage(b"missing"), None);
+
+        backend.set("longer".into(), BulkString::from("0".repeat(100)).into());
+        assert!(backend.memory_usage(b"longer").unwrap() > string);
+    }
+
+    #[test]
+    fn test_object_encoding() {
+        let backend = Backend::new();
+        backend.set("int".into(), BulkString::from("-12345").into());
+        backend.set("embstr".into(), BulkString::from("hello").into());
+        backend.set("raw".into(), BulkString::from("x".repeat(100)).into());
+        backend
+            .hset(
+                "small".into(),
+                "field".into(),
+                BulkString::from("value").into(),
+            )
+            .unwrap();
+        for i in 0..200 {
+            backend
+                .hset(
+                    "big".into(),
+                    i.to_string().into(),
+                    BulkString::from("value").into(),
+                )
+                .unwrap();
+            backend.sadd("ints", i.to_string()).unwrap();
+        }
+        backend.sadd("members", "member").unwrap();
+
+        for (key, encoding) in [
+            ("int", "int"),
+            ("embstr", "embstr"),
+            ("raw", "raw"),
+            ("small", "listpack"),
+            ("big", "hashtable"),
+            ("ints", "intset"),
+            ("members", "listpack"),
+        ] {
+            assert_eq!(
+                backend.object_encoding(key.as_bytes()),
+                Some(encoding),
+                "{}",
+                key
+            );
+        }
+        assert_eq!(backend.object_encoding(b"missing"), None);
+    }
+}
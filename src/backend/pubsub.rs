@@ -0,0 +1,427 @@
+use super::{glob_match, Backend, KillFilter};
+use crate::{cmd::CommandError, BulkString, RespFrame, RespPush};
+use bytes::Bytes;
+use dashmap::DashMap;
+use std::collections::HashSet;
+use tokio::sync::mpsc;
+
+// how many messages a slow subscriber may fall behind before it is disconnected
+const SUBSCRIBER_CAPACITY: usize = 1024;
+
+/// Who is subscribed to what, and where their messages go.
+#[derive(Debug, Default)]
+pub(super) struct PubSub {
+    // the ids of the clients subscribed to each channel
+    channels: DashMap<Bytes, HashSet<u64>>,
+    // and to each pattern
+    patterns: DashMap<Bytes, HashSet<u64>>,
+    // by client id
+    subscribers: DashMap<u64, Subscriber>,
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    messages: mpsc::Sender<RespFrame>,
+    channels: HashSet<Bytes>,
+    patterns: HashSet<Bytes>,
+}
+
+// what a subscription is to, the channels and the patterns go the same way
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Channel,
+    Pattern,
+}
+
+impl Kind {
+    fn registry(self, pubsub: &PubSub) -> &DashMap<Bytes, HashSet<u64>> {
+        match self {
+            Kind::Channel => &pubsub.channels,
+            Kind::Pattern => &pubsub.patterns,
+        }
+    }
+
+    fn subscriptions(self, subscriber: &mut Subscriber) -> &mut HashSet<Bytes> {
+        match self {
+            Kind::Channel => &mut subscriber.channels,
+            Kind::Pattern => &mut subscriber.patterns,
+        }
+    }
+
+    // the first word of the confirmations of subscribing and unsubscribing
+    fn confirmations(self) -> (&'static str, &'static str) {
+        match self {
+            Kind::Channel => ("subscribe", "unsubscribe"),
+            Kind::Pattern => ("psubscribe", "punsubscribe"),
+        }
+    }
+}
+
+impl Subscriber {
+    fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+}
+
+impl Backend {
+    /// Receive the messages published to the channels the connection the handle serves subscribes
+    /// to, from now on. Only the last receiver asked for gets them.
+    pub fn subscriber(&self) -> Result<mpsc::Receiver<RespFrame>, CommandError> {
+        let id = self.client.ok_or(CommandError::NoConnection)?;
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_CAPACITY);
+        let mut subscriber =
+            self.server
+                .pubsub
+                .subscribers
+                .entry(id)
+                .or_insert_with(|| Subscriber {
+                    messages: tx.clone(),
+                    channels: HashSet::new(),
+                    patterns: HashSet::new(),
+                });
+        subscriber.messages = tx;
+        Ok(rx)
+    }
+
+    /// Subscribe the connection the handle serves to `channels`. Returns the confirmation of each,
+    /// with how many channels and patterns it is subscribed to after it.
+    pub fn subscribe(&self, channels: &[Bytes]) -> Result<Vec<RespFrame>, CommandError> {
+        self.add_subscriptions(Kind::Channel, channels)
+    }
+
+    /// Subscribe the connection the handle serves to the channels matching the glob `patterns`,
+    /// like `subscribe`.
+    pub fn psubscribe(&self, patterns: &[Bytes]) -> Result<Vec<RespFrame>, CommandError> {
+        self.add_subscriptions(Kind::Pattern, patterns)
+    }
+
+    /// Unsubscribe the connection the handle serves from `channels`, or from every channel if
+    /// there are none. Returns the confirmation of each like `subscribe`.
+    pub fn unsubscribe(&self, channels: &[Bytes]) -> Vec<RespFrame> {
+        self.remove_subscriptions(Kind::Channel, channels)
+    }
+
+    /// Unsubscribe the connection the handle serves from `patterns`, or from every pattern if
+    /// there are none, like `unsubscribe`.
+    pub fn punsubscribe(&self, patterns: &[Bytes]) -> Vec<RespFrame> {
+        self.remove_subscriptions(Kind::Pattern, patterns)
+    }
+
+    /// The channels the connection the handle serves is subscribed to.
+    pub fn subscribed_channels(&self) -> Vec<Bytes> {
+        self.subscribed(Kind::Channel)
+    }
+
+    /// Whether the connection the handle serves is subscribed to any channel or pattern, which
+    /// only lets it send the commands of subscribers.
+    pub fn is_subscriber(&self) -> bool {
+        self.client
+            .and_then(|id| self.server.pubsub.subscribers.get(&id))
+            .is_some_and(|subscriber| subscriber.count() > 0)
+    }
+
+    /// Send `message` to the subscribers of `channel` and of the patterns matching it, returning
+    /// how many messages were sent. A client subscribed to the channel and to a pattern, or to
+    /// several patterns, gets it once for each.
+    ///
+    /// Like redis, a subscriber too slow to keep up with its messages is disconnected.
+    pub fn publish(&self, channel: &[u8], message: &[u8]) -> usize {
+        let mut deliveries = Vec::new();
+        if let Some(ids) = self.server.pubsub.channels.get(channel) {
+            let frame: RespFrame = RespPush::new(vec![
+                BulkString::from("message").into(),
+                BulkString::from(channel).into(),
+                BulkString::new(message.to_vec()).into(),
+            ])
+            .into();
+            deliveries.extend(ids.iter().map(|id| (*id, frame.clone())));
+        }
+        for entry in self.server.pubsub.patterns.iter() {
+            if !glob_match(entry.key(), channel) {
+                continue;
+            }
+            let frame: RespFrame = RespPush::new(vec![
+                BulkString::from("pmessage").into(),
+                BulkString::from(entry.key()).into(),
+                BulkString::from(channel).into(),
+                BulkString::new(message.to_vec()).into(),
+            ])
+            .into();
+            deliveries.extend(entry.value().iter().map(|id| (*id, frame.clone())));
+        }
+
+        // the registries are let go of before sending, a subscriber may be subscribing meanwhile
+        let mut received = 0;
+        for (id, frame) in deliveries {
+            let Some(subscriber) = self.server.pubsub.subscribers.get(&id) else {
+                continue;
+            };
+            match subscriber.messages.try_send(frame) {
+                Ok(()) => received += 1,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    drop(subscriber);
+                    self.kill_clients(&KillFilter {
+                        id: Some(id),
+                        ..Default::default()
+                    });
+                }
+                // the connection is closing, it is unsubscribed once it is gone
+                Err(mpsc::error::TrySendError::Closed(_)) => {}
+            }
+        }
+        received
+    }
+
+    /// The channels with at least one subscriber, only those matching the glob `pattern` if
+    /// there is one, like `PUBSUB CHANNELS`.
+    pub fn pubsub_channels(&self, pattern: Option<&[u8]>) -> Vec<Bytes> {
+        let mut channels = self
+            .server
+            .pubsub
+            .channels
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|channel| pattern.is_none_or(|p| glob_match(p, channel)))
+            .collect::<Vec<_>>();
+        channels.sort_unstable();
+        channels
+    }
+
+    /// How many clients are subscribed to each of `channels`, patterns left out.
+    pub fn pubsub_numsub(&self, channels: &[Bytes]) -> Vec<usize> {
+        channels
+            .iter()
+            .map(|channel| {
+                self.server
+                    .pubsub
+                    .channels
+                    .get(channel)
+                    .map_or(0, |ids| ids.len())
+            })
+            .collect()
+    }
+
+    /// How many patterns some client is subscribed to.
+    pub fn pubsub_numpat(&self) -> usize {
+        self.server.pubsub.patterns.len()
+    }
+
+    // forget the subscriptions of the connection the handle serves, once it is closed
+    pub(super) fn drop_subscriber(&self) {
+        let Some(id) = self.client else {
+            return;
+        };
+        if self.server.pubsub.subscribers.contains_key(&id) {
+            self.unsubscribe(&[]);
+            self.punsubscribe(&[]);
+            self.server.pubsub.subscribers.remove(&id);
+        }
+    }
+
+    fn subscribed(&self, kind: Kind) -> Vec<Bytes> {
+        self.client
+            .and_then(|id| self.server.pubsub.subscribers.get_mut(&id))
+            .map_or_else(Vec::new, |mut subscriber| {
+                kind.subscriptions(&mut subscriber)
+                    .iter()
+                    .cloned()
+                    .collect()
+            })
+    }
+
+    fn add_subscriptions(
+        &self,
+        kind: Kind,
+        names: &[Bytes],
+    ) -> Result<Vec<RespFrame>, CommandError> {
+        let id = self.client.ok_or(CommandError::NoConnection)?;
+        let registry = kind.registry(&self.server.pubsub);
+        let mut replies = Vec::with_capacity(names.len());
+        for name in names {
+            // the registry entry is let go of before touching the subscriber, a publisher takes
+            // them in the same order
+            registry.entry(name.clone()).or_default().insert(id);
+            let count = match self.server.pubsub.subscribers.get_mut(&id) {
+                Some(mut subscriber) => {
+                    kind.subscriptions(&mut subscriber).insert(name.clone());
+                    subscriber.count()
+                }
+                None => return Err(CommandError::NoConnection),
+            };
+            replies.push(confirmation(kind.confirmations().0, Some(name), count));
+        }
+        Ok(replies)
+    }
+
+    fn remove_subscriptions(&self, kind: Kind, names: &[Bytes]) -> Vec<RespFrame> {
+        let names = if names.is_empty() {
+            self.subscribed(kind)
+        } else {
+            names.to_vec()
+        };
+        let Some(id) = self.client else {
+            return Vec::new();
+        };
+        let registry = kind.registry(&self.server.pubsub);
+        let word = kind.confirmations().1;
+
+        let mut replies = Vec::with_capacity(names.len());
+        for name in &names {
+            if let Some(mut ids) = registry.get_mut(name) {
+                ids.remove(&id);
+            }
+            registry.remove_if(name, |_, ids| ids.is_empty());
+            let count = self
+                .server
+                .pubsub
+                .subscribers
+                .get_mut(&id)
+                .map_or(0, |mut subscriber| {
+                    kind.subscriptions(&mut subscriber).remove(name);
+                    subscriber.count()
+                });
+            replies.push(confirmation(word, Some(name), count));
+        }
+        // like redis, unsubscribing from nothing still confirms
+        if replies.is_empty() {
+            let count = self
+                .server
+                .pubsub
+                .subscribers
+                .get(&id)
+                .map_or(0, |subscriber| subscriber.count());
+            replies.push(confirmation(word, None, count));
+        }
+        replies
+    }
+}
+
+// the reply to a subscription or an unsubscription: what it was, the channel or pattern and how
+// many the client is subscribed to after it. Like the messages, it is a push, which a RESP2 client
+// gets as an array
+fn confirmation(kind: &str, name: Option<&Bytes>, count: usize) -> RespFrame {
+    let name = match name {
+        Some(name) => BulkString::from(name).into(),
+        None => RespFrame::NULL,
+    };
+    RespPush::new(vec![
+        BulkString::from(kind).into(),
+        name,
+        RespFrame::Integer(count as i64),
+    ])
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_publish_to_two_subscribers() -> Result<()> {
+        let server = Backend::new();
+        let (a, _) = server.connect_client("a".into(), "l".into());
+        let (b, _) = server.connect_client("b".into(), "l".into());
+        let mut a_messages = a.subscriber()?;
+        let mut b_messages = b.subscriber()?;
+
+        let replies = a.subscribe(&["news".into(), "sport".into()])?;
+        assert_eq!(replies.len(), 2);
+        assert_eq!(
+            replies[1],
+            confirmation("subscribe", Some(&"sport".into()), 2)
+        );
+        b.subscribe(&["news".into()])?;
+        assert!(a.is_subscriber());
+        assert!(!server.is_subscriber());
+
+        assert_eq!(server.publish(b"news", b"hello"), 2);
+        assert_eq!(server.publish(b"sport", b"goal"), 1);
+        assert_eq!(server.publish(b"weather", b"rain"), 0);
+        let message = |channel: &[u8], payload: &str| -> RespFrame {
+            RespPush::new(vec![
+                BulkString::from("message").into(),
+                BulkString::from(channel).into(),
+                BulkString::from(payload).into(),
+            ])
+            .into()
+        };
+        assert_eq!(a_messages.recv().await, Some(message(b"news", "hello")));
+        assert_eq!(a_messages.recv().await, Some(message(b"sport", "goal")));
+        assert_eq!(b_messages.recv().await, Some(message(b"news", "hello")));
+        assert!(b_messages.try_recv().is_err());
+
+        assert_eq!(
+            b.unsubscribe(&[]),
+            [confirmation("unsubscribe", Some(&"news".into()), 0)]
+        );
+        assert_eq!(b.unsubscribe(&[]), [confirmation("unsubscribe", None, 0)]);
+        assert_eq!(server.publish(b"news", b"again"), 1);
+
+        a.disconnect_client();
+        assert_eq!(server.publish(b"news", b"gone"), 0);
+        assert!(server.server.pubsub.channels.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_patterns_overlapping_channels() -> Result<()> {
+        let server = Backend::new();
+        let (client, _) = server.connect_client("a".into(), "l".into());
+        let mut messages = client.subscriber()?;
+        client.subscribe(&["news.tech".into()])?;
+        let replies = client.psubscribe(&["news.*".into(), "*".into()])?;
+        assert_eq!(replies[1], confirmation("psubscribe", Some(&"*".into()), 3));
+
+        // once for the channel and once for each pattern
+        assert_eq!(server.publish(b"news.tech", b"rust"), 3);
+        assert_eq!(server.publish(b"weather", b"rain"), 1);
+        assert!(matches!(messages.recv().await, Some(RespFrame::Push(m)) if m.len() == 3));
+        let mut patterns = Vec::new();
+        for _ in 0..3 {
+            let Some(RespFrame::Push(message)) = messages.recv().await else {
+                panic!("expected a message");
+            };
+            assert_eq!(message[0], BulkString::from("pmessage").into());
+            patterns.push(message[1].clone());
+        }
+        // the last one is for the other channel
+        assert_eq!(patterns[2], BulkString::from("*").into());
+        assert_eq!(server.pubsub_numpat(), 2);
+
+        assert_eq!(
+            client.punsubscribe(&[]).len(),
+            2,
+            "a confirmation per pattern"
+        );
+        assert_eq!(
+            client.punsubscribe(&[]),
+            [confirmation("punsubscribe", None, 1)]
+        );
+        assert_eq!(server.publish(b"news.tech", b"again"), 1);
+        assert_eq!(server.pubsub_numpat(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_introspection() -> Result<()> {
+        let server = Backend::new();
+        let (a, _) = server.connect_client("a".into(), "l".into());
+        let (b, _) = server.connect_client("b".into(), "l".into());
+        let (_a_messages, _b_messages) = (a.subscriber()?, b.subscriber()?);
+        a.subscribe(&["news".into(), "sport".into()])?;
+        b.subscribe(&["news".into()])?;
+        b.psubscribe(&["n*".into()])?;
+
+        assert_eq!(server.pubsub_channels(None), ["news", "sport"]);
+        assert_eq!(server.pubsub_channels(Some(b"s*")), ["sport"]);
+        let channels = ["news".into(), "sport".into(), "none".into()];
+        assert_eq!(server.pubsub_numsub(&channels), [2, 1, 0]);
+        assert_eq!(server.pubsub_numpat(), 1);
+
+        b.disconnect_client();
+        assert_eq!(server.pubsub_numsub(&channels), [1, 1, 0]);
+        assert_eq!(server.pubsub_numpat(), 0);
+        Ok(())
+    }
+}
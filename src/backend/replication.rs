@@ -0,0 +1,588 @@
+use super::Backend;
+use crate::cmd::{Command, CommandExecutor, ReplConf, Transaction, WatchedKeys};
+use crate::{BulkString, RespArray, RespDecode, RespEncode, RespError, RespFrame};
+use bytes::BytesMut;
+use rand::Rng;
+use std::fmt::Write as _;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// The replication stream of the server, which its replicas are fed, and the primary it is a
+/// replica of, if any.
+#[derive(Debug)]
+pub(super) struct Replication {
+    // the id of the stream, which replicas are told on a full resync
+    replid: String,
+    // the bytes of the stream so far, see `propagate`
+    offset: u64,
+    // the database of the last command of the stream, a SELECT goes first when the next is for
+    // another
+    db: Option<usize>,
+    replicas: Vec<Replica>,
+    next_replica_id: u64,
+    primary: Option<Primary>,
+}
+
+impl Default for Replication {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        let replid = (0..40).fold(String::new(), |mut id, _| {
+            let _ = write!(id, "{:x}", rng.gen_range(0..16));
+            id
+        });
+        Self {
+            replid,
+            offset: 0,
+            db: None,
+            replicas: Vec::new(),
+            next_replica_id: 0,
+            primary: None,
+        }
+    }
+}
+
+impl Replication {
+    // send `command` to every replica, forgetting those that are gone
+    fn feed(&mut self, command: &[u8]) {
+        self.offset += command.len() as u64;
+        self.replicas
+            .retain(|replica| replica.sender.send(command.to_vec()).is_ok());
+    }
+
+    // feed `command`, run on database `index`
+    fn feed_on(&mut self, index: usize, command: &[u8]) {
+        // without a replica there is no one to tell the database
+        if !self.replicas.is_empty() && self.db != Some(index) {
+            self.feed(&select_command(index));
+            self.db = Some(index);
+        }
+        self.feed(command);
+    }
+}
+
+// a replica that got the snapshot, and is sent the stream from then on
+#[derive(Debug)]
+struct Replica {
+    id: u64,
+    addr: String,
+    sender: mpsc::UnboundedSender<Vec<u8>>,
+    // the offset of the stream it last said it processed
+    ack: u64,
+}
+
+// the primary of a replica, followed by a task of its own until `stop` is cancelled
+#[derive(Debug)]
+struct Primary {
+    host: String,
+    port: u16,
+    stop: CancellationToken,
+    link: Arc<PrimaryLink>,
+}
+
+// what the task following the primary tells of it
+#[derive(Debug, Default)]
+struct PrimaryLink {
+    up: AtomicBool,
+    // the offset of the stream of the primary that was processed
+    offset: AtomicU64,
+}
+
+/// What a replica that asked for a full resync is sent: the snapshot of the data as it is at
+/// `offset` of the stream, then what `commands` receives.
+#[derive(Debug)]
+pub struct FullResync {
+    pub id: u64,
+    pub replid: String,
+    pub offset: u64,
+    pub snapshot: Vec<u8>,
+    pub commands: mpsc::UnboundedReceiver<Vec<u8>>,
+}
+
+impl Backend {
+    /// Note that the write command encoded as `command` ran: the replicas are sent it, which
+    /// moves the replication offset by its length, and it is appended to the append only file if
+    /// there is one. The write holds the `sequence_lock` from running until this, for both to
+    /// get the writes in the order they ran.
+    pub fn propagate(&self, command: &[u8]) {
+        self.server
+            .replication
+            .lock()
+            .unwrap()
+            .feed_on(self.index, command);
+        self.append_aof(command);
+    }
+
+    /// Propagate the write `commands` of a transaction, each with the database it ran on, like
+    /// `propagate`. The replicas are sent them between a `MULTI` and an `EXEC`, so they run
+    /// them at once too. A transaction that wrote nothing sends nothing.
+    pub fn propagate_transaction(&self, commands: &[(Backend, Vec<u8>)]) {
+        if commands.is_empty() {
+            return;
+        }
+        {
+            let mut replication = self.server.replication.lock().unwrap();
+            replication.feed(&command(&["MULTI"]));
+            for (db, command) in commands {
+                replication.feed_on(db.index, command);
+            }
+            replication.feed(&command(&["EXEC"]));
+        }
+        for (db, command) in commands {
+            db.append_aof(command);
+        }
+    }
+
+    /// How many bytes of write commands ran since the start, reported by `INFO replication`.
+    pub fn master_repl_offset(&self) -> u64 {
+        self.server.replication.lock().unwrap().offset
+    }
+
+    /// The replicas connected to the server.
+    pub fn connected_replicas(&self) -> usize {
+        self.server.replication.lock().unwrap().replicas.len()
+    }
+
+    /// How many replicas said they processed the stream up to `offset`.
+    pub fn replicas_acked(&self, offset: u64) -> usize {
+        let replication = self.server.replication.lock().unwrap();
+        replication
+            .replicas
+            .iter()
+            .filter(|replica| replica.ack >= offset)
+            .count()
+    }
+
+    /// Ask every replica to say how much of the stream it processed, see `replica_ack`.
+    pub fn request_acks(&self) {
+        let mut replication = self.server.replication.lock().unwrap();
+        if !replication.replicas.is_empty() {
+            replication.feed(&command(&["REPLCONF", "GETACK", "*"]));
+        }
+    }
+
+    /// Snapshot the data for a new replica at `addr`, with no command running, and send it every
+    /// write command from then on. What was written before the snapshot is not sent again.
+    pub async fn add_replica(&self, addr: String) -> FullResync {
+        let _exclusive = self.execution_lock().write().await;
+        let snapshot = self.snapshot();
+        let (sender, commands) = mpsc::unbounded_channel();
+        let mut replication = self.server.replication.lock().unwrap();
+        replication.next_replica_id += 1;
+        let (id, offset) = (replication.next_replica_id, replication.offset);
+        // the replica does not know the database of the stream yet
+        replication.db = None;
+        replication.replicas.push(Replica {
+            id,
+            addr,
+            sender,
+            ack: offset,
+        });
+        FullResync {
+            id,
+            replid: replication.replid.clone(),
+            offset,
+            snapshot,
+            commands,
+        }
+    }
+
+    /// Note that replica `id` processed the stream up to `offset`.
+    pub fn replica_ack(&self, id: u64, offset: u64) {
+        let mut replication = self.server.replication.lock().unwrap();
+        if let Some(replica) = replication.replicas.iter_mut().find(|r| r.id == id) {
+            replica.ack = offset;
+        }
+    }
+
+    /// Stop feeding replica `id`, which went away.
+    pub fn remove_replica(&self, id: u64) {
+        let mut replication = self.server.replication.lock().unwrap();
+        replication.replicas.retain(|replica| replica.id != id);
+    }
+
+    /// Whether the server is the replica of a primary, and refuses writes from its clients.
+    pub fn is_replica(&self) -> bool {
+        self.server.replication.lock().unwrap().primary.is_some()
+    }
+
+    /// Replicate the primary at `host:port`, like `REPLICAOF`: its data replaces the data of the
+    /// server, then every write command it runs is run here too. `None` stops replicating and
+    /// keeps the data, like `REPLICAOF NO ONE`. Must be called from a runtime.
+    pub fn replicaof(&self, primary: Option<(String, u16)>) {
+        let mut replication = self.server.replication.lock().unwrap();
+        if let (Some(current), Some((host, port))) = (&replication.primary, &primary) {
+            if current.host == *host && current.port == *port {
+                return;
+            }
+        }
+        if let Some(previous) = replication.primary.take() {
+            previous.stop.cancel();
+            info!("Stopped replicating {}:{}", previous.host, previous.port);
+        }
+        let Some((host, port)) = primary else {
+            return;
+        };
+        let (stop, link) = (CancellationToken::new(), Arc::new(PrimaryLink::default()));
+        tokio::spawn(follow(
+            self.clone(),
+            host.clone(),
+            port,
+            link.clone(),
+            stop.clone(),
+        ));
+        replication.primary = Some(Primary {
+            host,
+            port,
+            stop,
+            link,
+        });
+    }
+
+    // the replication section of `INFO`: the role, then the primary for a replica, then the
+    // replicas
+    pub(super) fn write_replication(&self, info: &mut String) {
+        let replication = self.server.replication.lock().unwrap();
+        match &replication.primary {
+            Some(primary) => {
+                let up = primary.link.up.load(Ordering::Relaxed);
+                let _ = write!(
+                    info,
+                    "role:slave\r\nmaster_host:{}\r\nmaster_port:{}\r\nmaster_link_status:{}\r\n\
+                     slave_repl_offset:{}\r\nslave_read_only:1\r\n",
+                    primary.host,
+                    primary.port,
+                    if up { "up" } else { "down" },
+                    primary.link.offset.load(Ordering::Relaxed)
+                );
+            }
+            None => info.push_str("role:master\r\n"),
+        }
+        let _ = write!(info, "connected_slaves:{}\r\n", replication.replicas.len());
+        for (i, replica) in replication.replicas.iter().enumerate() {
+            let (ip, port) = replica
+                .addr
+                .rsplit_once(':')
+                .unwrap_or((&replica.addr, "0"));
+            let _ = write!(
+                info,
+                "slave{}:ip={},port={},state=online,offset={},lag=0\r\n",
+                i, ip, port, replica.ack
+            );
+        }
+        let _ = write!(
+            info,
+            "master_replid:{}\r\nmaster_repl_offset:{}\r\n",
+            replication.replid, replication.offset
+        );
+    }
+}
+
+// follow the primary until stopped, connecting again a second after the link is lost
+async fn follow(
+    backend: Backend,
+    host: String,
+    port: u16,
+    link: Arc<PrimaryLink>,
+    stop: CancellationToken,
+) {
+    let shutdown = backend.shutdown_token();
+    loop {
+        let result = tokio::select! {
+            _ = stop.cancelled() => return,
+            _ = shutdown.cancelled() => return,
+            result = sync_with(&backend, &host, port, &link) => result,
+        };
+        link.up.store(false, Ordering::Relaxed);
+        if let Err(e) = result {
+            warn!("Replicating {}:{} failed: {}", host, port, e);
+        }
+        tokio::select! {
+            _ = stop.cancelled() => return,
+            _ = shutdown.cancelled() => return,
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+        }
+    }
+}
+
+// the connection of a replica to its primary
+struct PrimaryConnection {
+    stream: TcpStream,
+    buf: BytesMut,
+}
+
+impl PrimaryConnection {
+    async fn send(&mut self, args: &[&str]) -> io::Result<()> {
+        self.stream.write_all(&command(args)).await
+    }
+
+    // the next frame the primary sent, and how many bytes it took
+    async fn next_frame(&mut self) -> io::Result<(RespFrame, usize)> {
+        loop {
+            let len = self.buf.len();
+            match RespFrame::decode(&mut self.buf) {
+                Ok(frame) => return Ok((frame, len - self.buf.len())),
+                Err(RespError::NotComplete) => self.fill().await?,
+                Err(e) => return Err(invalid(&e.to_string())),
+            }
+        }
+    }
+
+    // send a command of the handshake, which must not fail
+    async fn call(&mut self, args: &[&str]) -> io::Result<RespFrame> {
+        self.send(args).await?;
+        match self.next_frame().await?.0 {
+            RespFrame::Error(e) => Err(invalid(&format!("{} replied {}", args[0], e.0))),
+            reply => Ok(reply),
+        }
+    }
+
+    // the snapshot after FULLRESYNC, a bulk string without the CRLF at the end
+    async fn snapshot(&mut self) -> io::Result<Vec<u8>> {
+        let header = loop {
+            if let Some(end) = self.buf.windows(2).position(|w| w == b"\r\n") {
+                break self.buf.split_to(end + 2);
+            }
+            self.fill().await?;
+        };
+        let len = std::str::from_utf8(&header[..header.len() - 2])
+            .ok()
+            .and_then(|header| header.strip_prefix('$'))
+            .and_then(|len| len.parse::<usize>().ok())
+            .ok_or_else(|| invalid("the snapshot has no length"))?;
+        while self.buf.len() < len {
+            self.fill().await?;
+        }
+        Ok(self.buf.split_to(len).to_vec())
+    }
+
+    async fn fill(&mut self) -> io::Result<()> {
+        if self.stream.read_buf(&mut self.buf).await? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the primary closed the connection",
+            ));
+        }
+        Ok(())
+    }
+}
+
+// a full resync with the primary, then the commands of its stream until the link is lost
+async fn sync_with(backend: &Backend, host: &str, port: u16, link: &PrimaryLink) -> io::Result<()> {
+    let stream = TcpStream::connect((host, port)).await?;
+    let mut primary = PrimaryConnection {
+        stream,
+        buf: BytesMut::new(),
+    };
+    primary.call(&["PING"]).await?;
+    let own_port = backend.config().port.to_string();
+    primary
+        .call(&["REPLCONF", "listening-port", &own_port])
+        .await?;
+    primary.call(&["REPLCONF", "capa", "psync2"]).await?;
+    // never a partial resync, there is no backlog to resume from
+    let reply = primary.call(&["PSYNC", "?", "-1"]).await?;
+    let mut offset = match &reply {
+        RespFrame::SimpleString(s) => s
+            .strip_prefix("FULLRESYNC ")
+            .and_then(|s| s.split(' ').nth(1))
+            .and_then(|offset| offset.parse::<u64>().ok()),
+        _ => None,
+    }
+    .ok_or_else(|| invalid(&format!("PSYNC replied {:?}", reply)))?;
+    let snapshot = primary.snapshot().await?;
+    let keys = {
+        let _exclusive = backend.execution_lock().write().await;
+        backend.flush_all(false);
+        backend.load_snapshot(&snapshot)?
+    };
+    info!(
+        "Full resync with {}:{} done, {} keys loaded",
+        host, port, keys
+    );
+    link.offset.store(offset, Ordering::Relaxed);
+    link.up.store(true, Ordering::Relaxed);
+
+    let mut db = backend.select(0).map_err(|e| invalid(&e.to_string()))?;
+    let mut acks = tokio::time::interval(Duration::from_secs(1));
+    let mut transaction: Option<Transaction> = None;
+    loop {
+        let (frame, len) = tokio::select! {
+            frame = primary.next_frame() => frame?,
+            _ = acks.tick() => {
+                primary.send(&["REPLCONF", "ACK", &offset.to_string()]).await?;
+                continue;
+            }
+        };
+        offset += len as u64;
+        link.offset.store(offset, Ordering::Relaxed);
+        let propagated = Command::propagated(&frame);
+        let written = Command::written_keys(&frame);
+        match Command::try_from(frame) {
+            Ok(Command::ReplConf(ReplConf { getack: true, .. })) => {
+                primary
+                    .send(&["REPLCONF", "ACK", &offset.to_string()])
+                    .await?;
+            }
+            // a transaction of the primary runs at once here too
+            Ok(Command::Multi(_)) => transaction = Some(Transaction::default()),
+            Ok(Command::Exec(_)) => {
+                let Some(queued) = transaction.take() else {
+                    continue;
+                };
+                match queued.exec(&db, &WatchedKeys::default()).await {
+                    Ok((_, selected)) => db = selected,
+                    Err(e) => warn!("A transaction of the primary failed here: {}", e),
+                }
+            }
+            Ok(cmd) if transaction.is_some() => {
+                if let Some(transaction) = transaction.as_mut() {
+                    transaction.queue(cmd, written, propagated);
+                }
+            }
+            Ok(Command::Select(select)) => {
+                db = backend
+                    .select(select.index)
+                    .map_err(|e| invalid(&e.to_string()))?
+            }
+            Ok(cmd) => {
+                let _shared = backend.execution_lock().read().await;
+                let _sequence = backend.sequence_lock().lock().await;
+                let reply = cmd.execute(&db);
+                db.signal_modified(&written);
+                match reply {
+                    // what the primary ran is on the replicas and in the file of this one too
+                    Ok(reply) => {
+                        if let Some(command) = propagated {
+                            command.propagate(&db, &reply);
+                        }
+                    }
+                    Err(e) => warn!("A command of the primary failed here: {}", e),
+                }
+            }
+            Err(e) => warn!("Can't run a command of the primary: {}", e),
+        }
+    }
+}
+
+// the bytes of a command with the given arguments
+fn command(args: &[&str]) -> Vec<u8> {
+    let args = args
+        .iter()
+        .map(|arg| BulkString::from(*arg).into())
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(args).encode()
+}
+
+fn select_command(index: usize) -> Vec<u8> {
+    command(&["SELECT", &index.to_string()])
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{request_args, run_args};
+
+    #[tokio::test]
+    async fn test_offset_grows_with_writes() {
+        let backend = Backend::new();
+        assert_eq!(backend.master_repl_offset(), 0);
+        let set = command(&["set", "key", "value"]);
+        backend.propagate(&set);
+        // on every database, it is the offset of the server
+        backend.select(1).unwrap().propagate(&set);
+        assert_eq!(backend.master_repl_offset(), 2 * set.len() as u64);
+
+        // a replica is sent the stream from its snapshot on, told the database first
+        backend.set("key".into(), BulkString::from("value").into());
+        let mut resync = backend.add_replica("127.0.0.1:6380".into()).await;
+        assert_eq!(resync.offset, backend.master_repl_offset());
+        assert_eq!(backend.connected_replicas(), 1);
+        backend.propagate(&set);
+        let select = select_command(0);
+        assert_eq!(resync.commands.recv().await, Some(select.clone()));
+        assert_eq!(resync.commands.recv().await, Some(set.clone()));
+        let offset = resync.offset + (select.len() + set.len()) as u64;
+        assert_eq!(backend.master_repl_offset(), offset);
+
+        assert_eq!(backend.replicas_acked(offset), 0);
+        backend.replica_ack(resync.id, offset);
+        assert_eq!(backend.replicas_acked(offset), 1);
+        drop(resync);
+        // a replica that went away is forgotten with the next command
+        backend.propagate(&set);
+        assert_eq!(backend.connected_replicas(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_pops_follow_their_push() {
+        let backend = Backend::new();
+        let mut resync = backend.add_replica("127.0.0.1:6380".into()).await;
+        let blpop = |args: &[&str]| {
+            let (backend, frame) = (backend.clone(), request_args(args).into());
+            tokio::spawn(async move {
+                let cmd = Command::from_request(frame, &backend).unwrap();
+                cmd.execute_async(&backend).await.unwrap()
+            })
+        };
+        // one that times out sends nothing
+        assert_eq!(
+            blpop(&["blpop", "list", "0.01"]).await.unwrap(),
+            RespFrame::NULL_ARRAY
+        );
+        let waiting = blpop(&["brpop", "list", "0"]);
+        while backend.blocked.is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        run_args(&backend, &["lpush", "list", "a", "b"]).unwrap();
+        waiting.await.unwrap();
+        // one that needs not wait pops right away
+        blpop(&["blpop", "list", "0"]).await.unwrap();
+        for expected in [
+            select_command(0),
+            command(&["lpush", "list", "a", "b"]),
+            command(&["RPOP", "list"]),
+            command(&["LPOP", "list"]),
+        ] {
+            assert_eq!(resync.commands.recv().await, Some(expected));
+        }
+        assert!(resync.commands.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_is_sent_whole() {
+        let backend = Backend::new();
+        let mut resync = backend.add_replica("127.0.0.1:6380".into()).await;
+        let (set, incr) = (command(&["set", "key", "1"]), command(&["incr", "key"]));
+        let other = backend.select(2).unwrap();
+        backend.propagate_transaction(&[
+            (backend.clone(), set.clone()),
+            (other.clone(), incr.clone()),
+        ]);
+        // nothing for a transaction that wrote nothing
+        backend.propagate_transaction(&[]);
+        backend.propagate(&set);
+        for expected in [
+            command(&["MULTI"]),
+            select_command(0),
+            set.clone(),
+            select_command(2),
+            incr,
+            command(&["EXEC"]),
+            select_command(0),
+            set,
+        ] {
+            assert_eq!(resync.commands.recv().await, Some(expected));
+        }
+    }
+}
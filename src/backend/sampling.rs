@@ -0,0 +1,73 @@
+use dashmap::RwLock;
+use rand::{seq::SliceRandom, Rng};
+
+// pick `count` random entries of a map or set given by its shards, distinct ones or exactly `count`
+// with repeats. The shards stay read locked while sampling so the picks are consistent with the
+// length, and only the picked entries are turned into owned values by `pick`.
+pub(crate) fn sample<M, K, V, T>(
+    shards: &[RwLock<M>],
+    count: usize,
+    distinct: bool,
+    pick: impl Fn(&K, &V) -> T,
+) -> Vec<T>
+where
+    for<'a> &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: 'static,
+    V: 'static,
+{
+    let shards = shards.iter().map(|s| s.read()).collect::<Vec<_>>();
+    let len = shards
+        .iter()
+        .map(|s| (&**s).into_iter().count())
+        .sum::<usize>();
+    let entries = shards.iter().flat_map(|s| &**s);
+    sample_iter(entries, len, count, distinct)
+        .into_iter()
+        .map(|(k, v)| pick(k, v))
+        .collect()
+}
+
+// the same as `sample` for the `len` items of an iterator
+pub(crate) fn sample_iter<T: Clone>(
+    entries: impl Iterator<Item = T>,
+    len: usize,
+    count: usize,
+    distinct: bool,
+) -> Vec<T> {
+    let mut rng = rand::thread_rng();
+    if len == 0 {
+        return Vec::new();
+    }
+
+    let mut picked = Vec::with_capacity(count.min(if distinct { len } else { count }));
+    if distinct {
+        // reservoir sampling: entry `i` replaces one of the picks with probability count / (i + 1)
+        for (i, entry) in entries.enumerate() {
+            if i < count {
+                picked.push(entry);
+            } else {
+                let j = rng.gen_range(0..=i);
+                if j < count {
+                    picked[j] = entry;
+                }
+            }
+        }
+    } else {
+        let mut indices = (0..count)
+            .map(|_| rng.gen_range(0..len))
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        let mut indices = indices.into_iter().peekable();
+        for (i, entry) in entries.enumerate() {
+            while indices.next_if_eq(&i).is_some() {
+                picked.push(entry.clone());
+            }
+            if indices.peek().is_none() {
+                break;
+            }
+        }
+    }
+    // neither the reservoir nor the walk in index order leave the picks in a random order
+    picked.shuffle(&mut rng);
+    picked
+}
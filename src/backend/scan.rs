@@ -0,0 +1,268 @@
+use super::{glob::glob_match, Backend, KeyType};
+use crate::{cmd::CommandError, RespFrame};
+use bytes::Bytes;
+use dashmap::RwLock;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+// a cursor is the index of a shard in the high bits and a position in the hash space of that shard
+// in the low bits. Keys are visited in the order of their position, which does not depend on the
+// other keys, so a key present for the whole scan is always reached no matter what is inserted or
+// removed in the meantime.
+const POSITION_BITS: u32 = 48;
+const POSITION_MASK: u64 = (1 << POSITION_BITS) - 1;
+
+impl Backend {
+    /// One step of a `SCAN`: up to about `count` keys from `cursor` on, keeping the ones matching
+    /// `pattern`, and the cursor to continue from. A returned cursor of 0 ends the iteration.
+    pub fn scan(&self, cursor: u64, pattern: Option<&[u8]>, count: usize) -> (u64, Vec<Bytes>) {
+        let (cursor, mut keys) = scan_shards(self.entries.shards(), cursor, pattern, count);
+        keys.retain(|key| !self.expire_if_needed(key));
+        (cursor, keys)
+    }
+
+    /// One step of a `HSCAN` over the fields of the hash at `key`, with the same cursor scheme as
+    /// `scan`. A missing key is an empty hash.
+    pub fn hscan(
+        &self,
+        key: &[u8],
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: usize,
+    ) -> Result<(u64, Vec<(Bytes, RespFrame)>), CommandError> {
+        self.check_type(key, KeyType::Hash)?;
+        let Some(hash) = self.shared_hash(key) else {
+            return Ok((0, Vec::new()));
+        };
+        let (cursor, fields) = scan_shards(hash.shards(), cursor, pattern, count);
+        // a field removed since it was visited is simply left out
+        let entries = fields
+            .into_iter()
+            .filter_map(|field| {
+                let value = hash.get(&field)?.clone();
+                Some((field, value))
+            })
+            .collect();
+        Ok((cursor, entries))
+    }
+
+    /// One step of a `SSCAN` over the members of the set at `key`, with the same cursor scheme as
+    /// `scan`. A missing key is an empty set.
+    pub fn sscan(
+        &self,
+        key: &[u8],
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: usize,
+    ) -> Result<(u64, Vec<Bytes>), CommandError> {
+        self.check_type(key, KeyType::Set)?;
+        Ok(match self.sets().get(key) {
+            Some(set) => scan_shards(set.shards(), cursor, pattern, count),
+            None => (0, Vec::new()),
+        })
+    }
+
+    /// One step of a `ZSCAN` over the members and scores of the sorted set at `key`, with the same
+    /// cursor scheme as `scan`. A sorted set is a single shard. A missing key is an empty set.
+    pub fn zscan(
+        &self,
+        key: &[u8],
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: usize,
+    ) -> Result<(u64, Vec<(Bytes, f64)>), CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        let Some(zset) = self.zsets().get(key) else {
+            return Ok((0, Vec::new()));
+        };
+        if cursor >> POSITION_BITS != 0 {
+            return Ok((0, Vec::new()));
+        }
+        let mut members = Vec::new();
+        let next = scan_map(zset.scores(), cursor, count, &mut members);
+        if let Some(pattern) = pattern {
+            members.retain(|member| glob_match(pattern, member));
+        }
+        let entries = members
+            .into_iter()
+            .filter_map(|member| {
+                let score = zset.score(&member)?;
+                Some((member, score))
+            })
+            .collect();
+        Ok((next.unwrap_or(0), entries))
+    }
+}
+
+// walk the shards of a single map from `cursor` on, the way `scan` walks all of them
+fn scan_shards<M, V>(
+    shards: &[RwLock<M>],
+    cursor: u64,
+    pattern: Option<&[u8]>,
+    count: usize,
+) -> (u64, Vec<Bytes>)
+where
+    for<'a> &'a M: IntoIterator<Item = (&'a Bytes, &'a V)>,
+    V: 'static,
+{
+    let mut shard = (cursor >> POSITION_BITS) as usize;
+    let mut from = cursor & POSITION_MASK;
+    let mut keys = Vec::new();
+    while shard < shards.len() && keys.len() < count {
+        match scan_shard(&shards[shard], from, count - keys.len(), &mut keys) {
+            Some(position) => from = position,
+            None => {
+                shard += 1;
+                from = 0;
+            }
+        }
+    }
+
+    if let Some(pattern) = pattern {
+        keys.retain(|key| glob_match(pattern, key));
+    }
+    let cursor = if shard < shards.len() {
+        (shard as u64) << POSITION_BITS | from
+    } else {
+        0
+    };
+    (cursor, keys)
+}
+
+fn position(key: &[u8]) -> u64 {
+    // `DefaultHasher::new` always uses the same keys, so positions are stable across calls
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish() & POSITION_MASK
+}
+
+// collect the keys of a shard at or after `from`, lowest positions first. Returns where to continue
+// in this shard, or `None` if it was exhausted.
+fn scan_shard<M, V>(
+    shard: &RwLock<M>,
+    from: u64,
+    count: usize,
+    keys: &mut Vec<Bytes>,
+) -> Option<u64>
+where
+    for<'a> &'a M: IntoIterator<Item = (&'a Bytes, &'a V)>,
+    V: 'static,
+{
+    scan_map(&*shard.read(), from, count, keys)
+}
+
+// the same as `scan_shard` on a map that is not behind a lock
+fn scan_map<M, V>(map: &M, from: u64, count: usize, keys: &mut Vec<Bytes>) -> Option<u64>
+where
+    for<'a> &'a M: IntoIterator<Item = (&'a Bytes, &'a V)>,
+    V: 'static,
+{
+    let mut candidates = map
+        .into_iter()
+        .map(|(key, _)| (position(key), key))
+        .filter(|(position, _)| *position >= from)
+        .collect::<Vec<_>>();
+    if candidates.len() <= count {
+        keys.extend(candidates.into_iter().map(|(_, key)| key.clone()));
+        return None;
+    }
+
+    candidates.sort_unstable_by_key(|(position, _)| *position);
+    let last = candidates[count - 1].0;
+    // keys sharing the position of the last one can't be told apart by the cursor, take them all
+    keys.extend(
+        candidates
+            .into_iter()
+            .take_while(|(position, _)| *position <= last)
+            .map(|(_, key)| key.clone()),
+    );
+    (last < POSITION_MASK).then_some(last + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_scan_visits_every_key_once() {
+        let backend = Backend::new();
+        for i in 0..1000 {
+            backend.set(format!("key{}", i).into(), BulkString::from("value").into());
+        }
+        backend
+            .hset(
+                "hash".into(),
+                "field".into(),
+                BulkString::from("value").into(),
+            )
+            .unwrap();
+        backend.sadd("set", "member").unwrap();
+
+        let mut seen = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (next, keys) = backend.scan(cursor, None, 10);
+            seen.extend(keys);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        assert_eq!(seen.len(), 1002);
+        assert_eq!(seen.iter().collect::<HashSet<_>>().len(), 1002);
+    }
+
+    #[test]
+    fn test_scan_match() {
+        let backend = Backend::new();
+        for i in 0..100 {
+            backend.set(
+                format!("user:{}", i).into(),
+                BulkString::from("value").into(),
+            );
+            backend.set(
+                format!("item:{}", i).into(),
+                BulkString::from("value").into(),
+            );
+        }
+
+        let (cursor, keys) = backend.scan(0, Some(b"user:*"), 1000);
+        assert_eq!(cursor, 0);
+        assert_eq!(keys.len(), 100);
+        assert!(keys.iter().all(|key| key.starts_with(b"user:")));
+    }
+
+    #[test]
+    fn test_sscan_with_concurrent_changes() {
+        let backend = Backend::new();
+        for i in 0..1000 {
+            backend.sadd("set", format!("stable{}", i)).unwrap();
+        }
+
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        let mut step = 0;
+        loop {
+            // churn the set between every step of the iteration
+            backend.sadd("set", format!("added{}", step)).unwrap();
+            backend
+                .sets()
+                .get(b"set")
+                .unwrap()
+                .remove(format!("added{}", step / 2).as_bytes());
+            let (next, members) = backend.sscan(b"set", cursor, Some(b"stable*"), 25).unwrap();
+            seen.extend(members);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+            step += 1;
+        }
+        assert_eq!(seen.len(), 1000);
+
+        assert_eq!(backend.sscan(b"missing", 0, None, 10).unwrap(), (0, vec![]));
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(backend.sscan(b"string", 0, None, 10).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use super::Backend;
+use std::sync::Arc;
+
+impl Backend {
+    /// Cache `script` for `EVALSHA`, returning the SHA1 digest it is known by.
+    pub fn script_load(&self, script: &str) -> String {
+        let sha = sha1_smol::Sha1::from(script).digest().to_string();
+        self.server
+            .scripts
+            .entry(sha.clone())
+            .or_insert_with(|| script.into());
+        sha
+    }
+
+    /// The cached script of digest `sha`, which like redis is not case sensitive.
+    pub fn script(&self, sha: &str) -> Option<Arc<str>> {
+        let sha = sha.to_ascii_lowercase();
+        self.server.scripts.get(&sha).map(|script| script.clone())
+    }
+
+    /// Whether each of `shas` is the digest of a cached script.
+    pub fn script_exists(&self, shas: &[String]) -> Vec<bool> {
+        shas.iter().map(|sha| self.script(sha).is_some()).collect()
+    }
+
+    /// Forget every cached script.
+    pub fn script_flush(&self) {
+        self.server.scripts.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_cache() {
+        let backend = Backend::new();
+        let sha = backend.script_load("return 1");
+        // the digest redis gives the same script
+        assert_eq!(sha, "e0e1f9fabfc9d4800c877a703b823ac0578ff8db");
+        assert_eq!(backend.script_load("return 1"), sha);
+        assert_eq!(
+            backend.script(&sha.to_uppercase()).as_deref(),
+            Some("return 1")
+        );
+        assert_eq!(
+            backend.script_exists(&[sha.clone(), "nope".into()]),
+            [true, false]
+        );
+        backend.script_flush();
+        assert_eq!(backend.script(&sha), None);
+    }
+}
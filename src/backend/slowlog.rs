@@ -0,0 +1,161 @@
+use super::Backend;
+use crate::{RespArray, RespFrame};
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// like redis, the arguments kept of a logged command
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+/// A command that took longer than `slowlog-log-slower-than`, as listed by `SLOWLOG GET`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowLogEntry {
+    pub id: u64,
+    /// When the command ran, in unix seconds.
+    pub timestamp: u64,
+    pub duration: Duration,
+    /// The arguments of the command, the name included, cut short if there are too many or
+    /// they are too long.
+    pub args: Vec<Vec<u8>>,
+    pub client_addr: String,
+    pub client_name: String,
+}
+
+// the newest entries first, and the id of the next one
+#[derive(Debug, Default)]
+pub(super) struct SlowLog {
+    entries: VecDeque<SlowLogEntry>,
+    next_id: u64,
+}
+
+impl Backend {
+    /// Whether commands are timed for the slow log at all.
+    pub fn slowlog_enabled(&self) -> bool {
+        self.config().slowlog_log_slower_than >= 0
+    }
+
+    /// Log the command in `args` if it took more than `slowlog-log-slower-than`.
+    pub fn slowlog_record(&self, args: &RespArray, duration: Duration) {
+        let (threshold, max_len) = {
+            let config = self.config();
+            (config.slowlog_log_slower_than, config.slowlog_max_len)
+        };
+        if threshold < 0 || duration.as_micros() < threshold as u128 {
+            return;
+        }
+
+        let mut kept = args
+            .iter()
+            .take(if args.len() > MAX_ARGS {
+                MAX_ARGS - 1
+            } else {
+                MAX_ARGS
+            })
+            .map(|arg| match arg {
+                RespFrame::BulkString(arg) if arg.len() > MAX_ARG_LEN => {
+                    let mut cut = arg[..MAX_ARG_LEN].to_vec();
+                    let more = format!("... ({} more bytes)", arg.len() - MAX_ARG_LEN);
+                    cut.extend_from_slice(more.as_bytes());
+                    cut
+                }
+                RespFrame::BulkString(arg) => arg.to_vec(),
+                frame => format!("{:?}", frame).into_bytes(),
+            })
+            .collect::<Vec<_>>();
+        if args.len() > MAX_ARGS {
+            let more = format!("... ({} more arguments)", args.len() - MAX_ARGS + 1);
+            kept.push(more.into_bytes());
+        }
+        let (client_addr, client_name) = self
+            .client_info()
+            .map(|info| (info.addr, info.name))
+            .unwrap_or_default();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut slowlog = self.server.slowlog.lock().unwrap();
+        let id = slowlog.next_id;
+        slowlog.next_id += 1;
+        slowlog.entries.push_front(SlowLogEntry {
+            id,
+            timestamp,
+            duration,
+            args: kept,
+            client_addr,
+            client_name,
+        });
+        slowlog.entries.truncate(max_len);
+    }
+
+    /// The `count` newest entries of the slow log, all of them if `None`.
+    pub fn slowlog_get(&self, count: Option<usize>) -> Vec<SlowLogEntry> {
+        let slowlog = self.server.slowlog.lock().unwrap();
+        let count = count.unwrap_or(slowlog.entries.len());
+        slowlog.entries.iter().take(count).cloned().collect()
+    }
+
+    pub fn slowlog_len(&self) -> usize {
+        self.server.slowlog.lock().unwrap().entries.len()
+    }
+
+    /// Empty the slow log, the ids keep counting up.
+    pub fn slowlog_reset(&self) {
+        self.server.slowlog.lock().unwrap().entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::request_args;
+
+    #[test]
+    fn test_slowlog_threshold_and_length() {
+        let backend = Backend::new();
+        let fast = Duration::from_micros(100);
+        let slow = Duration::from_millis(20);
+        backend.slowlog_record(&request_args(&["get", "a"]), fast);
+        backend.slowlog_record(&request_args(&["get", "b"]), slow);
+        assert_eq!(backend.slowlog_len(), 1);
+        assert_eq!(
+            backend.slowlog_get(None)[0].args,
+            [b"get".to_vec(), b"b".to_vec()]
+        );
+
+        let max_len = [("slowlog-max-len".into(), "2".into())];
+        backend.config_set(&max_len).unwrap();
+        backend.slowlog_record(&request_args(&["get", "c"]), slow);
+        backend.slowlog_record(&request_args(&["get", "d"]), slow);
+        let ids = backend
+            .slowlog_get(None)
+            .iter()
+            .map(|entry| entry.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [2, 1]);
+        assert_eq!(backend.slowlog_get(Some(1)).len(), 1);
+
+        backend.slowlog_reset();
+        assert_eq!(backend.slowlog_len(), 0);
+        backend.slowlog_record(&request_args(&["get", "e"]), slow);
+        assert_eq!(backend.slowlog_get(None)[0].id, 3);
+    }
+
+    #[test]
+    fn test_slowlog_truncates_arguments() {
+        let backend = Backend::new();
+        let long = "x".repeat(200);
+        let mut many = vec!["rpush", "list"];
+        many.extend(std::iter::repeat_n(long.as_str(), 40));
+        backend.slowlog_record(&request_args(&many), Duration::from_secs(1));
+
+        let entry = &backend.slowlog_get(None)[0];
+        assert_eq!(entry.args.len(), 32);
+        assert_eq!(
+            entry.args[2],
+            format!("{}... (72 more bytes)", "x".repeat(128)).into_bytes()
+        );
+        assert_eq!(entry.args[31], b"... (11 more arguments)");
+    }
+}
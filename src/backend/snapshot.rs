@@ -0,0 +1,723 @@
+use super::{expire::unix_millis, string_bytes, Backend, Stream, Value, ZSet};
+use crate::{cmd::CommandError, BulkString, RespDecode, RespEncode, RespFrame, SimpleString};
+use bytes::{Bytes, BytesMut};
+use dashmap::{DashMap, DashSet};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// a snapshot starts with the magic and the version of the format, then has the keys of each
+// database that has any after a `DATABASE` tag, and ends with an `END` tag:
+// - an entry is the tag of its type, its key, its expiry as unix milliseconds or -1, its value
+// - a length is 8 bytes, numbers are little endian, strings and collections are length-prefixed
+const MAGIC: &[u8] = b"SIMPLE-REDIS";
+const VERSION: u8 = 1;
+
+const STRING: u8 = 0;
+const LIST: u8 = 1;
+const SET: u8 = 2;
+const HASH: u8 = 3;
+const ZSET: u8 = 4;
+const STREAM: u8 = 5;
+const DATABASE: u8 = 0xFE;
+const END: u8 = 0xFF;
+
+// a `DUMP` payload is a value as in a snapshot, its tag first, then the version as 2 bytes and
+// the CRC-64 of everything before as 8
+const DUMP_VERSION: u16 = VERSION as u16;
+const DUMP_FOOTER: usize = 2 + 8;
+
+// how a value of a string, a list or a hash is written: most are bulk strings, and any other
+// frame is kept in its RESP encoding
+const FRAME_BULK: u8 = b'$';
+const FRAME_INTEGER: u8 = b':';
+const FRAME_SIMPLE: u8 = b'+';
+const FRAME_RESP: u8 = b'*';
+
+/// Builds the bytes of a snapshot.
+#[derive(Debug, Default)]
+pub(super) struct Writer(Vec<u8>);
+
+impl Writer {
+    pub(super) fn u8(&mut self, n: u8) {
+        self.0.push(n);
+    }
+
+    pub(super) fn u64(&mut self, n: u64) {
+        self.0.extend_from_slice(&n.to_le_bytes());
+    }
+
+    pub(super) fn i64(&mut self, n: i64) {
+        self.0.extend_from_slice(&n.to_le_bytes());
+    }
+
+    pub(super) fn f64(&mut self, n: f64) {
+        self.0.extend_from_slice(&n.to_le_bytes());
+    }
+
+    pub(super) fn len(&mut self, len: usize) {
+        self.u64(len as u64);
+    }
+
+    pub(super) fn bytes(&mut self, bytes: &[u8]) {
+        self.len(bytes.len());
+        self.0.extend_from_slice(bytes);
+    }
+
+    // the tag of the type of `value`, then the value
+    fn value(&mut self, value: &Value) {
+        self.u8(tag(value));
+        self.contents(value);
+    }
+
+    // a value without its tag. A string is written as the bulk string frame it once was, so that
+    // the snapshots written before keep loading
+    fn contents(&mut self, value: &Value) {
+        match value {
+            Value::Str(s) => {
+                self.u8(FRAME_BULK);
+                self.bytes(s);
+            }
+            Value::List(list) => self.list(list),
+            Value::Set(set) => self.set(set),
+            Value::Hash(hash) => self.hash(hash),
+            Value::ZSet(zset) => self.zset(zset),
+            Value::Stream(stream) => stream.write_snapshot(self),
+        }
+    }
+
+    fn list(&mut self, list: &VecDeque<RespFrame>) {
+        self.len(list.len());
+        list.iter().for_each(|value| self.frame(value));
+    }
+
+    fn set(&mut self, set: &DashSet<Bytes>) {
+        self.len(set.len());
+        set.iter().for_each(|member| self.bytes(&member));
+    }
+
+    fn hash(&mut self, hash: &DashMap<Bytes, RespFrame>) {
+        self.len(hash.len());
+        for field in hash.iter() {
+            self.bytes(field.key());
+            self.frame(field.value());
+        }
+    }
+
+    fn zset(&mut self, zset: &ZSet) {
+        self.len(zset.len());
+        for (member, score) in zset.iter() {
+            self.bytes(member);
+            self.f64(score);
+        }
+    }
+
+    pub(super) fn frame(&mut self, frame: &RespFrame) {
+        match frame {
+            RespFrame::BulkString(s) => {
+                self.u8(FRAME_BULK);
+                self.bytes(s);
+            }
+            RespFrame::Integer(n) => {
+                self.u8(FRAME_INTEGER);
+                self.i64(*n);
+            }
+            RespFrame::SimpleString(s) => {
+                self.u8(FRAME_SIMPLE);
+                self.bytes(s.as_bytes());
+            }
+            frame => {
+                self.u8(FRAME_RESP);
+                self.bytes(&frame.clone().encode());
+            }
+        }
+    }
+}
+
+/// Reads the bytes of a snapshot, failing with `InvalidData` on anything unexpected.
+#[derive(Debug)]
+pub(super) struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid("the snapshot is truncated"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    pub(super) fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(super) fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub(super) fn i64(&mut self) -> io::Result<i64> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    pub(super) fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    // a length is never more than what is left to read, which a corrupted one could be
+    pub(super) fn len(&mut self) -> io::Result<usize> {
+        let len = self.u64()?;
+        match usize::try_from(len) {
+            Ok(len) if len <= self.0.len() => Ok(len),
+            _ => Err(invalid("a length is out of range")),
+        }
+    }
+
+    pub(super) fn bytes(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.len()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    pub(super) fn string(&mut self) -> io::Result<String> {
+        String::from_utf8(self.bytes()?).map_err(|_| invalid("a string is not valid UTF-8"))
+    }
+
+    pub(super) fn frame(&mut self) -> io::Result<RespFrame> {
+        match self.u8()? {
+            FRAME_BULK => Ok(BulkString::new(self.bytes()?).into()),
+            FRAME_INTEGER => Ok(RespFrame::Integer(self.i64()?)),
+            FRAME_SIMPLE => Ok(SimpleString::new(self.string()?).into()),
+            FRAME_RESP => {
+                let mut buf = BytesMut::from(self.bytes()?.as_slice());
+                RespFrame::decode(&mut buf).map_err(|_| invalid("a value is not valid RESP"))
+            }
+            _ => Err(invalid("unknown value encoding")),
+        }
+    }
+
+    // the value of the type of `tag`, written by `Writer::value` after it
+    fn value(&mut self, tag: u8) -> io::Result<Value> {
+        Ok(match tag {
+            STRING => Value::Str(string_bytes(self.frame()?)),
+            LIST => Value::List(
+                (0..self.len()?)
+                    .map(|_| self.frame())
+                    .collect::<io::Result<_>>()?,
+            ),
+            SET => {
+                let set = DashSet::new();
+                for _ in 0..self.len()? {
+                    set.insert(self.bytes()?.into());
+                }
+                Value::Set(set)
+            }
+            HASH => {
+                let hash = DashMap::new();
+                for _ in 0..self.len()? {
+                    hash.insert(self.bytes()?.into(), self.frame()?);
+                }
+                Value::Hash(hash.into())
+            }
+            ZSET => {
+                let mut zset = ZSet::new();
+                for _ in 0..self.len()? {
+                    zset.insert(self.bytes()?.into(), self.f64()?);
+                }
+                Value::ZSet(zset)
+            }
+            STREAM => Value::Stream(Stream::read_snapshot(self)?),
+            _ => return Err(invalid("unknown value type")),
+        })
+    }
+}
+
+// the tag of the type of `value`
+fn tag(value: &Value) -> u8 {
+    match value {
+        Value::Str(_) => STRING,
+        Value::List(_) => LIST,
+        Value::Set(_) => SET,
+        Value::Hash(_) => HASH,
+        Value::ZSet(_) => ZSET,
+        Value::Stream(_) => STREAM,
+    }
+}
+
+// the CRC-64 redis checks its payloads with, the reflected Jones polynomial
+fn crc64(bytes: &[u8]) -> u64 {
+    const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+    let mut crc = 0;
+    for &byte in bytes {
+        crc ^= byte as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Backend {
+    /// Where `SAVE` and `BGSAVE` write the snapshot: `dbfilename` in `dir`.
+    pub fn dump_path(&self) -> PathBuf {
+        let config = self.config();
+        Path::new(&config.dir).join(&config.dbfilename)
+    }
+
+    /// Write the snapshot of every database to `dump_path` now, like `SAVE`.
+    pub fn save(&self) -> Result<(), CommandError> {
+        if self.bgsave_in_progress() {
+            return Err(CommandError::BgSaveInProgress);
+        }
+        self.save_to(&self.dump_path())
+            .map_err(|e| CommandError::SaveFailed(e.to_string()))?;
+        self.server.metrics.saved();
+        Ok(())
+    }
+
+    /// Write the snapshot to `dump_path` in the background, like `BGSAVE`. The data is copied with
+    /// no command running, the file is written while they run again.
+    pub fn bgsave(&self) -> Result<(), CommandError> {
+        if self.server.bgsave_running.swap(true, Ordering::SeqCst) {
+            return Err(CommandError::BgSaveInProgress);
+        }
+        let backend = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let snapshot = {
+                let _exclusive = backend.execution_lock().blocking_write();
+                backend.snapshot()
+            };
+            let path = backend.dump_path();
+            let result = write_atomically(&path, &snapshot);
+            match &result {
+                Ok(()) => {
+                    info!("Background saving terminated with success");
+                    backend.server.metrics.saved();
+                }
+                Err(e) => warn!("Background saving of {} failed: {}", path.display(), e),
+            }
+            let server = &backend.server;
+            server
+                .bgsave_failed
+                .store(result.is_err(), Ordering::Relaxed);
+            server.bgsave_running.store(false, Ordering::SeqCst);
+        });
+        Ok(())
+    }
+
+    /// Whether a `BGSAVE` is writing the snapshot.
+    pub fn bgsave_in_progress(&self) -> bool {
+        self.server.bgsave_running.load(Ordering::SeqCst)
+    }
+
+    /// Whether the last `BGSAVE` failed, false until one ran.
+    pub fn last_bgsave_failed(&self) -> bool {
+        self.server.bgsave_failed.load(Ordering::Relaxed)
+    }
+
+    /// Write the snapshot of every database to `path`, through a temporary file renamed over it
+    /// so that the file is always a whole snapshot. Expired keys are left out.
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        write_atomically(path, &self.snapshot())
+    }
+
+    /// Load the keys of the snapshot at `path` into their databases, replacing any key of the
+    /// same name, returning how many were loaded. Keys expired since are left out.
+    pub fn load_from(&self, path: &Path) -> io::Result<usize> {
+        self.load_snapshot(&std::fs::read(path)?)
+    }
+
+    /// Load the keys of the snapshot `bytes` like `load_from`, for a replica given it by its
+    /// primary.
+    pub fn load_snapshot(&self, bytes: &[u8]) -> io::Result<usize> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a snapshot"));
+        }
+        if reader.u8()? != VERSION {
+            return Err(invalid("unknown snapshot version"));
+        }
+        let (now, now_ms) = (Instant::now(), unix_millis());
+        let (mut db, mut loaded) = (self.select(0).map_err(|_| invalid("no database"))?, 0);
+        loop {
+            let tag = reader.u8()?;
+            match tag {
+                END => break,
+                DATABASE => {
+                    let index = reader.u64()?;
+                    db = i64::try_from(index)
+                        .ok()
+                        .and_then(|index| self.select(index).ok())
+                        .ok_or_else(|| invalid("a database is out of range"))?;
+                    continue;
+                }
+                _ => {}
+            }
+            let key = Bytes::from(reader.bytes()?);
+            let expiry = reader.i64()?;
+            let value = reader.value(tag)?;
+            db.remove_key(&key);
+            db.put_value(key.clone(), value);
+            match expiry {
+                -1 => loaded += 1,
+                expiry if expiry <= now_ms => db.remove_key(&key),
+                expiry => {
+                    let ttl = Duration::from_millis((expiry - now_ms) as u64);
+                    if let Some(mut entry) = db.entries.get_mut(&key) {
+                        entry.expires_at = now.checked_add(ttl);
+                    }
+                    loaded += 1;
+                }
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// The value of `key` serialized like in a snapshot, with a version and a checksum, like
+    /// `DUMP`. `None` if the key does not exist.
+    pub fn dump(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.expire_if_needed(key);
+        Some(dump_value(&self.entries.get(key)?.value))
+    }
+
+    /// Create `key` from a `payload` of `dump`, like `RESTORE`, expiring in `ttl_ms` or never if
+    /// it is 0. A key of the same name is only replaced with `replace`.
+    pub fn restore(
+        &self,
+        key: &[u8],
+        payload: &[u8],
+        ttl_ms: i64,
+        replace: bool,
+    ) -> Result<(), CommandError> {
+        if !replace && self.contains_key(key) {
+            return Err(CommandError::BusyKey);
+        }
+        let value = undump(payload).map_err(|_| CommandError::BadDumpPayload)?;
+        self.remove_key(key);
+        self.put_value(Bytes::copy_from_slice(key), value);
+        if ttl_ms > 0 {
+            self.expire(key, ttl_ms);
+        }
+        Ok(())
+    }
+
+    // the bytes of the snapshot of every database, as they are at the time
+    pub(super) fn snapshot(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.0.extend_from_slice(MAGIC);
+        w.u8(VERSION);
+        let (now, now_ms) = (Instant::now(), unix_millis());
+        for db in self.databases() {
+            if db.dbsize() == 0 {
+                continue;
+            }
+            w.u8(DATABASE);
+            w.u64(db.index() as u64);
+            for item in db.entries.iter() {
+                // nothing if the key is expired already
+                let Some(expiry) = item.unix_expiry(now, now_ms) else {
+                    continue;
+                };
+                w.u8(tag(&item.value));
+                w.bytes(item.key());
+                w.i64(expiry.unwrap_or(-1));
+                w.contents(&item.value);
+            }
+        }
+        w.u8(END);
+        w.0
+    }
+}
+
+// the value of a `DUMP` payload, once its version and checksum are checked
+// the payload of `dump` for `value`
+pub(super) fn dump_value(value: &Value) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.value(value);
+    w.0.extend_from_slice(&DUMP_VERSION.to_le_bytes());
+    let crc = crc64(&w.0);
+    w.0.extend_from_slice(&crc.to_le_bytes());
+    w.0
+}
+
+fn undump(payload: &[u8]) -> io::Result<Value> {
+    let Some(body) = payload.len().checked_sub(DUMP_FOOTER) else {
+        return Err(invalid("the payload is truncated"));
+    };
+    let (versioned, crc) = payload.split_at(body + 2);
+    if crc64(versioned).to_le_bytes() != crc {
+        return Err(invalid("the checksum is wrong"));
+    }
+    if versioned[body..] != DUMP_VERSION.to_le_bytes() {
+        return Err(invalid("unknown payload version"));
+    }
+    let mut reader = Reader(&payload[..body]);
+    let tag = reader.u8()?;
+    let value = reader.value(tag)?;
+    if !reader.0.is_empty() {
+        return Err(invalid("the payload is too long"));
+    }
+    Ok(value)
+}
+
+// write `bytes` to a temporary file next to `path`, then rename it over `path`
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    let temp = path.with_file_name(format!(
+        "temp-{}-{}",
+        std::process::id(),
+        name.to_string_lossy()
+    ));
+    let result = (|| {
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        std::fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RespArray, StreamId};
+    use anyhow::Result;
+
+    // a path of its own in the temporary directory
+    fn dump_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("simple-redis-{}-{}.rdb", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_round_trip_every_type() -> Result<()> {
+        let backend = Backend::new();
+        // keys may be any string, including the bytes of the snapshot format and RESP
+        let key = "bin\r\n\0\u{fe}\u{ff}ключ";
+        backend.set(
+            Bytes::copy_from_slice(key.as_bytes()),
+            BulkString::from("a\0b").into(),
+        );
+        backend.set("empty".into(), BulkString::new(Vec::new()).into());
+        backend.set("int".into(), RespFrame::Integer(-42));
+        backend.set(
+            "nested".into(),
+            RespArray::new(vec![RespFrame::Integer(1)]).into(),
+        );
+        backend.rpush("list".into(), vec![BulkString::from("x").into(); 3])?;
+        backend.sadd("set", "a")?;
+        backend.sadd("set", "b")?;
+        backend.hset(
+            "hash".into(),
+            "field".into(),
+            BulkString::from("value").into(),
+        )?;
+        backend.zadd(
+            "zset".into(),
+            vec![(1.5, "one".into()), (f64::INFINITY, "inf".into())],
+            Default::default(),
+        )?;
+        let fields = vec![(BulkString::from("f"), BulkString::from("v"))];
+        backend.xadd("stream".into(), Some(StreamId::new(5, 1)), fields, None)?;
+        backend.xgroup_create("stream".into(), "group".into(), None, false)?;
+        backend.xgroup_create("stream".into(), "new".into(), Some(StreamId::MIN), false)?;
+        backend.xreadgroup("new", "alice", &[("stream".into(), None)], None)?;
+        backend.expire(b"list", 100_000);
+        let db = backend.select(3)?;
+        db.set("other".into(), BulkString::from("db").into());
+
+        let path = dump_file("types");
+        backend.save_to(&path)?;
+        let loaded = Backend::new();
+        assert_eq!(loaded.load_from(&path)?, 10);
+        std::fs::remove_file(&path)?;
+
+        for (key, kind) in [
+            (key, "string"),
+            ("empty", "string"),
+            ("list", "list"),
+            ("set", "set"),
+            ("hash", "hash"),
+            ("zset", "zset"),
+            ("stream", "stream"),
+        ] {
+            assert_eq!(
+                loaded.key_type(key.as_bytes()).map(|t| t.as_str()),
+                Some(kind)
+            );
+        }
+        assert_eq!(
+            loaded.get(key.as_bytes()),
+            Some(BulkString::from("a\0b").into())
+        );
+        assert_eq!(
+            loaded.get(b"empty"),
+            Some(BulkString::new(Vec::new()).into())
+        );
+        // strings are stored as bytes, whatever frame they were set from
+        assert_eq!(loaded.get(b"int"), Some(BulkString::from("-42").into()));
+        assert_eq!(
+            loaded.get(b"nested"),
+            Some(BulkString::new(RespArray::new(vec![RespFrame::Integer(1)]).encode()).into())
+        );
+        assert_eq!(loaded.llen(b"list")?, 3);
+        assert!(loaded.sismember(b"set", b"b"));
+        assert_eq!(
+            loaded.hget(b"hash", b"field"),
+            Some(BulkString::from("value").into())
+        );
+        assert_eq!(loaded.zscore(b"zset", b"inf")?, Some(f64::INFINITY));
+        assert_eq!(
+            loaded.streams().get(b"stream").map(|s| s.len()),
+            backend.streams().get(b"stream").map(|s| s.len())
+        );
+        assert_eq!(loaded.xpending(b"stream", "new")?.count, 1);
+        assert_eq!(loaded.xpending(b"stream", "group")?.count, 0);
+        // new entries still get ids after the last one
+        let fields = vec![(BulkString::from("f"), BulkString::from("v"))];
+        assert!(loaded
+            .xadd("stream".into(), Some(StreamId::new(5, 1)), fields, None)
+            .is_err());
+        assert_eq!(
+            loaded.select(3)?.get(b"other"),
+            Some(BulkString::from("db").into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ttls_survive_and_expired_keys_are_left_out() -> Result<()> {
+        let backend = Backend::new();
+        backend.set_active_expire(false);
+        backend.set("lasting".into(), BulkString::from("1").into());
+        backend.expire(b"lasting", 60_000);
+        backend.set("short".into(), BulkString::from("1").into());
+        backend.expire(b"short", 30);
+
+        let path = dump_file("ttls");
+        backend.save_to(&path)?;
+        std::thread::sleep(Duration::from_millis(50));
+        // expired after the save, and on the way to loading it
+        backend.save_to(&dump_file("ttls-later"))?;
+        let loaded = Backend::new();
+        assert_eq!(loaded.load_from(&path)?, 1);
+        assert!((59_000..=60_000).contains(&loaded.pttl(b"lasting")));
+        assert_eq!(loaded.pttl(b"short"), -2);
+        let later = Backend::new();
+        assert_eq!(later.load_from(&dump_file("ttls-later"))?, 1);
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(dump_file("ttls-later"))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_and_restore_every_type() -> Result<()> {
+        // the check value of the CRC-64 redis uses
+        assert_eq!(crc64(b"123456789"), 0xe9c6_d914_c4b8_d9ca);
+
+        let backend = Backend::new();
+        backend.set("string".into(), BulkString::from("value").into());
+        backend.rpush("list".into(), vec![BulkString::from("x").into(); 2])?;
+        backend.sadd("set", "member")?;
+        backend.hset(
+            "hash".into(),
+            "field".into(),
+            BulkString::from("value").into(),
+        )?;
+        backend.zadd(
+            "zset".into(),
+            vec![(2.5, "member".into())],
+            Default::default(),
+        )?;
+        assert_eq!(backend.dump(b"missing"), None);
+
+        let other = Backend::new();
+        for key in ["string", "list", "set", "hash", "zset"] {
+            let payload = backend.dump(key.as_bytes()).expect("the key exists");
+            other.restore(key.as_bytes(), &payload, 0, false)?;
+            assert_eq!(
+                other.key_type(key.as_bytes()),
+                backend.key_type(key.as_bytes())
+            );
+            assert_eq!(other.dump(key.as_bytes()), Some(payload));
+        }
+        assert_eq!(other.get(b"string"), Some(BulkString::from("value").into()));
+        assert_eq!(other.llen(b"list")?, 2);
+        assert!(other.sismember(b"set", b"member"));
+        assert_eq!(other.zscore(b"zset", b"member")?, Some(2.5));
+        assert_eq!(other.pttl(b"string"), -1);
+
+        let payload = backend.dump(b"string").expect("the key exists");
+        assert!(matches!(
+            other.restore(b"string", &payload, 0, false),
+            Err(CommandError::BusyKey)
+        ));
+        other.restore(b"string", &backend.dump(b"hash").unwrap(), 60_000, true)?;
+        assert_eq!(other.key_type(b"string").map(|t| t.as_str()), Some("hash"));
+        assert!(other.pttl(b"string") > 59_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tampered_payloads_are_refused() {
+        let backend = Backend::new();
+        backend.set("key".into(), BulkString::from("value").into());
+        let payload = backend.dump(b"key").expect("the key exists");
+        let body = payload.len() - DUMP_FOOTER;
+
+        let mut flipped = payload.clone();
+        flipped[body - 1] ^= 1;
+        let mut version = payload.clone();
+        version[body] += 1;
+        let crc = crc64(&version[..body + 2]);
+        version[body + 2..].copy_from_slice(&crc.to_le_bytes());
+        for payload in [flipped, version, payload[1..].to_vec(), Vec::new()] {
+            assert!(matches!(
+                backend.restore(b"other", &payload, 0, false),
+                Err(CommandError::BadDumpPayload)
+            ));
+        }
+        assert!(!backend.contains_key(b"other"));
+    }
+
+    #[test]
+    fn test_invalid_snapshots() -> Result<()> {
+        let path = dump_file("invalid");
+        std::fs::write(&path, b"not a snapshot at all")?;
+        let backend = Backend::new();
+        assert_eq!(
+            backend.load_from(&path).map_err(|e| e.kind()),
+            Err(io::ErrorKind::InvalidData)
+        );
+
+        let saved = Backend::new();
+        saved.set("key".into(), BulkString::from("value").into());
+        saved.save_to(&path)?;
+        let bytes = std::fs::read(&path)?;
+        std::fs::write(&path, &bytes[..bytes.len() - 3])?;
+        assert_eq!(
+            backend.load_from(&path).map_err(|e| e.kind()),
+            Err(io::ErrorKind::InvalidData)
+        );
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
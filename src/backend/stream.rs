@@ -0,0 +1,611 @@
+use super::snapshot::{Reader, Writer};
+use super::{expire::unix_millis, Backend, KeyType};
+use crate::{cmd::CommandError, BulkString};
+use bytes::Bytes;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, io,
+    ops::Bound,
+};
+
+/// The id of a stream entry: the unix time in milliseconds it was added at and a sequence number
+/// among the entries of the same millisecond, ordered by both.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+/// The field value pairs of a stream entry, in the order they were given.
+pub type StreamFields = Vec<(BulkString, BulkString)>;
+
+/// An entry of a stream: its id and its fields.
+pub type StreamEntry = (StreamId, StreamFields);
+
+/// A stream: entries ordered by id, and the consumer groups reading them.
+#[derive(Debug, Clone, Default)]
+pub struct Stream {
+    entries: BTreeMap<StreamId, StreamFields>,
+    // the id of the last entry ever added, new ones must be greater even if it was deleted
+    last_id: StreamId,
+    groups: HashMap<String, ConsumerGroup>,
+}
+
+/// How a stream is capped by XTRIM or the trimming options of XADD.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamTrim {
+    pub threshold: TrimThreshold,
+    /// with `~`, entries are only removed by whole batches, possibly keeping more than needed
+    pub approximate: bool,
+    /// the most entries removed at once, 0 for no limit. By default there is none, except with `~`
+    /// where it is 100 batches like redis.
+    pub limit: Option<usize>,
+}
+
+/// What is kept when trimming a stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrimThreshold {
+    /// the newest entries, up to this many
+    MaxLen(usize),
+    /// the entries with an id at least this one
+    MinId(StreamId),
+}
+
+// a group of consumers sharing the entries of a stream, each entry going to one of them
+#[derive(Debug, Clone, Default)]
+struct ConsumerGroup {
+    // entries after this one have not been delivered to the group yet
+    last_delivered: StreamId,
+    // the entries delivered and not acknowledged yet
+    pending: BTreeMap<StreamId, PendingEntry>,
+    // the ids of the pending entries of each consumer, by name
+    consumers: BTreeMap<String, BTreeSet<StreamId>>,
+}
+
+/// An entry delivered to a consumer of a group and not acknowledged yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingEntry {
+    pub consumer: String,
+    /// the unix time in milliseconds of the last delivery
+    pub delivered_at: i64,
+    pub deliveries: u64,
+}
+
+/// The overview of the pending entries of a group given by `XPENDING key group`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingSummary {
+    pub count: usize,
+    /// the lowest and highest pending ids, `None` without pending entries
+    pub range: Option<(StreamId, StreamId)>,
+    /// the number of pending entries of each consumer that has some, by name
+    pub consumers: Vec<(String, usize)>,
+}
+
+// with `~`, the number of entries trimmed together, like the default stream-node-max-entries of
+// redis whose nodes are removed whole
+const TRIM_BATCH: usize = 100;
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        Self { ms, seq }
+    }
+
+    // the smallest id greater than `self`, `None` past `MAX`
+    fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => Some(StreamId::new(self.ms.checked_add(1)?, 0)),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl Stream {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = (&StreamId, &StreamFields)> {
+        self.entries.iter()
+    }
+
+    // the stream as a snapshot has it: the last id, the entries, then each group with its
+    // consumers and its pending entries
+    pub(super) fn write_snapshot(&self, w: &mut Writer) {
+        let id = |w: &mut Writer, id: &StreamId| {
+            w.u64(id.ms);
+            w.u64(id.seq);
+        };
+        id(w, &self.last_id);
+        w.len(self.entries.len());
+        for (entry, fields) in &self.entries {
+            id(w, entry);
+            w.len(fields.len());
+            for (field, value) in fields {
+                w.bytes(field);
+                w.bytes(value);
+            }
+        }
+        w.len(self.groups.len());
+        for (name, group) in &self.groups {
+            w.bytes(name.as_bytes());
+            id(w, &group.last_delivered);
+            w.len(group.consumers.len());
+            for consumer in group.consumers.keys() {
+                w.bytes(consumer.as_bytes());
+            }
+            w.len(group.pending.len());
+            for (entry, pending) in &group.pending {
+                id(w, entry);
+                w.bytes(pending.consumer.as_bytes());
+                w.i64(pending.delivered_at);
+                w.u64(pending.deliveries);
+            }
+        }
+    }
+
+    // the stream written by `write_snapshot`
+    pub(super) fn read_snapshot(r: &mut Reader) -> io::Result<Self> {
+        let id = |r: &mut Reader| Ok::<_, io::Error>(StreamId::new(r.u64()?, r.u64()?));
+        let mut stream = Stream {
+            last_id: id(r)?,
+            ..Default::default()
+        };
+        for _ in 0..r.len()? {
+            let entry = id(r)?;
+            let fields = (0..r.len()?)
+                .map(|_| Ok((BulkString::new(r.bytes()?), BulkString::new(r.bytes()?))))
+                .collect::<io::Result<StreamFields>>()?;
+            stream.entries.insert(entry, fields);
+        }
+        for _ in 0..r.len()? {
+            let name = r.string()?;
+            let mut group = ConsumerGroup {
+                last_delivered: id(r)?,
+                ..Default::default()
+            };
+            for _ in 0..r.len()? {
+                group.consumers.insert(r.string()?, BTreeSet::new());
+            }
+            for _ in 0..r.len()? {
+                let entry = id(r)?;
+                let pending = PendingEntry {
+                    consumer: r.string()?,
+                    delivered_at: r.i64()?,
+                    deliveries: r.u64()?,
+                };
+                group
+                    .consumers
+                    .entry(pending.consumer.clone())
+                    .or_default()
+                    .insert(entry);
+                group.pending.insert(entry, pending);
+            }
+            stream.groups.insert(name, group);
+        }
+        Ok(stream)
+    }
+
+    // remove the oldest entries as `trim` says, returning how many were removed. Pending entries
+    // of the groups stay pending, like with XDEL.
+    fn trim(&mut self, trim: &StreamTrim) -> usize {
+        let excess = match trim.threshold {
+            TrimThreshold::MaxLen(len) => self.entries.len().saturating_sub(len),
+            TrimThreshold::MinId(id) => self.entries.range(..id).count(),
+        };
+        let limit = match trim.limit {
+            Some(0) => usize::MAX,
+            Some(limit) => limit,
+            None if trim.approximate => 100 * TRIM_BATCH,
+            None => usize::MAX,
+        };
+        let mut removed = excess.min(limit);
+        if trim.approximate {
+            removed -= removed % TRIM_BATCH;
+        }
+        for _ in 0..removed {
+            self.entries.pop_first();
+        }
+        removed
+    }
+
+    fn range(&self, range: (Bound<StreamId>, Bound<StreamId>), count: usize) -> Vec<StreamEntry> {
+        self.entries
+            .range(range)
+            .take(count)
+            .map(|(id, fields)| (*id, fields.clone()))
+            .collect()
+    }
+}
+
+impl PendingEntry {
+    /// The milliseconds since the last delivery.
+    pub fn idle(&self) -> i64 {
+        (unix_millis() - self.delivered_at).max(0)
+    }
+}
+
+impl ConsumerGroup {
+    // record the delivery of the entry at `id` to `consumer`, taking it from whoever had it
+    fn deliver(&mut self, id: StreamId, consumer: &str, now: i64) {
+        let deliveries = match self.pending.get(&id) {
+            Some(pending) if pending.consumer == consumer => pending.deliveries + 1,
+            Some(pending) => {
+                if let Some(ids) = self.consumers.get_mut(&pending.consumer) {
+                    ids.remove(&id);
+                }
+                pending.deliveries + 1
+            }
+            None => 1,
+        };
+        self.pending.insert(
+            id,
+            PendingEntry {
+                consumer: consumer.to_string(),
+                delivered_at: now,
+                deliveries,
+            },
+        );
+        self.consumers
+            .entry(consumer.to_string())
+            .or_default()
+            .insert(id);
+    }
+}
+
+impl Backend {
+    /// Append an entry with `fields` to the stream at `key`, creating it if needed. With `None`
+    /// for `id` one is generated from the current time, greater than any id ever added to the
+    /// stream even if the clock went back. An explicit `id` must be greater than all of them. The
+    /// stream is then trimmed with `trim` if given. Returns the id of the entry.
+    pub fn xadd(
+        &self,
+        key: Bytes,
+        id: Option<StreamId>,
+        fields: StreamFields,
+        trim: Option<StreamTrim>,
+    ) -> Result<StreamId, CommandError> {
+        self.expire_if_needed(&key);
+        self.check_type(&key, KeyType::Stream)?;
+        if id == Some(StreamId::MIN) {
+            return Err(CommandError::StreamIdZero);
+        }
+        // the entry stays locked from picking the id to inserting, so concurrent adds to the same
+        // stream are ordered
+        let mut stream = self.streams().or_default(key.clone())?;
+        let last = stream.last_id;
+        let id = match id {
+            Some(id) if id <= last => return Err(CommandError::StreamIdTooSmall),
+            Some(id) => id,
+            None => {
+                let now = StreamId::new(unix_millis().max(0) as u64, 0);
+                if last >= now {
+                    last.next().ok_or(CommandError::StreamIdTooSmall)?
+                } else {
+                    now
+                }
+            }
+        };
+        stream.entries.insert(id, fields);
+        stream.last_id = id;
+        if let Some(trim) = trim {
+            stream.trim(&trim);
+        }
+        drop(stream);
+        self.wake_readers(&key);
+        Ok(id)
+    }
+
+    /// The number of entries of the stream at `key`, 0 if it does not exist.
+    pub fn xlen(&self, key: &[u8]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::Stream)?;
+        Ok(self
+            .streams()
+            .get(key)
+            .map_or(0, |stream| stream.len() as i64))
+    }
+
+    /// The entries of the stream at `key` with an id from `start` to `end` inclusive, oldest first
+    /// and at most `count` of them.
+    pub fn xrange(
+        &self,
+        key: &[u8],
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+    ) -> Result<Vec<StreamEntry>, CommandError> {
+        self.check_type(key, KeyType::Stream)?;
+        let Some(stream) = self.streams().get(key) else {
+            return Ok(Vec::new());
+        };
+        if start > end {
+            return Ok(Vec::new());
+        }
+        Ok(stream.range(
+            (Bound::Included(start), Bound::Included(end)),
+            count.unwrap_or(usize::MAX),
+        ))
+    }
+
+    /// The entries of each of `streams` with an id greater than the one given for it, oldest first
+    /// and at most `count` per stream. Streams without such entries are left out.
+    pub fn xread(
+        &self,
+        streams: &[(Bytes, StreamId)],
+        count: Option<usize>,
+    ) -> Result<Vec<(Bytes, Vec<StreamEntry>)>, CommandError> {
+        let mut read = Vec::new();
+        for (key, after) in streams {
+            self.check_type(key, KeyType::Stream)?;
+            let Some(stream) = self.streams().get(key) else {
+                continue;
+            };
+            let entries = stream.range(
+                (Bound::Excluded(*after), Bound::Unbounded),
+                count.unwrap_or(usize::MAX),
+            );
+            if !entries.is_empty() {
+                read.push((key.clone(), entries));
+            }
+        }
+        Ok(read)
+    }
+
+    // the id of the last entry of the stream at `key`, the lowest one if there are none
+    pub(crate) fn last_stream_id(&self, key: &[u8]) -> Result<StreamId, CommandError> {
+        self.check_type(key, KeyType::Stream)?;
+        Ok(self
+            .streams()
+            .get(key)
+            .map_or(StreamId::MIN, |stream| stream.last_id))
+    }
+
+    /// Remove the entries at `ids` from the stream at `key`. Returns how many there were. Pending
+    /// entries of the groups stay pending, to be acknowledged.
+    pub fn xdel(&self, key: &[u8], ids: &[StreamId]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::Stream)?;
+        let Some(mut stream) = self.streams().get_mut(key) else {
+            return Ok(0);
+        };
+        Ok(ids
+            .iter()
+            .filter(|id| stream.entries.remove(id).is_some())
+            .count() as i64)
+    }
+
+    /// Remove the oldest entries of the stream at `key` as `trim` says. Returns how many were
+    /// removed.
+    pub fn xtrim(&self, key: &[u8], trim: &StreamTrim) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::Stream)?;
+        Ok(self
+            .streams()
+            .get_mut(key)
+            .map_or(0, |mut stream| stream.trim(trim) as i64))
+    }
+
+    /// Create the consumer group `group` of the stream at `key`, the entries after `id` being the
+    /// ones not delivered yet, or after the last entry with `None`. With `mkstream` a missing
+    /// stream is created empty.
+    pub fn xgroup_create(
+        &self,
+        key: Bytes,
+        group: String,
+        id: Option<StreamId>,
+        mkstream: bool,
+    ) -> Result<(), CommandError> {
+        self.expire_if_needed(&key);
+        self.check_type(&key, KeyType::Stream)?;
+        let mut stream = match self.streams().get_mut(&key) {
+            Some(stream) => stream,
+            None if mkstream => self.streams().or_default(key)?,
+            None => return Err(CommandError::XGroupNoKey),
+        };
+        if stream.groups.contains_key(&group) {
+            return Err(CommandError::BusyGroup);
+        }
+        let last_delivered = id.unwrap_or(stream.last_id);
+        stream.groups.insert(
+            group,
+            ConsumerGroup {
+                last_delivered,
+                ..Default::default()
+            },
+        );
+        Ok(())
+    }
+
+    /// Read entries of `streams` as `consumer` of `group`, at most `count` per stream. An id of
+    /// `None` delivers the entries not delivered to the group yet, which become pending for the
+    /// consumer, and streams without any are left out. An explicit id delivers again the pending
+    /// entries of the consumer after it, with `None` for the fields of those deleted since.
+    #[allow(clippy::type_complexity)]
+    pub fn xreadgroup(
+        &self,
+        group: &str,
+        consumer: &str,
+        streams: &[(Bytes, Option<StreamId>)],
+        count: Option<usize>,
+    ) -> Result<Vec<(Bytes, Vec<(StreamId, Option<StreamFields>)>)>, CommandError> {
+        // nothing is delivered unless every group exists
+        for (key, _) in streams {
+            self.check_type(key, KeyType::Stream)?;
+            if !self
+                .streams()
+                .get(key)
+                .is_some_and(|stream| stream.groups.contains_key(group))
+            {
+                return Err(CommandError::NoGroup(
+                    String::from_utf8_lossy(key).into_owned(),
+                    group.to_string(),
+                ));
+            }
+        }
+
+        let count = count.unwrap_or(usize::MAX);
+        let now = unix_millis();
+        let mut read = Vec::new();
+        for (key, id) in streams {
+            let no_group = || {
+                CommandError::NoGroup(String::from_utf8_lossy(key).into_owned(), group.to_string())
+            };
+            let mut stream = self.streams().get_mut(key).ok_or_else(no_group)?;
+            let Stream {
+                entries, groups, ..
+            } = &mut *stream;
+            let group = groups.get_mut(group).ok_or_else(no_group)?;
+            // a consumer exists from its first read, even if it got nothing
+            group.consumers.entry(consumer.to_string()).or_default();
+
+            let delivered = match id {
+                None => {
+                    let delivered = entries
+                        .range((Bound::Excluded(group.last_delivered), Bound::Unbounded))
+                        .take(count)
+                        .map(|(id, fields)| (*id, Some(fields.clone())))
+                        .collect::<Vec<_>>();
+                    if delivered.is_empty() {
+                        continue;
+                    }
+                    group.last_delivered = delivered[delivered.len() - 1].0;
+                    delivered
+                }
+                Some(after) => group.consumers[consumer]
+                    .range((Bound::Excluded(*after), Bound::Unbounded))
+                    .take(count)
+                    .map(|id| (*id, entries.get(id).cloned()))
+                    .collect(),
+            };
+            // like redis, an entry deleted since is reported without counting as a delivery
+            for (id, _) in delivered.iter().filter(|(_, fields)| fields.is_some()) {
+                group.deliver(*id, consumer, now);
+            }
+            read.push((key.clone(), delivered));
+        }
+        Ok(read)
+    }
+
+    /// Acknowledge the entries at `ids` of the stream at `key` for `group`, which are no longer
+    /// pending. Returns how many were pending.
+    pub fn xack(&self, key: &[u8], group: &str, ids: &[StreamId]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::Stream)?;
+        let Some(mut stream) = self.streams().get_mut(key) else {
+            return Ok(0);
+        };
+        let Some(group) = stream.groups.get_mut(group) else {
+            return Ok(0);
+        };
+        let mut acked = 0;
+        for id in ids {
+            if let Some(pending) = group.pending.remove(id) {
+                if let Some(ids) = group.consumers.get_mut(&pending.consumer) {
+                    ids.remove(id);
+                }
+                acked += 1;
+            }
+        }
+        Ok(acked)
+    }
+
+    /// The overview of the pending entries of `group` of the stream at `key`.
+    pub fn xpending(&self, key: &[u8], group: &str) -> Result<PendingSummary, CommandError> {
+        self.check_type(key, KeyType::Stream)?;
+        let no_group =
+            || CommandError::NoGroup(String::from_utf8_lossy(key).into_owned(), group.to_string());
+        let stream = self.streams().get(key).ok_or_else(no_group)?;
+        let group = stream.groups.get(group).ok_or_else(no_group)?;
+        let range = group
+            .pending
+            .first_key_value()
+            .zip(group.pending.last_key_value())
+            .map(|((first, _), (last, _))| (*first, *last));
+        let consumers = group
+            .consumers
+            .iter()
+            .filter(|(_, ids)| !ids.is_empty())
+            .map(|(consumer, ids)| (consumer.clone(), ids.len()))
+            .collect();
+        Ok(PendingSummary {
+            count: group.pending.len(),
+            range,
+            consumers,
+        })
+    }
+
+    /// The pending entries of `group` of the stream at `key` with an id from `start` to `end`
+    /// inclusive, oldest first and at most `count` of them, only those of `consumer` if given.
+    pub fn xpending_range(
+        &self,
+        key: &[u8],
+        group: &str,
+        start: StreamId,
+        end: StreamId,
+        count: usize,
+        consumer: Option<&str>,
+    ) -> Result<Vec<(StreamId, PendingEntry)>, CommandError> {
+        self.check_type(key, KeyType::Stream)?;
+        let no_group =
+            || CommandError::NoGroup(String::from_utf8_lossy(key).into_owned(), group.to_string());
+        let stream = self.streams().get(key).ok_or_else(no_group)?;
+        let group = stream.groups.get(group).ok_or_else(no_group)?;
+        if start > end {
+            return Ok(Vec::new());
+        }
+        Ok(group
+            .pending
+            .range(start..=end)
+            .filter(|(_, pending)| consumer.is_none_or(|consumer| pending.consumer == consumer))
+            .take(count)
+            .map(|(id, pending)| (*id, pending.clone()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_id_order() {
+        let mut ids = vec![
+            StreamId::new(2, 0),
+            StreamId::new(1, 10),
+            StreamId::new(1, 2),
+            StreamId::MAX,
+            StreamId::MIN,
+        ];
+        ids.sort();
+        assert_eq!(
+            ids,
+            vec![
+                StreamId::MIN,
+                StreamId::new(1, 2),
+                StreamId::new(1, 10),
+                StreamId::new(2, 0),
+                StreamId::MAX,
+            ]
+        );
+        assert_eq!(StreamId::new(1, 2).next(), Some(StreamId::new(1, 3)));
+        assert_eq!(StreamId::new(1, u64::MAX).next(), Some(StreamId::new(2, 0)));
+        assert_eq!(StreamId::MAX.next(), None);
+        assert_eq!(
+            StreamId::new(1526985054069, 3).to_string(),
+            "1526985054069-3"
+        );
+    }
+}
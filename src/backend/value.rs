@@ -0,0 +1,401 @@
+use super::{format_float, Backend, KeyType, Stream, ZSet};
+use crate::{cmd::CommandError, BulkString, RespEncode, RespFrame};
+use bytes::Bytes;
+use dashmap::mapref::one::{MappedRef, MappedRefMut};
+use dashmap::{DashMap, DashSet};
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// The value stored at a key, with a variant for each type `TYPE` reports.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Str(Bytes),
+    Hash(Hash),
+    Set(DashSet<Bytes>),
+    List(VecDeque<RespFrame>),
+    ZSet(ZSet),
+    Stream(Stream),
+}
+
+/// A key of a database: its value and what is kept about the key besides.
+#[derive(Debug)]
+pub struct Entry {
+    pub value: Value,
+    /// When the key expires, `None` if it has no time to live.
+    pub expires_at: Option<Instant>,
+    /// The tick of the server clock the key last changed at, what `WATCH` compares.
+    pub version: u64,
+    /// The tick the key was last read or written at, the LRU eviction goes by it.
+    pub last_access: u64,
+    // the bytes the key counts for in the used memory, as of the last time it was sized
+    pub(crate) size: usize,
+}
+
+/// The fields of a hash. They are shared so that a long read, as of `HGETALL`, can go on out of
+/// the lock of the key, while cloning a hash copies the fields like the other values do.
+#[derive(Debug, Default)]
+pub struct Hash(Arc<DashMap<Bytes, RespFrame>>);
+
+impl Hash {
+    // the fields as they are, for a reader to keep once the lock of the key is released
+    pub(crate) fn shared(&self) -> Arc<DashMap<Bytes, RespFrame>> {
+        self.0.clone()
+    }
+}
+
+impl Clone for Hash {
+    fn clone(&self) -> Self {
+        Self(Arc::new(self.0.as_ref().clone()))
+    }
+}
+
+impl Deref for Hash {
+    type Target = DashMap<Bytes, RespFrame>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<DashMap<Bytes, RespFrame>> for Hash {
+    fn from(fields: DashMap<Bytes, RespFrame>) -> Self {
+        Self(Arc::new(fields))
+    }
+}
+
+impl Value {
+    pub fn key_type(&self) -> KeyType {
+        match self {
+            Value::Str(_) => KeyType::String,
+            Value::Hash(_) => KeyType::Hash,
+            Value::Set(_) => KeyType::Set,
+            Value::List(_) => KeyType::List,
+            Value::ZSet(_) => KeyType::ZSet,
+            Value::Stream(_) => KeyType::Stream,
+        }
+    }
+}
+
+impl Entry {
+    /// A key holding `value` with no time to live, created at `tick` of the server clock.
+    pub fn new(value: Value, tick: u64) -> Self {
+        Self {
+            value,
+            expires_at: None,
+            version: tick,
+            last_access: tick,
+            size: 0,
+        }
+    }
+
+    // whether the key has expired by `now`
+    pub(crate) fn expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|deadline| deadline <= now)
+    }
+}
+
+// what one of the variants of `Value` holds, so that the values of that type can be looked up
+// as if they had a map of their own
+pub(crate) trait Kind: Sized + Default {
+    fn of(value: &Value) -> Option<&Self>;
+    fn of_mut(value: &mut Value) -> Option<&mut Self>;
+    fn from_value(value: Value) -> Option<Self>;
+    fn into_value(self) -> Value;
+}
+
+macro_rules! kind {
+    ($type:ty, $variant:ident) => {
+        impl Kind for $type {
+            fn of(value: &Value) -> Option<&Self> {
+                match value {
+                    Value::$variant(value) => Some(value),
+                    _ => None,
+                }
+            }
+
+            fn of_mut(value: &mut Value) -> Option<&mut Self> {
+                match value {
+                    Value::$variant(value) => Some(value),
+                    _ => None,
+                }
+            }
+
+            fn from_value(value: Value) -> Option<Self> {
+                match value {
+                    Value::$variant(value) => Some(value),
+                    _ => None,
+                }
+            }
+
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+        }
+    };
+}
+
+kind!(Bytes, Str);
+kind!(Hash, Hash);
+kind!(DashSet<Bytes>, Set);
+kind!(VecDeque<RespFrame>, List);
+kind!(ZSet, ZSet);
+kind!(Stream, Stream);
+
+/// The values of one type in a database, see `Backend::strings` and the like. A key holding a
+/// value of another type is missing as far as they are concerned.
+pub(crate) struct Values<'a, T> {
+    backend: &'a Backend,
+    kind: PhantomData<T>,
+}
+
+impl<'a, T: Kind> Values<'a, T> {
+    pub(crate) fn get(&self, key: &[u8]) -> Option<MappedRef<'a, Bytes, Entry, T>> {
+        self.backend
+            .entries
+            .get(key)?
+            .try_map(|entry| T::of(&entry.value))
+            .ok()
+    }
+
+    pub(crate) fn get_mut(&self, key: &[u8]) -> Option<MappedRefMut<'a, Bytes, Entry, T>> {
+        self.backend
+            .entries
+            .get_mut(key)?
+            .try_map(|entry| T::of_mut(&mut entry.value))
+            .ok()
+    }
+
+    // the value at `key`, created empty if the key does not exist. Fails if the key holds a
+    // value of another type, which the callers check for first unless a concurrent write got in
+    // between.
+    pub(crate) fn or_default(
+        &self,
+        key: Bytes,
+    ) -> Result<MappedRefMut<'a, Bytes, Entry, T>, CommandError> {
+        self.or_insert_with(key, T::default)
+    }
+
+    // like `or_default`, creating the value with `create`
+    pub(crate) fn or_insert_with(
+        &self,
+        key: Bytes,
+        create: impl FnOnce() -> T,
+    ) -> Result<MappedRefMut<'a, Bytes, Entry, T>, CommandError> {
+        self.backend
+            .entries
+            .entry(key)
+            .or_insert_with(|| Entry::new(create().into_value(), self.backend.tick()))
+            .try_map(|entry| T::of_mut(&mut entry.value))
+            .map_err(|_| CommandError::WrongType)
+    }
+
+    // like `or_default`, only keeping a read lock on the key once it exists, for the types that
+    // are changed through a shared reference
+    pub(crate) fn or_default_shared(
+        &self,
+        key: Bytes,
+    ) -> Result<MappedRef<'a, Bytes, Entry, T>, CommandError> {
+        self.backend
+            .entries
+            .entry(key)
+            .or_insert_with(|| Entry::new(T::default().into_value(), self.backend.tick()))
+            .downgrade()
+            .try_map(|entry| T::of(&entry.value))
+            .map_err(|_| CommandError::WrongType)
+    }
+
+    // store `value` at `key`, in place of whatever is there but keeping its time to live
+    pub(crate) fn insert(&self, key: Bytes, value: T) {
+        self.backend.put_value(key, value.into_value());
+    }
+
+    pub(crate) fn remove(&self, key: &[u8]) -> Option<T> {
+        self.remove_if(key, |_| true)
+    }
+
+    // remove the value at `key` if it is of this type and `f` holds for it, under the write lock
+    pub(crate) fn remove_if(&self, key: &[u8], f: impl FnOnce(&T) -> bool) -> Option<T> {
+        self.backend
+            .remove_entry_if(key, |entry| T::of(&entry.value).is_some_and(f))
+            .and_then(|entry| T::from_value(entry.value))
+    }
+}
+
+/// The bytes of a string given as a frame, the way a client would have sent it.
+pub(crate) fn string_bytes(frame: RespFrame) -> Bytes {
+    match frame {
+        RespFrame::BulkString(s) => s.0,
+        RespFrame::SimpleString(s) => s.0.into(),
+        RespFrame::Integer(n) => n.to_string().into(),
+        RespFrame::Double(f) => format_float(f).into(),
+        // the commands never store anything else
+        frame => frame.encode().into(),
+    }
+}
+
+/// A string as the bulk string replying with it, sharing its bytes rather than copying them.
+pub(crate) fn string_frame(bytes: &Bytes) -> RespFrame {
+    BulkString::from(bytes).into()
+}
+
+// change a string in place, without copying it when nothing else shares its bytes
+pub(crate) fn edit_bytes<R>(bytes: &mut Bytes, edit: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    let mut vec = Vec::from(std::mem::take(bytes));
+    let result = edit(&mut vec);
+    *bytes = Bytes::from(vec);
+    result
+}
+
+impl Backend {
+    pub(crate) fn strings(&self) -> Values<'_, Bytes> {
+        self.values()
+    }
+
+    pub(crate) fn hashes(&self) -> Values<'_, Hash> {
+        self.values()
+    }
+
+    pub(crate) fn sets(&self) -> Values<'_, DashSet<Bytes>> {
+        self.values()
+    }
+
+    pub(crate) fn lists(&self) -> Values<'_, VecDeque<RespFrame>> {
+        self.values()
+    }
+
+    pub(crate) fn zsets(&self) -> Values<'_, ZSet> {
+        self.values()
+    }
+
+    pub(crate) fn streams(&self) -> Values<'_, Stream> {
+        self.values()
+    }
+
+    fn values<T>(&self) -> Values<'_, T> {
+        Values {
+            backend: self,
+            kind: PhantomData,
+        }
+    }
+
+    // the next tick of the clock that orders the changes and accesses of every key
+    pub(crate) fn tick(&self) -> u64 {
+        self.server.clock.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    // remove the key if `f` holds for it, keeping count of the removals for `WATCH` and of the
+    // memory it took
+    pub(crate) fn remove_entry_if(
+        &self,
+        key: &[u8],
+        f: impl FnOnce(&Entry) -> bool,
+    ) -> Option<Entry> {
+        let (_, entry) = self.entries.remove_if(key, |_, entry| f(entry))?;
+        self.removed_at.store(self.tick(), Ordering::Relaxed);
+        self.unaccounted.remove(key);
+        self.used_memory.fetch_sub(entry.size, Ordering::Relaxed);
+        Some(entry)
+    }
+
+    pub(crate) fn remove_entry(&self, key: &[u8]) -> Option<Entry> {
+        self.remove_entry_if(key, |_| true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::SimpleString;
+    use anyhow::Result;
+
+    fn wrong_type(backend: &Backend, args: &[&str]) -> bool {
+        run_args(backend, args).is_err_and(|e| matches!(e, CommandError::WrongType))
+    }
+
+    fn typed_keys() -> Result<Backend> {
+        let backend = Backend::new();
+        run_args(&backend, &["set", "string", "1"])?;
+        run_args(&backend, &["hset", "hash", "field", "1"])?;
+        run_args(&backend, &["sadd", "set", "member"])?;
+        run_args(&backend, &["rpush", "list", "a"])?;
+        run_args(&backend, &["zadd", "zset", "1", "member"])?;
+        run_args(&backend, &["xadd", "stream", "1-1", "field", "value"])?;
+        Ok(backend)
+    }
+
+    #[test]
+    fn test_commands_of_one_type_refuse_the_others() -> Result<()> {
+        let backend = typed_keys()?;
+        let commands: [(&str, &[&str]); 6] = [
+            ("string", &["incr"]),
+            ("hash", &["hset", "field", "1"]),
+            ("set", &["sadd", "member"]),
+            ("list", &["lpush", "a"]),
+            ("zset", &["zadd", "1", "member"]),
+            ("stream", &["xadd", "*", "field", "value"]),
+        ];
+        for (owner, command) in commands {
+            for key in ["string", "hash", "set", "list", "zset", "stream"] {
+                let mut args = vec![command[0], key];
+                args.extend(&command[1..]);
+                assert_eq!(wrong_type(&backend, &args), key != owner, "{:?}", args);
+            }
+        }
+        // and nothing was changed by the refused ones
+        assert_eq!(
+            run_args(&backend, &["get", "string"])?,
+            BulkString::from("2").into()
+        );
+        assert_eq!(
+            run_args(&backend, &["llen", "list"])?,
+            RespFrame::Integer(2)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_replaces_a_value_of_any_type() -> Result<()> {
+        let backend = typed_keys()?;
+        run_args(&backend, &["expire", "hash", "100"])?;
+        for key in ["hash", "set", "list", "zset", "stream"] {
+            assert_eq!(
+                run_args(&backend, &["set", key, "text"])?,
+                SimpleString::new("OK").into()
+            );
+            assert_eq!(backend.key_type(key.as_bytes()), Some(KeyType::String));
+            assert_eq!(
+                run_args(&backend, &["get", key])?,
+                BulkString::from("text").into()
+            );
+            assert!(wrong_type(&backend, &["lpush", key, "a"]));
+        }
+        // like any SET without KEEPTTL, the ttl went with the old value
+        assert_eq!(backend.pttl(b"hash"), -1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_metadata_moves_with_the_value() -> Result<()> {
+        let backend = typed_keys()?;
+        run_args(&backend, &["expire", "zset", "100"])?;
+        run_args(&backend, &["rename", "zset", "renamed"])?;
+        assert!(backend.pttl(b"renamed") > 99_000);
+        run_args(&backend, &["copy", "renamed", "copied"])?;
+        assert!(backend.pttl(b"copied") > 99_000);
+        assert_eq!(backend.key_type(b"copied"), Some(KeyType::ZSet));
+
+        // every write gives the key a new version, whatever its type
+        let version = backend.key_version(b"list");
+        run_args(&backend, &["rpush", "list", "b"])?;
+        assert!(backend.key_version(b"list") > version);
+        let version = backend.key_version(b"copied");
+        run_args(&backend, &["zincrby", "copied", "1", "member"])?;
+        assert!(backend.key_version(b"copied") > version);
+        Ok(())
+    }
+}
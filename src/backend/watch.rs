@@ -0,0 +1,29 @@
+use super::Backend;
+use bytes::Bytes;
+use std::sync::atomic::Ordering;
+
+impl Backend {
+    /// The version of `key`: the tick of the server clock it last changed at, or for a key that
+    /// does not exist the tick a key of the database was last removed at. A watched key changed
+    /// once its version is another.
+    pub fn key_version(&self, key: &[u8]) -> u64 {
+        self.expire_if_needed(key);
+        match self.entries.get(key) {
+            Some(entry) => entry.version,
+            None => self.removed_at.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Note that `keys` changed, for the transactions watching them and the count of the used
+    /// memory. This is once the write is done: a key watched meanwhile would have its new
+    /// version with its old value.
+    pub fn signal_modified(&self, keys: &[Bytes]) {
+        // a key removed has no value left to size
+        for key in keys {
+            if let Some(mut entry) = self.entries.get_mut(key) {
+                entry.version = self.tick();
+                self.mark_written(std::slice::from_ref(key));
+            }
+        }
+    }
+}
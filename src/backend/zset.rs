@@ -0,0 +1,879 @@
+use super::{list::normalize_range, sampling::sample_iter, Backend, KeyType};
+use crate::cmd::CommandError;
+use bytes::Bytes;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    ops::{Bound, Range},
+};
+
+/// A sorted set: members with a score, ordered by score and then by member.
+#[derive(Debug, Clone, Default)]
+pub struct ZSet {
+    scores: HashMap<Bytes, f64>,
+    // the same pairs as `scores`, kept in order
+    index: BTreeSet<(Score, Bytes)>,
+}
+
+// a score that can be ordered, scores are never NaN
+#[derive(Debug, Clone, Copy)]
+struct Score(f64);
+
+/// One end of a range of scores, e.g. `5`, `(5` or `+inf`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreBound {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+/// One end of a range of members compared bytewise, e.g. `[b`, `(b`, or `-` and `+` for the
+/// lowest and highest possible member.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexBound {
+    Min,
+    Max,
+    Inclusive(Vec<u8>),
+    Exclusive(Vec<u8>),
+}
+
+/// What the unified `ZRANGE` and `ZRANGESTORE` select: members by rank, by score or
+/// lexicographically. The bounds are always lowest first, even when given highest first with `REV`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZRangeBy {
+    Rank(i64, i64),
+    Score(ScoreBound, ScoreBound),
+    Lex(LexBound, LexBound),
+}
+
+/// How `ZUNIONSTORE` and `ZINTERSTORE` combine the scores of a member found in several inputs.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+/// The `LIMIT offset count` of the range commands: a negative offset selects nothing and a
+/// negative count everything after the offset.
+pub type Limit = Option<(i64, i64)>;
+
+/// The `NX`, `XX`, `GT`, `LT` and `CH` flags of `ZADD`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ZAddOptions {
+    /// Only add new members, never update existing ones.
+    pub nx: bool,
+    /// Only update existing members, never add new ones.
+    pub xx: bool,
+    /// Only update a score if the new one is greater.
+    pub gt: bool,
+    /// Only update a score if the new one is lower.
+    pub lt: bool,
+    /// Count the members whose score changed along with the added ones.
+    pub ch: bool,
+}
+
+impl ZSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// The score of every member, in no particular order.
+    pub(crate) fn scores(&self) -> &HashMap<Bytes, f64> {
+        &self.scores
+    }
+
+    /// Set the score of `member`, returning the previous one.
+    pub fn insert(&mut self, member: Bytes, score: f64) -> Option<f64> {
+        // -0 and 0 are the same score, but would not be ordered as such
+        let score = score + 0.0;
+        let old = self.scores.insert(member.clone(), score);
+        match old {
+            Some(old) if old == score => {}
+            Some(old) => {
+                let mut entry = (Score(old), member);
+                self.index.remove(&entry);
+                entry.0 = Score(score);
+                self.index.insert(entry);
+            }
+            None => {
+                self.index.insert((Score(score), member));
+            }
+        }
+        old
+    }
+
+    /// Remove `member`, returning its score.
+    pub fn remove(&mut self, member: &[u8]) -> Option<f64> {
+        let (member, score) = self.scores.remove_entry(member)?;
+        self.index.remove(&(Score(score), member));
+        Some(score)
+    }
+
+    /// The position of `member` by ascending score, and its score.
+    ///
+    /// The ordered index does not know the size of its subtrees, so this walks every member ranked
+    /// lower.
+    pub fn rank(&self, member: &[u8]) -> Option<(usize, f64)> {
+        let (member, score) = self.scores.get_key_value(member)?;
+        let rank = self.index.range(..(Score(*score), member.clone())).count();
+        Some((rank, *score))
+    }
+
+    /// Remove and return the member with the lowest score, or the highest one with `rev`.
+    pub fn pop(&mut self, rev: bool) -> Option<(Bytes, f64)> {
+        let (score, member) = if rev {
+            self.index.pop_last()?
+        } else {
+            self.index.pop_first()?
+        };
+        self.scores.remove(&member);
+        Some((member, score.0))
+    }
+
+    /// The members and their scores, lowest score first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&Bytes, f64)> + ExactSizeIterator + '_ {
+        self.index.iter().map(|(score, member)| (member, score.0))
+    }
+
+    /// The members with a score between `min` and `max`, lowest score first. Only the members in
+    /// the range are visited.
+    pub fn range_by_score(
+        &self,
+        min: ScoreBound,
+        max: ScoreBound,
+    ) -> impl DoubleEndedIterator<Item = (&Bytes, f64)> + '_ {
+        let lower = match min {
+            ScoreBound::Inclusive(score) => Bound::Included(score + 0.0),
+            // past every member of the score
+            ScoreBound::Exclusive(score) if score == f64::INFINITY => Bound::Unbounded,
+            ScoreBound::Exclusive(score) => Bound::Included((score + 0.0).next_up()),
+        };
+        let upper = match max {
+            ScoreBound::Inclusive(score) if score == f64::INFINITY => Bound::Unbounded,
+            ScoreBound::Inclusive(score) => Bound::Excluded((score + 0.0).next_up()),
+            ScoreBound::Exclusive(score) => Bound::Excluded(score + 0.0),
+        };
+        let empty = matches!(min, ScoreBound::Exclusive(score) if score == f64::INFINITY)
+            || match (lower, upper) {
+                (Bound::Included(lower), Bound::Excluded(upper)) => lower >= upper,
+                _ => false,
+            };
+        // the empty string sorts before all the members of a score
+        let at = |score: f64| (Score(score), Bytes::new());
+        let range = (lower.map(at), upper.map(at));
+        (!empty)
+            .then(|| self.index.range(range))
+            .into_iter()
+            .flatten()
+            .map(|(score, member)| (member, score.0))
+    }
+
+    /// The members between `min` and `max` in the order of the index, which is bytewise when all
+    /// the scores are the same, as the lexicographical commands expect.
+    pub fn range_by_lex(
+        &self,
+        min: &LexBound,
+        max: &LexBound,
+    ) -> impl DoubleEndedIterator<Item = (&Bytes, f64)> + '_ {
+        let start = self
+            .iter()
+            .take_while(|(member, _)| !min.is_below(member))
+            .count();
+        let len = self
+            .iter()
+            .skip(start)
+            .take_while(|(member, _)| max.is_above(member))
+            .count();
+        self.iter().skip(start).take(len)
+    }
+
+    /// The members ranked `range`, 0 being the lowest score, or the highest one with `rev`.
+    pub fn range(&self, range: Range<usize>, rev: bool) -> Vec<(Bytes, f64)> {
+        let owned = |(member, score): (&Bytes, f64)| (member.clone(), score);
+        if rev {
+            self.iter()
+                .rev()
+                .skip(range.start)
+                .take(range.len())
+                .map(owned)
+                .collect()
+        } else {
+            self.iter()
+                .skip(range.start)
+                .take(range.len())
+                .map(owned)
+                .collect()
+        }
+    }
+}
+
+impl LexBound {
+    // whether `member` is at or after this lower bound
+    fn is_below(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => true,
+            LexBound::Max => false,
+            LexBound::Inclusive(bound) => member >= bound.as_slice(),
+            LexBound::Exclusive(bound) => member > bound.as_slice(),
+        }
+    }
+
+    // whether `member` is at or before this upper bound
+    fn is_above(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(bound) => member <= bound.as_slice(),
+            LexBound::Exclusive(bound) => member < bound.as_slice(),
+        }
+    }
+}
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Backend {
+    /// Add the `(score, member)` pairs to the sorted set at `key`, creating it if needed, one
+    /// after the other so the last score of a member given twice wins. Returns the number of
+    /// members added, or with `CH` the number of members added or whose score changed.
+    pub fn zadd(
+        &self,
+        key: Bytes,
+        pairs: Vec<(f64, Bytes)>,
+        options: ZAddOptions,
+    ) -> Result<i64, CommandError> {
+        self.check_type(&key, KeyType::ZSet)?;
+        let mut zset = match self.zsets().get_mut(&key) {
+            Some(zset) => zset,
+            // nothing can be added to a missing key
+            None if options.xx => return Ok(0),
+            None => self.zsets().or_default(key)?,
+        };
+        let mut changed = 0;
+        for (score, member) in pairs {
+            match zset.score(&member) {
+                None if options.xx => {}
+                None => {
+                    zset.insert(member, score);
+                    changed += 1;
+                }
+                Some(_) if options.nx => {}
+                Some(old) if (options.gt && score <= old) || (options.lt && score >= old) => {}
+                Some(old) => {
+                    zset.insert(member, score);
+                    changed += (options.ch && old != score) as i64;
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    /// The members of the sorted set at `key` ranked from `start` to `stop` inclusive, lowest
+    /// score first or highest first with `rev`, and their scores. The ranks are read like the
+    /// indexes of `lrange`.
+    pub fn zrange(
+        &self,
+        key: &[u8],
+        start: i64,
+        stop: i64,
+        rev: bool,
+    ) -> Result<Vec<(Bytes, f64)>, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        let Some(zset) = self.zsets().get(key) else {
+            return Ok(Vec::new());
+        };
+        Ok(match normalize_range(zset.len(), start, stop) {
+            Some(range) => zset.range(range, rev),
+            None => Vec::new(),
+        })
+    }
+
+    /// The members of the sorted set at `key` with a score between `min` and `max` and their
+    /// scores, lowest score first or highest with `rev`, paged by `limit`.
+    pub fn zrange_by_score(
+        &self,
+        key: &[u8],
+        min: ScoreBound,
+        max: ScoreBound,
+        rev: bool,
+        limit: Limit,
+    ) -> Result<Vec<(Bytes, f64)>, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        let Some(zset) = self.zsets().get(key) else {
+            return Ok(Vec::new());
+        };
+        Ok(select(zset.range_by_score(min, max), rev, limit))
+    }
+
+    /// The members of the sorted set at `key` between `min` and `max` and their scores, reversed
+    /// with `rev` and paged by `limit`, see `ZSet::range_by_lex`.
+    pub fn zrange_by_lex(
+        &self,
+        key: &[u8],
+        min: &LexBound,
+        max: &LexBound,
+        rev: bool,
+        limit: Limit,
+    ) -> Result<Vec<(Bytes, f64)>, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        let Some(zset) = self.zsets().get(key) else {
+            return Ok(Vec::new());
+        };
+        Ok(select(zset.range_by_lex(min, max), rev, limit))
+    }
+
+    /// The members of the sorted set at `key` selected `by` and their scores, highest first with
+    /// `rev`. `limit` applies to scores and members only.
+    pub fn zrange_by(
+        &self,
+        key: &[u8],
+        by: &ZRangeBy,
+        rev: bool,
+        limit: Limit,
+    ) -> Result<Vec<(Bytes, f64)>, CommandError> {
+        match by {
+            ZRangeBy::Rank(start, stop) => self.zrange(key, *start, *stop, rev),
+            ZRangeBy::Score(min, max) => self.zrange_by_score(key, *min, *max, rev, limit),
+            ZRangeBy::Lex(min, max) => self.zrange_by_lex(key, min, max, rev, limit),
+        }
+    }
+
+    /// Store the members of the sorted set at `src` selected like `zrange_by` at `dest`, returning
+    /// how many there are. Nothing selected deletes `dest`.
+    pub fn zrangestore(
+        &self,
+        dest: &[u8],
+        src: &[u8],
+        by: &ZRangeBy,
+        rev: bool,
+        limit: Limit,
+    ) -> Result<i64, CommandError> {
+        let members = self.zrange_by(src, by, rev, limit)?;
+        Ok(self.zstore(dest, members.into_iter().collect()))
+    }
+
+    /// The number of members of the sorted set at `key` between `min` and `max`.
+    pub fn zlexcount(
+        &self,
+        key: &[u8],
+        min: &LexBound,
+        max: &LexBound,
+    ) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        Ok(self
+            .zsets()
+            .get(key)
+            .map_or(0, |zset| zset.range_by_lex(min, max).count() as i64))
+    }
+
+    /// The number of members of the sorted set at `key` with a score between `min` and `max`.
+    pub fn zcount(
+        &self,
+        key: &[u8],
+        min: ScoreBound,
+        max: ScoreBound,
+    ) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        Ok(self
+            .zsets()
+            .get(key)
+            .map_or(0, |zset| zset.range_by_score(min, max).count() as i64))
+    }
+
+    /// Remove `members` from the sorted set at `key`, returning how many were removed. The key is
+    /// removed along with its last member.
+    pub fn zrem(&self, key: &[u8], members: &[Bytes]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        let Some(mut zset) = self.zsets().get_mut(key) else {
+            return Ok(0);
+        };
+        let removed = members
+            .iter()
+            .filter(|member| zset.remove(member).is_some())
+            .count();
+        drop(zset);
+        self.zsets().remove_if(key, ZSet::is_empty);
+        Ok(removed as i64)
+    }
+
+    /// The number of members of the sorted set at `key`, 0 if the key does not exist.
+    pub fn zcard(&self, key: &[u8]) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        Ok(self.zsets().get(key).map_or(0, |zset| zset.len() as i64))
+    }
+
+    /// Add `delta` to the score of `member` in the sorted set at `key`, adding the member with a
+    /// score of `delta` if needed. Returns the new score.
+    pub fn zincrby(&self, key: Bytes, member: Bytes, delta: f64) -> Result<f64, CommandError> {
+        self.check_type(&key, KeyType::ZSet)?;
+        // reading the old score and moving the member in the index happen under the same entry
+        let mut zset = self.zsets().or_default(key.clone())?;
+        let score = zset.score(&member).unwrap_or(0.0) + delta;
+        if score.is_nan() {
+            // e.g. `+inf` plus `-inf`, a key created for nothing is removed again
+            drop(zset);
+            self.zsets().remove_if(&key, ZSet::is_empty);
+            return Err(CommandError::NaNScore);
+        }
+        zset.insert(member, score);
+        Ok(score)
+    }
+
+    /// The rank of `member` in the sorted set at `key`, 0 being the lowest score, and its score.
+    pub fn zrank(&self, key: &[u8], member: &[u8]) -> Result<Option<(usize, f64)>, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        Ok(self.zsets().get(key).and_then(|zset| zset.rank(member)))
+    }
+
+    /// Like `zrank`, 0 being the highest score.
+    pub fn zrevrank(
+        &self,
+        key: &[u8],
+        member: &[u8],
+    ) -> Result<Option<(usize, f64)>, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        Ok(self.zsets().get(key).and_then(|zset| {
+            let (rank, score) = zset.rank(member)?;
+            Some((zset.len() - 1 - rank, score))
+        }))
+    }
+
+    /// Remove and return up to `count` members with the lowest scores from the sorted set at
+    /// `key`, lowest first. The key is removed along with its last member.
+    pub fn zpopmin(&self, key: &[u8], count: usize) -> Result<Vec<(Bytes, f64)>, CommandError> {
+        self.zpop(key, count, false)
+    }
+
+    /// Like `zpopmin`, highest scores first.
+    pub fn zpopmax(&self, key: &[u8], count: usize) -> Result<Vec<(Bytes, f64)>, CommandError> {
+        self.zpop(key, count, true)
+    }
+
+    fn zpop(&self, key: &[u8], count: usize, rev: bool) -> Result<Vec<(Bytes, f64)>, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        let Some(mut zset) = self.zsets().get_mut(key) else {
+            return Ok(Vec::new());
+        };
+        let popped = std::iter::from_fn(|| zset.pop(rev)).take(count).collect();
+        drop(zset);
+        self.zsets().remove_if(key, ZSet::is_empty);
+        Ok(popped)
+    }
+
+    /// Store the union of the sorted sets or sets at `keys` at `dest`, the score of each set being
+    /// multiplied by its weight and the scores of a member combined by `aggregate`. The members
+    /// of a plain set have a score of 1. Returns the cardinality of the union.
+    pub fn zunionstore(
+        &self,
+        dest: &[u8],
+        keys: &[Bytes],
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> Result<i64, CommandError> {
+        let inputs = self.zset_inputs(keys)?;
+        let mut union: HashMap<Bytes, f64> = HashMap::new();
+        for (members, weight) in inputs.into_iter().zip(weights) {
+            for (member, score) in members.into_iter().flatten() {
+                let score = weighted(score, *weight);
+                union
+                    .entry(member)
+                    .and_modify(|total| *total = aggregate.combine(*total, score))
+                    .or_insert(score);
+            }
+        }
+        Ok(self.zstore(dest, union))
+    }
+
+    /// Like `zunionstore`, with only the members found in every input.
+    pub fn zinterstore(
+        &self,
+        dest: &[u8],
+        keys: &[Bytes],
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> Result<i64, CommandError> {
+        let mut inputs = self.zset_inputs(keys)?.into_iter().zip(weights);
+        let mut inter = match inputs.next() {
+            Some((Some(members), weight)) => members
+                .into_iter()
+                .map(|(member, score)| (member, weighted(score, *weight)))
+                .collect::<HashMap<_, _>>(),
+            _ => HashMap::new(),
+        };
+        for (members, weight) in inputs {
+            if inter.is_empty() {
+                break;
+            }
+            let members = members
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<HashMap<_, _>>();
+            inter.retain(|member, total| match members.get(member) {
+                Some(score) => {
+                    *total = aggregate.combine(*total, weighted(*score, *weight));
+                    true
+                }
+                None => false,
+            });
+        }
+        Ok(self.zstore(dest, inter))
+    }
+
+    // a copy of the members and scores of each of `keys`, sorted sets or sets, `None` for a
+    // missing key. Each input is read on its own so no two entries are ever locked together.
+    #[allow(clippy::type_complexity)]
+    fn zset_inputs(&self, keys: &[Bytes]) -> Result<Vec<Option<Vec<(Bytes, f64)>>>, CommandError> {
+        keys.iter()
+            .map(|key| match self.key_type(key) {
+                Some(KeyType::ZSet) => Ok(self.zsets().get(key).map(|zset| {
+                    zset.iter()
+                        .map(|(member, score)| (member.clone(), score))
+                        .collect()
+                })),
+                Some(KeyType::Set) => Ok(self
+                    .sets()
+                    .get(key)
+                    .map(|set| set.iter().map(|member| (member.clone(), 1.0)).collect())),
+                Some(_) => Err(CommandError::WrongType),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    // replace whatever is stored at `dest` with a sorted set of `members`, returning its
+    // cardinality. An empty sorted set deletes `dest` instead.
+    fn zstore(&self, dest: &[u8], members: HashMap<Bytes, f64>) -> i64 {
+        let len = members.len();
+        self.remove_key(dest);
+        if len > 0 {
+            let mut zset = ZSet::new();
+            for (member, score) in members {
+                zset.insert(member, score);
+            }
+            self.zsets().insert(Bytes::copy_from_slice(dest), zset);
+        }
+        len as i64
+    }
+
+    /// The score of `member` in the sorted set at `key`.
+    pub fn zscore(&self, key: &[u8], member: &[u8]) -> Result<Option<f64>, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        Ok(self.zsets().get(key).and_then(|zset| zset.score(member)))
+    }
+
+    /// Up to `count` random members of the sorted set at `key` with their scores. The members are
+    /// distinct if `distinct` is set, otherwise exactly `count` are picked and may repeat.
+    pub fn zrandmember(
+        &self,
+        key: &[u8],
+        count: usize,
+        distinct: bool,
+    ) -> Result<Vec<(Bytes, f64)>, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        Ok(self.zsets().get(key).map_or_else(Vec::new, |zset| {
+            sample_iter(zset.iter(), zset.len(), count, distinct)
+                .into_iter()
+                .map(|(member, score)| (member.clone(), score))
+                .collect()
+        }))
+    }
+
+    /// The score of each of `members` in the sorted set at `key`, `None` for a missing one.
+    pub fn zmscore(&self, key: &[u8], members: &[Bytes]) -> Result<Vec<Option<f64>>, CommandError> {
+        self.check_type(key, KeyType::ZSet)?;
+        let zset = self.zsets().get(key);
+        Ok(members
+            .iter()
+            .map(|member| zset.as_ref().and_then(|zset| zset.score(member)))
+            .collect())
+    }
+}
+
+impl Aggregate {
+    fn combine(self, a: f64, b: f64) -> f64 {
+        match self {
+            // like redis, `inf` plus `-inf` is 0
+            Aggregate::Sum if (a + b).is_nan() => 0.0,
+            Aggregate::Sum => a + b,
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+// a score multiplied by the weight of its input, an infinite score with a weight of 0 being 0
+fn weighted(score: f64, weight: f64) -> f64 {
+    match score * weight {
+        score if score.is_nan() => 0.0,
+        score => score,
+    }
+}
+
+// the members of a range and their scores, reversed with `rev` before `limit` applies
+fn select<'a>(
+    range: impl DoubleEndedIterator<Item = (&'a Bytes, f64)>,
+    rev: bool,
+    limit: Limit,
+) -> Vec<(Bytes, f64)> {
+    let owned = |(member, score): (&Bytes, f64)| (member.clone(), score);
+    if rev {
+        apply_limit(range.rev(), limit).map(owned).collect()
+    } else {
+        apply_limit(range, limit).map(owned).collect()
+    }
+}
+
+fn apply_limit<I: Iterator>(iter: I, limit: Limit) -> std::iter::Take<std::iter::Skip<I>> {
+    let (offset, count) = match limit {
+        None => (0, usize::MAX),
+        Some((offset, _)) if offset < 0 => (0, 0),
+        Some((offset, count)) => (
+            offset as usize,
+            usize::try_from(count).unwrap_or(usize::MAX),
+        ),
+    };
+    iter.skip(offset).take(count)
+}
+
+/// Format a score the way redis replies with it: the shortest representation that reads back as
+/// the same number, in exponent notation for very large or small ones, e.g. `1.5`, `1e+300` or
+/// `inf`.
+pub(crate) fn format_score(score: f64) -> String {
+    if score.is_infinite() {
+        return if score > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    // `LowerExp` gives the shortest digits, e.g. `1.5e-7`
+    let exp = format!("{:e}", score);
+    let (mantissa, exponent) = exp.split_once('e').expect("LowerExp has an exponent");
+    let exponent: i32 = exponent.parse().expect("LowerExp exponent is an integer");
+    // the thresholds of `%.17g`
+    if (-4..17).contains(&exponent) {
+        score.to_string()
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zset_keeps_index_in_sync() {
+        let mut zset = ZSet::new();
+        assert_eq!(zset.insert("b".into(), 2.0), None);
+        assert_eq!(zset.insert("a".into(), 2.0), None);
+        assert_eq!(zset.insert("c".into(), 1.0), None);
+        assert_eq!(zset.insert("c".into(), 3.0), Some(1.0));
+        assert_eq!(zset.insert("d".into(), -0.0), None);
+        assert_eq!(
+            zset.iter().map(|(m, s)| (&m[..], s)).collect::<Vec<_>>(),
+            vec![(&b"d"[..], 0.0), (b"a", 2.0), (b"b", 2.0), (b"c", 3.0)]
+        );
+
+        assert_eq!(zset.remove(b"a"), Some(2.0));
+        assert_eq!(zset.remove(b"a"), None);
+        assert_eq!(zset.len(), 3);
+        assert_eq!(zset.index.len(), 3);
+        assert_eq!(zset.score(b"c"), Some(3.0));
+    }
+
+    #[test]
+    fn test_rank_and_pop() {
+        let mut zset = ZSet::new();
+        for (member, score) in [("c", 2.0), ("a", 1.0), ("b", 2.0)] {
+            zset.insert(member.into(), score);
+        }
+        assert_eq!(zset.rank(b"a"), Some((0, 1.0)));
+        assert_eq!(zset.rank(b"b"), Some((1, 2.0)));
+        assert_eq!(zset.rank(b"c"), Some((2, 2.0)));
+        assert_eq!(zset.rank(b"d"), None);
+
+        assert_eq!(zset.pop(true), Some(("c".into(), 2.0)));
+        assert_eq!(zset.pop(false), Some(("a".into(), 1.0)));
+        assert_eq!(zset.rank(b"b"), Some((0, 2.0)));
+        assert_eq!(zset.pop(false), Some(("b".into(), 2.0)));
+        assert_eq!(zset.pop(false), None);
+        assert!(zset.is_empty());
+    }
+
+    #[test]
+    fn test_range_by_rank() {
+        let mut zset = ZSet::new();
+        for (member, score) in [("c", 2.0), ("a", 1.0), ("b", 2.0), ("d", 3.0)] {
+            zset.insert(member.into(), score);
+        }
+        let members = |pairs: Vec<(Bytes, f64)>| {
+            pairs
+                .into_iter()
+                .map(|(member, _)| member)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(members(zset.range(0..4, false)), ["a", "b", "c", "d"]);
+        assert_eq!(members(zset.range(1..3, false)), ["b", "c"]);
+        assert_eq!(members(zset.range(0..2, true)), ["d", "c"]);
+        assert_eq!(members(zset.range(3..10, true)), ["a"]);
+        assert!(zset.range(4..5, false).is_empty());
+    }
+
+    #[test]
+    fn test_range_by_score() {
+        use ScoreBound::{Exclusive, Inclusive};
+        const INF: f64 = f64::INFINITY;
+
+        let mut zset = ZSet::new();
+        for (member, score) in [("a", 1.0), ("b", 5.0), ("c", 5.0), ("d", 7.0), ("e", INF)] {
+            zset.insert(member.into(), score);
+        }
+        let cases: &[(ScoreBound, ScoreBound, &[&str])] = &[
+            (Inclusive(-INF), Inclusive(INF), &["a", "b", "c", "d", "e"]),
+            (Inclusive(1.0), Inclusive(5.0), &["a", "b", "c"]),
+            (Exclusive(1.0), Inclusive(5.0), &["b", "c"]),
+            (Inclusive(1.0), Exclusive(5.0), &["a"]),
+            (Exclusive(1.0), Exclusive(7.0), &["b", "c"]),
+            (Inclusive(5.0), Inclusive(5.0), &["b", "c"]),
+            (Exclusive(5.0), Inclusive(5.0), &[]),
+            (Inclusive(5.0), Exclusive(5.0), &[]),
+            (Exclusive(5.0), Exclusive(5.0), &[]),
+            (Exclusive(5.0), Inclusive(INF), &["d", "e"]),
+            (Exclusive(5.0), Exclusive(INF), &["d"]),
+            (Inclusive(INF), Inclusive(INF), &["e"]),
+            (Exclusive(-INF), Exclusive(2.0), &["a"]),
+            (Inclusive(-INF), Exclusive(-INF), &[]),
+            (Exclusive(INF), Inclusive(INF), &[]),
+            (Inclusive(2.0), Inclusive(4.9), &[]),
+            // min above max
+            (Inclusive(7.0), Inclusive(1.0), &[]),
+            (Exclusive(7.0), Exclusive(1.0), &[]),
+            (Inclusive(-0.0), Inclusive(1.0), &["a"]),
+        ];
+        for (min, max, expected) in cases {
+            let members = zset
+                .range_by_score(*min, *max)
+                .map(|(member, _)| member)
+                .collect::<Vec<_>>();
+            assert_eq!(members, *expected, "{:?} {:?}", min, max);
+        }
+    }
+
+    #[test]
+    fn test_range_by_lex() {
+        use LexBound::{Exclusive, Inclusive, Max, Min};
+        let bound = |b: &str| b.as_bytes().to_vec();
+
+        let mut zset = ZSet::new();
+        // bytewise, the multi-byte `é` sorts after every ascii member
+        for member in ["a", "b", "c", "d", "e", "f", "g", "é", "ab"] {
+            zset.insert(Bytes::copy_from_slice(member.as_bytes()), 0.0);
+        }
+        let cases: &[(LexBound, LexBound, &[&str])] = &[
+            (Min, Max, &["a", "ab", "b", "c", "d", "e", "f", "g", "é"]),
+            (Min, Inclusive(bound("c")), &["a", "ab", "b", "c"]),
+            (Min, Exclusive(bound("c")), &["a", "ab", "b"]),
+            (
+                Inclusive(bound("aaa")),
+                Exclusive(bound("g")),
+                &["ab", "b", "c", "d", "e", "f"],
+            ),
+            (Exclusive(bound("a")), Exclusive(bound("c")), &["ab", "b"]),
+            (Exclusive(bound("b")), Exclusive(bound("c")), &[]),
+            (Exclusive(bound("c")), Exclusive(bound("c")), &[]),
+            (Inclusive(bound("c")), Inclusive(bound("c")), &["c"]),
+            (Inclusive(bound("g")), Max, &["g", "é"]),
+            (Exclusive(bound("g")), Max, &["é"]),
+            (Inclusive(bound("")), Inclusive(bound("a")), &["a"]),
+            (Max, Max, &[]),
+            (Min, Min, &[]),
+            (Max, Min, &[]),
+            (Inclusive(bound("d")), Inclusive(bound("b")), &[]),
+        ];
+        for (min, max, expected) in cases {
+            let members = zset
+                .range_by_lex(min, max)
+                .map(|(member, _)| member)
+                .collect::<Vec<_>>();
+            assert_eq!(members, *expected, "{:?} {:?}", min, max);
+            let members = zset
+                .range_by_lex(min, max)
+                .rev()
+                .map(|(member, _)| member)
+                .collect::<Vec<_>>();
+            let reversed = expected.iter().rev().copied().collect::<Vec<_>>();
+            assert_eq!(members, reversed, "{:?} {:?} reversed", min, max);
+        }
+    }
+
+    #[test]
+    fn test_aggregate() {
+        assert_eq!(Aggregate::Sum.combine(1.5, 2.0), 3.5);
+        assert_eq!(
+            Aggregate::Sum.combine(f64::INFINITY, f64::NEG_INFINITY),
+            0.0
+        );
+        assert_eq!(Aggregate::Min.combine(1.5, -2.0), -2.0);
+        assert_eq!(Aggregate::Max.combine(1.5, -2.0), 1.5);
+        assert_eq!(weighted(f64::INFINITY, 0.0), 0.0);
+        assert_eq!(weighted(2.0, -1.5), -3.0);
+    }
+
+    #[test]
+    fn test_apply_limit() {
+        let limited = |limit| apply_limit(0..5, limit).collect::<Vec<_>>();
+        assert_eq!(limited(None), [0, 1, 2, 3, 4]);
+        assert_eq!(limited(Some((1, 2))), [1, 2]);
+        assert_eq!(limited(Some((3, -1))), [3, 4]);
+        assert_eq!(limited(Some((3, 0))), []);
+        assert_eq!(limited(Some((10, 2))), []);
+        assert_eq!(limited(Some((-1, 2))), []);
+    }
+
+    #[test]
+    fn test_format_score() {
+        for (score, expected) in [
+            (1.0, "1"),
+            (-2.5, "-2.5"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1e16, "10000000000000000"),
+            (1e17, "1e+17"),
+            (1.5e300, "1.5e+300"),
+            (0.0001, "0.0001"),
+            (0.00001, "1e-05"),
+            (-1.25e-10, "-1.25e-10"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+        ] {
+            assert_eq!(format_score(score), expected, "{}", score);
+        }
+    }
+}
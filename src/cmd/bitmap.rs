@@ -0,0 +1,741 @@
+use super::{
+    extract_args, extract_key_args, parse_integer, validate_command, BitCount, BitField, BitOp,
+    BitPos, CommandError, CommandExecutor, GetBit, SetBit,
+};
+use crate::{BitFieldOp, BitFieldType, BitOperation, BitUnit, Overflow, RespArray, RespFrame};
+
+impl CommandExecutor for SetBit {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let old = backend.setbit(&self.key, self.offset, self.bit)?;
+        Ok(RespFrame::Integer(old as i64))
+    }
+}
+
+impl CommandExecutor for GetBit {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let bit = backend.getbit(&self.key, self.offset)?;
+        Ok(RespFrame::Integer(bit as i64))
+    }
+}
+
+impl CommandExecutor for BitCount {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.bitcount(&self.key, self.range)?))
+    }
+}
+
+impl CommandExecutor for BitOp {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(
+            backend.bitop(self.op, &self.dest, &self.keys)?,
+        ))
+    }
+}
+
+impl CommandExecutor for BitPos {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let position = backend.bitpos(&self.key, self.bit, self.start, self.end, self.unit)?;
+        Ok(RespFrame::Integer(position))
+    }
+}
+
+impl CommandExecutor for BitField {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let values = backend
+            .bitfield(&self.key, &self.ops)?
+            .into_iter()
+            .map(|value| match value {
+                Some(value) => RespFrame::Integer(value),
+                None => RespFrame::NULL,
+            })
+            .collect::<Vec<_>>();
+        Ok(RespArray::new(values).into())
+    }
+}
+
+impl TryFrom<RespArray> for SetBit {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setbit"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(offset)),
+                Some(RespFrame::BulkString(bit)),
+            ) => {
+                let offset = parse_bit_offset(&offset)?;
+                let bit = match &bit[..] {
+                    b"0" => false,
+                    b"1" => true,
+                    _ => return Err(CommandError::BitOutOfRange),
+                };
+                Ok(SetBit {
+                    key: key.0,
+                    offset,
+                    bit,
+                })
+            }
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key, offset or bit".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for GetBit {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getbit"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(offset))) => Ok(GetBit {
+                key: key.0,
+                offset: parse_bit_offset(&offset)?,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or offset".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for BitCount {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bitcount"])?;
+        if value.len() > 5 {
+            return Err(CommandError::SyntaxError);
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => key.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let range = match (args.next(), args.next(), args.next()) {
+            (None, _, _) => None,
+            (Some(RespFrame::BulkString(start)), Some(RespFrame::BulkString(end)), unit) => {
+                let unit = match unit {
+                    None => BitUnit::Byte,
+                    Some(RespFrame::BulkString(unit)) if unit.eq_ignore_ascii_case(b"byte") => {
+                        BitUnit::Byte
+                    }
+                    Some(RespFrame::BulkString(unit)) if unit.eq_ignore_ascii_case(b"bit") => {
+                        BitUnit::Bit
+                    }
+                    Some(_) => return Err(CommandError::SyntaxError),
+                };
+                Some((parse_integer(&start)?, parse_integer(&end)?, unit))
+            }
+            // a start needs an end
+            _ => return Err(CommandError::SyntaxError),
+        };
+        Ok(BitCount { key, range })
+    }
+}
+
+impl TryFrom<RespArray> for BitOp {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bitop"])?;
+
+        let mut args = extract_key_args(value, 1)?.into_iter();
+        let op = match args
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+            .as_slice()
+        {
+            b"and" => BitOperation::And,
+            b"or" => BitOperation::Or,
+            b"xor" => BitOperation::Xor,
+            b"not" => BitOperation::Not,
+            _ => return Err(CommandError::SyntaxError),
+        };
+        let dest = args.next().unwrap_or_default();
+        let keys = args.collect::<Vec<_>>();
+        if op == BitOperation::Not && keys.len() != 1 {
+            return Err(CommandError::BitOpNotSingleKey);
+        }
+        Ok(BitOp { op, dest, keys })
+    }
+}
+
+impl TryFrom<RespArray> for BitPos {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bitpos"])?;
+        if value.len() > 6 {
+            return Err(CommandError::SyntaxError);
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, bit) = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(bit))) => {
+                let bit = match parse_integer(&bit)? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(CommandError::BitNotBinary),
+                };
+                (key.0, bit)
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key or bit".to_string(),
+                ))
+            }
+        };
+        let integer = |arg: Option<RespFrame>| match arg {
+            Some(RespFrame::BulkString(value)) => parse_integer(&value).map(Some),
+            Some(_) => Err(CommandError::NotAnInteger),
+            None => Ok(None),
+        };
+        let start = integer(args.next())?.unwrap_or(0);
+        let end = integer(args.next())?;
+        let unit = match args.next() {
+            None => BitUnit::Byte,
+            Some(RespFrame::BulkString(unit)) if unit.eq_ignore_ascii_case(b"byte") => {
+                BitUnit::Byte
+            }
+            Some(RespFrame::BulkString(unit)) if unit.eq_ignore_ascii_case(b"bit") => BitUnit::Bit,
+            Some(_) => return Err(CommandError::SyntaxError),
+        };
+        Ok(BitPos {
+            key,
+            bit,
+            start,
+            end,
+            unit,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for BitField {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bitfield"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => key.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let mut next = || match args.next() {
+            Some(RespFrame::BulkString(arg)) => Ok(arg),
+            _ => Err(CommandError::SyntaxError),
+        };
+
+        let mut ops = Vec::new();
+        // OVERFLOW applies to the writes after it
+        let mut overflow = Overflow::default();
+        while let Ok(subcommand) = next() {
+            let subcommand = subcommand.to_ascii_lowercase();
+            if subcommand == b"overflow" {
+                overflow = match next()?.to_ascii_lowercase().as_slice() {
+                    b"wrap" => Overflow::Wrap,
+                    b"sat" => Overflow::Sat,
+                    b"fail" => Overflow::Fail,
+                    _ => return Err(CommandError::InvalidOverflow),
+                };
+                continue;
+            }
+            let ty = parse_bitfield_type(&next()?)?;
+            let offset = parse_bitfield_offset(&next()?, ty)?;
+            ops.push(match subcommand.as_slice() {
+                b"get" => BitFieldOp::Get(ty, offset),
+                b"set" => BitFieldOp::Set(ty, offset, parse_integer(&next()?)?, overflow),
+                b"incrby" => BitFieldOp::IncrBy(ty, offset, parse_integer(&next()?)?, overflow),
+                _ => return Err(CommandError::SyntaxError),
+            });
+        }
+        Ok(BitField { key, ops })
+    }
+}
+
+// `i` or `u` and a number of bits, e.g. `i16` or `u8`
+fn parse_bitfield_type(value: &[u8]) -> Result<BitFieldType, CommandError> {
+    let (signed, bits) = match value {
+        [b'i' | b'I', bits @ ..] => (true, bits),
+        [b'u' | b'U', bits @ ..] => (false, bits),
+        _ => return Err(CommandError::InvalidBitFieldType),
+    };
+    let bits = std::str::from_utf8(bits)
+        .ok()
+        .and_then(|bits| bits.parse::<u32>().ok())
+        .filter(|bits| (1..=if signed { 64 } else { 63 }).contains(bits))
+        .ok_or(CommandError::InvalidBitFieldType)?;
+    Ok(BitFieldType { signed, bits })
+}
+
+// a bit offset, or with a `#` prefix the index of a field of type `ty` counted from the start
+fn parse_bitfield_offset(value: &[u8], ty: BitFieldType) -> Result<usize, CommandError> {
+    match value.strip_prefix(b"#") {
+        Some(index) => parse_bit_offset(index)?
+            .checked_mul(ty.bits as usize)
+            .ok_or(CommandError::BitOffsetOutOfRange),
+        None => parse_bit_offset(value),
+    }
+}
+
+// a bit offset, which can't be negative
+fn parse_bit_offset(value: &[u8]) -> Result<usize, CommandError> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .ok_or(CommandError::BitOffsetOutOfRange)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::{BulkString, RespDecode, RespEncode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn run(backend: &crate::Backend, request: &[u8]) -> Result<RespFrame> {
+        let mut buf = BytesMut::from(request);
+        let cmd = crate::cmd::Command::try_from(RespFrame::decode(&mut buf)?)?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    #[test]
+    fn test_setbit_getbit() -> Result<()> {
+        let backend = crate::Backend::new();
+        let request = b"*4\r\n$6\r\nsetbit\r\n$3\r\nkey\r\n$1\r\n7\r\n$1\r\n1\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b":0\r\n");
+        assert_eq!(
+            backend.get(b"key"),
+            Some(BulkString::new(vec![0x01]).into())
+        );
+
+        // growing across byte boundaries pads with zero bytes
+        assert_eq!(
+            run_args(&backend, &["setbit", "key", "8", "1"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(&backend, &["setbit", "key", "23", "1"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            backend.get(b"key"),
+            Some(BulkString::new(vec![0x01, 0x80, 0x01]).into())
+        );
+        assert_eq!(
+            run_args(&backend, &["setbit", "key", "8", "0"])?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            backend.get(b"key"),
+            Some(BulkString::new(vec![0x01, 0x00, 0x01]).into())
+        );
+
+        for (offset, expected) in [
+            ("7", 1),
+            ("6", 0),
+            ("8", 0),
+            ("23", 1),
+            ("24", 0),
+            ("1000", 0),
+        ] {
+            assert_eq!(
+                run_args(&backend, &["getbit", "key", offset])?,
+                RespFrame::Integer(expected),
+                "{}",
+                offset
+            );
+        }
+        assert_eq!(
+            run_args(&backend, &["getbit", "missing", "0"])?,
+            RespFrame::Integer(0)
+        );
+
+        // a string set as text is a bitmap too, `a` is 0b0110_0001
+        backend.set("text".into(), BulkString::from("a").into());
+        assert_eq!(
+            run_args(&backend, &["getbit", "text", "1"])?,
+            RespFrame::Integer(1)
+        );
+        run_args(&backend, &["setbit", "text", "6", "1"])?;
+        assert_eq!(backend.get(b"text"), Some(BulkString::from("c").into()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_setbit_errors() -> Result<()> {
+        let backend = crate::Backend::new();
+        let err = run_args(&backend, &["setbit", "key", "4294967296", "1"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "bit offset is not an integer or out of range"
+        );
+        assert_eq!(backend.get(b"key"), None);
+        for args in [
+            &["setbit", "key", "-1", "1"][..],
+            &["setbit", "key", "x", "1"],
+            &["getbit", "key", "-1"],
+        ] {
+            let err = run_args(&backend, args).unwrap_err().to_string();
+            assert_eq!(err, "bit offset is not an integer or out of range");
+        }
+        for bit in ["2", "-1", "x"] {
+            let err = run_args(&backend, &["setbit", "key", "0", bit])
+                .unwrap_err()
+                .to_string();
+            assert_eq!(err, "bit is not an integer or out of range");
+        }
+
+        backend.hset(
+            "hash".into(),
+            "field".into(),
+            BulkString::from("value").into(),
+        )?;
+        assert!(run_args(&backend, &["setbit", "hash", "0", "1"]).is_err());
+        assert!(run_args(&backend, &["getbit", "hash", "0"]).is_err());
+        assert!(run_args(&backend, &["bitcount", "hash"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitop() -> Result<()> {
+        let backend = crate::Backend::new();
+        backend.set("a".into(), BulkString::new(vec![0b1100_1100, 0xff]).into());
+        backend.set("b".into(), BulkString::new(vec![0b1010_1010]).into());
+        let request = b"*5\r\n$5\r\nbitop\r\n$3\r\nAND\r\n$4\r\ndest\r\n$1\r\na\r\n$1\r\nb\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b":2\r\n");
+        // the shorter source is padded with zero bytes
+        for (op, expected) in [
+            ("and", vec![0b1000_1000, 0x00]),
+            ("OR", vec![0b1110_1110, 0xff]),
+            ("xor", vec![0b0110_0110, 0xff]),
+        ] {
+            assert_eq!(
+                run_args(&backend, &["bitop", op, "dest", "a", "b"])?,
+                RespFrame::Integer(2)
+            );
+            assert_eq!(
+                backend.get(b"dest"),
+                Some(BulkString::new(expected).into()),
+                "{}",
+                op
+            );
+        }
+        // so is a missing source
+        run_args(&backend, &["bitop", "or", "dest", "missing", "b"])?;
+        assert_eq!(
+            backend.get(b"dest"),
+            Some(BulkString::new(vec![0b1010_1010]).into())
+        );
+        run_args(&backend, &["bitop", "and", "dest", "a", "a", "missing"])?;
+        assert_eq!(
+            backend.get(b"dest"),
+            Some(BulkString::new(vec![0, 0]).into())
+        );
+
+        assert_eq!(
+            run_args(&backend, &["bitop", "not", "dest", "a"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(
+            backend.get(b"dest"),
+            Some(BulkString::new(vec![0b0011_0011, 0x00]).into())
+        );
+
+        // the destination is overwritten whatever its type, and deleted by an empty result
+        backend.del(&["dest".into()]);
+        backend.sadd("dest", "member")?;
+        run_args(&backend, &["bitop", "xor", "dest", "b", "b"])?;
+        assert_eq!(backend.get(b"dest"), Some(BulkString::new(vec![0]).into()));
+        assert_eq!(
+            run_args(&backend, &["bitop", "or", "dest", "missing"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(backend.key_type(b"dest"), None);
+
+        let err = run_args(&backend, &["bitop", "not", "dest", "a", "b"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "BITOP NOT must be called with a single source key."
+        );
+        assert!(run_args(&backend, &["bitop", "nand", "dest", "a"]).is_err());
+        assert!(run_args(&backend, &["bitop", "and", "dest"]).is_err());
+        backend.sadd("set", "member")?;
+        assert!(run_args(&backend, &["bitop", "and", "dest", "a", "set"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitpos() -> Result<()> {
+        let backend = crate::Backend::new();
+        backend.set("key".into(), BulkString::new(vec![0x00, 0x0f, 0xff]).into());
+        backend.set("ones".into(), BulkString::new(vec![0xff, 0xff]).into());
+        for (args, expected) in [
+            (&["bitpos", "key", "1"][..], 12),
+            (&["bitpos", "key", "0"], 0),
+            (&["bitpos", "key", "1", "2"], 16),
+            (&["bitpos", "key", "0", "1"], 8),
+            (&["bitpos", "key", "1", "-1"], 16),
+            (&["bitpos", "key", "1", "0", "0"], -1),
+            (&["bitpos", "key", "1", "3"], -1),
+            (&["bitpos", "key", "1", "5", "13", "BIT"], 12),
+            (&["bitpos", "key", "0", "13", "-1", "bit"], -1),
+            (&["bitpos", "key", "1", "-4", "-1", "bit"], 20),
+            (&["bitpos", "key", "0", "9", "11", "bit"], 9),
+            // looking for a 0 in ones finds the bit past the end, unless an end is given
+            (&["bitpos", "ones", "0"], 16),
+            (&["bitpos", "ones", "0", "1"], 16),
+            (&["bitpos", "ones", "0", "0", "-1"], -1),
+            (&["bitpos", "ones", "0", "0", "1", "byte"], -1),
+            (&["bitpos", "ones", "1", "1"], 8),
+            (&["bitpos", "missing", "0"], 0),
+            (&["bitpos", "missing", "1"], -1),
+        ] {
+            assert_eq!(
+                run_args(&backend, args)?,
+                RespFrame::Integer(expected),
+                "{:?}",
+                args
+            );
+        }
+
+        let err = run_args(&backend, &["bitpos", "key", "2"]).unwrap_err();
+        assert_eq!(err.to_string(), "The bit argument must be 1 or 0.");
+        for args in [
+            &["bitpos", "key"][..],
+            &["bitpos", "key", "x"],
+            &["bitpos", "key", "1", "a"],
+            &["bitpos", "key", "1", "0", "1", "bits"],
+            &["bitpos", "key", "1", "0", "1", "bit", "x"],
+        ] {
+            assert!(run_args(&backend, args).is_err(), "{:?}", args);
+        }
+        Ok(())
+    }
+
+    fn integers(values: &[Option<i64>]) -> RespFrame {
+        RespArray::new(
+            values
+                .iter()
+                .map(|value| match value {
+                    Some(value) => RespFrame::Integer(*value),
+                    None => RespFrame::NULL,
+                })
+                .collect::<Vec<_>>(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn test_bitfield_examples() -> Result<()> {
+        let backend = crate::Backend::new();
+        let request = b"*9\r\n$8\r\nbitfield\r\n$5\r\nmykey\r\n$6\r\nINCRBY\r\n$2\r\ni5\r\n$3\r\n100\r\n$1\r\n1\r\n$3\r\nGET\r\n$2\r\nu4\r\n$1\r\n0\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b"*2\r\n:1\r\n:0\r\n");
+
+        // `#` offsets count fields, 200 wraps around in an i8
+        assert_eq!(
+            run_args(
+                &backend,
+                &["bitfield", "mystring", "SET", "i8", "#0", "100", "SET", "i8", "#1", "200"]
+            )?,
+            integers(&[Some(0), Some(0)])
+        );
+        assert_eq!(
+            backend.get(b"mystring"),
+            Some(BulkString::new(vec![100, 200]).into())
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &["bitfield", "mystring", "GET", "u8", "#1", "GET", "i8", "8"]
+            )?,
+            integers(&[Some(200), Some(-56)])
+        );
+
+        // the overflow example: a u2 wrapping around next to a saturating one
+        let args = [
+            "bitfield", "counters", "incrby", "u2", "100", "1", "OVERFLOW", "SAT", "incrby", "u2",
+            "102", "1",
+        ];
+        for expected in [[1, 1], [2, 2], [3, 3], [0, 3]] {
+            assert_eq!(
+                run_args(&backend, &args)?,
+                integers(&[Some(expected[0]), Some(expected[1])])
+            );
+        }
+        assert_eq!(
+            run_args(
+                &backend,
+                &["bitfield", "counters", "OVERFLOW", "FAIL", "incrby", "u2", "102", "1"]
+            )?,
+            integers(&[None])
+        );
+        assert_eq!(
+            run_args(&backend, &["bitfield", "counters", "get", "u2", "102"])?,
+            integers(&[Some(3)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitfield_overflow() -> Result<()> {
+        let backend = crate::Backend::new();
+        // u8 wraparound
+        assert_eq!(
+            run_args(
+                &backend,
+                &["bitfield", "key", "set", "u8", "0", "255", "incrby", "u8", "0", "10"]
+            )?,
+            integers(&[Some(0), Some(9)])
+        );
+        // i8 saturation both ways
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "bitfield", "key", "overflow", "sat", "incrby", "i8", "0", "200", "incrby",
+                    "i8", "0", "-300", "set", "i8", "0", "1000"
+                ]
+            )?,
+            integers(&[Some(127), Some(-128), Some(-128)])
+        );
+        assert_eq!(backend.get(b"key"), Some(BulkString::new(vec![127]).into()));
+        // a failed write leaves the field as it was
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "bitfield", "key", "overflow", "fail", "set", "u4", "0", "16", "incrby", "i8",
+                    "0", "1", "overflow", "wrap", "incrby", "i8", "0", "1"
+                ]
+            )?,
+            integers(&[None, None, Some(-128)])
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &["bitfield", "key", "incrby", "i64", "8", "-1", "incrby", "u63", "#2", "-1"]
+            )?,
+            integers(&[Some(-1), Some(i64::MAX)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitfield_growth_and_errors() -> Result<()> {
+        let backend = crate::Backend::new();
+        // reading alone doesn't create the key, writing grows it to the last field written
+        assert_eq!(
+            run_args(&backend, &["bitfield", "key", "get", "u8", "0"])?,
+            integers(&[Some(0)])
+        );
+        assert_eq!(backend.get(b"key"), None);
+        run_args(&backend, &["bitfield", "key", "set", "u4", "20", "15"])?;
+        assert_eq!(
+            backend.get(b"key"),
+            Some(BulkString::new(vec![0, 0, 0x0f]).into())
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &["bitfield", "key", "get", "u16", "16", "get", "i4", "20"]
+            )?,
+            integers(&[Some(0x0f00), Some(-1)])
+        );
+
+        for (args, error) in [
+            (
+                &["bitfield", "key", "get", "u64", "0"][..],
+                "Invalid bitfield type",
+            ),
+            (
+                &["bitfield", "key", "get", "i65", "0"],
+                "Invalid bitfield type",
+            ),
+            (
+                &["bitfield", "key", "get", "i0", "0"],
+                "Invalid bitfield type",
+            ),
+            (
+                &["bitfield", "key", "get", "x8", "0"],
+                "Invalid bitfield type",
+            ),
+            (
+                &["bitfield", "key", "get", "u8", "-1"],
+                "bit offset is not an integer",
+            ),
+            (
+                &["bitfield", "key", "get", "u8", "#x"],
+                "bit offset is not an integer",
+            ),
+            (
+                &["bitfield", "key", "set", "u8", "4294967290", "1"],
+                "bit offset is not an integer",
+            ),
+            (
+                &["bitfield", "key", "set", "u8", "0", "x"],
+                "not an integer",
+            ),
+            (
+                &["bitfield", "key", "overflow", "clamp"],
+                "Invalid OVERFLOW type",
+            ),
+            (&["bitfield", "key", "get", "u8"], "syntax error"),
+            (&["bitfield", "key", "del", "u8", "0"], "syntax error"),
+            (&["bitfield", "key", "set", "u8", "0"], "syntax error"),
+        ] {
+            let err = run_args(&backend, args).unwrap_err().to_string();
+            assert!(err.contains(error), "{:?}: {}", args, err);
+        }
+        assert_eq!(
+            backend.get(b"key"),
+            Some(BulkString::new(vec![0, 0, 0x0f]).into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitcount() -> Result<()> {
+        let backend = crate::Backend::new();
+        backend.set("key".into(), BulkString::from("foobar").into());
+        for (args, expected) in [
+            (&["bitcount", "key"][..], 26),
+            (&["bitcount", "key", "0", "0"], 4),
+            (&["bitcount", "key", "1", "1"], 6),
+            (&["bitcount", "key", "1", "1", "BYTE"], 6),
+            (&["bitcount", "key", "-2", "-1"], 7),
+            (&["bitcount", "key", "-100", "100"], 26),
+            (&["bitcount", "key", "3", "1"], 0),
+            // `f` is 0b0110_0110 and `o` 0b0110_1111
+            (&["bitcount", "key", "5", "30", "bit"], 17),
+            (&["bitcount", "key", "0", "7", "BIT"], 4),
+            (&["bitcount", "key", "1", "2", "bit"], 2),
+            (&["bitcount", "key", "7", "8", "bit"], 0),
+            (&["bitcount", "key", "6", "9", "bit"], 2),
+            (&["bitcount", "key", "-8", "-1", "bit"], 4),
+            (&["bitcount", "key", "47", "100", "bit"], 0),
+            (&["bitcount", "missing"], 0),
+            (&["bitcount", "missing", "0", "-1", "bit"], 0),
+        ] {
+            assert_eq!(
+                run_args(&backend, args)?,
+                RespFrame::Integer(expected),
+                "{:?}",
+                args
+            );
+        }
+
+        for args in [
+            &["bitcount", "key", "0"][..],
+            &["bitcount", "key", "0", "1", "bits"],
+            &["bitcount", "key", "0", "1", "bit", "x"],
+            &["bitcount", "key", "a", "1"],
+        ] {
+            assert!(run_args(&backend, args).is_err(), "{:?}", args);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1016 @@
+use super::{
+    extract_string_args, validate_command, validate_subcommand, validate_variadic_subcommand,
+    CommandCount, CommandDocs, CommandError, CommandExecutor, CommandInfo, CommandList,
+};
+use crate::{BulkString, RespArray, RespFrame, RespMap, SimpleString};
+use bytes::Bytes;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+/// What `COMMAND` reports about a command. The dispatcher checks the arity here before a command
+/// is parsed, the parsers only check that of their subcommands.
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// like redis, counting the name itself: exactly this many if positive, at least the absolute
+    /// value if negative
+    pub arity: i64,
+    pub flags: &'static [&'static str],
+    /// the position of the first key, of the last key (negative counting from the end), and the
+    /// step between keys. All zeros for commands without keys, or whose keys can't be told from
+    /// their position alone.
+    pub keys: (i64, i64, i64),
+    pub group: &'static str,
+}
+
+const fn spec(
+    name: &'static str,
+    arity: i64,
+    flags: &'static [&'static str],
+    keys: (i64, i64, i64),
+    group: &'static str,
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        arity,
+        flags,
+        keys,
+        group,
+    }
+}
+
+// every command the dispatcher knows, commands with subcommands are listed once
+pub static COMMANDS: &[CommandSpec] = &[
+    spec("get", 2, &["readonly", "fast"], (1, 1, 1), "string"),
+    spec("set", -3, &["write", "denyoom"], (1, 1, 1), "string"),
+    spec("echo", 2, &["fast"], (0, 0, 0), "connection"),
+    spec("ping", -1, &["fast"], (0, 0, 0), "connection"),
+    spec("hget", 3, &["readonly", "fast"], (1, 1, 1), "hash"),
+    spec("hset", -4, &["write", "denyoom", "fast"], (1, 1, 1), "hash"),
+    spec("hgetall", 2, &["readonly"], (1, 1, 1), "hash"),
+    spec("hmget", -3, &["readonly", "fast"], (1, 1, 1), "hash"),
+    spec("hdel", -3, &["write", "fast"], (1, 1, 1), "hash"),
+    spec("hexists", 3, &["readonly", "fast"], (1, 1, 1), "hash"),
+    spec("hlen", 2, &["readonly", "fast"], (1, 1, 1), "hash"),
+    spec("hkeys", 2, &["readonly"], (1, 1, 1), "hash"),
+    spec(
+        "hmset",
+        -4,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "hash",
+    ),
+    spec(
+        "hincrby",
+        4,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "hash",
+    ),
+    spec(
+        "hincrbyfloat",
+        4,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "hash",
+    ),
+    spec(
+        "hsetnx",
+        4,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "hash",
+    ),
+    spec("hrandfield", -2, &["readonly"], (1, 1, 1), "hash"),
+    spec("hscan", -3, &["readonly"], (1, 1, 1), "hash"),
+    spec("hstrlen", 3, &["readonly", "fast"], (1, 1, 1), "hash"),
+    spec("hvals", 2, &["readonly"], (1, 1, 1), "hash"),
+    spec("sadd", -3, &["write", "denyoom", "fast"], (1, 1, 1), "set"),
+    spec("sismember", 3, &["readonly", "fast"], (1, 1, 1), "set"),
+    spec("sscan", -3, &["readonly"], (1, 1, 1), "set"),
+    spec("srem", -3, &["write", "fast"], (1, 1, 1), "set"),
+    spec("scard", 2, &["readonly", "fast"], (1, 1, 1), "set"),
+    spec("smembers", 2, &["readonly"], (1, 1, 1), "set"),
+    spec("spop", -2, &["write", "fast"], (1, 1, 1), "set"),
+    spec("srandmember", -2, &["readonly"], (1, 1, 1), "set"),
+    spec("smove", 4, &["write", "fast"], (1, 2, 1), "set"),
+    spec(
+        "sintercard",
+        -3,
+        &["readonly", "movablekeys"],
+        (0, 0, 0),
+        "set",
+    ),
+    spec("smismember", -3, &["readonly", "fast"], (1, 1, 1), "set"),
+    spec("sinter", -2, &["readonly"], (1, -1, 1), "set"),
+    spec("sinterstore", -3, &["write", "denyoom"], (1, -1, 1), "set"),
+    spec("sunion", -2, &["readonly"], (1, -1, 1), "set"),
+    spec("sunionstore", -3, &["write", "denyoom"], (1, -1, 1), "set"),
+    spec("sdiff", -2, &["readonly"], (1, -1, 1), "set"),
+    spec("sdiffstore", -3, &["write", "denyoom"], (1, -1, 1), "set"),
+    spec("del", -2, &["write"], (1, -1, 1), "generic"),
+    spec("exists", -2, &["readonly", "fast"], (1, -1, 1), "generic"),
+    spec("expire", -3, &["write", "fast"], (1, 1, 1), "generic"),
+    spec("pexpire", -3, &["write", "fast"], (1, 1, 1), "generic"),
+    spec("expireat", -3, &["write", "fast"], (1, 1, 1), "generic"),
+    spec("pexpireat", -3, &["write", "fast"], (1, 1, 1), "generic"),
+    spec("ttl", 2, &["readonly", "fast"], (1, 1, 1), "generic"),
+    spec("pttl", 2, &["readonly", "fast"], (1, 1, 1), "generic"),
+    spec("persist", 2, &["write", "fast"], (1, 1, 1), "generic"),
+    spec(
+        "incr",
+        2,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "string",
+    ),
+    spec(
+        "decr",
+        2,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "string",
+    ),
+    spec(
+        "incrby",
+        3,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "string",
+    ),
+    spec(
+        "decrby",
+        3,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "string",
+    ),
+    spec(
+        "incrbyfloat",
+        3,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "string",
+    ),
+    spec("mget", -2, &["readonly", "fast"], (1, -1, 1), "string"),
+    spec("mset", -3, &["write", "denyoom"], (1, -1, 2), "string"),
+    spec("msetnx", -3, &["write", "denyoom"], (1, -1, 2), "string"),
+    spec(
+        "append",
+        3,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "string",
+    ),
+    spec("strlen", 2, &["readonly", "fast"], (1, 1, 1), "string"),
+    spec("getrange", 4, &["readonly"], (1, 1, 1), "string"),
+    spec("setrange", 4, &["write", "denyoom"], (1, 1, 1), "string"),
+    spec(
+        "getset",
+        3,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "string",
+    ),
+    spec("getdel", 2, &["write", "fast"], (1, 1, 1), "string"),
+    spec("getex", -2, &["write", "fast"], (1, 1, 1), "string"),
+    spec(
+        "setnx",
+        3,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "string",
+    ),
+    spec("setex", 4, &["write", "denyoom"], (1, 1, 1), "string"),
+    spec("type", 2, &["readonly", "fast"], (1, 1, 1), "generic"),
+    spec("keys", 2, &["readonly"], (0, 0, 0), "generic"),
+    spec("scan", -2, &["readonly"], (0, 0, 0), "generic"),
+    spec("dbsize", 1, &["readonly", "fast"], (0, 0, 0), "server"),
+    spec("randomkey", 1, &["readonly"], (0, 0, 0), "generic"),
+    spec("rename", 3, &["write"], (1, 2, 1), "generic"),
+    spec("renamenx", 3, &["write", "fast"], (1, 2, 1), "generic"),
+    spec("copy", -3, &["write", "denyoom"], (1, 2, 1), "generic"),
+    spec("dump", 2, &["readonly"], (1, 1, 1), "generic"),
+    spec("restore", -4, &["write", "denyoom"], (1, 1, 1), "generic"),
+    spec("touch", -2, &["readonly", "fast"], (1, -1, 1), "generic"),
+    spec("unlink", -2, &["write", "fast"], (1, -1, 1), "generic"),
+    spec("memory", 3, &["readonly"], (2, 2, 1), "server"),
+    spec("object", 3, &["readonly"], (2, 2, 1), "generic"),
+    spec(
+        "lpush",
+        -3,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "list",
+    ),
+    spec(
+        "rpush",
+        -3,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "list",
+    ),
+    spec(
+        "lpushx",
+        -3,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "list",
+    ),
+    spec(
+        "rpushx",
+        -3,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "list",
+    ),
+    spec("lpop", -2, &["write", "fast"], (1, 1, 1), "list"),
+    spec("rpop", -2, &["write", "fast"], (1, 1, 1), "list"),
+    spec("llen", 2, &["readonly", "fast"], (1, 1, 1), "list"),
+    spec("lrange", 4, &["readonly"], (1, 1, 1), "list"),
+    spec("lindex", 3, &["readonly"], (1, 1, 1), "list"),
+    spec("lset", 4, &["write", "denyoom"], (1, 1, 1), "list"),
+    spec("lrem", 4, &["write"], (1, 1, 1), "list"),
+    spec("ltrim", 4, &["write"], (1, 1, 1), "list"),
+    spec("linsert", 5, &["write", "denyoom"], (1, 1, 1), "list"),
+    spec("lpos", -3, &["readonly"], (1, 1, 1), "list"),
+    spec("lmove", 5, &["write", "denyoom"], (1, 2, 1), "list"),
+    spec("rpoplpush", 3, &["write", "denyoom"], (1, 2, 1), "list"),
+    spec("blpop", -3, &["write", "blocking"], (1, -2, 1), "list"),
+    spec("brpop", -3, &["write", "blocking"], (1, -2, 1), "list"),
+    spec(
+        "zadd",
+        -4,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "sorted_set",
+    ),
+    spec("zscore", 3, &["readonly", "fast"], (1, 1, 1), "sorted_set"),
+    spec("zrange", -4, &["readonly"], (1, 1, 1), "sorted_set"),
+    spec("zrevrange", -4, &["readonly"], (1, 1, 1), "sorted_set"),
+    spec("zrangebyscore", -4, &["readonly"], (1, 1, 1), "sorted_set"),
+    spec("zcount", 4, &["readonly", "fast"], (1, 1, 1), "sorted_set"),
+    spec("zrem", -3, &["write", "fast"], (1, 1, 1), "sorted_set"),
+    spec("zcard", 2, &["readonly", "fast"], (1, 1, 1), "sorted_set"),
+    spec(
+        "zincrby",
+        4,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "sorted_set",
+    ),
+    spec("zrank", -3, &["readonly", "fast"], (1, 1, 1), "sorted_set"),
+    spec(
+        "zrevrank",
+        -3,
+        &["readonly", "fast"],
+        (1, 1, 1),
+        "sorted_set",
+    ),
+    spec("zpopmin", -2, &["write", "fast"], (1, 1, 1), "sorted_set"),
+    spec("zpopmax", -2, &["write", "fast"], (1, 1, 1), "sorted_set"),
+    spec("zrangebylex", -4, &["readonly"], (1, 1, 1), "sorted_set"),
+    spec(
+        "zlexcount",
+        4,
+        &["readonly", "fast"],
+        (1, 1, 1),
+        "sorted_set",
+    ),
+    spec(
+        "zunionstore",
+        -4,
+        &["write", "denyoom", "movablekeys"],
+        (1, 1, 1),
+        "sorted_set",
+    ),
+    spec(
+        "zinterstore",
+        -4,
+        &["write", "denyoom", "movablekeys"],
+        (1, 1, 1),
+        "sorted_set",
+    ),
+    spec("zscan", -3, &["readonly"], (1, 1, 1), "sorted_set"),
+    spec(
+        "zmscore",
+        -3,
+        &["readonly", "fast"],
+        (1, 1, 1),
+        "sorted_set",
+    ),
+    spec(
+        "zrangestore",
+        -5,
+        &["write", "denyoom"],
+        (1, 2, 1),
+        "sorted_set",
+    ),
+    spec("zrandmember", -2, &["readonly"], (1, 1, 1), "sorted_set"),
+    spec("setbit", 4, &["write", "denyoom"], (1, 1, 1), "bitmap"),
+    spec("getbit", 3, &["readonly", "fast"], (1, 1, 1), "bitmap"),
+    spec("bitcount", -2, &["readonly"], (1, 1, 1), "bitmap"),
+    spec("bitop", -4, &["write", "denyoom"], (2, -1, 1), "bitmap"),
+    spec("bitpos", -3, &["readonly"], (1, 1, 1), "bitmap"),
+    spec("bitfield", -2, &["write", "denyoom"], (1, 1, 1), "bitmap"),
+    spec(
+        "pfadd",
+        -2,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "hyperloglog",
+    ),
+    spec("pfcount", -2, &["readonly"], (1, -1, 1), "hyperloglog"),
+    spec(
+        "pfmerge",
+        -2,
+        &["write", "denyoom"],
+        (1, -1, 1),
+        "hyperloglog",
+    ),
+    spec("geoadd", -5, &["write", "denyoom"], (1, 1, 1), "geo"),
+    spec("geopos", -2, &["readonly"], (1, 1, 1), "geo"),
+    spec("geodist", -4, &["readonly"], (1, 1, 1), "geo"),
+    spec("geosearch", -7, &["readonly"], (1, 1, 1), "geo"),
+    spec(
+        "xadd",
+        -5,
+        &["write", "denyoom", "fast"],
+        (1, 1, 1),
+        "stream",
+    ),
+    spec("xlen", 2, &["readonly", "fast"], (1, 1, 1), "stream"),
+    spec("xrange", -4, &["readonly"], (1, 1, 1), "stream"),
+    spec(
+        "xread",
+        -4,
+        &["readonly", "blocking", "movablekeys"],
+        (0, 0, 0),
+        "stream",
+    ),
+    spec("xdel", -3, &["write", "fast"], (1, 1, 1), "stream"),
+    spec("xtrim", -4, &["write"], (1, 1, 1), "stream"),
+    spec("xgroup", -5, &["write"], (2, 2, 1), "stream"),
+    spec(
+        "xreadgroup",
+        -7,
+        &["write", "movablekeys"],
+        (0, 0, 0),
+        "stream",
+    ),
+    spec("xack", -4, &["write", "fast"], (1, 1, 1), "stream"),
+    spec("xpending", -3, &["readonly"], (1, 1, 1), "stream"),
+    spec("command", -1, &["loading", "stale"], (0, 0, 0), "server"),
+    spec("info", -1, &["loading", "stale"], (0, 0, 0), "server"),
+    spec("flushdb", -1, &["write"], (0, 0, 0), "server"),
+    spec("flushall", -1, &["write"], (0, 0, 0), "server"),
+    spec(
+        "select",
+        2,
+        &["loading", "stale", "fast"],
+        (0, 0, 0),
+        "connection",
+    ),
+    spec("swapdb", 3, &["write", "fast"], (0, 0, 0), "server"),
+    spec(
+        "config",
+        -2,
+        &["admin", "noscript", "loading", "stale"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "shutdown",
+        -1,
+        &["admin", "noscript", "loading", "stale"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "debug",
+        -2,
+        &["admin", "noscript", "loading", "stale"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "slowlog",
+        -2,
+        &["admin", "random", "loading", "stale"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "time",
+        1,
+        &["loading", "stale", "fast"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "lastsave",
+        1,
+        &["loading", "stale", "fast"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "save",
+        1,
+        &["admin", "noscript", "no_async_loading", "no_multi"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "bgsave",
+        -1,
+        &["admin", "noscript", "no_async_loading"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "bgrewriteaof",
+        1,
+        &["admin", "noscript", "no_async_loading"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec("wait", 3, &["noscript"], (0, 0, 0), "generic"),
+    spec(
+        "replicaof",
+        3,
+        &["admin", "noscript", "stale", "no_async_loading"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "replconf",
+        -1,
+        &["admin", "noscript", "loading", "stale", "allow_busy"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "psync",
+        -3,
+        &["admin", "noscript", "no_async_loading", "no_multi"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "sync",
+        1,
+        &["admin", "noscript", "no_async_loading", "no_multi"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "failover",
+        -1,
+        &["admin", "noscript", "stale"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec("lolwut", -1, &["readonly", "fast"], (0, 0, 0), "server"),
+    spec(
+        "auth",
+        -2,
+        &["noscript", "loading", "stale", "fast", "no_auth"],
+        (0, 0, 0),
+        "connection",
+    ),
+    spec(
+        "hello",
+        -1,
+        &[
+            "noscript",
+            "loading",
+            "stale",
+            "fast",
+            "no_auth",
+            "allow_busy",
+        ],
+        (0, 0, 0),
+        "connection",
+    ),
+    spec(
+        "quit",
+        -1,
+        &["loading", "stale", "fast", "no_auth"],
+        (0, 0, 0),
+        "connection",
+    ),
+    spec(
+        "reset",
+        1,
+        &[
+            "noscript",
+            "loading",
+            "stale",
+            "fast",
+            "no_auth",
+            "allow_busy",
+        ],
+        (0, 0, 0),
+        "connection",
+    ),
+    spec(
+        "multi",
+        1,
+        &["noscript", "loading", "stale", "fast", "allow_busy"],
+        (0, 0, 0),
+        "transactions",
+    ),
+    spec(
+        "exec",
+        1,
+        &["noscript", "loading", "stale", "skip_slowlog"],
+        (0, 0, 0),
+        "transactions",
+    ),
+    spec(
+        "discard",
+        1,
+        &["noscript", "loading", "stale", "fast", "allow_busy"],
+        (0, 0, 0),
+        "transactions",
+    ),
+    spec(
+        "watch",
+        -2,
+        &["noscript", "loading", "stale", "fast", "allow_busy"],
+        (1, -1, 1),
+        "transactions",
+    ),
+    spec(
+        "unwatch",
+        1,
+        &["noscript", "loading", "stale", "fast", "allow_busy"],
+        (0, 0, 0),
+        "transactions",
+    ),
+    spec(
+        "subscribe",
+        -2,
+        &["pubsub", "noscript", "loading", "stale"],
+        (0, 0, 0),
+        "pubsub",
+    ),
+    spec(
+        "unsubscribe",
+        -1,
+        &["pubsub", "noscript", "loading", "stale"],
+        (0, 0, 0),
+        "pubsub",
+    ),
+    spec(
+        "psubscribe",
+        -2,
+        &["pubsub", "noscript", "loading", "stale"],
+        (0, 0, 0),
+        "pubsub",
+    ),
+    spec(
+        "punsubscribe",
+        -1,
+        &["pubsub", "noscript", "loading", "stale"],
+        (0, 0, 0),
+        "pubsub",
+    ),
+    spec(
+        "pubsub",
+        -2,
+        &["pubsub", "loading", "stale"],
+        (0, 0, 0),
+        "pubsub",
+    ),
+    spec(
+        "publish",
+        3,
+        &["pubsub", "loading", "stale", "fast"],
+        (0, 0, 0),
+        "pubsub",
+    ),
+    spec(
+        "eval",
+        -3,
+        &["noscript", "stale", "skip_monitor", "movablekeys"],
+        (0, 0, 0),
+        "scripting",
+    ),
+    spec(
+        "evalsha",
+        -3,
+        &["noscript", "stale", "skip_monitor", "movablekeys"],
+        (0, 0, 0),
+        "scripting",
+    ),
+    spec("script", -2, &["noscript"], (0, 0, 0), "scripting"),
+    spec(
+        "monitor",
+        1,
+        &["admin", "noscript", "loading", "stale"],
+        (0, 0, 0),
+        "server",
+    ),
+    spec(
+        "client",
+        -2,
+        &["admin", "noscript", "loading", "stale"],
+        (0, 0, 0),
+        "connection",
+    ),
+];
+
+lazy_static! {
+    static ref BY_NAME: HashMap<&'static str, &'static CommandSpec> =
+        COMMANDS.iter().map(|spec| (spec.name, spec)).collect();
+}
+
+impl CommandSpec {
+    /// The spec of a command by its lowercase name.
+    pub fn lookup(name: &[u8]) -> Option<&'static CommandSpec> {
+        BY_NAME.get(std::str::from_utf8(name).ok()?).copied()
+    }
+
+    /// Whether the command has `flag`, e.g. `denyoom` for those refused over `maxmemory`.
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// Whether a command of `len` frames, the name included, has the right number of arguments.
+    pub fn accepts(&self, len: usize) -> bool {
+        let len = len as i64;
+        if self.arity < 0 {
+            len >= -self.arity
+        } else {
+            len == self.arity
+        }
+    }
+
+    // the keys a command with `flag` (`readonly` or `write`) has in `args`, by the key
+    // positions, or past the `STREAMS` of `XREAD` and `XREADGROUP`. Other commands without
+    // positions, and those without the flag, have none.
+    fn keys_of(&self, args: &RespArray, flag: &str) -> Vec<Bytes> {
+        let (first, last, step) = self.keys;
+        if !self.flags.contains(&flag) || !self.accepts(args.len()) {
+            return Vec::new();
+        }
+        if matches!(self.name, "xread" | "xreadgroup") {
+            return stream_keys(args);
+        }
+        if first <= 0 {
+            return Vec::new();
+        }
+        let last = if last < 0 {
+            args.len() as i64 + last
+        } else {
+            last
+        };
+        (first..=last)
+            .step_by(step as usize)
+            .filter_map(|i| match args.get(i as usize) {
+                Some(RespFrame::BulkString(key)) => Some(Bytes::copy_from_slice(key)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // the `COMMAND INFO` reply for this command
+    fn info(&self) -> RespFrame {
+        let flags = self
+            .flags
+            .iter()
+            .map(|flag| SimpleString::new(*flag).into())
+            .collect::<Vec<RespFrame>>();
+        let (first, last, step) = self.keys;
+        RespArray::new(vec![
+            BulkString::from(self.name).into(),
+            RespFrame::Integer(self.arity),
+            RespArray::new(flags).into(),
+            RespFrame::Integer(first),
+            RespFrame::Integer(last),
+            RespFrame::Integer(step),
+        ])
+        .into()
+    }
+
+    // the `COMMAND DOCS` reply for this command, only the group it belongs to
+    fn docs(&self) -> RespFrame {
+        let mut docs = RespMap::new();
+        docs.insert("group".into(), BulkString::from(self.group).into());
+        docs.into()
+    }
+}
+
+// the keys of `XREAD` or `XREADGROUP`: the first half of what follows `STREAMS`, the second
+// half being their ids. The options before it are skipped with their values, for a group or a
+// consumer named `streams` not to be taken for it
+fn stream_keys(args: &RespArray) -> Vec<Bytes> {
+    let mut i = 1;
+    while let Some(RespFrame::BulkString(arg)) = args.get(i) {
+        i += match arg.to_ascii_lowercase().as_slice() {
+            b"streams" => break,
+            b"group" => 3,
+            b"count" | b"block" => 2,
+            _ => 1,
+        };
+    }
+    let streams = args.len().saturating_sub(i + 1);
+    if streams == 0 || !streams.is_multiple_of(2) {
+        return Vec::new();
+    }
+    args[i + 1..i + 1 + streams / 2]
+        .iter()
+        .filter_map(|arg| match arg {
+            RespFrame::BulkString(key) => Some(Bytes::copy_from_slice(key)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The keys the command in `args` reads, which count as keyspace hits or misses.
+pub(super) fn read_keys(args: &RespArray) -> Vec<Bytes> {
+    spec_of(args).map_or_else(Vec::new, |spec| spec.keys_of(args, "readonly"))
+}
+
+/// The keys the write command in `args` may change, none for other commands.
+pub(super) fn write_keys(args: &RespArray) -> Vec<Bytes> {
+    spec_of(args).map_or_else(Vec::new, |spec| spec.keys_of(args, "write"))
+}
+
+/// The entry of the command in `args`, if it is a known one.
+pub(super) fn spec_of(args: &RespArray) -> Option<&'static CommandSpec> {
+    match args.first() {
+        Some(RespFrame::BulkString(name)) => CommandSpec::lookup(&name.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+impl CommandExecutor for CommandList {
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespArray::new(COMMANDS.iter().map(CommandSpec::info).collect::<Vec<_>>()).into())
+    }
+}
+
+impl CommandExecutor for CommandCount {
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(COMMANDS.len() as i64))
+    }
+}
+
+impl CommandExecutor for CommandInfo {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        // without names, every command
+        if self.names.is_empty() {
+            return CommandList.execute(backend);
+        }
+        let infos = self
+            .names
+            .iter()
+            .map(
+                |name| match CommandSpec::lookup(name.to_ascii_lowercase().as_bytes()) {
+                    Some(spec) => spec.info(),
+                    None => RespFrame::NULL_ARRAY,
+                },
+            )
+            .collect::<Vec<_>>();
+        Ok(RespArray::new(infos).into())
+    }
+}
+
+impl CommandExecutor for CommandDocs {
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let mut docs = RespMap::new();
+        if self.names.is_empty() {
+            for spec in COMMANDS {
+                docs.insert(spec.name.into(), spec.docs());
+            }
+        }
+        // unknown names are left out
+        for name in self.names {
+            if let Some(spec) = CommandSpec::lookup(name.to_ascii_lowercase().as_bytes()) {
+                docs.insert(name.into(), spec.docs());
+            }
+        }
+        Ok(docs.into())
+    }
+}
+
+impl TryFrom<RespArray> for CommandList {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["command"])?;
+        Ok(CommandList)
+    }
+}
+
+impl TryFrom<RespArray> for CommandCount {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_subcommand(&value, &["command", "count"], 0)?;
+        Ok(CommandCount)
+    }
+}
+
+impl TryFrom<RespArray> for CommandInfo {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_subcommand(&value, &["command", "info"], 0)?;
+        Ok(CommandInfo {
+            names: extract_string_args(value, 2)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for CommandDocs {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_subcommand(&value, &["command", "docs"], 0)?;
+        Ok(CommandDocs {
+            names: extract_string_args(value, 2)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::{cmd::Command, Backend, RespEncode};
+    use anyhow::Result;
+
+    // whether the parser ran out of arguments: the arity errors of the subcommands, and what
+    // the parsers reply when an argument they want isn't there
+    fn is_missing_arguments(result: &Result<Command, CommandError>) -> bool {
+        matches!(
+            result,
+            Err(CommandError::WrongArity(_) | CommandError::InvalidArgument(_))
+        )
+    }
+
+    #[test]
+    fn test_table_matches_parsers() {
+        // the subcommand to reach the parser of the commands that have one
+        let subcommands = HashMap::from([
+            ("memory", "usage"),
+            ("object", "encoding"),
+            ("xgroup", "create"),
+            ("config", "resetstat"),
+            ("client", "id"),
+            ("debug", "help"),
+            ("slowlog", "len"),
+            ("pubsub", "numpat"),
+            ("script", "flush"),
+        ]);
+        let request = |spec: &CommandSpec, len: usize| {
+            let mut args = vec![BulkString::from(spec.name).into()];
+            args.extend(
+                subcommands
+                    .get(spec.name)
+                    .map(|sub| BulkString::from(*sub).into()),
+            );
+            while args.len() < len {
+                args.push(BulkString::from("1").into());
+            }
+            args.truncate(len.max(1));
+            RespArray::new(args)
+        };
+        let parse = |spec: &CommandSpec, len: usize| {
+            super::super::parse_command(spec.name.as_bytes(), request(spec, len))
+        };
+
+        // the parsers don't check the arity, the table is all there is to it
+        for spec in COMMANDS {
+            let min = spec.arity.unsigned_abs() as usize;
+            for len in [(min - 1).max(1), min + 1, min + 2] {
+                if spec.accepts(len) {
+                    continue;
+                }
+                assert!(
+                    matches!(
+                        Command::try_from(request(spec, len)),
+                        Err(CommandError::WrongArity(name)) if name == spec.name
+                    ),
+                    "{} should be refused {} arguments",
+                    spec.name,
+                    len.saturating_sub(1)
+                );
+            }
+            // with as few arguments as the table allows the parser finds every one it needs,
+            // and with more it doesn't panic
+            assert!(
+                !is_missing_arguments(&parse(spec, min)),
+                "{} should need more than {} arguments",
+                spec.name,
+                min - 1
+            );
+            if spec.arity < 0 {
+                parse(spec, min + 1).ok();
+                parse(spec, min + 2).ok();
+            }
+        }
+    }
+
+    #[test]
+    fn test_dispatcher_checks_arity() -> Result<()> {
+        let backend = Backend::new();
+        for args in [
+            &["get"][..],
+            &["get", "a", "b"],
+            &["GET"],
+            &["hset", "k", "f"],
+        ] {
+            let err = run_args(&backend, args).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "wrong number of arguments for '{}' command",
+                    args[0].to_lowercase()
+                )
+            );
+        }
+        // the name is case insensitive
+        run_args(&backend, &["SET", "key", "value"])?;
+        assert_eq!(
+            run_args(&backend, &["Get", "key"])?,
+            BulkString::from("value").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_keys() {
+        let keys = |args: &[&str]| {
+            let args = crate::cmd::request_args(args);
+            (read_keys(&args), write_keys(&args))
+        };
+        let streams = vec![Bytes::from("s1"), Bytes::from("s2")];
+        assert_eq!(
+            keys(&["xread", "COUNT", "2", "streams", "s1", "s2", "0", "$"]),
+            (streams.clone(), Vec::new())
+        );
+        assert_eq!(
+            keys(&[
+                "XREADGROUP",
+                "group",
+                "streams",
+                "STREAMS",
+                "noack",
+                "streams",
+                "s1",
+                "s2",
+                ">",
+                ">",
+            ]),
+            (Vec::new(), streams)
+        );
+        // without as many ids as keys, there's no telling the keys
+        assert_eq!(
+            keys(&["xread", "streams", "s1", "s2", "0"]),
+            (Vec::new(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_command() -> Result<()> {
+        let backend = Backend::new();
+        let RespFrame::Array(all) = run_args(&backend, &["command"])? else {
+            panic!("expected an array");
+        };
+        assert_eq!(all.len(), COMMANDS.len());
+        assert_eq!(
+            run_args(&backend, &["COMMAND", "COUNT"])?,
+            RespFrame::Integer(COMMANDS.len() as i64)
+        );
+
+        let info = run_args(&backend, &["command", "info", "GET", "nope", "mset"])?;
+        assert_eq!(
+            info.encode(),
+            b"*3\r\n\
+              *6\r\n$3\r\nget\r\n:2\r\n*2\r\n+readonly\r\n+fast\r\n:1\r\n:1\r\n:1\r\n\
+              _\r\n\
+              *6\r\n$4\r\nmset\r\n:-3\r\n*2\r\n+write\r\n+denyoom\r\n:1\r\n:-1\r\n:2\r\n"
+        );
+        assert_eq!(run_args(&backend, &["command", "info"])?, all.into());
+
+        // this is what redis-cli asks for when it connects
+        let RespFrame::Map(docs) = run_args(&backend, &["COMMAND", "DOCS"])? else {
+            panic!("expected a map");
+        };
+        assert_eq!(docs.len(), COMMANDS.len());
+        assert_eq!(
+            run_args(&backend, &["command", "docs", "zadd", "nope"])?.encode(),
+            b"%1\r\n$4\r\nzadd\r\n%1\r\n$5\r\ngroup\r\n$10\r\nsorted_set\r\n"
+        );
+
+        assert!(run_args(&backend, &["command", "count", "1"]).is_err());
+        assert_eq!(
+            run_args(&backend, &["command", "nope"])
+                .unwrap_err()
+                .to_string(),
+            "unknown subcommand 'nope'. Try COMMAND HELP."
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,399 @@
+use super::{
+    extract_string_args, validate_command, Auth, ClientGetName, ClientId, ClientKill, ClientList,
+    ClientSetName, CommandError, CommandExecutor, Hello, KillFilter, Monitor, Quit, Reset, RESP_OK,
+};
+use crate::{BulkString, RespArray, RespFrame, RespMap, SimpleString, REDIS_VERSION};
+
+impl CommandExecutor for Auth {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.authenticate(self.username.as_deref(), &self.password)?;
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for Hello {
+    // switches the protocol before replying, the reply is in the new one
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        match &self.auth {
+            Some((username, password)) => backend.authenticate(Some(username), password)?,
+            None if !backend.is_authenticated() => return Err(CommandError::NoAuth),
+            None => {}
+        }
+        if let Some(name) = self.name {
+            backend.set_client_name(name);
+        }
+        if let Some(protocol) = self.protocol {
+            backend.set_client_protocol(protocol);
+        }
+
+        let role = if backend.is_replica() {
+            "replica"
+        } else {
+            "master"
+        };
+        let mut reply = RespMap::new();
+        reply.insert("server".into(), BulkString::from("redis").into());
+        reply.insert("version".into(), BulkString::from(REDIS_VERSION).into());
+        reply.insert(
+            "proto".into(),
+            RespFrame::Integer(backend.client_protocol().into()),
+        );
+        reply.insert(
+            "id".into(),
+            RespFrame::Integer(backend.client_id().unwrap_or_default() as i64),
+        );
+        reply.insert("mode".into(), BulkString::from("standalone").into());
+        reply.insert("role".into(), BulkString::from(role).into());
+        Ok(reply.into())
+    }
+}
+
+impl CommandExecutor for Quit {
+    // the connection closes once it has replied
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        if let Some(id) = backend.client_id() {
+            backend.kill_clients(&KillFilter {
+                id: Some(id),
+                ..Default::default()
+            });
+        }
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for Monitor {
+    // only acknowledges, streaming the commands is up to the connection
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for Reset {
+    // only acknowledges, the connection resets its own state
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(SimpleString::new("RESET").into())
+    }
+}
+
+impl CommandExecutor for ClientId {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let id = backend.client_id().ok_or(CommandError::NoConnection)?;
+        Ok(RespFrame::Integer(id as i64))
+    }
+}
+
+impl CommandExecutor for ClientSetName {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.client_id().ok_or(CommandError::NoConnection)?;
+        backend.set_client_name(self.name);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for ClientGetName {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let info = backend.client_info().ok_or(CommandError::NoConnection)?;
+        Ok(if info.name.is_empty() {
+            RespFrame::NULL
+        } else {
+            BulkString::from(info.name).into()
+        })
+    }
+}
+
+impl CommandExecutor for ClientList {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(BulkString::from(backend.client_list()).into())
+    }
+}
+
+impl CommandExecutor for ClientKill {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let killed = backend.kill_clients(&self.filter);
+        match (self.legacy, killed) {
+            (false, killed) => Ok(RespFrame::Integer(killed as i64)),
+            (true, 0) => Err(CommandError::NoSuchClient),
+            (true, _) => Ok(RESP_OK.clone()),
+        }
+    }
+}
+
+// - AUTH [username] password
+impl TryFrom<RespArray> for Auth {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_string_args(value, 1)?;
+        match args.len() {
+            1 => Ok(Auth {
+                username: None,
+                password: args.remove(0),
+            }),
+            2 => Ok(Auth {
+                username: Some(args.remove(0)),
+                password: args.remove(0),
+            }),
+            _ => Err(CommandError::SyntaxError),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Hello {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_string_args(value, 1)?.into_iter();
+        let mut hello = Hello::default();
+        if let Some(protocol) = args.next() {
+            let protocol = protocol
+                .parse::<i64>()
+                .map_err(|_| CommandError::InvalidProtocolVersion)?;
+            hello.protocol = match protocol {
+                2 | 3 => Some(protocol as u8),
+                _ => return Err(CommandError::NoProto),
+            };
+        }
+        while let Some(option) = args.next() {
+            match option.to_ascii_lowercase().as_str() {
+                "auth" => match (args.next(), args.next()) {
+                    (Some(username), Some(password)) => hello.auth = Some((username, password)),
+                    _ => return Err(CommandError::HelloOption(option)),
+                },
+                "setname" => match args.next() {
+                    Some(name) => hello.name = Some(client_name(name)?),
+                    None => return Err(CommandError::HelloOption(option)),
+                },
+                _ => return Err(CommandError::HelloOption(option)),
+            }
+        }
+        Ok(hello)
+    }
+}
+
+impl TryFrom<RespArray> for Quit {
+    type Error = CommandError;
+    fn try_from(_value: RespArray) -> Result<Self, Self::Error> {
+        // like redis, any arguments are ignored
+        Ok(Quit)
+    }
+}
+
+impl TryFrom<RespArray> for Monitor {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["monitor"])?;
+        Ok(Monitor)
+    }
+}
+
+impl TryFrom<RespArray> for Reset {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["reset"])?;
+        Ok(Reset)
+    }
+}
+
+impl TryFrom<RespArray> for ClientId {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 2 {
+            return Err(CommandError::WrongArity("client|id"));
+        }
+        Ok(ClientId)
+    }
+}
+
+impl TryFrom<RespArray> for ClientSetName {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 3 {
+            return Err(CommandError::WrongArity("client|setname"));
+        }
+        let name = extract_string_args(value, 2)?.remove(0);
+        Ok(ClientSetName {
+            name: client_name(name)?,
+        })
+    }
+}
+
+// like redis, a client name has only printable characters and no spaces
+fn client_name(name: String) -> Result<String, CommandError> {
+    if !name.bytes().all(|b| (b'!'..=b'~').contains(&b)) {
+        return Err(CommandError::InvalidClientName);
+    }
+    Ok(name)
+}
+
+impl TryFrom<RespArray> for ClientGetName {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 2 {
+            return Err(CommandError::WrongArity("client|getname"));
+        }
+        Ok(ClientGetName)
+    }
+}
+
+impl TryFrom<RespArray> for ClientList {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 2 {
+            return Err(CommandError::SyntaxError);
+        }
+        Ok(ClientList)
+    }
+}
+
+// - CLIENT KILL addr
+// - CLIENT KILL [ID id] [ADDR addr] [LADDR addr] [SKIPME yes|no]
+impl TryFrom<RespArray> for ClientKill {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() < 3 {
+            return Err(CommandError::WrongArity("client|kill"));
+        }
+        let mut args = extract_string_args(value, 2)?;
+        if args.len() == 1 {
+            let filter = KillFilter {
+                addr: args.pop(),
+                ..Default::default()
+            };
+            return Ok(ClientKill {
+                filter,
+                legacy: true,
+            });
+        }
+
+        if !args.len().is_multiple_of(2) {
+            return Err(CommandError::SyntaxError);
+        }
+        let mut filter = KillFilter {
+            skip_me: true,
+            ..Default::default()
+        };
+        for pair in args.chunks(2) {
+            let (option, value) = (&pair[0], &pair[1]);
+            match option.to_ascii_lowercase().as_str() {
+                "id" => {
+                    let id = value.parse().ok().filter(|id| *id > 0);
+                    filter.id = Some(id.ok_or(CommandError::InvalidClientId)?);
+                }
+                "addr" => filter.addr = Some(value.clone()),
+                "laddr" => filter.laddr = Some(value.clone()),
+                "skipme" => {
+                    filter.skip_me = match value.to_ascii_lowercase().as_str() {
+                        "yes" => true,
+                        "no" => false,
+                        _ => return Err(CommandError::SyntaxError),
+                    }
+                }
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+        Ok(ClientKill {
+            filter,
+            legacy: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::Backend;
+    use anyhow::Result;
+    use futures::FutureExt;
+
+    #[test]
+    fn test_client_name_and_id() -> Result<()> {
+        let server = Backend::new();
+        let (a, _) = server.connect_client("10.0.0.1:5000".into(), "10.0.0.9:6379".into());
+        let (b, _) = server.connect_client("10.0.0.2:5000".into(), "10.0.0.9:6379".into());
+        assert_eq!(run_args(&a, &["client", "id"])?, RespFrame::Integer(1));
+        assert_eq!(run_args(&b, &["CLIENT", "ID"])?, RespFrame::Integer(2));
+
+        assert_eq!(run_args(&a, &["client", "getname"])?, RespFrame::NULL);
+        run_args(&a, &["client", "setname", "worker"])?;
+        assert_eq!(
+            run_args(&a, &["client", "getname"])?,
+            BulkString::from("worker").into()
+        );
+        assert!(run_args(&a, &["client", "setname", "two words"]).is_err());
+        // a handle that serves no connection has no id
+        assert!(run_args(&server, &["client", "id"]).is_err());
+
+        let list = match run_args(&b, &["client", "list"])? {
+            RespFrame::BulkString(list) => String::from_utf8(list.to_vec())?,
+            frame => panic!("expected a bulk string, got {:?}", frame),
+        };
+        let lines = list.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(
+            "id=1 addr=10.0.0.1:5000 laddr=10.0.0.9:6379 name=worker age=0 idle=0 flags=N db=0 cmd=client resp=2"
+        ));
+        assert!(lines[1].starts_with("id=2 addr=10.0.0.2:5000 "));
+
+        a.disconnect_client();
+        assert_eq!(server.client_list().lines().count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_auth() -> Result<()> {
+        let server = Backend::new();
+        let (before, _) = server.connect_client("a".into(), "l".into());
+        // without a password AUTH is a mistake, except for the default user
+        assert!(run_args(&before, &["auth", "pw"]).is_err());
+        assert_eq!(
+            run_args(&before, &["auth", "default", "anything"])?,
+            RESP_OK.clone()
+        );
+
+        run_args(&server, &["config", "set", "requirepass", "pw"])?;
+        let (after, _) = server.connect_client("b".into(), "l".into());
+        assert!(run_args(&after, &["get", "key"]).is_err());
+        assert!(run_args(&after, &["auth", "p"]).is_err());
+        assert!(run_args(&after, &["auth", "pw!"]).is_err());
+        assert!(run_args(&after, &["auth", "a", "b", "c"]).is_err());
+        // unknown commands are reported as such
+        assert!(matches!(
+            run_args(&after, &["nope"]),
+            Err(CommandError::UnknownCommand(..))
+        ));
+        assert_eq!(run_args(&after, &["AUTH", "pw"])?, RESP_OK.clone());
+        run_args(&after, &["get", "key"])?;
+
+        // a connection made before the password was set stays usable
+        run_args(&before, &["get", "key"])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_kill() -> Result<()> {
+        let server = Backend::new();
+        let (a, a_kill) = server.connect_client("10.0.0.1:5000".into(), "l".into());
+        let (b, b_kill) = server.connect_client("10.0.0.2:5000".into(), "l".into());
+
+        // the client asking is skipped unless SKIPME no
+        assert_eq!(
+            run_args(&a, &["client", "kill", "id", "1"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(&a, &["client", "kill", "addr", "10.0.0.2:5000"])?,
+            RespFrame::Integer(1)
+        );
+        // the wakeup waits for the connection, which closes on it
+        assert!(b_kill.notified().now_or_never().is_some());
+        assert!(a_kill.notified().now_or_never().is_none());
+        assert_eq!(
+            run_args(&a, &["client", "kill", "10.0.0.1:5000"])?,
+            RESP_OK.clone()
+        );
+        assert!(a_kill.notified().now_or_never().is_some());
+        assert!(run_args(&a, &["client", "kill", "10.0.0.3:5000"]).is_err());
+        assert!(run_args(&b, &["client", "kill", "id", "0"]).is_err());
+        assert!(run_args(&b, &["client", "kill", "id"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use super::{
+    extract_args, extract_key_args, extract_string_args, parse_float, CommandError,
+    CommandExecutor, DebugHelp, DebugNoop, DebugObject, DebugSetActiveExpire, DebugSleep, RESP_OK,
+};
+use crate::{RespArray, RespFrame, SimpleString};
+use std::time::Duration;
+
+// the reply of `DEBUG HELP`
+const HELP: [&str; 9] = [
+    "DEBUG <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+    "OBJECT <key>",
+    "    Show low level info about the key and associated value.",
+    "SET-ACTIVE-EXPIRE <0|1>",
+    "    Setting it to 0 disables expiring keys in background when they are not accessed.",
+    "SLEEP <seconds>",
+    "    Stop the server for <seconds>. Decimals allowed.",
+    "HELP",
+    "    Print this help.",
+];
+
+impl CommandExecutor for DebugHelp {
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let lines = HELP
+            .iter()
+            .map(|line| SimpleString::new(*line).into())
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(lines).into())
+    }
+}
+
+impl CommandExecutor for DebugSleep {
+    // outside of a connection there is nothing else to let run in the meantime
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        std::thread::sleep(self.duration);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl DebugSleep {
+    // only the connection sleeps, the other ones are served in the meantime
+    pub(super) async fn sleep(self) -> Result<RespFrame, CommandError> {
+        tokio::time::sleep(self.duration).await;
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for DebugObject {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let encoding = backend
+            .object_encoding(&self.key)
+            .ok_or(CommandError::NoSuchKey)?;
+        // values are not shared nor serialized, the size in memory stands in for the latter
+        let size = backend.memory_usage(&self.key).unwrap_or_default();
+        Ok(SimpleString::new(format!(
+            "Value at:0x0 refcount:1 encoding:{} serializedlength:{} lru:0 lru_seconds_idle:0",
+            encoding, size
+        ))
+        .into())
+    }
+}
+
+impl CommandExecutor for DebugSetActiveExpire {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.set_active_expire(self.enabled);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for DebugNoop {
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl TryFrom<RespArray> for DebugSleep {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 3 {
+            return Err(CommandError::WrongArity("debug|sleep"));
+        }
+        let seconds = match extract_args(value, 2)?.pop() {
+            Some(RespFrame::BulkString(seconds)) => parse_float(&seconds)?,
+            _ => return Err(CommandError::NotAFloat),
+        };
+        Ok(DebugSleep {
+            duration: Duration::from_secs_f64(seconds.max(0.0)),
+        })
+    }
+}
+
+impl TryFrom<RespArray> for DebugObject {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 3 {
+            return Err(CommandError::WrongArity("debug|object"));
+        }
+        Ok(DebugObject {
+            key: extract_key_args(value, 2)?.remove(0),
+        })
+    }
+}
+
+impl TryFrom<RespArray> for DebugSetActiveExpire {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 3 {
+            return Err(CommandError::WrongArity("debug|set-active-expire"));
+        }
+        // like redis, anything but 0 turns it on
+        let enabled = extract_string_args(value, 2)?[0] != "0";
+        Ok(DebugSetActiveExpire { enabled })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::{Backend, BackendConfig, BulkString};
+    use anyhow::Result;
+
+    #[test]
+    fn test_debug_object() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("key".into(), BulkString::from("12345").into());
+        let reply = run_args(&backend, &["debug", "object", "key"])?;
+        let RespFrame::SimpleString(reply) = reply else {
+            panic!("expected a simple string, got {:?}", reply);
+        };
+        assert!(reply.starts_with("Value at:0x0 refcount:1 encoding:int serializedlength:"));
+        assert!(run_args(&backend, &["debug", "object", "missing"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_subcommands() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run_args(&backend, &["debug", "change-repl-id"])?,
+            RESP_OK.clone()
+        );
+        assert_eq!(
+            run_args(&backend, &["debug", "sleep", "0"])?,
+            RESP_OK.clone()
+        );
+        assert!(run_args(&backend, &["debug", "sleep", "soon"]).is_err());
+        let error = run_args(&backend, &["DEBUG", "nope"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown subcommand 'nope'. Try DEBUG HELP."
+        );
+        let RespFrame::Array(help) = run_args(&backend, &["debug", "help"])? else {
+            panic!("expected an array");
+        };
+        assert_eq!(help.len(), HELP.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_debug_set_active_expire() -> Result<()> {
+        let backend = Backend::new_with_config(BackendConfig {
+            expire_interval: Duration::from_millis(10),
+            ..Default::default()
+        });
+        backend.spawn_active_expire();
+        run_args(&backend, &["debug", "set-active-expire", "0"])?;
+        backend.set("key".into(), BulkString::from("value").into());
+        backend.expire(b"key", 10);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // look at the map directly, going through `get` would evict the key lazily
+        assert!(backend.entries.contains_key(b"key".as_slice()));
+
+        run_args(&backend, &["debug", "set-active-expire", "1"])?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!backend.entries.contains_key(b"key".as_slice()));
+        Ok(())
+    }
+}
@@ -0,0 +1,705 @@
+use super::{
+    extract_args, parse_float, parse_integer, validate_command, CommandError, CommandExecutor,
+    GeoAdd, GeoDist, GeoPos, GeoSearch,
+};
+use crate::{
+    backend::format_score, BulkString, GeoOrigin, GeoShape, GeoUnit, RespArray, RespFrame,
+    ZAddOptions,
+};
+
+impl CommandExecutor for GeoAdd {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let added = backend.geoadd(self.key, self.points, self.options)?;
+        Ok(RespFrame::Integer(added))
+    }
+}
+
+impl CommandExecutor for GeoPos {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let positions = backend
+            .geopos(&self.key, &self.members)?
+            .into_iter()
+            .map(|position| match position {
+                Some((lon, lat)) => position_reply(lon, lat),
+                None => RespFrame::NULL_ARRAY,
+            })
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(positions).into())
+    }
+}
+
+impl CommandExecutor for GeoDist {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(match backend.geodist(&self.key, &self.a, &self.b)? {
+            Some(distance) => distance_reply(distance, self.unit),
+            None => RespFrame::NULL,
+        })
+    }
+}
+
+impl CommandExecutor for GeoSearch {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let matches =
+            backend.geosearch(&self.key, &self.origin, self.shape, self.desc, self.count)?;
+        let with_any = self.with_dist || self.with_hash || self.with_coord;
+        let matches = matches
+            .into_iter()
+            .map(|found| {
+                if !with_any {
+                    return BulkString::from(found.member).into();
+                }
+                // the member, then what was asked for in this order
+                let mut frames = vec![BulkString::from(found.member).into()];
+                if self.with_dist {
+                    frames.push(distance_reply(found.distance, self.unit));
+                }
+                if self.with_hash {
+                    frames.push(RespFrame::Integer(found.hash as i64));
+                }
+                if self.with_coord {
+                    frames.push(position_reply(found.lon, found.lat));
+                }
+                RespArray::new(frames).into()
+            })
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(matches).into())
+    }
+}
+
+fn position_reply(lon: f64, lat: f64) -> RespFrame {
+    RespArray::new(vec![
+        BulkString::from(format_score(lon)).into(),
+        BulkString::from(format_score(lat)).into(),
+    ])
+    .into()
+}
+
+// a distance in meters converted to `unit`, with four decimals like redis
+fn distance_reply(distance: f64, unit: GeoUnit) -> RespFrame {
+    BulkString::from(format!("{:.4}", distance / unit.meters())).into()
+}
+
+impl TryFrom<RespArray> for GeoAdd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["geoadd"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => key.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+
+        let mut options = ZAddOptions::default();
+        while let Some(RespFrame::BulkString(arg)) = args.peek() {
+            match arg.to_ascii_lowercase().as_slice() {
+                b"nx" => options.nx = true,
+                b"xx" => options.xx = true,
+                b"ch" => options.ch = true,
+                // the first longitude
+                _ => break,
+            }
+            args.next();
+        }
+        if options.nx && options.xx {
+            return Err(CommandError::IncompatibleOptions("XX and NX"));
+        }
+
+        let args = args.collect::<Vec<_>>();
+        if args.is_empty() || args.len() % 3 != 0 {
+            return Err(CommandError::SyntaxError);
+        }
+        // all the positions are checked before anything is added
+        let points = args
+            .chunks(3)
+            .map(|point| match point {
+                [RespFrame::BulkString(lon), RespFrame::BulkString(lat), RespFrame::BulkString(member)] => {
+                    Ok((
+                        parse_float(lon)?,
+                        parse_float(lat)?,
+                        member.0.clone(),
+                    ))
+                }
+                _ => Err(CommandError::SyntaxError),
+            })
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        Ok(GeoAdd {
+            key,
+            options,
+            points,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for GeoPos {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["geopos"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => key.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let members = args
+            .map(|frame| match frame {
+                RespFrame::BulkString(member) => Ok(member.0),
+                _ => Err(CommandError::InvalidArgument("Invalid member".to_string())),
+            })
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        Ok(GeoPos { key, members })
+    }
+}
+
+impl TryFrom<RespArray> for GeoDist {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        // the unit is optional
+        if value.len() > 5 {
+            return Err(CommandError::WrongArity("geodist"));
+        }
+        validate_command(&value, &["geodist"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, a, b) = match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(a)),
+                Some(RespFrame::BulkString(b)),
+            ) => (key.0, a.0, b.0),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key or members".to_string(),
+                ))
+            }
+        };
+        let unit = match args.next() {
+            Some(RespFrame::BulkString(unit)) => parse_unit(&unit)?,
+            None => GeoUnit::Meters,
+            _ => return Err(CommandError::InvalidGeoUnit),
+        };
+        Ok(GeoDist { key, a, b, unit })
+    }
+}
+
+impl TryFrom<RespArray> for GeoSearch {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["geosearch"])?;
+
+        let args = extract_args(value, 1)?
+            .into_iter()
+            .map(|frame| match frame {
+                RespFrame::BulkString(arg) => Ok(arg),
+                _ => Err(CommandError::SyntaxError),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut args = args.into_iter();
+        let key = args.next().expect("the key is there").0;
+
+        let mut origin = None;
+        let mut shape = None;
+        let mut search = GeoSearch {
+            key,
+            origin: GeoOrigin::LonLat(0.0, 0.0),
+            shape: GeoShape::Radius(0.0),
+            unit: GeoUnit::Meters,
+            desc: false,
+            count: None,
+            with_coord: false,
+            with_dist: false,
+            with_hash: false,
+        };
+        let mut next = || args.next().ok_or(CommandError::SyntaxError);
+        while let Ok(arg) = next() {
+            match arg.to_ascii_lowercase().as_slice() {
+                b"frommember" if origin.is_none() => origin = Some(GeoOrigin::Member(next()?.0)),
+                b"fromlonlat" if origin.is_none() => {
+                    let (lon, lat) = (parse_float(&next()?)?, parse_float(&next()?)?);
+                    origin = Some(GeoOrigin::LonLat(lon, lat))
+                }
+                b"byradius" if shape.is_none() => {
+                    let radius = parse_float(&next()?)?;
+                    search.unit = parse_unit(&next()?)?;
+                    if radius < 0.0 {
+                        return Err(CommandError::NegativeGeoSize("radius"));
+                    }
+                    shape = Some(GeoShape::Radius(radius * search.unit.meters()))
+                }
+                b"bybox" if shape.is_none() => {
+                    let (width, height) = (parse_float(&next()?)?, parse_float(&next()?)?);
+                    search.unit = parse_unit(&next()?)?;
+                    if width < 0.0 || height < 0.0 {
+                        return Err(CommandError::NegativeGeoSize("height or width"));
+                    }
+                    let meters = search.unit.meters();
+                    shape = Some(GeoShape::Box(width * meters, height * meters))
+                }
+                b"frommember" | b"fromlonlat" => return Err(CommandError::GeoSearchOrigin),
+                b"byradius" | b"bybox" => return Err(CommandError::GeoSearchShape),
+                b"asc" => search.desc = false,
+                b"desc" => search.desc = true,
+                b"count" => match parse_integer(&next()?)? {
+                    count if count <= 0 => return Err(CommandError::NotPositive),
+                    count => search.count = Some(count as usize),
+                },
+                // any match is as good as the nearest ones, which are what is returned anyway
+                b"any" if search.count.is_some() => {}
+                b"withcoord" => search.with_coord = true,
+                b"withdist" => search.with_dist = true,
+                b"withhash" => search.with_hash = true,
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+        search.origin = origin.ok_or(CommandError::GeoSearchOrigin)?;
+        search.shape = shape.ok_or(CommandError::GeoSearchShape)?;
+        Ok(search)
+    }
+}
+
+fn parse_unit(value: &[u8]) -> Result<GeoUnit, CommandError> {
+    match value.to_ascii_lowercase().as_slice() {
+        b"m" => Ok(GeoUnit::Meters),
+        b"km" => Ok(GeoUnit::Kilometers),
+        b"mi" => Ok(GeoUnit::Miles),
+        b"ft" => Ok(GeoUnit::Feet),
+        _ => Err(CommandError::InvalidGeoUnit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::{RespDecode, RespEncode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn run(backend: &crate::Backend, request: &[u8]) -> Result<RespFrame> {
+        let mut buf = BytesMut::from(request);
+        let cmd = crate::cmd::Command::try_from(RespFrame::decode(&mut buf)?)?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    fn bulk(value: &str) -> RespFrame {
+        BulkString::from(value).into()
+    }
+
+    fn float(frame: &RespFrame) -> f64 {
+        let RespFrame::BulkString(value) = frame else {
+            panic!("expected a bulk string");
+        };
+        std::str::from_utf8(value).unwrap().parse().unwrap()
+    }
+
+    fn sicily(backend: &crate::Backend) -> Result<()> {
+        run_args(
+            backend,
+            &[
+                "geoadd",
+                "Sicily",
+                "13.361389",
+                "38.115556",
+                "Palermo",
+                "15.087269",
+                "37.502669",
+                "Catania",
+            ],
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_geoadd_geopos() -> Result<()> {
+        let backend = crate::Backend::new();
+        let request = b"*8\r\n$6\r\ngeoadd\r\n$6\r\nSicily\r\n$9\r\n13.361389\r\n$9\r\n38.115556\r\n$7\r\nPalermo\r\n$9\r\n15.087269\r\n$9\r\n37.502669\r\n$7\r\nCatania\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b":2\r\n");
+        // the score is the geohash, the same as redis
+        assert_eq!(
+            backend.zmscore(b"Sicily", &["Palermo".into()])?,
+            vec![Some(3479099956230698.0)]
+        );
+
+        let RespFrame::Array(positions) = run_args(
+            &backend,
+            &["geopos", "Sicily", "Palermo", "missing", "Catania"],
+        )?
+        else {
+            panic!("expected an array");
+        };
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions.0[1], RespFrame::NULL);
+        for (position, (lon, lat)) in [&positions.0[0], &positions.0[2]]
+            .into_iter()
+            .zip([(13.361389, 38.115556), (15.087269, 37.502669)])
+        {
+            let RespFrame::Array(position) = position else {
+                panic!("expected an array");
+            };
+            assert!((float(&position.0[0]) - lon).abs() < 1e-5);
+            assert!((float(&position.0[1]) - lat).abs() < 1e-5);
+        }
+
+        // moving a member is an update, not an addition
+        assert_eq!(
+            run_args(
+                &backend,
+                &["geoadd", "Sicily", "CH", "13.5", "38.1", "Palermo"]
+            )?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            run_args(&backend, &["geoadd", "Sicily", "NX", "0", "0", "Palermo"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(&backend, &["geoadd", "Sicily", "200", "0", "Nowhere"])
+                .unwrap_err()
+                .to_string(),
+            "invalid longitude,latitude pair 200.000000,0.000000"
+        );
+        assert!(run_args(&backend, &["geoadd", "Sicily", "0", "86", "Pole"]).is_err());
+        assert!(run_args(&backend, &["geoadd", "Sicily", "0", "0"]).is_err());
+        assert!(run_args(&backend, &["geoadd", "Sicily", "NX", "XX", "0", "0", "a"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_geodist() -> Result<()> {
+        let backend = crate::Backend::new();
+        sicily(&backend)?;
+        // the values redis gives for the same cities
+        for (unit, expected) in [
+            (None, "166274.1516"),
+            (Some("km"), "166.2742"),
+            (Some("MI"), "103.3182"),
+            (Some("ft"), "545518.8700"),
+        ] {
+            let mut args = vec!["geodist", "Sicily", "Palermo", "Catania"];
+            args.extend(unit);
+            assert_eq!(run_args(&backend, &args)?, bulk(expected), "{:?}", unit);
+        }
+        assert_eq!(
+            run_args(&backend, &["geodist", "Sicily", "Palermo", "missing"])?,
+            RespFrame::NULL
+        );
+        assert_eq!(
+            run_args(&backend, &["geodist", "missing", "a", "b"])?,
+            RespFrame::NULL
+        );
+        assert!(run_args(&backend, &["geodist", "Sicily", "Palermo", "Catania", "yd"]).is_err());
+
+        // London to Paris is about 343.5 km
+        run_args(
+            &backend,
+            &[
+                "geoadd", "cities", "-0.1278", "51.5074", "London", "2.3522", "48.8566", "Paris",
+            ],
+        )?;
+        let distance = float(&run_args(
+            &backend,
+            &["geodist", "cities", "London", "Paris", "km"],
+        )?);
+        assert!((distance - 343.5).abs() < 1.0, "{}", distance);
+        Ok(())
+    }
+
+    #[test]
+    fn test_geosearch() -> Result<()> {
+        let backend = crate::Backend::new();
+        sicily(&backend)?;
+        run_args(
+            &backend,
+            &[
+                "geoadd",
+                "Sicily",
+                "12.758489",
+                "38.788135",
+                "edge1",
+                "17.241510",
+                "38.788135",
+                "edge2",
+            ],
+        )?;
+
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "geosearch",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "200",
+                    "km",
+                    "ASC"
+                ]
+            )?,
+            RespArray::new(vec![bulk("Catania"), bulk("Palermo")]).into()
+        );
+        // the same answers as the redis documentation
+        let RespFrame::Array(found) = run_args(
+            &backend,
+            &[
+                "geosearch",
+                "Sicily",
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYBOX",
+                "400",
+                "400",
+                "km",
+                "ASC",
+                "WITHCOORD",
+                "WITHDIST",
+            ],
+        )?
+        else {
+            panic!("expected an array");
+        };
+        let found = found
+            .0
+            .iter()
+            .map(|item| match item {
+                RespFrame::Array(item) => (item.0[0].clone(), item.0[1].clone()),
+                _ => panic!("expected an array"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (bulk("Catania"), bulk("56.4413")),
+                (bulk("Palermo"), bulk("190.4424")),
+                (bulk("edge2"), bulk("279.7403")),
+                (bulk("edge1"), bulk("279.7405")),
+            ]
+        );
+
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "geosearch",
+                    "Sicily",
+                    "FROMMEMBER",
+                    "Palermo",
+                    "BYRADIUS",
+                    "170",
+                    "km",
+                    "DESC",
+                    "COUNT",
+                    "2"
+                ]
+            )?,
+            RespArray::new(vec![bulk("Catania"), bulk("edge1")]).into()
+        );
+        let RespFrame::Array(found) = run_args(
+            &backend,
+            &[
+                "geosearch",
+                "Sicily",
+                "FROMMEMBER",
+                "Catania",
+                "BYRADIUS",
+                "1",
+                "m",
+                "WITHHASH",
+            ],
+        )?
+        else {
+            panic!("expected an array");
+        };
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found.0[0],
+            RespArray::new(vec![bulk("Catania"), RespFrame::Integer(3479447370796909)]).into()
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "geosearch",
+                    "missing",
+                    "FROMMEMBER",
+                    "a",
+                    "BYRADIUS",
+                    "1",
+                    "km"
+                ]
+            )?,
+            RespArray::new(vec![]).into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_geosearch_covers_every_cell() -> Result<()> {
+        let backend = crate::Backend::new();
+        // a grid of points around the antimeridian and the equator, compared with a full scan
+        let mut points = Vec::new();
+        for i in -20..=20 {
+            for j in -20..=20 {
+                let lon = 180.0 + i as f64 * 0.05;
+                let lon = if lon > 180.0 { lon - 360.0 } else { lon };
+                points.push((lon, j as f64 * 0.05, format!("{}:{}", i, j).into()));
+            }
+        }
+        backend.geoadd("grid".into(), points.clone(), ZAddOptions::default())?;
+        for (radius, lon, lat) in [
+            (50_000.0, 180.0, 0.0),
+            (12_345.0, 179.8, 0.3),
+            (1.0, -179.95, 0.0),
+        ] {
+            let found = backend.geosearch(
+                b"grid",
+                &GeoOrigin::LonLat(lon, lat),
+                GeoShape::Radius(radius),
+                false,
+                None,
+            )?;
+            let expected = points
+                .iter()
+                .filter(|(.., member)| {
+                    // compare against the stored position, which is the center of its cell
+                    let (lon2, lat2) = backend
+                        .geopos(b"grid", std::slice::from_ref(member))
+                        .unwrap()[0]
+                        .unwrap();
+                    haversine(lon, lat, lon2, lat2) <= radius
+                })
+                .count();
+            assert_eq!(found.len(), expected, "{} around {},{}", radius, lon, lat);
+            assert!(found
+                .windows(2)
+                .all(|pair| pair[0].distance <= pair[1].distance));
+        }
+        Ok(())
+    }
+
+    fn haversine(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+        let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+        let u = ((lat2 - lat1) / 2.0).sin();
+        let v = ((lon2 - lon1).to_radians() / 2.0).sin();
+        2.0 * 6372797.560856 * (u * u + lat1.cos() * lat2.cos() * v * v).sqrt().asin()
+    }
+
+    #[test]
+    fn test_geosearch_errors() -> Result<()> {
+        let backend = crate::Backend::new();
+        sicily(&backend)?;
+        for (args, message) in [
+            (
+                &[
+                    "geosearch",
+                    "Sicily",
+                    "BYRADIUS",
+                    "1",
+                    "km",
+                    "ASC",
+                    "WITHDIST",
+                ][..],
+                "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH",
+            ),
+            (
+                &[
+                    "geosearch",
+                    "Sicily",
+                    "FROMMEMBER",
+                    "Palermo",
+                    "FROMLONLAT",
+                    "0",
+                    "0",
+                    "BYRADIUS",
+                    "1",
+                    "km",
+                ],
+                "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH",
+            ),
+            (
+                &[
+                    "geosearch",
+                    "Sicily",
+                    "FROMMEMBER",
+                    "Palermo",
+                    "ASC",
+                    "WITHDIST",
+                    "WITHHASH",
+                ],
+                "exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH",
+            ),
+            (
+                &[
+                    "geosearch",
+                    "Sicily",
+                    "FROMMEMBER",
+                    "Rome",
+                    "BYRADIUS",
+                    "1",
+                    "km",
+                ],
+                "could not decode requested zset member",
+            ),
+            (
+                &[
+                    "geosearch",
+                    "Sicily",
+                    "FROMMEMBER",
+                    "Palermo",
+                    "BYRADIUS",
+                    "-1",
+                    "km",
+                ],
+                "radius cannot be negative",
+            ),
+            (
+                &[
+                    "geosearch",
+                    "Sicily",
+                    "FROMMEMBER",
+                    "Palermo",
+                    "BYRADIUS",
+                    "1",
+                    "parsec",
+                ],
+                "unsupported unit provided. please use M, KM, FT, MI",
+            ),
+        ] {
+            assert_eq!(run_args(&backend, args).unwrap_err().to_string(), message);
+        }
+        assert!(run_args(
+            &backend,
+            &[
+                "geosearch",
+                "Sicily",
+                "FROMLONLAT",
+                "0",
+                "90",
+                "BYRADIUS",
+                "1",
+                "km"
+            ]
+        )
+        .is_err());
+        assert!(run_args(
+            &backend,
+            &[
+                "geosearch",
+                "Sicily",
+                "FROMMEMBER",
+                "Palermo",
+                "BYRADIUS",
+                "1",
+                "km",
+                "COUNT",
+                "0"
+            ]
+        )
+        .is_err());
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(run_args(&backend, &["geopos", "string", "a"]).is_err());
+        Ok(())
+    }
+}
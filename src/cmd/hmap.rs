@@ -1,58 +1,50 @@
-use super::{extract_args, validate_command, CommandExecutor, HGet, HGetAll, HMGet, HSet, RESP_OK};
-use crate::{cmd::CommandError, BulkString, RespArray, RespFrame};
+use super::{
+    extract_args, extract_key_args, extract_key_scan_args, parse_float, parse_integer,
+    validate_command, CommandExecutor, HDel, HExists, HGet, HGetAll, HIncrBy, HIncrByFloat, HKeys,
+    HLen, HMGet, HMSet, HRandField, HScan, HSet, HSetNx, HStrLen, HVals, RESP_OK,
+};
+use crate::{
+    cmd::CommandError, BulkString, KeyType, KeyspaceEvents, RespArray, RespFrame, RespMap,
+};
+use bytes::Bytes;
 
 impl CommandExecutor for HGet {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        match backend.hget(&self.key, &self.field) {
-            Some(value) => value,
-            None => RespFrame::Null(crate::RespNull),
-        }
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.check_type(&self.key, KeyType::Hash)?;
+        Ok(backend
+            .hget(&self.key, &self.field)
+            .unwrap_or(RespFrame::NULL))
     }
 }
 
 impl CommandExecutor for HGetAll {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        let hmap = backend.hmap.get(&self.key);
-
-        match hmap {
-            Some(hmap) => {
-                let mut data = Vec::with_capacity(hmap.len());
-                for v in hmap.iter() {
-                    let key = v.key().to_owned();
-                    data.push((key, v.value().clone()));
-                }
-                if self.sort {
-                    data.sort_by(|a, b| a.0.cmp(&b.0));
-                }
-                let ret = data
-                    .into_iter()
-                    .flat_map(|(k, v)| vec![BulkString::from(k).into(), v])
-                    .collect::<Vec<RespFrame>>();
-
-                RespArray::new(ret).into()
-            }
-            None => RespArray::new([]).into(),
-        }
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        // a map, which a RESP2 client gets as the flat array of the fields and the values. The
+        // shards of the hash have no useful order, the map sorts the fields so replies are stable
+        let mut map = RespMap::new();
+        map.extend(backend.hentries(&self.key)?);
+        Ok(map.into())
     }
 }
 
 impl CommandExecutor for HSet {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        backend.hset(self.key, self.field, self.value);
-        RESP_OK.clone()
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let created = backend.hset_multi(self.key.clone(), self.fields)?;
+        backend.notify_keyspace_event(KeyspaceEvents::HASH, "hset", &self.key);
+        Ok(RespFrame::Integer(created))
     }
 }
 
 impl TryFrom<RespArray> for HGet {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["hget"], 2)?;
+        validate_command(&value, &["hget"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field))) => Ok(HGet {
-                key: String::from_utf8(key.0)?,
-                field: String::from_utf8(field.0)?,
+                key: key.0,
+                field: field.0,
             }),
             _ => Err(CommandError::InvalidArgument(
                 "Invalid key or field".to_string(),
@@ -64,30 +56,28 @@ impl TryFrom<RespArray> for HGet {
 impl TryFrom<RespArray> for HGetAll {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["hgetall"], 1)?;
+        validate_command(&value, &["hgetall"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
-            Some(RespFrame::BulkString(key)) => Ok(HGetAll {
-                key: String::from_utf8(key.0)?,
-                sort: false,
-            }),
+            Some(RespFrame::BulkString(key)) => Ok(HGetAll { key: key.0 }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
     }
 }
 
 impl CommandExecutor for HMGet {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.check_type(&self.hash, KeyType::Hash)?;
         let fields = self
             .fields
             .iter()
             .map(|f| match backend.hget(&self.hash, f) {
                 Some(value) => value,
-                None => RespFrame::Null(crate::RespNull),
+                None => RespFrame::NULL,
             })
             .collect::<Vec<_>>();
-        RespFrame::Array(RespArray(fields))
+        Ok(RespFrame::Array(RespArray(fields)))
     }
 }
 
@@ -95,50 +85,236 @@ impl CommandExecutor for HMGet {
 impl TryFrom<RespArray> for HMGet {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        let len = value.len();
-        match len {
-            0 => {
-                return Err(CommandError::InvalidCommand(
-                    "hmget does not accept null array".to_string(),
-                ))
-            }
-            1..=2 => {
-                return Err(CommandError::InvalidCommand(format!(
-                    "hmget command must have at least 2 argument, got {len}",
-                )))
-            }
-            _ => validate_command(&value, &["hmget"], len - 1)?,
+        validate_command(&value, &["hmget"])?;
+
+        let mut args = extract_key_args(value, 1)?.into_iter();
+        let Some(hash) = args.next() else {
+            return Err(CommandError::InvalidArgument("Invalid key".to_string()));
+        };
+        Ok(HMGet {
+            hash,
+            fields: args.collect(),
+        })
+    }
+}
+
+
+impl TryFrom<RespArray> for HSet {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, fields) = extract_field_pairs(value, "hset")?;
+        Ok(HSet { key, fields })
+    }
+}
+
+impl CommandExecutor for HMSet {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.hset_multi(self.key.clone(), self.fields)?;
+        backend.notify_keyspace_event(KeyspaceEvents::HASH, "hset", &self.key);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl TryFrom<RespArray> for HMSet {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, fields) = extract_field_pairs(value, "hmset")?;
+        Ok(HMSet { key, fields })
+    }
+}
+
+// - HSET key field value [field value ...]
+fn extract_field_pairs(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, Vec<(Bytes, RespFrame)>), CommandError> {
+    if value.len() < 4 || !value.len().is_multiple_of(2) {
+        return Err(CommandError::WrongArity(name));
+    }
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = match args.next() {
+        Some(RespFrame::BulkString(key)) => key.0,
+        _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    };
+    let mut fields = Vec::with_capacity(args.len() / 2);
+    while let (Some(field), Some(value)) = (args.next(), args.next()) {
+        match field {
+            RespFrame::BulkString(field) => fields.push((field.0, value)),
+            _ => return Err(CommandError::InvalidArgument("Invalid field".to_string())),
         }
+    }
+    Ok((key, fields))
+}
 
-        let mut args = extract_args(value, 1)?.into_iter();
-        let hash = match args.next() {
-            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
-            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+impl CommandExecutor for HDel {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let removed = backend.hdel(&self.key, &self.fields)?;
+        if removed > 0 {
+            backend.notify_keyspace_event(KeyspaceEvents::HASH, "hdel", &self.key);
+        }
+        Ok(RespFrame::Integer(removed))
+    }
+}
+
+impl CommandExecutor for HExists {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let exists = backend.hexists(&self.key, &self.field)?;
+        Ok(RespFrame::Integer(exists as i64))
+    }
+}
+
+impl CommandExecutor for HLen {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.hlen(&self.key)?))
+    }
+}
+
+impl TryFrom<RespArray> for HDel {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hdel"])?;
+
+        let mut args = extract_key_args(value, 1)?.into_iter();
+        let Some(key) = args.next() else {
+            return Err(CommandError::InvalidArgument("Invalid key".to_string()));
         };
-        let mut fields = vec![];
-        loop {
-            match args.next() {
-                Some(RespFrame::BulkString(key)) => fields.push(String::from_utf8(key.0)?),
-                None => break,
-                _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
-            };
+        Ok(HDel {
+            key,
+            fields: args.collect(),
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HExists {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hexists"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field))) => Ok(HExists {
+                key: key.0,
+                field: field.0,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or field".to_string(),
+            )),
         }
-        Ok(HMGet { hash, fields })
     }
 }
 
+impl TryFrom<RespArray> for HLen {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(HLen {
+            key: extract_hash_key(value, "hlen")?,
+        })
+    }
+}
+
+impl CommandExecutor for HKeys {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let keys = backend
+            .hentries(&self.key)?
+            .into_iter()
+            .map(|(field, _)| BulkString::from(field).into())
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(keys).into())
+    }
+}
 
-impl TryFrom<RespArray> for HSet {
+impl CommandExecutor for HVals {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let values = backend
+            .hentries(&self.key)?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(values).into())
+    }
+}
+
+impl TryFrom<RespArray> for HKeys {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["hset"], 3)?;
+        Ok(HKeys {
+            key: extract_hash_key(value, "hkeys")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HVals {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(HVals {
+            key: extract_hash_key(value, "hvals")?,
+        })
+    }
+}
+
+impl CommandExecutor for HIncrBy {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let value = backend.hincr_by(&self.key, &self.field, self.delta)?;
+        backend.notify_keyspace_event(KeyspaceEvents::HASH, "hincrby", &self.key);
+        Ok(RespFrame::Integer(value))
+    }
+}
+
+impl CommandExecutor for HIncrByFloat {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let value = backend.hincr_by_float(&self.key, &self.field, self.delta)?;
+        backend.notify_keyspace_event(KeyspaceEvents::HASH, "hincrbyfloat", &self.key);
+        Ok(BulkString::from(value).into())
+    }
+}
+
+impl TryFrom<RespArray> for HIncrBy {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, field, delta) = extract_field_and_delta(value, "hincrby")?;
+        Ok(HIncrBy {
+            key,
+            field,
+            delta: parse_integer(&delta)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HIncrByFloat {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, field, delta) = extract_field_and_delta(value, "hincrbyfloat")?;
+        Ok(HIncrByFloat {
+            key,
+            field,
+            delta: parse_float(&delta)?,
+        })
+    }
+}
+
+impl CommandExecutor for HSetNx {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let set = backend.hsetnx(self.key.clone(), self.field, self.value)?;
+        if set {
+            backend.notify_keyspace_event(KeyspaceEvents::HASH, "hset", &self.key);
+        }
+        Ok(RespFrame::Integer(set as i64))
+    }
+}
+
+impl TryFrom<RespArray> for HSetNx {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hsetnx"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field)), Some(value)) => {
-                Ok(HSet {
-                    key: String::from_utf8(key.0)?,
-                    field: String::from_utf8(field.0)?,
+                Ok(HSetNx {
+                    key: key.0,
+                    field: field.0,
                     value,
                 })
             }
@@ -149,15 +325,155 @@ impl TryFrom<RespArray> for HSet {
     }
 }
 
+impl CommandExecutor for HRandField {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let Some(count) = self.count else {
+            return Ok(match backend.hrandfield(&self.key, 1, true)?.pop() {
+                Some((field, _)) => BulkString::from(field).into(),
+                None => RespFrame::NULL,
+            });
+        };
+
+        // a negative count asks for exactly that many fields, allowing repeats
+        let entries = backend.hrandfield(&self.key, count.unsigned_abs() as usize, count >= 0)?;
+        let ret = entries
+            .into_iter()
+            .flat_map(|(field, value)| {
+                let field = BulkString::from(field).into();
+                match self.with_values {
+                    true => vec![field, value],
+                    false => vec![field],
+                }
+            })
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(ret).into())
+    }
+}
+
+impl TryFrom<RespArray> for HRandField {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hrandfield"])?;
+        if value.len() > 4 {
+            return Err(CommandError::SyntaxError);
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => key.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let count = match args.next() {
+            Some(RespFrame::BulkString(count)) => Some(parse_integer(&count)?),
+            Some(_) => return Err(CommandError::NotAnInteger),
+            None => None,
+        };
+        let with_values = match args.next() {
+            Some(RespFrame::BulkString(arg)) if arg.eq_ignore_ascii_case(b"withvalues") => true,
+            Some(_) => return Err(CommandError::SyntaxError),
+            None => false,
+        };
+        Ok(HRandField {
+            key,
+            count,
+            with_values,
+        })
+    }
+}
+
+impl CommandExecutor for HScan {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let pattern = self.pattern.as_deref();
+        let (cursor, entries) = backend.hscan(&self.key, self.cursor, pattern, self.count)?;
+        let entries = entries
+            .into_iter()
+            .flat_map(|(field, value)| [BulkString::from(field).into(), value])
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(vec![
+            BulkString::from(cursor.to_string()).into(),
+            RespArray::new(entries).into(),
+        ])
+        .into())
+    }
+}
+
+impl TryFrom<RespArray> for HScan {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, cursor, pattern, count) = extract_key_scan_args(value, "hscan")?;
+        Ok(HScan {
+            key,
+            cursor,
+            pattern,
+            count,
+        })
+    }
+}
+
+impl CommandExecutor for HStrLen {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.hstrlen(&self.key, &self.field)?))
+    }
+}
+
+impl TryFrom<RespArray> for HStrLen {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hstrlen"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field))) => Ok(HStrLen {
+                key: key.0,
+                field: field.0,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or field".to_string(),
+            )),
+        }
+    }
+}
+
+fn extract_field_and_delta(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, Bytes, Bytes), CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    match (args.next(), args.next(), args.next()) {
+        (
+            Some(RespFrame::BulkString(key)),
+            Some(RespFrame::BulkString(field)),
+            Some(RespFrame::BulkString(delta)),
+        ) => Ok((key.0, field.0, delta.0)),
+        _ => Err(CommandError::InvalidArgument(
+            "Invalid key, field or increment".to_string(),
+        )),
+    }
+}
+
+fn extract_hash_key(value: RespArray, name: &'static str) -> Result<Bytes, CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    match args.next() {
+        Some(RespFrame::BulkString(key)) => Ok(key.0),
+        _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    }
+}
+
 
 
 #[cfg(test)]
 mod tests {
-    use crate::RespDecode;
+    use crate::{RespDecode, RespEncode, SimpleError};
 
     use super::*;
+    use crate::cmd::{request_args, Command};
     use anyhow::Result;
     use bytes::BytesMut;
+    use std::collections::HashSet;
 
     #[test]
     fn test_hget_from_resp_array() -> Result<()> {
@@ -195,8 +511,10 @@ mod tests {
 
         let result: HSet = frame.try_into()?;
         assert_eq!(result.key, "map");
-        assert_eq!(result.field, "hello");
-        assert_eq!(result.value, RespFrame::BulkString(b"world".into()));
+        assert_eq!(
+            result.fields,
+            vec![("hello".into(), RespFrame::BulkString(b"world".into()))]
+        );
 
         Ok(())
     }
@@ -205,32 +523,27 @@ mod tests {
     fn test_hset_hget_hgetall_commands() -> Result<()> {
         let backend = crate::Backend::new();
         let cmd = HSet {
-            key: "map".to_string(),
-            field: "hello".to_string(),
-            value: RespFrame::BulkString(b"world".into()),
+            key: "map".into(),
+            fields: vec![("hello".into(), RespFrame::BulkString(b"world".into()))],
         };
-        let result = cmd.execute(&backend);
-        assert_eq!(result, RESP_OK.clone());
+        let result = cmd.execute(&backend).unwrap();
+        assert_eq!(result, RespFrame::Integer(1));
 
         let cmd = HSet {
-            key: "map".to_string(),
-            field: "hello1".to_string(),
-            value: RespFrame::BulkString(b"world1".into()),
+            key: "map".into(),
+            fields: vec![("hello1".into(), RespFrame::BulkString(b"world1".into()))],
         };
-        cmd.execute(&backend);
+        cmd.execute(&backend).unwrap();
 
         let cmd = HGet {
-            key: "map".to_string(),
-            field: "hello".to_string(),
+            key: "map".into(),
+            field: "hello".into(),
         };
-        let result = cmd.execute(&backend);
+        let result = cmd.execute(&backend).unwrap();
         assert_eq!(result, RespFrame::BulkString(b"world".into()));
 
-        let cmd = HGetAll {
-            key: "map".to_string(),
-            sort: true,
-        };
-        let result = cmd.execute(&backend);
+        let cmd = HGetAll { key: "map".into() };
+        let result = cmd.execute(&backend).unwrap().into_resp2();
 
         let expected = RespArray::new([
             BulkString::from("hello").into(),
@@ -255,4 +568,518 @@ mod tests {
         assert_eq!(result.fields[2], "nofield");
         Ok(())
     }
+
+    fn hash_backend() -> crate::Backend {
+        let backend = crate::Backend::new();
+        for field in ["f1", "f2", "f3"] {
+            backend
+                .hset(
+                    "map".into(),
+                    field.to_string().into(),
+                    BulkString::from("value").into(),
+                )
+                .unwrap();
+        }
+        backend
+    }
+
+    #[test]
+    fn test_hdel_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$4\r\nhdel\r\n$3\r\nmap\r\n$2\r\nf1\r\n$2\r\nf2\r\n");
+        let result: HDel = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(result.key, "map");
+        assert_eq!(result.fields, vec!["f1", "f2"]);
+
+        // the dispatcher checks the arity
+        buf.extend_from_slice(b"*2\r\n$4\r\nhdel\r\n$3\r\nmap\r\n");
+        let result: Result<Command, _> = RespArray::decode(&mut buf)?.try_into();
+        assert!(matches!(result, Err(CommandError::WrongArity("hdel"))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hdel_hexists_hlen_commands() -> Result<()> {
+        let backend = hash_backend();
+        let hlen = |key: &[u8]| {
+            HLen {
+                key: Bytes::copy_from_slice(key),
+            }
+            .execute(&backend)
+            .unwrap()
+        };
+        let hexists = |field: &[u8]| {
+            HExists {
+                key: "map".into(),
+                field: Bytes::copy_from_slice(field),
+            }
+            .execute(&backend)
+            .unwrap()
+        };
+        assert_eq!(hlen(b"map"), RespFrame::Integer(3));
+        assert_eq!(hlen(b"missing"), RespFrame::Integer(0));
+        assert_eq!(hexists(b"f1"), RespFrame::Integer(1));
+
+        let cmd = HDel {
+            key: "map".into(),
+            fields: vec!["f1".into(), "nope".into(), "f1".into()],
+        };
+        assert_eq!(cmd.execute(&backend)?, RespFrame::Integer(1));
+        assert_eq!(hexists(b"f1"), RespFrame::Integer(0));
+        assert_eq!(hlen(b"map"), RespFrame::Integer(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hdel_last_field_removes_key() -> Result<()> {
+        let backend = hash_backend();
+        backend.expire(b"map", 100_000);
+        let cmd = HDel {
+            key: "map".into(),
+            fields: vec!["f1".into(), "f2".into(), "f3".into()],
+        };
+        assert_eq!(cmd.execute(&backend)?, RespFrame::Integer(3));
+        assert_eq!(backend.key_type(b"map"), None);
+        assert!(!backend.entries.contains_key(b"map".as_slice()));
+        assert!(backend.volatile_keys() == 0);
+
+        // nothing left to delete
+        let cmd = HDel {
+            key: "map".into(),
+            fields: vec!["f1".into()],
+        };
+        assert_eq!(cmd.execute(&backend)?, RespFrame::Integer(0));
+        Ok(())
+    }
+
+    fn run(backend: &crate::Backend, request: &[u8]) -> Result<RespFrame> {
+        let mut buf = BytesMut::from(request);
+        let cmd = crate::cmd::Command::try_from(RespFrame::decode(&mut buf)?)?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    #[test]
+    fn test_hkeys_hvals_commands() -> Result<()> {
+        let backend = hash_backend();
+        let keys = run(&backend, b"*2\r\n$5\r\nhkeys\r\n$3\r\nmap\r\n")?;
+        let RespFrame::Array(mut keys) = keys else {
+            panic!("unexpected reply {:?}", keys);
+        };
+        keys.0.sort_by_key(|k| k.clone().encode());
+        assert_eq!(
+            keys.0,
+            vec![
+                BulkString::from("f1").into(),
+                BulkString::from("f2").into(),
+                BulkString::from("f3").into()
+            ]
+        );
+
+        let values = run(&backend, b"*2\r\n$5\r\nhvals\r\n$3\r\nmap\r\n")?;
+        assert_eq!(
+            values,
+            RespArray::new(vec![BulkString::from("value").into(); 3]).into()
+        );
+
+        let empty = run(&backend, b"*2\r\n$5\r\nhkeys\r\n$7\r\nmissing\r\n")?;
+        assert_eq!(empty, RespArray::new([]).into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hmget_positional_nils() -> Result<()> {
+        let backend = hash_backend();
+        let reply = run(
+            &backend,
+            b"*5\r\n$5\r\nhmget\r\n$3\r\nmap\r\n$2\r\nf2\r\n$4\r\nnope\r\n$2\r\nf1\r\n",
+        )?;
+        assert_eq!(
+            reply,
+            RespArray::new([
+                BulkString::from("value").into(),
+                RespFrame::NULL,
+                BulkString::from("value").into(),
+            ])
+            .into()
+        );
+
+        let reply = run(
+            &backend,
+            b"*3\r\n$5\r\nhmget\r\n$7\r\nmissing\r\n$2\r\nf1\r\n",
+        )?;
+        assert_eq!(reply, RespArray::new([RespFrame::NULL]).into());
+
+        let mut buf = BytesMut::from(&b"*2\r\n$5\r\nhmget\r\n$3\r\nmap\r\n"[..]);
+        let result: Result<Command, _> = RespArray::decode(&mut buf)?.try_into();
+        assert!(matches!(result, Err(CommandError::WrongArity("hmget"))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hset_multi_counts_new_fields() -> Result<()> {
+        let backend = hash_backend();
+        let reply = run(
+            &backend,
+            b"*8\r\n$4\r\nhset\r\n$3\r\nmap\r\n$2\r\nf1\r\n$3\r\nnew\r\n$2\r\nf4\r\n$1\r\na\r\n$2\r\nf5\r\n$1\r\nb\r\n",
+        )?;
+        // f1 already existed and is only updated
+        assert_eq!(reply, RespFrame::Integer(2));
+        assert_eq!(
+            backend.hget(b"map", b"f1"),
+            Some(BulkString::from("new").into())
+        );
+        assert_eq!(backend.hlen(b"map")?, 5);
+
+        let reply = run(
+            &backend,
+            b"*4\r\n$5\r\nhmset\r\n$3\r\nmap\r\n$2\r\nf6\r\n$1\r\nc\r\n",
+        )?;
+        assert_eq!(reply, RESP_OK.clone());
+        assert_eq!(
+            backend.hget(b"map", b"f6"),
+            Some(BulkString::from("c").into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hset_odd_arguments() -> Result<()> {
+        let mut buf = BytesMut::from(
+            &b"*5\r\n$4\r\nhset\r\n$3\r\nmap\r\n$2\r\nf1\r\n$1\r\na\r\n$2\r\nf2\r\n"[..],
+        );
+        let result: Result<HSet, _> = RespArray::decode(&mut buf)?.try_into();
+        assert!(matches!(result, Err(CommandError::WrongArity("hset"))));
+
+        let mut buf = BytesMut::from(&b"*3\r\n$5\r\nhmset\r\n$3\r\nmap\r\n$2\r\nf1\r\n"[..]);
+        let result: Result<HMSet, _> = RespArray::decode(&mut buf)?.try_into();
+        assert!(matches!(result, Err(CommandError::WrongArity("hmset"))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hincrby_hincrbyfloat_commands() -> Result<()> {
+        let backend = crate::Backend::new();
+        let reply = run(
+            &backend,
+            b"*4\r\n$7\r\nhincrby\r\n$3\r\nmap\r\n$5\r\ncount\r\n$2\r\n-5\r\n",
+        )?;
+        assert_eq!(reply, RespFrame::Integer(-5));
+        assert_eq!(
+            backend.hget(b"map", b"count"),
+            Some(BulkString::from("-5").into())
+        );
+
+        let reply = run(
+            &backend,
+            b"*4\r\n$12\r\nhincrbyfloat\r\n$3\r\nmap\r\n$5\r\ncount\r\n$3\r\n5.5\r\n",
+        )?;
+        assert_eq!(reply, BulkString::from("0.5").into());
+        let reply = run(
+            &backend,
+            b"*4\r\n$12\r\nhincrbyfloat\r\n$3\r\nmap\r\n$5\r\nfloat\r\n$4\r\n3.00\r\n",
+        )?;
+        assert_eq!(reply, BulkString::from("3").into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hincrby_errors() -> Result<()> {
+        let backend = hash_backend();
+        assert_eq!(
+            RespFrame::from(backend.hincr_by(b"map", b"f1", 1).unwrap_err()),
+            SimpleError::new("ERR hash value is not an integer").into()
+        );
+        assert_eq!(
+            RespFrame::from(backend.hincr_by_float(b"map", b"f1", 1.0).unwrap_err()),
+            SimpleError::new("ERR hash value is not a float").into()
+        );
+
+        backend.hset(
+            "map".into(),
+            "max".into(),
+            BulkString::from(i64::MAX.to_string()).into(),
+        )?;
+        assert!(matches!(
+            backend.hincr_by(b"map", b"max", 1),
+            Err(CommandError::Overflow)
+        ));
+        // the failed increment left the value alone
+        assert_eq!(
+            backend.hget(b"map", b"max"),
+            Some(BulkString::from(i64::MAX.to_string()).into())
+        );
+
+        let mut buf =
+            BytesMut::from(&b"*4\r\n$7\r\nhincrby\r\n$3\r\nmap\r\n$2\r\nf1\r\n$3\r\n1.5\r\n"[..]);
+        let result: Result<HIncrBy, _> = RespArray::decode(&mut buf)?.try_into();
+        assert!(matches!(result, Err(CommandError::NotAnInteger)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_hincrby() {
+        let backend = crate::Backend::new();
+        let handles = (0..8)
+            .map(|_| {
+                let backend = backend.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        backend.hincr_by(b"counters", b"hits", 1).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(
+            backend.hget(b"counters", b"hits"),
+            Some(BulkString::from("8000").into())
+        );
+    }
+
+    #[test]
+    fn test_hsetnx_command() -> Result<()> {
+        let backend = crate::Backend::new();
+        let request = b"*4\r\n$6\r\nhsetnx\r\n$3\r\nmap\r\n$5\r\nfield\r\n$2\r\nv1\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(1));
+        let request = b"*4\r\n$6\r\nhsetnx\r\n$3\r\nmap\r\n$5\r\nfield\r\n$2\r\nv2\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(0));
+        assert_eq!(
+            backend.hget(b"map", b"field"),
+            Some(BulkString::from("v1").into())
+        );
+
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(matches!(
+            backend.hsetnx(
+                "string".into(),
+                "field".into(),
+                BulkString::from("value").into()
+            ),
+            Err(CommandError::WrongType)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_hsetnx_single_winner() {
+        for round in 0..20 {
+            let backend = crate::Backend::new();
+            let key = format!("race{}", round);
+            let tasks = (0..32)
+                .map(|i| {
+                    let (backend, key) = (backend.clone(), key.clone());
+                    tokio::spawn(async move {
+                        let value = BulkString::from(i.to_string()).into();
+                        backend
+                            .hsetnx(key.into(), "field".into(), value)
+                            .map(|set| set.then_some(i))
+                    })
+                })
+                .collect::<Vec<_>>();
+            let mut winners = Vec::new();
+            for task in tasks {
+                if let Some(i) = task.await.unwrap().unwrap() {
+                    winners.push(i);
+                }
+            }
+            assert_eq!(winners.len(), 1);
+            assert_eq!(
+                backend.hget(key.as_bytes(), b"field"),
+                Some(BulkString::from(winners[0].to_string()).into())
+            );
+        }
+    }
+
+    fn hrandfield(backend: &crate::Backend, args: &[&str]) -> Result<RespFrame> {
+        let cmd = HRandField::try_from(request_args(&[&["hrandfield"], args].concat()))?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    fn bulk_strings(frame: RespFrame) -> Vec<String> {
+        let RespFrame::Array(frames) = frame else {
+            panic!("unexpected reply {:?}", frame);
+        };
+        frames
+            .0
+            .into_iter()
+            .map(|frame| match frame {
+                RespFrame::BulkString(s) => String::from_utf8(s.to_vec()).unwrap(),
+                frame => panic!("unexpected element {:?}", frame),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hrandfield_command() -> Result<()> {
+        let backend = crate::Backend::new();
+        for i in 0..10 {
+            backend
+                .hset(
+                    "map".into(),
+                    format!("f{}", i).into(),
+                    BulkString::from(format!("v{}", i)).into(),
+                )
+                .unwrap();
+        }
+
+        let RespFrame::BulkString(field) = hrandfield(&backend, &["map"])? else {
+            panic!("expected a single field");
+        };
+        assert!(backend.hexists(b"map", &field.0)?);
+
+        // a positive count never repeats a field and is capped by the size of the hash
+        for (count, expected) in [("5", 5), ("10", 10), ("20", 10)] {
+            let mut fields = bulk_strings(hrandfield(&backend, &["map", count])?);
+            assert_eq!(fields.len(), expected);
+            fields.sort();
+            fields.dedup();
+            assert_eq!(fields.len(), expected);
+        }
+
+        // a negative count returns exactly that many fields, so 30 out of 10 must repeat
+        let fields = bulk_strings(hrandfield(&backend, &["map", "-30"])?);
+        assert_eq!(fields.len(), 30);
+        assert!(fields.iter().collect::<HashSet<_>>().len() < 30);
+        assert!(fields.iter().all(|f| f.starts_with('f')));
+
+        // WITHVALUES interleaves every field with its own value
+        for count in ["4", "-4"] {
+            let reply = bulk_strings(hrandfield(&backend, &["map", count, "WITHVALUES"])?);
+            assert_eq!(reply.len(), 8);
+            for pair in reply.chunks(2) {
+                assert_eq!(pair[1], pair[0].replace('f', "v"));
+            }
+        }
+
+        assert_eq!(hrandfield(&backend, &["missing"])?, RespFrame::NULL);
+        assert_eq!(
+            hrandfield(&backend, &["missing", "3"])?,
+            RespArray::new([]).into()
+        );
+        assert!(hrandfield(&backend, &["map", "3", "WITHSCORES"]).is_err());
+        assert!(hrandfield(&backend, &["map", "many"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hscan_reassembles_hash() -> Result<()> {
+        let backend = crate::Backend::new();
+        let fields = (0..5000)
+            .map(|i| {
+                (
+                    format!("field{}", i).into(),
+                    BulkString::from(i.to_string()).into(),
+                )
+            })
+            .collect::<Vec<_>>();
+        backend.hset_multi("map".into(), fields)?;
+
+        let mut seen = std::collections::HashMap::new();
+        let mut cursor = "0".to_string();
+        loop {
+            let request = format!(
+                "*5\r\n$5\r\nhscan\r\n$3\r\nmap\r\n${}\r\n{}\r\n$5\r\nCOUNT\r\n$2\r\n50\r\n",
+                cursor.len(),
+                cursor
+            );
+            let RespFrame::Array(reply) = run(&backend, request.as_bytes())? else {
+                panic!("expected an array");
+            };
+            let mut reply = reply.0.into_iter();
+            let (Some(RespFrame::BulkString(next)), Some(entries)) = (reply.next(), reply.next())
+            else {
+                panic!("expected a cursor and the entries");
+            };
+            let entries = match entries {
+                RespFrame::Array(entries) => entries.0,
+                _ => panic!("expected the entries"),
+            };
+            assert!(entries.len() <= 2 * 60);
+            for pair in entries.chunks(2) {
+                let (RespFrame::BulkString(field), RespFrame::BulkString(value)) =
+                    (&pair[0], &pair[1])
+                else {
+                    panic!("expected field value pairs");
+                };
+                seen.insert(field.to_vec(), value.to_vec());
+            }
+            cursor = String::from_utf8(next.to_vec())?;
+            if cursor == "0" {
+                break;
+            }
+        }
+
+        assert_eq!(seen.len(), 5000);
+        for i in 0..5000 {
+            assert_eq!(
+                seen[format!("field{}", i).as_bytes()],
+                i.to_string().as_bytes()
+            );
+        }
+
+        let reply = run(
+            &backend,
+            b"*3\r\n$5\r\nhscan\r\n$7\r\nmissing\r\n$1\r\n0\r\n",
+        )?;
+        assert_eq!(
+            reply,
+            RespArray::new(vec![
+                BulkString::from("0").into(),
+                RespArray::new([]).into()
+            ])
+            .into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hgetall_sorted_round_trip() -> Result<()> {
+        let backend = crate::Backend::new();
+        for field in ["zeta", "alpha", "mid", "beta"] {
+            let request = format!(
+                "*4\r\n$4\r\nhset\r\n$3\r\nmap\r\n${}\r\n{}\r\n$1\r\n{}\r\n",
+                field.len(),
+                field,
+                &field[..1]
+            );
+            run(&backend, request.as_bytes())?;
+        }
+
+        let reply = run(&backend, b"*2\r\n$7\r\nhgetall\r\n$3\r\nmap\r\n")?;
+        assert_eq!(
+            reply.clone().encode(),
+            b"%4\r\n$5\r\nalpha\r\n$1\r\na\r\n$4\r\nbeta\r\n$1\r\nb\r\n\
+              $3\r\nmid\r\n$1\r\nm\r\n$4\r\nzeta\r\n$1\r\nz\r\n"
+        );
+        assert_eq!(
+            reply.into_resp2().encode(),
+            b"*8\r\n$5\r\nalpha\r\n$1\r\na\r\n$4\r\nbeta\r\n$1\r\nb\r\n\
+              $3\r\nmid\r\n$1\r\nm\r\n$4\r\nzeta\r\n$1\r\nz\r\n"
+        );
+        assert_eq!(
+            run(&backend, b"*2\r\n$7\r\nhgetall\r\n$7\r\nmissing\r\n")?,
+            RespMap::new().into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hstrlen_command() -> Result<()> {
+        let backend = crate::Backend::new();
+        backend.hset(
+            "map".into(),
+            "field".into(),
+            BulkString::from("hello world").into(),
+        )?;
+
+        let request = b"*3\r\n$7\r\nhstrlen\r\n$3\r\nmap\r\n$5\r\nfield\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b":11\r\n");
+        let request = b"*3\r\n$7\r\nhstrlen\r\n$3\r\nmap\r\n$7\r\nmissing\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(0));
+        let request = b"*3\r\n$7\r\nhstrlen\r\n$7\r\nmissing\r\n$5\r\nfield\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(0));
+        Ok(())
+    }
 }
@@ -1,26 +1,175 @@
-use crate::{RespArray, RespFrame};
+use crate::{BulkString, KeyType, KeyspaceEvents, RespArray, RespFrame};
 
-use super::{extract_args, validate_command, CommandError, CommandExecutor, SAdd, SIsMember};
+use super::{
+    extract_args, extract_key_args, extract_key_scan_args, parse_integer, validate_command,
+    CommandError, CommandExecutor, SAdd, SCard, SDiff, SDiffStore, SInter, SInterCard, SInterStore,
+    SIsMember, SMIsMember, SMembers, SMove, SPop, SRandMember, SRem, SScan, SUnion, SUnionStore,
+};
+use bytes::Bytes;
 
 impl CommandExecutor for SAdd {
-    fn execute(self, backend: &crate::Backend) -> crate::RespFrame {
-        let response = self
-            .members
-            .into_iter()
-            .map(|f| backend.sadd(self.key.clone(), f))
-            .map(|b| RespFrame::Integer(b as i64))
-            .collect();
-        RespFrame::Array(RespArray(response))
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let mut added = 0;
+        for member in self.members {
+            added += backend.sadd(self.key.clone(), member)? as i64;
+        }
+        if added > 0 {
+            backend.notify_keyspace_event(KeyspaceEvents::SET, "sadd", &self.key);
+        }
+        Ok(RespFrame::Integer(added))
     }
 }
 
 
 impl CommandExecutor for SIsMember {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        RespFrame::Integer(backend.sismember(&self.key, &self.member) as i64)
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.check_type(&self.key, KeyType::Set)?;
+        Ok(RespFrame::Integer(
+            backend.sismember(&self.key, &self.member) as i64,
+        ))
+    }
+}
+
+impl CommandExecutor for SScan {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let pattern = self.pattern.as_deref();
+        let (cursor, members) = backend.sscan(&self.key, self.cursor, pattern, self.count)?;
+        Ok(RespArray::new(vec![
+            BulkString::from(cursor.to_string()).into(),
+            members_array(members),
+        ])
+        .into())
+    }
+}
+
+impl CommandExecutor for SRem {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let removed = backend.srem(&self.key, &self.members)?;
+        if removed > 0 {
+            backend.notify_keyspace_event(KeyspaceEvents::SET, "srem", &self.key);
+        }
+        Ok(RespFrame::Integer(removed))
+    }
+}
+
+impl CommandExecutor for SCard {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.scard(&self.key)?))
+    }
+}
+
+impl CommandExecutor for SMembers {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(members_array(backend.smembers(&self.key)?))
+    }
+}
+
+impl CommandExecutor for SPop {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        match self.count {
+            None => Ok(single_member(backend.spop(&self.key, 1)?)),
+            Some(count) => Ok(members_array(backend.spop(&self.key, count as usize)?)),
+        }
+    }
+}
+
+impl CommandExecutor for SRandMember {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        match self.count {
+            None => Ok(single_member(backend.srandom(&self.key, 1, true)?)),
+            // a negative count asks for exactly that many members, allowing repeats
+            Some(count) => Ok(members_array(backend.srandom(
+                &self.key,
+                count.unsigned_abs() as usize,
+                count >= 0,
+            )?)),
+        }
+    }
+}
+
+impl CommandExecutor for SInter {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let members = backend.sinter(&self.keys)?;
+        Ok(members_array(members.into_iter().collect()))
+    }
+}
+
+impl CommandExecutor for SInterStore {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let members = backend.sinter(&self.keys)?;
+        Ok(RespFrame::Integer(backend.sstore(&self.dest, members)))
+    }
+}
+
+impl CommandExecutor for SUnion {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let members = backend.sunion(&self.keys)?;
+        Ok(members_array(members.into_iter().collect()))
+    }
+}
+
+impl CommandExecutor for SUnionStore {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let members = backend.sunion(&self.keys)?;
+        Ok(RespFrame::Integer(backend.sstore(&self.dest, members)))
+    }
+}
+
+impl CommandExecutor for SDiff {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let members = backend.sdiff(&self.keys)?;
+        Ok(members_array(members.into_iter().collect()))
     }
 }
 
+impl CommandExecutor for SDiffStore {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let members = backend.sdiff(&self.keys)?;
+        Ok(RespFrame::Integer(backend.sstore(&self.dest, members)))
+    }
+}
+
+impl CommandExecutor for SMove {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let moved = backend.smove(&self.src, &self.dst, &self.member)?;
+        Ok(RespFrame::Integer(moved as i64))
+    }
+}
+
+impl CommandExecutor for SMIsMember {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let found = backend
+            .smismember(&self.key, &self.members)?
+            .into_iter()
+            .map(|found| RespFrame::Integer(found as i64))
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(found).into())
+    }
+}
+
+impl CommandExecutor for SInterCard {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(
+            backend.sintercard(&self.keys, self.limit)?,
+        ))
+    }
+}
+
+fn single_member(mut members: Vec<Bytes>) -> RespFrame {
+    match members.pop() {
+        Some(member) => BulkString::from(member).into(),
+        None => RespFrame::NULL,
+    }
+}
+
+fn members_array(members: Vec<Bytes>) -> RespFrame {
+    let members = members
+        .into_iter()
+        .map(|member| BulkString::from(member).into())
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(members).into()
+}
+
 impl TryFrom<RespArray> for SAdd {
     type Error = CommandError;
 
@@ -37,18 +186,18 @@ impl TryFrom<RespArray> for SAdd {
                     "sadd command needs at least 2 argument, got {len}",
                 )))
             }
-            _ => validate_command(&value, &["sadd"], len - 1)?,
+            _ => validate_command(&value, &["sadd"])?,
         }
 
         let mut args = extract_args(value, 1)?.into_iter();
         let key = match args.next() {
-            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            Some(RespFrame::BulkString(key)) => key.0,
             _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
         };
         let mut members = vec![];
         loop {
             match args.next() {
-                Some(RespFrame::BulkString(key)) => members.push(String::from_utf8(key.0)?),
+                Some(RespFrame::BulkString(key)) => members.push(key.0),
                 None => break,
                 _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
             };
@@ -61,14 +210,14 @@ impl TryFrom<RespArray> for SIsMember {
     type Error = CommandError;
 
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["sismember"], 2)?;
+        validate_command(&value, &["sismember"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(member))) => {
                 Ok(SIsMember {
-                    key: String::from_utf8(key.0)?,
-                    member: String::from_utf8(member.0)?,
+                    key: key.0,
+                    member: member.0,
                 })
             }
             _ => Err(CommandError::InvalidArgument(
@@ -76,4 +225,622 @@ impl TryFrom<RespArray> for SIsMember {
             )),
         }
     }
-}
\ No newline at end of file
+}
+
+impl TryFrom<RespArray> for SScan {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, cursor, pattern, count) = extract_key_scan_args(value, "sscan")?;
+        Ok(SScan {
+            key,
+            cursor,
+            pattern,
+            count,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SRem {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["srem"])?;
+
+        let mut members = extract_key_args(value, 1)?;
+        let key = members.remove(0);
+        Ok(SRem { key, members })
+    }
+}
+
+impl TryFrom<RespArray> for SCard {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(SCard {
+            key: extract_set_key(value, "scard")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SMembers {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(SMembers {
+            key: extract_set_key(value, "smembers")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_key_and_count(value, "spop")?;
+        if count.is_some_and(|count| count < 0) {
+            return Err(CommandError::NotPositive);
+        }
+        Ok(SPop { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for SRandMember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_key_and_count(value, "srandmember")?;
+        Ok(SRandMember { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for SInter {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sinter"])?;
+        Ok(SInter {
+            keys: extract_key_args(value, 1)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SInterStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (dest, keys) = extract_store_args(value, "sinterstore")?;
+        Ok(SInterStore { dest, keys })
+    }
+}
+
+impl TryFrom<RespArray> for SUnion {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sunion"])?;
+        Ok(SUnion {
+            keys: extract_key_args(value, 1)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SUnionStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (dest, keys) = extract_store_args(value, "sunionstore")?;
+        Ok(SUnionStore { dest, keys })
+    }
+}
+
+impl TryFrom<RespArray> for SDiff {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sdiff"])?;
+        Ok(SDiff {
+            keys: extract_key_args(value, 1)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SDiffStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (dest, keys) = extract_store_args(value, "sdiffstore")?;
+        Ok(SDiffStore { dest, keys })
+    }
+}
+
+impl TryFrom<RespArray> for SMove {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["smove"])?;
+        let mut args = extract_key_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (Some(src), Some(dst), Some(member)) => Ok(SMove { src, dst, member }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid source, destination or member".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for SMIsMember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["smismember"])?;
+        let mut members = extract_key_args(value, 1)?;
+        let key = members.remove(0);
+        Ok(SMIsMember { key, members })
+    }
+}
+
+impl TryFrom<RespArray> for SInterCard {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sintercard"])?;
+
+        // unlike the other set commands the keys are counted up front
+        let mut args = extract_args(value, 1)?.into_iter();
+        let numkeys = match args.next() {
+            Some(RespFrame::BulkString(numkeys)) => parse_integer(&numkeys)?,
+            _ => return Err(CommandError::NotAnInteger),
+        };
+        if numkeys < 1 {
+            return Err(CommandError::NumKeysNotPositive);
+        }
+        if numkeys as usize > args.len() {
+            return Err(CommandError::TooManyKeys);
+        }
+        let keys = args
+            .by_ref()
+            .take(numkeys as usize)
+            .map(|frame| match frame {
+                RespFrame::BulkString(key) => Ok(key.0),
+                _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+            })
+            .collect::<Result<Vec<_>, CommandError>>()?;
+
+        let limit = match (args.next(), args.next(), args.next()) {
+            (None, _, _) => 0,
+            (Some(RespFrame::BulkString(arg)), Some(RespFrame::BulkString(limit)), None)
+                if arg.eq_ignore_ascii_case(b"limit") =>
+            {
+                match parse_integer(&limit)? {
+                    limit if limit < 0 => return Err(CommandError::NegativeLimit),
+                    limit => limit as usize,
+                }
+            }
+            _ => return Err(CommandError::SyntaxError),
+        };
+        Ok(SInterCard { keys, limit })
+    }
+}
+
+// the `dest key [key ...]` arguments of the commands storing their result
+fn extract_store_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, Vec<Bytes>), CommandError> {
+    validate_command(&value, &[name])?;
+    let mut keys = extract_key_args(value, 1)?;
+    let dest = keys.remove(0);
+    Ok((dest, keys))
+}
+
+// the `key [count]` arguments of the commands picking random members
+fn extract_key_and_count(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, Option<i64>), CommandError> {
+    validate_command(&value, &[name])?;
+    if value.len() > 3 {
+        return Err(CommandError::SyntaxError);
+    }
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = match args.next() {
+        Some(RespFrame::BulkString(key)) => key.0,
+        _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    };
+    let count = match args.next() {
+        Some(RespFrame::BulkString(count)) => Some(parse_integer(&count)?),
+        Some(_) => return Err(CommandError::NotAnInteger),
+        None => None,
+    };
+    Ok((key, count))
+}
+
+fn extract_set_key(value: RespArray, name: &'static str) -> Result<Bytes, CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    match args.next() {
+        Some(RespFrame::BulkString(key)) => Ok(key.0),
+        _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::request_args;
+    use crate::{RespDecode, RespEncode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn run(backend: &crate::Backend, request: &[u8]) -> Result<RespFrame> {
+        let mut buf = BytesMut::from(request);
+        let cmd = crate::cmd::Command::try_from(RespFrame::decode(&mut buf)?)?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    #[test]
+    fn test_srem_scard_smembers_commands() -> Result<()> {
+        let backend = crate::Backend::new();
+        for member in ["a", "b", "c"] {
+            backend.sadd("set", member)?;
+        }
+
+        assert_eq!(
+            run(&backend, b"*2\r\n$5\r\nscard\r\n$3\r\nset\r\n")?,
+            RespFrame::Integer(3)
+        );
+        let RespFrame::Array(members) = run(&backend, b"*2\r\n$8\r\nsmembers\r\n$3\r\nset\r\n")?
+        else {
+            panic!("expected an array");
+        };
+        let mut members = members
+            .0
+            .into_iter()
+            .map(|m| match m {
+                RespFrame::BulkString(m) => String::from_utf8(m.to_vec()).unwrap(),
+                m => panic!("unexpected member {:?}", m),
+            })
+            .collect::<Vec<_>>();
+        members.sort();
+        assert_eq!(members, ["a", "b", "c"]);
+
+        // only members that existed are counted
+        let request = b"*4\r\n$4\r\nsrem\r\n$3\r\nset\r\n$1\r\na\r\n$1\r\nx\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(1));
+        assert_eq!(backend.scard(b"set")?, 2);
+
+        // removing the last members removes the key
+        let request = b"*4\r\n$4\r\nsrem\r\n$3\r\nset\r\n$1\r\nb\r\n$1\r\nc\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(2));
+        assert_eq!(
+            run(&backend, b"*2\r\n$6\r\nexists\r\n$3\r\nset\r\n")?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(backend.scard(b"set")?, 0);
+        assert_eq!(backend.smembers(b"set")?, Vec::<String>::new());
+        assert_eq!(
+            run(&backend, b"*3\r\n$4\r\nsrem\r\n$3\r\nset\r\n$1\r\na\r\n")?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_commands_wrong_type() {
+        let backend = crate::Backend::new();
+        backend.set("string".into(), crate::BulkString::from("value").into());
+        assert!(backend.srem(b"string", &["a".into()]).is_err());
+        assert!(backend.scard(b"string").is_err());
+        assert!(backend.smembers(b"string").is_err());
+    }
+
+    #[test]
+    fn test_spop_shrinks_set() -> Result<()> {
+        let backend = crate::Backend::new();
+        for i in 0..10 {
+            backend.sadd("set", i.to_string())?;
+        }
+
+        let RespFrame::BulkString(popped) = run(&backend, b"*2\r\n$4\r\nspop\r\n$3\r\nset\r\n")?
+        else {
+            panic!("expected a single member");
+        };
+        let popped = String::from_utf8(popped.to_vec())?;
+        assert!(!backend.sismember(b"set", popped.as_bytes()));
+        assert_eq!(backend.scard(b"set")?, 9);
+
+        let RespFrame::Array(popped) =
+            run(&backend, b"*3\r\n$4\r\nspop\r\n$3\r\nset\r\n$1\r\n4\r\n")?
+        else {
+            panic!("expected an array");
+        };
+        assert_eq!(popped.len(), 4);
+        assert_eq!(backend.scard(b"set")?, 5);
+
+        // more than the cardinality pops everything and deletes the key
+        let RespFrame::Array(popped) =
+            run(&backend, b"*3\r\n$4\r\nspop\r\n$3\r\nset\r\n$2\r\n50\r\n")?
+        else {
+            panic!("expected an array");
+        };
+        assert_eq!(popped.len(), 5);
+        assert_eq!(backend.key_type(b"set"), None);
+        assert_eq!(
+            run(&backend, b"*2\r\n$4\r\nspop\r\n$3\r\nset\r\n")?,
+            RespFrame::NULL
+        );
+
+        let request = b"*3\r\n$4\r\nspop\r\n$3\r\nset\r\n$2\r\n-1\r\n";
+        assert!(run(&backend, request).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_srandmember_command() -> Result<()> {
+        let backend = crate::Backend::new();
+        for member in ["a", "b", "c"] {
+            backend.sadd("set", member)?;
+        }
+
+        // a positive count returns distinct members, capped by the cardinality
+        let mut members = backend.srandom(b"set", 10, true)?;
+        members.sort();
+        assert_eq!(members, ["a", "b", "c"]);
+
+        // a negative count returns exactly that many, so 20 out of 3 must repeat
+        let request = b"*3\r\n$11\r\nsrandmember\r\n$3\r\nset\r\n$3\r\n-20\r\n";
+        let RespFrame::Array(members) = run(&backend, request)? else {
+            panic!("expected an array");
+        };
+        assert_eq!(members.len(), 20);
+        let distinct = members
+            .iter()
+            .map(|m| m.clone().encode())
+            .collect::<std::collections::HashSet<_>>();
+        assert!(distinct.len() <= 3);
+        assert_eq!(backend.scard(b"set")?, 3);
+
+        let request = b"*2\r\n$11\r\nsrandmember\r\n$7\r\nmissing\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::NULL);
+        Ok(())
+    }
+
+    fn set_backend() -> crate::Backend {
+        let backend = crate::Backend::new();
+        for (key, members) in [
+            ("s1", &["a", "b", "c", "d"][..]),
+            ("s2", &["c", "d", "e"]),
+            ("s3", &["d", "x"]),
+            ("other", &["y", "z"]),
+        ] {
+            for member in members {
+                backend.sadd(key, *member).unwrap();
+            }
+        }
+        backend
+    }
+
+    fn sorted(members: std::collections::HashSet<Bytes>) -> Vec<Bytes> {
+        let mut members = members.into_iter().collect::<Vec<_>>();
+        members.sort();
+        members
+    }
+
+    fn keys(keys: &[&str]) -> Vec<Bytes> {
+        keys.iter()
+            .map(|key| Bytes::copy_from_slice(key.as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_set_algebra() -> Result<()> {
+        let backend = set_backend();
+
+        // overlapping sets
+        assert_eq!(sorted(backend.sinter(&keys(&["s1", "s2"]))?), ["c", "d"]);
+        assert_eq!(sorted(backend.sinter(&keys(&["s1", "s2", "s3"]))?), ["d"]);
+        assert_eq!(
+            sorted(backend.sunion(&keys(&["s1", "s2", "s3"]))?),
+            ["a", "b", "c", "d", "e", "x"]
+        );
+        assert_eq!(sorted(backend.sdiff(&keys(&["s1", "s2"]))?), ["a", "b"]);
+        assert_eq!(sorted(backend.sdiff(&keys(&["s2", "s1", "s3"]))?), ["e"]);
+
+        // disjoint sets
+        assert!(backend.sinter(&keys(&["s1", "other"]))?.is_empty());
+        assert_eq!(sorted(backend.sdiff(&keys(&["other", "s1"]))?), ["y", "z"]);
+
+        // missing keys are empty sets
+        assert!(backend.sinter(&keys(&["s1", "missing"]))?.is_empty());
+        assert_eq!(
+            sorted(backend.sunion(&keys(&["missing", "s3"]))?),
+            ["d", "x"]
+        );
+        assert_eq!(
+            sorted(backend.sdiff(&keys(&["s3", "missing"]))?),
+            ["d", "x"]
+        );
+        assert!(backend.sdiff(&keys(&["missing", "s3"]))?.is_empty());
+
+        backend.set("string".into(), crate::BulkString::from("value").into());
+        assert!(backend.sunion(&keys(&["s1", "string"])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_algebra_commands() -> Result<()> {
+        let backend = set_backend();
+        let request = b"*3\r\n$6\r\nsinter\r\n$2\r\ns1\r\n$2\r\ns3\r\n";
+        assert_eq!(
+            run(&backend, request)?,
+            RespArray::new(vec![BulkString::from("d").into()]).into()
+        );
+
+        let request = b"*4\r\n$11\r\nsunionstore\r\n$4\r\ndest\r\n$2\r\ns2\r\n$2\r\ns3\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(4));
+        assert_eq!(
+            sorted(backend.smembers(b"dest")?.into_iter().collect()),
+            ["c", "d", "e", "x"]
+        );
+
+        // an empty result deletes the destination
+        let request = b"*4\r\n$11\r\nsinterstore\r\n$4\r\ndest\r\n$2\r\ns1\r\n$5\r\nother\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(0));
+        assert_eq!(backend.key_type(b"dest"), None);
+
+        // the destination may be one of the sources
+        let request = b"*4\r\n$10\r\nsdiffstore\r\n$2\r\ns1\r\n$2\r\ns1\r\n$2\r\ns2\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(2));
+        assert_eq!(
+            sorted(backend.smembers(b"s1")?.into_iter().collect()),
+            ["a", "b"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_replaces_string() -> Result<()> {
+        let backend = set_backend();
+        backend.set("dest".into(), BulkString::from("value").into());
+        backend.expire(b"dest", 100_000);
+
+        let request = b"*4\r\n$11\r\nsinterstore\r\n$4\r\ndest\r\n$2\r\ns1\r\n$2\r\ns2\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(2));
+        assert_eq!(backend.get(b"dest"), None);
+        assert_eq!(
+            sorted(backend.smembers(b"dest")?.into_iter().collect()),
+            ["c", "d"]
+        );
+        assert_eq!(backend.pttl(b"dest"), -1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_smove() -> Result<()> {
+        let backend = set_backend();
+
+        // the member is not in the source
+        assert!(!backend.smove(b"s1", b"s2", b"x")?);
+        assert!(!backend.smove(b"missing", b"s2", b"a")?);
+        assert_eq!(backend.scard(b"s2")?, 3);
+
+        assert!(backend.smove(b"s1", b"s2", b"a")?);
+        assert!(!backend.sismember(b"s1", b"a"));
+        assert!(backend.sismember(b"s2", b"a"));
+        // already in the destination, it is only removed from the source
+        assert!(backend.smove(b"s1", b"s2", b"c")?);
+        assert_eq!(backend.scard(b"s2")?, 4);
+
+        // a missing destination is created, an emptied source removed
+        assert!(backend.smove(b"s3", b"new", b"d")?);
+        assert!(backend.smove(b"s3", b"new", b"x")?);
+        assert_eq!(backend.key_type(b"s3"), None);
+        assert_eq!(
+            sorted(backend.smembers(b"new")?.into_iter().collect()),
+            ["d", "x"]
+        );
+
+        // moving within the same set only reports whether the member is there
+        assert!(backend.smove(b"new", b"new", b"d")?);
+        assert!(!backend.smove(b"new", b"new", b"a")?);
+        assert_eq!(backend.scard(b"new")?, 2);
+
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(backend.smove(b"new", b"string", b"d").is_err());
+        assert!(backend.sismember(b"new", b"d"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_smismember_command() -> Result<()> {
+        let backend = set_backend();
+        let request = b"*5\r\n$10\r\nsmismember\r\n$2\r\ns3\r\n$1\r\nx\r\n$1\r\na\r\n$1\r\nd\r\n";
+        assert_eq!(
+            run(&backend, request)?.encode(),
+            b"*3\r\n:1\r\n:0\r\n:1\r\n"
+        );
+        let request = b"*4\r\n$10\r\nsmismember\r\n$7\r\nmissing\r\n$1\r\na\r\n$1\r\nb\r\n";
+        assert_eq!(
+            run(&backend, request)?,
+            RespArray::new(vec![RespFrame::Integer(0), RespFrame::Integer(0)]).into()
+        );
+        Ok(())
+    }
+
+    fn sintercard(backend: &crate::Backend, args: &[&str]) -> Result<RespFrame> {
+        let cmd = SInterCard::try_from(request_args(&[&["sintercard"], args].concat()))?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    #[test]
+    fn test_sintercard_command() -> Result<()> {
+        let backend = set_backend();
+        assert_eq!(
+            sintercard(&backend, &["2", "s1", "s2"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(
+            sintercard(&backend, &["3", "s1", "s2", "s3"])?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            sintercard(&backend, &["2", "s1", "missing"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(sintercard(&backend, &["1", "s1"])?, RespFrame::Integer(4));
+
+        // the count stops at the limit, 0 means no limit
+        assert_eq!(
+            sintercard(&backend, &["1", "s1", "LIMIT", "3"])?,
+            RespFrame::Integer(3)
+        );
+        assert_eq!(
+            sintercard(&backend, &["2", "s1", "s2", "limit", "1"])?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            sintercard(&backend, &["2", "s1", "s2", "LIMIT", "0"])?,
+            RespFrame::Integer(2)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sintercard_numkeys() {
+        let backend = set_backend();
+        for (args, error) in [
+            (&["0", "s1"][..], CommandError::NumKeysNotPositive),
+            (&["3", "s1", "s2"], CommandError::TooManyKeys),
+            // with numkeys 1 the second key is an unknown option
+            (&["1", "s1", "s2"], CommandError::SyntaxError),
+            (&["1", "s1", "LIMIT"], CommandError::SyntaxError),
+            (&["1", "s1", "LIMIT", "-1"], CommandError::NegativeLimit),
+            (&["many", "s1"], CommandError::NotAnInteger),
+        ] {
+            let err = sintercard(&backend, args).unwrap_err();
+            assert_eq!(err.to_string(), error.to_string(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_sadd_replies_with_count() -> Result<()> {
+        let backend = crate::Backend::new();
+        backend.sadd("set", "b")?;
+
+        let mut buf = BytesMut::from(
+            &b"*5\r\n$4\r\nsadd\r\n$3\r\nset\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"[..],
+        );
+        let cmd = crate::cmd::Command::try_from(RespFrame::decode(&mut buf)?)?;
+        assert_eq!(cmd.execute(&backend)?.encode(), b":2\r\n");
+        assert_eq!(backend.scard(b"set")?, 3);
+
+        // a member repeated within the same command is only added once
+        let request = b"*5\r\n$4\r\nsadd\r\n$3\r\nset\r\n$1\r\nd\r\n$1\r\nd\r\n$1\r\na\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(1));
+        assert_eq!(backend.scard(b"set")?, 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use super::{
+    extract_args, extract_key_args, validate_command, CommandError, CommandExecutor, PfAdd,
+    PfCount, PfMerge, RESP_OK,
+};
+use crate::{RespArray, RespFrame};
+
+impl CommandExecutor for PfAdd {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let changed = backend.pfadd(&self.key, &self.elements)?;
+        Ok(RespFrame::Integer(changed as i64))
+    }
+}
+
+impl CommandExecutor for PfCount {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.pfcount(&self.keys)?))
+    }
+}
+
+impl CommandExecutor for PfMerge {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.pfmerge(&self.dest, &self.sources)?;
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl TryFrom<RespArray> for PfAdd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["pfadd"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => key.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        // elements are hashed as raw bytes
+        let elements = args
+            .map(|frame| match frame {
+                RespFrame::BulkString(element) => Ok(element.0),
+                _ => Err(CommandError::InvalidArgument("Invalid element".to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PfAdd { key, elements })
+    }
+}
+
+impl TryFrom<RespArray> for PfCount {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["pfcount"])?;
+        Ok(PfCount {
+            keys: extract_key_args(value, 1)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PfMerge {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["pfmerge"])?;
+
+        let mut sources = extract_key_args(value, 1)?;
+        let dest = sources.remove(0);
+        Ok(PfMerge { dest, sources })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::{BulkString, RespDecode, RespEncode};
+    use anyhow::Result;
+    use bytes::{Bytes, BytesMut};
+
+    fn run(backend: &crate::Backend, request: &[u8]) -> Result<RespFrame> {
+        let mut buf = BytesMut::from(request);
+        let cmd = crate::cmd::Command::try_from(RespFrame::decode(&mut buf)?)?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    fn add_range(backend: &crate::Backend, key: &[u8], elements: std::ops::Range<usize>) {
+        let elements = elements
+            .map(|i| Bytes::from(format!("element:{}", i)))
+            .collect::<Vec<_>>();
+        backend.pfadd(key, &elements).unwrap();
+    }
+
+    #[test]
+    fn test_pfadd_pfcount() -> Result<()> {
+        let backend = crate::Backend::new();
+        let request = b"*5\r\n$5\r\npfadd\r\n$3\r\nhll\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b":1\r\n");
+        // nothing new to add
+        assert_eq!(
+            run_args(&backend, &["pfadd", "hll", "b", "a"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(&backend, &["pfadd", "hll", "d"])?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            run_args(&backend, &["pfcount", "hll"])?,
+            RespFrame::Integer(4)
+        );
+
+        // creating the key counts as a change, even without elements
+        assert_eq!(
+            run_args(&backend, &["pfadd", "empty"])?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            run_args(&backend, &["pfadd", "empty"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(&backend, &["pfcount", "empty"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(&backend, &["pfcount", "missing"])?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pfcount_merges_without_changing_sources() -> Result<()> {
+        let backend = crate::Backend::new();
+        add_range(&backend, b"first", 0..6000);
+        add_range(&backend, b"second", 4000..10000);
+        let before = (backend.get(b"first"), backend.get(b"second"));
+
+        let RespFrame::Integer(count) =
+            run_args(&backend, &["pfcount", "first", "second", "missing"])?
+        else {
+            panic!("expected an integer");
+        };
+        assert!((count - 10000).abs() < 200, "estimated {}", count);
+        assert_eq!((backend.get(b"first"), backend.get(b"second")), before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pfmerge() -> Result<()> {
+        let backend = crate::Backend::new();
+        add_range(&backend, b"first", 0..600);
+        add_range(&backend, b"second", 400..1000);
+        add_range(&backend, b"dest", 900..1200);
+        assert_eq!(
+            run_args(&backend, &["pfmerge", "dest", "first", "second"])?,
+            RESP_OK.clone()
+        );
+        // the union includes what was already at the destination
+        let RespFrame::Integer(count) = run_args(&backend, &["pfcount", "dest"])? else {
+            panic!("expected an integer");
+        };
+        assert!((count - 1200).abs() < 24, "estimated {}", count);
+        assert_eq!(
+            run_args(&backend, &["pfcount", "dest"])?,
+            run_args(&backend, &["pfcount", "first", "second", "dest"])?
+        );
+
+        run_args(&backend, &["pfmerge", "new", "first"])?;
+        assert_eq!(backend.get(b"new"), backend.get(b"first"));
+        run_args(&backend, &["pfmerge", "nothing"])?;
+        assert_eq!(
+            run_args(&backend, &["pfcount", "nothing"])?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hyperloglog_is_a_string() -> Result<()> {
+        let backend = crate::Backend::new();
+        add_range(&backend, b"hll", 0..100);
+        assert_eq!(backend.key_type(b"hll"), Some(crate::KeyType::String));
+        // a copy made with GET and SET is the same HyperLogLog
+        let value = backend.get(b"hll").unwrap();
+        backend.set("copy".into(), value);
+        assert_eq!(
+            run_args(&backend, &["pfcount", "copy"])?,
+            run_args(&backend, &["pfcount", "hll"])?
+        );
+
+        backend.set("string".into(), BulkString::from("value").into());
+        for args in [
+            &["pfadd", "string", "a"][..],
+            &["pfcount", "hll", "string"],
+            &["pfmerge", "string", "hll"],
+            &["pfmerge", "hll", "string"],
+        ] {
+            let err = run_args(&backend, args).unwrap_err();
+            let err = RespFrame::from(err);
+            assert_eq!(
+                err.encode(),
+                b"-WRONGTYPE Key is not a valid HyperLogLog string value.\r\n",
+                "{:?}",
+                args
+            );
+        }
+        assert_eq!(
+            backend.get(b"string"),
+            Some(BulkString::from("value").into())
+        );
+
+        backend.sadd("set", "member")?;
+        assert!(run_args(&backend, &["pfadd", "set", "a"]).is_err());
+        assert!(run_args(&backend, &["pfcount"]).is_err());
+        assert!(run_args(&backend, &["pfmerge"]).is_err());
+        Ok(())
+    }
+}
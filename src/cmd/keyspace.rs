@@ -0,0 +1,1562 @@
+use super::{
+    extract_args, extract_key_args, parse_cursor, parse_integer, parse_scan_options,
+    validate_command, validate_subcommand, CommandError, CommandExecutor, Copy, DbSize, Del, Dump,
+    Exists, Expire, ExpireAt, Keys, MemoryUsage, ObjectEncoding, Persist, Pexpire, PexpireAt, Pttl,
+    RandomKey, Rename, RenameNx, Restore, Scan, Touch, Ttl, Type, Unlink, RESP_OK,
+};
+use crate::{BulkString, ExpireCondition, KeyspaceEvents, RespArray, RespFrame, SimpleString};
+use bytes::Bytes;
+
+impl CommandExecutor for Del {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let mut removed = 0;
+        for key in &self.keys {
+            if backend.del(std::slice::from_ref(key)) > 0 {
+                removed += 1;
+                backend.notify_keyspace_event(KeyspaceEvents::GENERIC, "del", key);
+            }
+        }
+        Ok(RespFrame::Integer(removed))
+    }
+}
+
+impl CommandExecutor for Exists {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.exists(&self.keys)))
+    }
+}
+
+impl CommandExecutor for Touch {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.touch(&self.keys)))
+    }
+}
+
+impl CommandExecutor for Unlink {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let mut removed = 0;
+        for key in &self.keys {
+            if backend.unlink(std::slice::from_ref(key)) > 0 {
+                removed += 1;
+                backend.notify_keyspace_event(KeyspaceEvents::GENERIC, "del", key);
+            }
+        }
+        Ok(RespFrame::Integer(removed))
+    }
+}
+
+impl CommandExecutor for MemoryUsage {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(match backend.memory_usage(&self.key) {
+            Some(bytes) => RespFrame::Integer(bytes as i64),
+            None => RespFrame::NULL,
+        })
+    }
+}
+
+impl CommandExecutor for ObjectEncoding {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(match backend.object_encoding(&self.key) {
+            Some(encoding) => BulkString::from(encoding).into(),
+            None => RespFrame::NULL,
+        })
+    }
+}
+
+impl CommandExecutor for Expire {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        // the multiplication is checked when parsing
+        let set = backend.expire_with(&self.key, self.seconds * 1000, self.condition);
+        notify_expire(backend, &self.key, set);
+        Ok(RespFrame::Integer(set as i64))
+    }
+}
+
+impl CommandExecutor for Pexpire {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let set = backend.expire_with(&self.key, self.milliseconds, self.condition);
+        notify_expire(backend, &self.key, set);
+        Ok(RespFrame::Integer(set as i64))
+    }
+}
+
+impl CommandExecutor for ExpireAt {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        // the multiplication is checked when parsing
+        let set = backend.expire_at(&self.key, self.seconds * 1000, self.condition);
+        notify_expire(backend, &self.key, set);
+        Ok(RespFrame::Integer(set as i64))
+    }
+}
+
+impl CommandExecutor for PexpireAt {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let set = backend.expire_at(&self.key, self.milliseconds, self.condition);
+        notify_expire(backend, &self.key, set);
+        Ok(RespFrame::Integer(set as i64))
+    }
+}
+
+// an expiry in the past deletes the key instead, which is a `del` event
+fn notify_expire(backend: &crate::Backend, key: &[u8], set: bool) {
+    if set {
+        let event = if backend.key_type(key).is_some() {
+            "expire"
+        } else {
+            "del"
+        };
+        backend.notify_keyspace_event(KeyspaceEvents::GENERIC, event, key);
+    }
+}
+
+impl CommandExecutor for Ttl {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let ttl = match backend.pttl(&self.key) {
+            ttl if ttl < 0 => ttl,
+            // round to the nearest second like redis does
+            ttl => (ttl + 500) / 1000,
+        };
+        Ok(RespFrame::Integer(ttl))
+    }
+}
+
+impl CommandExecutor for Pttl {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.pttl(&self.key)))
+    }
+}
+
+impl CommandExecutor for Persist {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let persisted = backend.persist(&self.key);
+        if persisted {
+            backend.notify_keyspace_event(KeyspaceEvents::GENERIC, "persist", &self.key);
+        }
+        Ok(RespFrame::Integer(persisted as i64))
+    }
+}
+
+impl CommandExecutor for Type {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let name = backend.key_type(&self.key).map_or("none", |t| t.as_str());
+        Ok(SimpleString::new(name).into())
+    }
+}
+
+impl CommandExecutor for Keys {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let keys = backend
+            .keys(&self.pattern)
+            .into_iter()
+            .map(|key| BulkString::from(key).into())
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(keys).into())
+    }
+}
+
+impl CommandExecutor for Scan {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let pattern = self.pattern.as_deref();
+        let (cursor, keys) = backend.scan(self.cursor, pattern, self.count);
+        let keys = keys
+            .into_iter()
+            .map(|key| BulkString::from(key).into())
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(vec![
+            BulkString::from(cursor.to_string()).into(),
+            RespArray::new(keys).into(),
+        ])
+        .into())
+    }
+}
+
+impl CommandExecutor for DbSize {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.dbsize()))
+    }
+}
+
+impl CommandExecutor for RandomKey {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(match backend.random_key() {
+            Some(key) => BulkString::from(key).into(),
+            None => RespFrame::NULL,
+        })
+    }
+}
+
+impl CommandExecutor for Rename {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.rename(&self.src, &self.dst, false)?;
+        notify_rename(backend, &self.src, &self.dst);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for RenameNx {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let renamed = backend.rename(&self.src, &self.dst, true)?;
+        if renamed {
+            notify_rename(backend, &self.src, &self.dst);
+        }
+        Ok(RespFrame::Integer(renamed as i64))
+    }
+}
+
+fn notify_rename(backend: &crate::Backend, src: &[u8], dst: &[u8]) {
+    backend.notify_keyspace_event(KeyspaceEvents::GENERIC, "rename_from", src);
+    backend.notify_keyspace_event(KeyspaceEvents::GENERIC, "rename_to", dst);
+}
+
+impl CommandExecutor for Copy {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let copied = backend.copy(&self.src, &self.dst, self.replace)?;
+        Ok(RespFrame::Integer(copied as i64))
+    }
+}
+
+impl CommandExecutor for Dump {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(match backend.dump(&self.key) {
+            Some(payload) => BulkString::new(payload).into(),
+            None => RespFrame::NULL,
+        })
+    }
+}
+
+impl CommandExecutor for Restore {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.restore(&self.key, &self.payload, self.ttl, self.replace)?;
+        backend.notify_keyspace_event(KeyspaceEvents::GENERIC, "restore", &self.key);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl TryFrom<RespArray> for Del {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["del"])?;
+
+        let keys = extract_key_args(value, 1)?;
+        Ok(Del { keys })
+    }
+}
+
+impl TryFrom<RespArray> for Exists {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["exists"])?;
+
+        let keys = extract_key_args(value, 1)?;
+        Ok(Exists { keys })
+    }
+}
+
+impl TryFrom<RespArray> for Touch {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["touch"])?;
+
+        let keys = extract_key_args(value, 1)?;
+        Ok(Touch { keys })
+    }
+}
+
+impl TryFrom<RespArray> for Unlink {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["unlink"])?;
+
+        let keys = extract_key_args(value, 1)?;
+        Ok(Unlink { keys })
+    }
+}
+
+impl TryFrom<RespArray> for MemoryUsage {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_subcommand(&value, &["memory", "usage"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(MemoryUsage { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for ObjectEncoding {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_subcommand(&value, &["object", "encoding"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(ObjectEncoding { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Expire {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, seconds, condition) = extract_expire_args(value, "expire")?;
+        if seconds.checked_mul(1000).is_none() {
+            return Err(CommandError::InvalidExpireTime("expire"));
+        }
+        Ok(Expire {
+            key,
+            seconds,
+            condition,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Pexpire {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, milliseconds, condition) = extract_expire_args(value, "pexpire")?;
+        Ok(Pexpire {
+            key,
+            milliseconds,
+            condition,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ExpireAt {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, seconds, condition) = extract_expire_args(value, "expireat")?;
+        if seconds.checked_mul(1000).is_none() {
+            return Err(CommandError::InvalidExpireTime("expireat"));
+        }
+        Ok(ExpireAt {
+            key,
+            seconds,
+            condition,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PexpireAt {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, milliseconds, condition) = extract_expire_args(value, "pexpireat")?;
+        Ok(PexpireAt {
+            key,
+            milliseconds,
+            condition,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Ttl {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let key = extract_key(value, "ttl")?;
+        Ok(Ttl { key })
+    }
+}
+
+impl TryFrom<RespArray> for Pttl {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let key = extract_key(value, "pttl")?;
+        Ok(Pttl { key })
+    }
+}
+
+impl TryFrom<RespArray> for Persist {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let key = extract_key(value, "persist")?;
+        Ok(Persist { key })
+    }
+}
+
+// parse commands with a single key argument, e.g. `TTL key`
+impl TryFrom<RespArray> for Type {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Type {
+            key: extract_key(value, "type")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Keys {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["keys"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(pattern)) => Ok(Keys { pattern: pattern.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid pattern".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Scan {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["scan"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let cursor = parse_cursor(args.next())?;
+        let (pattern, count) = parse_scan_options(args)?;
+        Ok(Scan {
+            cursor,
+            pattern,
+            count,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for DbSize {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["dbsize"])?;
+        Ok(DbSize)
+    }
+}
+
+impl TryFrom<RespArray> for RandomKey {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["randomkey"])?;
+        Ok(RandomKey)
+    }
+}
+
+impl TryFrom<RespArray> for Rename {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (src, dst) = extract_two_keys(value, "rename")?;
+        Ok(Rename { src, dst })
+    }
+}
+
+impl TryFrom<RespArray> for RenameNx {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (src, dst) = extract_two_keys(value, "renamenx")?;
+        Ok(RenameNx { src, dst })
+    }
+}
+
+impl TryFrom<RespArray> for Copy {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["copy"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (src, dst) = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(src)), Some(RespFrame::BulkString(dst))) => (src.0, dst.0),
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let replace = match args.next() {
+            None => false,
+            Some(RespFrame::BulkString(arg)) if arg.eq_ignore_ascii_case(b"replace") => true,
+            Some(_) => return Err(CommandError::SyntaxError),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::SyntaxError);
+        }
+        Ok(Copy { src, dst, replace })
+    }
+}
+
+impl TryFrom<RespArray> for Dump {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Dump {
+            key: extract_key(value, "dump")?,
+        })
+    }
+}
+
+// - RESTORE key ttl serialized-value [REPLACE]
+impl TryFrom<RespArray> for Restore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["restore"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, ttl, payload) = match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(ttl)),
+                Some(RespFrame::BulkString(payload)),
+            ) => (key.0, parse_integer(&ttl)?, payload.0),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key, ttl or payload".to_string(),
+                ))
+            }
+        };
+        if ttl < 0 {
+            return Err(CommandError::NegativeTtl);
+        }
+        let mut replace = false;
+        for arg in args {
+            match arg {
+                RespFrame::BulkString(arg) if arg.eq_ignore_ascii_case(b"replace") => {
+                    replace = true
+                }
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+        Ok(Restore {
+            key,
+            ttl,
+            payload,
+            replace,
+        })
+    }
+}
+
+fn extract_two_keys(value: RespArray, name: &'static str) -> Result<(Bytes, Bytes), CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    match (args.next(), args.next()) {
+        (Some(RespFrame::BulkString(src)), Some(RespFrame::BulkString(dst))) => Ok((src.0, dst.0)),
+        _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    }
+}
+
+fn extract_key(value: RespArray, name: &'static str) -> Result<Bytes, CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    match args.next() {
+        Some(RespFrame::BulkString(key)) => Ok(key.0),
+        _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    }
+}
+
+// parse commands with a key and an integer ttl, e.g. `EXPIRE key seconds`
+// the key, time and `NX`/`XX`/`GT`/`LT` flags shared by the expire commands
+fn extract_expire_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, i64, ExpireCondition), CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let (key, ttl) = match (args.next(), args.next()) {
+        (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(ttl))) => {
+            (key.0, parse_integer(&ttl)?)
+        }
+        _ => {
+            return Err(CommandError::InvalidArgument(
+                "Invalid key or ttl".to_string(),
+            ))
+        }
+    };
+
+    let mut condition = ExpireCondition::default();
+    for arg in args {
+        let RespFrame::BulkString(arg) = arg else {
+            return Err(CommandError::SyntaxError);
+        };
+        match arg.to_ascii_lowercase().as_slice() {
+            b"nx" => condition.nx = true,
+            b"xx" => condition.xx = true,
+            b"gt" => condition.gt = true,
+            b"lt" => condition.lt = true,
+            _ => {
+                return Err(CommandError::UnsupportedOption(
+                    String::from_utf8_lossy(&arg).into_owned(),
+                ))
+            }
+        }
+    }
+    if condition.nx && (condition.xx || condition.gt || condition.lt) {
+        return Err(CommandError::IncompatibleOptions("NX and XX, GT or LT"));
+    }
+    if condition.gt && condition.lt {
+        return Err(CommandError::IncompatibleOptions("GT and LT"));
+    }
+    Ok((key, ttl, condition))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{request_args, run_args};
+    use crate::{cmd::Command, Backend, BulkString, KeyType, RespDecode, SimpleError};
+    use anyhow::Result;
+    use bytes::BytesMut;
+    use std::{
+        collections::HashSet,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    };
+
+    fn mixed_backend() -> Backend {
+        let backend = Backend::new();
+        backend.set("string".into(), BulkString::from("value").into());
+        backend
+            .hset(
+                "hash".into(),
+                "field".into(),
+                BulkString::from("value").into(),
+            )
+            .unwrap();
+        backend.sadd("set", "member").unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_del_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$3\r\ndel\r\n$2\r\nk1\r\n$2\r\nk2\r\n$2\r\nk3\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+
+        let result: Del = frame.try_into()?;
+        assert_eq!(result.keys, vec!["k1", "k2", "k3"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exists_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nEXISTS\r\n$2\r\nk1\r\n$2\r\nk1\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+
+        let result: Exists = frame.try_into()?;
+        assert_eq!(result.keys, vec!["k1", "k1"]);
+
+        let mut buf = BytesMut::new();
+        // the dispatcher checks the arity
+        buf.extend_from_slice(b"*1\r\n$6\r\nexists\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<Command, _> = frame.try_into();
+        assert!(matches!(result, Err(CommandError::WrongArity("exists"))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exists_mixed_keys() {
+        let backend = mixed_backend();
+        let cmd = Exists {
+            keys: vec![
+                "string".into(),
+                "hash".into(),
+                "set".into(),
+                "missing".into(),
+            ],
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(3));
+
+        // redis counts a key each time it is given
+        let cmd = Exists {
+            keys: vec![
+                "hash".into(),
+                "hash".into(),
+                "missing".into(),
+                "hash".into(),
+            ],
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(3));
+    }
+
+    #[test]
+    fn test_del_mixed_keys() {
+        let backend = mixed_backend();
+        let cmd = Del {
+            keys: vec![
+                "string".into(),
+                "hash".into(),
+                "set".into(),
+                "missing".into(),
+            ],
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(3));
+
+        let cmd = Exists {
+            keys: vec!["string".into(), "hash".into(), "set".into()],
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(0));
+    }
+
+    #[test]
+    fn test_del_duplicate_keys() {
+        let backend = mixed_backend();
+        // a key deleted once is not counted again for later positions
+        let cmd = Del {
+            keys: vec!["hash".into(), "string".into(), "hash".into()],
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(2));
+        assert_eq!(backend.exists(&["set".into()]), 1);
+    }
+
+    #[test]
+    fn test_expire_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nexpire\r\n$5\r\nhello\r\n$2\r\n10\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+
+        let result: Expire = frame.try_into()?;
+        assert_eq!(result.key, "hello");
+        assert_eq!(result.seconds, 10);
+
+        buf.extend_from_slice(b"*3\r\n$7\r\npexpire\r\n$5\r\nhello\r\n$3\r\nabc\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<Pexpire, _> = frame.try_into();
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ttl_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$3\r\nttl\r\n$5\r\nhello\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+
+        let result: Ttl = frame.try_into()?;
+        assert_eq!(result.key, "hello");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_ttl_persist() {
+        let backend = mixed_backend();
+        let ttl = |key: &[u8]| {
+            Ttl {
+                key: Bytes::copy_from_slice(key),
+            }
+            .execute(&backend)
+            .unwrap()
+        };
+
+        assert_eq!(ttl(b"missing"), RespFrame::Integer(-2));
+        assert_eq!(ttl(b"string"), RespFrame::Integer(-1));
+
+        let cmd = Expire {
+            key: "missing".into(),
+            seconds: 100,
+            condition: Default::default(),
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(0));
+
+        let cmd = Expire {
+            key: "hash".into(),
+            seconds: 100,
+            condition: Default::default(),
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(1));
+        assert_eq!(ttl(b"hash"), RespFrame::Integer(100));
+
+        let cmd = Pttl { key: "hash".into() };
+        match cmd.execute(&backend).unwrap() {
+            RespFrame::Integer(ms) => assert!(ms > 99_000 && ms <= 100_000),
+            frame => panic!("unexpected frame: {:?}", frame),
+        }
+
+        let cmd = Persist { key: "hash".into() };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(1));
+        assert_eq!(ttl(b"hash"), RespFrame::Integer(-1));
+
+        let cmd = Persist { key: "hash".into() };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(0));
+    }
+
+    #[test]
+    fn test_lazy_expiration_on_read() {
+        let backend = mixed_backend();
+        for key in ["string", "hash", "set"] {
+            let cmd = Pexpire {
+                key: key.to_string().into(),
+                milliseconds: 10,
+                condition: Default::default(),
+            };
+            assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(1));
+        }
+
+        thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(backend.get(b"string"), None);
+        assert_eq!(backend.hget(b"hash", b"field"), None);
+        assert!(backend.hentries(b"hash").unwrap().is_empty());
+        assert!(!backend.sismember(b"set", b"member"));
+
+        // the expired keys are evicted from the underlying maps on read
+        assert!(!backend.entries.contains_key(b"string".as_slice()));
+        assert!(!backend.entries.contains_key(b"hash".as_slice()));
+        assert!(!backend.entries.contains_key(b"set".as_slice()));
+        assert!(backend.volatile_keys() == 0);
+    }
+
+    #[test]
+    fn test_non_positive_expire_deletes_key() {
+        let backend = mixed_backend();
+        let cmd = Expire {
+            key: "string".into(),
+            seconds: -1,
+            condition: Default::default(),
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(1));
+        assert_eq!(backend.exists(&["string".into()]), 0);
+    }
+
+    #[test]
+    fn test_set_clears_ttl() {
+        let backend = mixed_backend();
+        assert!(backend.expire(b"string", 100_000));
+        assert!(backend.pttl(b"string") > 0);
+
+        backend.set("string".into(), BulkString::from("other").into());
+        assert_eq!(backend.pttl(b"string"), -1);
+
+        // an expiry set before the overwrite must not evict the new value
+        assert!(backend.expire(b"string", 10));
+        backend.set("string".into(), BulkString::from("again").into());
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(
+            backend.get(b"string"),
+            Some(BulkString::from("again").into())
+        );
+    }
+
+    #[test]
+    fn test_type_command() -> Result<()> {
+        let backend = mixed_backend();
+        for (key, expected) in [
+            ("string", "string"),
+            ("hash", "hash"),
+            ("set", "set"),
+            ("missing", "none"),
+        ] {
+            let mut buf = BytesMut::new();
+            buf.extend_from_slice(
+                format!("*2\r\n$4\r\ntype\r\n${}\r\n{}\r\n", key.len(), key).as_bytes(),
+            );
+            let cmd: Type = RespArray::decode(&mut buf)?.try_into()?;
+            assert_eq!(
+                cmd.execute(&backend).unwrap(),
+                SimpleString::new(expected).into()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_converts_hash_to_string() {
+        let backend = mixed_backend();
+        backend.set("hash".into(), BulkString::from("now a string").into());
+        assert_eq!(backend.key_type(b"hash"), Some(KeyType::String));
+        assert_eq!(backend.hget(b"hash", b"field"), None);
+        assert_eq!(
+            backend.get(b"hash"),
+            Some(BulkString::from("now a string").into())
+        );
+    }
+
+    #[test]
+    fn test_type_is_exclusive() {
+        let backend = mixed_backend();
+        assert!(matches!(
+            backend.hset(
+                "string".into(),
+                "field".into(),
+                BulkString::from("value").into()
+            ),
+            Err(CommandError::WrongType)
+        ));
+        assert!(matches!(
+            backend.sadd("hash", "member"),
+            Err(CommandError::WrongType)
+        ));
+        assert_eq!(backend.key_type(b"string"), Some(KeyType::String));
+        assert_eq!(backend.key_type(b"hash"), Some(KeyType::Hash));
+    }
+
+    #[test]
+    fn test_wrong_type_matrix() {
+        let backend = mixed_backend();
+        let wrong_type: RespFrame =
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into();
+        // each command is run against every key, it must only succeed on the key of its own type
+        let commands: &[(KeyType, &[&str])] = &[
+            (KeyType::String, &["get"]),
+            (KeyType::String, &["strlen"]),
+            (KeyType::String, &["append", "x"]),
+            (KeyType::String, &["getrange", "0", "-1"]),
+            (KeyType::Hash, &["hget", "field"]),
+            (KeyType::Hash, &["hgetall"]),
+            (KeyType::Hash, &["hmget", "field"]),
+            (KeyType::Hash, &["hset", "field", "value"]),
+            (KeyType::Set, &["sismember", "member"]),
+            (KeyType::Set, &["sadd", "member"]),
+        ];
+        for (expected, args) in commands {
+            for (key, actual) in [
+                ("string", KeyType::String),
+                ("hash", KeyType::Hash),
+                ("set", KeyType::Set),
+            ] {
+                let args = [&[args[0], key], &args[1..]].concat();
+                let cmd = Command::try_from(request_args(&args)).unwrap();
+                let result = cmd.execute(&backend).unwrap_or_else(RespFrame::from);
+                if *expected == actual {
+                    assert_ne!(result, wrong_type, "{} on {}", args[0], key);
+                } else {
+                    assert_eq!(result, wrong_type, "{} on {}", args[0], key);
+                }
+            }
+        }
+        // the failed writes left every key untouched
+        assert_eq!(backend.key_type(b"string"), Some(KeyType::String));
+        assert_eq!(backend.key_type(b"hash"), Some(KeyType::Hash));
+        assert_eq!(backend.key_type(b"set"), Some(KeyType::Set));
+    }
+
+    fn keys_cmd(backend: &Backend, pattern: &str) -> Result<Vec<String>> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            format!("*2\r\n$4\r\nkeys\r\n${}\r\n{}\r\n", pattern.len(), pattern).as_bytes(),
+        );
+        let cmd: Keys = RespArray::decode(&mut buf)?.try_into()?;
+        match cmd.execute(backend)? {
+            RespFrame::Array(keys) => {
+                let mut keys = keys
+                    .0
+                    .into_iter()
+                    .map(|key| match key {
+                        RespFrame::BulkString(key) => String::from_utf8(key.to_vec()).unwrap(),
+                        frame => panic!("unexpected key {:?}", frame),
+                    })
+                    .collect::<Vec<_>>();
+                keys.sort();
+                Ok(keys)
+            }
+            frame => panic!("unexpected reply {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn test_keys_command() -> Result<()> {
+        let backend = mixed_backend();
+        backend.set("user:1".into(), BulkString::from("a").into());
+        backend.set("user:2".into(), BulkString::from("b").into());
+        backend.set("user:10".into(), BulkString::from("c").into());
+        backend.set("expired".into(), BulkString::from("d").into());
+        backend.expire(b"expired", 1);
+        thread::sleep(Duration::from_millis(5));
+
+        let names = |keys: &[&str]| -> Vec<String> { keys.iter().map(|k| k.to_string()).collect() };
+        assert_eq!(
+            keys_cmd(&backend, "*")?,
+            names(&["hash", "set", "string", "user:1", "user:10", "user:2"])
+        );
+        assert_eq!(keys_cmd(&backend, "user:?")?, names(&["user:1", "user:2"]));
+        assert_eq!(
+            keys_cmd(&backend, "user:[1-9]*")?,
+            names(&["user:1", "user:10", "user:2"])
+        );
+        assert_eq!(
+            keys_cmd(&backend, "[hs]*")?,
+            names(&["hash", "set", "string"])
+        );
+        assert_eq!(keys_cmd(&backend, "nomatch*")?, names(&[]));
+        // the expired key was evicted on the way
+        assert!(!backend.entries.contains_key(b"expired".as_slice()));
+        Ok(())
+    }
+
+    fn scan_cmd(args: &[&str]) -> Result<Scan, CommandError> {
+        request_args(&[&["scan"], args].concat()).try_into()
+    }
+
+    #[test]
+    fn test_scan_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$4\r\nscan\r\n$2\r\n42\r\n$5\r\nMATCH\r\n$2\r\nk*\r\n$5\r\ncount\r\n$3\r\n100\r\n",
+        );
+        let cmd: Scan = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.cursor, 42);
+        assert_eq!(cmd.pattern.as_deref(), Some(&b"k*"[..]));
+        assert_eq!(cmd.count, 100);
+
+        assert!(matches!(
+            scan_cmd(&["nope"]),
+            Err(CommandError::InvalidCursor)
+        ));
+        assert!(matches!(
+            scan_cmd(&["0", "count", "0"]),
+            Err(CommandError::SyntaxError)
+        ));
+        assert!(matches!(
+            scan_cmd(&["0", "match"]),
+            Err(CommandError::SyntaxError)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_scan_with_concurrent_writer() -> Result<()> {
+        let backend = Backend::new();
+        for i in 0..10_000 {
+            backend.set(
+                format!("key:{}", i).into(),
+                BulkString::from("value").into(),
+            );
+        }
+
+        // keep inserting and deleting other keys for the whole scan
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (backend, stop) = (backend.clone(), stop.clone());
+            tokio::task::spawn_blocking(move || {
+                let mut i = 0u64;
+                while !stop.load(Ordering::Relaxed) {
+                    let key = format!("churn:{}", i % 5000);
+                    if i.is_multiple_of(3) {
+                        backend.del(&[key.into()]);
+                    } else {
+                        backend.set(key.into(), BulkString::from("value").into());
+                    }
+                    i += 1;
+                }
+            })
+        };
+
+        let mut seen = HashSet::new();
+        let mut cursor = "0".to_string();
+        loop {
+            let reply = scan_cmd(&[&cursor, "COUNT", "100"])?.execute(&backend)?;
+            let RespFrame::Array(reply) = reply else {
+                panic!("unexpected reply {:?}", reply);
+            };
+            let mut reply = reply.0.into_iter();
+            let (Some(RespFrame::BulkString(next)), Some(keys)) = (reply.next(), reply.next())
+            else {
+                panic!("unexpected reply");
+            };
+            if let RespFrame::Array(keys) = keys {
+                for key in keys.0 {
+                    if let RespFrame::BulkString(key) = key {
+                        seen.insert(key.0);
+                    }
+                }
+            }
+            cursor = String::from_utf8(next.to_vec())?;
+            if cursor == "0" {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        stop.store(true, Ordering::Relaxed);
+        writer.await?;
+
+        for i in 0..10_000 {
+            assert!(
+                seen.contains(format!("key:{}", i).as_bytes()),
+                "key:{} not scanned",
+                i
+            );
+        }
+        Ok(())
+    }
+
+    fn run(backend: &Backend, request: &[u8]) -> Result<RespFrame> {
+        let mut buf = BytesMut::from(request);
+        let cmd = Command::try_from(RespFrame::decode(&mut buf)?)?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    #[test]
+    fn test_dbsize_randomkey_empty() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run(&backend, b"*1\r\n$6\r\ndbsize\r\n")?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run(&backend, b"*1\r\n$9\r\nrandomkey\r\n")?,
+            RespFrame::NULL
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dbsize_randomkey() -> Result<()> {
+        let backend = mixed_backend();
+        assert_eq!(
+            run(&backend, b"*1\r\n$6\r\ndbsize\r\n")?,
+            RespFrame::Integer(3)
+        );
+
+        let mut seen = HashSet::new();
+        for _ in 0..200 {
+            match run(&backend, b"*1\r\n$9\r\nrandomkey\r\n")? {
+                RespFrame::BulkString(key) => seen.insert(key.0),
+                frame => panic!("unexpected reply {:?}", frame),
+            };
+        }
+        let expected = ["string", "hash", "set"].map(Bytes::from);
+        assert_eq!(seen, HashSet::from(expected));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dbsize_randomkey_only_expired() -> Result<()> {
+        let backend = mixed_backend();
+        for key in ["string", "hash", "set"] {
+            backend.expire(key.as_bytes(), 1);
+        }
+        thread::sleep(Duration::from_millis(5));
+
+        assert_eq!(
+            run(&backend, b"*1\r\n$9\r\nrandomkey\r\n")?,
+            RespFrame::NULL
+        );
+        assert_eq!(
+            run(&backend, b"*1\r\n$6\r\ndbsize\r\n")?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nrename\r\n$3\r\nsrc\r\n$3\r\ndst\r\n");
+        let cmd: Rename = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.src, "src");
+        assert_eq!(cmd.dst, "dst");
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_every_type() -> Result<()> {
+        let backend = mixed_backend();
+        backend.expire(b"hash", 100_000);
+        for key in ["string", "hash", "set"] {
+            let dst = format!("{}-renamed", key);
+            let cmd = Rename {
+                src: key.to_string().into(),
+                dst: dst.clone().into(),
+            };
+            assert_eq!(cmd.execute(&backend)?, RESP_OK.clone());
+            assert_eq!(backend.key_type(key.as_bytes()), None);
+        }
+        assert_eq!(
+            backend.get(b"string-renamed"),
+            Some(BulkString::from("value").into())
+        );
+        assert_eq!(
+            backend.hget(b"hash-renamed", b"field"),
+            Some(BulkString::from("value").into())
+        );
+        assert!(backend.sismember(b"set-renamed", b"member"));
+        // the ttl moved along with the value
+        assert!(backend.pttl(b"hash-renamed") > 99_000);
+        assert_eq!(backend.pttl(b"set-renamed"), -1);
+        assert!(backend.deadline(b"hash").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_overwrites_any_type() -> Result<()> {
+        let backend = mixed_backend();
+        backend.expire(b"string", 100_000);
+        let cmd = Rename {
+            src: "set".into(),
+            dst: "string".into(),
+        };
+        cmd.execute(&backend)?;
+        assert_eq!(backend.key_type(b"string"), Some(KeyType::Set));
+        assert!(backend.sismember(b"string", b"member"));
+        // the destination's own ttl is gone with its value
+        assert_eq!(backend.pttl(b"string"), -1);
+
+        let cmd = Rename {
+            src: "missing".into(),
+            dst: "other".into(),
+        };
+        assert_eq!(
+            RespFrame::from(cmd.execute(&backend).unwrap_err()),
+            SimpleError::new("ERR no such key").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_renamenx() -> Result<()> {
+        let backend = mixed_backend();
+        let cmd = RenameNx {
+            src: "hash".into(),
+            dst: "set".into(),
+        };
+        assert_eq!(cmd.execute(&backend)?, RespFrame::Integer(0));
+        assert_eq!(backend.key_type(b"hash"), Some(KeyType::Hash));
+        assert_eq!(backend.key_type(b"set"), Some(KeyType::Set));
+
+        let cmd = RenameNx {
+            src: "hash".into(),
+            dst: "new".into(),
+        };
+        assert_eq!(cmd.execute(&backend)?, RespFrame::Integer(1));
+        assert_eq!(backend.key_type(b"new"), Some(KeyType::Hash));
+        assert_eq!(backend.key_type(b"hash"), None);
+        Ok(())
+    }
+
+    fn copy_cmd(args: &[&str]) -> Result<Copy, CommandError> {
+        request_args(&[&["copy"], args].concat()).try_into()
+    }
+
+    #[test]
+    fn test_copy_from_resp_array() -> Result<()> {
+        let cmd = copy_cmd(&["src", "dst"])?;
+        assert_eq!(
+            (cmd.src, cmd.dst, cmd.replace),
+            ("src".into(), "dst".into(), false)
+        );
+        assert!(copy_cmd(&["src", "dst", "REPLACE"])?.replace);
+        assert!(matches!(
+            copy_cmd(&["src", "dst", "db"]),
+            Err(CommandError::SyntaxError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_and_restore_commands() -> Result<()> {
+        let backend = mixed_backend();
+        let run = |args: &[&str]| run_args(&backend, args);
+        assert_eq!(run(&["dump", "missing"])?, RespFrame::NULL);
+        let RespFrame::BulkString(payload) = run(&["dump", "hash"])? else {
+            panic!("DUMP replies with a bulk string");
+        };
+        let restore = |args: &[&str], payload: &[u8]| {
+            let mut frames: Vec<RespFrame> = vec![BulkString::from("restore").into()];
+            frames.push(BulkString::from(args[0]).into());
+            frames.push(BulkString::from(args[1]).into());
+            frames.push(BulkString::new(payload.to_vec()).into());
+            frames.extend(args[2..].iter().map(|arg| BulkString::from(*arg).into()));
+            Command::from_request(RespArray::new(frames).into(), &backend)?.execute(&backend)
+        };
+        assert_eq!(restore(&["copy", "0"], &payload)?, RESP_OK.clone());
+        assert_eq!(
+            backend.hget(b"copy", b"field"),
+            Some(BulkString::from("value").into())
+        );
+        assert_eq!(
+            RespFrame::from(restore(&["string", "0"], &payload).unwrap_err()),
+            SimpleError::new("BUSYKEY Target key name already exists.").into()
+        );
+        assert_eq!(
+            restore(&["string", "0", "REPLACE"], &payload)?,
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.key_type(b"string"), Some(KeyType::Hash));
+        assert!(matches!(
+            restore(&["key", "-1"], &payload),
+            Err(CommandError::NegativeTtl)
+        ));
+        assert!(matches!(
+            restore(&["key", "0", "ABSTTL"], &payload),
+            Err(CommandError::SyntaxError)
+        ));
+        let mut tampered = payload.to_vec();
+        tampered[0] ^= 1;
+        assert_eq!(
+            RespFrame::from(restore(&["key", "0"], &tampered).unwrap_err()),
+            SimpleError::new("ERR Bad data format").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_is_deep() -> Result<()> {
+        let backend = mixed_backend();
+        backend.expire(b"hash", 100_000);
+        assert_eq!(
+            copy_cmd(&["hash", "hash-copy"])?.execute(&backend)?,
+            RespFrame::Integer(1)
+        );
+        assert!(backend.pttl(b"hash-copy") > 99_000);
+
+        backend.hset(
+            "hash-copy".into(),
+            "other".into(),
+            BulkString::from("value").into(),
+        )?;
+        backend.hset(
+            "hash-copy".into(),
+            "field".into(),
+            BulkString::from("changed").into(),
+        )?;
+        assert_eq!(backend.hget(b"hash", b"other"), None);
+        assert_eq!(
+            backend.hget(b"hash", b"field"),
+            Some(BulkString::from("value").into())
+        );
+
+        copy_cmd(&["set", "set-copy"])?.execute(&backend)?;
+        backend.sadd("set-copy", "other")?;
+        assert!(!backend.sismember(b"set", b"other"));
+        assert!(backend.sismember(b"set-copy", b"member"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_replace() -> Result<()> {
+        let backend = mixed_backend();
+        assert_eq!(
+            copy_cmd(&["string", "hash"])?.execute(&backend)?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(backend.key_type(b"hash"), Some(KeyType::Hash));
+
+        assert_eq!(
+            copy_cmd(&["string", "hash", "replace"])?.execute(&backend)?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(backend.get(b"hash"), Some(BulkString::from("value").into()));
+        assert_eq!(backend.hget(b"hash", b"field"), None);
+
+        assert_eq!(
+            copy_cmd(&["missing", "dst"])?.execute(&backend)?,
+            RespFrame::Integer(0)
+        );
+        assert!(matches!(
+            copy_cmd(&["string", "string"])?.execute(&backend),
+            Err(CommandError::SameObject)
+        ));
+        Ok(())
+    }
+
+    fn expire_cmd(args: &[&str]) -> Result<Command, CommandError> {
+        Command::try_from(request_args(args))
+    }
+
+    #[test]
+    fn test_expire_flags_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$9\r\npexpireat\r\n$3\r\nkey\r\n$3\r\n123\r\n$2\r\nXX\r\n$2\r\ngt\r\n",
+        );
+        let cmd: PexpireAt = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.key, "key");
+        assert_eq!(cmd.milliseconds, 123);
+        assert_eq!(
+            cmd.condition,
+            ExpireCondition {
+                xx: true,
+                gt: true,
+                ..Default::default()
+            }
+        );
+
+        for flags in [["nx", "gt"], ["nx", "xx"], ["lt", "gt"]] {
+            let err = expire_cmd(&["expire", "key", "10", flags[0], flags[1]]).unwrap_err();
+            assert!(matches!(err, CommandError::IncompatibleOptions(_)));
+        }
+        assert_eq!(
+            RespFrame::from(expire_cmd(&["expire", "key", "10", "nx", "lt"]).unwrap_err()),
+            SimpleError::new("ERR NX and XX, GT or LT options at the same time are not compatible")
+                .into()
+        );
+        assert!(matches!(
+            expire_cmd(&["expire", "key", "10", "sometimes"]),
+            Err(CommandError::UnsupportedOption(_))
+        ));
+        assert!(matches!(
+            expire_cmd(&["expireat", "key", &i64::MAX.to_string()]),
+            Err(CommandError::InvalidExpireTime("expireat"))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_expireat_pexpireat() -> Result<()> {
+        let backend = mixed_backend();
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+
+        let at = (now_ms / 1000 + 100).to_string();
+        assert_eq!(
+            expire_cmd(&["expireat", "string", &at])?.execute(&backend)?,
+            RespFrame::Integer(1)
+        );
+        let ttl = backend.pttl(b"string");
+        assert!(ttl > 98_000 && ttl <= 100_000, "ttl {}", ttl);
+
+        let at = (now_ms + 50_000).to_string();
+        assert_eq!(
+            expire_cmd(&["pexpireat", "hash", &at])?.execute(&backend)?,
+            RespFrame::Integer(1)
+        );
+        let ttl = backend.pttl(b"hash");
+        assert!(ttl > 49_000 && ttl <= 50_000, "ttl {}", ttl);
+
+        // a timestamp in the past deletes the key right away
+        assert_eq!(
+            expire_cmd(&["expireat", "set", "1"])?.execute(&backend)?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(backend.key_type(b"set"), None);
+        assert_eq!(
+            expire_cmd(&["pexpireat", "missing", &at])?.execute(&backend)?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_flags() -> Result<()> {
+        let backend = mixed_backend();
+        let run = |args: &[&str]| expire_cmd(args).unwrap().execute(&backend).unwrap();
+
+        assert_eq!(
+            run(&["expire", "string", "100", "xx"]),
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run(&["expire", "string", "100", "gt"]),
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run(&["expire", "string", "100", "nx"]),
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            run(&["expire", "string", "200", "nx"]),
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run(&["pexpire", "string", "50000", "gt"]),
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run(&["pexpire", "string", "50000", "lt"]),
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            run(&["expire", "string", "200", "xx", "gt"]),
+            RespFrame::Integer(1)
+        );
+        assert!(backend.pttl(b"string") > 199_000);
+
+        // LT on a key without ttl sets one, since no ttl means never expiring
+        assert_eq!(run(&["expire", "hash", "100", "lt"]), RespFrame::Integer(1));
+        assert!(backend.pttl(b"hash") > 99_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_touch_unlink_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$5\r\ntouch\r\n$2\r\nk1\r\n$2\r\nk2\r\n");
+        let cmd: Touch = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.keys, vec!["k1", "k2"]);
+
+        buf.extend_from_slice(b"*2\r\n$6\r\nunlink\r\n$2\r\nk1\r\n");
+        let cmd: Unlink = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.keys, vec!["k1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_touch_unlink() -> Result<()> {
+        let backend = mixed_backend();
+        let keys = ["string", "hash", "set", "missing", "string"].map(Bytes::from);
+        let cmd = Touch {
+            keys: keys.to_vec(),
+        };
+        assert_eq!(cmd.execute(&backend)?, RespFrame::Integer(4));
+
+        let cmd = Unlink {
+            keys: keys.to_vec(),
+        };
+        assert_eq!(cmd.execute(&backend)?, RespFrame::Integer(3));
+        assert_eq!(backend.exists(&keys), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unlink_drops_in_background() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        struct SlowDrop;
+        impl Drop for SlowDrop {
+            fn drop(&mut self) {
+                thread::sleep(Duration::from_millis(100));
+                DROPPED.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let backend = Backend::new();
+        let start = Instant::now();
+        backend.drop_in_background(SlowDrop);
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+
+        let start = Instant::now();
+        while DROPPED.load(Ordering::SeqCst) == 0 {
+            assert!(start.elapsed() < Duration::from_secs(5), "never dropped");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_unlink_big_hash() {
+        let backend = Backend::new();
+        for i in 0..100_000 {
+            backend
+                .hset(
+                    "big".into(),
+                    format!("field{}", i).into(),
+                    BulkString::from("value").into(),
+                )
+                .unwrap();
+        }
+        assert_eq!(backend.unlink(&["big".into()]), 1);
+        assert_eq!(backend.key_type(b"big"), None);
+    }
+
+    #[test]
+    fn test_memory_usage_object_encoding_commands() -> Result<()> {
+        let backend = mixed_backend();
+        let reply = run(
+            &backend,
+            b"*3\r\n$6\r\nmemory\r\n$5\r\nUSAGE\r\n$6\r\nstring\r\n",
+        )?;
+        assert!(matches!(reply, RespFrame::Integer(n) if n > 0));
+        assert_eq!(
+            run(
+                &backend,
+                b"*3\r\n$6\r\nmemory\r\n$5\r\nusage\r\n$7\r\nmissing\r\n"
+            )?,
+            RespFrame::NULL
+        );
+        assert_eq!(
+            run(
+                &backend,
+                b"*3\r\n$6\r\nobject\r\n$8\r\nencoding\r\n$4\r\nhash\r\n"
+            )?,
+            BulkString::from("listpack").into()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,1133 @@
+use crate::{BulkString, KeyspaceEvents, LPosOptions, ListEnd, RespArray, RespFrame};
+use bytes::Bytes;
+use std::time::Duration;
+
+use super::{
+    extract_args, parse_float, parse_integer, validate_command, BLPop, BRPop, CommandError,
+    CommandExecutor, LIndex, LInsert, LLen, LMove, LPop, LPos, LPush, LPushX, LRange, LRem, LSet,
+    LTrim, RPop, RPopLPush, RPush, RPushX, RESP_OK,
+};
+
+impl CommandExecutor for LPush {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let len = backend.lpush(self.key.clone(), self.values)?;
+        notify_list(backend, "lpush", &self.key, len > 0);
+        Ok(RespFrame::Integer(len))
+    }
+}
+
+impl CommandExecutor for RPush {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let len = backend.rpush(self.key.clone(), self.values)?;
+        notify_list(backend, "rpush", &self.key, len > 0);
+        Ok(RespFrame::Integer(len))
+    }
+}
+
+impl CommandExecutor for LPushX {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let len = backend.lpushx(self.key.clone(), self.values)?;
+        notify_list(backend, "lpush", &self.key, len > 0);
+        Ok(RespFrame::Integer(len))
+    }
+}
+
+impl CommandExecutor for RPushX {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let len = backend.rpushx(self.key.clone(), self.values)?;
+        notify_list(backend, "rpush", &self.key, len > 0);
+        Ok(RespFrame::Integer(len))
+    }
+}
+
+impl CommandExecutor for LPop {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let popped = backend.lpop(&self.key, self.count.unwrap_or(1))?;
+        notify_list(backend, "lpop", &self.key, popped.is_some());
+        Ok(popped_reply(popped, self.count.is_some()))
+    }
+}
+
+impl CommandExecutor for RPop {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let popped = backend.rpop(&self.key, self.count.unwrap_or(1))?;
+        notify_list(backend, "rpop", &self.key, popped.is_some());
+        Ok(popped_reply(popped, self.count.is_some()))
+    }
+}
+
+// the event of a push or a pop, if it changed the list
+fn notify_list(backend: &crate::Backend, event: &str, key: &[u8], changed: bool) {
+    if changed {
+        backend.notify_keyspace_event(KeyspaceEvents::LIST, event, key);
+    }
+}
+
+impl CommandExecutor for LLen {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.llen(&self.key)?))
+    }
+}
+
+impl CommandExecutor for LRange {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let elements = backend.lrange(&self.key, self.start, self.stop)?;
+        Ok(RespArray::new(elements).into())
+    }
+}
+
+impl CommandExecutor for LIndex {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(backend
+            .lindex(&self.key, self.index)?
+            .unwrap_or(RespFrame::NULL))
+    }
+}
+
+impl CommandExecutor for LSet {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.lset(&self.key, self.index, self.value)?;
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for LRem {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let removed = backend.lrem(&self.key, self.count, &self.element)?;
+        Ok(RespFrame::Integer(removed))
+    }
+}
+
+impl CommandExecutor for LTrim {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.ltrim(&self.key, self.start, self.stop)?;
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for LInsert {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let len = backend.linsert(&self.key, self.end, &self.pivot, self.element)?;
+        Ok(RespFrame::Integer(len))
+    }
+}
+
+impl CommandExecutor for LPos {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let positions = backend.lpos(&self.key, &self.element, self.options)?;
+        let mut positions = positions.into_iter().map(|i| RespFrame::Integer(i as i64));
+        if self.with_count {
+            return Ok(RespArray::new(positions.collect::<Vec<_>>()).into());
+        }
+        Ok(positions.next().unwrap_or(RespFrame::NULL))
+    }
+}
+
+impl CommandExecutor for LMove {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let element = backend.lmove(&self.src, &self.dst, self.from, self.to)?;
+        Ok(element.unwrap_or(RespFrame::NULL))
+    }
+}
+
+impl CommandExecutor for RPopLPush {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let element = backend.lmove(&self.src, &self.dst, ListEnd::Right, ListEnd::Left)?;
+        Ok(element.unwrap_or(RespFrame::NULL))
+    }
+}
+
+// outside of a connection, e.g. in a transaction, the blocking pops don't wait
+impl CommandExecutor for BLPop {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        try_pop(backend, &self.keys, ListEnd::Left)
+    }
+}
+
+impl CommandExecutor for BRPop {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        try_pop(backend, &self.keys, ListEnd::Right)
+    }
+}
+
+impl BLPop {
+    pub(super) async fn block(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let popped = backend
+            .blocking_pop(&self.keys, ListEnd::Left, self.timeout)
+            .await?;
+        Ok(blocking_pop_reply(popped))
+    }
+}
+
+impl BRPop {
+    pub(super) async fn block(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let popped = backend
+            .blocking_pop(&self.keys, ListEnd::Right, self.timeout)
+            .await?;
+        Ok(blocking_pop_reply(popped))
+    }
+}
+
+fn try_pop(
+    backend: &crate::Backend,
+    keys: &[Bytes],
+    end: ListEnd,
+) -> Result<RespFrame, CommandError> {
+    for key in keys {
+        let popped = match end {
+            ListEnd::Left => backend.lpop(key, 1)?,
+            ListEnd::Right => backend.rpop(key, 1)?,
+        };
+        if let Some(element) = popped.and_then(|mut popped| popped.pop()) {
+            return Ok(blocking_pop_reply(Some((key.clone(), element))));
+        }
+    }
+    Ok(blocking_pop_reply(None))
+}
+
+// the key and the element it produced, a null array if nothing was popped
+fn blocking_pop_reply(popped: Option<(Bytes, RespFrame)>) -> RespFrame {
+    match popped {
+        Some((key, element)) => RespArray::new(vec![BulkString::from(key).into(), element]).into(),
+        None => RespFrame::NULL_ARRAY,
+    }
+}
+
+// a missing list is nil, a null array if a count was given. Otherwise the popped elements, as an
+// array if a count was given
+fn popped_reply(popped: Option<Vec<RespFrame>>, with_count: bool) -> RespFrame {
+    match popped {
+        None if with_count => RespFrame::NULL_ARRAY,
+        None => RespFrame::NULL,
+        Some(popped) if with_count => RespArray::new(popped).into(),
+        Some(mut popped) => popped.pop().unwrap_or(RespFrame::NULL),
+    }
+}
+
+impl TryFrom<RespArray> for LPush {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, values) = extract_push_args(value, "lpush")?;
+        Ok(LPush { key, values })
+    }
+}
+
+impl TryFrom<RespArray> for RPush {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, values) = extract_push_args(value, "rpush")?;
+        Ok(RPush { key, values })
+    }
+}
+
+impl TryFrom<RespArray> for LPushX {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, values) = extract_push_args(value, "lpushx")?;
+        Ok(LPushX { key, values })
+    }
+}
+
+impl TryFrom<RespArray> for RPushX {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, values) = extract_push_args(value, "rpushx")?;
+        Ok(RPushX { key, values })
+    }
+}
+
+impl TryFrom<RespArray> for LPop {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_pop_args(value, "lpop")?;
+        Ok(LPop { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for RPop {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_pop_args(value, "rpop")?;
+        Ok(RPop { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for LLen {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["llen"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(LLen { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for LRange {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, start, stop) = extract_range_args(value, "lrange")?;
+        Ok(LRange { key, start, stop })
+    }
+}
+
+impl TryFrom<RespArray> for LTrim {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, start, stop) = extract_range_args(value, "ltrim")?;
+        Ok(LTrim { key, start, stop })
+    }
+}
+
+impl TryFrom<RespArray> for LRem {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lrem"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(count)),
+                Some(element),
+            ) => Ok(LRem {
+                key: key.0,
+                count: parse_integer(&count)?,
+                element,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key, count or element".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for LInsert {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["linsert"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(position)),
+                Some(pivot),
+                Some(element),
+            ) => {
+                let end = match position.to_ascii_lowercase().as_slice() {
+                    b"before" => ListEnd::Left,
+                    b"after" => ListEnd::Right,
+                    _ => return Err(CommandError::SyntaxError),
+                };
+                Ok(LInsert {
+                    key: key.0,
+                    end,
+                    pivot,
+                    element,
+                })
+            }
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key, position, pivot or element".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for LMove {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lmove"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(src)),
+                Some(RespFrame::BulkString(dst)),
+                Some(RespFrame::BulkString(from)),
+                Some(RespFrame::BulkString(to)),
+            ) => Ok(LMove {
+                src: src.0,
+                dst: dst.0,
+                from: parse_list_end(&from)?,
+                to: parse_list_end(&to)?,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid source, destination or direction".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for RPopLPush {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["rpoplpush"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(src)), Some(RespFrame::BulkString(dst))) => Ok(RPopLPush {
+                src: src.0,
+                dst: dst.0,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid source or destination".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for LPos {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lpos"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, element) = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(element)) => (key.0, element),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key or element".to_string(),
+                ))
+            }
+        };
+        let (options, with_count) = parse_lpos_options(args)?;
+        Ok(LPos {
+            key,
+            element,
+            options,
+            with_count,
+        })
+    }
+}
+
+// the `RANK`, `COUNT` and `MAXLEN` options of LPOS, in any order, and whether COUNT was given
+fn parse_lpos_options(
+    mut args: impl Iterator<Item = RespFrame>,
+) -> Result<(LPosOptions, bool), CommandError> {
+    let mut options = LPosOptions::default();
+    let mut with_count = false;
+    while let Some(arg) = args.next() {
+        let RespFrame::BulkString(arg) = arg else {
+            return Err(CommandError::SyntaxError);
+        };
+        let Some(RespFrame::BulkString(value)) = args.next() else {
+            return Err(CommandError::SyntaxError);
+        };
+        let value = parse_integer(&value)?;
+        match arg.to_ascii_lowercase().as_slice() {
+            // like redis, the lowest rank is refused as its absolute value does not fit
+            b"rank" if value == 0 || value == i64::MIN => return Err(CommandError::ZeroRank),
+            b"rank" => options.rank = value,
+            b"count" if value < 0 => return Err(CommandError::NegativeCount),
+            b"count" => {
+                options.count = value as usize;
+                with_count = true;
+            }
+            b"maxlen" if value < 0 => return Err(CommandError::NegativeMaxLen),
+            b"maxlen" => options.maxlen = value as usize,
+            _ => return Err(CommandError::SyntaxError),
+        }
+    }
+    Ok((options, with_count))
+}
+
+impl TryFrom<RespArray> for BLPop {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (keys, timeout) = extract_blocking_args(value, "blpop")?;
+        Ok(BLPop { keys, timeout })
+    }
+}
+
+impl TryFrom<RespArray> for BRPop {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (keys, timeout) = extract_blocking_args(value, "brpop")?;
+        Ok(BRPop { keys, timeout })
+    }
+}
+
+// the `key [key ...] timeout` arguments of the blocking pops, the timeout being in seconds
+fn extract_blocking_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Vec<Bytes>, Option<Duration>), CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?;
+    let timeout = match args.pop() {
+        Some(RespFrame::BulkString(timeout)) => {
+            parse_float(&timeout).map_err(|_| CommandError::InvalidTimeout)?
+        }
+        _ => return Err(CommandError::InvalidTimeout),
+    };
+    let timeout = match timeout {
+        t if t < 0.0 => return Err(CommandError::NegativeTimeout),
+        0.0 => None,
+        t => Some(Duration::try_from_secs_f64(t).map_err(|_| CommandError::InvalidTimeout)?),
+    };
+    let keys = args
+        .into_iter()
+        .map(|frame| match frame {
+            RespFrame::BulkString(key) => Ok(key.0),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        })
+        .collect::<Result<Vec<_>, CommandError>>()?;
+    Ok((keys, timeout))
+}
+
+fn parse_list_end(value: &[u8]) -> Result<ListEnd, CommandError> {
+    match value.to_ascii_lowercase().as_slice() {
+        b"left" => Ok(ListEnd::Left),
+        b"right" => Ok(ListEnd::Right),
+        _ => Err(CommandError::SyntaxError),
+    }
+}
+
+// the `key start stop` arguments of the range commands
+fn extract_range_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, i64, i64), CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    match (args.next(), args.next(), args.next()) {
+        (
+            Some(RespFrame::BulkString(key)),
+            Some(RespFrame::BulkString(start)),
+            Some(RespFrame::BulkString(stop)),
+        ) => Ok((key.0, parse_integer(&start)?, parse_integer(&stop)?)),
+        _ => Err(CommandError::InvalidArgument(
+            "Invalid key, start or stop".to_string(),
+        )),
+    }
+}
+
+impl TryFrom<RespArray> for LIndex {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lindex"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(index))) => Ok(LIndex {
+                key: key.0,
+                index: parse_integer(&index)?,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or index".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for LSet {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lset"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(index)), Some(value)) => {
+                Ok(LSet {
+                    key: key.0,
+                    index: parse_integer(&index)?,
+                    value,
+                })
+            }
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key, index or value".to_string(),
+            )),
+        }
+    }
+}
+
+// the `key element [element ...]` arguments of the push commands
+fn extract_push_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, Vec<RespFrame>), CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    match args.next() {
+        Some(RespFrame::BulkString(key)) => Ok((key.0, args.collect())),
+        _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    }
+}
+
+// the `key [count]` arguments of the pop commands
+fn extract_pop_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, Option<usize>), CommandError> {
+    validate_command(&value, &[name])?;
+    if value.len() > 3 {
+        return Err(CommandError::SyntaxError);
+    }
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = match args.next() {
+        Some(RespFrame::BulkString(key)) => key.0,
+        _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    };
+    let count = match args.next() {
+        Some(RespFrame::BulkString(count)) => match parse_integer(&count)? {
+            count if count < 0 => return Err(CommandError::NotPositive),
+            count => Some(count as usize),
+        },
+        Some(_) => return Err(CommandError::NotAnInteger),
+        None => None,
+    };
+    Ok((key, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::{BulkString, RespDecode, RespEncode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn run(backend: &crate::Backend, request: &[u8]) -> Result<RespFrame> {
+        let mut buf = BytesMut::from(request);
+        let cmd = crate::cmd::Command::try_from(RespFrame::decode(&mut buf)?)?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    fn elements(frames: &[&str]) -> RespFrame {
+        RespArray::new(
+            frames
+                .iter()
+                .map(|f| BulkString::from(*f).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn test_push_ordering() -> Result<()> {
+        let backend = crate::Backend::new();
+        let request = b"*5\r\n$5\r\nlpush\r\n$4\r\nlist\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b":3\r\n");
+        let request = b"*4\r\n$5\r\nrpush\r\n$4\r\nlist\r\n$1\r\nd\r\n$1\r\ne\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::Integer(5));
+        assert_eq!(
+            run(&backend, b"*2\r\n$4\r\nllen\r\n$4\r\nlist\r\n")?,
+            RespFrame::Integer(5)
+        );
+
+        // LPUSH a b c puts c first
+        let request = b"*3\r\n$4\r\nlpop\r\n$4\r\nlist\r\n$1\r\n5\r\n";
+        assert_eq!(
+            run(&backend, request)?,
+            elements(&["c", "b", "a", "d", "e"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pushx() -> Result<()> {
+        let backend = crate::Backend::new();
+        assert_eq!(
+            run_args(&backend, &["lpushx", "list", "a", "b"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(&backend, &["rpushx", "list", "a"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(backend.key_type(b"list"), None);
+
+        backend.rpush("list".into(), elements_vec(&["b"]))?;
+        assert_eq!(
+            run_args(&backend, &["rpushx", "list", "c", "d"])?,
+            RespFrame::Integer(3)
+        );
+        assert_eq!(
+            run_args(&backend, &["lpushx", "list", "a"])?,
+            RespFrame::Integer(4)
+        );
+        assert_eq!(
+            lrange(&backend, "0", "-1")?,
+            elements(&["a", "b", "c", "d"])
+        );
+
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(run_args(&backend, &["lpushx", "string", "a"]).is_err());
+        assert!(run_args(&backend, &["rpushx", "list"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pop_commands() -> Result<()> {
+        let backend = crate::Backend::new();
+        backend.rpush(
+            "list".into(),
+            ["a", "b", "c", "d"]
+                .iter()
+                .map(|v| BulkString::from(*v).into())
+                .collect(),
+        )?;
+
+        assert_eq!(
+            run(&backend, b"*2\r\n$4\r\nlpop\r\n$4\r\nlist\r\n")?,
+            BulkString::from("a").into()
+        );
+        assert_eq!(
+            run(&backend, b"*2\r\n$4\r\nrpop\r\n$4\r\nlist\r\n")?,
+            BulkString::from("d").into()
+        );
+        // a count replies with an array even for one element, and is capped by the length
+        let request = b"*3\r\n$4\r\nrpop\r\n$4\r\nlist\r\n$2\r\n10\r\n";
+        assert_eq!(run(&backend, request)?, elements(&["c", "b"]));
+
+        // the emptied list is gone
+        assert_eq!(backend.key_type(b"list"), None);
+        assert_eq!(
+            run(&backend, b"*2\r\n$4\r\nlpop\r\n$4\r\nlist\r\n")?,
+            RespFrame::NULL
+        );
+        let request = b"*3\r\n$4\r\nlpop\r\n$4\r\nlist\r\n$1\r\n2\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::NULL);
+        let request = b"*3\r\n$4\r\nlpop\r\n$4\r\nlist\r\n$2\r\n-1\r\n";
+        assert!(run(&backend, request).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_wrong_type() -> Result<()> {
+        let backend = crate::Backend::new();
+        backend.set("string".into(), BulkString::from("value").into());
+        let request = b"*3\r\n$5\r\nlpush\r\n$6\r\nstring\r\n$1\r\na\r\n";
+        assert!(matches!(
+            run(&backend, request)
+                .unwrap_err()
+                .downcast::<CommandError>()?,
+            CommandError::WrongType
+        ));
+        assert!(backend.lpop(b"string", 1).is_err());
+
+        // and the other way around
+        backend.lpush("list".into(), vec![BulkString::from("a").into()])?;
+        assert_eq!(backend.key_type(b"list"), Some(crate::KeyType::List));
+        assert!(backend.hlen(b"list").is_err());
+        Ok(())
+    }
+
+    fn list_backend(values: &[&str]) -> crate::Backend {
+        let backend = crate::Backend::new();
+        backend
+            .rpush(
+                "list".into(),
+                values.iter().map(|v| BulkString::from(*v).into()).collect(),
+            )
+            .unwrap();
+        backend
+    }
+
+    fn lrange(backend: &crate::Backend, start: &str, stop: &str) -> Result<RespFrame> {
+        let request = format!(
+            "*4\r\n$6\r\nlrange\r\n$4\r\nlist\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
+            start.len(),
+            start,
+            stop.len(),
+            stop
+        );
+        run(backend, request.as_bytes())
+    }
+
+    #[test]
+    fn test_lrange_command() -> Result<()> {
+        let backend = list_backend(&["a", "b", "c", "d", "e"]);
+        assert_eq!(
+            lrange(&backend, "0", "-1")?,
+            elements(&["a", "b", "c", "d", "e"])
+        );
+        assert_eq!(lrange(&backend, "1", "2")?, elements(&["b", "c"]));
+        assert_eq!(lrange(&backend, "-2", "-1")?, elements(&["d", "e"]));
+        assert_eq!(lrange(&backend, "-100", "1")?, elements(&["a", "b"]));
+        assert_eq!(lrange(&backend, "3", "100")?, elements(&["d", "e"]));
+        assert_eq!(lrange(&backend, "3", "1")?, RespArray::new([]).into());
+        assert_eq!(lrange(&backend, "5", "10")?, RespArray::new([]).into());
+        assert!(lrange(&backend, "a", "1").is_err());
+
+        backend.del(&["list".into()]);
+        assert_eq!(lrange(&backend, "0", "-1")?, RespArray::new([]).into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lindex_lset_commands() -> Result<()> {
+        let backend = list_backend(&["a", "b", "c"]);
+        let request = b"*3\r\n$6\r\nlindex\r\n$4\r\nlist\r\n$2\r\n-1\r\n";
+        assert_eq!(run(&backend, request)?, BulkString::from("c").into());
+        let request = b"*3\r\n$6\r\nlindex\r\n$4\r\nlist\r\n$1\r\n3\r\n";
+        assert_eq!(run(&backend, request)?, RespFrame::NULL);
+
+        let request = b"*4\r\n$4\r\nlset\r\n$4\r\nlist\r\n$2\r\n-3\r\n$1\r\nx\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b"+OK\r\n");
+        assert_eq!(lrange(&backend, "0", "-1")?, elements(&["x", "b", "c"]));
+
+        let errors = [
+            (
+                &b"*4\r\n$4\r\nlset\r\n$4\r\nlist\r\n$1\r\n3\r\n$1\r\nx\r\n"[..],
+                &b"-ERR index out of range\r\n"[..],
+            ),
+            (
+                b"*4\r\n$4\r\nlset\r\n$7\r\nmissing\r\n$1\r\n0\r\n$1\r\nx\r\n",
+                b"-ERR no such key\r\n",
+            ),
+        ];
+        for (request, expected) in errors {
+            let mut buf = BytesMut::from(request);
+            let cmd = crate::cmd::Command::try_from(RespFrame::decode(&mut buf)?)?;
+            let reply = RespFrame::from(cmd.execute(&backend).unwrap_err());
+            assert_eq!(reply.encode(), expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_lrem_command() -> Result<()> {
+        let values = ["x", "a", "x", "b", "x", "c", "x"];
+
+        // a positive count removes from the head
+        let backend = list_backend(&values);
+        assert_eq!(
+            run_args(&backend, &["lrem", "list", "2", "x"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(
+            lrange(&backend, "0", "-1")?,
+            elements(&["a", "b", "x", "c", "x"])
+        );
+
+        // a negative count from the tail
+        let backend = list_backend(&values);
+        assert_eq!(
+            run_args(&backend, &["lrem", "list", "-3", "x"])?,
+            RespFrame::Integer(3)
+        );
+        assert_eq!(
+            lrange(&backend, "0", "-1")?,
+            elements(&["x", "a", "b", "c"])
+        );
+
+        // zero removes every occurrence
+        let backend = list_backend(&values);
+        assert_eq!(
+            run_args(&backend, &["lrem", "list", "0", "x"])?,
+            RespFrame::Integer(4)
+        );
+        assert_eq!(lrange(&backend, "0", "-1")?, elements(&["a", "b", "c"]));
+        assert_eq!(
+            run_args(&backend, &["lrem", "list", "0", "y"])?,
+            RespFrame::Integer(0)
+        );
+
+        // removing everything removes the key
+        let backend = list_backend(&["x", "x"]);
+        assert_eq!(
+            run_args(&backend, &["lrem", "list", "-5", "x"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(backend.key_type(b"list"), None);
+        assert_eq!(
+            run_args(&backend, &["lrem", "list", "0", "x"])?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ltrim_command() -> Result<()> {
+        let backend = list_backend(&["a", "b", "c", "d", "e"]);
+        assert_eq!(
+            run_args(&backend, &["ltrim", "list", "1", "-2"])?,
+            RESP_OK.clone()
+        );
+        assert_eq!(lrange(&backend, "0", "-1")?, elements(&["b", "c", "d"]));
+        run_args(&backend, &["ltrim", "list", "-100", "1"])?;
+        assert_eq!(lrange(&backend, "0", "-1")?, elements(&["b", "c"]));
+
+        // an empty range deletes the key, its ttl included
+        backend.expire(b"list", 100_000);
+        assert_eq!(
+            run_args(&backend, &["ltrim", "list", "5", "10"])?,
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.key_type(b"list"), None);
+        assert_eq!(backend.pttl(b"list"), -2);
+        assert!(backend.volatile_keys() == 0);
+
+        let backend = list_backend(&["a", "b"]);
+        run_args(&backend, &["ltrim", "list", "1", "0"])?;
+        assert_eq!(
+            run_args(&backend, &["exists", "list"])?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_linsert_command() -> Result<()> {
+        let backend = list_backend(&["a", "c"]);
+        assert_eq!(
+            run_args(&backend, &["linsert", "list", "BEFORE", "c", "b"])?,
+            RespFrame::Integer(3)
+        );
+        assert_eq!(
+            run_args(&backend, &["linsert", "list", "after", "c", "d"])?,
+            RespFrame::Integer(4)
+        );
+        assert_eq!(
+            lrange(&backend, "0", "-1")?,
+            elements(&["a", "b", "c", "d"])
+        );
+
+        assert_eq!(
+            run_args(&backend, &["linsert", "list", "before", "x", "y"])?,
+            RespFrame::Integer(-1)
+        );
+        assert_eq!(
+            run_args(&backend, &["linsert", "missing", "before", "a", "b"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(backend.key_type(b"missing"), None);
+        assert!(run_args(&backend, &["linsert", "list", "middle", "a", "b"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lmove_between_lists() -> Result<()> {
+        let backend = list_backend(&["a", "b", "c"]);
+        assert_eq!(
+            run_args(&backend, &["lmove", "list", "other", "LEFT", "RIGHT"])?,
+            BulkString::from("a").into()
+        );
+        assert_eq!(
+            run_args(&backend, &["lmove", "list", "other", "left", "right"])?,
+            BulkString::from("b").into()
+        );
+        // RPOPLPUSH takes from the tail and pushes onto the head
+        assert_eq!(
+            run_args(&backend, &["rpoplpush", "list", "other"])?,
+            BulkString::from("c").into()
+        );
+        assert_eq!(backend.key_type(b"list"), None);
+        assert_eq!(
+            backend.lrange(b"other", 0, -1)?,
+            elements_vec(&["c", "a", "b"])
+        );
+
+        // a missing source moves nothing and creates nothing
+        assert_eq!(
+            run_args(&backend, &["rpoplpush", "missing", "created"])?,
+            RespFrame::NULL
+        );
+        assert_eq!(backend.key_type(b"created"), None);
+
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(run_args(&backend, &["rpoplpush", "other", "string"]).is_err());
+        assert_eq!(backend.llen(b"other")?, 3);
+        assert!(run_args(&backend, &["lmove", "other", "x", "up", "left"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lmove_rotates_same_list() -> Result<()> {
+        let backend = list_backend(&["a", "b", "c"]);
+        assert_eq!(
+            run_args(&backend, &["rpoplpush", "list", "list"])?,
+            BulkString::from("c").into()
+        );
+        assert_eq!(
+            backend.lrange(b"list", 0, -1)?,
+            elements_vec(&["c", "a", "b"])
+        );
+        assert_eq!(
+            run_args(&backend, &["lmove", "list", "list", "left", "right"])?,
+            BulkString::from("c").into()
+        );
+        assert_eq!(
+            backend.lrange(b"list", 0, -1)?,
+            elements_vec(&["a", "b", "c"])
+        );
+        // the same end is a no-op that still returns the element
+        assert_eq!(
+            run_args(&backend, &["lmove", "list", "list", "left", "left"])?,
+            BulkString::from("a").into()
+        );
+        assert_eq!(
+            backend.lrange(b"list", 0, -1)?,
+            elements_vec(&["a", "b", "c"])
+        );
+        Ok(())
+    }
+
+    fn elements_vec(frames: &[&str]) -> Vec<RespFrame> {
+        frames.iter().map(|f| BulkString::from(*f).into()).collect()
+    }
+
+    #[test]
+    fn test_parse_lpos_options() {
+        let parse = |args: &[&str]| parse_lpos_options(elements_vec(args).into_iter());
+
+        assert_eq!(parse(&[]).unwrap(), (LPosOptions::default(), false));
+        assert_eq!(
+            parse(&["RANK", "-2", "count", "0", "MaxLen", "10"]).unwrap(),
+            (
+                LPosOptions {
+                    rank: -2,
+                    count: 0,
+                    maxlen: 10
+                },
+                true
+            )
+        );
+        // the last occurrence of an option wins
+        assert_eq!(parse(&["rank", "2", "rank", "3"]).unwrap().0.rank, 3);
+        assert_eq!(
+            parse(&["count", "1"]).unwrap(),
+            (LPosOptions::default(), true)
+        );
+
+        for (args, error) in [
+            (&["rank", "0"][..], "RANK can't be zero"),
+            (&["rank", "-9223372036854775808"], "RANK can't be zero"),
+            (&["count", "-1"], "COUNT can't be negative"),
+            (&["maxlen", "-1"], "MAXLEN can't be negative"),
+            (&["rank", "first"], "not an integer"),
+            (&["rank"], "syntax error"),
+            (&["limit", "1"], "syntax error"),
+        ] {
+            let err = parse(args).unwrap_err().to_string();
+            assert!(err.contains(error), "{:?}: {}", args, err);
+        }
+    }
+
+    #[test]
+    fn test_lpos() -> Result<()> {
+        let backend = list_backend(&["a", "b", "c", "1", "2", "3", "c", "c"]);
+        assert_eq!(
+            run_args(&backend, &["lpos", "list", "c"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(
+            run_args(&backend, &["lpos", "list", "c", "rank", "-1"])?,
+            RespFrame::Integer(7)
+        );
+        assert_eq!(
+            run_args(&backend, &["lpos", "list", "c", "count", "0", "rank", "2"])?,
+            RespArray::new(vec![RespFrame::Integer(6), RespFrame::Integer(7)]).into()
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &["lpos", "list", "c", "count", "2", "maxlen", "3"]
+            )?,
+            RespArray::new(vec![RespFrame::Integer(2)]).into()
+        );
+        assert_eq!(run_args(&backend, &["lpos", "list", "x"])?, RespFrame::NULL);
+        assert_eq!(
+            run_args(&backend, &["lpos", "missing", "a", "count", "1"])?,
+            RespArray::new(vec![]).into()
+        );
+
+        backend.set("string".into(), BulkString::from("a").into());
+        assert!(run_args(&backend, &["lpos", "string", "a"]).is_err());
+        assert!(run_args(&backend, &["lpos", "list", "a", "rank", "0"]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blpop_waits_for_push() -> Result<()> {
+        let backend = crate::Backend::new();
+        let pusher = backend.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            pusher
+                .rpush("second".into(), elements_vec(&["a", "b"]))
+                .unwrap();
+        });
+
+        let cmd = crate::cmd::Command::try_from(RespArray::new(elements_vec(&[
+            "blpop", "first", "second", "5",
+        ])))?;
+        let reply = cmd.execute_async(&backend).await?;
+        assert_eq!(reply, elements(&["second", "a"]));
+        // the blocked client took one element, the rest stays in the list
+        assert_eq!(backend.lrange(b"second", 0, -1)?, elements_vec(&["b"]));
+        assert!(backend.blocked.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blpop_timeout() -> Result<()> {
+        let backend = crate::Backend::new();
+        let cmd = crate::cmd::Command::try_from(RespArray::new(elements_vec(&[
+            "brpop", "list", "0.05",
+        ])))?;
+        let started = std::time::Instant::now();
+        let reply = cmd.execute_async(&backend).await?;
+        assert_eq!(reply, RespFrame::NULL);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(backend.blocked.is_empty());
+
+        // a push after the timeout is kept in the list
+        backend.rpush("list".into(), elements_vec(&["a"]))?;
+        assert_eq!(backend.llen(b"list")?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blocked_clients_are_served_in_order() -> Result<()> {
+        let backend = crate::Backend::new();
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            let backend = backend.clone();
+            clients.push(tokio::spawn(async move {
+                backend
+                    .blocking_pop(&["list".into()], ListEnd::Left, None)
+                    .await
+            }));
+            // let the client queue up before the next one
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        backend.rpush("list".into(), elements_vec(&["a", "b", "c", "d"]))?;
+        for (client, expected) in clients.into_iter().zip(["a", "b", "c"]) {
+            let (key, element) = client.await??.unwrap();
+            assert_eq!(key, "list");
+            assert_eq!(element, BulkString::from(expected).into());
+        }
+        assert_eq!(backend.lrange(b"list", 0, -1)?, elements_vec(&["d"]));
+
+        // an element available right away does not block
+        let popped = backend
+            .blocking_pop(&["missing".into(), "list".into()], ListEnd::Right, None)
+            .await?;
+        assert_eq!(popped, Some(("list".into(), BulkString::from("d").into())));
+        assert_eq!(backend.key_type(b"list"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocking_pop_timeout_parsing() {
+        let backend = crate::Backend::new();
+        assert!(run_args(&backend, &["blpop", "list", "-1"]).is_err());
+        assert!(run_args(&backend, &["blpop", "list", "soon"]).is_err());
+        assert!(run_args(&backend, &["blpop", "list"]).is_err());
+        // without a connection to wait on, the pop doesn't block
+        assert_eq!(
+            run_args(&backend, &["blpop", "list", "0"]).unwrap(),
+            RespFrame::NULL
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use super::{command, Command, CommandError, CommandExecutor};
+use crate::{Backend, BulkString, RespArray, RespFrame, SimpleError, SimpleString};
+use bytes::Bytes;
+use mlua::{HookTriggers, Lua, LuaOptions, MultiValue, StdLib, Table, Value};
+use std::cell::RefCell;
+use std::time::Instant;
+
+// how many instructions a script runs between two checks of how long it has been running
+const HOOK_INSTRUCTIONS: u32 = 100_000;
+
+// a command the script called failed, which fails the script with the same error unless it is
+// caught with `pcall`
+#[derive(Debug)]
+struct CallError(String);
+
+impl std::fmt::Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CallError {}
+
+// the script ran for longer than `busy-reply-threshold`, which fails it even inside a `pcall`
+#[derive(Debug)]
+struct TimedOut;
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("script timed out")
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Check that `script` compiles, before `SCRIPT LOAD` caches it.
+pub(super) fn compile(script: &str) -> Result<(), CommandError> {
+    let lua = sandbox()?;
+    lua.load(script)
+        .set_name("@user_script")
+        .into_function()
+        .map_err(|e| CommandError::Script(message(&e)))?;
+    Ok(())
+}
+
+/// Run `script` with the `KEYS` and `ARGV` tables, replying with what it returns. The caller
+/// makes sure no other command runs meanwhile, so a script running for longer than
+/// `busy-reply-threshold` is aborted, keeping what it wrote until then. What it wrote is
+/// propagated once it stopped, as one transaction.
+pub(super) fn run(
+    backend: &Backend,
+    script: &str,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+) -> Result<RespFrame, CommandError> {
+    let lua = sandbox()?;
+    if let Some(limit) = backend.script_time_limit() {
+        let start = Instant::now();
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS),
+            move |lua, _| {
+                if start.elapsed() <= limit {
+                    return Ok(());
+                }
+                // like redis, raised again at every instruction from now on, for a `pcall` not
+                // to keep the script going
+                lua.set_hook(HookTriggers::new().every_nth_instruction(1), |_, _| {
+                    Err(mlua::Error::external(TimedOut))
+                });
+                Err(mlua::Error::external(TimedOut))
+            },
+        );
+    }
+    // like redis, a SELECT in the script is only for the rest of the script
+    let db = RefCell::new(backend.clone());
+    let writes = RefCell::new(Vec::new());
+    let result = lua.scope(|scope| {
+        let globals = lua.globals();
+        let keys = keys
+            .iter()
+            .map(|key| lua.create_string(key))
+            .collect::<mlua::Result<Vec<_>>>()?;
+        globals.set("KEYS", lua.create_sequence_from(keys)?)?;
+        let args = args
+            .iter()
+            .map(|arg| lua.create_string(arg))
+            .collect::<mlua::Result<Vec<_>>>()?;
+        globals.set("ARGV", lua.create_sequence_from(args)?)?;
+
+        let redis = lua.create_table()?;
+        redis.set(
+            "call",
+            scope.create_function(|lua, args: MultiValue| match call(&db, &writes, args) {
+                Ok(reply) => to_lua(lua, reply),
+                Err(e) => Err(mlua::Error::external(CallError(error_message(e)))),
+            })?,
+        )?;
+        redis.set(
+            "pcall",
+            scope.create_function(|lua, args: MultiValue| {
+                let reply = call(&db, &writes, args).unwrap_or_else(RespFrame::from);
+                to_lua(lua, reply)
+            })?,
+        )?;
+        redis.set(
+            "error_reply",
+            lua.create_function(|lua, message: mlua::String| reply_table(lua, "err", message))?,
+        )?;
+        redis.set(
+            "status_reply",
+            lua.create_function(|lua, message: mlua::String| reply_table(lua, "ok", message))?,
+        )?;
+        globals.set("redis", redis)?;
+
+        let value = lua.load(script).set_name("@user_script").eval::<Value>()?;
+        Ok(from_lua(value))
+    });
+    backend.propagate_transaction(&writes.into_inner());
+    match result {
+        Ok(reply) => Ok(reply),
+        Err(e) if source_error::<TimedOut>(&e).is_some() => Err(CommandError::ScriptTimedOut),
+        Err(e) => match source_error::<CallError>(&e) {
+            Some(CallError(message)) => Ok(SimpleError::new(message.clone()).into()),
+            None => Err(CommandError::Script(message(&e))),
+        },
+    }
+}
+
+// a state with only the libraries that can't reach outside the server
+fn sandbox() -> Result<Lua, CommandError> {
+    Lua::new_with(
+        StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+        LuaOptions::default(),
+    )
+    .map_err(|e| CommandError::Script(message(&e)))
+}
+
+// run the command of the arguments of `redis.call`, on the database the script is on. What it
+// wrote joins the `writes` of the script, with the database it wrote to
+fn call(
+    db: &RefCell<Backend>,
+    writes: &RefCell<Vec<(Backend, Vec<u8>)>>,
+    args: MultiValue,
+) -> Result<RespFrame, CommandError> {
+    let args = args
+        .into_iter()
+        .map(|arg| match arg {
+            Value::String(s) => Ok(BulkString::new(s.as_bytes()).into()),
+            Value::Integer(n) => Ok(BulkString::from(n.to_string()).into()),
+            Value::Number(n) => Ok(BulkString::from(n.to_string()).into()),
+            _ => Err(CommandError::ScriptArgument),
+        })
+        .collect::<Result<Vec<RespFrame>, _>>()?;
+    if args.is_empty() {
+        return Err(CommandError::ScriptNoCommand);
+    }
+    let args = RespArray::new(args);
+    if command::spec_of(&args).is_some_and(|spec| spec.has_flag("noscript")) {
+        return Err(CommandError::NotAllowedFromScript);
+    }
+
+    let backend = db.borrow().clone();
+    let frame = RespFrame::from(args);
+    let (written, propagated) = (Command::written_keys(&frame), Command::propagated(&frame));
+    let cmd = Command::from_request(frame, &backend)?;
+    if propagated.is_some() && backend.is_replica() {
+        return Err(CommandError::ReadOnly);
+    }
+    let index = match &cmd {
+        Command::Select(select) => Some(select.index),
+        _ => None,
+    };
+    let reply = cmd.execute(&backend);
+    backend.signal_modified(&written);
+    let reply = reply?;
+    if let Some(command) = propagated {
+        let mut writes = writes.borrow_mut();
+        for command in command.commands(&backend, &reply) {
+            writes.push((backend.clone(), command));
+        }
+    }
+    if let Some(index) = index {
+        *db.borrow_mut() = backend.select(index)?;
+    }
+    Ok(reply)
+}
+
+// a reply as a Lua value the way redis converts them: a status or an error is a table with an
+// `ok` or `err` field, and a nil is false
+fn to_lua(lua: &Lua, frame: RespFrame) -> mlua::Result<Value<'_>> {
+    Ok(match frame {
+        RespFrame::SimpleString(s) => reply_table(lua, "ok", lua.create_string(&s.0)?)?,
+        RespFrame::Error(e) => reply_table(lua, "err", lua.create_string(&e.0)?)?,
+        RespFrame::Integer(n) => Value::Integer(n),
+        RespFrame::BulkString(s) => Value::String(lua.create_string(&s.0)?),
+        RespFrame::Null(_) => Value::Boolean(false),
+        RespFrame::Boolean(b) => Value::Boolean(b),
+        RespFrame::Double(f) => Value::Number(f),
+        RespFrame::BigNumber(n) => Value::String(lua.create_string(&n.0)?),
+        RespFrame::Verbatim(s) => Value::String(lua.create_string(&s.data)?),
+        RespFrame::Array(RespArray(frames))
+        | RespFrame::Set(crate::RespSet(frames))
+        | RespFrame::Push(crate::RespPush(frames)) => sequence(lua, frames)?,
+        // as the flat array of a RESP2 reply
+        RespFrame::Map(map) => sequence(
+            lua,
+            map.0
+                .into_iter()
+                .flat_map(|(key, value)| [BulkString::from(key).into(), value])
+                .collect(),
+        )?,
+    })
+}
+
+fn sequence(lua: &Lua, frames: Vec<RespFrame>) -> mlua::Result<Value<'_>> {
+    let table = lua.create_table_with_capacity(frames.len(), 0)?;
+    for frame in frames {
+        table.raw_push(to_lua(lua, frame)?)?;
+    }
+    Ok(Value::Table(table))
+}
+
+fn reply_table<'lua>(
+    lua: &'lua Lua,
+    field: &str,
+    message: mlua::String<'lua>,
+) -> mlua::Result<Value<'lua>> {
+    let table = lua.create_table()?;
+    table.raw_set(field, message)?;
+    Ok(Value::Table(table))
+}
+
+// what a script returns as a reply: numbers are truncated to integers, true is 1, false and nil
+// are nil, and an array ends at its first nil
+fn from_lua(value: Value) -> RespFrame {
+    match value {
+        Value::Boolean(true) => RespFrame::Integer(1),
+        Value::Integer(n) => RespFrame::Integer(n),
+        Value::Number(n) => RespFrame::Integer(n as i64),
+        Value::String(s) => BulkString::new(s.as_bytes()).into(),
+        Value::Table(table) => from_table(table),
+        _ => RespFrame::NULL,
+    }
+}
+
+fn from_table(table: Table) -> RespFrame {
+    if let Ok(Value::String(message)) = table.raw_get("err") {
+        return SimpleError::new(message.to_string_lossy()).into();
+    }
+    if let Ok(Value::String(message)) = table.raw_get("ok") {
+        return SimpleString::new(message.to_string_lossy()).into();
+    }
+    let mut frames = Vec::new();
+    for index in 1.. {
+        match table.raw_get(index) {
+            Ok(Value::Nil) | Err(_) => break,
+            Ok(value) => frames.push(from_lua(value)),
+        }
+    }
+    RespArray::new(frames).into()
+}
+
+// the text of an error reply, without the `-`
+fn error_message(e: CommandError) -> String {
+    match RespFrame::from(e) {
+        RespFrame::Error(e) => e.0,
+        frame => format!("{:?}", frame),
+    }
+}
+
+// the failed call or the timeout a script error comes from, if any
+fn source_error<T: std::error::Error + 'static>(e: &mlua::Error) -> Option<&T> {
+    match e {
+        mlua::Error::CallbackError { cause, .. } => source_error::<T>(cause),
+        e => e.downcast_ref(),
+    }
+}
+
+// the message of a Lua error without the traceback
+fn message(e: &mlua::Error) -> String {
+    match e {
+        mlua::Error::SyntaxError { message, .. } | mlua::Error::RuntimeError(message) => {
+            message.clone()
+        }
+        mlua::Error::CallbackError { cause, .. } => message(cause),
+        e => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::request_args;
+    use crate::RespEncode;
+    use anyhow::Result;
+
+    fn eval(backend: &Backend, script: &str, keys: &[&str], args: &[&str]) -> Result<RespFrame> {
+        let keys = keys
+            .iter()
+            .map(|key| Bytes::copy_from_slice(key.as_bytes()))
+            .collect();
+        let args = args
+            .iter()
+            .map(|arg| Bytes::copy_from_slice(arg.as_bytes()))
+            .collect();
+        Ok(run(backend, script, keys, args)?)
+    }
+
+    #[test]
+    fn test_compare_and_set() -> Result<()> {
+        let backend = Backend::new();
+        let script = "if redis.call('get', KEYS[1]) == ARGV[1] then \
+                          return redis.call('set', KEYS[1], ARGV[2]) \
+                      end \
+                      return false";
+        backend.set("key".into(), BulkString::from("old").into());
+        assert_eq!(
+            eval(&backend, script, &["key"], &["other", "new"])?,
+            RespFrame::NULL
+        );
+        assert_eq!(
+            eval(&backend, script, &["key"], &["old", "new"])?,
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(backend.get(b"key"), Some(BulkString::from("new").into()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversions() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            eval(&backend, "return {1, 2.9, 'three', true, nil, 6}", &[], &[])?,
+            RespArray::new(vec![
+                RespFrame::Integer(1),
+                RespFrame::Integer(2),
+                BulkString::from("three").into(),
+                RespFrame::Integer(1),
+            ])
+            .into()
+        );
+        assert_eq!(
+            eval(&backend, "return redis.error_reply('MY oops')", &[], &[])?,
+            SimpleError::new("MY oops").into()
+        );
+        assert_eq!(
+            eval(&backend, "return {ok = 'fine'}", &[], &[])?,
+            SimpleString::new("fine").into()
+        );
+        // a failed call fails the script with its error, unless it is a pcall
+        assert_eq!(
+            eval(
+                &backend,
+                "redis.call('incr', 'nope', 'extra') return 1",
+                &[],
+                &[]
+            )?,
+            SimpleError::new("ERR wrong number of arguments for 'incr' command").into()
+        );
+        backend.set("text".into(), BulkString::from("abc").into());
+        assert_eq!(
+            eval(
+                &backend,
+                "return redis.pcall('incr', 'text')['err']",
+                &[],
+                &[]
+            )?,
+            BulkString::from("ERR value is not an integer or out of range").into()
+        );
+        assert!(eval(&backend, "return redis.call('multi')", &[], &[])
+            .is_ok_and(|reply| matches!(reply, RespFrame::Error(e) if e.contains("not allowed"))));
+        assert!(eval(&backend, "return +", &[], &[]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_writes_are_propagated_whole() -> Result<()> {
+        let backend = Backend::new();
+        let mut resync = backend.add_replica("127.0.0.1:6380".into()).await;
+        let script = "redis.call('set', 'a', '1') \
+                      redis.call('select', '2') \
+                      redis.call('incr', 'b') \
+                      return redis.call('get', 'b')";
+        assert_eq!(
+            eval(&backend, script, &[], &[])?,
+            BulkString::from("1").into()
+        );
+        // nothing for a script that wrote nothing
+        eval(&backend, "return redis.call('get', 'a')", &[], &[])?;
+        backend.propagate(&request_args(&["set", "c", "3"]).encode());
+        for expected in [
+            &["MULTI"][..],
+            &["SELECT", "0"],
+            &["set", "a", "1"],
+            &["SELECT", "2"],
+            &["incr", "b"],
+            &["EXEC"],
+            &["SELECT", "0"],
+            &["set", "c", "3"],
+        ] {
+            assert_eq!(
+                resync.commands.recv().await,
+                Some(request_args(expected).encode())
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_busy_script_is_aborted() -> Result<()> {
+        let backend = Backend::new();
+        backend
+            .config_set(&[("busy-reply-threshold".to_string(), "50".to_string())])
+            .unwrap();
+        let script = "redis.call('set', 'key', 'value') \
+                      while true do pcall(function() while true do end end) end";
+        assert!(matches!(
+            run(&backend, script, Vec::new(), Vec::new()),
+            Err(CommandError::ScriptTimedOut)
+        ));
+        // what it wrote until then is kept
+        assert_eq!(backend.get(b"key"), Some(BulkString::from("value").into()));
+        Ok(())
+    }
+}
@@ -1,27 +1,219 @@
-use super::{extract_args, validate_command, CommandExecutor, Echo, Set, RESP_OK};
+use super::{
+    extract_args, extract_key_args, parse_float, parse_integer, validate_command, Append,
+    CommandExecutor, Decr, DecrBy, Echo, GetDel, GetEx, GetRange, GetSet, Incr, IncrBy,
+    IncrByFloat, MGet, MSet, MSetNx, Ping, Set, SetEx, SetNx, SetRange, Strlen, RESP_OK,
+};
 use crate::{
-    cmd::{CommandError, Get}, BulkString, RespArray, RespFrame, RespNull
+    cmd::{CommandError, Get},
+    BulkString, KeyType, KeyspaceEvents, RespArray, RespFrame, SetCondition, SetExpiry, SetOptions,
+    SimpleString,
 };
+use bytes::Bytes;
 
 impl CommandExecutor for Get {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        match backend.get(&self.key) {
-            Some(value) => value,
-            None => RespFrame::Null(RespNull),
-        }
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.check_type(&self.key, KeyType::String)?;
+        Ok(backend.get(&self.key).unwrap_or(RespFrame::NULL))
     }
 }
 
 impl CommandExecutor for Set {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        backend.set(self.key, self.value);
-        RESP_OK.clone()
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let (written, old) =
+            backend.set_with_options(self.key.clone(), self.value, &self.options)?;
+        if written {
+            backend.notify_keyspace_event(KeyspaceEvents::STRING, "set", &self.key);
+        }
+        Ok(match written {
+            // with GET the reply is the previous value, whether or not the new one was written
+            _ if self.options.get => old.unwrap_or(RespFrame::NULL),
+            true => RESP_OK.clone(),
+            false => RespFrame::NULL,
+        })
     }
 }
 
 impl CommandExecutor for Echo {
-    fn execute(self, _backend: &crate::Backend) -> RespFrame {
-        RespFrame::BulkString(BulkString::new(self.message))
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::BulkString(BulkString::new(self.message)))
+    }
+}
+
+impl CommandExecutor for Ping {
+    // like redis, a subscriber is answered with an array, which can't be mistaken for a message
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        if backend.is_subscriber() {
+            return Ok(RespArray::new(vec![
+                BulkString::from("pong").into(),
+                BulkString::new(self.message.unwrap_or_default()).into(),
+            ])
+            .into());
+        }
+        Ok(match self.message {
+            Some(message) => RespFrame::BulkString(BulkString::new(message)),
+            None => SimpleString::new("PONG").into(),
+        })
+    }
+}
+
+impl CommandExecutor for Incr {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(incr_by(backend, &self.key, 1)?))
+    }
+}
+
+impl CommandExecutor for Decr {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(incr_by(backend, &self.key, -1)?))
+    }
+}
+
+impl CommandExecutor for IncrBy {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(incr_by(backend, &self.key, self.delta)?))
+    }
+}
+
+impl CommandExecutor for DecrBy {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let delta = self.delta.checked_neg().ok_or(CommandError::NotAnInteger)?;
+        Ok(RespFrame::Integer(incr_by(backend, &self.key, delta)?))
+    }
+}
+
+// like redis, the four commands make the same `incrby` event
+fn incr_by(backend: &crate::Backend, key: &[u8], delta: i64) -> Result<i64, CommandError> {
+    let value = backend.incr_by(key, delta)?;
+    backend.notify_keyspace_event(KeyspaceEvents::STRING, "incrby", key);
+    Ok(value)
+}
+
+impl CommandExecutor for IncrByFloat {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let value = backend.incr_by_float(&self.key, self.delta)?;
+        backend.notify_keyspace_event(KeyspaceEvents::STRING, "incrbyfloat", &self.key);
+        Ok(BulkString::from(value).into())
+    }
+}
+
+impl CommandExecutor for MGet {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let values = backend
+            .mget(&self.keys)
+            .into_iter()
+            .map(|v| v.unwrap_or(RespFrame::NULL))
+            .collect::<Vec<_>>();
+        Ok(RespArray::new(values).into())
+    }
+}
+
+impl CommandExecutor for MSet {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let keys = self.pairs.iter().map(|(key, _)| key.clone()).collect();
+        backend.mset(self.pairs);
+        notify_set(backend, keys);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for MSetNx {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let keys = self.pairs.iter().map(|(key, _)| key.clone()).collect();
+        let written = backend.msetnx(self.pairs);
+        if written {
+            notify_set(backend, keys);
+        }
+        Ok(RespFrame::Integer(written as i64))
+    }
+}
+
+// the `set` event of each key written by MSET or MSETNX
+fn notify_set(backend: &crate::Backend, keys: Vec<Bytes>) {
+    for key in keys {
+        backend.notify_keyspace_event(KeyspaceEvents::STRING, "set", &key);
+    }
+}
+
+impl CommandExecutor for Append {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let len = backend.append(&self.key, &self.value)?;
+        backend.notify_keyspace_event(KeyspaceEvents::STRING, "append", &self.key);
+        Ok(RespFrame::Integer(len))
+    }
+}
+
+impl CommandExecutor for Strlen {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.strlen(&self.key)?))
+    }
+}
+
+impl CommandExecutor for GetRange {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let value = backend.getrange(&self.key, self.start, self.end)?;
+        Ok(BulkString::new(value).into())
+    }
+}
+
+impl CommandExecutor for SetRange {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        // the offset is checked to be non-negative when parsing
+        let len = backend.setrange(&self.key, self.offset as usize, &self.value)?;
+        backend.notify_keyspace_event(KeyspaceEvents::STRING, "setrange", &self.key);
+        Ok(RespFrame::Integer(len))
+    }
+}
+
+impl CommandExecutor for GetSet {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let old = backend.getset(self.key.clone(), self.value)?;
+        backend.notify_keyspace_event(KeyspaceEvents::STRING, "set", &self.key);
+        Ok(old.unwrap_or(RespFrame::NULL))
+    }
+}
+
+impl CommandExecutor for GetDel {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let value = backend.getdel(&self.key)?;
+        if value.is_some() {
+            backend.notify_keyspace_event(KeyspaceEvents::GENERIC, "del", &self.key);
+        }
+        Ok(value.unwrap_or(RespFrame::NULL))
+    }
+}
+
+impl CommandExecutor for GetEx {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let value = backend.getex(&self.key, self.expiry)?;
+        Ok(value.unwrap_or(RespFrame::NULL))
+    }
+}
+
+impl CommandExecutor for SetNx {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let options = SetOptions {
+            condition: SetCondition::IfNotExists,
+            ..Default::default()
+        };
+        let (written, _) = backend.set_with_options(self.key.clone(), self.value, &options)?;
+        if written {
+            backend.notify_keyspace_event(KeyspaceEvents::STRING, "set", &self.key);
+        }
+        Ok(RespFrame::Integer(written as i64))
+    }
+}
+
+impl CommandExecutor for SetEx {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let options = SetOptions {
+            // the multiplication is checked when parsing
+            expiry: SetExpiry::After(self.seconds * 1000),
+            ..Default::default()
+        };
+        backend.set_with_options(self.key.clone(), self.value, &options)?;
+        backend.notify_keyspace_event(KeyspaceEvents::STRING, "set", &self.key);
+        backend.notify_keyspace_event(KeyspaceEvents::GENERIC, "expire", &self.key);
+        Ok(RESP_OK.clone())
     }
 }
 
@@ -29,13 +221,11 @@ impl CommandExecutor for Echo {
 impl TryFrom<RespArray> for Get {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["get"], 1)?;
+        validate_command(&value, &["get"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
-            Some(RespFrame::BulkString(key)) => Ok(Get {
-                key: String::from_utf8(key.0)?,
-            }),
+            Some(RespFrame::BulkString(key)) => Ok(Get { key: key.0 }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
     }
@@ -44,13 +234,14 @@ impl TryFrom<RespArray> for Get {
 impl TryFrom<RespArray> for Set {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["set"], 2)?;
+        validate_command(&value, &["set"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(value)) => Ok(Set {
-                key: String::from_utf8(key.0)?,
+                key: key.0,
                 value,
+                options: parse_set_options(args)?,
             }),
             _ => Err(CommandError::InvalidArgument(
                 "Invalid key or value".to_string(),
@@ -59,82 +250,1395 @@ impl TryFrom<RespArray> for Set {
     }
 }
 
+impl TryFrom<RespArray> for MGet {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["mget"])?;
 
-impl TryFrom<RespArray> for Echo {
+        let keys = extract_key_args(value, 1)?;
+        Ok(MGet { keys })
+    }
+}
+
+impl TryFrom<RespArray> for MSet {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let pairs = extract_pairs(value, "mset")?;
+        Ok(MSet { pairs })
+    }
+}
+
+impl TryFrom<RespArray> for MSetNx {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["echo"], 1)?;
+        let pairs = extract_pairs(value, "msetnx")?;
+        Ok(MSetNx { pairs })
+    }
+}
+
+impl TryFrom<RespArray> for Append {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["append"])?;
 
         let mut args = extract_args(value, 1)?.into_iter();
-        match args.next() {
-            Some(RespFrame::BulkString(msg)) => Ok(Echo {
-                message: String::from_utf8(msg.0)?,
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(value))) => Ok(Append {
+                key: key.0,
+                value: value.0,
             }),
-            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or value".to_string(),
+            )),
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{Backend, RespDecode};
-    use anyhow::Result;
-    use bytes::BytesMut;
+impl TryFrom<RespArray> for Strlen {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["strlen"])?;
 
-    #[test]
-    fn test_get_from_resp_array() -> Result<()> {
-        let mut buf = BytesMut::new();
-        buf.extend_from_slice(b"*2\r\n$3\r\nget\r\n$5\r\nhello\r\n");
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(Strlen { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
 
-        let frame = RespArray::decode(&mut buf)?;
+impl TryFrom<RespArray> for GetRange {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getrange"])?;
 
-        let result: Get = frame.try_into()?;
-        assert_eq!(result.key, "hello");
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(start)),
+                Some(RespFrame::BulkString(end)),
+            ) => Ok(GetRange {
+                key: key.0,
+                start: parse_integer(&start)?,
+                end: parse_integer(&end)?,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key, start or end".to_string(),
+            )),
+        }
+    }
+}
 
-        Ok(())
+impl TryFrom<RespArray> for SetRange {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setrange"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(offset)),
+                Some(RespFrame::BulkString(value)),
+            ) => {
+                let offset = parse_integer(&offset)?;
+                if offset < 0 {
+                    return Err(CommandError::OffsetOutOfRange);
+                }
+                Ok(SetRange {
+                    key: key.0,
+                    offset,
+                    value: value.0,
+                })
+            }
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key, offset or value".to_string(),
+            )),
+        }
     }
+}
 
-    #[test]
-    fn test_set_from_resp_array() -> Result<()> {
-        let mut buf = BytesMut::new();
-        buf.extend_from_slice(b"*3\r\n$3\r\nset\r\n$5\r\nhello\r\n$5\r\nworld\r\n");
+impl TryFrom<RespArray> for GetSet {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getset"])?;
 
-        let frame = RespArray::decode(&mut buf)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(value)) => Ok(GetSet { key: key.0, value }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or value".to_string(),
+            )),
+        }
+    }
+}
 
-        let result: Set = frame.try_into()?;
-        assert_eq!(result.key, "hello");
-        assert_eq!(result.value, RespFrame::BulkString(b"world".into()));
+impl TryFrom<RespArray> for GetDel {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getdel"])?;
 
-        Ok(())
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(GetDel { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
     }
+}
 
-    #[test]
-    fn test_set_get_command() -> Result<()> {
-        let backend = Backend::new();
-        let cmd = Set {
-            key: "hello".to_string(),
-            value: RespFrame::BulkString(b"world".into()),
+// - GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+//   PXAT unix-time-milliseconds | PERSIST]
+impl TryFrom<RespArray> for GetEx {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getex"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => key.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
         };
-        let result = cmd.execute(&backend);
-        assert_eq!(result, RESP_OK.clone());
+        let mut expiry = SetExpiry::Keep;
+        while let Some(arg) = args.next() {
+            let RespFrame::BulkString(arg) = arg else {
+                return Err(CommandError::SyntaxError);
+            };
+            match arg.to_ascii_lowercase().as_slice() {
+                b"persist" if expiry == SetExpiry::Keep => expiry = SetExpiry::Clear,
+                unit @ (b"ex" | b"px" | b"exat" | b"pxat") if expiry == SetExpiry::Keep => {
+                    expiry = parse_expire_option(unit, args.next(), "getex")?;
+                }
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+        Ok(GetEx { key, expiry })
+    }
+}
 
-        let cmd = Get {
-            key: "hello".to_string(),
+impl TryFrom<RespArray> for SetNx {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setnx"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(value)) => Ok(SetNx { key: key.0, value }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or value".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for SetEx {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setex"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(seconds)),
+                Some(value),
+            ) => {
+                let seconds = parse_integer(&seconds)?;
+                if seconds <= 0 || seconds.checked_mul(1000).is_none() {
+                    return Err(CommandError::InvalidExpireTime("setex"));
+                }
+                Ok(SetEx {
+                    key: key.0,
+                    seconds,
+                    value,
+                })
+            }
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key, seconds or value".to_string(),
+            )),
+        }
+    }
+}
+
+// - MSET key value [key value ...]
+fn extract_pairs(
+    value: RespArray,
+    name: &'static str,
+) -> Result<Vec<(Bytes, RespFrame)>, CommandError> {
+    if value.len() < 3 || value.len().is_multiple_of(2) {
+        return Err(CommandError::WrongArity(name));
+    }
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let mut pairs = Vec::with_capacity(args.len() / 2);
+    while let (Some(key), Some(value)) = (args.next(), args.next()) {
+        match key {
+            RespFrame::BulkString(key) => pairs.push((key.0, value)),
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+    Ok(pairs)
+}
+
+// - SET key value [NX | XX] [GET] [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+//   PXAT unix-time-milliseconds | KEEPTTL]
+fn parse_set_options(
+    mut args: impl Iterator<Item = RespFrame>,
+) -> Result<SetOptions, CommandError> {
+    let mut options = SetOptions::default();
+    while let Some(arg) = args.next() {
+        let RespFrame::BulkString(arg) = arg else {
+            return Err(CommandError::SyntaxError);
         };
-        let result = cmd.execute(&backend);
-        assert_eq!(result, RespFrame::BulkString(b"world".into()));
+        match arg.to_ascii_lowercase().as_slice() {
+            b"nx" if options.condition == SetCondition::Always => {
+                options.condition = SetCondition::IfNotExists
+            }
+            b"xx" if options.condition == SetCondition::Always => {
+                options.condition = SetCondition::IfExists
+            }
+            b"get" => options.get = true,
+            b"keepttl" if options.expiry == SetExpiry::Clear => options.expiry = SetExpiry::Keep,
+            unit @ (b"ex" | b"px" | b"exat" | b"pxat") if options.expiry == SetExpiry::Clear => {
+                options.expiry = parse_expire_option(unit, args.next(), "set")?;
+            }
+            _ => return Err(CommandError::SyntaxError),
+        }
+    }
+    Ok(options)
+}
 
-        Ok(())
+
+impl TryFrom<RespArray> for Echo {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["echo"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(msg)) => Ok(Echo {
+                message: String::from_utf8(msg.to_vec())?,
+            }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
     }
+}
 
-    #[test]
-    fn test_echo() -> Result<()> {
-        let mut buf = BytesMut::new();
-        buf.extend_from_slice(b"*2\r\n$4\r\necho\r\n$12\r\nHello World!\r\n");
-        let frame = RespArray::decode(&mut buf)?;
-        let result: Echo = frame.try_into()?;
-        assert_eq!(result.message, "Hello World!");
-        Ok(())
+impl TryFrom<RespArray> for Ping {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() > 2 {
+            return Err(CommandError::WrongArity("ping"));
+        }
+        validate_command(&value, &["ping"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(msg)) => Ok(Ping {
+                message: Some(String::from_utf8(msg.to_vec())?),
+            }),
+            Some(_) => Err(CommandError::InvalidArgument("Invalid message".to_string())),
+            None => Ok(Ping { message: None }),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Incr {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["incr"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(Incr { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Decr {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["decr"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(Decr { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for IncrBy {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["incrby"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(delta))) => Ok(IncrBy {
+                key: key.0,
+                delta: parse_integer(&delta)?,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or increment".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for DecrBy {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["decrby"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(delta))) => Ok(DecrBy {
+                key: key.0,
+                delta: parse_integer(&delta)?,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or decrement".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for IncrByFloat {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["incrbyfloat"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(delta))) => {
+                Ok(IncrByFloat {
+                    key: key.0,
+                    delta: parse_float(&delta)?,
+                })
+            }
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or increment".to_string(),
+            )),
+        }
+    }
+}
+
+// parse the argument of an `EX seconds` or `PX milliseconds` option into a positive ttl in ms
+fn parse_expire_option(
+    unit: &[u8],
+    arg: Option<RespFrame>,
+    name: &'static str,
+) -> Result<SetExpiry, CommandError> {
+    let ttl = match arg {
+        Some(RespFrame::BulkString(ttl)) => parse_integer(&ttl)?,
+        _ => return Err(CommandError::SyntaxError),
+    };
+    let ttl_ms = match unit {
+        b"ex" | b"exat" => ttl.checked_mul(1000),
+        _ => Some(ttl),
+    };
+    match ttl_ms {
+        Some(ttl_ms) if ttl_ms > 0 && unit.ends_with(b"at") => Ok(SetExpiry::At(ttl_ms)),
+        Some(ttl_ms) if ttl_ms > 0 => Ok(SetExpiry::After(ttl_ms)),
+        _ => Err(CommandError::InvalidExpireTime(name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::request_args;
+    use crate::{cmd::Command, Backend, RespDecode, RespEncode, SimpleError};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_get_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$3\r\nget\r\n$5\r\nhello\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+
+        let result: Get = frame.try_into()?;
+        assert_eq!(result.key, "hello");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$3\r\nset\r\n$5\r\nhello\r\n$5\r\nworld\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+
+        let result: Set = frame.try_into()?;
+        assert_eq!(result.key, "hello");
+        assert_eq!(result.value, RespFrame::BulkString(b"world".into()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_get_command() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = Set {
+            key: "hello".into(),
+            value: RespFrame::BulkString(b"world".into()),
+            options: SetOptions::default(),
+        };
+        let result = cmd.execute(&backend).unwrap();
+        assert_eq!(result, RESP_OK.clone());
+
+        let cmd = Get {
+            key: "hello".into(),
+        };
+        let result = cmd.execute(&backend).unwrap();
+        assert_eq!(result, RespFrame::BulkString(b"world".into()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_echo() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$4\r\necho\r\n$12\r\nHello World!\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Echo = frame.try_into()?;
+        assert_eq!(result.message, "Hello World!");
+        Ok(())
+    }
+
+    #[test]
+    fn test_ping() -> Result<()> {
+        let backend = Backend::new();
+        let mut buf = BytesMut::from(&b"*1\r\n$4\r\nping\r\n"[..]);
+        let result: Ping = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(result.execute(&backend)?.encode(), b"+PONG\r\n");
+
+        let mut buf = BytesMut::from(&b"*2\r\n$4\r\nping\r\n$5\r\nhello\r\n"[..]);
+        let result: Ping = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(result.execute(&backend)?.encode(), b"$5\r\nhello\r\n");
+
+        let mut buf = BytesMut::from(&b"*3\r\n$4\r\nping\r\n$1\r\na\r\n$1\r\nb\r\n"[..]);
+        let result: Result<Ping, _> = RespArray::decode(&mut buf)?.try_into();
+        assert!(matches!(result, Err(CommandError::WrongArity("ping"))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_command() -> Result<()> {
+        let mut buf = BytesMut::from(&b"*3\r\n$3\r\nfoo\r\n$3\r\nbar\r\n$4\r\na\r\nb\r\n"[..]);
+        let result = Command::try_from(RespArray::decode(&mut buf)?).unwrap_err();
+        assert_eq!(
+            RespFrame::from(result).encode(),
+            b"-ERR unknown command 'foo', with args beginning with: 'bar' 'a  b' \r\n"
+        );
+
+        // long arguments are cut short
+        let long = "x".repeat(200);
+        let cmd = RespArray::new(vec![
+            BulkString::from("foo").into(),
+            BulkString::from(long.as_str()).into(),
+            BulkString::from("more").into(),
+        ]);
+        let CommandError::UnknownCommand(name, args) = Command::try_from(cmd).unwrap_err() else {
+            panic!("expected an unknown command");
+        };
+        assert_eq!(name, "foo");
+        assert_eq!(args, format!("'{}' ", "x".repeat(127)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_incrby_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nincrby\r\n$7\r\ncounter\r\n$2\r\n-5\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+
+        let result: IncrBy = frame.try_into()?;
+        assert_eq!(result.key, "counter");
+        assert_eq!(result.delta, -5);
+
+        buf.extend_from_slice(b"*3\r\n$6\r\ndecrby\r\n$7\r\ncounter\r\n$3\r\nabc\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<DecrBy, _> = frame.try_into();
+        assert!(matches!(result, Err(CommandError::NotAnInteger)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_incr_decr_commands() {
+        let backend = Backend::new();
+        let cmd = Incr {
+            key: "counter".into(),
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(1));
+
+        let cmd = IncrBy {
+            key: "counter".into(),
+            delta: 10,
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(11));
+
+        let cmd = DecrBy {
+            key: "counter".into(),
+            delta: 20,
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(-9));
+
+        let cmd = Decr {
+            key: "counter".into(),
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(-10));
+
+        // the counter is stored back as a string
+        assert_eq!(
+            backend.get(b"counter"),
+            Some(BulkString::from("-10").into())
+        );
+    }
+
+    #[test]
+    fn test_incr_errors() {
+        let backend = Backend::new();
+        let not_an_integer: RespFrame =
+            SimpleError::new("ERR value is not an integer or out of range").into();
+
+        backend.set("hello".into(), BulkString::from("world").into());
+        let cmd = Incr {
+            key: "hello".into(),
+        };
+        assert_eq!(
+            RespFrame::from(cmd.execute(&backend).unwrap_err()),
+            not_an_integer
+        );
+        assert_eq!(
+            backend.get(b"hello"),
+            Some(BulkString::from("world").into())
+        );
+
+        backend.set("max".into(), BulkString::from(i64::MAX.to_string()).into());
+        let cmd = Incr { key: "max".into() };
+        assert_eq!(
+            RespFrame::from(cmd.execute(&backend).unwrap_err()),
+            not_an_integer
+        );
+
+        let cmd = DecrBy {
+            key: "counter".into(),
+            delta: i64::MIN,
+        };
+        assert_eq!(
+            RespFrame::from(cmd.execute(&backend).unwrap_err()),
+            not_an_integer
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_incr() -> Result<()> {
+        let backend = Backend::new();
+        let tasks = (0..8)
+            .map(|_| {
+                let backend = backend.clone();
+                tokio::spawn(async move {
+                    for _ in 0..1000 {
+                        let cmd = Incr {
+                            key: "counter".into(),
+                        };
+                        cmd.execute(&backend).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await?;
+        }
+        assert_eq!(
+            backend.get(b"counter"),
+            Some(BulkString::from("8000").into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_incrbyfloat_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$11\r\nincrbyfloat\r\n$3\r\nkey\r\n$4\r\n-1.5\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+
+        let result: IncrByFloat = frame.try_into()?;
+        assert_eq!(result.key, "key");
+        assert_eq!(result.delta, -1.5);
+
+        for delta in ["nan", "inf", "-inf", "abc"] {
+            let frame = RespArray::new([
+                BulkString::from("incrbyfloat").into(),
+                BulkString::from("key").into(),
+                BulkString::from(delta).into(),
+            ]);
+            let result: Result<IncrByFloat, _> = frame.try_into();
+            assert!(matches!(result, Err(CommandError::NotAFloat)));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_incrbyfloat_command() {
+        let backend = Backend::new();
+        let incr = |delta: f64| {
+            IncrByFloat {
+                key: "float".into(),
+                delta,
+            }
+            .execute(&backend)
+            .unwrap()
+        };
+
+        assert_eq!(incr(3.0), BulkString::from("3").into());
+        assert_eq!(incr(0.1), BulkString::from("3.1").into());
+        assert_eq!(incr(-5.1), BulkString::from("-2").into());
+        assert_eq!(incr(2.0), BulkString::from("0").into());
+        assert_eq!(incr(-0.0001), BulkString::from("-0.0001").into());
+        assert_eq!(
+            backend.get(b"float"),
+            Some(BulkString::from("-0.0001").into())
+        );
+
+        // large values are never written in exponent notation
+        assert_eq!(
+            incr(5.0e20),
+            BulkString::from("500000000000000000000").into()
+        );
+    }
+
+    #[test]
+    fn test_incrbyfloat_errors() {
+        let backend = Backend::new();
+
+        backend.set("hello".into(), BulkString::from("world").into());
+        let cmd = IncrByFloat {
+            key: "hello".into(),
+            delta: 1.0,
+        };
+        assert_eq!(
+            RespFrame::from(cmd.execute(&backend).unwrap_err()),
+            SimpleError::new("ERR value is not a valid float").into()
+        );
+
+        backend
+            .hset("hash".into(), "field".into(), BulkString::from("1").into())
+            .unwrap();
+        let cmd = IncrByFloat {
+            key: "hash".into(),
+            delta: 1.0,
+        };
+        assert_eq!(
+            RespFrame::from(cmd.execute(&backend).unwrap_err()),
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+
+        backend.set("max".into(), BulkString::from(f64::MAX.to_string()).into());
+        let cmd = IncrByFloat {
+            key: "max".into(),
+            delta: f64::MAX,
+        };
+        assert_eq!(
+            RespFrame::from(cmd.execute(&backend).unwrap_err()),
+            SimpleError::new("ERR increment would produce NaN or Infinity").into()
+        );
+    }
+
+    fn set_cmd(args: &[&str]) -> Result<Set, CommandError> {
+        request_args(&[&["set"], args].concat()).try_into()
+    }
+
+    #[test]
+    fn test_set_options_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$3\r\nset\r\n$5\r\nhello\r\n$5\r\nworld\r\n$2\r\nEX\r\n$2\r\n10\r\n$2\r\nnx\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Set = frame.try_into()?;
+        assert_eq!(
+            result.options,
+            SetOptions {
+                condition: SetCondition::IfNotExists,
+                expiry: SetExpiry::After(10_000),
+                get: false,
+            }
+        );
+
+        let result = set_cmd(&["k", "v", "px", "1500", "xx", "get"])?;
+        assert_eq!(
+            result.options,
+            SetOptions {
+                condition: SetCondition::IfExists,
+                expiry: SetExpiry::After(1500),
+                get: true,
+            }
+        );
+
+        let result = set_cmd(&["k", "v", "KEEPTTL"])?;
+        assert_eq!(result.options.expiry, SetExpiry::Keep);
+        let result = set_cmd(&["k", "v", "exat", "1700000000"])?;
+        assert_eq!(result.options.expiry, SetExpiry::At(1_700_000_000_000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_options_conflicts() {
+        for args in [
+            &["k", "v", "nx", "xx"][..],
+            &["k", "v", "ex", "10", "keepttl"],
+            &["k", "v", "keepttl", "px", "10"],
+            &["k", "v", "ex", "10", "px", "10"],
+            &["k", "v", "ex"],
+            &["k", "v", "foo"],
+        ] {
+            assert!(
+                matches!(set_cmd(args), Err(CommandError::SyntaxError)),
+                "{:?}",
+                args
+            );
+        }
+
+        for args in [&["k", "v", "ex", "0"][..], &["k", "v", "px", "-1"]] {
+            let err = set_cmd(args).unwrap_err();
+            assert!(matches!(err, CommandError::InvalidExpireTime("set")));
+            assert_eq!(
+                RespFrame::from(err),
+                SimpleError::new("ERR invalid expire time in 'set' command").into()
+            );
+        }
+
+        let err = set_cmd(&["k", "v", "ex", "ten"]).unwrap_err();
+        assert!(matches!(err, CommandError::NotAnInteger));
+    }
+
+    #[test]
+    fn test_set_nx_xx() -> Result<()> {
+        let backend = Backend::new();
+        let null = RespFrame::NULL;
+
+        // XX on a missing key does nothing
+        assert_eq!(
+            set_cmd(&["k", "v1", "xx"])?.execute(&backend).unwrap(),
+            null
+        );
+        assert_eq!(backend.get(b"k"), None);
+
+        // NX on a missing key sets it
+        assert_eq!(
+            set_cmd(&["k", "v1", "nx"])?.execute(&backend).unwrap(),
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.get(b"k"), Some(BulkString::from("v1").into()));
+
+        // NX on a present key does nothing
+        assert_eq!(
+            set_cmd(&["k", "v2", "nx"])?.execute(&backend).unwrap(),
+            null
+        );
+        assert_eq!(backend.get(b"k"), Some(BulkString::from("v1").into()));
+
+        // XX on a present key overwrites it
+        assert_eq!(
+            set_cmd(&["k", "v3", "xx"])?.execute(&backend).unwrap(),
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.get(b"k"), Some(BulkString::from("v3").into()));
+
+        // NX treats keys of other types as existing
+        backend.sadd("set", "member").unwrap();
+        assert_eq!(
+            set_cmd(&["set", "v", "nx"])?.execute(&backend).unwrap(),
+            null
+        );
+        assert!(backend.sismember(b"set", b"member"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_get() -> Result<()> {
+        let backend = Backend::new();
+        let null = RespFrame::NULL;
+
+        assert_eq!(
+            set_cmd(&["k", "v1", "get"])?.execute(&backend).unwrap(),
+            null
+        );
+        assert_eq!(
+            set_cmd(&["k", "v2", "get"])?.execute(&backend).unwrap(),
+            BulkString::from("v1").into()
+        );
+        // with NX the old value is returned and nothing is written
+        assert_eq!(
+            set_cmd(&["k", "v3", "nx", "get"])?
+                .execute(&backend)
+                .unwrap(),
+            BulkString::from("v2").into()
+        );
+        assert_eq!(backend.get(b"k"), Some(BulkString::from("v2").into()));
+        // XX on a missing key returns nil
+        assert_eq!(
+            set_cmd(&["missing", "v", "xx", "get"])?
+                .execute(&backend)
+                .unwrap(),
+            null
+        );
+        assert_eq!(backend.get(b"missing"), None);
+
+        // GET on a key of another type is an error and leaves the key alone
+        backend
+            .hset(
+                "hash".into(),
+                "field".into(),
+                BulkString::from("value").into(),
+            )
+            .unwrap();
+        assert_eq!(
+            RespFrame::from(
+                set_cmd(&["hash", "v", "get"])?
+                    .execute(&backend)
+                    .unwrap_err()
+            ),
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+        assert!(backend.hget(b"hash", b"field").is_some());
+
+        // a plain SET converts the key to a string
+        assert_eq!(
+            set_cmd(&["hash", "v"])?.execute(&backend).unwrap(),
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.hget(b"hash", b"field"), None);
+        assert_eq!(backend.get(b"hash"), Some(BulkString::from("v").into()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_expiry() -> Result<()> {
+        let backend = Backend::new();
+
+        set_cmd(&["k", "v", "ex", "100"])?
+            .execute(&backend)
+            .unwrap();
+        let ttl = backend.pttl(b"k");
+        assert!(ttl > 99_000 && ttl <= 100_000);
+
+        set_cmd(&["k", "v", "px", "500"])?
+            .execute(&backend)
+            .unwrap();
+        let ttl = backend.pttl(b"k");
+        assert!(ttl > 0 && ttl <= 500);
+
+        // KEEPTTL retains the previous ttl
+        set_cmd(&["k", "v2", "keepttl"])?.execute(&backend).unwrap();
+        let ttl = backend.pttl(b"k");
+        assert!(ttl > 0 && ttl <= 500);
+
+        // a plain SET clears it
+        set_cmd(&["k", "v3"])?.execute(&backend).unwrap();
+        assert_eq!(backend.pttl(b"k"), -1);
+
+        // an expiry is not applied when NX prevents the write
+        set_cmd(&["k", "v4", "nx", "px", "10"])?
+            .execute(&backend)
+            .unwrap();
+        assert_eq!(backend.pttl(b"k"), -1);
+
+        // expiry together with XX and GET
+        assert_eq!(
+            set_cmd(&["k", "v5", "xx", "get", "px", "10"])?
+                .execute(&backend)
+                .unwrap(),
+            BulkString::from("v3").into()
+        );
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(backend.get(b"k"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mget_mset_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$4\r\nmget\r\n$2\r\nk1\r\n$2\r\nk2\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: MGet = frame.try_into()?;
+        assert_eq!(result.keys, vec!["k1", "k2"]);
+
+        buf.extend_from_slice(
+            b"*5\r\n$4\r\nMSET\r\n$2\r\nk1\r\n$2\r\nv1\r\n$2\r\nk2\r\n$2\r\nv2\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: MSet = frame.try_into()?;
+        assert_eq!(
+            result.pairs,
+            vec![
+                ("k1".into(), BulkString::from("v1").into()),
+                ("k2".into(), BulkString::from("v2").into()),
+            ]
+        );
+
+        buf.extend_from_slice(b"*4\r\n$4\r\nmset\r\n$2\r\nk1\r\n$2\r\nv1\r\n$2\r\nk2\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let err = MSet::try_from(frame).unwrap_err();
+        assert_eq!(
+            RespFrame::from(err),
+            SimpleError::new("ERR wrong number of arguments for 'mset' command").into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mget_mset_commands() {
+        let backend = Backend::new();
+        let cmd = MSet {
+            pairs: vec![
+                ("k1".into(), BulkString::from("v1").into()),
+                ("k2".into(), BulkString::from("v2").into()),
+            ],
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RESP_OK.clone());
+
+        let cmd = MGet {
+            keys: vec!["k2".into(), "missing".into(), "k1".into()],
+        };
+        assert_eq!(
+            cmd.execute(&backend).unwrap(),
+            RespArray::new([
+                BulkString::from("v2").into(),
+                RespFrame::NULL,
+                BulkString::from("v1").into(),
+            ])
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_msetnx_command() {
+        let backend = Backend::new();
+        let cmd = MSetNx {
+            pairs: vec![
+                ("k1".into(), BulkString::from("v1").into()),
+                ("k2".into(), BulkString::from("v2").into()),
+            ],
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(1));
+
+        // k2 exists, so k3 is not written either
+        let cmd = MSetNx {
+            pairs: vec![
+                ("k3".into(), BulkString::from("v3").into()),
+                ("k2".into(), BulkString::from("other").into()),
+            ],
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(0));
+        assert_eq!(backend.get(b"k3"), None);
+        assert_eq!(backend.get(b"k2"), Some(BulkString::from("v2").into()));
+    }
+
+    #[test]
+    fn test_append_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nappend\r\n$3\r\nkey\r\n$3\r\n\xff\x00\xfe\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Append = frame.try_into()?;
+        assert_eq!(result.key, "key");
+        assert_eq!(result.value, b"\xff\x00\xfe"[..]);
+
+        buf.extend_from_slice(b"*2\r\n$6\r\nstrlen\r\n$3\r\nkey\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Strlen = frame.try_into()?;
+        assert_eq!(result.key, "key");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_strlen_commands() {
+        let backend = Backend::new();
+        let strlen = || Strlen { key: "key".into() }.execute(&backend).unwrap();
+        assert_eq!(strlen(), RespFrame::Integer(0));
+
+        // binary payloads which are not valid utf8 are appended byte for byte
+        let cmd = Append {
+            key: "key".into(),
+            value: Bytes::from_static(b"\xffhello"),
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(6));
+        let cmd = Append {
+            key: "key".into(),
+            value: Bytes::from_static(b"\x00\xc3\x28"),
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::Integer(9));
+        assert_eq!(strlen(), RespFrame::Integer(9));
+        assert_eq!(
+            backend.get(b"key"),
+            Some(BulkString::new(b"\xffhello\x00\xc3\x28".to_vec()).into())
+        );
+    }
+
+    #[test]
+    fn test_append_strlen_wrong_type() {
+        let backend = Backend::new();
+        let wrong_type: RespFrame =
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into();
+        backend
+            .hset(
+                "hash".into(),
+                "field".into(),
+                BulkString::from("value").into(),
+            )
+            .unwrap();
+
+        let cmd = Append {
+            key: "hash".into(),
+            value: "value".into(),
+        };
+        assert_eq!(
+            RespFrame::from(cmd.execute(&backend).unwrap_err()),
+            wrong_type
+        );
+        assert_eq!(backend.get(b"hash"), None);
+
+        let cmd = Strlen { key: "hash".into() };
+        assert_eq!(
+            RespFrame::from(cmd.execute(&backend).unwrap_err()),
+            wrong_type
+        );
+    }
+
+    #[test]
+    fn test_getrange_setrange_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$8\r\ngetrange\r\n$3\r\nkey\r\n$1\r\n0\r\n$2\r\n-1\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: GetRange = frame.try_into()?;
+        assert_eq!((result.start, result.end), (0, -1));
+
+        buf.extend_from_slice(
+            b"*4\r\n$8\r\nsetrange\r\n$3\r\nkey\r\n$1\r\n5\r\n$2\r\n\x00\xff\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: SetRange = frame.try_into()?;
+        assert_eq!(result.offset, 5);
+        assert_eq!(result.value, b"\x00\xff"[..]);
+
+        buf.extend_from_slice(b"*4\r\n$8\r\ngetrange\r\n$3\r\nkey\r\n$1\r\na\r\n$2\r\n-1\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<GetRange, _> = frame.try_into();
+        assert!(matches!(result, Err(CommandError::NotAnInteger)));
+
+        buf.extend_from_slice(b"*4\r\n$8\r\nsetrange\r\n$3\r\nkey\r\n$2\r\n-1\r\n$1\r\na\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<SetRange, _> = frame.try_into();
+        assert!(matches!(result, Err(CommandError::OffsetOutOfRange)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_getrange_command() {
+        let backend = Backend::new();
+        // "h\u{e9}llo w\u{f6}rld" is 14 bytes but only 11 chars
+        backend.set(
+            "key".into(),
+            BulkString::from("h\u{e9}llo w\u{f6}rld").into(),
+        );
+        let getrange = |start, end| {
+            GetRange {
+                key: "key".into(),
+                start,
+                end,
+            }
+            .execute(&backend)
+            .unwrap()
+        };
+
+        assert_eq!(
+            getrange(0, 2),
+            BulkString::new(b"h\xc3\xa9".to_vec()).into()
+        );
+        // the range splits the two bytes of a multi-byte character
+        assert_eq!(getrange(0, 1), BulkString::new(b"h\xc3".to_vec()).into());
+        assert_eq!(getrange(-5, -1), BulkString::from("\u{f6}rld").into());
+        assert_eq!(
+            getrange(0, -1),
+            BulkString::from("h\u{e9}llo w\u{f6}rld").into()
+        );
+        // out of range offsets are clamped
+        assert_eq!(getrange(-100, 100), getrange(0, -1));
+        assert_eq!(getrange(10, 100), BulkString::from("rld").into());
+        // inverted ranges are empty
+        assert_eq!(getrange(5, 2), BulkString::new(Vec::new()).into());
+        assert_eq!(getrange(-1, -5), BulkString::new(Vec::new()).into());
+
+        let cmd = GetRange {
+            key: "missing".into(),
+            start: 0,
+            end: -1,
+        };
+        assert_eq!(
+            cmd.execute(&backend).unwrap(),
+            BulkString::new(Vec::new()).into()
+        );
+    }
+
+    #[test]
+    fn test_setrange_command() {
+        let backend = Backend::new();
+        let setrange = |key: &[u8], offset, value: &[u8]| {
+            SetRange {
+                key: Bytes::copy_from_slice(key),
+                offset,
+                value: Bytes::copy_from_slice(value),
+            }
+            .execute(&backend)
+            .unwrap_or_else(RespFrame::from)
+        };
+
+        backend.set("key".into(), BulkString::from("Hello World").into());
+        assert_eq!(setrange(b"key", 6, b"Redis"), RespFrame::Integer(11));
+        assert_eq!(
+            backend.get(b"key"),
+            Some(BulkString::from("Hello Redis").into())
+        );
+
+        // past the end of a missing key, the gap is zero padded
+        assert_eq!(setrange(b"padded", 3, b"\xff\xfe"), RespFrame::Integer(5));
+        assert_eq!(
+            backend.get(b"padded"),
+            Some(BulkString::new(b"\x00\x00\x00\xff\xfe".to_vec()).into())
+        );
+
+        // an empty value does not create the key
+        assert_eq!(setrange(b"empty", 10, b""), RespFrame::Integer(0));
+        assert_eq!(backend.get(b"empty"), None);
+
+        assert_eq!(
+            setrange(b"key", 512 * 1024 * 1024, b"x"),
+            SimpleError::new("ERR string exceeds maximum allowed size (proto-max-bulk-len)").into()
+        );
+    }
+
+    fn getex_cmd(args: &[&str]) -> Result<GetEx, CommandError> {
+        request_args(&[&["getex"], args].concat()).try_into()
+    }
+
+    #[test]
+    fn test_getset_getdel_getex_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\ngetset\r\n$3\r\nkey\r\n$5\r\nvalue\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: GetSet = frame.try_into()?;
+        assert_eq!(result.key, "key");
+        assert_eq!(result.value, BulkString::from("value").into());
+
+        buf.extend_from_slice(b"*2\r\n$6\r\ngetdel\r\n$3\r\nkey\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: GetDel = frame.try_into()?;
+        assert_eq!(result.key, "key");
+
+        assert_eq!(getex_cmd(&["key"])?.expiry, SetExpiry::Keep);
+        assert_eq!(
+            getex_cmd(&["key", "EX", "10"])?.expiry,
+            SetExpiry::After(10_000)
+        );
+        assert_eq!(
+            getex_cmd(&["key", "px", "10"])?.expiry,
+            SetExpiry::After(10)
+        );
+        assert_eq!(getex_cmd(&["key", "persist"])?.expiry, SetExpiry::Clear);
+        assert_eq!(
+            getex_cmd(&["key", "PXAT", "1700000000000"])?.expiry,
+            SetExpiry::At(1_700_000_000_000)
+        );
+        assert!(matches!(
+            getex_cmd(&["key", "ex", "10", "persist"]),
+            Err(CommandError::SyntaxError)
+        ));
+        assert!(matches!(
+            getex_cmd(&["key", "px", "0"]),
+            Err(CommandError::InvalidExpireTime("getex"))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_getset_getdel_commands() {
+        let backend = Backend::new();
+        let cmd = GetSet {
+            key: "key".into(),
+            value: BulkString::from("v1").into(),
+        };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::NULL);
+
+        backend.expire(b"key", 100_000);
+        let cmd = GetSet {
+            key: "key".into(),
+            value: BulkString::from("v2").into(),
+        };
+        assert_eq!(
+            cmd.execute(&backend).unwrap(),
+            BulkString::from("v1").into()
+        );
+        assert_eq!(backend.pttl(b"key"), -1);
+
+        let cmd = GetDel { key: "key".into() };
+        assert_eq!(
+            cmd.execute(&backend).unwrap(),
+            BulkString::from("v2").into()
+        );
+        assert_eq!(backend.exists(&["key".into()]), 0);
+
+        let cmd = GetDel { key: "key".into() };
+        assert_eq!(cmd.execute(&backend).unwrap(), RespFrame::NULL);
+    }
+
+    #[test]
+    fn test_getex_command() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            getex_cmd(&["key", "ex", "10"])?.execute(&backend).unwrap(),
+            RespFrame::NULL
+        );
+        assert_eq!(backend.pttl(b"key"), -2);
+
+        backend.set("key".into(), BulkString::from("value").into());
+        assert_eq!(
+            getex_cmd(&["key", "ex", "100"])?.execute(&backend).unwrap(),
+            BulkString::from("value").into()
+        );
+        let ttl = backend.pttl(b"key");
+        assert!(ttl > 99_000 && ttl <= 100_000);
+
+        // without options the ttl is left alone
+        getex_cmd(&["key"])?.execute(&backend).unwrap();
+        assert!(backend.pttl(b"key") > 0);
+
+        getex_cmd(&["key", "persist"])?.execute(&backend).unwrap();
+        assert_eq!(backend.pttl(b"key"), -1);
+
+        getex_cmd(&["key", "px", "10"])?.execute(&backend).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(backend.get(b"key"), None);
+
+        // a unix time in the past deletes the key once it is read
+        backend.set("key".into(), BulkString::from("value").into());
+        assert_eq!(
+            getex_cmd(&["key", "pxat", "1"])?.execute(&backend).unwrap(),
+            BulkString::from("value").into()
+        );
+        assert_eq!(backend.pttl(b"key"), -2);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_getset() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("key".into(), BulkString::from("initial").into());
+
+        let tasks = (0..8)
+            .map(|task| {
+                let backend = backend.clone();
+                tokio::spawn(async move {
+                    let mut replaced = Vec::new();
+                    for i in 0..500 {
+                        let cmd = GetSet {
+                            key: "key".into(),
+                            value: BulkString::from(format!("{}-{}", task, i)).into(),
+                        };
+                        replaced.push(cmd.execute(&backend).unwrap());
+                    }
+                    replaced
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut seen = Vec::new();
+        for task in tasks {
+            seen.extend(task.await?);
+        }
+        seen.push(backend.get(b"key").unwrap());
+
+        // every value written is either replaced exactly once or is the final value
+        let mut seen = seen
+            .into_iter()
+            .map(|frame| match frame {
+                RespFrame::BulkString(s) => String::from_utf8(s.to_vec()).unwrap(),
+                frame => panic!("unexpected frame: {:?}", frame),
+            })
+            .collect::<Vec<_>>();
+        seen.sort();
+        let mut expected = (0..8)
+            .flat_map(|task| (0..500).map(move |i| format!("{}-{}", task, i)))
+            .chain(std::iter::once("initial".into()))
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(seen, expected);
+
+        Ok(())
+    }
+
+    // decode a raw request, dispatch it and encode the reply
+    fn round_trip(backend: &Backend, request: &[u8]) -> Vec<u8> {
+        let mut buf = BytesMut::from(request);
+        let frame = RespFrame::decode(&mut buf).unwrap();
+        match Command::try_from(frame) {
+            Ok(cmd) => cmd.execute(backend).unwrap_or_else(RespFrame::from),
+            Err(e) => e.into(),
+        }
+        .encode()
+    }
+
+    #[test]
+    fn test_setnx_round_trip() {
+        let backend = Backend::new();
+        let request = b"*3\r\n$5\r\nsetnx\r\n$3\r\nkey\r\n$2\r\nv1\r\n";
+        assert_eq!(round_trip(&backend, request), b":1\r\n");
+
+        let request = b"*3\r\n$5\r\nsetnx\r\n$3\r\nkey\r\n$2\r\nv2\r\n";
+        assert_eq!(round_trip(&backend, request), b":0\r\n");
+        assert_eq!(backend.get(b"key"), Some(BulkString::from("v1").into()));
+    }
+
+    #[test]
+    fn test_setex_round_trip() {
+        let backend = Backend::new();
+        let request = b"*4\r\n$5\r\nsetex\r\n$3\r\nkey\r\n$3\r\n100\r\n$5\r\nvalue\r\n";
+        assert_eq!(round_trip(&backend, request), b"+OK\r\n");
+        assert_eq!(backend.get(b"key"), Some(BulkString::from("value").into()));
+        let ttl = backend.pttl(b"key");
+        assert!(ttl > 99_000 && ttl <= 100_000);
+
+        for seconds in [&b"$1\r\n0"[..], b"$2\r\n-5"] {
+            let mut request = b"*4\r\n$5\r\nsetex\r\n$3\r\nkey\r\n".to_vec();
+            request.extend_from_slice(seconds);
+            request.extend_from_slice(b"\r\n$5\r\nvalue\r\n");
+            assert_eq!(
+                round_trip(&backend, &request),
+                b"-ERR invalid expire time in 'setex' command\r\n"
+            );
+        }
     }
 }
@@ -1,23 +1,257 @@
+mod bitmap;
+mod command;
+mod connection;
+mod debug;
+mod geo;
 mod hmap;
-mod map;
 mod hset;
+mod hyperloglog;
+mod keyspace;
+mod list;
+#[cfg(feature = "scripting")]
+mod lua;
+mod map;
+mod propagate;
+mod pubsub;
+mod replication;
+mod scripting;
+mod server;
+mod stream;
+mod transaction;
+mod zset;
 
-use crate::{Backend, RespArray, RespError, RespFrame, SimpleString};
+use crate::{
+    Aggregate, Backend, BitFieldOp, BitOperation, BitUnit, ExpireCondition, GeoOrigin, GeoShape,
+    GeoUnit, KillFilter, LPosOptions, LexBound, Limit, ListEnd, RespArray, RespError, RespFrame,
+    ScoreBound, SetExpiry, SetOptions, SimpleError, SimpleString, StreamFields, StreamId,
+    StreamTrim, ZAddOptions, ZRangeBy,
+};
+use bytes::Bytes;
+use command::CommandSpec;
 use enum_dispatch::enum_dispatch;
 use lazy_static::lazy_static;
+use std::time::Duration;
 use thiserror::Error;
+pub use transaction::{Transaction, WatchedKeys};
 
 // you could also use once_cell instead of lazy_static
 lazy_static! {
     static ref RESP_OK: RespFrame = SimpleString::new("OK").into();
 }
 
+// what a connection subscribed to a channel may still send
+const SUBSCRIBER_COMMANDS: [&str; 7] = [
+    "subscribe",
+    "unsubscribe",
+    "psubscribe",
+    "punsubscribe",
+    "ping",
+    "quit",
+    "reset",
+];
+
 #[derive(Error, Debug)]
 pub enum CommandError {
     #[error("Invalid command: {0}")]
     InvalidCommand(String),
     #[error("Invalid argument: {0}")]
     InvalidArgument(String),
+    #[error("unknown command '{0}', with args beginning with: {1}")]
+    UnknownCommand(String, String),
+    #[error("DB index is out of range")]
+    DbIndexOutOfRange,
+    #[error("Unknown option or number of arguments for CONFIG SET - '{0}'")]
+    UnknownConfig(String),
+    #[error("CONFIG SET failed (possibly related to argument '{0}') - can't set immutable config")]
+    ImmutableConfig(String),
+    #[error("CONFIG SET failed (possibly related to argument '{0}') - {1}")]
+    InvalidConfig(String, &'static str),
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
+    OutOfMemory,
+    #[error("NOAUTH Authentication required.")]
+    NoAuth,
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+    #[error("NOPROTO unsupported protocol version")]
+    NoProto,
+    #[error("Protocol version is not an integer or out of range")]
+    InvalidProtocolVersion,
+    #[error("Syntax error in HELLO option '{0}'")]
+    HelloOption(String),
+    #[error("AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?")]
+    NoPassword,
+    #[error("count should be greater than or equal to -1")]
+    SlowLogCount,
+    #[error("No such client")]
+    NoSuchClient,
+    #[error("Client names cannot contain spaces, newlines or special characters.")]
+    InvalidClientName,
+    #[error("client-id should be greater than 0")]
+    InvalidClientId,
+    #[error("this command needs a client connection")]
+    NoConnection,
+    #[error("MULTI calls can not be nested")]
+    NestedMulti,
+    #[error("EXEC without MULTI")]
+    ExecWithoutMulti,
+    #[error("DISCARD without MULTI")]
+    DiscardWithoutMulti,
+    #[error("EXECABORT Transaction discarded because of previous errors.")]
+    ExecAbort,
+    #[error("WATCH inside MULTI is not allowed")]
+    WatchInsideMulti,
+    #[error("Can't execute '{0}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context")]
+    SubscriberOnly(&'static str),
+    #[error("NOSCRIPT No matching script. Please use EVAL.")]
+    NoScript,
+    #[error("Number of keys can't be negative")]
+    NegativeNumKeys,
+    #[error("This Redis command is not allowed from script")]
+    NotAllowedFromScript,
+    #[error("Lua redis lib command arguments must be strings or integers")]
+    ScriptArgument,
+    #[error("Please specify at least one argument for this redis lib call")]
+    ScriptNoCommand,
+    #[error("{0}")]
+    Script(String),
+    #[error("Script ran for longer than busy-reply-threshold and was aborted")]
+    ScriptTimedOut,
+    #[error("this server was built without the scripting feature")]
+    ScriptingDisabled,
+    #[error("unknown subcommand '{0}'. Try {1} HELP.")]
+    UnknownSubcommand(String, &'static str),
+    #[error("value is not an integer or out of range")]
+    NotAnInteger,
+    #[error("value is not a valid float")]
+    NotAFloat,
+    #[error("increment would produce NaN or Infinity")]
+    NanOrInfinity,
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("WRONGTYPE Key is not a valid HyperLogLog string value.")]
+    InvalidHyperLogLog,
+    #[error("syntax error")]
+    SyntaxError,
+    #[error("invalid expire time in '{0}' command")]
+    InvalidExpireTime(&'static str),
+    #[error("wrong number of arguments for '{0}' command")]
+    WrongArity(&'static str),
+    #[error("offset is out of range")]
+    OffsetOutOfRange,
+    #[error("string exceeds maximum allowed size (proto-max-bulk-len)")]
+    StringTooLong,
+    #[error("bit offset is not an integer or out of range")]
+    BitOffsetOutOfRange,
+    #[error("bit is not an integer or out of range")]
+    BitOutOfRange,
+    #[error("The bit argument must be 1 or 0.")]
+    BitNotBinary,
+    #[error("The ID specified in XADD is equal or smaller than the target stream top item")]
+    StreamIdTooSmall,
+    #[error("The ID specified in XADD must be greater than 0-0")]
+    StreamIdZero,
+    #[error("Invalid stream ID specified as stream command argument")]
+    InvalidStreamId,
+    #[error(
+        "Unbalanced '{0}' list of streams: for each stream key an ID or '$' must be specified."
+    )]
+    UnbalancedStreams(&'static str),
+    #[error("The MAXLEN argument must be >= 0.")]
+    NegativeStreamMaxLen,
+    #[error("The LIMIT argument must be >= 0.")]
+    NegativeTrimLimit,
+    #[error("syntax error, LIMIT cannot be used without the special ~ option")]
+    TrimLimitWithoutApprox,
+    #[error("invalid longitude,latitude pair {0:.6},{1:.6}")]
+    InvalidLonLat(f64, f64),
+    #[error("unsupported unit provided. please use M, KM, FT, MI")]
+    InvalidGeoUnit,
+    #[error("could not decode requested zset member")]
+    GeoMemberNotFound,
+    #[error("{0} cannot be negative")]
+    NegativeGeoSize(&'static str),
+    #[error("exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH")]
+    GeoSearchOrigin,
+    #[error("exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH")]
+    GeoSearchShape,
+    #[error("BUSYGROUP Consumer Group name already exists")]
+    BusyGroup,
+    #[error("BUSYKEY Target key name already exists.")]
+    BusyKey,
+    #[error("Bad data format")]
+    BadDumpPayload,
+    #[error("Invalid TTL value, must be >= 0")]
+    NegativeTtl,
+    #[error("NOGROUP No such key '{0}' or consumer group '{1}'")]
+    NoGroup(String, String),
+    #[error("The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.")]
+    XGroupNoKey,
+    #[error("BITOP NOT must be called with a single source key.")]
+    BitOpNotSingleKey,
+    #[error("Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.")]
+    InvalidBitFieldType,
+    #[error("Invalid OVERFLOW type specified")]
+    InvalidOverflow,
+    #[error("hash value is not an integer")]
+    HashNotAnInteger,
+    #[error("hash value is not a float")]
+    HashNotAFloat,
+    #[error("increment or decrement would overflow")]
+    Overflow,
+    #[error("value is out of range, must be positive")]
+    NotPositive,
+    #[error("numkeys should be greater than 0")]
+    NumKeysNotPositive,
+    #[error("Number of keys can't be greater than number of args")]
+    TooManyKeys,
+    #[error("LIMIT can't be negative")]
+    NegativeLimit,
+    #[error("index out of range")]
+    IndexOutOfRange,
+    #[error("RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list")]
+    ZeroRank,
+    #[error("COUNT can't be negative")]
+    NegativeCount,
+    #[error("MAXLEN can't be negative")]
+    NegativeMaxLen,
+    #[error("resulting score is not a number (NaN)")]
+    NaNScore,
+    #[error("at least 1 input key is needed for '{0}' command")]
+    NoInputKeys(&'static str),
+    #[error("weight value is not a float")]
+    InvalidWeight,
+    #[error("min or max not valid string range item")]
+    InvalidLexBound,
+    #[error("min or max is not a float")]
+    InvalidScoreBound,
+    #[error("timeout is not a float or out of range")]
+    InvalidTimeout,
+    #[error("timeout is negative")]
+    NegativeTimeout,
+    #[error("Background save already in progress")]
+    BgSaveInProgress,
+    #[error("Background append only file rewriting already in progress")]
+    AofRewriteInProgress,
+    #[error("saving failed: {0}")]
+    SaveFailed(String),
+    #[error("FAILOVER requires connected replicas.")]
+    FailoverNoReplicas,
+    #[error("No failover in progress.")]
+    NoFailover,
+    #[error("READONLY You can't write against a read only replica.")]
+    ReadOnly,
+    #[error("Replication can't start inside a transaction")]
+    PsyncInsideMulti,
+    #[error("invalid cursor")]
+    InvalidCursor,
+    #[error("no such key")]
+    NoSuchKey,
+    #[error("source and destination objects are the same")]
+    SameObject,
+    #[error("{0} options at the same time are not compatible")]
+    IncompatibleOptions(&'static str),
+    #[error("Unsupported option {0}")]
+    UnsupportedOption(String),
 
     #[error("{0}")]
     RespError(#[from] RespError),
@@ -25,9 +259,30 @@ pub enum CommandError {
     Utf8Error(#[from] std::string::FromUtf8Error),
 }
 
+// errors are sent back to the client as `-ERR <message>`, unless they carry their own prefix
+impl From<CommandError> for RespFrame {
+    fn from(e: CommandError) -> Self {
+        match e {
+            CommandError::WrongType
+            | CommandError::InvalidHyperLogLog
+            | CommandError::BusyGroup
+            | CommandError::BusyKey
+            | CommandError::ReadOnly
+            | CommandError::OutOfMemory
+            | CommandError::NoAuth
+            | CommandError::WrongPass
+            | CommandError::NoProto
+            | CommandError::ExecAbort
+            | CommandError::NoScript
+            | CommandError::NoGroup(..) => SimpleError::new(e.to_string()).into(),
+            _ => SimpleError::new(format!("ERR {}", e)).into(),
+        }
+    }
+}
+
 #[enum_dispatch]
 pub trait CommandExecutor {
-    fn execute(self, backend: &Backend) -> RespFrame;
+    fn execute(self, backend: &Backend) -> Result<RespFrame, CommandError>;
 }
 
 #[enum_dispatch(CommandExecutor)]
@@ -36,31 +291,220 @@ pub enum Command {
     Get(Get),
     Set(Set),
     Echo(Echo),
+    Ping(Ping),
+    CommandList(CommandList),
+    CommandCount(CommandCount),
+    CommandInfo(CommandInfo),
+    CommandDocs(CommandDocs),
+    Info(Info),
+    FlushDb(FlushDb),
+    FlushAll(FlushAll),
+    Select(Select),
+    ConfigGet(ConfigGet),
+    ConfigSet(ConfigSet),
+    ConfigResetStat(ConfigResetStat),
+    Shutdown(Shutdown),
+    Time(Time),
+    SlowLogGet(SlowLogGet),
+    SlowLogLen(SlowLogLen),
+    SlowLogReset(SlowLogReset),
+    LastSave(LastSave),
+    Save(Save),
+    BgSave(BgSave),
+    BgRewriteAof(BgRewriteAof),
+    Wait(Wait),
+    ReplicaOf(ReplicaOf),
+    ReplConf(ReplConf),
+    Psync(Psync),
+    Failover(Failover),
+    Lolwut(Lolwut),
+    DebugHelp(DebugHelp),
+    DebugSleep(DebugSleep),
+    DebugObject(DebugObject),
+    DebugSetActiveExpire(DebugSetActiveExpire),
+    DebugNoop(DebugNoop),
+    Auth(Auth),
+    Hello(Hello),
+    Quit(Quit),
+    Monitor(Monitor),
+    Reset(Reset),
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
+    Watch(Watch),
+    Unwatch(Unwatch),
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    Publish(Publish),
+    PSubscribe(PSubscribe),
+    PUnsubscribe(PUnsubscribe),
+    PubSubChannels(PubSubChannels),
+    PubSubNumSub(PubSubNumSub),
+    PubSubNumPat(PubSubNumPat),
+    Eval(Eval),
+    EvalSha(EvalSha),
+    ScriptLoad(ScriptLoad),
+    ScriptExists(ScriptExists),
+    ScriptFlush(ScriptFlush),
+    ClientId(ClientId),
+    ClientSetName(ClientSetName),
+    ClientGetName(ClientGetName),
+    ClientList(ClientList),
+    ClientKill(ClientKill),
+    SwapDb(SwapDb),
     HGet(HGet),
     HSet(HSet),
     HGetAll(HGetAll),
     HMGet(HMGet),
+    HDel(HDel),
+    HExists(HExists),
+    HLen(HLen),
+    HKeys(HKeys),
+    HMSet(HMSet),
+    HIncrBy(HIncrBy),
+    HIncrByFloat(HIncrByFloat),
+    HSetNx(HSetNx),
+    HRandField(HRandField),
+    HScan(HScan),
+    HStrLen(HStrLen),
+    HVals(HVals),
     SAdd(SAdd),
     SIsMember(SIsMember),
-    // unrecognized command
-    Unrecognized(Unrecognized),
+    SScan(SScan),
+    SRem(SRem),
+    SCard(SCard),
+    SMembers(SMembers),
+    SPop(SPop),
+    SRandMember(SRandMember),
+    SMove(SMove),
+    SInterCard(SInterCard),
+    SMIsMember(SMIsMember),
+    SInter(SInter),
+    SInterStore(SInterStore),
+    SUnion(SUnion),
+    SUnionStore(SUnionStore),
+    SDiff(SDiff),
+    SDiffStore(SDiffStore),
+    Del(Del),
+    Exists(Exists),
+    Expire(Expire),
+    Pexpire(Pexpire),
+    ExpireAt(ExpireAt),
+    PexpireAt(PexpireAt),
+    Ttl(Ttl),
+    Pttl(Pttl),
+    Persist(Persist),
+    Incr(Incr),
+    Decr(Decr),
+    IncrBy(IncrBy),
+    DecrBy(DecrBy),
+    IncrByFloat(IncrByFloat),
+    MGet(MGet),
+    MSet(MSet),
+    MSetNx(MSetNx),
+    Append(Append),
+    Strlen(Strlen),
+    GetRange(GetRange),
+    SetRange(SetRange),
+    GetSet(GetSet),
+    GetDel(GetDel),
+    GetEx(GetEx),
+    SetNx(SetNx),
+    SetEx(SetEx),
+    Type(Type),
+    Keys(Keys),
+    Scan(Scan),
+    DbSize(DbSize),
+    RandomKey(RandomKey),
+    Rename(Rename),
+    RenameNx(RenameNx),
+    Copy(Copy),
+    Dump(Dump),
+    Restore(Restore),
+    Touch(Touch),
+    Unlink(Unlink),
+    MemoryUsage(MemoryUsage),
+    ObjectEncoding(ObjectEncoding),
+    LPush(LPush),
+    RPush(RPush),
+    LPushX(LPushX),
+    RPushX(RPushX),
+    LPop(LPop),
+    RPop(RPop),
+    LLen(LLen),
+    LRange(LRange),
+    LIndex(LIndex),
+    LSet(LSet),
+    LRem(LRem),
+    LTrim(LTrim),
+    LInsert(LInsert),
+    LPos(LPos),
+    LMove(LMove),
+    RPopLPush(RPopLPush),
+    BLPop(BLPop),
+    BRPop(BRPop),
+    ZAdd(ZAdd),
+    ZScore(ZScore),
+    ZRange(ZRange),
+    ZRevRange(ZRevRange),
+    ZRangeByScore(ZRangeByScore),
+    ZCount(ZCount),
+    ZRem(ZRem),
+    ZCard(ZCard),
+    ZIncrBy(ZIncrBy),
+    ZRank(ZRank),
+    ZRevRank(ZRevRank),
+    ZPopMin(ZPopMin),
+    ZPopMax(ZPopMax),
+    ZRangeByLex(ZRangeByLex),
+    ZLexCount(ZLexCount),
+    ZUnionStore(ZUnionStore),
+    ZInterStore(ZInterStore),
+    ZScan(ZScan),
+    ZMScore(ZMScore),
+    ZRangeStore(ZRangeStore),
+    ZRandMember(ZRandMember),
+    SetBit(SetBit),
+    GetBit(GetBit),
+    BitCount(BitCount),
+    BitOp(BitOp),
+    BitPos(BitPos),
+    BitField(BitField),
+    PfAdd(PfAdd),
+    PfCount(PfCount),
+    PfMerge(PfMerge),
+    GeoAdd(GeoAdd),
+    GeoPos(GeoPos),
+    GeoDist(GeoDist),
+    GeoSearch(GeoSearch),
+    XAdd(XAdd),
+    XLen(XLen),
+    XRange(XRange),
+    XRead(XRead),
+    XDel(XDel),
+    XTrim(XTrim),
+    XGroupCreate(XGroupCreate),
+    XReadGroup(XReadGroup),
+    XAck(XAck),
+    XPending(XPending),
 }
 
 #[derive(Debug)]
 pub struct Get {
-    key: String,
+    key: Bytes,
 }
 
 #[derive(Debug)]
 pub struct Set {
-    key: String,
+    key: Bytes,
     value: RespFrame,
+    options: SetOptions,
 }
 
 #[derive(Debug)]
 pub struct HGet {
-    key: String,
-    field: String,
+    key: Bytes,
+    field: Bytes,
 }
 
 #[derive(Debug)]
@@ -68,123 +512,1742 @@ pub struct Echo {
     message: String,
 }
 
+#[derive(Debug)]
+pub struct Ping {
+    message: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CommandList;
+
+#[derive(Debug)]
+pub struct CommandCount;
+
+#[derive(Debug)]
+pub struct CommandInfo {
+    names: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct CommandDocs {
+    names: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Info {
+    sections: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct FlushDb {
+    // `ASYNC`: free the contents in the background
+    lazy: bool,
+}
+
+#[derive(Debug)]
+pub struct FlushAll {
+    lazy: bool,
+}
+
+#[derive(Debug)]
+pub struct ConfigGet {
+    patterns: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ConfigSet {
+    pairs: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+pub struct ConfigResetStat;
+
+#[derive(Debug)]
+pub struct Auth {
+    username: Option<String>,
+    password: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Hello {
+    protocol: Option<u8>,
+    // the username and the password
+    auth: Option<(String, String)>,
+    name: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Quit;
+
+#[derive(Debug)]
+pub struct Monitor;
+
+#[derive(Debug)]
+pub struct Reset;
+
+#[derive(Debug)]
+pub struct Multi;
+
+#[derive(Debug)]
+pub struct Exec;
+
+#[derive(Debug)]
+pub struct Discard;
+
+#[derive(Debug)]
+pub struct Watch {
+    pub(crate) keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct Unwatch;
+
+#[derive(Debug)]
+pub struct Subscribe {
+    channels: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct Unsubscribe {
+    channels: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct Publish {
+    channel: Bytes,
+    message: Bytes,
+}
+
+#[derive(Debug)]
+pub struct PSubscribe {
+    patterns: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct PUnsubscribe {
+    patterns: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct PubSubChannels {
+    pattern: Option<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct PubSubNumSub {
+    channels: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct PubSubNumPat;
+
+#[derive(Debug)]
+pub struct Eval {
+    script: String,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct EvalSha {
+    sha: String,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct ScriptLoad {
+    script: String,
+}
+
+#[derive(Debug)]
+pub struct ScriptExists {
+    shas: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ScriptFlush;
+
+#[derive(Debug)]
+pub struct ClientId;
+
+#[derive(Debug)]
+pub struct ClientSetName {
+    name: String,
+}
+
+#[derive(Debug)]
+pub struct ClientGetName;
+
+#[derive(Debug)]
+pub struct ClientList;
+
+#[derive(Debug)]
+pub struct ClientKill {
+    filter: KillFilter,
+    // `CLIENT KILL addr`, which replies OK or an error instead of a count
+    legacy: bool,
+}
+
+#[derive(Debug)]
+pub struct Shutdown {
+    // `SAVE` or `NOSAVE`, neither saves if there are save points
+    save: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct DebugHelp;
+
+#[derive(Debug)]
+pub struct DebugSleep {
+    duration: Duration,
+}
+
+#[derive(Debug)]
+pub struct DebugObject {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct DebugSetActiveExpire {
+    enabled: bool,
+}
+
+// the subcommands test suites send whose effect doesn't apply here, they just succeed
+#[derive(Debug)]
+pub struct DebugNoop;
+
+#[derive(Debug)]
+pub struct SlowLogGet {
+    // all of them if `None`
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct SlowLogLen;
+
+#[derive(Debug)]
+pub struct SlowLogReset;
+
+#[derive(Debug)]
+pub struct Time;
+
+#[derive(Debug)]
+pub struct LastSave;
+
+#[derive(Debug)]
+pub struct Save;
+
+#[derive(Debug)]
+pub struct BgSave;
+
+#[derive(Debug)]
+pub struct BgRewriteAof;
+
+#[derive(Debug)]
+pub struct Wait {
+    replicas: usize,
+    // how long to wait for them, forever if zero
+    timeout: Duration,
+}
+
+#[derive(Debug)]
+pub struct ReplicaOf {
+    // `None` for `REPLICAOF NO ONE`
+    primary: Option<(String, u16)>,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplConf {
+    pub(crate) listening_port: Option<u16>,
+    pub(crate) ack: Option<u64>,
+    pub(crate) getack: bool,
+}
+
+#[derive(Debug)]
+pub struct Psync;
+
+#[derive(Debug)]
+pub struct Failover {
+    abort: bool,
+}
+
+#[derive(Debug)]
+pub struct Lolwut;
+
+#[derive(Debug)]
+pub struct Select {
+    pub(crate) index: i64,
+}
+
+#[derive(Debug)]
+pub struct SwapDb {
+    a: i64,
+    b: i64,
+}
+
 #[derive(Debug)]
 pub struct HSet {
-    key: String,
-    field: String,
-    value: RespFrame,
+    key: Bytes,
+    fields: Vec<(Bytes, RespFrame)>,
+}
+
+#[derive(Debug)]
+pub struct HMSet {
+    key: Bytes,
+    fields: Vec<(Bytes, RespFrame)>,
 }
 
 #[derive(Debug)]
 pub struct HGetAll {
-    key: String,
-    sort: bool,
+    key: Bytes,
 }
 
 #[derive(Debug)]
 pub struct HMGet {
-    hash: String,
-    fields: Vec<String>,
+    hash: Bytes,
+    fields: Vec<Bytes>,
 }
 
 #[derive(Debug)]
 pub struct SAdd {
-    key: String,
-    members: Vec<String>,
+    key: Bytes,
+    members: Vec<Bytes>,
 }
 
 #[derive(Debug)]
 pub struct SIsMember {
-    key: String,
-    member: String,
+    key: Bytes,
+    member: Bytes,
 }
 
 #[derive(Debug)]
-pub struct Unrecognized;
+pub struct Del {
+    keys: Vec<Bytes>,
+}
 
-impl TryFrom<RespFrame> for Command {
-    type Error = CommandError;
-    fn try_from(v: RespFrame) -> Result<Self, Self::Error> {
-        match v {
-            RespFrame::Array(array) => array.try_into(),
-            _ => Err(CommandError::InvalidCommand(
-                "Command must be an Array".to_string(),
-            )),
-        }
-    }
+#[derive(Debug)]
+pub struct Exists {
+    keys: Vec<Bytes>,
 }
 
-impl TryFrom<RespArray> for Command {
-    type Error = CommandError;
-    fn try_from(v: RespArray) -> Result<Self, Self::Error> {
-        match v.first() {
-            Some(RespFrame::BulkString(ref cmd)) => match cmd.as_ref() {
-                b"get" => Ok(Get::try_from(v)?.into()),
-                b"set" => Ok(Set::try_from(v)?.into()),
-                b"echo" => Ok(Echo::try_from(v)?.into()),
-                b"hget" => Ok(HGet::try_from(v)?.into()),
-                b"hset" => Ok(HSet::try_from(v)?.into()),
-                b"hgetall" => Ok(HGetAll::try_from(v)?.into()),
-                b"hmget" => Ok(HMGet::try_from(v)?.into()),
-                b"sadd" => Ok(SAdd::try_from(v)?.into()),
-                b"sismember" => Ok(SIsMember::try_from(v)?.into()),
-                _ => Ok(Unrecognized.into()),
-            },
-            _ => Err(CommandError::InvalidCommand(
-                "Command must have a BulkString as the first argument".to_string(),
-            )),
-        }
-    }
+#[derive(Debug)]
+pub struct Expire {
+    key: Bytes,
+    seconds: i64,
+    condition: ExpireCondition,
 }
 
-impl CommandExecutor for Unrecognized {
-    fn execute(self, _: &Backend) -> RespFrame {
-        RESP_OK.clone()
-    }
+#[derive(Debug)]
+pub struct Pexpire {
+    key: Bytes,
+    milliseconds: i64,
+    condition: ExpireCondition,
 }
 
-fn validate_command(
-    value: &RespArray,
-    names: &[&'static str],
-    n_args: usize,
-) -> Result<(), CommandError> {
-    if value.len() != n_args + names.len() {
-        return Err(CommandError::InvalidArgument(format!(
-            "{} command must have exactly {} argument",
-            names.join(" "),
-            n_args
-        )));
-    }
+#[derive(Debug)]
+pub struct ExpireAt {
+    key: Bytes,
+    seconds: i64,
+    condition: ExpireCondition,
+}
 
-    for (i, name) in names.iter().enumerate() {
-        match value[i] {
-            RespFrame::BulkString(ref cmd) => {
-                if cmd.as_ref().to_ascii_lowercase() != name.as_bytes() {
-                    return Err(CommandError::InvalidCommand(format!(
-                        "Invalid command: expected {}, got {}",
-                        name,
-                        String::from_utf8_lossy(cmd.as_ref())
-                    )));
-                }
-            }
-            _ => {
-                return Err(CommandError::InvalidCommand(
-                    "Command must have a BulkString as the first argument".to_string(),
-                ))
-            }
-        }
-    }
-    Ok(())
+#[derive(Debug)]
+pub struct PexpireAt {
+    key: Bytes,
+    milliseconds: i64,
+    condition: ExpireCondition,
 }
 
-fn extract_args(value: RespArray, start: usize) -> Result<Vec<RespFrame>, CommandError> {
-    Ok(value.0.into_iter().skip(start).collect::<Vec<RespFrame>>())
+#[derive(Debug)]
+pub struct Ttl {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct Pttl {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct Persist {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct Incr {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct Decr {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct IncrBy {
+    key: Bytes,
+    delta: i64,
+}
+
+#[derive(Debug)]
+pub struct DecrBy {
+    key: Bytes,
+    delta: i64,
+}
+
+#[derive(Debug)]
+pub struct IncrByFloat {
+    key: Bytes,
+    delta: f64,
+}
+
+#[derive(Debug)]
+pub struct MGet {
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct MSet {
+    pairs: Vec<(Bytes, RespFrame)>,
+}
+
+#[derive(Debug)]
+pub struct MSetNx {
+    pairs: Vec<(Bytes, RespFrame)>,
+}
+
+#[derive(Debug)]
+pub struct Append {
+    key: Bytes,
+    value: Bytes,
+}
+
+#[derive(Debug)]
+pub struct Strlen {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct GetRange {
+    key: Bytes,
+    start: i64,
+    end: i64,
+}
+
+#[derive(Debug)]
+pub struct SetRange {
+    key: Bytes,
+    offset: i64,
+    value: Bytes,
+}
+
+#[derive(Debug)]
+pub struct GetSet {
+    key: Bytes,
+    value: RespFrame,
+}
+
+#[derive(Debug)]
+pub struct GetDel {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct GetEx {
+    key: Bytes,
+    expiry: SetExpiry,
+}
+
+#[derive(Debug)]
+pub struct SetNx {
+    key: Bytes,
+    value: RespFrame,
+}
+
+#[derive(Debug)]
+pub struct SetEx {
+    key: Bytes,
+    seconds: i64,
+    value: RespFrame,
+}
+
+#[derive(Debug)]
+pub struct Type {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct Keys {
+    pattern: Bytes,
+}
+
+#[derive(Debug)]
+pub struct Scan {
+    cursor: u64,
+    pattern: Option<Bytes>,
+    count: usize,
+}
+
+#[derive(Debug)]
+pub struct DbSize;
+
+#[derive(Debug)]
+pub struct RandomKey;
+
+#[derive(Debug)]
+pub struct Rename {
+    src: Bytes,
+    dst: Bytes,
+}
+
+#[derive(Debug)]
+pub struct RenameNx {
+    src: Bytes,
+    dst: Bytes,
+}
+
+#[derive(Debug)]
+pub struct Dump {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct Restore {
+    key: Bytes,
+    ttl: i64,
+    payload: Bytes,
+    replace: bool,
+}
+
+#[derive(Debug)]
+pub struct Copy {
+    src: Bytes,
+    dst: Bytes,
+    replace: bool,
+}
+
+#[derive(Debug)]
+pub struct Touch {
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct Unlink {
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct MemoryUsage {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct ObjectEncoding {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct HDel {
+    key: Bytes,
+    fields: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct HExists {
+    key: Bytes,
+    field: Bytes,
+}
+
+#[derive(Debug)]
+pub struct HLen {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct HKeys {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct HVals {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct HIncrBy {
+    key: Bytes,
+    field: Bytes,
+    delta: i64,
+}
+
+#[derive(Debug)]
+pub struct HIncrByFloat {
+    key: Bytes,
+    field: Bytes,
+    delta: f64,
+}
+
+#[derive(Debug)]
+pub struct HSetNx {
+    key: Bytes,
+    field: Bytes,
+    value: RespFrame,
+}
+
+#[derive(Debug)]
+pub struct HRandField {
+    key: Bytes,
+    // `None` replies with a single field instead of an array
+    count: Option<i64>,
+    with_values: bool,
+}
+
+#[derive(Debug)]
+pub struct HScan {
+    key: Bytes,
+    cursor: u64,
+    pattern: Option<Bytes>,
+    count: usize,
+}
+
+#[derive(Debug)]
+pub struct SScan {
+    key: Bytes,
+    cursor: u64,
+    pattern: Option<Bytes>,
+    count: usize,
+}
+
+#[derive(Debug)]
+pub struct HStrLen {
+    key: Bytes,
+    field: Bytes,
+}
+
+#[derive(Debug)]
+pub struct SRem {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SCard {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct SMembers {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct SPop {
+    key: Bytes,
+    // `None` replies with a single member instead of an array
+    count: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct SRandMember {
+    key: Bytes,
+    count: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct SInter {
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SInterStore {
+    dest: Bytes,
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SUnion {
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SUnionStore {
+    dest: Bytes,
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SDiff {
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SDiffStore {
+    dest: Bytes,
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SMove {
+    src: Bytes,
+    dst: Bytes,
+    member: Bytes,
+}
+
+#[derive(Debug)]
+pub struct SMIsMember {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct SInterCard {
+    keys: Vec<Bytes>,
+    // 0 for no limit
+    limit: usize,
+}
+
+#[derive(Debug)]
+pub struct LPush {
+    key: Bytes,
+    values: Vec<RespFrame>,
+}
+
+#[derive(Debug)]
+pub struct RPush {
+    key: Bytes,
+    values: Vec<RespFrame>,
+}
+
+#[derive(Debug)]
+pub struct LPushX {
+    key: Bytes,
+    values: Vec<RespFrame>,
+}
+
+#[derive(Debug)]
+pub struct RPushX {
+    key: Bytes,
+    values: Vec<RespFrame>,
+}
+
+#[derive(Debug)]
+pub struct LPop {
+    key: Bytes,
+    // `None` replies with a single element instead of an array
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct RPop {
+    key: Bytes,
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct LLen {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct LRange {
+    key: Bytes,
+    start: i64,
+    stop: i64,
+}
+
+#[derive(Debug)]
+pub struct LIndex {
+    key: Bytes,
+    index: i64,
+}
+
+#[derive(Debug)]
+pub struct LSet {
+    key: Bytes,
+    index: i64,
+    value: RespFrame,
+}
+
+#[derive(Debug)]
+pub struct LRem {
+    key: Bytes,
+    count: i64,
+    element: RespFrame,
+}
+
+#[derive(Debug)]
+pub struct LTrim {
+    key: Bytes,
+    start: i64,
+    stop: i64,
+}
+
+#[derive(Debug)]
+pub struct LInsert {
+    key: Bytes,
+    // `Left` for BEFORE, `Right` for AFTER
+    end: ListEnd,
+    pivot: RespFrame,
+    element: RespFrame,
+}
+
+#[derive(Debug)]
+pub struct LPos {
+    key: Bytes,
+    element: RespFrame,
+    options: LPosOptions,
+    // without COUNT the reply is a single index instead of an array
+    with_count: bool,
+}
+
+#[derive(Debug)]
+pub struct LMove {
+    src: Bytes,
+    dst: Bytes,
+    from: ListEnd,
+    to: ListEnd,
+}
+
+#[derive(Debug)]
+pub struct RPopLPush {
+    src: Bytes,
+    dst: Bytes,
+}
+
+#[derive(Debug)]
+pub struct BLPop {
+    keys: Vec<Bytes>,
+    // `None` waits forever
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct BRPop {
+    keys: Vec<Bytes>,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct ZAdd {
+    key: Bytes,
+    options: ZAddOptions,
+    pairs: Vec<(f64, Bytes)>,
+}
+
+#[derive(Debug)]
+pub struct ZScore {
+    key: Bytes,
+    member: Bytes,
+}
+
+#[derive(Debug)]
+pub struct ZRange {
+    key: Bytes,
+    by: ZRangeBy,
+    rev: bool,
+    limit: Limit,
+    with_scores: bool,
+}
+
+#[derive(Debug)]
+pub struct ZRevRange {
+    key: Bytes,
+    start: i64,
+    stop: i64,
+    with_scores: bool,
+}
+
+#[derive(Debug)]
+pub struct ZRangeByScore {
+    key: Bytes,
+    min: ScoreBound,
+    max: ScoreBound,
+    with_scores: bool,
+    limit: Limit,
+}
+
+#[derive(Debug)]
+pub struct ZCount {
+    key: Bytes,
+    min: ScoreBound,
+    max: ScoreBound,
+}
+
+#[derive(Debug)]
+pub struct ZRem {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct ZCard {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct ZIncrBy {
+    key: Bytes,
+    delta: f64,
+    member: Bytes,
+}
+
+#[derive(Debug)]
+pub struct ZRank {
+    key: Bytes,
+    member: Bytes,
+    with_score: bool,
+}
+
+#[derive(Debug)]
+pub struct ZRevRank {
+    key: Bytes,
+    member: Bytes,
+    with_score: bool,
+}
+
+#[derive(Debug)]
+pub struct ZPopMin {
+    key: Bytes,
+    count: usize,
+}
+
+#[derive(Debug)]
+pub struct ZPopMax {
+    key: Bytes,
+    count: usize,
+}
+
+#[derive(Debug)]
+pub struct ZRangeByLex {
+    key: Bytes,
+    min: LexBound,
+    max: LexBound,
+    limit: Limit,
+}
+
+#[derive(Debug)]
+pub struct ZLexCount {
+    key: Bytes,
+    min: LexBound,
+    max: LexBound,
+}
+
+#[derive(Debug)]
+pub struct ZUnionStore {
+    dest: Bytes,
+    keys: Vec<Bytes>,
+    // one per key
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+}
+
+#[derive(Debug)]
+pub struct ZInterStore {
+    dest: Bytes,
+    keys: Vec<Bytes>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+}
+
+#[derive(Debug)]
+pub struct ZScan {
+    key: Bytes,
+    cursor: u64,
+    pattern: Option<Bytes>,
+    count: usize,
+}
+
+#[derive(Debug)]
+pub struct ZMScore {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct ZRangeStore {
+    dest: Bytes,
+    src: Bytes,
+    by: ZRangeBy,
+    rev: bool,
+    limit: Limit,
+}
+
+#[derive(Debug)]
+pub struct ZRandMember {
+    key: Bytes,
+    // `None` replies with a single member instead of an array
+    count: Option<i64>,
+    with_scores: bool,
+}
+
+#[derive(Debug)]
+pub struct SetBit {
+    key: Bytes,
+    offset: usize,
+    bit: bool,
+}
+
+#[derive(Debug)]
+pub struct GetBit {
+    key: Bytes,
+    offset: usize,
+}
+
+#[derive(Debug)]
+pub struct BitCount {
+    key: Bytes,
+    // `None` counts the whole string
+    range: Option<(i64, i64, BitUnit)>,
+}
+
+#[derive(Debug)]
+pub struct BitOp {
+    op: BitOperation,
+    dest: Bytes,
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct BitPos {
+    key: Bytes,
+    bit: bool,
+    start: i64,
+    // `None` searches to the end of the string and past it for a 0
+    end: Option<i64>,
+    unit: BitUnit,
+}
+
+#[derive(Debug)]
+pub struct BitField {
+    key: Bytes,
+    ops: Vec<BitFieldOp>,
+}
+
+#[derive(Debug)]
+pub struct PfAdd {
+    key: Bytes,
+    elements: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct PfCount {
+    keys: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct PfMerge {
+    dest: Bytes,
+    sources: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct GeoAdd {
+    key: Bytes,
+    options: ZAddOptions,
+    // `(lon, lat, member)`
+    points: Vec<(f64, f64, Bytes)>,
+}
+
+#[derive(Debug)]
+pub struct GeoPos {
+    key: Bytes,
+    members: Vec<Bytes>,
+}
+
+#[derive(Debug)]
+pub struct GeoDist {
+    key: Bytes,
+    a: Bytes,
+    b: Bytes,
+    unit: GeoUnit,
+}
+
+#[derive(Debug)]
+pub struct GeoSearch {
+    key: Bytes,
+    origin: GeoOrigin,
+    // in meters, `unit` is the one distances are given in
+    shape: GeoShape,
+    unit: GeoUnit,
+    desc: bool,
+    count: Option<usize>,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+}
+
+#[derive(Debug)]
+pub struct XAdd {
+    key: Bytes,
+    id: Option<StreamId>,
+    fields: StreamFields,
+    trim: Option<StreamTrim>,
+}
+
+#[derive(Debug)]
+pub struct XDel {
+    key: Bytes,
+    ids: Vec<StreamId>,
+}
+
+#[derive(Debug)]
+pub struct XTrim {
+    key: Bytes,
+    trim: StreamTrim,
+}
+
+#[derive(Debug)]
+pub struct XLen {
+    key: Bytes,
+}
+
+#[derive(Debug)]
+pub struct XRange {
+    key: Bytes,
+    start: StreamId,
+    end: StreamId,
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct XRead {
+    // `None` for `$`, the last entry when the read starts
+    streams: Vec<(Bytes, Option<StreamId>)>,
+    count: Option<usize>,
+    // with BLOCK, how long to wait with zero being forever
+    block: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct XGroupCreate {
+    key: Bytes,
+    group: String,
+    // `None` for `$`, the last entry
+    id: Option<StreamId>,
+    mkstream: bool,
+}
+
+#[derive(Debug)]
+pub struct XReadGroup {
+    group: String,
+    consumer: String,
+    // `None` for `>`, the entries never delivered to the group
+    streams: Vec<(Bytes, Option<StreamId>)>,
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct XAck {
+    key: Bytes,
+    group: String,
+    ids: Vec<StreamId>,
+}
+
+#[derive(Debug)]
+pub struct XPending {
+    key: Bytes,
+    group: String,
+    // `start end count` for the entries themselves instead of an overview
+    range: Option<(StreamId, StreamId, usize)>,
+    consumer: Option<String>,
+}
+
+/// A write command as the client sent it, see `Command::propagated`.
+#[derive(Debug, Clone)]
+pub struct Propagated(RespArray);
+
+impl Command {
+    /// Parse a request like `try_from`, also counting it and the keys it reads for `INFO`.
+    pub fn from_request(frame: RespFrame, backend: &Backend) -> Result<Self, CommandError> {
+        let (spec, reads) = match &frame {
+            RespFrame::Array(args) => (command::spec_of(args), command::read_keys(args)),
+            _ => (None, Vec::new()),
+        };
+        let rejected = |e: CommandError| {
+            if let Some(spec) = spec {
+                backend.metrics().command_rejected(spec.name);
+            }
+            e
+        };
+        // unknown commands are reported as such even before authenticating
+        if spec.is_some_and(|spec| !spec.has_flag("no_auth")) && !backend.is_authenticated() {
+            return Err(rejected(CommandError::NoAuth));
+        }
+        if let Some(spec) = spec.filter(|spec| !SUBSCRIBER_COMMANDS.contains(&spec.name)) {
+            if backend.is_subscriber() {
+                return Err(rejected(CommandError::SubscriberOnly(spec.name)));
+            }
+        }
+        if let (Some(_), RespFrame::Array(args)) = (spec, &frame) {
+            backend.feed_monitors(args);
+        }
+        let cmd = Command::try_from(frame).map_err(rejected)?;
+        if let Some(spec) = spec {
+            backend.record_command(spec.name);
+        }
+        if spec.is_some_and(|spec| spec.has_flag("denyoom")) {
+            backend.free_memory().map_err(rejected)?;
+        }
+        backend.metrics().command_processed();
+        backend.record_reads(&reads);
+        backend.record_access(&reads);
+        Ok(cmd)
+    }
+
+    /// The name of the command `frame` asks for, as counted by `INFO commandstats`, `None` if it
+    /// is not a known one.
+    pub fn name_of(frame: &RespFrame) -> Option<&'static str> {
+        match frame {
+            RespFrame::Array(args) => command::spec_of(args).map(|spec| spec.name),
+            _ => None,
+        }
+    }
+
+    /// The keys the command `frame` asks for may change, to be signalled to the transactions
+    /// watching them once it ran.
+    pub fn written_keys(frame: &RespFrame) -> Vec<Bytes> {
+        match frame {
+            RespFrame::Array(args) => command::write_keys(args),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The request `frame` to propagate to the replicas and the append only file once it ran, if
+    /// it is a write command, see `Propagated::propagate`.
+    pub fn propagated(frame: &RespFrame) -> Option<Propagated> {
+        match frame {
+            RespFrame::Array(args)
+                if command::spec_of(args).is_some_and(|spec| spec.has_flag("write")) =>
+            {
+                Some(Propagated(args.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Execute the command like `execute`, except that the blocking commands wait for their
+    /// keys instead of replying right away.
+    pub async fn execute_async(self, backend: &Backend) -> Result<RespFrame, CommandError> {
+        match self {
+            Command::BLPop(cmd) => cmd.block(backend).await,
+            Command::BRPop(cmd) => cmd.block(backend).await,
+            Command::XRead(cmd) => cmd.block(backend).await,
+            Command::DebugSleep(cmd) => cmd.sleep().await,
+            Command::Wait(cmd) => cmd.wait(backend).await,
+            cmd => cmd.execute(backend),
+        }
+    }
+}
+
+impl TryFrom<RespFrame> for Command {
+    type Error = CommandError;
+    fn try_from(v: RespFrame) -> Result<Self, Self::Error> {
+        match v {
+            RespFrame::Array(array) => array.try_into(),
+            _ => Err(CommandError::InvalidCommand(
+                "Command must be an Array".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Command {
+    type Error = CommandError;
+    fn try_from(v: RespArray) -> Result<Self, Self::Error> {
+        let name = match v.first() {
+            Some(RespFrame::BulkString(cmd)) => cmd.to_ascii_lowercase(),
+            _ => {
+                return Err(CommandError::InvalidCommand(
+                    "Command must have a BulkString as the first argument".to_string(),
+                ))
+            }
+        };
+        match CommandSpec::lookup(&name) {
+            Some(spec) if !spec.accepts(v.len()) => Err(CommandError::WrongArity(spec.name)),
+            Some(_) => parse_command(&name, v),
+            None => Err(unknown_command(&v)),
+        }
+    }
+}
+
+// build the command called `name`, without checking its arity against the table
+fn parse_command(name: &[u8], v: RespArray) -> Result<Command, CommandError> {
+    match name {
+        b"get" => Ok(Get::try_from(v)?.into()),
+        b"set" => Ok(Set::try_from(v)?.into()),
+        b"echo" => Ok(Echo::try_from(v)?.into()),
+        b"ping" => Ok(Ping::try_from(v)?.into()),
+        b"hget" => Ok(HGet::try_from(v)?.into()),
+        b"hset" => Ok(HSet::try_from(v)?.into()),
+        b"hgetall" => Ok(HGetAll::try_from(v)?.into()),
+        b"hmget" => Ok(HMGet::try_from(v)?.into()),
+        b"hdel" => Ok(HDel::try_from(v)?.into()),
+        b"hexists" => Ok(HExists::try_from(v)?.into()),
+        b"hlen" => Ok(HLen::try_from(v)?.into()),
+        b"hkeys" => Ok(HKeys::try_from(v)?.into()),
+        b"hmset" => Ok(HMSet::try_from(v)?.into()),
+        b"hincrby" => Ok(HIncrBy::try_from(v)?.into()),
+        b"hincrbyfloat" => Ok(HIncrByFloat::try_from(v)?.into()),
+        b"hsetnx" => Ok(HSetNx::try_from(v)?.into()),
+        b"hrandfield" => Ok(HRandField::try_from(v)?.into()),
+        b"hscan" => Ok(HScan::try_from(v)?.into()),
+        b"hstrlen" => Ok(HStrLen::try_from(v)?.into()),
+        b"hvals" => Ok(HVals::try_from(v)?.into()),
+        b"sadd" => Ok(SAdd::try_from(v)?.into()),
+        b"sismember" => Ok(SIsMember::try_from(v)?.into()),
+        b"sscan" => Ok(SScan::try_from(v)?.into()),
+        b"srem" => Ok(SRem::try_from(v)?.into()),
+        b"scard" => Ok(SCard::try_from(v)?.into()),
+        b"smembers" => Ok(SMembers::try_from(v)?.into()),
+        b"spop" => Ok(SPop::try_from(v)?.into()),
+        b"srandmember" => Ok(SRandMember::try_from(v)?.into()),
+        b"smove" => Ok(SMove::try_from(v)?.into()),
+        b"sintercard" => Ok(SInterCard::try_from(v)?.into()),
+        b"smismember" => Ok(SMIsMember::try_from(v)?.into()),
+        b"sinter" => Ok(SInter::try_from(v)?.into()),
+        b"sinterstore" => Ok(SInterStore::try_from(v)?.into()),
+        b"sunion" => Ok(SUnion::try_from(v)?.into()),
+        b"sunionstore" => Ok(SUnionStore::try_from(v)?.into()),
+        b"sdiff" => Ok(SDiff::try_from(v)?.into()),
+        b"sdiffstore" => Ok(SDiffStore::try_from(v)?.into()),
+        b"del" => Ok(Del::try_from(v)?.into()),
+        b"exists" => Ok(Exists::try_from(v)?.into()),
+        b"expire" => Ok(Expire::try_from(v)?.into()),
+        b"pexpire" => Ok(Pexpire::try_from(v)?.into()),
+        b"expireat" => Ok(ExpireAt::try_from(v)?.into()),
+        b"pexpireat" => Ok(PexpireAt::try_from(v)?.into()),
+        b"ttl" => Ok(Ttl::try_from(v)?.into()),
+        b"pttl" => Ok(Pttl::try_from(v)?.into()),
+        b"persist" => Ok(Persist::try_from(v)?.into()),
+        b"incr" => Ok(Incr::try_from(v)?.into()),
+        b"decr" => Ok(Decr::try_from(v)?.into()),
+        b"incrby" => Ok(IncrBy::try_from(v)?.into()),
+        b"decrby" => Ok(DecrBy::try_from(v)?.into()),
+        b"incrbyfloat" => Ok(IncrByFloat::try_from(v)?.into()),
+        b"mget" => Ok(MGet::try_from(v)?.into()),
+        b"mset" => Ok(MSet::try_from(v)?.into()),
+        b"msetnx" => Ok(MSetNx::try_from(v)?.into()),
+        b"append" => Ok(Append::try_from(v)?.into()),
+        b"strlen" => Ok(Strlen::try_from(v)?.into()),
+        b"getrange" => Ok(GetRange::try_from(v)?.into()),
+        b"setrange" => Ok(SetRange::try_from(v)?.into()),
+        b"getset" => Ok(GetSet::try_from(v)?.into()),
+        b"getdel" => Ok(GetDel::try_from(v)?.into()),
+        b"getex" => Ok(GetEx::try_from(v)?.into()),
+        b"setnx" => Ok(SetNx::try_from(v)?.into()),
+        b"setex" => Ok(SetEx::try_from(v)?.into()),
+        b"type" => Ok(Type::try_from(v)?.into()),
+        b"keys" => Ok(Keys::try_from(v)?.into()),
+        b"scan" => Ok(Scan::try_from(v)?.into()),
+        b"dbsize" => Ok(DbSize::try_from(v)?.into()),
+        b"randomkey" => Ok(RandomKey::try_from(v)?.into()),
+        b"rename" => Ok(Rename::try_from(v)?.into()),
+        b"renamenx" => Ok(RenameNx::try_from(v)?.into()),
+        b"copy" => Ok(Copy::try_from(v)?.into()),
+        b"dump" => Ok(Dump::try_from(v)?.into()),
+        b"restore" => Ok(Restore::try_from(v)?.into()),
+        b"touch" => Ok(Touch::try_from(v)?.into()),
+        b"unlink" => Ok(Unlink::try_from(v)?.into()),
+        b"memory" => Ok(MemoryUsage::try_from(v)?.into()),
+        b"object" => Ok(ObjectEncoding::try_from(v)?.into()),
+        b"lpush" => Ok(LPush::try_from(v)?.into()),
+        b"rpush" => Ok(RPush::try_from(v)?.into()),
+        b"lpushx" => Ok(LPushX::try_from(v)?.into()),
+        b"rpushx" => Ok(RPushX::try_from(v)?.into()),
+        b"lpop" => Ok(LPop::try_from(v)?.into()),
+        b"rpop" => Ok(RPop::try_from(v)?.into()),
+        b"llen" => Ok(LLen::try_from(v)?.into()),
+        b"lrange" => Ok(LRange::try_from(v)?.into()),
+        b"lindex" => Ok(LIndex::try_from(v)?.into()),
+        b"lset" => Ok(LSet::try_from(v)?.into()),
+        b"lrem" => Ok(LRem::try_from(v)?.into()),
+        b"ltrim" => Ok(LTrim::try_from(v)?.into()),
+        b"linsert" => Ok(LInsert::try_from(v)?.into()),
+        b"lpos" => Ok(LPos::try_from(v)?.into()),
+        b"lmove" => Ok(LMove::try_from(v)?.into()),
+        b"rpoplpush" => Ok(RPopLPush::try_from(v)?.into()),
+        b"blpop" => Ok(BLPop::try_from(v)?.into()),
+        b"brpop" => Ok(BRPop::try_from(v)?.into()),
+        b"zadd" => Ok(ZAdd::try_from(v)?.into()),
+        b"zscore" => Ok(ZScore::try_from(v)?.into()),
+        b"zrange" => Ok(ZRange::try_from(v)?.into()),
+        b"zrevrange" => Ok(ZRevRange::try_from(v)?.into()),
+        b"zrangebyscore" => Ok(ZRangeByScore::try_from(v)?.into()),
+        b"zcount" => Ok(ZCount::try_from(v)?.into()),
+        b"zrem" => Ok(ZRem::try_from(v)?.into()),
+        b"zcard" => Ok(ZCard::try_from(v)?.into()),
+        b"zincrby" => Ok(ZIncrBy::try_from(v)?.into()),
+        b"zrank" => Ok(ZRank::try_from(v)?.into()),
+        b"zrevrank" => Ok(ZRevRank::try_from(v)?.into()),
+        b"zpopmin" => Ok(ZPopMin::try_from(v)?.into()),
+        b"zpopmax" => Ok(ZPopMax::try_from(v)?.into()),
+        b"zrangebylex" => Ok(ZRangeByLex::try_from(v)?.into()),
+        b"zlexcount" => Ok(ZLexCount::try_from(v)?.into()),
+        b"zunionstore" => Ok(ZUnionStore::try_from(v)?.into()),
+        b"zinterstore" => Ok(ZInterStore::try_from(v)?.into()),
+        b"zscan" => Ok(ZScan::try_from(v)?.into()),
+        b"zmscore" => Ok(ZMScore::try_from(v)?.into()),
+        b"zrangestore" => Ok(ZRangeStore::try_from(v)?.into()),
+        b"zrandmember" => Ok(ZRandMember::try_from(v)?.into()),
+        b"setbit" => Ok(SetBit::try_from(v)?.into()),
+        b"getbit" => Ok(GetBit::try_from(v)?.into()),
+        b"bitcount" => Ok(BitCount::try_from(v)?.into()),
+        b"bitop" => Ok(BitOp::try_from(v)?.into()),
+        b"bitpos" => Ok(BitPos::try_from(v)?.into()),
+        b"bitfield" => Ok(BitField::try_from(v)?.into()),
+        b"pfadd" => Ok(PfAdd::try_from(v)?.into()),
+        b"pfcount" => Ok(PfCount::try_from(v)?.into()),
+        b"pfmerge" => Ok(PfMerge::try_from(v)?.into()),
+        b"geoadd" => Ok(GeoAdd::try_from(v)?.into()),
+        b"geopos" => Ok(GeoPos::try_from(v)?.into()),
+        b"geodist" => Ok(GeoDist::try_from(v)?.into()),
+        b"geosearch" => Ok(GeoSearch::try_from(v)?.into()),
+        b"xadd" => Ok(XAdd::try_from(v)?.into()),
+        b"xlen" => Ok(XLen::try_from(v)?.into()),
+        b"xrange" => Ok(XRange::try_from(v)?.into()),
+        b"xread" => Ok(XRead::try_from(v)?.into()),
+        b"xdel" => Ok(XDel::try_from(v)?.into()),
+        b"xtrim" => Ok(XTrim::try_from(v)?.into()),
+        b"xgroup" => Ok(XGroupCreate::try_from(v)?.into()),
+        b"xreadgroup" => Ok(XReadGroup::try_from(v)?.into()),
+        b"xack" => Ok(XAck::try_from(v)?.into()),
+        b"xpending" => Ok(XPending::try_from(v)?.into()),
+        b"command" => match v.get(1) {
+            None => Ok(CommandList::try_from(v)?.into()),
+            Some(RespFrame::BulkString(sub)) => match sub.to_ascii_lowercase().as_slice() {
+                b"count" => Ok(CommandCount::try_from(v)?.into()),
+                b"info" => Ok(CommandInfo::try_from(v)?.into()),
+                b"docs" => Ok(CommandDocs::try_from(v)?.into()),
+                _ => Err(CommandError::UnknownSubcommand(
+                    String::from_utf8_lossy(sub).into_owned(),
+                    "COMMAND",
+                )),
+            },
+            _ => Err(CommandError::SyntaxError),
+        },
+        b"info" => Ok(Info::try_from(v)?.into()),
+        b"flushdb" => Ok(FlushDb::try_from(v)?.into()),
+        b"flushall" => Ok(FlushAll::try_from(v)?.into()),
+        b"select" => Ok(Select::try_from(v)?.into()),
+        b"swapdb" => Ok(SwapDb::try_from(v)?.into()),
+        b"config" => match v.get(1) {
+            Some(RespFrame::BulkString(sub)) => match sub.to_ascii_lowercase().as_slice() {
+                b"get" => Ok(ConfigGet::try_from(v)?.into()),
+                b"set" => Ok(ConfigSet::try_from(v)?.into()),
+                b"resetstat" => Ok(ConfigResetStat::try_from(v)?.into()),
+                _ => Err(CommandError::UnknownSubcommand(
+                    String::from_utf8_lossy(sub).into_owned(),
+                    "CONFIG",
+                )),
+            },
+            _ => Err(CommandError::WrongArity("config")),
+        },
+        b"shutdown" => Ok(Shutdown::try_from(v)?.into()),
+        b"time" => Ok(Time::try_from(v)?.into()),
+        b"slowlog" => match v.get(1) {
+            Some(RespFrame::BulkString(sub)) => match sub.to_ascii_lowercase().as_slice() {
+                b"get" => Ok(SlowLogGet::try_from(v)?.into()),
+                b"len" => Ok(SlowLogLen::try_from(v)?.into()),
+                b"reset" => Ok(SlowLogReset::try_from(v)?.into()),
+                _ => Err(CommandError::UnknownSubcommand(
+                    String::from_utf8_lossy(sub).into_owned(),
+                    "SLOWLOG",
+                )),
+            },
+            _ => Err(CommandError::WrongArity("slowlog")),
+        },
+        b"lastsave" => Ok(LastSave::try_from(v)?.into()),
+        b"save" => Ok(Save::try_from(v)?.into()),
+        b"bgsave" => Ok(BgSave::try_from(v)?.into()),
+        b"bgrewriteaof" => Ok(BgRewriteAof::try_from(v)?.into()),
+        b"wait" => Ok(Wait::try_from(v)?.into()),
+        b"replicaof" => Ok(ReplicaOf::try_from(v)?.into()),
+        b"replconf" => Ok(ReplConf::try_from(v)?.into()),
+        b"psync" | b"sync" => Ok(Psync::try_from(v)?.into()),
+        b"failover" => Ok(Failover::try_from(v)?.into()),
+        // like redis, VERSION and the other arguments only change the drawing, there is none
+        b"lolwut" => Ok(Lolwut.into()),
+        b"debug" => match v.get(1) {
+            Some(RespFrame::BulkString(sub)) => match sub.to_ascii_lowercase().as_slice() {
+                b"help" => Ok(DebugHelp.into()),
+                b"sleep" => Ok(DebugSleep::try_from(v)?.into()),
+                b"object" => Ok(DebugObject::try_from(v)?.into()),
+                b"set-active-expire" => Ok(DebugSetActiveExpire::try_from(v)?.into()),
+                b"change-repl-id"
+                | b"quicklist-packed-threshold"
+                | b"stringmatch-len"
+                | b"set-skip-checksum-validation" => Ok(DebugNoop.into()),
+                _ => Err(CommandError::UnknownSubcommand(
+                    String::from_utf8_lossy(sub).into_owned(),
+                    "DEBUG",
+                )),
+            },
+            _ => Err(CommandError::WrongArity("debug")),
+        },
+        b"auth" => Ok(Auth::try_from(v)?.into()),
+        b"hello" => Ok(Hello::try_from(v)?.into()),
+        b"quit" => Ok(Quit::try_from(v)?.into()),
+        b"monitor" => Ok(Monitor::try_from(v)?.into()),
+        b"reset" => Ok(Reset::try_from(v)?.into()),
+        b"multi" => Ok(Multi::try_from(v)?.into()),
+        b"exec" => Ok(Exec::try_from(v)?.into()),
+        b"discard" => Ok(Discard::try_from(v)?.into()),
+        b"watch" => Ok(Watch::try_from(v)?.into()),
+        b"unwatch" => Ok(Unwatch::try_from(v)?.into()),
+        b"subscribe" => Ok(Subscribe::try_from(v)?.into()),
+        b"unsubscribe" => Ok(Unsubscribe::try_from(v)?.into()),
+        b"publish" => Ok(Publish::try_from(v)?.into()),
+        b"psubscribe" => Ok(PSubscribe::try_from(v)?.into()),
+        b"punsubscribe" => Ok(PUnsubscribe::try_from(v)?.into()),
+        b"pubsub" => match v.get(1) {
+            Some(RespFrame::BulkString(sub)) => match sub.to_ascii_lowercase().as_slice() {
+                b"channels" => Ok(PubSubChannels::try_from(v)?.into()),
+                b"numsub" => Ok(PubSubNumSub::try_from(v)?.into()),
+                b"numpat" => Ok(PubSubNumPat::try_from(v)?.into()),
+                _ => Err(CommandError::UnknownSubcommand(
+                    String::from_utf8_lossy(sub).into_owned(),
+                    "PUBSUB",
+                )),
+            },
+            _ => Err(CommandError::WrongArity("pubsub")),
+        },
+        b"eval" => Ok(Eval::try_from(v)?.into()),
+        b"evalsha" => Ok(EvalSha::try_from(v)?.into()),
+        b"script" => match v.get(1) {
+            Some(RespFrame::BulkString(sub)) => match sub.to_ascii_lowercase().as_slice() {
+                b"load" => Ok(ScriptLoad::try_from(v)?.into()),
+                b"exists" => Ok(ScriptExists::try_from(v)?.into()),
+                b"flush" => Ok(ScriptFlush::try_from(v)?.into()),
+                _ => Err(CommandError::UnknownSubcommand(
+                    String::from_utf8_lossy(sub).into_owned(),
+                    "SCRIPT",
+                )),
+            },
+            _ => Err(CommandError::WrongArity("script")),
+        },
+        b"client" => match v.get(1) {
+            Some(RespFrame::BulkString(sub)) => match sub.to_ascii_lowercase().as_slice() {
+                b"id" => Ok(ClientId::try_from(v)?.into()),
+                b"setname" => Ok(ClientSetName::try_from(v)?.into()),
+                b"getname" => Ok(ClientGetName::try_from(v)?.into()),
+                b"list" => Ok(ClientList::try_from(v)?.into()),
+                b"kill" => Ok(ClientKill::try_from(v)?.into()),
+                _ => Err(CommandError::UnknownSubcommand(
+                    String::from_utf8_lossy(sub).into_owned(),
+                    "CLIENT",
+                )),
+            },
+            _ => Err(CommandError::WrongArity("client")),
+        },
+        _ => Err(unknown_command(&v)),
+    }
+}
+
+// the error redis gives for a command it doesn't know, quoting the start of the arguments
+fn unknown_command(value: &RespArray) -> CommandError {
+    let quote = |frame: &RespFrame| match frame {
+        RespFrame::BulkString(arg) => String::from_utf8_lossy(arg).into_owned(),
+        _ => String::new(),
+    };
+    let mut args = String::new();
+    for arg in value.iter().skip(1) {
+        if args.len() >= 128 {
+            break;
+        }
+        args.push('\'');
+        args.extend(quote(arg).chars().take(128 - args.len()));
+        args.push_str("' ");
+    }
+    let name = quote(&value[0]).chars().take(128).collect::<String>();
+    // the reply is a simple error, which can't hold a line break
+    let clean = |s: String| s.replace(['\r', '\n'], " ");
+    CommandError::UnknownCommand(clean(name), clean(args))
+}
+
+// check that the command is called `names`, its arity was checked against `COMMANDS` by the
+// dispatcher already
+fn validate_command(value: &RespArray, names: &[&'static str]) -> Result<(), CommandError> {
+    for (i, name) in names.iter().enumerate() {
+        match value[i] {
+            RespFrame::BulkString(ref cmd) => {
+                if cmd.as_ref().to_ascii_lowercase() != name.as_bytes() {
+                    return Err(CommandError::InvalidCommand(format!(
+                        "Invalid command: expected {}, got {}",
+                        name,
+                        String::from_utf8_lossy(cmd.as_ref())
+                    )));
+                }
+            }
+            _ => {
+                return Err(CommandError::InvalidCommand(
+                    "Command must have a BulkString as the first argument".to_string(),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+// validate a subcommand such as `MEMORY USAGE`, which `COMMANDS` has no arity for, taking
+// exactly `n_args` arguments
+fn validate_subcommand(
+    value: &RespArray,
+    names: &[&'static str],
+    n_args: usize,
+) -> Result<(), CommandError> {
+    if value.len() != n_args + names.len() {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command must have exactly {} argument",
+            names.join(" "),
+            n_args
+        )));
+    }
+    validate_command(value, names)
+}
+
+// the same for a subcommand taking a variable number of arguments, at least `min_args`
+fn validate_variadic_subcommand(
+    value: &RespArray,
+    names: &[&'static str],
+    min_args: usize,
+) -> Result<(), CommandError> {
+    if value.len() < min_args + names.len() {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command needs at least {} argument, got {}",
+            names.join(" "),
+            min_args,
+            value.len().saturating_sub(names.len())
+        )));
+    }
+    validate_command(value, names)
+}
+
+fn extract_args(value: RespArray, start: usize) -> Result<Vec<RespFrame>, CommandError> {
+    Ok(value.0.into_iter().skip(start).collect::<Vec<RespFrame>>())
+}
+
+fn parse_integer(value: &[u8]) -> Result<i64, CommandError> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(CommandError::NotAnInteger)
+}
+
+// parse a finite float, redis rejects nan and infinity as arguments
+fn parse_float(value: &[u8]) -> Result<f64, CommandError> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|f| f.is_finite())
+        .ok_or(CommandError::NotAFloat)
+}
+
+// extract the remaining arguments as they are, e.g. the keys of `DEL k1 k2 k3`, which are any
+// bytes
+fn extract_key_args(value: RespArray, start: usize) -> Result<Vec<Bytes>, CommandError> {
+    extract_args(value, start)?
+        .into_iter()
+        .map(|frame| match frame {
+            RespFrame::BulkString(key) => Ok(key.0),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        })
+        .collect()
+}
+
+// extract the remaining arguments as a list of utf8 strings, e.g. the parameters of `CONFIG GET`
+fn extract_string_args(value: RespArray, start: usize) -> Result<Vec<String>, CommandError> {
+    extract_args(value, start)?
+        .into_iter()
+        .map(|frame| match frame {
+            RespFrame::BulkString(arg) => Ok(String::from_utf8(arg.to_vec())?),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid argument".to_string(),
+            )),
+        })
+        .collect()
+}
+
+fn parse_cursor(value: Option<RespFrame>) -> Result<u64, CommandError> {
+    match value {
+        Some(RespFrame::BulkString(cursor)) => std::str::from_utf8(&cursor)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(CommandError::InvalidCursor),
+        _ => Err(CommandError::InvalidCursor),
+    }
+}
+
+// the `key cursor [MATCH pattern] [COUNT count]` arguments of the collection scans
+fn extract_key_scan_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, u64, Option<Bytes>, usize), CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = match args.next() {
+        Some(RespFrame::BulkString(key)) => key.0,
+        _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    };
+    let cursor = parse_cursor(args.next())?;
+    let (pattern, count) = parse_scan_options(args)?;
+    Ok((key, cursor, pattern, count))
+}
+
+// the `[MATCH pattern] [COUNT count]` options shared by the scan commands
+fn parse_scan_options(
+    mut args: impl Iterator<Item = RespFrame>,
+) -> Result<(Option<Bytes>, usize), CommandError> {
+    let (mut pattern, mut count) = (None, 10);
+    while let Some(arg) = args.next() {
+        let RespFrame::BulkString(arg) = arg else {
+            return Err(CommandError::SyntaxError);
+        };
+        match (arg.to_ascii_lowercase().as_slice(), args.next()) {
+            (b"match", Some(RespFrame::BulkString(value))) => pattern = Some(value.0),
+            (b"count", Some(RespFrame::BulkString(value))) => {
+                count = match parse_integer(&value)? {
+                    n if n < 1 => return Err(CommandError::SyntaxError),
+                    n => n as usize,
+                }
+            }
+            _ => return Err(CommandError::SyntaxError),
+        }
+    }
+    Ok((pattern, count))
+}
+
+// the request of a client sending `args`, for the tests
+#[cfg(test)]
+pub(crate) fn request_args(args: &[&str]) -> RespArray {
+    RespArray::new(
+        args.iter()
+            .map(|arg| crate::BulkString::from(*arg).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+}
+
+// run the command `args` the way a connection does, for the tests: counted, its writes
+// signalled once it ran and propagated once it succeeded
+#[cfg(test)]
+pub(crate) fn run_args(backend: &Backend, args: &[&str]) -> Result<RespFrame, CommandError> {
+    let frame = RespFrame::from(request_args(args));
+    let (written, propagated) = (Command::written_keys(&frame), Command::propagated(&frame));
+    let cmd = Command::from_request(frame, backend)?;
+    let reply = cmd.execute(backend);
+    backend.signal_modified(&written);
+    let reply = reply?;
+    if let Some(command) = propagated {
+        command.propagate(backend, &reply);
+    }
+    Ok(reply)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{RespDecode, RespNull};
+    use crate::RespDecode;
     use anyhow::Result;
     use bytes::BytesMut;
 
@@ -199,8 +2262,8 @@ mod tests {
 
         let backend = Backend::new();
 
-        let ret = cmd.execute(&backend);
-        assert_eq!(ret, RespFrame::Null(RespNull));
+        let ret = cmd.execute(&backend).unwrap();
+        assert_eq!(ret, RespFrame::NULL);
 
         Ok(())
     }
@@ -0,0 +1,232 @@
+use super::{parse_integer, stream, Propagated};
+use crate::{unix_millis, Backend, BulkString, RespArray, RespEncode, RespFrame};
+
+impl Propagated {
+    /// Send the replicas and the append only file what the command did, given its `reply`. Like
+    /// redis it is rewritten to do the same when it runs again later: a relative time to live
+    /// becomes a unix time, `XADD` gets the id it generated and `SPOP` removes the members it
+    /// popped. What changed nothing is not sent at all. A push serving blocked clients is
+    /// followed by the pops it served them.
+    pub fn propagate(&self, backend: &Backend, reply: &RespFrame) {
+        for command in self.commands(backend, reply) {
+            backend.propagate(&command);
+        }
+    }
+
+    // what to propagate once the command replied `reply`, encoded: the commands doing what it
+    // did, then the pops of the blocked clients it served
+    pub(super) fn commands(&self, backend: &Backend, reply: &RespFrame) -> Vec<Vec<u8>> {
+        let mut commands = self
+            .rewrite(reply)
+            .into_iter()
+            .map(RespEncode::encode)
+            .collect::<Vec<_>>();
+        commands.extend(backend.take_served());
+        commands
+    }
+
+    // the commands doing what this one did once it replied `reply`
+    pub(super) fn rewrite(&self, reply: &RespFrame) -> Vec<RespArray> {
+        // the command ran, so its arguments are valid
+        let args = &self.0;
+        let name = match args.first() {
+            Some(RespFrame::BulkString(name)) => name.to_ascii_lowercase(),
+            _ => return vec![args.clone()],
+        };
+        let arg = |index: usize| match args.get(index) {
+            Some(RespFrame::BulkString(arg)) => Some(arg.clone()),
+            _ => None,
+        };
+        let Some(key) = arg(1) else {
+            return vec![args.clone()];
+        };
+        // the time to live, or the count, after the key
+        let first = arg(2).unwrap_or_else(|| BulkString::new(""));
+        match name.as_slice() {
+            // a condition that did not hold changed nothing
+            b"expire" | b"pexpire" => match (reply, ttl_ms(&first, name == b"expire")) {
+                (RespFrame::Integer(1), Some(ttl_ms)) => vec![pexpireat(key, ttl_ms)],
+                (RespFrame::Integer(1), None) => vec![args.clone()],
+                _ => Vec::new(),
+            },
+            b"setex" => match (ttl_ms(&first, true), arg(3)) {
+                (Some(ttl_ms), Some(value)) => vec![command(vec![
+                    "SET".into(),
+                    key,
+                    value,
+                    "PXAT".into(),
+                    deadline(ttl_ms).into(),
+                ])],
+                _ => vec![args.clone()],
+            },
+            // the options around the expiry are kept, such as NX and GET
+            b"set" => vec![absolute_expiry(args, 3)],
+            b"getex" if matches!(reply, RespFrame::Null(_)) => Vec::new(),
+            b"getex" => vec![absolute_expiry(args, 2)],
+            // the payload is restored without an expiry, which is set right after
+            b"restore" => match parse_integer(&first) {
+                Ok(ttl_ms) if ttl_ms > 0 => {
+                    let mut restore = args.clone();
+                    restore.0[2] = BulkString::from("0").into();
+                    vec![restore, pexpireat(key, ttl_ms)]
+                }
+                _ => vec![args.clone()],
+            },
+            b"xadd" => match (reply, stream::xadd_id_index(args)) {
+                (RespFrame::BulkString(id), Some(index)) => {
+                    let mut xadd = args.clone();
+                    xadd.0[index] = id.clone().into();
+                    vec![xadd]
+                }
+                _ => vec![args.clone()],
+            },
+            // a blocking pop that did not wait, in a transaction or a script. One that waited is
+            // propagated when it pops, see `Backend::blocking_pop`
+            b"blpop" | b"brpop" => match reply {
+                RespFrame::Array(popped) => match popped.first() {
+                    Some(RespFrame::BulkString(key)) => {
+                        let pop = if name == b"blpop" { "LPOP" } else { "RPOP" };
+                        vec![command(vec![pop.into(), key.clone()])]
+                    }
+                    _ => Vec::new(),
+                },
+                _ => Vec::new(),
+            },
+            b"spop" => {
+                let popped = match reply {
+                    RespFrame::BulkString(member) => vec![member.clone().into()],
+                    RespFrame::Array(members) => members.0.clone(),
+                    _ => Vec::new(),
+                };
+                if popped.is_empty() {
+                    return Vec::new();
+                }
+                let mut srem = vec![BulkString::from("SREM").into(), key.into()];
+                srem.extend(popped);
+                vec![RespArray::new(srem)]
+            }
+            _ => vec![args.clone()],
+        }
+    }
+}
+
+// the milliseconds of `ttl`, given in seconds or in milliseconds
+fn ttl_ms(ttl: &[u8], seconds: bool) -> Option<i64> {
+    let ttl = parse_integer(ttl).ok()?;
+    match seconds {
+        true => ttl.checked_mul(1000),
+        false => Some(ttl),
+    }
+}
+
+// the unix time in milliseconds `ttl_ms` from now
+fn deadline(ttl_ms: i64) -> String {
+    unix_millis().saturating_add(ttl_ms).to_string()
+}
+
+fn pexpireat(key: BulkString, ttl_ms: i64) -> RespArray {
+    command(vec!["PEXPIREAT".into(), key, deadline(ttl_ms).into()])
+}
+
+fn command(args: Vec<BulkString>) -> RespArray {
+    RespArray::new(args.into_iter().map(RespFrame::from).collect::<Vec<_>>())
+}
+
+// the command with its `EX` or `PX` option, among the options from `start` on, turned into a
+// `PXAT`
+fn absolute_expiry(args: &RespArray, start: usize) -> RespArray {
+    let mut args = args.clone();
+    for index in start..args.len().saturating_sub(1) {
+        let (RespFrame::BulkString(option), RespFrame::BulkString(ttl)) =
+            (&args.0[index], &args.0[index + 1])
+        else {
+            continue;
+        };
+        let seconds = match option.to_ascii_lowercase().as_slice() {
+            b"ex" => true,
+            b"px" => false,
+            _ => continue,
+        };
+        if let Some(ttl_ms) = ttl_ms(ttl, seconds) {
+            args.0[index] = BulkString::from("PXAT").into();
+            args.0[index + 1] = BulkString::from(deadline(ttl_ms)).into();
+            break;
+        }
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::request_args;
+
+    fn propagated(args: &[&str]) -> Propagated {
+        Propagated(request_args(args))
+    }
+
+    fn args(commands: Vec<RespArray>) -> Vec<Vec<String>> {
+        commands
+            .into_iter()
+            .map(|command| {
+                command
+                    .0
+                    .into_iter()
+                    .map(|arg| match arg {
+                        RespFrame::BulkString(arg) => String::from_utf8_lossy(&arg).into_owned(),
+                        arg => format!("{:?}", arg),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rewrite_is_deterministic() {
+        let ok = RespFrame::from(crate::SimpleString::new("OK"));
+        let rewrite =
+            |command: &[&str], reply: &RespFrame| args(propagated(command).rewrite(reply));
+        let unix_ms = |arg: &str| arg.parse::<i64>().unwrap() - unix_millis();
+
+        let set = rewrite(&["set", "key", "value", "nx", "ex", "10", "get"], &ok);
+        assert_eq!(set[0][..5], ["set", "key", "value", "nx", "PXAT"]);
+        assert!((9000..=10_000).contains(&unix_ms(&set[0][5])));
+        assert_eq!(set[0][6], "get");
+
+        let setex = rewrite(&["setex", "key", "10", "value"], &ok);
+        assert_eq!(setex[0][..4], ["SET", "key", "value", "PXAT"]);
+        let restore = rewrite(&["restore", "key", "500", "payload", "replace"], &ok);
+        assert_eq!(restore[0], ["restore", "key", "0", "payload", "replace"]);
+        assert_eq!(restore[1][..2], ["PEXPIREAT", "key"]);
+        assert!((0..=500).contains(&unix_ms(&restore[1][2])));
+        assert_eq!(
+            rewrite(&["getex", "key", "persist"], &ok),
+            [["getex", "key", "persist"]]
+        );
+
+        // what changed nothing is not propagated
+        assert!(rewrite(&["expire", "key", "10", "nx"], &RespFrame::Integer(0)).is_empty());
+        assert!(rewrite(&["getex", "key", "ex", "10"], &RespFrame::NULL).is_empty());
+        assert!(rewrite(&["spop", "key"], &RespFrame::NULL).is_empty());
+        assert!(rewrite(&["blpop", "a", "b", "0"], &RespFrame::NULL_ARRAY).is_empty());
+        let popped = RespArray::new(vec![
+            BulkString::from("b").into(),
+            BulkString::from("x").into(),
+        ]);
+        assert_eq!(
+            rewrite(&["brpop", "a", "b", "0"], &popped.into()),
+            [["RPOP", "b"]]
+        );
+        assert_eq!(
+            rewrite(&["spop", "key"], &BulkString::from("a").into()),
+            [["SREM", "key", "a"]]
+        );
+        assert_eq!(
+            rewrite(
+                &["xadd", "key", "maxlen", "~", "10", "*", "field", "value"],
+                &BulkString::from("5-1").into()
+            ),
+            [["xadd", "key", "maxlen", "~", "10", "5-1", "field", "value"]]
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use super::{
+    extract_key_args, validate_command, CommandError, CommandExecutor, PSubscribe, PUnsubscribe,
+    PubSubChannels, PubSubNumPat, PubSubNumSub, Publish, Subscribe, Unsubscribe,
+};
+use crate::{BulkString, RespArray, RespFrame};
+use bytes::Bytes;
+
+impl CommandExecutor for Subscribe {
+    // a confirmation per channel, the connection sends each as a reply of its own
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespArray::new(backend.subscribe(&self.channels)?).into())
+    }
+}
+
+impl CommandExecutor for Unsubscribe {
+    // the same
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespArray::new(backend.unsubscribe(&self.channels)).into())
+    }
+}
+
+impl CommandExecutor for PSubscribe {
+    // the same
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespArray::new(backend.psubscribe(&self.patterns)?).into())
+    }
+}
+
+impl CommandExecutor for PUnsubscribe {
+    // the same
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespArray::new(backend.punsubscribe(&self.patterns)).into())
+    }
+}
+
+impl CommandExecutor for PubSubChannels {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let channels = backend
+            .pubsub_channels(self.pattern.as_deref())
+            .into_iter()
+            .map(|channel| BulkString::from(channel).into())
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(channels).into())
+    }
+}
+
+impl CommandExecutor for PubSubNumSub {
+    // each channel followed by its number of subscribers
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let counts = backend.pubsub_numsub(&self.channels);
+        let reply = self
+            .channels
+            .into_iter()
+            .zip(counts)
+            .flat_map(|(channel, count)| {
+                [
+                    BulkString::from(channel).into(),
+                    RespFrame::Integer(count as i64),
+                ]
+            })
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(reply).into())
+    }
+}
+
+impl CommandExecutor for PubSubNumPat {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.pubsub_numpat() as i64))
+    }
+}
+
+impl CommandExecutor for Publish {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(
+            backend.publish(&self.channel, &self.message) as i64,
+        ))
+    }
+}
+
+// - SUBSCRIBE channel [channel ...]
+impl TryFrom<RespArray> for Subscribe {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() < 2 {
+            return Err(CommandError::WrongArity("subscribe"));
+        }
+        Ok(Subscribe {
+            channels: extract_key_args(value, 1)?,
+        })
+    }
+}
+
+// - UNSUBSCRIBE [channel ...]
+impl TryFrom<RespArray> for Unsubscribe {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Unsubscribe {
+            channels: extract_key_args(value, 1)?,
+        })
+    }
+}
+
+// - PSUBSCRIBE pattern [pattern ...]
+impl TryFrom<RespArray> for PSubscribe {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() < 2 {
+            return Err(CommandError::WrongArity("psubscribe"));
+        }
+        Ok(PSubscribe {
+            patterns: extract_key_args(value, 1)?,
+        })
+    }
+}
+
+// - PUNSUBSCRIBE [pattern ...]
+impl TryFrom<RespArray> for PUnsubscribe {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(PUnsubscribe {
+            patterns: extract_key_args(value, 1)?,
+        })
+    }
+}
+
+// - PUBSUB CHANNELS [pattern]
+impl TryFrom<RespArray> for PubSubChannels {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() > 3 {
+            return Err(CommandError::WrongArity("pubsub|channels"));
+        }
+        Ok(PubSubChannels {
+            pattern: extract_key_args(value, 2)?.pop(),
+        })
+    }
+}
+
+// - PUBSUB NUMSUB [channel ...]
+impl TryFrom<RespArray> for PubSubNumSub {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(PubSubNumSub {
+            channels: extract_key_args(value, 2)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PubSubNumPat {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 2 {
+            return Err(CommandError::WrongArity("pubsub|numpat"));
+        }
+        Ok(PubSubNumPat)
+    }
+}
+
+// - PUBLISH channel message
+impl TryFrom<RespArray> for Publish {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["publish"])?;
+        match (value.get(1), value.get(2)) {
+            (Some(RespFrame::BulkString(channel)), Some(RespFrame::BulkString(message))) => {
+                Ok(Publish {
+                    channel: Bytes::copy_from_slice(channel),
+                    message: message.0.clone(),
+                })
+            }
+            _ => Err(CommandError::InvalidArgument(
+                "channel and message must be bulk strings".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::{Backend, BulkString};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_subscriber_commands() -> Result<()> {
+        let server = Backend::new();
+        let (client, _) = server.connect_client("a".into(), "l".into());
+        let mut messages = client.subscriber()?;
+        let RespFrame::Array(replies) = run_args(&client, &["subscribe", "a", "b"])? else {
+            panic!("expected an array");
+        };
+        assert_eq!(replies.len(), 2);
+
+        assert_eq!(
+            run_args(&server, &["publish", "a", "hi"])?,
+            RespFrame::Integer(1)
+        );
+        assert!(messages.recv().await.is_some());
+
+        // a subscriber may only manage its subscriptions, and ping
+        assert!(run_args(&client, &["get", "key"]).is_err());
+        assert_eq!(
+            run_args(&client, &["ping"])?,
+            RespArray::new(vec![
+                BulkString::from("pong").into(),
+                BulkString::from("").into()
+            ])
+            .into()
+        );
+        run_args(&client, &["unsubscribe"])?;
+        run_args(&client, &["get", "key"])?;
+        assert_eq!(
+            run_args(&client, &["ping"])?,
+            crate::SimpleString::new("PONG").into()
+        );
+        assert!(run_args(&client, &["subscribe"]).is_err());
+
+        run_args(&client, &["psubscribe", "a*"])?;
+        run_args(&client, &["subscribe", "b"])?;
+        assert_eq!(
+            run_args(&server, &["pubsub", "channels"])?,
+            RespArray::new(vec![BulkString::from("b").into()]).into()
+        );
+        assert_eq!(
+            run_args(&server, &["pubsub", "numsub", "b", "a"])?,
+            RespArray::new(vec![
+                BulkString::from("b").into(),
+                RespFrame::Integer(1),
+                BulkString::from("a").into(),
+                RespFrame::Integer(0),
+            ])
+            .into()
+        );
+        assert_eq!(
+            run_args(&server, &["pubsub", "numpat"])?,
+            RespFrame::Integer(1)
+        );
+        assert!(run_args(&server, &["pubsub", "numpat", "x"]).is_err());
+        assert!(run_args(&server, &["pubsub", "nope"]).is_err());
+        // a pattern subscription alone makes a subscriber
+        run_args(&client, &["unsubscribe"])?;
+        assert!(run_args(&client, &["get", "key"]).is_err());
+        let RespFrame::Array(replies) = run_args(&client, &["punsubscribe"])? else {
+            panic!("expected an array");
+        };
+        assert_eq!(replies.len(), 1);
+        run_args(&client, &["get", "key"])?;
+        assert!(run_args(&server, &["subscribe", "a"]).is_err());
+        Ok(())
+    }
+}
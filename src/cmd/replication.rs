@@ -0,0 +1,267 @@
+use super::{
+    extract_string_args, parse_integer, CommandError, CommandExecutor, Failover, Psync, ReplConf,
+    ReplicaOf, Wait, RESP_OK,
+};
+use crate::{RespArray, RespFrame};
+use std::time::{Duration, Instant};
+
+impl CommandExecutor for Wait {
+    // without waiting, as in a transaction: the replicas that have every write so far
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let acked = backend.replicas_acked(backend.master_repl_offset());
+        Ok(RespFrame::Integer(acked as i64))
+    }
+}
+
+impl Wait {
+    // until enough replicas say they have every write so far, or the timeout. Without any
+    // replica there is no one to wait for, not even forever
+    pub(super) async fn wait(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let offset = backend.master_repl_offset();
+        let deadline = (!self.timeout.is_zero()).then(|| Instant::now() + self.timeout);
+        if backend.replicas_acked(offset) < self.replicas {
+            backend.request_acks();
+        }
+        loop {
+            let acked = backend.replicas_acked(offset);
+            if acked >= self.replicas
+                || backend.connected_replicas() == 0
+                || deadline.is_some_and(|d| Instant::now() >= d)
+            {
+                return Ok(RespFrame::Integer(acked as i64));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+impl CommandExecutor for ReplicaOf {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.replicaof(self.primary);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for ReplConf {
+    // the connection keeps the listening port, and the acks only come after PSYNC
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for Psync {
+    // the connection turns into a replica itself, it can't from a transaction
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Err(CommandError::PsyncInsideMulti)
+    }
+}
+
+impl CommandExecutor for Failover {
+    // without a replica there is neither a failover to start nor one to abort
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        if self.abort {
+            Err(CommandError::NoFailover)
+        } else {
+            Err(CommandError::FailoverNoReplicas)
+        }
+    }
+}
+
+// - WAIT numreplicas timeout
+impl TryFrom<RespArray> for Wait {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 3 {
+            return Err(CommandError::WrongArity("wait"));
+        }
+        let args = extract_string_args(value, 1)?;
+        let replicas = parse_integer(args[0].as_bytes())?;
+        let timeout = parse_integer(args[1].as_bytes())?;
+        if timeout < 0 {
+            return Err(CommandError::NegativeTimeout);
+        }
+        Ok(Wait {
+            replicas: usize::try_from(replicas).unwrap_or(0),
+            timeout: Duration::from_millis(timeout as u64),
+        })
+    }
+}
+
+// - REPLICAOF host port
+// - REPLICAOF NO ONE
+impl TryFrom<RespArray> for ReplicaOf {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 3 {
+            return Err(CommandError::WrongArity("replicaof"));
+        }
+        let mut args = extract_string_args(value, 1)?;
+        if args[0].eq_ignore_ascii_case("no") && args[1].eq_ignore_ascii_case("one") {
+            return Ok(ReplicaOf { primary: None });
+        }
+        let port = u16::try_from(parse_integer(args[1].as_bytes())?)
+            .map_err(|_| CommandError::InvalidArgument("Invalid master port".to_string()))?;
+        Ok(ReplicaOf {
+            primary: Some((args.swap_remove(0), port)),
+        })
+    }
+}
+
+// - REPLCONF option value [option value ...], of which the listening port of a replica, its
+//   ack of the stream, and the request of the primary for one mean something
+impl TryFrom<RespArray> for ReplConf {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = extract_string_args(value, 1)?;
+        if args.len() % 2 != 0 {
+            return Err(CommandError::SyntaxError);
+        }
+        let mut conf = ReplConf::default();
+        for pair in args.chunks(2) {
+            match pair[0].to_ascii_lowercase().as_str() {
+                "listening-port" => {
+                    let port = u16::try_from(parse_integer(pair[1].as_bytes())?)
+                        .map_err(|_| CommandError::NotAnInteger)?;
+                    conf.listening_port = Some(port);
+                }
+                "ack" => {
+                    let offset = u64::try_from(parse_integer(pair[1].as_bytes())?)
+                        .map_err(|_| CommandError::NotAnInteger)?;
+                    conf.ack = Some(offset);
+                }
+                "getack" => conf.getack = true,
+                "capa" | "ip-address" | "fack" | "rdb-only" | "rdb-filter-only" => {}
+                option => return Err(CommandError::UnsupportedOption(option.to_string())),
+            }
+        }
+        Ok(conf)
+    }
+}
+
+// - PSYNC replicationid offset, any replica gets a full resync
+// - SYNC
+impl TryFrom<RespArray> for Psync {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = extract_string_args(value, 0)?;
+        match args.len() {
+            1 if args[0].eq_ignore_ascii_case("sync") => Ok(Psync),
+            3 if args[0].eq_ignore_ascii_case("psync") => Ok(Psync),
+            _ if args[0].eq_ignore_ascii_case("sync") => Err(CommandError::WrongArity("sync")),
+            _ => Err(CommandError::WrongArity("psync")),
+        }
+    }
+}
+
+// - FAILOVER [TO host port [FORCE]] [ABORT] [TIMEOUT milliseconds]
+impl TryFrom<RespArray> for Failover {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = extract_string_args(value, 1)?;
+        let mut abort = false;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.to_ascii_lowercase().as_str() {
+                "abort" => abort = true,
+                "force" => {}
+                "to" => {
+                    let (Some(_host), Some(port)) = (args.next(), args.next()) else {
+                        return Err(CommandError::SyntaxError);
+                    };
+                    parse_integer(port.as_bytes())?;
+                }
+                "timeout" => {
+                    let timeout = args.next().ok_or(CommandError::SyntaxError)?;
+                    parse_integer(timeout.as_bytes())?;
+                }
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+        Ok(Failover { abort })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::{Backend, BulkString};
+    use anyhow::Result;
+
+    #[test]
+    fn test_wait_and_failover() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run_args(&backend, &["wait", "1", "100"])?,
+            RespFrame::Integer(0)
+        );
+        assert!(matches!(
+            run_args(&backend, &["wait", "1", "-1"]),
+            Err(CommandError::NegativeTimeout)
+        ));
+        assert!(matches!(
+            run_args(&backend, &["wait", "x", "0"]),
+            Err(CommandError::NotAnInteger)
+        ));
+
+        assert!(matches!(
+            run_args(
+                &backend,
+                &["failover", "to", "host", "6380", "timeout", "10"]
+            ),
+            Err(CommandError::FailoverNoReplicas)
+        ));
+        assert!(matches!(
+            run_args(&backend, &["failover", "abort"]),
+            Err(CommandError::NoFailover)
+        ));
+        assert!(matches!(
+            run_args(&backend, &["failover", "to", "host"]),
+            Err(CommandError::SyntaxError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replication_commands() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run_args(
+                &backend,
+                &["replconf", "listening-port", "6380", "capa", "psync2"]
+            )?,
+            RESP_OK.clone()
+        );
+        assert!(matches!(
+            run_args(&backend, &["replconf", "nope", "1"]),
+            Err(CommandError::UnsupportedOption(option)) if option == "nope"
+        ));
+        assert!(matches!(
+            run_args(&backend, &["replconf", "ack"]),
+            Err(CommandError::SyntaxError)
+        ));
+        let frame = RespArray::new(vec![
+            BulkString::from("REPLCONF").into(),
+            BulkString::from("ACK").into(),
+            BulkString::from("42").into(),
+        ]);
+        let conf = ReplConf::try_from(frame)?;
+        assert_eq!((conf.ack, conf.getack), (Some(42), false));
+
+        // only the connection of a replica runs it, as it turns into one
+        assert!(matches!(
+            run_args(&backend, &["psync", "?", "-1"]),
+            Err(CommandError::PsyncInsideMulti)
+        ));
+        assert!(matches!(
+            run_args(&backend, &["replicaof", "host", "70000"]),
+            Err(CommandError::InvalidArgument(_))
+        ));
+        assert_eq!(
+            run_args(&backend, &["replicaof", "NO", "ONE"])?,
+            RESP_OK.clone()
+        );
+        assert!(!backend.is_replica());
+        Ok(())
+    }
+}
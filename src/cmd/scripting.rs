@@ -0,0 +1,226 @@
+use super::{
+    extract_args, extract_string_args, parse_integer, CommandError, CommandExecutor, Eval, EvalSha,
+    ScriptExists, ScriptFlush, ScriptLoad, RESP_OK,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame};
+use bytes::Bytes;
+
+impl CommandExecutor for Eval {
+    // the script is cached as if loaded, so that EVALSHA can run it next time, once it is known
+    // to compile
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        compile(&self.script)?;
+        backend.script_load(&self.script);
+        run(backend, &self.script, self.keys, self.args)
+    }
+}
+
+impl CommandExecutor for EvalSha {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let script = backend.script(&self.sha).ok_or(CommandError::NoScript)?;
+        run(backend, &script, self.keys, self.args)
+    }
+}
+
+impl CommandExecutor for ScriptLoad {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        compile(&self.script)?;
+        Ok(BulkString::from(backend.script_load(&self.script)).into())
+    }
+}
+
+impl CommandExecutor for ScriptExists {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let exists = backend
+            .script_exists(&self.shas)
+            .into_iter()
+            .map(|exists| RespFrame::Integer(exists as i64))
+            .collect::<Vec<_>>();
+        Ok(RespArray::new(exists).into())
+    }
+}
+
+impl CommandExecutor for ScriptFlush {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.script_flush();
+        Ok(RESP_OK.clone())
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn compile(script: &str) -> Result<(), CommandError> {
+    super::lua::compile(script)
+}
+
+#[cfg(feature = "scripting")]
+fn run(
+    backend: &Backend,
+    script: &str,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+) -> Result<RespFrame, CommandError> {
+    super::lua::run(backend, script, keys, args)
+}
+
+// without Lua a script can still be loaded, but never run
+#[cfg(not(feature = "scripting"))]
+fn compile(_script: &str) -> Result<(), CommandError> {
+    Ok(())
+}
+
+#[cfg(not(feature = "scripting"))]
+fn run(
+    _backend: &Backend,
+    _script: &str,
+    _keys: Vec<Bytes>,
+    _args: Vec<Bytes>,
+) -> Result<RespFrame, CommandError> {
+    Err(CommandError::ScriptingDisabled)
+}
+
+// - EVAL script numkeys [key ...] [arg ...]
+// - EVALSHA sha1 numkeys [key ...] [arg ...], the digest taking the place of the script
+fn extract_script_args(value: RespArray, name: &'static str) -> Result<Eval, CommandError> {
+    if value.len() < 3 {
+        return Err(CommandError::WrongArity(name));
+    }
+    let mut args = extract_args(value, 1)?.into_iter();
+    let (Some(RespFrame::BulkString(script)), Some(RespFrame::BulkString(numkeys))) =
+        (args.next(), args.next())
+    else {
+        return Err(CommandError::InvalidArgument(
+            "script and numkeys must be bulk strings".to_string(),
+        ));
+    };
+    let numkeys = parse_integer(&numkeys)?;
+    let numkeys = usize::try_from(numkeys).map_err(|_| CommandError::NegativeNumKeys)?;
+    if numkeys > args.len() {
+        return Err(CommandError::TooManyKeys);
+    }
+    let mut args = args
+        .map(|arg| match arg {
+            RespFrame::BulkString(arg) => Ok(arg.0),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid argument".to_string(),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let keys = args.drain(..numkeys).collect::<Vec<_>>();
+    Ok(Eval {
+        script: String::from_utf8(script.to_vec())?,
+        keys,
+        args,
+    })
+}
+
+impl TryFrom<RespArray> for Eval {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        extract_script_args(value, "eval")
+    }
+}
+
+impl TryFrom<RespArray> for EvalSha {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let Eval { script, keys, args } = extract_script_args(value, "evalsha")?;
+        Ok(EvalSha {
+            sha: script,
+            keys,
+            args,
+        })
+    }
+}
+
+// - SCRIPT LOAD script
+impl TryFrom<RespArray> for ScriptLoad {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 3 {
+            return Err(CommandError::WrongArity("script|load"));
+        }
+        Ok(ScriptLoad {
+            script: extract_string_args(value, 2)?.remove(0),
+        })
+    }
+}
+
+// - SCRIPT EXISTS sha1 [sha1 ...]
+impl TryFrom<RespArray> for ScriptExists {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() < 3 {
+            return Err(CommandError::WrongArity("script|exists"));
+        }
+        Ok(ScriptExists {
+            shas: extract_string_args(value, 2)?,
+        })
+    }
+}
+
+// - SCRIPT FLUSH [ASYNC|SYNC]
+impl TryFrom<RespArray> for ScriptFlush {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        // the cache is small, it is always flushed right away
+        match extract_string_args(value, 2)?.as_slice() {
+            [] => Ok(ScriptFlush),
+            [mode] if ["async", "sync"].contains(&mode.to_ascii_lowercase().as_str()) => {
+                Ok(ScriptFlush)
+            }
+            [_] => Err(CommandError::SyntaxError),
+            _ => Err(CommandError::WrongArity("script|flush")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use anyhow::Result;
+
+    #[test]
+    fn test_script_commands() -> Result<()> {
+        let backend = Backend::new();
+        let sha = "e0e1f9fabfc9d4800c877a703b823ac0578ff8db";
+        assert!(matches!(
+            run_args(&backend, &["evalsha", sha, "0"]),
+            Err(CommandError::NoScript)
+        ));
+        assert_eq!(
+            run_args(&backend, &["script", "load", "return 1"])?,
+            BulkString::from(sha).into()
+        );
+        assert_eq!(
+            run_args(&backend, &["script", "exists", sha, "ffff"])?,
+            RespArray::new(vec![RespFrame::Integer(1), RespFrame::Integer(0)]).into()
+        );
+        run_args(&backend, &["script", "flush", "async"])?;
+        assert_eq!(backend.script(sha), None);
+
+        assert!(matches!(
+            run_args(&backend, &["eval", "return 1", "-1"]),
+            Err(CommandError::NegativeNumKeys)
+        ));
+        assert!(matches!(
+            run_args(&backend, &["eval", "return 1", "2", "key"]),
+            Err(CommandError::TooManyKeys)
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn test_eval_caches_scripts_that_compile() -> Result<()> {
+        let backend = Backend::new();
+        let sha = |script: &str| sha1_smol::Sha1::from(script).digest().to_string();
+        assert!(run_args(&backend, &["eval", "return +", "0"]).is_err());
+        assert_eq!(backend.script(&sha("return +")), None);
+        // one failing as it runs still compiled
+        let script = "return redis.call('nope')";
+        run_args(&backend, &["eval", script, "0"])?;
+        assert!(backend.script(&sha(script)).is_some());
+        Ok(())
+    }
+}
@@ -0,0 +1,778 @@
+use super::{
+    extract_args, extract_string_args, parse_integer, validate_command, validate_subcommand,
+    BgRewriteAof, BgSave, CommandError, CommandExecutor, ConfigGet, ConfigResetStat, ConfigSet,
+    FlushAll, FlushDb, Info, LastSave, Lolwut, Save, Select, Shutdown, SlowLogGet, SlowLogLen,
+    SlowLogReset, SwapDb, Time, RESP_OK,
+};
+use crate::{version_banner, BulkString, RespArray, RespFrame, SimpleString};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+impl CommandExecutor for Info {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(BulkString::from(backend.info(&self.sections)).into())
+    }
+}
+
+impl CommandExecutor for FlushDb {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.flush(self.lazy);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for FlushAll {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.flush_all(self.lazy);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for ConfigGet {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let reply = backend
+            .config_get(&self.patterns)
+            .into_iter()
+            .flat_map(|(name, value)| {
+                [
+                    BulkString::from(name).into(),
+                    BulkString::from(value).into(),
+                ]
+            })
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(reply).into())
+    }
+}
+
+impl CommandExecutor for ConfigSet {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.config_set(&self.pairs)?;
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for ConfigResetStat {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.metrics().reset();
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for Shutdown {
+    // the connections close once they have replied, this one included
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.shutdown(self.save);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for SlowLogGet {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let entries = backend
+            .slowlog_get(self.count)
+            .into_iter()
+            .map(|entry| {
+                let args = entry
+                    .args
+                    .into_iter()
+                    .map(|arg| BulkString::new(arg).into())
+                    .collect::<Vec<RespFrame>>();
+                RespArray::new(vec![
+                    RespFrame::Integer(entry.id as i64),
+                    RespFrame::Integer(entry.timestamp as i64),
+                    RespFrame::Integer(entry.duration.as_micros() as i64),
+                    RespArray::new(args).into(),
+                    BulkString::from(entry.client_addr).into(),
+                    BulkString::from(entry.client_name).into(),
+                ])
+                .into()
+            })
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(entries).into())
+    }
+}
+
+impl CommandExecutor for SlowLogLen {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.slowlog_len() as i64))
+    }
+}
+
+impl CommandExecutor for SlowLogReset {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.slowlog_reset();
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for Time {
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(RespArray::new(vec![
+            BulkString::from(now.as_secs().to_string()).into(),
+            BulkString::from(now.subsec_micros().to_string()).into(),
+        ])
+        .into())
+    }
+}
+
+impl CommandExecutor for LastSave {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.metrics().last_save() as i64))
+    }
+}
+
+impl CommandExecutor for Save {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.save()?;
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for BgSave {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.bgsave()?;
+        Ok(SimpleString::new("Background saving started").into())
+    }
+}
+
+impl CommandExecutor for BgRewriteAof {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.bgrewriteaof()?;
+        Ok(SimpleString::new("Background append only file rewriting started").into())
+    }
+}
+
+impl CommandExecutor for Lolwut {
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(BulkString::from(version_banner()).into())
+    }
+}
+
+impl CommandExecutor for Select {
+    // only checks the index, switching databases is up to the connection
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.select(self.index)?;
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for SwapDb {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.swapdb(self.a, self.b)?;
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl TryFrom<RespArray> for Info {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["info"])?;
+        Ok(Info {
+            sections: extract_string_args(value, 1)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for FlushDb {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(FlushDb {
+            lazy: extract_flush_mode(value, "flushdb")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for FlushAll {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(FlushAll {
+            lazy: extract_flush_mode(value, "flushall")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ConfigGet {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() < 3 {
+            return Err(CommandError::WrongArity("config|get"));
+        }
+        Ok(ConfigGet {
+            patterns: extract_string_args(value, 2)?,
+        })
+    }
+}
+
+// - CONFIG SET parameter value [parameter value ...]
+impl TryFrom<RespArray> for ConfigSet {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() < 4 || !value.len().is_multiple_of(2) {
+            return Err(CommandError::WrongArity("config|set"));
+        }
+        let args = extract_string_args(value, 2)?;
+        Ok(ConfigSet {
+            pairs: args
+                .chunks(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect(),
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ConfigResetStat {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 2 {
+            return Err(CommandError::WrongArity("config|resetstat"));
+        }
+        Ok(ConfigResetStat)
+    }
+}
+
+// - SHUTDOWN [NOSAVE | SAVE]
+impl TryFrom<RespArray> for Shutdown {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = extract_string_args(value, 1)?;
+        let save = match args.as_slice() {
+            [] => None,
+            [mode] if mode.eq_ignore_ascii_case("save") => Some(true),
+            [mode] if mode.eq_ignore_ascii_case("nosave") => Some(false),
+            _ => return Err(CommandError::SyntaxError),
+        };
+        Ok(Shutdown { save })
+    }
+}
+
+// - SLOWLOG GET [count], 10 entries by default and all of them for -1
+impl TryFrom<RespArray> for SlowLogGet {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let count = match extract_args(value, 2)?.as_slice() {
+            [] => Some(10),
+            [RespFrame::BulkString(count)] => match parse_integer(count)? {
+                -1 => None,
+                count => Some(usize::try_from(count).map_err(|_| CommandError::SlowLogCount)?),
+            },
+            [_] => return Err(CommandError::NotAnInteger),
+            _ => return Err(CommandError::WrongArity("slowlog|get")),
+        };
+        Ok(SlowLogGet { count })
+    }
+}
+
+impl TryFrom<RespArray> for SlowLogLen {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_subcommand(&value, &["slowlog", "len"], 0)?;
+        Ok(SlowLogLen)
+    }
+}
+
+impl TryFrom<RespArray> for SlowLogReset {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_subcommand(&value, &["slowlog", "reset"], 0)?;
+        Ok(SlowLogReset)
+    }
+}
+
+impl TryFrom<RespArray> for Time {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["time"])?;
+        Ok(Time)
+    }
+}
+
+impl TryFrom<RespArray> for LastSave {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lastsave"])?;
+        Ok(LastSave)
+    }
+}
+
+impl TryFrom<RespArray> for Save {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["save"])?;
+        Ok(Save)
+    }
+}
+
+// - BGSAVE [SCHEDULE], there is nothing else running for it to wait for
+impl TryFrom<RespArray> for BgSave {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        match extract_string_args(value, 1)?.as_slice() {
+            [] => Ok(BgSave),
+            [schedule] if schedule.eq_ignore_ascii_case("schedule") => Ok(BgSave),
+            [_] => Err(CommandError::SyntaxError),
+            _ => Err(CommandError::WrongArity("bgsave")),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for BgRewriteAof {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bgrewriteaof"])?;
+        Ok(BgRewriteAof)
+    }
+}
+
+impl TryFrom<RespArray> for Select {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["select"])?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(Select {
+            index: parse_db_index(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SwapDb {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["swapdb"])?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(SwapDb {
+            a: parse_db_index(args.next())?,
+            b: parse_db_index(args.next())?,
+        })
+    }
+}
+
+fn parse_db_index(value: Option<RespFrame>) -> Result<i64, CommandError> {
+    match value {
+        Some(RespFrame::BulkString(index)) => parse_integer(&index),
+        _ => Err(CommandError::NotAnInteger),
+    }
+}
+
+// - FLUSHDB [ASYNC | SYNC], whether to flush in the background
+fn extract_flush_mode(value: RespArray, name: &'static str) -> Result<bool, CommandError> {
+    validate_command(&value, &[name])?;
+    let args = extract_string_args(value, 1)?;
+    match args.as_slice() {
+        [] => Ok(false),
+        [mode] if mode.eq_ignore_ascii_case("sync") => Ok(false),
+        [mode] if mode.eq_ignore_ascii_case("async") => Ok(true),
+        _ => Err(CommandError::SyntaxError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::SimpleError;
+    use anyhow::Result;
+    use std::time::Duration;
+
+    fn info(backend: &crate::Backend, section: &str) -> Result<String> {
+        match run_args(backend, &["INFO", section])? {
+            RespFrame::BulkString(info) => Ok(String::from_utf8(info.to_vec())?),
+            frame => panic!("expected a bulk string, got {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn test_keyspace_hits_and_misses() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(&backend, &["get", "missing"])?;
+        assert_eq!(
+            (
+                backend.metrics().keyspace_hits(),
+                backend.metrics().keyspace_misses()
+            ),
+            (0, 1)
+        );
+
+        run_args(&backend, &["set", "key", "value"])?;
+        run_args(&backend, &["get", "key"])?;
+        run_args(&backend, &["mget", "key", "missing", "key"])?;
+        assert_eq!(
+            (
+                backend.metrics().keyspace_hits(),
+                backend.metrics().keyspace_misses()
+            ),
+            (3, 2)
+        );
+
+        // writes and rejected commands don't count
+        run_args(&backend, &["del", "key"])?;
+        assert!(run_args(&backend, &["get"]).is_err());
+        let stats = info(&backend, "stats")?;
+        assert!(stats.contains("\r\nkeyspace_hits:3\r\nkeyspace_misses:2\r\n"));
+        // the INFO itself is counted before it runs
+        assert!(stats.contains("\r\ntotal_commands_processed:6\r\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_info() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(&backend, &["set", "key", "value"])?;
+        run_args(&backend, &["set", "other", "value", "EX", "100"])?;
+
+        let all = match run_args(&backend, &["info"])? {
+            RespFrame::BulkString(info) => String::from_utf8(info.to_vec())?,
+            frame => panic!("expected a bulk string, got {:?}", frame),
+        };
+        assert!(all.starts_with("# Server\r\nredis_version:"));
+        assert!(all.ends_with("# Keyspace\r\ndb0:keys=2,expires=1,avg_ttl=0\r\n"));
+        assert!(all.contains(&format!("\r\nprocess_id:{}\r\n", std::process::id())));
+        let headers = |info: &str| {
+            info.lines()
+                .filter(|line| line.starts_with('#'))
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(headers(&info(&backend, "default")?), headers(&all));
+        // with the sections left out by default
+        assert_eq!(
+            headers(&info(&backend, "everything")?).len(),
+            headers(&all).len() + 1
+        );
+
+        let memory = info(&backend, "Memory")?;
+        let used = memory
+            .lines()
+            .find_map(|line| line.strip_prefix("used_memory:"))
+            .unwrap()
+            .parse::<usize>()?;
+        let expected =
+            backend.memory_usage(b"key").unwrap() + backend.memory_usage(b"other").unwrap();
+        assert_eq!(used, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flushdb() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(&backend, &["set", "string", "value", "EX", "100"])?;
+        run_args(&backend, &["hset", "hash", "field", "value"])?;
+        run_args(&backend, &["sadd", "set", "member"])?;
+        run_args(&backend, &["rpush", "list", "a", "b"])?;
+        run_args(&backend, &["zadd", "zset", "1", "a"])?;
+        run_args(&backend, &["xadd", "stream", "*", "field", "value"])?;
+        assert_eq!(run_args(&backend, &["dbsize"])?, RespFrame::Integer(6));
+
+        assert_eq!(run_args(&backend, &["flushdb"])?, RESP_OK.clone());
+        assert_eq!(run_args(&backend, &["dbsize"])?, RespFrame::Integer(0));
+        assert!(backend.volatile_keys() == 0);
+
+        for mode in ["ASYNC", "sync"] {
+            run_args(&backend, &["set", "string", "value", "EX", "100"])?;
+            run_args(&backend, &["rpush", "list", "a"])?;
+            assert_eq!(run_args(&backend, &["flushall", mode])?, RESP_OK.clone());
+            assert_eq!(run_args(&backend, &["dbsize"])?, RespFrame::Integer(0));
+            assert_eq!(
+                run_args(&backend, &["ttl", "string"])?,
+                RespFrame::Integer(-2)
+            );
+        }
+        assert!(run_args(&backend, &["flushdb", "lazy"]).is_err());
+        assert!(run_args(&backend, &["flushdb", "async", "sync"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush_with_concurrent_writers() -> Result<()> {
+        let backend = crate::Backend::new();
+        let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let writers = (0..4)
+            .map(|i| {
+                let (backend, stop) = (backend.clone(), stop.clone());
+                std::thread::spawn(move || {
+                    let mut n = 0;
+                    while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                        let key = format!("{}:{}", i, n % 100);
+                        run_args(&backend, &["set", &key, "value", "PX", "1000"]).unwrap();
+                        run_args(&backend, &["rpush", &format!("list{}", key), "a"]).unwrap();
+                        run_args(&backend, &["hincrby", "hash", &key, "1"]).unwrap();
+                        n += 1;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for lazy in [true, false, true, false] {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            backend.flush(lazy);
+        }
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        run_args(&backend, &["flushall", "async"])?;
+        assert_eq!(run_args(&backend, &["dbsize"])?, RespFrame::Integer(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_isolates_databases() -> Result<()> {
+        let db0 = crate::Backend::new();
+        let db1 = db0.select(1)?;
+        run_args(&db0, &["set", "key", "zero"])?;
+        run_args(&db1, &["set", "key", "one"])?;
+        run_args(&db1, &["rpush", "list", "a"])?;
+        assert_eq!(
+            run_args(&db0, &["get", "key"])?,
+            BulkString::from("zero").into()
+        );
+        assert_eq!(
+            run_args(&db1, &["get", "key"])?,
+            BulkString::from("one").into()
+        );
+        assert_eq!(run_args(&db0, &["dbsize"])?, RespFrame::Integer(1));
+        assert_eq!(run_args(&db1, &["dbsize"])?, RespFrame::Integer(2));
+        assert!(info(&db0, "keyspace")?
+            .ends_with("db0:keys=1,expires=0,avg_ttl=0\r\ndb1:keys=2,expires=0,avg_ttl=0\r\n"));
+
+        assert_eq!(run_args(&db0, &["select", "15"])?, RESP_OK.clone());
+        for index in ["16", "-1"] {
+            assert_eq!(
+                run_args(&db0, &["select", index]).unwrap_err().to_string(),
+                "DB index is out of range"
+            );
+        }
+        assert!(run_args(&db0, &["select", "one"]).is_err());
+
+        // the number of databases is configurable
+        let small = crate::Backend::new_with_config(crate::BackendConfig {
+            databases: 2,
+            ..Default::default()
+        });
+        assert!(small.select(1).is_ok());
+        assert!(small.select(2).is_err());
+
+        // FLUSHDB only clears the selected database, FLUSHALL all of them
+        run_args(&db1, &["flushdb"])?;
+        assert_eq!(run_args(&db1, &["dbsize"])?, RespFrame::Integer(0));
+        assert_eq!(run_args(&db0, &["dbsize"])?, RespFrame::Integer(1));
+        run_args(&db1, &["set", "key", "one"])?;
+        run_args(&db0, &["flushall"])?;
+        assert_eq!(run_args(&db0, &["dbsize"])?, RespFrame::Integer(0));
+        assert_eq!(run_args(&db1, &["dbsize"])?, RespFrame::Integer(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_swapdb() -> Result<()> {
+        let db0 = crate::Backend::new();
+        run_args(&db0, &["set", "key", "zero"])?;
+        run_args(&db0.select(1)?, &["set", "key", "one"])?;
+
+        assert_eq!(run_args(&db0, &["swapdb", "0", "1"])?, RESP_OK.clone());
+        // handles see the swap once they are refreshed
+        let db1 = db0.select(1)?;
+        let db0 = db0.current();
+        assert_eq!(
+            run_args(&db0, &["get", "key"])?,
+            BulkString::from("one").into()
+        );
+        assert_eq!(
+            run_args(&db1, &["get", "key"])?,
+            BulkString::from("zero").into()
+        );
+
+        // and back again
+        run_args(&db1, &["swapdb", "1", "0"])?;
+        let (db0, db1) = (db0.current(), db1.current());
+        assert_eq!(
+            run_args(&db0, &["get", "key"])?,
+            BulkString::from("zero").into()
+        );
+        assert_eq!(
+            run_args(&db1, &["get", "key"])?,
+            BulkString::from("one").into()
+        );
+
+        run_args(&db0, &["swapdb", "3", "3"])?;
+        assert!(run_args(&db0, &["swapdb", "0", "16"]).is_err());
+        assert!(run_args(&db0, &["swapdb", "0", "x"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_get_and_set() -> Result<()> {
+        let backend = crate::Backend::new();
+        assert_eq!(
+            run_args(&backend, &["config", "get", "maxmemory*"])?,
+            RespArray::new(vec![
+                BulkString::from("maxmemory").into(),
+                BulkString::from("0").into(),
+                BulkString::from("maxmemory-policy").into(),
+                BulkString::from("noeviction").into(),
+                BulkString::from("maxmemory-samples").into(),
+                BulkString::from("5").into(),
+            ])
+            .into()
+        );
+
+        run_args(
+            &backend,
+            &["config", "set", "timeout", "30", "requirepass", "secret"],
+        )?;
+        assert_eq!(
+            run_args(
+                &backend,
+                &["CONFIG", "GET", "TIMEOUT", "require*", "timeout"]
+            )?,
+            RespArray::new(vec![
+                BulkString::from("requirepass").into(),
+                BulkString::from("secret").into(),
+                BulkString::from("timeout").into(),
+                BulkString::from("30").into(),
+            ])
+            .into()
+        );
+
+        let error = |args: &[&str]| -> RespFrame { run_args(&backend, args).unwrap_err().into() };
+        assert_eq!(
+            error(&["config", "set", "nope", "1"]),
+            SimpleError::new("ERR Unknown option or number of arguments for CONFIG SET - 'nope'")
+                .into()
+        );
+        assert_eq!(
+            error(&["config", "set", "databases", "1"]),
+            SimpleError::new("ERR CONFIG SET failed (possibly related to argument 'databases') - can't set immutable config").into()
+        );
+        assert_eq!(
+            error(&["config", "set", "maxmemory", "lots"]),
+            SimpleError::new("ERR CONFIG SET failed (possibly related to argument 'maxmemory') - argument must be a memory value").into()
+        );
+        assert!(run_args(&backend, &["config", "set", "timeout"]).is_err());
+        assert!(run_args(&backend, &["config", "nope"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_maxmemory_refuses_writes() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(&backend, &["set", "key", "value"])?;
+        run_args(&backend, &["config", "set", "maxmemory", "1"])?;
+
+        // writes that grow the data are refused, reads and deletes still go through
+        let refused = run_args(&backend, &["set", "other", "value"]).unwrap_err();
+        assert_eq!(
+            RespFrame::from(refused),
+            SimpleError::new("OOM command not allowed when used memory > 'maxmemory'.").into()
+        );
+        assert!(run_args(&backend, &["rpush", "list", "a"]).is_err());
+        assert_eq!(
+            run_args(&backend, &["get", "key"])?,
+            BulkString::from("value").into()
+        );
+        run_args(&backend, &["del", "key"])?;
+        run_args(&backend, &["config", "set", "maxmemory", "1"])?;
+        run_args(&backend, &["set", "key", "value"])?;
+
+        run_args(&backend, &["config", "set", "maxmemory", "0"])?;
+        run_args(&backend, &["set", "other", "value"])?;
+        assert_eq!(backend.dbsize(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_resetstat() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(&backend, &["get", "missing"])?;
+        assert_eq!(backend.metrics().keyspace_misses(), 1);
+        run_args(&backend, &["config", "resetstat"])?;
+        assert_eq!(backend.metrics().keyspace_misses(), 0);
+        // the INFO itself is the only command since
+        assert!(info(&backend, "stats")?.contains("total_commands_processed:1\r\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_slowlog() -> Result<()> {
+        let backend = crate::Backend::new();
+        for n in 0..12 {
+            let args = RespArray::new(vec![
+                BulkString::from("incr").into(),
+                BulkString::from(format!("key{}", n)).into(),
+            ]);
+            backend.slowlog_record(&args, Duration::from_millis(20));
+        }
+        assert_eq!(
+            run_args(&backend, &["slowlog", "len"])?,
+            RespFrame::Integer(12)
+        );
+
+        // the newest entries first, 10 of them unless asked otherwise
+        let RespFrame::Array(entries) = run_args(&backend, &["slowlog", "get"])? else {
+            panic!("expected an array");
+        };
+        assert_eq!(entries.len(), 10);
+        let RespFrame::Array(newest) = &entries[0] else {
+            panic!("expected an array");
+        };
+        assert_eq!(newest.len(), 6);
+        assert_eq!(newest[0], RespFrame::Integer(11));
+        assert_eq!(newest[2], RespFrame::Integer(20_000));
+        assert_eq!(
+            newest[3],
+            RespArray::new(vec![
+                BulkString::from("incr").into(),
+                BulkString::from("key11").into(),
+            ])
+            .into()
+        );
+        // recorded without a connection, so without an address or a name
+        assert_eq!(newest[4], BulkString::from("").into());
+        let RespFrame::Array(all) = run_args(&backend, &["slowlog", "get", "-1"])? else {
+            panic!("expected an array");
+        };
+        assert_eq!(all.len(), 12);
+        assert!(run_args(&backend, &["slowlog", "get", "-2"]).is_err());
+        assert!(run_args(&backend, &["slowlog", "get", "1", "2"]).is_err());
+        assert!(run_args(&backend, &["slowlog", "nope"]).is_err());
+
+        assert_eq!(run_args(&backend, &["slowlog", "reset"])?, RESP_OK.clone());
+        assert_eq!(
+            run_args(&backend, &["slowlog", "len"])?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_lastsave_and_lolwut() -> Result<()> {
+        let backend = crate::Backend::new();
+        let RespFrame::Array(time) = run_args(&backend, &["time"])? else {
+            panic!("expected an array");
+        };
+        let fields = time
+            .iter()
+            .map(|field| match field {
+                RespFrame::BulkString(field) => std::str::from_utf8(field).unwrap().parse(),
+                frame => panic!("expected a bulk string, got {:?}", frame),
+            })
+            .collect::<Result<Vec<u64>, _>>()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        assert!(fields[0].abs_diff(now) <= 1);
+        assert!(fields[1] < 1_000_000);
+
+        let RespFrame::Integer(started) = run_args(&backend, &["lastsave"])? else {
+            panic!("expected an integer");
+        };
+        assert!((started as u64).abs_diff(now) <= 1);
+        // what a save does once it is done
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        backend.metrics().saved();
+        assert!(run_args(&backend, &["LASTSAVE"])? > RespFrame::Integer(started));
+
+        let banner = run_args(&backend, &["lolwut", "version", "5"])?;
+        assert_eq!(banner, BulkString::from(version_banner()).into());
+        Ok(())
+    }
+}
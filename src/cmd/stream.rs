@@ -0,0 +1,1437 @@
+use super::{
+    extract_args, parse_integer, validate_command, validate_variadic_subcommand, CommandError,
+    CommandExecutor, XAck, XAdd, XDel, XGroupCreate, XLen, XPending, XRange, XRead, XReadGroup,
+    XTrim, RESP_OK,
+};
+use crate::{
+    BulkString, KeyspaceEvents, RespArray, RespFrame, StreamFields, StreamId, StreamTrim,
+    TrimThreshold,
+};
+use bytes::Bytes;
+use std::{iter::Peekable, time::Duration};
+
+impl CommandExecutor for XAdd {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let id = backend.xadd(self.key.clone(), self.id, self.fields, self.trim)?;
+        backend.notify_keyspace_event(KeyspaceEvents::STREAM, "xadd", &self.key);
+        Ok(BulkString::from(id.to_string()).into())
+    }
+}
+
+impl CommandExecutor for XDel {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.xdel(&self.key, &self.ids)?))
+    }
+}
+
+impl CommandExecutor for XTrim {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.xtrim(&self.key, &self.trim)?))
+    }
+}
+
+impl CommandExecutor for XLen {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.xlen(&self.key)?))
+    }
+}
+
+impl CommandExecutor for XRange {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let entries = backend.xrange(&self.key, self.start, self.end, self.count)?;
+        Ok(entries_reply(entries))
+    }
+}
+
+// outside of a connection, e.g. in a transaction, XREAD doesn't wait
+impl CommandExecutor for XRead {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        // nothing comes after the last entry without waiting, nor after the highest id
+        let streams = self
+            .streams
+            .into_iter()
+            .map(|(key, id)| (key, id.unwrap_or(StreamId::MAX)))
+            .collect::<Vec<_>>();
+        let read = backend.xread(&streams, self.count)?;
+        Ok(read_reply((!read.is_empty()).then_some(read)))
+    }
+}
+
+impl XRead {
+    pub(super) async fn block(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let Some(block) = self.block else {
+            return self.execute(backend);
+        };
+        let timeout = (!block.is_zero()).then_some(block);
+        let read = backend
+            .blocking_read(&self.streams, self.count, timeout)
+            .await?;
+        Ok(read_reply(read))
+    }
+}
+
+impl CommandExecutor for XGroupCreate {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        backend.xgroup_create(self.key, self.group, self.id, self.mkstream)?;
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for XReadGroup {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let read = backend.xreadgroup(&self.group, &self.consumer, &self.streams, self.count)?;
+        Ok(read_reply((!read.is_empty()).then_some(read)))
+    }
+}
+
+impl CommandExecutor for XAck {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.xack(
+            &self.key,
+            &self.group,
+            &self.ids,
+        )?))
+    }
+}
+
+impl CommandExecutor for XPending {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let id = |id: StreamId| -> RespFrame { BulkString::from(id.to_string()).into() };
+        let Some((start, end, count)) = self.range else {
+            // the count, the lowest and highest ids and how many each consumer has
+            let summary = backend.xpending(&self.key, &self.group)?;
+            let (first, last) = match summary.range {
+                Some((first, last)) => (id(first), id(last)),
+                None => (RespFrame::NULL, RespFrame::NULL),
+            };
+            let consumers = if summary.consumers.is_empty() {
+                RespFrame::NULL_ARRAY
+            } else {
+                let consumers = summary
+                    .consumers
+                    .into_iter()
+                    .map(|(consumer, count)| {
+                        RespArray::new(vec![
+                            BulkString::from(consumer).into(),
+                            BulkString::from(count.to_string()).into(),
+                        ])
+                        .into()
+                    })
+                    .collect::<Vec<RespFrame>>();
+                RespArray::new(consumers).into()
+            };
+            return Ok(RespArray::new(vec![
+                RespFrame::Integer(summary.count as i64),
+                first,
+                last,
+                consumers,
+            ])
+            .into());
+        };
+
+        // each entry with its consumer, the milliseconds since its last delivery and the number
+        // of deliveries
+        let pending = backend.xpending_range(
+            &self.key,
+            &self.group,
+            start,
+            end,
+            count,
+            self.consumer.as_deref(),
+        )?;
+        let pending = pending
+            .into_iter()
+            .map(|(entry, pending)| {
+                RespArray::new(vec![
+                    id(entry),
+                    BulkString::from(pending.consumer.clone()).into(),
+                    RespFrame::Integer(pending.idle()),
+                    RespFrame::Integer(pending.deliveries as i64),
+                ])
+                .into()
+            })
+            .collect::<Vec<RespFrame>>();
+        Ok(RespArray::new(pending).into())
+    }
+}
+
+// each stream read as an array of its key and its entries, a null array if nothing was read
+#[allow(clippy::type_complexity)]
+fn read_reply<F: Into<Option<StreamFields>>>(
+    read: Option<Vec<(Bytes, Vec<(StreamId, F)>)>>,
+) -> RespFrame {
+    let Some(read) = read else {
+        return RespFrame::NULL_ARRAY;
+    };
+    let streams = read
+        .into_iter()
+        .map(|(key, entries)| {
+            RespArray::new(vec![BulkString::from(key).into(), entries_reply(entries)]).into()
+        })
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(streams).into()
+}
+
+// each entry as an array of its id and an array of its fields and values, a null array for the
+// fields of an entry deleted since it was delivered
+fn entries_reply<F: Into<Option<StreamFields>>>(entries: Vec<(StreamId, F)>) -> RespFrame {
+    let entries = entries
+        .into_iter()
+        .map(|(id, fields)| {
+            let fields = match fields.into() {
+                Some(fields) => RespArray::new(
+                    fields
+                        .into_iter()
+                        .flat_map(|(field, value)| [field.into(), value.into()])
+                        .collect::<Vec<RespFrame>>(),
+                )
+                .into(),
+                None => RespFrame::NULL_ARRAY,
+            };
+            RespArray::new(vec![BulkString::from(id.to_string()).into(), fields]).into()
+        })
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(entries).into()
+}
+
+impl TryFrom<RespArray> for XAdd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xadd"])?;
+
+        let mut args = extract_bulk_args(value)?.into_iter().peekable();
+        let key = match args.next() {
+            Some(key) => key.0,
+            None => return Err(CommandError::WrongArity("xadd")),
+        };
+        let mut trim = None;
+        while let Some(strategy) = args.next_if(|arg| is_trim_strategy(arg)) {
+            trim = Some(parse_trim(&strategy, &mut args)?);
+        }
+        let id = match args.next() {
+            Some(id) if &id[..] == b"*" => None,
+            Some(id) => Some(parse_stream_id(&id, 0)?),
+            None => return Err(CommandError::WrongArity("xadd")),
+        };
+        // the fields come in pairs after the id
+        let mut fields = Vec::with_capacity(args.len() / 2);
+        while let Some(field) = args.next() {
+            let Some(value) = args.next() else {
+                return Err(CommandError::WrongArity("xadd"));
+            };
+            fields.push((field, value));
+        }
+        if fields.is_empty() {
+            return Err(CommandError::WrongArity("xadd"));
+        }
+        Ok(XAdd {
+            key,
+            id,
+            fields,
+            trim,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for XDel {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xdel"])?;
+
+        let mut args = extract_bulk_args(value)?.into_iter();
+        let key = args.next().expect("the key is there").0;
+        let ids = args
+            .map(|id| parse_stream_id(&id, 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(XDel { key, ids })
+    }
+}
+
+impl TryFrom<RespArray> for XTrim {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xtrim"])?;
+
+        let mut args = extract_bulk_args(value)?.into_iter().peekable();
+        let key = args.next().expect("the key is there").0;
+        let trim = match args.next() {
+            Some(strategy) if is_trim_strategy(&strategy) => parse_trim(&strategy, &mut args)?,
+            _ => return Err(CommandError::SyntaxError),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::SyntaxError);
+        }
+        Ok(XTrim { key, trim })
+    }
+}
+
+impl TryFrom<RespArray> for XLen {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xlen"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(XLen { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for XRange {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xrange"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, start, end) = match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(start)),
+                Some(RespFrame::BulkString(end)),
+            ) => (
+                key.0,
+                parse_range_bound(&start, 0)?,
+                parse_range_bound(&end, u64::MAX)?,
+            ),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key, start or end".to_string(),
+                ))
+            }
+        };
+        let count = match (args.next(), args.next(), args.next()) {
+            (None, _, _) => None,
+            (Some(RespFrame::BulkString(arg)), Some(RespFrame::BulkString(count)), None)
+                if arg.eq_ignore_ascii_case(b"count") =>
+            {
+                // like redis, a negative count is no entries at all
+                Some(parse_integer(&count)?.max(0) as usize)
+            }
+            _ => return Err(CommandError::SyntaxError),
+        };
+        Ok(XRange {
+            key,
+            start,
+            end,
+            count,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for XRead {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xread"])?;
+
+        let args = extract_args(value, 1)?.into_iter();
+        let (count, block, streams) = extract_read_args(args, "xread", b"$")?;
+        Ok(XRead {
+            streams,
+            count,
+            block,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for XGroupCreate {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_subcommand(&value, &["xgroup", "create"], 3)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let (key, group, id) = match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(group)),
+                Some(RespFrame::BulkString(id)),
+            ) => {
+                let id = match &id[..] {
+                    b"$" => None,
+                    id => Some(parse_stream_id(id, 0)?),
+                };
+                (key.0, String::from_utf8(group.to_vec())?, id)
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key, group or id".to_string(),
+                ))
+            }
+        };
+        let mkstream = match args.next() {
+            None => false,
+            Some(RespFrame::BulkString(arg)) if arg.eq_ignore_ascii_case(b"mkstream") => true,
+            _ => return Err(CommandError::SyntaxError),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::SyntaxError);
+        }
+        Ok(XGroupCreate {
+            key,
+            group,
+            id,
+            mkstream,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for XReadGroup {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xreadgroup"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (group, consumer) = match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(arg)),
+                Some(RespFrame::BulkString(group)),
+                Some(RespFrame::BulkString(consumer)),
+            ) if arg.eq_ignore_ascii_case(b"group") => (
+                String::from_utf8(group.to_vec())?,
+                String::from_utf8(consumer.to_vec())?,
+            ),
+            _ => return Err(CommandError::SyntaxError),
+        };
+        let (count, block, streams) = extract_read_args(args, "xreadgroup", b">")?;
+        // a group read never waits, nor is it flagged `blocking` in `COMMANDS`
+        if block.is_some() {
+            return Err(CommandError::SyntaxError);
+        }
+        Ok(XReadGroup {
+            group,
+            consumer,
+            streams,
+            count,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for XAck {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xack"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, group) = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(group))) => {
+                (key.0, String::from_utf8(group.to_vec())?)
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key or group".to_string(),
+                ))
+            }
+        };
+        let ids = args
+            .map(|frame| match frame {
+                RespFrame::BulkString(id) => parse_stream_id(&id, 0),
+                _ => Err(CommandError::InvalidStreamId),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(XAck { key, group, ids })
+    }
+}
+
+impl TryFrom<RespArray> for XPending {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xpending"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, group) = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(group))) => {
+                (key.0, String::from_utf8(group.to_vec())?)
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key or group".to_string(),
+                ))
+            }
+        };
+        // either nothing more for the overview, or `start end count [consumer]`
+        let (range, consumer) = match (args.next(), args.next(), args.next(), args.next()) {
+            (None, _, _, _) => (None, None),
+            (
+                Some(RespFrame::BulkString(start)),
+                Some(RespFrame::BulkString(end)),
+                Some(RespFrame::BulkString(count)),
+                consumer,
+            ) => {
+                let consumer = match consumer {
+                    Some(RespFrame::BulkString(consumer)) => {
+                        Some(String::from_utf8(consumer.to_vec())?)
+                    }
+                    None => None,
+                    _ => return Err(CommandError::SyntaxError),
+                };
+                let range = (
+                    parse_range_bound(&start, 0)?,
+                    parse_range_bound(&end, u64::MAX)?,
+                    // like redis, a negative count is no entries at all
+                    parse_integer(&count)?.max(0) as usize,
+                );
+                (Some(range), consumer)
+            }
+            _ => return Err(CommandError::SyntaxError),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::SyntaxError);
+        }
+        Ok(XPending {
+            key,
+            group,
+            range,
+            consumer,
+        })
+    }
+}
+
+// the `[COUNT n] [BLOCK ms] STREAMS key [key ...] id [id ...]` arguments of the stream reads, where
+// an id of `latest` is `None`
+#[allow(clippy::type_complexity)]
+fn extract_read_args(
+    mut args: impl Iterator<Item = RespFrame>,
+    name: &'static str,
+    latest: &[u8],
+) -> Result<
+    (
+        Option<usize>,
+        Option<Duration>,
+        Vec<(Bytes, Option<StreamId>)>,
+    ),
+    CommandError,
+> {
+    let mut count = None;
+    let mut block = None;
+    loop {
+        let Some(RespFrame::BulkString(arg)) = args.next() else {
+            return Err(CommandError::SyntaxError);
+        };
+        let arg = arg.to_ascii_lowercase();
+        if arg == b"streams" {
+            break;
+        }
+        let Some(RespFrame::BulkString(value)) = args.next() else {
+            return Err(CommandError::SyntaxError);
+        };
+        match arg.as_slice() {
+            // like redis, a count that is not positive is no limit
+            b"count" => {
+                count = Some(parse_integer(&value)?)
+                    .filter(|count| *count > 0)
+                    .map(|count| count as usize)
+            }
+            b"block" => match parse_integer(&value)? {
+                ms if ms < 0 => return Err(CommandError::NegativeTimeout),
+                ms => block = Some(Duration::from_millis(ms as u64)),
+            },
+            _ => return Err(CommandError::SyntaxError),
+        }
+    }
+
+    // the keys, then an id for each of them
+    let args = args
+        .map(|frame| match frame {
+            RespFrame::BulkString(arg) => Ok(arg),
+            _ => Err(CommandError::SyntaxError),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.is_empty() || args.len() % 2 != 0 {
+        return Err(CommandError::UnbalancedStreams(name));
+    }
+    let (keys, ids) = args.split_at(args.len() / 2);
+    let streams = keys
+        .iter()
+        .zip(ids)
+        .map(|(key, id)| {
+            let id = match &id[..] {
+                id if id == latest => None,
+                id => Some(parse_stream_id(id, 0)?),
+            };
+            Ok((key.0.clone(), id))
+        })
+        .collect::<Result<Vec<_>, CommandError>>()?;
+    Ok((count, block, streams))
+}
+
+// the arguments after the command name, all bulk strings
+fn extract_bulk_args(value: RespArray) -> Result<Vec<BulkString>, CommandError> {
+    extract_args(value, 1)?
+        .into_iter()
+        .map(|frame| match frame {
+            RespFrame::BulkString(arg) => Ok(arg),
+            _ => Err(CommandError::SyntaxError),
+        })
+        .collect()
+}
+
+// where the id is among the arguments of a valid XADD, after its key and trimming options
+pub(super) fn xadd_id_index(value: &RespArray) -> Option<usize> {
+    let mut args = extract_bulk_args(value.clone())
+        .ok()?
+        .into_iter()
+        .peekable();
+    args.next()?;
+    while let Some(strategy) = args.next_if(|arg| is_trim_strategy(arg)) {
+        parse_trim(&strategy, &mut args).ok()?;
+    }
+    Some(value.len() - args.len())
+}
+
+fn is_trim_strategy(arg: &[u8]) -> bool {
+    arg.eq_ignore_ascii_case(b"maxlen") || arg.eq_ignore_ascii_case(b"minid")
+}
+
+// the `[=|~] threshold [LIMIT count]` arguments following the MAXLEN or MINID `strategy`
+fn parse_trim(
+    strategy: &[u8],
+    args: &mut Peekable<impl Iterator<Item = BulkString>>,
+) -> Result<StreamTrim, CommandError> {
+    let approximate = match args.next_if(|arg| matches!(&arg[..], b"=" | b"~")) {
+        Some(arg) => &arg[..] == b"~",
+        None => false,
+    };
+    let Some(threshold) = args.next() else {
+        return Err(CommandError::SyntaxError);
+    };
+    let threshold = if strategy.eq_ignore_ascii_case(b"maxlen") {
+        match parse_integer(&threshold)? {
+            len if len < 0 => return Err(CommandError::NegativeStreamMaxLen),
+            len => TrimThreshold::MaxLen(len as usize),
+        }
+    } else {
+        TrimThreshold::MinId(parse_stream_id(&threshold, 0)?)
+    };
+    let limit = match args.next_if(|arg| arg.eq_ignore_ascii_case(b"limit")) {
+        Some(_) => {
+            let Some(limit) = args.next() else {
+                return Err(CommandError::SyntaxError);
+            };
+            match parse_integer(&limit)? {
+                limit if limit < 0 => return Err(CommandError::NegativeTrimLimit),
+                _ if !approximate => return Err(CommandError::TrimLimitWithoutApprox),
+                limit => Some(limit as usize),
+            }
+        }
+        None => None,
+    };
+    Ok(StreamTrim {
+        threshold,
+        approximate,
+        limit,
+    })
+}
+
+// a bound of XRANGE: `-` or `+` for either end of the stream, or an id where a missing sequence
+// number is `missing_seq`
+fn parse_range_bound(value: &[u8], missing_seq: u64) -> Result<StreamId, CommandError> {
+    match value {
+        b"-" => Ok(StreamId::MIN),
+        b"+" => Ok(StreamId::MAX),
+        value => parse_stream_id(value, missing_seq),
+    }
+}
+
+// an id as `ms-seq`, or `ms` alone with `missing_seq` for the sequence number
+fn parse_stream_id(value: &[u8], missing_seq: u64) -> Result<StreamId, CommandError> {
+    let value = std::str::from_utf8(value).map_err(|_| CommandError::InvalidStreamId)?;
+    let (ms, seq) = match value.split_once('-') {
+        Some((ms, seq)) => (ms, Some(seq)),
+        None => (value, None),
+    };
+    let parse = |part: &str| {
+        part.parse::<u64>()
+            .map_err(|_| CommandError::InvalidStreamId)
+    };
+    let seq = match seq {
+        Some(seq) => parse(seq)?,
+        None => missing_seq,
+    };
+    Ok(StreamId::new(parse(ms)?, seq))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{request_args, run_args};
+    use crate::{RespDecode, RespEncode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+    use std::{sync::Arc, thread};
+
+    fn run(backend: &crate::Backend, request: &[u8]) -> Result<RespFrame> {
+        let mut buf = BytesMut::from(request);
+        let cmd = crate::cmd::Command::try_from(RespFrame::decode(&mut buf)?)?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    fn entry(id: &str, fields: &[&str]) -> RespFrame {
+        RespArray::new(vec![
+            BulkString::from(id).into(),
+            RespArray::new(
+                fields
+                    .iter()
+                    .map(|field| BulkString::from(*field).into())
+                    .collect::<Vec<RespFrame>>(),
+            )
+            .into(),
+        ])
+        .into()
+    }
+
+    fn parse_id(frame: RespFrame) -> StreamId {
+        let RespFrame::BulkString(id) = frame else {
+            panic!("expected a bulk string");
+        };
+        parse_stream_id(&id, 0).unwrap()
+    }
+
+    #[test]
+    fn test_xadd_xlen_xrange() -> Result<()> {
+        let backend = crate::Backend::new();
+        let request =
+            b"*7\r\n$4\r\nxadd\r\n$1\r\ns\r\n$3\r\n1-1\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n2\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b"$3\r\n1-1\r\n");
+        assert_eq!(
+            run_args(&backend, &["xadd", "s", "1", "c", "3"])
+                .unwrap_err()
+                .to_string(),
+            "The ID specified in XADD is equal or smaller than the target stream top item"
+        );
+        run_args(&backend, &["xadd", "s", "2", "c", "3"])?;
+        let id = parse_id(run_args(&backend, &["xadd", "s", "*", "d", "4"])?);
+        assert!(id > StreamId::new(2, 0));
+        assert_eq!(run_args(&backend, &["xlen", "s"])?, RespFrame::Integer(3));
+        assert_eq!(
+            run_args(&backend, &["xlen", "missing"])?,
+            RespFrame::Integer(0)
+        );
+
+        assert_eq!(
+            run_args(&backend, &["xrange", "s", "-", "2"])?.encode(),
+            RespFrame::from(RespArray::new(vec![
+                entry("1-1", &["a", "1", "b", "2"]),
+                entry("2-0", &["c", "3"]),
+            ]))
+            .encode()
+        );
+        assert_eq!(
+            run_args(&backend, &["xrange", "s", "1-0", "1-1"])?,
+            RespArray::new(vec![entry("1-1", &["a", "1", "b", "2"])]).into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_xrange_partial_ids_and_count() -> Result<()> {
+        let backend = crate::Backend::new();
+        for id in [
+            "1526985054069-0",
+            "1526985054069-1",
+            "1526985054070-0",
+            "1526985054071-5",
+        ] {
+            run_args(&backend, &["xadd", "s", id, "f", id])?;
+        }
+        let ids = |reply: RespFrame| {
+            let RespFrame::Array(entries) = reply else {
+                panic!("expected an array");
+            };
+            entries
+                .0
+                .into_iter()
+                .map(|entry| match entry {
+                    RespFrame::Array(entry) => entry.0[0].clone(),
+                    _ => panic!("expected an array"),
+                })
+                .collect::<Vec<_>>()
+        };
+        let bulk = |id: &str| -> RespFrame { BulkString::from(id).into() };
+
+        // a partial id covers every sequence number of its millisecond
+        assert_eq!(
+            ids(run_args(
+                &backend,
+                &["xrange", "s", "1526985054069", "1526985054069"]
+            )?),
+            vec![bulk("1526985054069-0"), bulk("1526985054069-1")]
+        );
+        assert_eq!(
+            ids(run_args(&backend, &["xrange", "s", "1526985054070", "+"])?),
+            vec![bulk("1526985054070-0"), bulk("1526985054071-5")]
+        );
+        assert_eq!(
+            ids(run_args(
+                &backend,
+                &["xrange", "s", "-", "+", "COUNT", "3"]
+            )?),
+            vec![
+                bulk("1526985054069-0"),
+                bulk("1526985054069-1"),
+                bulk("1526985054070-0")
+            ]
+        );
+        assert_eq!(
+            run_args(&backend, &["xrange", "s", "-", "+", "count", "0"])?,
+            RespArray::new(vec![]).into()
+        );
+        assert_eq!(
+            run_args(&backend, &["xrange", "s", "+", "-"])?,
+            RespArray::new(vec![]).into()
+        );
+        assert_eq!(
+            run_args(&backend, &["xrange", "missing", "-", "+"])?,
+            RespArray::new(vec![]).into()
+        );
+
+        assert!(run_args(&backend, &["xrange", "s", "abc", "+"]).is_err());
+        assert!(run_args(&backend, &["xrange", "s", "-", "+", "count"]).is_err());
+        assert!(run_args(&backend, &["xrange", "s", "-", "+", "limit", "1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_xadd_errors() -> Result<()> {
+        let backend = crate::Backend::new();
+        assert_eq!(
+            run_args(&backend, &["xadd", "s", "0-0", "f", "v"])
+                .unwrap_err()
+                .to_string(),
+            "The ID specified in XADD must be greater than 0-0"
+        );
+        // a rejected id does not create the stream
+        assert_eq!(backend.key_type(b"s"), None);
+        assert!(run_args(&backend, &["xadd", "s", "1-x", "f", "v"]).is_err());
+        assert!(run_args(&backend, &["xadd", "s", "*", "f"]).is_err());
+        assert!(run_args(&backend, &["xadd", "s", "*"]).is_err());
+
+        backend.set("string".into(), BulkString::from("value").into());
+        for args in [
+            &["xadd", "string", "*", "f", "v"][..],
+            &["xlen", "string"],
+            &["xrange", "string", "-", "+"],
+        ] {
+            let err = run_args(&backend, args).unwrap_err();
+            assert!(matches!(err, CommandError::WrongType));
+        }
+
+        run_args(&backend, &["xadd", "s", "*", "f", "v"])?;
+        assert_eq!(backend.key_type(b"s"), Some(crate::KeyType::Stream));
+        assert!(backend.sadd("s", "member").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_xread() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(&backend, &["xadd", "first", "1-0", "a", "1"])?;
+        run_args(&backend, &["xadd", "first", "2-0", "b", "2"])?;
+        run_args(&backend, &["xadd", "second", "1-0", "c", "3"])?;
+
+        let request = b"*6\r\n$5\r\nxread\r\n$7\r\nstreams\r\n$5\r\nfirst\r\n$6\r\nsecond\r\n$3\r\n1-0\r\n$1\r\n0\r\n";
+        assert_eq!(
+            run(&backend, request)?.encode(),
+            b"*2\r\n\
+              *2\r\n$5\r\nfirst\r\n*1\r\n*2\r\n$3\r\n2-0\r\n*2\r\n$1\r\nb\r\n$1\r\n2\r\n\
+              *2\r\n$6\r\nsecond\r\n*1\r\n*2\r\n$3\r\n1-0\r\n*2\r\n$1\r\nc\r\n$1\r\n3\r\n"
+        );
+        // streams with nothing new are left out, COUNT applies to each stream
+        assert_eq!(
+            run_args(
+                &backend,
+                &["xread", "COUNT", "1", "STREAMS", "first", "second", "missing", "0", "1", "0"]
+            )?,
+            RespArray::new(vec![RespArray::new(vec![
+                BulkString::from("first").into(),
+                RespArray::new(vec![entry("1-0", &["a", "1"])]).into(),
+            ])
+            .into()])
+            .into()
+        );
+        // nothing is read, and without a connection `$` and BLOCK don't wait
+        assert_eq!(
+            run_args(&backend, &["xread", "streams", "first", "2"])?,
+            RespFrame::NULL
+        );
+        assert_eq!(
+            run_args(&backend, &["xread", "block", "0", "streams", "first", "$"])?,
+            RespFrame::NULL
+        );
+
+        assert_eq!(
+            run_args(&backend, &["xread", "streams", "first", "second", "0"])
+                .unwrap_err()
+                .to_string(),
+            "Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified."
+        );
+        assert!(run_args(&backend, &["xread", "streams"]).is_err());
+        assert!(run_args(&backend, &["xread", "count", "1", "first", "0"]).is_err());
+        assert!(run_args(&backend, &["xread", "block", "-1", "streams", "first", "0"]).is_err());
+        assert!(run_args(&backend, &["xread", "streams", "first", "x"]).is_err());
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(run_args(&backend, &["xread", "streams", "string", "$"]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_xread_block_wakes_on_xadd() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(&backend, &["xadd", "s", "1-0", "old", "1"])?;
+        let writer = backend.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            // another stream doesn't wake the reader
+            run_args(&writer, &["xadd", "other", "5-0", "f", "v"]).unwrap();
+            run_args(&writer, &["xadd", "s", "7-0", "new", "2"]).unwrap();
+        });
+
+        let cmd = crate::cmd::Command::try_from(request_args(&[
+            "xread", "block", "5000", "streams", "s", "$",
+        ]))?;
+        let reply = cmd.execute_async(&backend).await?;
+        assert_eq!(
+            reply,
+            RespArray::new(vec![RespArray::new(vec![
+                BulkString::from("s").into(),
+                RespArray::new(vec![entry("7-0", &["new", "2"])]).into(),
+            ])
+            .into()])
+            .into()
+        );
+        assert!(backend.readers.is_empty());
+
+        // entries already there are read without waiting
+        let read = backend
+            .blocking_read(&[("s".into(), Some(StreamId::MIN))], None, None)
+            .await?
+            .unwrap();
+        assert_eq!(read[0].1.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_xread_block_timeout() -> Result<()> {
+        let backend = crate::Backend::new();
+        let started = std::time::Instant::now();
+        let read = backend
+            .blocking_read(&[("s".into(), None)], None, Some(Duration::from_millis(50)))
+            .await?;
+        assert_eq!(read, None);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(backend.readers.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_consumer_group_lifecycle() -> Result<()> {
+        let backend = crate::Backend::new();
+        let bulk = |value: &str| -> RespFrame { BulkString::from(value).into() };
+        let read = |key: &str, entries: Vec<RespFrame>| -> RespFrame {
+            RespArray::new(vec![RespArray::new(vec![
+                bulk(key),
+                RespArray::new(entries).into(),
+            ])
+            .into()])
+            .into()
+        };
+        run_args(&backend, &["xadd", "s", "1-0", "job", "1"])?;
+        assert_eq!(
+            run_args(&backend, &["xgroup", "create", "s", "workers", "0"])?,
+            RESP_OK.clone()
+        );
+        assert_eq!(
+            RespFrame::from(
+                run_args(&backend, &["xgroup", "create", "s", "workers", "$"]).unwrap_err()
+            )
+            .encode(),
+            b"-BUSYGROUP Consumer Group name already exists\r\n"
+        );
+        run_args(&backend, &["xadd", "s", "2-0", "job", "2"])?;
+        run_args(&backend, &["xadd", "s", "3-0", "job", "3"])?;
+
+        // produce → read: new entries are split between the consumers and become pending
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "xreadgroup",
+                    "GROUP",
+                    "workers",
+                    "alice",
+                    "COUNT",
+                    "2",
+                    "STREAMS",
+                    "s",
+                    ">"
+                ]
+            )?,
+            read(
+                "s",
+                vec![entry("1-0", &["job", "1"]), entry("2-0", &["job", "2"])]
+            )
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &["xreadgroup", "group", "workers", "bob", "streams", "s", ">"]
+            )?,
+            read("s", vec![entry("3-0", &["job", "3"])])
+        );
+        // nothing left to deliver
+        assert_eq!(
+            run_args(
+                &backend,
+                &["xreadgroup", "group", "workers", "bob", "streams", "s", ">"]
+            )?,
+            RespFrame::NULL
+        );
+        assert_eq!(
+            run_args(&backend, &["xpending", "s", "workers"])?,
+            RespArray::new(vec![
+                RespFrame::Integer(3),
+                bulk("1-0"),
+                bulk("3-0"),
+                RespArray::new(vec![
+                    RespArray::new(vec![bulk("alice"), bulk("2")]).into(),
+                    RespArray::new(vec![bulk("bob"), bulk("1")]).into(),
+                ])
+                .into(),
+            ])
+            .into()
+        );
+
+        // crash: alice comes back and reads her own pending entries again
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "xreadgroup",
+                    "group",
+                    "workers",
+                    "alice",
+                    "streams",
+                    "s",
+                    "0"
+                ]
+            )?,
+            read(
+                "s",
+                vec![entry("1-0", &["job", "1"]), entry("2-0", &["job", "2"])]
+            )
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "xreadgroup",
+                    "group",
+                    "workers",
+                    "alice",
+                    "streams",
+                    "s",
+                    "1-0"
+                ]
+            )?,
+            read("s", vec![entry("2-0", &["job", "2"])])
+        );
+        let pending =
+            backend.xpending_range(b"s", "workers", StreamId::MIN, StreamId::MAX, 10, None)?;
+        assert_eq!(
+            pending
+                .iter()
+                .map(|(id, pending)| (
+                    id.to_string(),
+                    pending.consumer.as_str(),
+                    pending.deliveries
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("1-0".into(), "alice", 2),
+                ("2-0".into(), "alice", 3),
+                ("3-0".into(), "bob", 1)
+            ]
+        );
+        let RespFrame::Array(entries) = run_args(
+            &backend,
+            &["xpending", "s", "workers", "-", "+", "10", "bob"],
+        )?
+        else {
+            panic!("expected an array");
+        };
+        let RespFrame::Array(ref bob) = entries.0[0] else {
+            panic!("expected an array");
+        };
+        assert_eq!(entries.len(), 1);
+        assert_eq!(bob.0[0], bulk("3-0"));
+        assert_eq!(bob.0[1], bulk("bob"));
+        assert!(matches!(bob.0[2], RespFrame::Integer(idle) if idle >= 0));
+        assert_eq!(bob.0[3], RespFrame::Integer(1));
+
+        // ack: acknowledged entries are no longer pending, acking twice counts once
+        assert_eq!(
+            run_args(&backend, &["xack", "s", "workers", "1-0", "2-0", "9-0"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(
+            run_args(&backend, &["xack", "s", "workers", "1-0"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "xreadgroup",
+                    "group",
+                    "workers",
+                    "alice",
+                    "streams",
+                    "s",
+                    "0"
+                ]
+            )?,
+            read("s", vec![])
+        );
+        run_args(&backend, &["xack", "s", "workers", "3-0"])?;
+        assert_eq!(
+            run_args(&backend, &["xpending", "s", "workers"])?,
+            RespArray::new(vec![
+                RespFrame::Integer(0),
+                RespFrame::NULL,
+                RespFrame::NULL,
+                RespFrame::NULL,
+            ])
+            .into()
+        );
+        // the entries stay in the stream
+        assert_eq!(run_args(&backend, &["xlen", "s"])?, RespFrame::Integer(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_consumer_group_errors() -> Result<()> {
+        let backend = crate::Backend::new();
+        let err = |args: &[&str]| -> Result<Vec<u8>> {
+            let err = run_args(&backend, args).unwrap_err();
+            Ok(RespFrame::from(err).encode())
+        };
+        assert!(
+            String::from_utf8(err(&["xgroup", "create", "s", "g", "$"])?)?
+                .starts_with("-ERR The XGROUP subcommand requires the key to exist.")
+        );
+        assert_eq!(
+            run_args(&backend, &["xgroup", "create", "s", "g", "$", "MKSTREAM"])?,
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.key_type(b"s"), Some(crate::KeyType::Stream));
+        assert_eq!(run_args(&backend, &["xlen", "s"])?, RespFrame::Integer(0));
+        // a group created with `$` only gets what is added after it
+        run_args(&backend, &["xadd", "s", "5-0", "f", "v"])?;
+        run_args(&backend, &["xgroup", "create", "s", "late", "$"])?;
+        assert_eq!(
+            run_args(
+                &backend,
+                &["xreadgroup", "group", "late", "c", "streams", "s", ">"]
+            )?,
+            RespFrame::NULL
+        );
+
+        assert_eq!(
+            err(&["xreadgroup", "group", "missing", "c", "streams", "s", ">"])?,
+            b"-NOGROUP No such key 's' or consumer group 'missing'\r\n"
+        );
+        assert_eq!(
+            err(&["xpending", "nokey", "g"])?,
+            b"-NOGROUP No such key 'nokey' or consumer group 'g'\r\n"
+        );
+        // nothing is delivered from any stream if one of the groups is missing
+        run_args(
+            &backend,
+            &["xgroup", "create", "other", "solo", "0", "mkstream"],
+        )?;
+        assert!(run_args(
+            &backend,
+            &[
+                "xreadgroup",
+                "group",
+                "g",
+                "c",
+                "streams",
+                "s",
+                "other",
+                ">",
+                ">"
+            ]
+        )
+        .is_err());
+        assert_eq!(
+            run_args(&backend, &["xpending", "s", "g"])?,
+            RespArray::new(vec![
+                RespFrame::Integer(0),
+                RespFrame::NULL,
+                RespFrame::NULL,
+                RespFrame::NULL,
+            ])
+            .into()
+        );
+        assert_eq!(
+            run_args(&backend, &["xack", "nokey", "g", "1-0"])?,
+            RespFrame::Integer(0)
+        );
+
+        assert!(run_args(&backend, &["xgroup", "create", "s", "g2", "$", "extra"]).is_err());
+        assert!(run_args(&backend, &["xreadgroup", "g", "c", "streams", "s", ">"]).is_err());
+        assert!(run_args(&backend, &["xreadgroup", "group", "g", "c", "streams", "s"]).is_err());
+        assert!(run_args(&backend, &["xpending", "s", "g", "-", "+"]).is_err());
+        assert!(run_args(&backend, &["xack", "s", "g", "x"]).is_err());
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(run_args(
+            &backend,
+            &["xgroup", "create", "string", "g", "$", "mkstream"]
+        )
+        .is_err());
+        Ok(())
+    }
+
+    fn add_entries(backend: &crate::Backend, key: &str, ids: std::ops::RangeInclusive<u64>) {
+        for ms in ids {
+            run_args(backend, &["xadd", key, &ms.to_string(), "f", "v"]).unwrap();
+        }
+    }
+
+    fn first_id(backend: &crate::Backend, key: &[u8]) -> Option<StreamId> {
+        backend
+            .xrange(key, StreamId::MIN, StreamId::MAX, Some(1))
+            .unwrap()
+            .first()
+            .map(|(id, _)| *id)
+    }
+
+    #[test]
+    fn test_xdel() -> Result<()> {
+        let backend = crate::Backend::new();
+        add_entries(&backend, "s", 1..=3);
+        // only the ids that were there count
+        assert_eq!(
+            run_args(&backend, &["xdel", "s", "1-0", "3", "9-9", "1-0"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(run_args(&backend, &["xlen", "s"])?, RespFrame::Integer(1));
+        assert_eq!(
+            run_args(&backend, &["xdel", "missing", "1-0"])?,
+            RespFrame::Integer(0)
+        );
+
+        // ids keep growing past the deleted last entry
+        assert!(run_args(&backend, &["xadd", "s", "3-0", "f", "v"]).is_err());
+        run_args(&backend, &["xadd", "s", "3-1", "f", "v"])?;
+        // a stream emptied by XDEL is still there
+        run_args(&backend, &["xdel", "s", "2-0", "3-1"])?;
+        assert_eq!(backend.key_type(b"s"), Some(crate::KeyType::Stream));
+        assert!(run_args(&backend, &["xdel", "s", "bad"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_xadd_maxlen() -> Result<()> {
+        let backend = crate::Backend::new();
+        for ms in 1..=10 {
+            run_args(
+                &backend,
+                &["xadd", "s", "MAXLEN", "3", &ms.to_string(), "f", "v"],
+            )?;
+        }
+        assert_eq!(run_args(&backend, &["xlen", "s"])?, RespFrame::Integer(3));
+        assert_eq!(first_id(&backend, b"s"), Some(StreamId::new(8, 0)));
+        run_args(&backend, &["xadd", "s", "maxlen", "=", "1", "*", "f", "v"])?;
+        assert_eq!(run_args(&backend, &["xlen", "s"])?, RespFrame::Integer(1));
+
+        // approximately, whole batches go and no more
+        for ms in 0..250 {
+            run_args(
+                &backend,
+                &[
+                    "xadd",
+                    "big",
+                    "MAXLEN",
+                    "~",
+                    "10",
+                    &(ms + 1).to_string(),
+                    "f",
+                    "v",
+                ],
+            )?;
+        }
+        let RespFrame::Integer(len) = run_args(&backend, &["xlen", "big"])? else {
+            panic!("expected an integer");
+        };
+        assert!((10..110).contains(&len), "{}", len);
+
+        assert!(run_args(&backend, &["xadd", "s", "maxlen", "-1", "*", "f", "v"]).is_err());
+        assert!(run_args(&backend, &["xadd", "s", "maxlen", "*", "f", "v"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_xtrim() -> Result<()> {
+        let backend = crate::Backend::new();
+        add_entries(&backend, "s", 1..=10);
+        assert_eq!(
+            run_args(&backend, &["xtrim", "s", "MINID", "4"])?,
+            RespFrame::Integer(3)
+        );
+        assert_eq!(first_id(&backend, b"s"), Some(StreamId::new(4, 0)));
+        assert_eq!(
+            run_args(&backend, &["xtrim", "s", "MAXLEN", "=", "5"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(first_id(&backend, b"s"), Some(StreamId::new(6, 0)));
+        // already within the limits
+        assert_eq!(
+            run_args(&backend, &["xtrim", "s", "maxlen", "5"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(&backend, &["xtrim", "missing", "maxlen", "0"])?,
+            RespFrame::Integer(0)
+        );
+
+        // approximate trimming removes nothing newer than the threshold
+        add_entries(&backend, "big", 1..=1000);
+        assert_eq!(
+            run_args(&backend, &["xtrim", "big", "MINID", "~", "351"])?,
+            RespFrame::Integer(300)
+        );
+        assert_eq!(first_id(&backend, b"big"), Some(StreamId::new(301, 0)));
+        assert_eq!(
+            run_args(&backend, &["xtrim", "big", "MAXLEN", "~", "650"])?,
+            RespFrame::Integer(0)
+        );
+        // and at most LIMIT entries, rounded down to whole batches
+        assert_eq!(
+            run_args(
+                &backend,
+                &["xtrim", "big", "MAXLEN", "~", "0", "LIMIT", "250"]
+            )?,
+            RespFrame::Integer(200)
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &["xtrim", "big", "MAXLEN", "~", "0", "LIMIT", "0"]
+            )?,
+            RespFrame::Integer(500)
+        );
+
+        assert_eq!(
+            run_args(&backend, &["xtrim", "s", "maxlen", "1", "limit", "10"])
+                .unwrap_err()
+                .to_string(),
+            "syntax error, LIMIT cannot be used without the special ~ option"
+        );
+        assert!(run_args(&backend, &["xtrim", "s", "maxlen", "-1"]).is_err());
+        assert!(run_args(&backend, &["xtrim", "s", "maxlen", "~", "1", "limit", "-1"]).is_err());
+        assert!(run_args(&backend, &["xtrim", "s", "count", "1"]).is_err());
+        assert!(run_args(&backend, &["xtrim", "s", "minid", "1", "extra"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trimmed_entries_stay_pending() -> Result<()> {
+        let backend = crate::Backend::new();
+        add_entries(&backend, "s", 1..=3);
+        run_args(&backend, &["xgroup", "create", "s", "g", "0"])?;
+        run_args(
+            &backend,
+            &["xreadgroup", "group", "g", "c", "streams", "s", ">"],
+        )?;
+        run_args(&backend, &["xtrim", "s", "maxlen", "1"])?;
+
+        // the deleted entries are still pending, and reported without their fields
+        let summary = backend.xpending(b"s", "g")?;
+        assert_eq!(summary.count, 3);
+        assert_eq!(
+            run_args(
+                &backend,
+                &["xreadgroup", "group", "g", "c", "streams", "s", "0"]
+            )?,
+            RespArray::new(vec![RespArray::new(vec![
+                BulkString::from("s").into(),
+                RespArray::new(vec![
+                    RespArray::new(vec![BulkString::from("1-0").into(), RespFrame::NULL]).into(),
+                    RespArray::new(vec![BulkString::from("2-0").into(), RespFrame::NULL]).into(),
+                    entry("3-0", &["f", "v"]),
+                ])
+                .into(),
+            ])
+            .into()])
+            .into()
+        );
+        let deliveries = backend
+            .xpending_range(b"s", "g", StreamId::MIN, StreamId::MAX, 10, None)?
+            .into_iter()
+            .map(|(_, pending)| pending.deliveries)
+            .collect::<Vec<_>>();
+        assert_eq!(deliveries, vec![1, 1, 2]);
+
+        // and can still be acknowledged
+        assert_eq!(
+            run_args(&backend, &["xack", "s", "g", "1-0", "2-0", "3-0"])?,
+            RespFrame::Integer(3)
+        );
+        assert_eq!(backend.xpending(b"s", "g")?.count, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_xadd_ids_increase() -> Result<()> {
+        let backend = Arc::new(crate::Backend::new());
+        let threads = (0..8)
+            .map(|t| {
+                let backend = backend.clone();
+                thread::spawn(move || {
+                    (0..500)
+                        .map(|i| {
+                            let reply = run_args(
+                                &backend,
+                                &[
+                                    "xadd",
+                                    "s",
+                                    "*",
+                                    "thread",
+                                    &t.to_string(),
+                                    "i",
+                                    &i.to_string(),
+                                ],
+                            )
+                            .unwrap();
+                            parse_id(reply)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut all = Vec::new();
+        for thread in threads {
+            let ids = thread.join().unwrap();
+            // each client sees its ids strictly increase
+            assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+            all.extend(ids);
+        }
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), 4000);
+        assert_eq!(
+            run_args(&backend, &["xlen", "s"])?,
+            RespFrame::Integer(4000)
+        );
+
+        // the stream holds them in id order, and every id was handed out once
+        let entries = backend.xrange(b"s", StreamId::MIN, StreamId::MAX, None)?;
+        assert_eq!(
+            entries.into_iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            all
+        );
+        Ok(())
+    }
+}
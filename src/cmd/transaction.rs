@@ -0,0 +1,344 @@
+use super::{
+    extract_key_args, validate_command, Command, CommandError, CommandExecutor, Discard, Exec,
+    Multi, Propagated, Unwatch, Watch, RESP_OK,
+};
+use crate::{Backend, RespArray, RespFrame, SimpleString};
+use bytes::Bytes;
+
+/// The commands a connection queued after a `MULTI`, run back to back by `EXEC`.
+#[derive(Debug, Default)]
+pub struct Transaction {
+    queued: Vec<Queued>,
+    // a command failed to queue, so `EXEC` only discards the transaction
+    aborted: bool,
+}
+
+// a command of a transaction
+#[derive(Debug)]
+struct Queued {
+    cmd: Command,
+    // the keys it may change, see `Command::written_keys`
+    written: Vec<Bytes>,
+    // see `Command::propagated`
+    propagated: Option<Propagated>,
+}
+
+/// The keys a connection `WATCH`es, by database, with the version each had then.
+#[derive(Debug, Default)]
+pub struct WatchedKeys(Vec<(usize, Bytes, u64)>);
+
+impl WatchedKeys {
+    /// Watch `keys` of the database of `backend`, a key watched already keeps its first version.
+    pub fn watch(&mut self, backend: &Backend, keys: &[Bytes]) {
+        for key in keys {
+            if !self
+                .0
+                .iter()
+                .any(|(db, k, _)| *db == backend.index() && k == key)
+            {
+                self.0
+                    .push((backend.index(), key.clone(), backend.key_version(key)));
+            }
+        }
+    }
+
+    /// Stop watching any key, like `UNWATCH`.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    // whether a key changed since it was watched, including by a SWAPDB moving its database
+    fn changed(&self, backend: &Backend) -> Result<bool, CommandError> {
+        for (db, key, version) in &self.0 {
+            let db = backend.select(*db as i64)?;
+            if db.key_version(key) != *version {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl Transaction {
+    /// Queue `cmd`, which may change `written`, to run on `EXEC`, replying `QUEUED`. What
+    /// replicas are sent of it once it ran is `propagated`.
+    pub fn queue(
+        &mut self,
+        cmd: Command,
+        written: Vec<Bytes>,
+        propagated: Option<Propagated>,
+    ) -> RespFrame {
+        self.queued.push(Queued {
+            cmd,
+            written,
+            propagated,
+        });
+        SimpleString::new("QUEUED").into()
+    }
+
+    /// Note that a command could not be queued, which makes `EXEC` fail.
+    pub fn abort(&mut self) {
+        self.aborted = true;
+    }
+
+    /// Run the queued commands with no command of another connection in between. Returns the
+    /// array of their replies, errors included, and the database selected once they ran. If
+    /// any of the `watched` keys changed nothing runs, and the reply is a null array. What
+    /// they wrote is propagated as one transaction too.
+    ///
+    /// Blocking commands don't wait inside a transaction, they reply as if the timeout expired.
+    pub async fn exec(
+        self,
+        backend: &Backend,
+        watched: &WatchedKeys,
+    ) -> Result<(RespFrame, Backend), CommandError> {
+        if self.aborted {
+            return Err(CommandError::ExecAbort);
+        }
+        let _exclusive = backend.execution_lock().write().await;
+        let _sequence = backend.sequence_lock().lock().await;
+        if watched.changed(backend)? {
+            return Ok((RespFrame::NULL_ARRAY, backend.clone()));
+        }
+        let mut db = backend.clone();
+        let mut replies = Vec::with_capacity(self.queued.len());
+        let mut writes = Vec::new();
+        for Queued {
+            cmd,
+            written,
+            propagated,
+        } in self.queued
+        {
+            let index = match &cmd {
+                Command::Select(select) => Some(select.index),
+                _ => None,
+            };
+            let reply = cmd.execute(&db);
+            db.signal_modified(&written);
+            // the command itself only checks the index, the connection keeps the database
+            if let (Ok(_), Some(index)) = (&reply, index) {
+                db = db.select(index)?;
+            }
+            if let (Ok(reply), Some(command)) = (&reply, &propagated) {
+                for command in command.commands(&db, reply) {
+                    writes.push((db.clone(), command));
+                }
+            }
+            replies.push(reply.unwrap_or_else(RespFrame::from));
+        }
+        backend.propagate_transaction(&writes);
+        Ok((RespArray::new(replies).into(), db))
+    }
+}
+
+impl CommandExecutor for Multi {
+    // only acknowledges, queueing the commands is up to the connection
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for Exec {
+    // inside a transaction the connection runs it instead
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Err(CommandError::ExecWithoutMulti)
+    }
+}
+
+impl CommandExecutor for Discard {
+    // inside a transaction the connection runs it instead
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Err(CommandError::DiscardWithoutMulti)
+    }
+}
+
+impl CommandExecutor for Watch {
+    // only acknowledges, the connection keeps what it watches
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for Unwatch {
+    // the same
+    fn execute(self, _backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl TryFrom<RespArray> for Multi {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["multi"])?;
+        Ok(Multi)
+    }
+}
+
+impl TryFrom<RespArray> for Exec {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["exec"])?;
+        Ok(Exec)
+    }
+}
+
+impl TryFrom<RespArray> for Discard {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["discard"])?;
+        Ok(Discard)
+    }
+}
+
+// - WATCH key [key ...]
+impl TryFrom<RespArray> for Watch {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["watch"])?;
+        Ok(Watch {
+            keys: extract_key_args(value, 1)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Unwatch {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["unwatch"])?;
+        Ok(Unwatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{request_args, run_args};
+    use crate::BulkString;
+    use anyhow::Result;
+
+    fn parse(backend: &Backend, args: &[&str]) -> Result<Command, CommandError> {
+        Command::from_request(request_args(args).into(), backend)
+    }
+
+    #[tokio::test]
+    async fn test_exec_runs_the_queue() -> Result<()> {
+        let backend = Backend::new();
+        let mut transaction = Transaction::default();
+        for args in [
+            &["set", "key", "1"][..],
+            &["incr", "key"],
+            &["lpush", "key", "x"],
+            &["select", "2"],
+            &["set", "key", "other"],
+        ] {
+            transaction.queue(parse(&backend, args)?, Vec::new(), None);
+        }
+        // nothing runs before EXEC
+        assert_eq!(backend.get(b"key"), None);
+
+        let (replies, db) = transaction.exec(&backend, &Default::default()).await?;
+        let RespFrame::Array(replies) = replies else {
+            panic!("expected an array");
+        };
+        assert_eq!(replies[0], RESP_OK.clone());
+        assert_eq!(replies[1], RespFrame::Integer(2));
+        // a failing command doesn't stop the ones after it
+        assert!(matches!(replies[2], RespFrame::Error(_)));
+        assert_eq!(replies.len(), 5);
+        assert_eq!(db.index(), 2);
+        assert_eq!(db.get(b"key"), Some(BulkString::from("other").into()));
+        assert_eq!(backend.get(b"key"), Some(BulkString::from("2").into()));
+        Ok(())
+    }
+
+    // a transaction of a single INCR
+    async fn exec_incr(backend: &Backend, watched: &WatchedKeys) -> Result<RespFrame> {
+        let mut transaction = Transaction::default();
+        transaction.queue(parse(backend, &["incr", "counter"])?, Vec::new(), None);
+        Ok(transaction.exec(backend, watched).await?.0)
+    }
+
+    #[tokio::test]
+    async fn test_watched_keys() -> Result<()> {
+        let backend = Backend::new();
+        let null = RespFrame::NULL_ARRAY;
+
+        let mut watched = WatchedKeys::default();
+        watched.watch(&backend, &["counter".into()]);
+        // a key not watched changing doesn't matter
+        backend.signal_modified(&["other".into()]);
+        assert_ne!(exec_incr(&backend, &watched).await?, null);
+        backend.signal_modified(&["counter".into()]);
+        assert_eq!(exec_incr(&backend, &watched).await?, null);
+
+        // neither does a change before watching
+        watched.clear();
+        watched.watch(&backend, &["counter".into()]);
+        assert_ne!(exec_incr(&backend, &watched).await?, null);
+
+        // a flush or a swap changes every key of the databases
+        backend.flush(false);
+        assert_eq!(exec_incr(&backend, &watched).await?, null);
+        watched.clear();
+        watched.watch(&backend, &["counter".into()]);
+        backend.swapdb(0, 1)?;
+        assert_eq!(exec_incr(&backend, &watched).await?, null);
+
+        // and so does expiring
+        watched.clear();
+        backend.set("counter".into(), BulkString::from("1").into());
+        backend.expire(b"counter", 1);
+        watched.watch(&backend, &["counter".into()]);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(backend.get(b"counter"), None);
+        assert_eq!(exec_incr(&backend, &watched).await?, null);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watched_keys_written_before_exec() -> Result<()> {
+        let backend = Backend::new();
+        let null = RespFrame::NULL_ARRAY;
+        run_args(&backend, &["rpush", "list", "a", "b", "c"])?;
+        run_args(&backend, &["set", "counter", "1"])?;
+
+        // a write changing a value in place, between WATCH and EXEC
+        let mut watched = WatchedKeys::default();
+        watched.watch(&backend, &["list".into(), "counter".into()]);
+        run_args(&backend, &["lpop", "list"])?;
+        assert_eq!(exec_incr(&backend, &watched).await?, null);
+
+        // a key watched while a write runs, before it is signalled, still sees the write
+        watched.clear();
+        let frame = RespFrame::from(request_args(&["lpop", "list"]));
+        let written = Command::written_keys(&frame);
+        let cmd = Command::from_request(frame, &backend)?;
+        cmd.execute(&backend)?;
+        watched.watch(&backend, &["list".into()]);
+        backend.signal_modified(&written);
+        assert_eq!(exec_incr(&backend, &watched).await?, null);
+        assert_eq!(
+            run_args(&backend, &["llen", "list"])?,
+            RespFrame::Integer(1)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_aborted_exec() -> Result<()> {
+        let backend = Backend::new();
+        let mut transaction = Transaction::default();
+        transaction.queue(parse(&backend, &["set", "key", "1"])?, Vec::new(), None);
+        assert!(parse(&backend, &["set", "key"]).is_err());
+        transaction.abort();
+        assert!(matches!(
+            transaction.exec(&backend, &Default::default()).await,
+            Err(CommandError::ExecAbort)
+        ));
+        assert_eq!(backend.get(b"key"), None);
+
+        let exec = parse(&backend, &["exec"])?;
+        assert!(exec.execute(&backend).is_err());
+        Ok(())
+    }
+}
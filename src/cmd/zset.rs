@@ -0,0 +1,2026 @@
+use crate::{
+    backend::format_score, Aggregate, BulkString, KeyspaceEvents, LexBound, Limit, RespArray,
+    RespFrame, ScoreBound, ZAddOptions, ZRangeBy,
+};
+
+use super::{
+    extract_args, extract_key_args, extract_key_scan_args, parse_integer, validate_command,
+    CommandError, CommandExecutor, ZAdd, ZCard, ZCount, ZIncrBy, ZInterStore, ZLexCount, ZMScore,
+    ZPopMax, ZPopMin, ZRandMember, ZRange, ZRangeByLex, ZRangeByScore, ZRangeStore, ZRank, ZRem,
+    ZRevRange, ZRevRank, ZScan, ZScore, ZUnionStore,
+};
+use bytes::Bytes;
+
+impl CommandExecutor for ZAdd {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let count = backend.zadd(self.key.clone(), self.pairs, self.options)?;
+        backend.notify_keyspace_event(KeyspaceEvents::ZSET, "zadd", &self.key);
+        Ok(RespFrame::Integer(count))
+    }
+}
+
+impl CommandExecutor for ZScore {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(score_reply(backend.zscore(&self.key, &self.member)?))
+    }
+}
+
+impl CommandExecutor for ZRange {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let members = backend.zrange_by(&self.key, &self.by, self.rev, self.limit)?;
+        Ok(members_reply(members, self.with_scores))
+    }
+}
+
+impl CommandExecutor for ZRevRange {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let members = backend.zrange(&self.key, self.start, self.stop, true)?;
+        Ok(members_reply(members, self.with_scores))
+    }
+}
+
+impl CommandExecutor for ZRangeByScore {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let members = backend.zrange_by_score(&self.key, self.min, self.max, false, self.limit)?;
+        Ok(members_reply(members, self.with_scores))
+    }
+}
+
+impl CommandExecutor for ZCount {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(
+            backend.zcount(&self.key, self.min, self.max)?,
+        ))
+    }
+}
+
+impl CommandExecutor for ZRem {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let removed = backend.zrem(&self.key, &self.members)?;
+        if removed > 0 {
+            backend.notify_keyspace_event(KeyspaceEvents::ZSET, "zrem", &self.key);
+        }
+        Ok(RespFrame::Integer(removed))
+    }
+}
+
+impl CommandExecutor for ZCard {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(backend.zcard(&self.key)?))
+    }
+}
+
+impl CommandExecutor for ZIncrBy {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let score = backend.zincrby(self.key.clone(), self.member, self.delta)?;
+        backend.notify_keyspace_event(KeyspaceEvents::ZSET, "zincr", &self.key);
+        Ok(score_reply(Some(score)))
+    }
+}
+
+impl CommandExecutor for ZRank {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let rank = backend.zrank(&self.key, &self.member)?;
+        Ok(rank_reply(rank, self.with_score))
+    }
+}
+
+impl CommandExecutor for ZRevRank {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let rank = backend.zrevrank(&self.key, &self.member)?;
+        Ok(rank_reply(rank, self.with_score))
+    }
+}
+
+impl CommandExecutor for ZPopMin {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let popped = backend.zpopmin(&self.key, self.count)?;
+        Ok(members_reply(popped, true))
+    }
+}
+
+impl CommandExecutor for ZPopMax {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let popped = backend.zpopmax(&self.key, self.count)?;
+        Ok(members_reply(popped, true))
+    }
+}
+
+impl CommandExecutor for ZRangeByLex {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let members = backend.zrange_by_lex(&self.key, &self.min, &self.max, false, self.limit)?;
+        Ok(members_reply(members, false))
+    }
+}
+
+impl CommandExecutor for ZLexCount {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(
+            backend.zlexcount(&self.key, &self.min, &self.max)?,
+        ))
+    }
+}
+
+impl CommandExecutor for ZUnionStore {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let len = backend.zunionstore(&self.dest, &self.keys, &self.weights, self.aggregate)?;
+        Ok(RespFrame::Integer(len))
+    }
+}
+
+impl CommandExecutor for ZInterStore {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let len = backend.zinterstore(&self.dest, &self.keys, &self.weights, self.aggregate)?;
+        Ok(RespFrame::Integer(len))
+    }
+}
+
+impl CommandExecutor for ZScan {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let pattern = self.pattern.as_deref();
+        let (cursor, members) = backend.zscan(&self.key, self.cursor, pattern, self.count)?;
+        Ok(RespArray::new(vec![
+            BulkString::from(cursor.to_string()).into(),
+            members_reply(members, true),
+        ])
+        .into())
+    }
+}
+
+impl CommandExecutor for ZRangeStore {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let len = backend.zrangestore(&self.dest, &self.src, &self.by, self.rev, self.limit)?;
+        Ok(RespFrame::Integer(len))
+    }
+}
+
+impl CommandExecutor for ZRandMember {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let Some(count) = self.count else {
+            return Ok(match backend.zrandmember(&self.key, 1, true)?.pop() {
+                Some((member, _)) => BulkString::from(member).into(),
+                None => RespFrame::NULL,
+            });
+        };
+
+        // a negative count asks for exactly that many members, allowing repeats
+        let members = backend.zrandmember(&self.key, count.unsigned_abs() as usize, count >= 0)?;
+        Ok(members_reply(members, self.with_scores))
+    }
+}
+
+impl CommandExecutor for ZMScore {
+    fn execute(self, backend: &crate::Backend) -> Result<RespFrame, CommandError> {
+        let scores = backend.zmscore(&self.key, &self.members)?;
+        Ok(RespArray::new(scores.into_iter().map(score_reply).collect::<Vec<_>>()).into())
+    }
+}
+
+// the rank as an integer, or `WITHSCORE` an array of the rank and the score. A missing member is
+// nil, a null array `WITHSCORE`.
+fn rank_reply(rank: Option<(usize, f64)>, with_score: bool) -> RespFrame {
+    match rank {
+        Some((rank, score)) if with_score => RespArray::new(vec![
+            RespFrame::Integer(rank as i64),
+            BulkString::from(format_score(score)).into(),
+        ])
+        .into(),
+        Some((rank, _)) => RespFrame::Integer(rank as i64),
+        None if with_score => RespFrame::NULL_ARRAY,
+        None => RespFrame::NULL,
+    }
+}
+
+// the members as an array, each followed by its score `with_scores`
+fn members_reply(members: Vec<(Bytes, f64)>, with_scores: bool) -> RespFrame {
+    let mut frames = Vec::with_capacity(members.len() * (1 + with_scores as usize));
+    for (member, score) in members {
+        frames.push(BulkString::from(member).into());
+        if with_scores {
+            frames.push(BulkString::from(format_score(score)).into());
+        }
+    }
+    RespArray::new(frames).into()
+}
+
+// a score as a bulk string, nil if there is none
+fn score_reply(score: Option<f64>) -> RespFrame {
+    match score {
+        Some(score) => BulkString::from(format_score(score)).into(),
+        None => RespFrame::NULL,
+    }
+}
+
+impl TryFrom<RespArray> for ZAdd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zadd"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => key.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+
+        let mut options = ZAddOptions::default();
+        while let Some(RespFrame::BulkString(arg)) = args.peek() {
+            match arg.to_ascii_lowercase().as_slice() {
+                b"nx" => options.nx = true,
+                b"xx" => options.xx = true,
+                b"gt" => options.gt = true,
+                b"lt" => options.lt = true,
+                b"ch" => options.ch = true,
+                // the first score
+                _ => break,
+            }
+            args.next();
+        }
+        if options.nx && options.xx {
+            return Err(CommandError::IncompatibleOptions("XX and NX"));
+        }
+        if (options.gt && options.lt) || (options.nx && (options.gt || options.lt)) {
+            return Err(CommandError::IncompatibleOptions("GT, LT, and/or NX"));
+        }
+
+        let args = args.collect::<Vec<_>>();
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err(CommandError::SyntaxError);
+        }
+        // all the scores are checked before anything is added
+        let pairs = args
+            .chunks(2)
+            .map(|pair| match pair {
+                [RespFrame::BulkString(score), RespFrame::BulkString(member)] => {
+                    Ok((parse_score(score)?, member.0.clone()))
+                }
+                _ => Err(CommandError::SyntaxError),
+            })
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        Ok(ZAdd {
+            key,
+            options,
+            pairs,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZScore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zscore"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(member))) => Ok(ZScore {
+                key: key.0,
+                member: member.0,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or member".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for ZRange {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zrange"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => key.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let (by, rev, limit, with_scores) = parse_zrange_args(args)?;
+        Ok(ZRange {
+            key,
+            by,
+            rev,
+            limit,
+            with_scores,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZRangeStore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zrangestore"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (dest, src) = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(dest)), Some(RespFrame::BulkString(src))) => {
+                (dest.0, src.0)
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid destination or source".to_string(),
+                ))
+            }
+        };
+        let (by, rev, limit, with_scores) = parse_zrange_args(args)?;
+        if with_scores {
+            return Err(CommandError::SyntaxError);
+        }
+        Ok(ZRangeStore {
+            dest,
+            src,
+            by,
+            rev,
+            limit,
+        })
+    }
+}
+
+// the `min max [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]` arguments of the
+// unified range commands. With REV, scores and members are given highest first, ranks never are.
+fn parse_zrange_args(
+    mut args: impl Iterator<Item = RespFrame>,
+) -> Result<(ZRangeBy, bool, Limit, bool), CommandError> {
+    let (start, stop) = match (args.next(), args.next()) {
+        (Some(RespFrame::BulkString(start)), Some(RespFrame::BulkString(stop))) => (start, stop),
+        _ => {
+            return Err(CommandError::InvalidArgument(
+                "Invalid min or max".to_string(),
+            ))
+        }
+    };
+    let (mut by_score, mut by_lex, mut rev, mut limit, mut with_scores) =
+        (false, false, false, None, false);
+    while let Some(arg) = args.next() {
+        let RespFrame::BulkString(arg) = arg else {
+            return Err(CommandError::SyntaxError);
+        };
+        match arg.to_ascii_lowercase().as_slice() {
+            b"byscore" => by_score = true,
+            b"bylex" => by_lex = true,
+            b"rev" => rev = true,
+            b"limit" => limit = Some(parse_limit(&mut args)?),
+            b"withscores" => with_scores = true,
+            _ => return Err(CommandError::SyntaxError),
+        }
+    }
+
+    let (min, max) = if rev {
+        (&stop, &start)
+    } else {
+        (&start, &stop)
+    };
+    let by = match (by_score, by_lex) {
+        (true, true) => return Err(CommandError::SyntaxError),
+        (true, false) => ZRangeBy::Score(parse_score_bound(min)?, parse_score_bound(max)?),
+        // like redis, WITHSCORES makes no sense for members that all have the same score
+        (false, true) if with_scores => return Err(CommandError::SyntaxError),
+        (false, true) => ZRangeBy::Lex(parse_lex_bound(min)?, parse_lex_bound(max)?),
+        (false, false) if limit.is_some() => return Err(CommandError::SyntaxError),
+        (false, false) => ZRangeBy::Rank(parse_integer(&start)?, parse_integer(&stop)?),
+    };
+    Ok((by, rev, limit, with_scores))
+}
+
+impl TryFrom<RespArray> for ZRevRange {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, start, stop, with_scores) = extract_rank_range_args(value, "zrevrange")?;
+        Ok(ZRevRange {
+            key,
+            start,
+            stop,
+            with_scores,
+        })
+    }
+}
+
+// the `key start stop [WITHSCORES]` arguments of the range commands by rank
+fn extract_rank_range_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, i64, i64, bool), CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let (key, start, stop) = match (args.next(), args.next(), args.next()) {
+        (
+            Some(RespFrame::BulkString(key)),
+            Some(RespFrame::BulkString(start)),
+            Some(RespFrame::BulkString(stop)),
+        ) => (key.0, parse_integer(&start)?, parse_integer(&stop)?),
+        _ => {
+            return Err(CommandError::InvalidArgument(
+                "Invalid key, start or stop".to_string(),
+            ))
+        }
+    };
+    let with_scores = match (args.next(), args.next()) {
+        (None, _) => false,
+        (Some(RespFrame::BulkString(arg)), None) if arg.eq_ignore_ascii_case(b"withscores") => true,
+        _ => return Err(CommandError::SyntaxError),
+    };
+    Ok((key, start, stop, with_scores))
+}
+
+impl TryFrom<RespArray> for ZRangeByScore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zrangebyscore"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, min, max) = extract_score_range(&mut args)?;
+        let mut with_scores = false;
+        let mut limit = None;
+        while let Some(arg) = args.next() {
+            let RespFrame::BulkString(arg) = arg else {
+                return Err(CommandError::SyntaxError);
+            };
+            match arg.to_ascii_lowercase().as_slice() {
+                b"withscores" => with_scores = true,
+                b"limit" => limit = Some(parse_limit(&mut args)?),
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+        Ok(ZRangeByScore {
+            key,
+            min,
+            max,
+            with_scores,
+            limit,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZCount {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zcount"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, min, max) = extract_score_range(&mut args)?;
+        Ok(ZCount { key, min, max })
+    }
+}
+
+impl TryFrom<RespArray> for ZRem {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zrem"])?;
+
+        let mut members = extract_key_args(value, 1)?;
+        let key = members.remove(0);
+        Ok(ZRem { key, members })
+    }
+}
+
+impl TryFrom<RespArray> for ZScan {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, cursor, pattern, count) = extract_key_scan_args(value, "zscan")?;
+        Ok(ZScan {
+            key,
+            cursor,
+            pattern,
+            count,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZRandMember {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zrandmember"])?;
+        if value.len() > 4 {
+            return Err(CommandError::SyntaxError);
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => key.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let count = match args.next() {
+            Some(RespFrame::BulkString(count)) => Some(parse_integer(&count)?),
+            Some(_) => return Err(CommandError::NotAnInteger),
+            None => None,
+        };
+        let with_scores = match args.next() {
+            Some(RespFrame::BulkString(arg)) if arg.eq_ignore_ascii_case(b"withscores") => true,
+            Some(_) => return Err(CommandError::SyntaxError),
+            None => false,
+        };
+        Ok(ZRandMember {
+            key,
+            count,
+            with_scores,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZMScore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zmscore"])?;
+
+        let mut members = extract_key_args(value, 1)?;
+        let key = members.remove(0);
+        Ok(ZMScore { key, members })
+    }
+}
+
+impl TryFrom<RespArray> for ZCard {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zcard"])?;
+
+        match extract_args(value, 1)?.into_iter().next() {
+            Some(RespFrame::BulkString(key)) => Ok(ZCard { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for ZIncrBy {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zincrby"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(key)),
+                Some(RespFrame::BulkString(delta)),
+                Some(RespFrame::BulkString(member)),
+            ) => Ok(ZIncrBy {
+                key: key.0,
+                delta: parse_score(&delta)?,
+                member: member.0,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key, increment or member".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for ZRank {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, member, with_score) = extract_rank_args(value, "zrank")?;
+        Ok(ZRank {
+            key,
+            member,
+            with_score,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZRevRank {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, member, with_score) = extract_rank_args(value, "zrevrank")?;
+        Ok(ZRevRank {
+            key,
+            member,
+            with_score,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZPopMin {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_zpop_args(value, "zpopmin")?;
+        Ok(ZPopMin { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for ZPopMax {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_zpop_args(value, "zpopmax")?;
+        Ok(ZPopMax { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for ZRangeByLex {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zrangebylex"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, min, max) = extract_lex_range(&mut args)?;
+        let limit = match args.next() {
+            None => None,
+            Some(RespFrame::BulkString(arg)) if arg.eq_ignore_ascii_case(b"limit") => {
+                Some(parse_limit(&mut args)?)
+            }
+            Some(_) => return Err(CommandError::SyntaxError),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::SyntaxError);
+        }
+        Ok(ZRangeByLex {
+            key,
+            min,
+            max,
+            limit,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZLexCount {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zlexcount"])?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, min, max) = extract_lex_range(&mut args)?;
+        Ok(ZLexCount { key, min, max })
+    }
+}
+
+// the `key min max` arguments of the commands taking a range of members
+fn extract_lex_range(
+    args: &mut impl Iterator<Item = RespFrame>,
+) -> Result<(Bytes, LexBound, LexBound), CommandError> {
+    match (args.next(), args.next(), args.next()) {
+        (
+            Some(RespFrame::BulkString(key)),
+            Some(RespFrame::BulkString(min)),
+            Some(RespFrame::BulkString(max)),
+        ) => Ok((key.0, parse_lex_bound(&min)?, parse_lex_bound(&max)?)),
+        _ => Err(CommandError::InvalidArgument(
+            "Invalid key, min or max".to_string(),
+        )),
+    }
+}
+
+// `-`, `+`, or a member prefixed with `[` when inclusive and `(` when exclusive
+fn parse_lex_bound(value: &[u8]) -> Result<LexBound, CommandError> {
+    match value {
+        b"-" => Ok(LexBound::Min),
+        b"+" => Ok(LexBound::Max),
+        [b'[', member @ ..] => Ok(LexBound::Inclusive(member.to_vec())),
+        [b'(', member @ ..] => Ok(LexBound::Exclusive(member.to_vec())),
+        _ => Err(CommandError::InvalidLexBound),
+    }
+}
+
+impl TryFrom<RespArray> for ZUnionStore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (dest, keys, weights, aggregate) = extract_zstore_args(value, "zunionstore")?;
+        Ok(ZUnionStore {
+            dest,
+            keys,
+            weights,
+            aggregate,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZInterStore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (dest, keys, weights, aggregate) = extract_zstore_args(value, "zinterstore")?;
+        Ok(ZInterStore {
+            dest,
+            keys,
+            weights,
+            aggregate,
+        })
+    }
+}
+
+// the `dest numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM|MIN|MAX]`
+// arguments of the store commands, with a weight of 1 for every key by default
+fn extract_zstore_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, Vec<Bytes>, Vec<f64>, Aggregate), CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let (dest, numkeys) = match (args.next(), args.next()) {
+        (Some(RespFrame::BulkString(dest)), Some(RespFrame::BulkString(numkeys))) => {
+            (dest.0, parse_integer(&numkeys)?)
+        }
+        _ => {
+            return Err(CommandError::InvalidArgument(
+                "Invalid destination or numkeys".to_string(),
+            ))
+        }
+    };
+    if numkeys < 1 {
+        return Err(CommandError::NoInputKeys(name));
+    }
+    let numkeys = numkeys as usize;
+    if numkeys > args.len() {
+        return Err(CommandError::SyntaxError);
+    }
+    let keys = args
+        .by_ref()
+        .take(numkeys)
+        .map(|frame| match frame {
+            RespFrame::BulkString(key) => Ok(key.0),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        })
+        .collect::<Result<Vec<_>, CommandError>>()?;
+
+    let mut weights = vec![1.0; numkeys];
+    let mut aggregate = Aggregate::default();
+    while let Some(arg) = args.next() {
+        let RespFrame::BulkString(arg) = arg else {
+            return Err(CommandError::SyntaxError);
+        };
+        match arg.to_ascii_lowercase().as_slice() {
+            b"weights" => {
+                for weight in weights.iter_mut() {
+                    // exactly one weight per key
+                    let Some(RespFrame::BulkString(value)) = args.next() else {
+                        return Err(CommandError::SyntaxError);
+                    };
+                    *weight = parse_score(&value).map_err(|_| CommandError::InvalidWeight)?;
+                }
+            }
+            b"aggregate" => {
+                let Some(RespFrame::BulkString(value)) = args.next() else {
+                    return Err(CommandError::SyntaxError);
+                };
+                aggregate = match value.to_ascii_lowercase().as_slice() {
+                    b"sum" => Aggregate::Sum,
+                    b"min" => Aggregate::Min,
+                    b"max" => Aggregate::Max,
+                    _ => return Err(CommandError::SyntaxError),
+                };
+            }
+            _ => return Err(CommandError::SyntaxError),
+        }
+    }
+    Ok((dest, keys, weights, aggregate))
+}
+
+// the `key member [WITHSCORE]` arguments of the rank commands
+fn extract_rank_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Bytes, Bytes, bool), CommandError> {
+    validate_command(&value, &[name])?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let (key, member) = match (args.next(), args.next()) {
+        (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(member))) => {
+            (key.0, member.0)
+        }
+        _ => {
+            return Err(CommandError::InvalidArgument(
+                "Invalid key or member".to_string(),
+            ))
+        }
+    };
+    let with_score = match (args.next(), args.next()) {
+        (None, _) => false,
+        (Some(RespFrame::BulkString(arg)), None) if arg.eq_ignore_ascii_case(b"withscore") => true,
+        _ => return Err(CommandError::SyntaxError),
+    };
+    Ok((key, member, with_score))
+}
+
+// the `key [count]` arguments of the pop commands, a single member without a count
+fn extract_zpop_args(value: RespArray, name: &'static str) -> Result<(Bytes, usize), CommandError> {
+    validate_command(&value, &[name])?;
+    if value.len() > 3 {
+        return Err(CommandError::SyntaxError);
+    }
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = match args.next() {
+        Some(RespFrame::BulkString(key)) => key.0,
+        _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    };
+    let count = match args.next() {
+        Some(RespFrame::BulkString(count)) => match parse_integer(&count)? {
+            count if count < 0 => return Err(CommandError::NotPositive),
+            count => count as usize,
+        },
+        Some(_) => return Err(CommandError::NotAnInteger),
+        None => 1,
+    };
+    Ok((key, count))
+}
+
+// the `key min max` arguments of the commands taking a range of scores
+fn extract_score_range(
+    args: &mut impl Iterator<Item = RespFrame>,
+) -> Result<(Bytes, ScoreBound, ScoreBound), CommandError> {
+    match (args.next(), args.next(), args.next()) {
+        (
+            Some(RespFrame::BulkString(key)),
+            Some(RespFrame::BulkString(min)),
+            Some(RespFrame::BulkString(max)),
+        ) => Ok((key.0, parse_score_bound(&min)?, parse_score_bound(&max)?)),
+        _ => Err(CommandError::InvalidArgument(
+            "Invalid key, min or max".to_string(),
+        )),
+    }
+}
+
+// a score, exclusive when prefixed with `(`
+fn parse_score_bound(value: &[u8]) -> Result<ScoreBound, CommandError> {
+    let bound = match value.strip_prefix(b"(") {
+        Some(score) => parse_score(score).map(ScoreBound::Exclusive),
+        None => parse_score(value).map(ScoreBound::Inclusive),
+    };
+    bound.map_err(|_| CommandError::InvalidScoreBound)
+}
+
+// the `offset count` following LIMIT
+fn parse_limit(args: &mut impl Iterator<Item = RespFrame>) -> Result<(i64, i64), CommandError> {
+    match (args.next(), args.next()) {
+        (Some(RespFrame::BulkString(offset)), Some(RespFrame::BulkString(count))) => {
+            Ok((parse_integer(&offset)?, parse_integer(&count)?))
+        }
+        _ => Err(CommandError::SyntaxError),
+    }
+}
+
+// unlike other floats, a score can be infinite, e.g. `+inf`
+fn parse_score(value: &[u8]) -> Result<f64, CommandError> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|f| !f.is_nan())
+        .ok_or(CommandError::NotAFloat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::run_args;
+    use crate::{RespDecode, RespEncode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn run(backend: &crate::Backend, request: &[u8]) -> Result<RespFrame> {
+        let mut buf = BytesMut::from(request);
+        let cmd = crate::cmd::Command::try_from(RespFrame::decode(&mut buf)?)?;
+        Ok(cmd.execute(backend)?)
+    }
+
+    fn zscore(backend: &crate::Backend, member: &[u8]) -> Option<f64> {
+        backend.zscore(b"zset", member).unwrap()
+    }
+
+    #[test]
+    fn test_zadd_and_zscore() -> Result<()> {
+        let backend = crate::Backend::new();
+        let request =
+            b"*6\r\n$4\r\nzadd\r\n$4\r\nzset\r\n$1\r\n1\r\n$3\r\none\r\n$3\r\n2.5\r\n$3\r\ntwo\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b":2\r\n");
+        let request = b"*3\r\n$6\r\nzscore\r\n$4\r\nzset\r\n$3\r\ntwo\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b"$3\r\n2.5\r\n");
+        assert_eq!(
+            run_args(&backend, &["zscore", "zset", "three"])?,
+            RespFrame::NULL
+        );
+        assert_eq!(
+            run_args(&backend, &["zscore", "missing", "one"])?,
+            RespFrame::NULL
+        );
+
+        // updating a score adds nothing
+        assert_eq!(
+            run_args(&backend, &["zadd", "zset", "3", "one", "-inf", "low"])?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            run_args(&backend, &["zscore", "zset", "one"])?,
+            BulkString::from("3").into()
+        );
+        assert_eq!(
+            run_args(&backend, &["zscore", "zset", "low"])?,
+            BulkString::from("-inf").into()
+        );
+        assert_eq!(backend.key_type(b"zset"), Some(crate::KeyType::ZSet));
+        Ok(())
+    }
+
+    fn members(members: &[&str]) -> RespFrame {
+        RespArray::new(
+            members
+                .iter()
+                .map(|m| BulkString::from(*m).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+        .into()
+    }
+
+    // a..e, with b, c and d tied
+    fn zset_backend() -> crate::Backend {
+        let backend = crate::Backend::new();
+        run_args(
+            &backend,
+            &[
+                "zadd", "zset", "1", "a", "2", "d", "2", "b", "2", "c", "3.5", "e",
+            ],
+        )
+        .unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_zrange() -> Result<()> {
+        let backend = zset_backend();
+        let request = b"*4\r\n$6\r\nzrange\r\n$4\r\nzset\r\n$1\r\n0\r\n$2\r\n-1\r\n";
+        assert_eq!(
+            run(&backend, request)?.encode(),
+            b"*5\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\nd\r\n$1\r\ne\r\n"
+        );
+        // ties are ordered by member
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "1", "3"])?,
+            members(&["b", "c", "d"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "-2", "-1"])?,
+            members(&["d", "e"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "-100", "0"])?,
+            members(&["a"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "3", "1"])?,
+            members(&[])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "5", "10"])?,
+            members(&[])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "missing", "0", "-1"])?,
+            members(&[])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrevrange() -> Result<()> {
+        let backend = zset_backend();
+        assert_eq!(
+            run_args(&backend, &["zrevrange", "zset", "0", "-1"])?,
+            members(&["e", "d", "c", "b", "a"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrevrange", "zset", "1", "2"])?,
+            members(&["d", "c"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrevrange", "zset", "-1", "-1"])?,
+            members(&["a"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange_withscores() -> Result<()> {
+        let backend = zset_backend();
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "0", "1", "WITHSCORES"])?,
+            members(&["a", "1", "b", "2"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrevrange", "zset", "0", "0", "withscores"])?,
+            members(&["e", "3.5"])
+        );
+        assert!(run_args(&backend, &["zrange", "zset", "0", "1", "scores"]).is_err());
+        assert!(run_args(&backend, &["zrange", "zset", "0", "1", "withscores", "x"]).is_err());
+        assert!(run_args(&backend, &["zrange", "zset", "first", "1"]).is_err());
+
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(run_args(&backend, &["zrange", "string", "0", "-1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_score_bound() {
+        use ScoreBound::{Exclusive, Inclusive};
+        for (bound, expected) in [
+            ("5", Inclusive(5.0)),
+            ("(5", Exclusive(5.0)),
+            ("-2.5", Inclusive(-2.5)),
+            ("(-2.5", Exclusive(-2.5)),
+            ("-inf", Inclusive(f64::NEG_INFINITY)),
+            ("+inf", Inclusive(f64::INFINITY)),
+            ("(+inf", Exclusive(f64::INFINITY)),
+            ("inf", Inclusive(f64::INFINITY)),
+        ] {
+            assert_eq!(parse_score_bound(bound.as_bytes()).unwrap(), expected);
+        }
+        for bound in ["", "(", "((5", "[5", "five", "nan", "5)"] {
+            assert!(parse_score_bound(bound.as_bytes()).is_err(), "{}", bound);
+        }
+    }
+
+    #[test]
+    fn test_zrangebyscore() -> Result<()> {
+        let backend = zset_backend();
+        let request = b"*4\r\n$13\r\nzrangebyscore\r\n$4\r\nzset\r\n$2\r\n(1\r\n$1\r\n2\r\n";
+        assert_eq!(run(&backend, request)?, members(&["b", "c", "d"]));
+        assert_eq!(
+            run_args(
+                &backend,
+                &["zrangebyscore", "zset", "-inf", "+inf", "withscores"]
+            )?,
+            members(&["a", "1", "b", "2", "c", "2", "d", "2", "e", "3.5"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrangebyscore", "zset", "(2", "(3.5"])?,
+            members(&[])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrangebyscore", "zset", "(5", "(5"])?,
+            members(&[])
+        );
+
+        // the limit pages through the members in range
+        assert_eq!(
+            run_args(
+                &backend,
+                &["zrangebyscore", "zset", "2", "+inf", "LIMIT", "1", "2"]
+            )?,
+            members(&["c", "d"])
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "zrangebyscore",
+                    "zset",
+                    "2",
+                    "+inf",
+                    "limit",
+                    "2",
+                    "-1",
+                    "withscores"
+                ]
+            )?,
+            members(&["d", "2", "e", "3.5"])
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &["zrangebyscore", "zset", "-inf", "2", "limit", "-1", "5"]
+            )?,
+            members(&[])
+        );
+
+        for args in [
+            &["zrangebyscore", "zset", "x", "2"][..],
+            &["zrangebyscore", "zset", "0", "2", "limit", "1"],
+            &["zrangebyscore", "zset", "0", "2", "limit", "a", "1"],
+            &["zrangebyscore", "zset", "0", "2", "scores"],
+        ] {
+            assert!(run_args(&backend, args).is_err(), "{:?}", args);
+        }
+        assert_eq!(
+            run_args(&backend, &["zrangebyscore", "zset", "x", "2"])
+                .unwrap_err()
+                .to_string(),
+            "min or max is not a float"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zcount() -> Result<()> {
+        let backend = zset_backend();
+        for (min, max, count) in [
+            ("-inf", "+inf", 5),
+            ("2", "2", 3),
+            ("(1", "3.5", 4),
+            ("(2", "(3.5", 0),
+            ("3", "1", 0),
+        ] {
+            assert_eq!(
+                run_args(&backend, &["zcount", "zset", min, max])?,
+                RespFrame::Integer(count),
+                "{} {}",
+                min,
+                max
+            );
+        }
+        assert_eq!(
+            run_args(&backend, &["zcount", "missing", "-inf", "+inf"])?,
+            RespFrame::Integer(0)
+        );
+        assert!(run_args(&backend, &["zcount", "zset", "0"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrem_and_zcard() -> Result<()> {
+        let backend = zset_backend();
+        let request = b"*2\r\n$5\r\nzcard\r\n$4\r\nzset\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b":5\r\n");
+        assert_eq!(
+            run_args(&backend, &["zrem", "zset", "a", "x", "b", "a"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(
+            run_args(&backend, &["zcard", "zset"])?,
+            RespFrame::Integer(3)
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "0", "-1"])?,
+            members(&["c", "d", "e"])
+        );
+
+        // removing the last members removes the key
+        backend.expire(b"zset", 10_000);
+        assert_eq!(
+            run_args(&backend, &["zrem", "zset", "c", "d", "e"])?,
+            RespFrame::Integer(3)
+        );
+        assert_eq!(backend.key_type(b"zset"), None);
+        assert!(backend.volatile_keys() == 0);
+        assert_eq!(
+            run_args(&backend, &["zcard", "zset"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(&backend, &["zrem", "zset", "a"])?,
+            RespFrame::Integer(0)
+        );
+        assert!(run_args(&backend, &["zrem", "zset"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zincrby() -> Result<()> {
+        let backend = zset_backend();
+        assert_eq!(
+            run_args(&backend, &["zincrby", "zset", "2.5", "a"])?,
+            BulkString::from("3.5").into()
+        );
+        // a new member starts at the increment
+        assert_eq!(
+            run_args(&backend, &["zincrby", "zset", "-1", "new"])?,
+            BulkString::from("-1").into()
+        );
+        // the index follows the new scores
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "0", "-1"])?,
+            members(&["new", "b", "c", "d", "a", "e"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrangebyscore", "zset", "3.5", "3.5"])?,
+            members(&["a", "e"])
+        );
+
+        run_args(&backend, &["zincrby", "zset", "+inf", "a"])?;
+        let err = run_args(&backend, &["zincrby", "zset", "-inf", "a"]).unwrap_err();
+        assert_eq!(err.to_string(), "resulting score is not a number (NaN)");
+        assert_eq!(zscore(&backend, b"a"), Some(f64::INFINITY));
+        assert!(run_args(&backend, &["zincrby", "zset", "one", "a"]).is_err());
+
+        // a failed increment does not leave an empty key behind
+        run_args(&backend, &["zincrby", "inf", "+inf", "a"])?;
+        run_args(&backend, &["zrem", "inf", "a"])?;
+        assert!(backend.zincrby("inf".into(), "a".into(), f64::NAN).is_err());
+        assert_eq!(backend.key_type(b"inf"), None);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_zincrby_concurrently() {
+        let backend = crate::Backend::new();
+        let tasks = (0..16)
+            .map(|i| {
+                let backend = backend.clone();
+                tokio::spawn(async move {
+                    for _ in 0..100 {
+                        backend
+                            .zincrby("zset".into(), "member".into(), i as f64)
+                            .unwrap();
+                        // another member moving around in the same index
+                        backend
+                            .zincrby("zset".into(), format!("other{}", i % 2).into(), 1.0)
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        // 100 times the sum of 0 to 15
+        assert_eq!(zscore(&backend, b"member"), Some(12_000.0));
+        assert_eq!(zscore(&backend, b"other0"), Some(800.0));
+        assert_eq!(
+            backend.zrange(b"zset", 0, -1, false).unwrap(),
+            vec![
+                ("other0".into(), 800.0),
+                ("other1".into(), 800.0),
+                ("member".into(), 12_000.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zrank() -> Result<()> {
+        let backend = zset_backend();
+        let request = b"*3\r\n$5\r\nzrank\r\n$4\r\nzset\r\n$1\r\nc\r\n";
+        assert_eq!(run(&backend, request)?.encode(), b":2\r\n");
+        assert_eq!(
+            run_args(&backend, &["zrevrank", "zset", "c"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(
+            run_args(&backend, &["zrevrank", "zset", "e"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run_args(&backend, &["zrank", "zset", "x"])?,
+            RespFrame::NULL
+        );
+        assert_eq!(
+            run_args(&backend, &["zrank", "missing", "a"])?,
+            RespFrame::NULL
+        );
+
+        let request = b"*4\r\n$5\r\nzrank\r\n$4\r\nzset\r\n$1\r\ne\r\n$9\r\nWITHSCORE\r\n";
+        assert_eq!(
+            run(&backend, request)?.encode(),
+            b"*2\r\n:4\r\n$3\r\n3.5\r\n"
+        );
+        assert_eq!(
+            run_args(&backend, &["zrevrank", "zset", "a", "withscore"])?,
+            RespArray::new(vec![RespFrame::Integer(4), BulkString::from("1").into()]).into()
+        );
+        assert_eq!(
+            run_args(&backend, &["zrank", "zset", "x", "withscore"])?,
+            RespFrame::NULL
+        );
+        assert!(run_args(&backend, &["zrank", "zset", "a", "withscores"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zpopmin_zpopmax() -> Result<()> {
+        let backend = zset_backend();
+        assert_eq!(
+            run_args(&backend, &["zpopmin", "zset"])?,
+            members(&["a", "1"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zpopmax", "zset", "2"])?,
+            members(&["e", "3.5", "d", "2"])
+        );
+        assert_eq!(run_args(&backend, &["zpopmin", "zset", "0"])?, members(&[]));
+        assert_eq!(
+            run_args(&backend, &["zcard", "zset"])?,
+            RespFrame::Integer(2)
+        );
+
+        // popping more than there is empties the key
+        assert_eq!(
+            run_args(&backend, &["zpopmin", "zset", "10"])?,
+            members(&["b", "2", "c", "2"])
+        );
+        assert_eq!(backend.key_type(b"zset"), None);
+        assert_eq!(run_args(&backend, &["zpopmax", "zset"])?, members(&[]));
+
+        assert!(run_args(&backend, &["zpopmin", "zset", "-1"]).is_err());
+        assert!(run_args(&backend, &["zpopmin", "zset", "1", "2"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_lex_bound() {
+        for (bound, expected) in [
+            ("-", LexBound::Min),
+            ("+", LexBound::Max),
+            ("[a", LexBound::Inclusive(b"a".to_vec())),
+            ("(abc", LexBound::Exclusive(b"abc".to_vec())),
+            ("[", LexBound::Inclusive(vec![])),
+            ("((", LexBound::Exclusive(b"(".to_vec())),
+            ("[-", LexBound::Inclusive(b"-".to_vec())),
+        ] {
+            assert_eq!(parse_lex_bound(bound.as_bytes()).unwrap(), expected);
+        }
+        for bound in ["", "a", "--", "+inf", "5", "]a"] {
+            assert!(parse_lex_bound(bound.as_bytes()).is_err(), "{}", bound);
+        }
+    }
+
+    #[test]
+    fn test_zrangebylex() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(
+            &backend,
+            &[
+                "zadd", "zset", "0", "a", "0", "b", "0", "c", "0", "d", "0", "e", "0", "f", "0",
+                "g",
+            ],
+        )?;
+        let request = b"*4\r\n$11\r\nzrangebylex\r\n$4\r\nzset\r\n$1\r\n-\r\n$2\r\n[c\r\n";
+        assert_eq!(run(&backend, request)?, members(&["a", "b", "c"]));
+        assert_eq!(
+            run_args(&backend, &["zrangebylex", "zset", "-", "(c"])?,
+            members(&["a", "b"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrangebylex", "zset", "[aaa", "(g"])?,
+            members(&["b", "c", "d", "e", "f"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrangebylex", "zset", "(b", "(e"])?,
+            members(&["c", "d"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrangebylex", "zset", "(c", "(d"])?,
+            members(&[])
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &["zrangebylex", "zset", "-", "+", "LIMIT", "2", "3"]
+            )?,
+            members(&["c", "d", "e"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrangebylex", "missing", "-", "+"])?,
+            members(&[])
+        );
+
+        let err = run_args(&backend, &["zrangebylex", "zset", "a", "+"]).unwrap_err();
+        assert_eq!(err.to_string(), "min or max not valid string range item");
+        assert!(run_args(&backend, &["zrangebylex", "zset", "-", "+", "limit", "1"]).is_err());
+        assert!(run_args(&backend, &["zrangebylex", "zset", "-", "+", "withscores"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zlexcount() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(
+            &backend,
+            &[
+                "zadd", "zset", "0", "a", "0", "b", "0", "c", "0", "d", "0", "e",
+            ],
+        )?;
+        for (min, max, count) in [
+            ("-", "+", 5),
+            ("[b", "[d", 3),
+            ("(b", "(d", 1),
+            ("(a", "+", 4),
+            ("+", "-", 0),
+        ] {
+            assert_eq!(
+                run_args(&backend, &["zlexcount", "zset", min, max])?,
+                RespFrame::Integer(count),
+                "{} {}",
+                min,
+                max
+            );
+        }
+        assert!(run_args(&backend, &["zlexcount", "zset", "b", "d"]).is_err());
+        Ok(())
+    }
+
+    fn bulk_strings(frame: RespFrame) -> Vec<String> {
+        let RespFrame::Array(frames) = frame else {
+            panic!("expected an array, got {:?}", frame);
+        };
+        frames
+            .0
+            .into_iter()
+            .map(|frame| match frame {
+                RespFrame::BulkString(s) => String::from_utf8(s.to_vec()).unwrap(),
+                frame => panic!("unexpected element {:?}", frame),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_zrandmember() -> Result<()> {
+        let backend = crate::Backend::new();
+        let pairs = (0..10)
+            .map(|i| (i as f64, format!("m{}", i).into()))
+            .collect::<Vec<_>>();
+        backend.zadd("zset".into(), pairs, ZAddOptions::default())?;
+
+        let RespFrame::BulkString(member) = run_args(&backend, &["zrandmember", "zset"])? else {
+            panic!("expected a single member");
+        };
+        assert!(zscore(&backend, &member.0).is_some());
+
+        // a positive count never repeats a member and is capped by the size of the set
+        for (count, expected) in [("5", 5), ("10", 10), ("20", 10)] {
+            let mut members = bulk_strings(run_args(&backend, &["zrandmember", "zset", count])?);
+            assert_eq!(members.len(), expected);
+            members.sort();
+            members.dedup();
+            assert_eq!(members.len(), expected);
+        }
+
+        // a negative count returns exactly that many members, so 30 out of 10 must repeat
+        let members = bulk_strings(run_args(&backend, &["zrandmember", "zset", "-30"])?);
+        assert_eq!(members.len(), 30);
+        assert!(
+            members
+                .iter()
+                .collect::<std::collections::HashSet<_>>()
+                .len()
+                < 30
+        );
+
+        // WITHSCORES follows every member with its own score
+        for count in ["4", "-4"] {
+            let reply = bulk_strings(run_args(
+                &backend,
+                &["zrandmember", "zset", count, "WITHSCORES"],
+            )?);
+            assert_eq!(reply.len(), 8);
+            for pair in reply.chunks(2) {
+                assert_eq!(pair[1], pair[0].trim_start_matches('m'));
+            }
+        }
+
+        assert_eq!(
+            run_args(&backend, &["zrandmember", "missing"])?,
+            RespFrame::NULL
+        );
+        assert_eq!(
+            run_args(&backend, &["zrandmember", "missing", "3"])?,
+            RespArray::new([]).into()
+        );
+        assert!(run_args(&backend, &["zrandmember", "zset", "3", "WITHVALUES"]).is_err());
+        assert!(run_args(&backend, &["zrandmember", "zset", "many"]).is_err());
+        assert!(run_args(&backend, &["zrandmember", "zset", "1", "withscores", "x"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zmscore() -> Result<()> {
+        let backend = zset_backend();
+        let request =
+            b"*5\r\n$7\r\nzmscore\r\n$4\r\nzset\r\n$1\r\ne\r\n$7\r\nmissing\r\n$1\r\na\r\n";
+        assert_eq!(
+            run(&backend, request)?.encode(),
+            b"*3\r\n$3\r\n3.5\r\n_\r\n$1\r\n1\r\n"
+        );
+        assert_eq!(
+            run_args(&backend, &["zmscore", "missing", "a", "b"])?,
+            RespArray::new(vec![RespFrame::NULL, RespFrame::NULL]).into()
+        );
+        assert!(run_args(&backend, &["zmscore", "zset"]).is_err());
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(run_args(&backend, &["zmscore", "string", "a"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zscan_reassembles_zset() -> Result<()> {
+        let backend = crate::Backend::new();
+        let pairs = (0..2000)
+            .map(|i| (i as f64 / 4.0, format!("member{}", i).into()))
+            .collect::<Vec<_>>();
+        backend.zadd("zset".into(), pairs, ZAddOptions::default())?;
+
+        let mut seen = std::collections::HashMap::new();
+        let mut cursor = "0".to_string();
+        loop {
+            let reply = run_args(
+                &backend,
+                &["zscan", "zset", &cursor, "MATCH", "member1*", "COUNT", "50"],
+            )?;
+            let RespFrame::Array(reply) = reply else {
+                panic!("expected an array");
+            };
+            let mut reply = reply.0.into_iter();
+            let (Some(RespFrame::BulkString(next)), Some(entries)) = (reply.next(), reply.next())
+            else {
+                panic!("expected a cursor and the entries");
+            };
+            let entries = match entries {
+                RespFrame::Array(entries) => entries.0,
+                _ => panic!("expected the entries"),
+            };
+            assert!(entries.len() <= 2 * 60);
+            for pair in entries.chunks(2) {
+                let (RespFrame::BulkString(member), RespFrame::BulkString(score)) =
+                    (&pair[0], &pair[1])
+                else {
+                    panic!("expected member score pairs");
+                };
+                seen.insert(String::from_utf8(member.to_vec())?, score.to_vec());
+            }
+            cursor = String::from_utf8(next.to_vec())?;
+            if cursor == "0" {
+                break;
+            }
+        }
+
+        // member1, member10..member19, member100..member199, member1000..member1999
+        assert_eq!(seen.len(), 1 + 10 + 100 + 1000);
+        assert_eq!(seen["member1"], b"0.25");
+        assert_eq!(seen["member1999"], b"499.75");
+
+        assert_eq!(
+            run_args(&backend, &["zscan", "missing", "0"])?,
+            RespArray::new(vec![
+                BulkString::from("0").into(),
+                RespArray::new([]).into()
+            ])
+            .into()
+        );
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(run_args(&backend, &["zscan", "string", "0"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange_by_score_rev() -> Result<()> {
+        let backend = zset_backend();
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "2", "3.5", "BYSCORE"])?,
+            members(&["b", "c", "d", "e"])
+        );
+        // with REV the first bound is the highest one
+        let request = b"*6\r\n$6\r\nzrange\r\n$4\r\nzset\r\n$3\r\n3.5\r\n$1\r\n2\r\n$7\r\nbyscore\r\n$3\r\nrev\r\n";
+        assert_eq!(
+            run(&backend, request)?.encode(),
+            b"*4\r\n$1\r\ne\r\n$1\r\nd\r\n$1\r\nc\r\n$1\r\nb\r\n"
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "2", "3.5", "byscore", "rev"])?,
+            members(&[])
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "zrange",
+                    "zset",
+                    "(3.5",
+                    "(1",
+                    "byscore",
+                    "rev",
+                    "withscores"
+                ]
+            )?,
+            RespArray::new(
+                ["d", "2", "c", "2", "b", "2"]
+                    .into_iter()
+                    .map(|arg| BulkString::from(arg).into())
+                    .collect::<Vec<RespFrame>>()
+            )
+            .into()
+        );
+        assert_eq!(
+            run_args(
+                &backend,
+                &["zrange", "zset", "(3.5", "3.5", "byscore", "rev"]
+            )?,
+            members(&[])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "3.5", "(2", "byscore", "rev"])?,
+            members(&["e"])
+        );
+        // LIMIT pages the reversed range
+        assert_eq!(
+            run_args(
+                &backend,
+                &["zrange", "zset", "+inf", "-inf", "byscore", "rev", "limit", "1", "2"]
+            )?,
+            members(&["d", "c"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "zset", "0", "1", "REV"])?,
+            members(&["e", "d"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange_by_lex() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(
+            &backend,
+            &[
+                "zadd", "lex", "0", "a", "0", "b", "0", "c", "0", "d", "0", "e",
+            ],
+        )?;
+        assert_eq!(
+            run_args(
+                &backend,
+                &["zrange", "lex", "-", "+", "bylex", "limit", "1", "2"]
+            )?,
+            members(&["b", "c"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "lex", "[d", "(a", "bylex", "rev"])?,
+            members(&["d", "c", "b"])
+        );
+        assert_eq!(
+            run_args(&backend, &["zrange", "lex", "(a", "[d", "bylex", "rev"])?,
+            members(&[])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange_errors() {
+        let backend = zset_backend();
+        for args in [
+            &["zrange", "zset", "0", "1", "byscore", "bylex"][..],
+            &["zrange", "zset", "0", "1", "limit", "0", "1"],
+            &["zrange", "zset", "-", "+", "bylex", "withscores"],
+            &["zrange", "zset", "a", "b", "byscore"],
+            &["zrange", "zset", "a", "b", "bylex"],
+            &["zrange", "zset", "0", "1", "byscore", "limit", "0"],
+            &["zrange", "zset", "0", "1", "extra"],
+            &["zrangestore", "out", "zset", "0", "1", "withscores"],
+            &["zrangestore", "out", "zset", "0"],
+        ] {
+            assert!(run_args(&backend, args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_zrangestore() -> Result<()> {
+        let backend = zset_backend();
+        backend.sadd("out", "member")?;
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "zrangestore",
+                    "out",
+                    "zset",
+                    "+inf",
+                    "(1",
+                    "byscore",
+                    "rev",
+                    "limit",
+                    "0",
+                    "2"
+                ]
+            )?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(backend.key_type(b"out"), Some(crate::KeyType::ZSet));
+        assert_eq!(
+            backend.zrange(b"out", 0, -1, false)?,
+            vec![("d".into(), 2.0), ("e".into(), 3.5)]
+        );
+
+        assert_eq!(
+            run_args(&backend, &["zrangestore", "out", "zset", "1", "2"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(
+            backend.zrange(b"out", 0, -1, false)?,
+            vec![("b".into(), 2.0), ("c".into(), 2.0)]
+        );
+
+        // nothing selected deletes the destination
+        assert_eq!(
+            run_args(
+                &backend,
+                &["zrangestore", "out", "zset", "5", "7", "byscore"]
+            )?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(backend.key_type(b"out"), None);
+        assert_eq!(
+            run_args(&backend, &["zrangestore", "out", "missing", "0", "-1"])?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    // z1 = a:1 b:2 c:3, z2 = b:10 c:20 d:30, s = c d e as a plain set
+    fn store_backend() -> crate::Backend {
+        let backend = crate::Backend::new();
+        run_args(&backend, &["zadd", "z1", "1", "a", "2", "b", "3", "c"]).unwrap();
+        run_args(&backend, &["zadd", "z2", "10", "b", "20", "c", "30", "d"]).unwrap();
+        for member in ["c", "d", "e"] {
+            backend.sadd("s", member).unwrap();
+        }
+        backend
+    }
+
+    fn stored(backend: &crate::Backend, key: &[u8]) -> Vec<(Bytes, f64)> {
+        backend.zrange(key, 0, -1, false).unwrap()
+    }
+
+    fn pairs(pairs: &[(&str, f64)]) -> Vec<(Bytes, f64)> {
+        pairs
+            .iter()
+            .map(|(m, s)| (Bytes::copy_from_slice(m.as_bytes()), *s))
+            .collect()
+    }
+
+    #[test]
+    fn test_zunionstore() -> Result<()> {
+        let backend = store_backend();
+        assert_eq!(
+            run_args(&backend, &["zunionstore", "out", "2", "z1", "z2"])?,
+            RespFrame::Integer(4)
+        );
+        assert_eq!(
+            stored(&backend, b"out"),
+            pairs(&[("a", 1.0), ("b", 12.0), ("c", 23.0), ("d", 30.0)])
+        );
+
+        // plain sets count with a score of 1, missing keys as empty
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "zunionstore",
+                    "out",
+                    "3",
+                    "z1",
+                    "s",
+                    "missing",
+                    "weights",
+                    "2",
+                    "0.5",
+                    "7"
+                ]
+            )?,
+            RespFrame::Integer(5)
+        );
+        assert_eq!(
+            stored(&backend, b"out"),
+            pairs(&[("d", 0.5), ("e", 0.5), ("a", 2.0), ("b", 4.0), ("c", 6.5)])
+        );
+
+        for (aggregate, expected) in [
+            ("min", [("a", 1.0), ("b", 2.0), ("c", 3.0), ("d", 30.0)]),
+            ("MAX", [("a", 1.0), ("b", 10.0), ("c", 20.0), ("d", 30.0)]),
+            ("sum", [("a", 1.0), ("b", 12.0), ("c", 23.0), ("d", 30.0)]),
+        ] {
+            run_args(
+                &backend,
+                &[
+                    "zunionstore",
+                    "out",
+                    "2",
+                    "z1",
+                    "z2",
+                    "aggregate",
+                    aggregate,
+                ],
+            )?;
+            assert_eq!(stored(&backend, b"out"), pairs(&expected), "{}", aggregate);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_zinterstore() -> Result<()> {
+        let backend = store_backend();
+        assert_eq!(
+            run_args(&backend, &["zinterstore", "out", "2", "z1", "z2"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(stored(&backend, b"out"), pairs(&[("b", 12.0), ("c", 23.0)]));
+
+        assert_eq!(
+            run_args(
+                &backend,
+                &[
+                    "zinterstore",
+                    "out",
+                    "3",
+                    "z1",
+                    "z2",
+                    "s",
+                    "aggregate",
+                    "max"
+                ]
+            )?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(stored(&backend, b"out"), pairs(&[("c", 20.0)]));
+        run_args(
+            &backend,
+            &[
+                "zinterstore",
+                "out",
+                "2",
+                "s",
+                "z2",
+                "weights",
+                "3",
+                "-1",
+                "aggregate",
+                "min",
+            ],
+        )?;
+        assert_eq!(
+            stored(&backend, b"out"),
+            pairs(&[("d", -30.0), ("c", -20.0)])
+        );
+
+        // an empty intersection deletes the destination
+        assert_eq!(
+            run_args(&backend, &["zinterstore", "out", "2", "z1", "missing"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(backend.key_type(b"out"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zstore_overwrites_destination() -> Result<()> {
+        let backend = store_backend();
+        backend.set("out".into(), BulkString::from("value").into());
+        backend.expire(b"out", 10_000);
+        run_args(&backend, &["zunionstore", "out", "1", "z1"])?;
+        assert_eq!(backend.key_type(b"out"), Some(crate::KeyType::ZSet));
+        assert_eq!(backend.pttl(b"out"), -1);
+
+        // the destination can be one of the inputs
+        run_args(&backend, &["zinterstore", "z1", "2", "z1", "z2"])?;
+        assert_eq!(stored(&backend, b"z1"), pairs(&[("b", 12.0), ("c", 23.0)]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zstore_errors() {
+        let backend = store_backend();
+        backend.set("string".into(), BulkString::from("value").into());
+        for (args, error) in [
+            (
+                &["zunionstore", "out", "0", "z1"][..],
+                "at least 1 input key is needed",
+            ),
+            (&["zunionstore", "out", "3", "z1", "z2"], "syntax error"),
+            (
+                &["zunionstore", "out", "2", "z1", "z2", "weights", "1"],
+                "syntax error",
+            ),
+            (
+                &[
+                    "zunionstore",
+                    "out",
+                    "2",
+                    "z1",
+                    "z2",
+                    "weights",
+                    "1",
+                    "2",
+                    "3",
+                ],
+                "syntax error",
+            ),
+            (
+                &["zinterstore", "out", "1", "z1", "weights", "x"],
+                "weight value is not a float",
+            ),
+            (
+                &["zinterstore", "out", "1", "z1", "aggregate", "avg"],
+                "syntax error",
+            ),
+            (
+                &["zinterstore", "out", "1", "z1", "aggregate"],
+                "syntax error",
+            ),
+            (&["zinterstore", "out", "x", "z1"], "not an integer"),
+            (&["zinterstore", "out", "2", "z1", "string"], "WRONGTYPE"),
+        ] {
+            let err = run_args(&backend, args).unwrap_err().to_string();
+            assert!(err.contains(error), "{:?}: {}", args, err);
+        }
+        assert_eq!(backend.key_type(b"out"), None);
+    }
+
+    #[test]
+    fn test_zadd_duplicate_members() -> Result<()> {
+        let backend = crate::Backend::new();
+        // the member is added once and the last score wins
+        assert_eq!(
+            run_args(&backend, &["zadd", "zset", "1", "a", "2", "a", "3", "a"])?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(zscore(&backend, b"a"), Some(3.0));
+        // with CH every update counts, even of the same member
+        assert_eq!(
+            run_args(&backend, &["zadd", "zset", "ch", "4", "a", "3", "a"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(zscore(&backend, b"a"), Some(3.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zadd_nx_xx() -> Result<()> {
+        let backend = crate::Backend::new();
+        // XX never creates the key
+        assert_eq!(
+            run_args(&backend, &["zadd", "zset", "xx", "1", "a"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(backend.key_type(b"zset"), None);
+
+        run_args(&backend, &["zadd", "zset", "1", "a"])?;
+        assert_eq!(
+            run_args(&backend, &["zadd", "zset", "NX", "5", "a", "2", "b"])?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(zscore(&backend, b"a"), Some(1.0));
+        assert_eq!(zscore(&backend, b"b"), Some(2.0));
+
+        assert_eq!(
+            run_args(&backend, &["zadd", "zset", "xx", "5", "a", "3", "c"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(zscore(&backend, b"a"), Some(5.0));
+        assert_eq!(zscore(&backend, b"c"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zadd_gt_lt() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(&backend, &["zadd", "zset", "5", "a", "5", "b"])?;
+
+        // GT and LT only restrict updates, new members are still added
+        assert_eq!(
+            run_args(
+                &backend,
+                &["zadd", "zset", "gt", "ch", "1", "a", "6", "b", "1", "c"]
+            )?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(zscore(&backend, b"a"), Some(5.0));
+        assert_eq!(zscore(&backend, b"b"), Some(6.0));
+        assert_eq!(zscore(&backend, b"c"), Some(1.0));
+
+        assert_eq!(
+            run_args(&backend, &["zadd", "zset", "lt", "ch", "1", "a", "7", "b"])?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(zscore(&backend, b"a"), Some(1.0));
+        assert_eq!(zscore(&backend, b"b"), Some(6.0));
+
+        // an equal score is neither greater nor lower
+        assert_eq!(
+            run_args(&backend, &["zadd", "zset", "xx", "gt", "ch", "6", "b"])?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zadd_ch() -> Result<()> {
+        let backend = crate::Backend::new();
+        run_args(&backend, &["zadd", "zset", "1", "a", "2", "b"])?;
+        // without CH only the added members count
+        assert_eq!(
+            run_args(&backend, &["zadd", "zset", "10", "a", "3", "c"])?,
+            RespFrame::Integer(1)
+        );
+        // an unchanged score is not a change
+        assert_eq!(
+            run_args(
+                &backend,
+                &["zadd", "zset", "CH", "20", "a", "2", "b", "4", "d"]
+            )?,
+            RespFrame::Integer(2)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zadd_errors() {
+        let backend = crate::Backend::new();
+        for (args, error) in [
+            (&["zadd", "zset", "nx", "xx", "1", "a"][..], "XX and NX"),
+            (&["zadd", "zset", "nx", "gt", "1", "a"], "GT, LT, and/or NX"),
+            (&["zadd", "zset", "gt", "lt", "1", "a"], "GT, LT, and/or NX"),
+            (&["zadd", "zset", "1", "a", "2"], "syntax error"),
+            (&["zadd", "zset", "ch", "1"], "syntax error"),
+            (&["zadd", "zset", "one", "a"], "not a valid float"),
+            (&["zadd", "zset", "nan", "a"], "not a valid float"),
+            (&["zadd", "zset"], "wrong number of arguments"),
+        ] {
+            let err = run_args(&backend, args).unwrap_err().to_string();
+            assert!(err.contains(error), "{:?}: {}", args, err);
+        }
+        // a bad score anywhere means nothing is added
+        assert!(run_args(&backend, &["zadd", "zset", "1", "a", "x", "b"]).is_err());
+        assert_eq!(backend.key_type(b"zset"), None);
+
+        backend.set("string".into(), BulkString::from("value").into());
+        assert!(run_args(&backend, &["zadd", "string", "1", "a"]).is_err());
+        assert!(run_args(&backend, &["zscore", "string", "a"]).is_err());
+    }
+}
@@ -1,5 +1,5 @@
-use anyhow::Result;
-use simple_redis::{network, Backend};
+use anyhow::{Context, Result};
+use simple_redis::{network, Backend, ServerConfig};
 use tokio::net::TcpListener;
 use tracing::{info, warn};
 
@@ -7,24 +7,62 @@ use tracing::{info, warn};
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
-    let addr = "0.0.0.0:6379";
+    // simple-redis [/path/to/redis.conf] [--directive value ...]
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut config = match args.first() {
+        Some(path) if !path.starts_with("--") => {
+            let config = ServerConfig::from_file(path)?;
+            args.remove(0);
+            config
+        }
+        _ => ServerConfig::default(),
+    };
+    config.apply_args(&args)?;
+
+    // like redis, `bind` may list several addresses, only the first one is used here
+    let bind = config.bind.split_whitespace().next().unwrap_or("0.0.0.0");
+    let addr = format!("{}:{}", bind, config.port);
     info!("Simple-Redis-Server is listening on {}", addr);
-    let listener = TcpListener::bind(addr).await?;
+    let listener = TcpListener::bind(&addr).await?;
 
-    let backend = Backend::new();
-    loop {
-        let (stream, raddr) = listener.accept().await?;
-        info!("Accepted connection from: {}", raddr);
-        let cloned_backend = backend.clone();
-        tokio::spawn(async move {
-            match network::stream_handler(stream, cloned_backend).await {
-                Ok(_) => {
-                    info!("Connection from {} exited", raddr);
-                }
-                Err(e) => {
-                    warn!("handle error for {}: {:?}", raddr, e);
-                }
-            }
-        });
+    let backend = Backend::new_with_server_config(config);
+    // like redis, the data is loaded before any client is served: from the append only file,
+    // which has every write since it was turned on, or else from the snapshot
+    let appendonly = backend.config().appendonly;
+    if appendonly {
+        let aof = backend.aof_path();
+        if aof.exists() {
+            let commands = backend
+                .load_aof(&aof)
+                .with_context(|| format!("can't load {}", aof.display()))?;
+            info!("DB loaded from append only file: {} commands", commands);
+        }
+        let fsync = backend.config().appendfsync;
+        backend
+            .start_aof(&aof, fsync)
+            .with_context(|| format!("can't open {}", aof.display()))?;
+    } else {
+        let dump = backend.dump_path();
+        if dump.exists() {
+            let keys = backend
+                .load_from(&dump)
+                .with_context(|| format!("can't load {}", dump.display()))?;
+            info!("DB loaded from disk: {} keys", keys);
+        }
     }
+    backend.spawn_active_expire();
+    let server = network::Server::new(listener, backend);
+
+    // Ctrl-C stops the server like SHUTDOWN NOSAVE
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(async move {
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => shutdown.cancel(),
+            Err(e) => warn!("can't listen for Ctrl-C: {}", e),
+        }
+    });
+
+    server.run().await?;
+    info!("Simple-Redis-Server stopped");
+    Ok(())
 }
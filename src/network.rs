@@ -1,16 +1,30 @@
 use crate::{
-    cmd::{Command, CommandExecutor},
-    Backend, RespDecode, RespEncode, RespError, RespFrame,
+    cmd::{Command, CommandError, Propagated, ReplConf, Transaction, WatchedKeys},
+    Backend, MonitorEvent, PartialRequest, ProtoLimits, RespEncode, RespError, RespFrame,
+    SimpleError, SimpleString,
 };
 use anyhow::Result;
+use bytes::Bytes;
 use futures::SinkExt;
-use tokio::net::TcpStream;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, Notify};
+use tokio::task::JoinSet;
 use tokio_stream::StreamExt;
 use tokio_util::codec::{Decoder, Encoder, Framed};
-use tracing::info;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 
+// decodes under the limits of the configuration as of the last command, and encodes in the
+// protocol the client asked for
 #[derive(Debug)]
-struct RespFrameCodec;
+struct RespFrameCodec {
+    limits: ProtoLimits,
+    protocol: u8,
+    // how far the request being read got
+    partial: PartialRequest,
+}
 
 #[derive(Debug)]
 struct RedisRequest {
@@ -20,44 +34,476 @@ struct RedisRequest {
 
 #[derive(Debug)]
 struct RedisResponse {
-    frame: RespFrame,
+    // a single reply, except for the confirmations of (un)subscribing to several channels
+    frames: Vec<RespFrame>,
+}
+
+// what a connection remembers between its commands, all of which RESET forgets
+#[derive(Debug)]
+struct ConnectionState {
+    // the selected database
+    backend: Backend,
+    // the commands of every other client, after a MONITOR
+    monitor: Option<broadcast::Receiver<MonitorEvent>>,
+    // the commands queued since a MULTI
+    transaction: Option<Transaction>,
+    // the keys whose change makes the next EXEC fail
+    watched: WatchedKeys,
+    // the messages published to its channels, after a SUBSCRIBE
+    messages: Option<mpsc::Receiver<RespFrame>>,
+    // the port a replica says it listens on, before its PSYNC
+    listening_port: Option<u16>,
+    // whether a PSYNC made the connection a replica, fed the replication stream from now on
+    replica: bool,
+}
+
+impl ConnectionState {
+    // the state of a connection that was just made
+    fn new(backend: Backend) -> Self {
+        Self {
+            backend,
+            monitor: None,
+            transaction: None,
+            watched: WatchedKeys::default(),
+            messages: None,
+            listening_port: None,
+            replica: false,
+        }
+    }
+
+    // back to the state it had when it connected: the transaction, the watched keys, MONITOR and
+    // the subscriptions go with the old state, what the server keeps of the client is reset
+    fn reset(&mut self) {
+        self.backend.reset_client();
+        let db = self.backend.select(0).expect("database 0 always exists");
+        *self = Self::new(db);
+    }
+}
+
+/// Serves a backend on a listener until it is shut down, see `shutdown_handle`.
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    backend: Backend,
+}
+
+impl Server {
+    pub fn new(listener: TcpListener, backend: Backend) -> Self {
+        Self { listener, backend }
+    }
+
+    /// What to cancel to stop the server, the same way `SHUTDOWN NOSAVE` does.
+    pub fn shutdown_handle(&self) -> CancellationToken {
+        self.backend.shutdown_token()
+    }
+
+    /// Accept connections until the server is shut down, then wait for the open ones to finish
+    /// the command at hand and close.
+    pub async fn run(self) -> Result<()> {
+        let shutdown = self.backend.shutdown_token();
+        let mut connections = JoinSet::new();
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                // forget the connections that are done
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                accepted = self.listener.accept() => {
+                    let (stream, raddr) = accepted?;
+                    info!("Accepted connection from: {}", raddr);
+                    let backend = self.backend.clone();
+                    connections.spawn(async move {
+                        match stream_handler(stream, backend).await {
+                            Ok(_) => info!("Connection from {} exited", raddr),
+                            Err(e) => warn!("handle error for {}: {:?}", raddr, e),
+                        }
+                    });
+                }
+            }
+        }
+
+        info!("Shutting down, closing {} connections", connections.len());
+        // refuse new connections while the open ones finish
+        drop(self.listener);
+        while connections.join_next().await.is_some() {}
+        self.backend.sync_aof();
+        if self.backend.save_on_shutdown() {
+            match self.backend.save() {
+                Ok(()) => info!("DB saved on disk"),
+                Err(e) => warn!("Saving on shutdown failed, the data is not saved: {}", e),
+            }
+        }
+        Ok(())
+    }
 }
 
 pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
+    let (addr, laddr) = (stream.peer_addr()?, stream.local_addr()?);
+    let (backend, kill) = backend.connect_client(addr.to_string(), laddr.to_string());
+    backend.metrics().connection_opened();
+    let result = serve(stream, &backend, &kill).await;
+    backend.metrics().connection_closed();
+    backend.disconnect_client();
+    result
+}
+
+async fn serve(stream: TcpStream, backend: &Backend, kill: &Notify) -> Result<()> {
+    let shutdown = backend.shutdown_token();
     // how to get a frame from the stream?
-    let mut framed = Framed::new(stream, RespFrameCodec);
+    let limits = backend.proto_limits();
+    let codec = RespFrameCodec {
+        limits,
+        protocol: backend.client_protocol(),
+        partial: PartialRequest::default(),
+    };
+    let mut framed = Framed::new(stream, codec);
+    let mut connection = ConnectionState::new(backend.clone());
     loop {
-        match framed.next().await {
+        framed.codec_mut().limits = backend.proto_limits();
+        // like redis, a subscriber or a monitor waits for what it is sent, it is never idle
+        let idle = backend
+            .idle_timeout()
+            .filter(|_| connection.messages.is_none() && connection.monitor.is_none());
+        // a killed connection closes between commands, after replying to the one at hand
+        let frame = tokio::select! {
+            biased;
+            _ = kill.notified() => return Ok(()),
+            _ = shutdown.cancelled() => return Ok(()),
+            line = next_monitor_line(&mut connection.monitor, backend.client_id()) => {
+                framed.send(SimpleString::new(line).into()).await?;
+                continue;
+            }
+            message = next_message(&mut connection.messages) => {
+                framed.send(message).await?;
+                continue;
+            }
+            frame = framed.next() => frame,
+            _ = idle_for(idle) => {
+                info!("Closing the connection idle for {:?}", idle);
+                return Ok(());
+            }
+        };
+        match frame {
             Some(Ok(frame)) => {
                 info!("Received frame: {:?}", frame);
                 let request = RedisRequest {
                     frame,
-                    backend: backend.clone(),
+                    backend: connection.backend.current(),
+                };
+                let response = tokio::select! {
+                    biased;
+                    response = request_handler(request, &mut connection) => response?,
+                    // a blocked command is given up on shutdown, any other one finishes first
+                    _ = shutdown.cancelled() => return Ok(()),
                 };
-                let response = request_handler(request).await?;
-                info!("Sending response: {:?}", response.frame);
-                framed.send(response.frame).await?;
+                info!("Sending response: {:?}", response.frames);
+                // a HELLO or a RESET replies in the protocol it switches to
+                framed.codec_mut().protocol = backend.client_protocol();
+                for frame in response.frames {
+                    framed.send(frame).await?;
+                }
+                if connection.replica {
+                    return serve_replica(&mut framed, &connection, kill).await;
+                }
             }
-            Some(Err(e)) => return Err(e),
+            // like redis, a frame that can't be read is answered before closing the connection,
+            // nothing after it can be trusted to start where a frame does
+            Some(Err(e)) => match e.downcast_ref::<RespError>() {
+                Some(e) => {
+                    framed.send(protocol_error(e)).await?;
+                    return Ok(());
+                }
+                None => return Err(e),
+            },
             None => return Ok(()),
         }
     }
 }
 
-async fn request_handler(request: RedisRequest) -> Result<RedisResponse> {
+fn protocol_error(e: &RespError) -> RespFrame {
+    let message = match e {
+        RespError::InvalidFrameLength(header) if header.starts_with('$') => {
+            "invalid bulk length".to_string()
+        }
+        RespError::InvalidFrameLength(_) => "invalid multibulk length".to_string(),
+        RespError::InvalidFrame(expected) | RespError::InvalidFrameType(expected) => {
+            expected.clone()
+        }
+        e => e.to_string(),
+    };
+    SimpleError::new(format!("ERR Protocol error: {}", message)).into()
+}
+
+async fn request_handler(
+    request: RedisRequest,
+    connection: &mut ConnectionState,
+) -> Result<RedisResponse> {
     let (frame, backend) = (request.frame, request.backend);
-    let cmd = Command::try_from(frame)?;
-    info!("Executing command: {:?}", cmd);
-    let frame = cmd.execute(&backend);
-    Ok(RedisResponse { frame })
+    let (name, written) = (Command::name_of(&frame), Command::written_keys(&frame));
+    let propagated = Command::propagated(&frame);
+    // the arguments are only kept when the slow log may want them
+    let args = match &frame {
+        RespFrame::Array(args) if backend.slowlog_enabled() => Some(args.clone()),
+        _ => None,
+    };
+    let mut split = false;
+    let cmd = match Command::from_request(frame, &backend) {
+        // a replica only runs the writes of its primary
+        Ok(_) if propagated.is_some() && backend.is_replica() => Err(CommandError::ReadOnly),
+        cmd => cmd,
+    };
+    // an invalid command is reported to the client, the connection stays usable
+    let frame = match cmd {
+        // not even a transaction queues a RESET
+        Ok(cmd @ Command::Reset(_)) => {
+            connection.reset();
+            cmd.execute_async(&backend)
+                .await
+                .unwrap_or_else(RespFrame::from)
+        }
+        // inside a transaction the commands are queued, not run
+        cmd if connection.transaction.is_some() => {
+            transaction_handler(cmd, written, propagated, &backend, connection).await
+        }
+        // the replies to a replica are the snapshot and the stream, see `serve_replica`
+        Ok(Command::Psync(_)) => {
+            connection.replica = true;
+            return Ok(RedisResponse { frames: Vec::new() });
+        }
+        Ok(cmd) => {
+            info!("Executing command: {:?}", cmd);
+            // the command itself only checks the index, the connection keeps the database
+            if let Command::Select(select) = &cmd {
+                if let Ok(db) = backend.select(select.index) {
+                    connection.backend = db;
+                }
+            }
+            if let Command::Monitor(_) = &cmd {
+                connection.monitor.get_or_insert_with(|| backend.monitor());
+            }
+            match &cmd {
+                Command::Multi(_) => connection.transaction = Some(Transaction::default()),
+                Command::Watch(watch) => connection.watched.watch(&backend, &watch.keys),
+                Command::Unwatch(_) => connection.watched.clear(),
+                Command::Subscribe(_) | Command::PSubscribe(_) => {
+                    // without a receiver the subscription fails, and tells why
+                    if connection.messages.is_none() {
+                        connection.messages = backend.subscriber().ok();
+                    }
+                    split = true;
+                }
+                Command::Unsubscribe(_) | Command::PUnsubscribe(_) => split = true,
+                Command::ReplConf(conf) => {
+                    if let Some(port) = conf.listening_port {
+                        connection.listening_port = Some(port);
+                    }
+                }
+                _ => {}
+            }
+            // like redis, the time a command spends blocked is not counted, nor makes it slow
+            let blocking = matches!(
+                cmd,
+                Command::BLPop(_) | Command::BRPop(_) | Command::XRead(_) | Command::Wait(_)
+            );
+            // a write reaches the replicas before the lock is released, for a snapshot taken
+            // for a new one to be either before or after it
+            let propagate = |reply: &Result<RespFrame, CommandError>| {
+                if let (Ok(reply), Some(command)) = (reply, &propagated) {
+                    command.propagate(&backend, reply);
+                }
+            };
+            let start = Instant::now();
+            // a blocked command would hold up every transaction, it only locks what it pops. It
+            // propagates its pop as it pops, or the push serving it does
+            let reply = if blocking {
+                let reply = cmd.execute_async(&backend).await;
+                backend.signal_modified(&written);
+                reply
+            } else if matches!(
+                cmd,
+                Command::Eval(_) | Command::EvalSha(_) | Command::Save(_)
+            ) {
+                // a script runs alone like a transaction, it signals what it writes as it goes.
+                // So does SAVE, for the snapshot to be of a single point in time
+                let _exclusive = backend.execution_lock().write().await;
+                let _sequence = backend.sequence_lock().lock().await;
+                let reply = cmd.execute_async(&backend).await;
+                propagate(&reply);
+                reply
+            } else {
+                let _shared = backend.execution_lock().read().await;
+                // a write is propagated before another one runs, in the order they ran
+                let _sequence = match propagated {
+                    Some(_) => Some(backend.sequence_lock().lock().await),
+                    None => None,
+                };
+                let reply = cmd.execute_async(&backend).await;
+                backend.signal_modified(&written);
+                propagate(&reply);
+                reply
+            };
+            let frame = reply.unwrap_or_else(RespFrame::from);
+            let elapsed = if blocking {
+                Duration::ZERO
+            } else {
+                start.elapsed()
+            };
+            if let Some(name) = name {
+                backend.metrics().command_called(name, elapsed);
+            }
+            if let Some(args) = args.filter(|_| !blocking) {
+                backend.slowlog_record(&args, elapsed);
+            }
+            frame
+        }
+        Err(e) => e.into(),
+    };
+    let frames = match frame {
+        RespFrame::Array(confirmations) if split => confirmations.0,
+        frame => vec![frame],
+    };
+    Ok(RedisResponse { frames })
+}
+
+// feed a replica that sent PSYNC: a snapshot, then every write command, reading its acks until
+// it goes away
+async fn serve_replica(
+    framed: &mut Framed<TcpStream, RespFrameCodec>,
+    connection: &ConnectionState,
+    kill: &Notify,
+) -> Result<()> {
+    let backend = &connection.backend;
+    let shutdown = backend.shutdown_token();
+    let ip = framed.get_ref().peer_addr()?.ip();
+    let port = connection.listening_port.unwrap_or_default();
+    let mut resync = backend.add_replica(format!("{}:{}", ip, port)).await;
+    info!("Replica {}:{} asked for synchronization", ip, port);
+    let fullresync = format!("FULLRESYNC {} {}", resync.replid, resync.offset);
+    framed.send(SimpleString::new(fullresync).into()).await?;
+    // like redis, the snapshot is a bulk string without the CRLF at the end
+    let stream = framed.get_mut();
+    stream
+        .write_all(format!("${}\r\n", resync.snapshot.len()).as_bytes())
+        .await?;
+    stream.write_all(&resync.snapshot).await?;
+    let result = loop {
+        tokio::select! {
+            biased;
+            _ = kill.notified() => break Ok(()),
+            _ = shutdown.cancelled() => break Ok(()),
+            command = resync.commands.recv() => match command {
+                Some(command) => {
+                    if let Err(e) = framed.get_mut().write_all(&command).await {
+                        break Err(e.into());
+                    }
+                }
+                None => break Ok(()),
+            },
+            frame = framed.next() => match frame {
+                Some(Ok(frame)) => {
+                    if let Ok(Command::ReplConf(ReplConf { ack: Some(offset), .. })) =
+                        Command::try_from(frame)
+                    {
+                        backend.replica_ack(resync.id, offset);
+                    }
+                }
+                Some(Err(e)) => break Err(e),
+                None => break Ok(()),
+            },
+        }
+    };
+    backend.remove_replica(resync.id);
+    info!("Replica {}:{} is gone", ip, port);
+    result
+}
+
+// queue the command for EXEC, or run EXEC or DISCARD. A command that can't be queued aborts the
+// transaction
+async fn transaction_handler(
+    cmd: Result<Command, CommandError>,
+    written: Vec<Bytes>,
+    propagated: Option<Propagated>,
+    backend: &Backend,
+    connection: &mut ConnectionState,
+) -> RespFrame {
+    let Some(transaction) = connection.transaction.as_mut() else {
+        return CommandError::ExecWithoutMulti.into();
+    };
+    match cmd {
+        Ok(Command::Multi(_)) => CommandError::NestedMulti.into(),
+        Ok(Command::Watch(_)) => CommandError::WatchInsideMulti.into(),
+        Ok(Command::Discard(_)) => {
+            connection.transaction = None;
+            connection.watched.clear();
+            SimpleString::new("OK").into()
+        }
+        Ok(Command::Exec(_)) => {
+            let transaction = std::mem::take(transaction);
+            connection.transaction = None;
+            let result = transaction.exec(backend, &connection.watched).await;
+            connection.watched.clear();
+            match result {
+                Ok((replies, db)) => {
+                    connection.backend = db;
+                    replies
+                }
+                Err(e) => e.into(),
+            }
+        }
+        Ok(cmd) => transaction.queue(cmd, written, propagated),
+        Err(e) => {
+            transaction.abort();
+            e.into()
+        }
+    }
+}
+
+// done once the client has sent nothing for `timeout`, never without one
+async fn idle_for(timeout: Option<Duration>) {
+    match timeout {
+        Some(timeout) => tokio::time::sleep(timeout).await,
+        None => std::future::pending().await,
+    }
+}
+
+// the next message for a subscribed connection, never for any other one
+async fn next_message(messages: &mut Option<mpsc::Receiver<RespFrame>>) -> RespFrame {
+    let Some(receiver) = messages else {
+        return std::future::pending().await;
+    };
+    match receiver.recv().await {
+        Some(message) => message,
+        None => std::future::pending().await,
+    }
+}
+
+// the next command of another client for a monitoring connection, never for any other one
+async fn next_monitor_line(
+    monitor: &mut Option<broadcast::Receiver<MonitorEvent>>,
+    client: Option<u64>,
+) -> String {
+    let Some(receiver) = monitor else {
+        return std::future::pending().await;
+    };
+    loop {
+        match receiver.recv().await {
+            Ok((from, line)) if from != client => return line,
+            // its own commands, and those it was too slow to see, are skipped
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return std::future::pending().await,
+        }
+    }
 }
 
 impl Encoder<RespFrame> for RespFrameCodec {
     type Error = anyhow::Error;
 
     fn encode(&mut self, item: RespFrame, dst: &mut bytes::BytesMut) -> Result<()> {
-        let encoded = item.encode();
-        dst.extend_from_slice(&encoded);
+        let item = match self.protocol {
+            3 => item,
+            _ => item.into_resp2(),
+        };
+        dst.reserve(item.encoded_len());
+        item.encode_into(dst);
         Ok(())
     }
 }
@@ -67,7 +513,7 @@ impl Decoder for RespFrameCodec {
     type Error = anyhow::Error;
 
     fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<RespFrame>> {
-        match RespFrame::decode(src) {
+        match self.limits.decode_partial(src, &mut self.partial) {
             Ok(frame) => Ok(Some(frame)),
             Err(RespError::NotComplete) => Ok(None),
             Err(e) => Err(e.into()),
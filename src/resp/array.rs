@@ -4,24 +4,22 @@ use bytes::{Buf, BytesMut};
 
 use crate::{RespDecode, RespEncode, RespError, RespFrame};
 
-use super::{calc_total_length, parse_length, BUF_CAP, CRLF_LEN};
+use super::{calc_total_length, encode_header, header_len, null_frame, parse_length, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespArray(pub(crate) Vec<RespFrame>);
 
 // - array: "*<number-of-elements>\r\n<element-1>...<element-n>"
-// - null array: "*-1\r\n"
 impl RespEncode for RespArray {
-    fn encode(self) -> Vec<u8> {
-        if self.len() == 0 {
-            return b"*-1\r\n".to_vec();
+    fn encode_into(&self, buf: &mut BytesMut) {
+        encode_header(buf, '*', self.len());
+        for frame in &self.0 {
+            frame.encode_into(buf);
         }
-        let mut buf = Vec::with_capacity(BUF_CAP);
-        buf.extend_from_slice(&format!("*{}\r\n", self.0.len()).into_bytes());
-        for frame in self.0 {
-            buf.extend_from_slice(&frame.encode());
-        }
-        buf
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len()) + self.iter().map(RespEncode::encoded_len).sum::<usize>()
     }
 }
 
@@ -33,7 +31,7 @@ impl RespDecode for RespArray {
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
         let (end, len) = parse_length(buf, Self::PREFIX)?;
         if len == -1 {
-            return Ok(RespArray::new(Vec::new()));
+            return Err(null_frame("Array"));
         }
         let total_len = calc_total_length(buf, end, len as usize, Self::PREFIX)?;
 
@@ -54,7 +52,7 @@ impl RespDecode for RespArray {
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
         let (end, len) = parse_length(buf, Self::PREFIX)?;
         if len == -1 {
-            return Ok(4);
+            return Err(null_frame("Array"));
         }
         calc_total_length(buf, end, len as usize, Self::PREFIX)
     }
@@ -96,18 +94,38 @@ mod tests {
     }
 
     #[test]
-    fn test_null_array_encode() {
+    fn test_empty_array_encode() {
         let frame: RespFrame = RespArray::new(Vec::new()).into();
-        assert_eq!(frame.encode(), b"*-1\r\n");
+        assert_eq!(frame.encode(), b"*0\r\n");
+    }
+
+    #[test]
+    fn test_empty_array_encode_into() {
+        let mut buf = BytesMut::from(&b"*1\r\n"[..]);
+        let frame = RespArray::new(Vec::new());
+        frame.encode_into(&mut buf);
+        assert_eq!(&buf[..], b"*1\r\n*0\r\n");
+        assert_eq!(frame.encoded_len(), 4);
     }
 
     #[test]
     fn test_null_array_decode() -> Result<()> {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"*-1\r\n");
+        assert_eq!(
+            RespArray::decode(&mut buf),
+            Err(RespError::InvalidFrameType(
+                "expect: Array, got: null".to_string()
+            ))
+        );
+        assert_eq!(RespFrame::decode(&mut buf)?, RespFrame::NULL_ARRAY);
 
-        let frame = RespArray::decode(&mut buf)?;
-        assert_eq!(frame, RespArray::new(Vec::new()));
+        // not to be mistaken for an empty array
+        buf.extend_from_slice(b"*0\r\n");
+        assert_eq!(
+            RespFrame::decode(&mut buf)?,
+            RespArray::new(Vec::new()).into()
+        );
         Ok(())
     }
 
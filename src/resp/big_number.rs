@@ -0,0 +1,94 @@
+use bytes::{Buf, BytesMut};
+
+use crate::{RespDecode, RespEncode, RespError};
+
+use super::{extract_simple_frame_data, preview, CRLF, CRLF_LEN};
+
+/// An integer of any size, kept as its decimal digits.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+pub struct BigNumber(pub(crate) String);
+
+impl BigNumber {
+    /// The number written as `s`, an optional sign then decimal digits. `None` for anything else.
+    pub fn new(s: impl Into<String>) -> Option<Self> {
+        let s = s.into();
+        let digits = s.strip_prefix(['+', '-']).unwrap_or(&s);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some(BigNumber(s))
+    }
+}
+
+// - big number: "(<big number>\r\n"
+impl RespEncode for BigNumber {
+    fn encode_into(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(b"(");
+        buf.extend_from_slice(self.0.as_bytes());
+        buf.extend_from_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        1 + self.0.len() + CRLF_LEN
+    }
+}
+
+impl RespDecode for BigNumber {
+    const PREFIX: &'static str = "(";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
+        let line = &buf[Self::PREFIX.len()..end];
+        let number = std::str::from_utf8(line)
+            .ok()
+            .and_then(BigNumber::new)
+            .ok_or_else(|| {
+                RespError::InvalidFrame(format!("expect: big number, got: {:?}", preview(line)))
+            })?;
+        buf.advance(end + CRLF_LEN);
+        Ok(number)
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
+        Ok(end + CRLF_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_big_number_encode() {
+        let frame: RespFrame = BigNumber::new("3492890328409238509324850943850943825024385")
+            .unwrap()
+            .into();
+        assert_eq!(
+            frame.encode(),
+            b"(3492890328409238509324850943850943825024385\r\n"
+        );
+    }
+
+    #[test]
+    fn test_big_number_decode() -> Result<()> {
+        let mut buf = BytesMut::from(&b"(-3492890328409238509324850943850943825024385\r\n"[..]);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            BigNumber::new("-3492890328409238509324850943850943825024385")
+                .unwrap()
+                .into()
+        );
+
+        let mut buf = BytesMut::from(&b"(12a\r\n"[..]);
+        assert_eq!(
+            BigNumber::decode(&mut buf),
+            Err(RespError::InvalidFrame(
+                "expect: big number, got: \"12a\"".to_string()
+            ))
+        );
+        Ok(())
+    }
+}
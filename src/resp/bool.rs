@@ -6,8 +6,12 @@ use super::extract_fixed_data;
 
 // - boolean: "#<t|f>\r\n"
 impl RespEncode for bool {
-    fn encode(self) -> Vec<u8> {
-        format!("#{}\r\n", if self { "t" } else { "f" }).into_bytes()
+    fn encode_into(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(if *self { b"#t\r\n" } else { b"#f\r\n" });
+    }
+
+    fn encoded_len(&self) -> usize {
+        4
     }
 }
 
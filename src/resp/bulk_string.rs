@@ -1,30 +1,36 @@
 use std::ops::Deref;
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 
 use crate::{RespDecode, RespEncode, RespError};
 
-use super::{parse_length, CRLF_LEN};
+use super::{
+    add_length, encode_header, expected_crlf, header_len, null_frame, parse_length, CRLF, CRLF_LEN,
+};
+
+// the values from this many bytes on keep sharing the memory they were read into, like redis
+// does for big arguments. A smaller one is copied, so that it does not keep the whole read
+// buffer alive for as long as it is stored.
+const SHARED_BULK_LEN: usize = 32 * 1024;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
-pub struct BulkString(pub(crate) Vec<u8>);
+pub struct BulkString(pub(crate) Bytes);
 
 impl BulkString {
     pub fn new(s: impl Into<Vec<u8>>) -> Self {
-        BulkString(s.into())
+        BulkString(Bytes::from(s.into()))
     }
 }
 
 impl RespEncode for BulkString {
-    fn encode(self) -> Vec<u8> {
-        if self.len() == 0 {
-            return b"$-1\r\n".to_vec();
-        }
-        let mut buf = Vec::with_capacity(self.len() + 16);
-        buf.extend_from_slice(&format!("${}\r\n", self.len()).into_bytes());
-        buf.extend_from_slice(&self);
-        buf.extend_from_slice(b"\r\n");
-        buf
+    fn encode_into(&self, buf: &mut BytesMut) {
+        encode_header(buf, '$', self.len());
+        buf.extend_from_slice(self);
+        buf.extend_from_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len()) + self.len() + CRLF_LEN
     }
 }
 
@@ -32,26 +38,48 @@ impl RespDecode for BulkString {
     const PREFIX: &'static str = "$";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-
         if len == -1 {
-            buf.advance(end + CRLF_LEN);
-            return Ok(BulkString::new(Vec::new()));
+            return Err(null_frame("BulkString"));
         }
 
         let remained = &buf[end + CRLF_LEN..];
-        if remained.len() < len as usize + CRLF_LEN {
+        let data_len = add_length(len as usize, CRLF_LEN)?;
+        check_terminator(remained, len as usize)?;
+        if remained.len() < data_len {
             return Err(RespError::NotComplete);
         }
 
         buf.advance(end + CRLF_LEN);
 
-        let data = buf.split_to(len as usize + CRLF_LEN);
-        Ok(BulkString::new(data[..len as usize].to_vec()))
+        let len = len as usize;
+        if len < SHARED_BULK_LEN {
+            let data = Bytes::copy_from_slice(&buf[..len]);
+            buf.advance(data_len);
+            return Ok(BulkString(data));
+        }
+        let data = buf.split_to(data_len).freeze();
+        Ok(BulkString(data.slice(..len)))
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN + len as usize + CRLF_LEN)
+        if len == -1 {
+            return Err(null_frame("BulkString"));
+        }
+        check_terminator(&buf[end + CRLF_LEN..], len as usize)?;
+        add_length(end + 2 * CRLF_LEN, len as usize)
+    }
+}
+
+// whether what arrived of the CRLF after the `len` bytes of the value is one, so that a wrong
+// length is found out without waiting for more
+pub(super) fn check_terminator(data: &[u8], len: usize) -> Result<(), RespError> {
+    let terminator = data.get(len..).unwrap_or_default();
+    let terminator = &terminator[..terminator.len().min(CRLF_LEN)];
+    if CRLF.starts_with(terminator) {
+        Ok(())
+    } else {
+        Err(expected_crlf(terminator))
     }
 }
 
@@ -62,7 +90,7 @@ impl AsRef<[u8]> for BulkString {
 }
 
 impl Deref for BulkString {
-    type Target = Vec<u8>;
+    type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -71,25 +99,37 @@ impl Deref for BulkString {
 
 impl From<&str> for BulkString {
     fn from(s: &str) -> Self {
-        BulkString(s.as_bytes().to_vec())
+        BulkString(Bytes::copy_from_slice(s.as_bytes()))
     }
 }
 
 impl From<String> for BulkString {
     fn from(s: String) -> Self {
-        BulkString(s.into_bytes())
+        BulkString(s.into())
     }
 }
 
 impl From<&[u8]> for BulkString {
     fn from(s: &[u8]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
+    }
+}
+
+impl From<Bytes> for BulkString {
+    fn from(s: Bytes) -> Self {
+        BulkString(s)
+    }
+}
+
+impl From<&Bytes> for BulkString {
+    fn from(s: &Bytes) -> Self {
+        BulkString(s.clone())
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for BulkString {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
     }
 }
 
@@ -107,9 +147,18 @@ mod tests {
     }
 
     #[test]
-    fn test_null_bulk_string_encode() {
+    fn test_empty_bulk_string_encode() {
         let frame: RespFrame = BulkString::new("").into();
-        assert_eq!(frame.encode(), b"$-1\r\n");
+        assert_eq!(frame.encode(), b"$0\r\n\r\n");
+    }
+
+    #[test]
+    fn test_empty_bulk_string_encode_into() {
+        let mut buf = BytesMut::from(&b"*1\r\n"[..]);
+        let frame = BulkString::new("");
+        frame.encode_into(&mut buf);
+        assert_eq!(&buf[..], b"*1\r\n$0\r\n\r\n");
+        assert_eq!(frame.encoded_len(), 6);
     }
 
     #[test]
@@ -131,13 +180,58 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_bulk_string_wrong_terminator() {
+        for (frame, got) in [
+            (&b"$5\r\nhelloXX"[..], "\"XX\""),
+            (b"$5\r\nhello\n\r", "\"\\n\\r\""),
+            // a length shorter than the value
+            (b"$3\r\nhello\r\n", "\"lo\""),
+        ] {
+            assert_eq!(
+                BulkString::decode(&mut BytesMut::from(frame)),
+                Err(RespError::InvalidFrame(format!(
+                    "expect: CRLF, got: {}",
+                    got
+                )))
+            );
+        }
+    }
+
+    #[test]
+    fn test_only_big_bulk_strings_share_the_buffer() -> Result<()> {
+        let big = "x".repeat(SHARED_BULK_LEN);
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"$5\r\nhello\r\n");
+        buf.extend_from_slice(&BulkString::from(big.as_str()).encode());
+        let read = buf.as_ptr_range();
+        let shares = |frame: &BulkString| read.contains(&frame.as_ptr());
+
+        let small = BulkString::decode(&mut buf)?;
+        assert!(!shares(&small));
+        assert_eq!(small, BulkString::new("hello"));
+        let big = BulkString::decode(&mut buf)?;
+        assert!(shares(&big));
+        assert_eq!(big.as_ptr(), read.start.wrapping_add(11 + 8));
+        assert_eq!(big.len(), SHARED_BULK_LEN);
+        Ok(())
+    }
+
     #[test]
     fn test_null_bulk_string_decode() -> Result<()> {
         let mut buf = BytesMut::new();
         buf.extend_from_slice(b"$-1\r\n");
-
-        let frame = BulkString::decode(&mut buf)?;
-        assert_eq!(frame, BulkString::new(""));
+        assert_eq!(
+            BulkString::decode(&mut buf),
+            Err(RespError::InvalidFrameType(
+                "expect: BulkString, got: null".to_string()
+            ))
+        );
+        assert_eq!(RespFrame::decode(&mut buf)?, RespFrame::NULL);
+
+        // not to be mistaken for an empty string
+        buf.extend_from_slice(b"$0\r\n\r\n");
+        assert_eq!(RespFrame::decode(&mut buf)?, BulkString::new("").into());
 
         Ok(())
     }
@@ -1,22 +1,28 @@
-use bytes::BytesMut;
+use bytes::{Buf, BytesMut};
 
 use crate::{RespDecode, RespEncode, RespError};
 
-use super::{extract_simple_frame_data, CRLF_LEN};
+use super::{encode_fmt, extract_simple_frame_data, formatted_len, parse_number, CRLF_LEN};
+use std::fmt;
 
 // - double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
 impl RespEncode for f64 {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(32);
-        let ret = if self.abs() > 1e+8 || self.abs() < 1e-8 {
-            format!(",{:+e}\r\n", self)
-        } else {
-            let sign = if self < 0.0 { "" } else { "+" };
-            format!(",{}{}\r\n", sign, self)
-        };
-
-        buf.extend_from_slice(&ret.into_bytes());
-        buf
+    fn encode_into(&self, buf: &mut BytesMut) {
+        double_args(*self, |args| encode_fmt(buf, args));
+    }
+
+    fn encoded_len(&self) -> usize {
+        double_args(*self, formatted_len)
+    }
+}
+
+// the double the way it is encoded, for `f` to write or measure
+fn double_args<R>(value: f64, f: impl FnOnce(fmt::Arguments) -> R) -> R {
+    if value.abs() > 1e+8 || value.abs() < 1e-8 {
+        f(format_args!(",{:+e}\r\n", value))
+    } else {
+        let sign = if value < 0.0 { "" } else { "+" };
+        f(format_args!(",{}{}\r\n", sign, value))
     }
 }
 
@@ -25,9 +31,9 @@ impl RespDecode for f64 {
     const PREFIX: &'static str = ",";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
         let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        let data = buf.split_to(end + CRLF_LEN);
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-        Ok(s.parse()?)
+        let value = parse_number(&buf[Self::PREFIX.len()..end], "double")?;
+        buf.advance(end + CRLF_LEN);
+        Ok(value)
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
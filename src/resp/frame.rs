@@ -1,8 +1,8 @@
 use crate::{
-    BulkString, RespArray, RespDecode, RespError, RespMap, RespNull, RespSet, SimpleError,
-    SimpleString,
+    BigNumber, BulkString, RespArray, RespDecode, RespEncode, RespError, RespMap, RespNull,
+    RespPush, RespSet, SimpleError, SimpleString, VerbatimString,
 };
-use bytes::BytesMut;
+use bytes::{Buf, BytesMut};
 use enum_dispatch::enum_dispatch;
 
 #[enum_dispatch(RespEncode)]
@@ -16,13 +16,21 @@ pub enum RespFrame {
     Null(RespNull),
     Boolean(bool),
     Double(f64),
+    BigNumber(BigNumber),
+    Verbatim(VerbatimString),
     Map(RespMap),
     Set(RespSet),
+    Push(RespPush),
 }
 
 impl RespDecode for RespFrame {
     const PREFIX: &'static str = "";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        // a RESP2 null starts like a bulk string or an array
+        if let Some(null) = RespNull::resp2(buf) {
+            buf.advance(null.encoded_len());
+            return Ok(null.into());
+        }
         let mut iter = buf.iter().peekable();
         match iter.peek() {
             Some(b'+') => {
@@ -54,6 +62,14 @@ impl RespDecode for RespFrame {
                 let frame = f64::decode(buf)?;
                 Ok(frame.into())
             }
+            Some(b'(') => {
+                let frame = BigNumber::decode(buf)?;
+                Ok(frame.into())
+            }
+            Some(b'=') => {
+                let frame = VerbatimString::decode(buf)?;
+                Ok(frame.into())
+            }
             Some(b'%') => {
                 let frame = RespMap::decode(buf)?;
                 Ok(frame.into())
@@ -62,20 +78,30 @@ impl RespDecode for RespFrame {
                 let frame = RespSet::decode(buf)?;
                 Ok(frame.into())
             }
+            Some(b'>') => {
+                let frame = RespPush::decode(buf)?;
+                Ok(frame.into())
+            }
             None => Err(RespError::NotComplete),
             _ => Err(RespError::InvalidFrameType(format!(
-                "expect_length: unknown frame type: {:?}",
-                buf
+                "unknown frame type: {:?}",
+                super::preview(buf)
             ))),
         }
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        if let Some(null) = RespNull::resp2(buf) {
+            return Ok(null.encoded_len());
+        }
         let mut iter = buf.iter().peekable();
         match iter.peek() {
             Some(b'*') => RespArray::expect_length(buf),
             Some(b'~') => RespSet::expect_length(buf),
             Some(b'%') => RespMap::expect_length(buf),
+            Some(b'>') => RespPush::expect_length(buf),
+            Some(b'=') => VerbatimString::expect_length(buf),
+            Some(b'(') => BigNumber::expect_length(buf),
             Some(b'$') => BulkString::expect_length(buf),
             Some(b':') => i64::expect_length(buf),
             Some(b'+') => SimpleString::expect_length(buf),
@@ -88,6 +114,37 @@ impl RespDecode for RespFrame {
     }
 }
 
+impl RespFrame {
+    /// The frame the way a RESP2 client reads it, which knows none of the RESP3 types: a map is
+    /// the flat array of its keys and values, a set or a push is an array, a null is a null bulk
+    /// string or a null array, a boolean is 0 or 1, and the other ones are bulk strings.
+    pub fn into_resp2(self) -> RespFrame {
+        match self {
+            RespFrame::Array(RespArray(frames))
+            | RespFrame::Set(RespSet(frames))
+            | RespFrame::Push(RespPush(frames)) => RespArray::new(
+                frames
+                    .into_iter()
+                    .map(RespFrame::into_resp2)
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Map(RespMap(map)) => RespArray::new(
+                map.into_iter()
+                    .flat_map(|(key, value)| [BulkString(key).into(), value.into_resp2()])
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Null(null) => null.into_resp2().into(),
+            RespFrame::Boolean(b) => RespFrame::Integer(b as i64),
+            RespFrame::Double(f) => BulkString::from(f.to_string()).into(),
+            RespFrame::BigNumber(n) => BulkString::from(n.0).into(),
+            RespFrame::Verbatim(s) => BulkString(s.data).into(),
+            frame => frame,
+        }
+    }
+}
+
 impl From<&str> for RespFrame {
     fn from(s: &str) -> Self {
         SimpleString(s.to_string()).into()
@@ -108,5 +165,59 @@ impl<const N: usize> From<&[u8; N]> for RespFrame {
 
 #[cfg(test)]
 mod tests {
-    // TODO: Add tests
+    use super::*;
+
+    #[test]
+    fn test_encode_into_appends_encoded_len_bytes() {
+        let mut map = RespMap::new();
+        map.insert("key".into(), RespFrame::Double(-1.5e10));
+        let frames: Vec<RespFrame> = vec![
+            SimpleString::new("OK").into(),
+            SimpleError::new("ERR oops").into(),
+            RespFrame::Integer(-123),
+            BulkString::new("").into(),
+            RespArray::new(vec![b"nested".into(), RespArray::new(Vec::new()).into()]).into(),
+            RespFrame::NULL,
+            true.into(),
+            RespFrame::Double(0.25),
+            map.into(),
+            RespSet::new(vec![RespFrame::Integer(7)]).into(),
+            BigNumber::new("-12345678901234567890").unwrap().into(),
+            VerbatimString::new(*b"txt", "some text").into(),
+            RespPush::new(vec![BulkString::new("message").into()]).into(),
+        ];
+
+        let mut buf = BytesMut::from(&b"before"[..]);
+        for frame in frames {
+            let start = buf.len();
+            frame.encode_into(&mut buf);
+            assert_eq!(buf.len() - start, frame.encoded_len(), "{:?}", frame);
+            assert_eq!(&buf[start..], &frame.encode()[..]);
+        }
+        assert!(buf.starts_with(b"before+OK\r\n-ERR oops\r\n:-123\r\n$0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_into_resp2() {
+        let mut map = RespMap::new();
+        map.insert("b".into(), RespFrame::NULL);
+        map.insert(
+            "a".into(),
+            RespSet::new(vec![true.into(), false.into()]).into(),
+        );
+        let frame: RespFrame = RespPush::new(vec![
+            map.into(),
+            RespFrame::Double(1.5),
+            BigNumber::new("123456789012345678901234567890")
+                .unwrap()
+                .into(),
+            VerbatimString::new(*b"txt", "text").into(),
+        ])
+        .into();
+        assert_eq!(
+            frame.into_resp2().encode(),
+            b"*4\r\n*4\r\n$1\r\na\r\n*2\r\n:1\r\n:0\r\n$1\r\nb\r\n$-1\r\n\
+              $3\r\n1.5\r\n$30\r\n123456789012345678901234567890\r\n$4\r\ntext\r\n"
+        );
+    }
 }
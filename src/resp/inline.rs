@@ -0,0 +1,142 @@
+use super::{BulkString, RespArray, RespError, RespFrame};
+use bytes::BytesMut;
+
+// the longest line an inline command may take, like redis
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// Whether a request starting with `byte` is an inline command, the line of arguments a telnet
+/// session types, rather than a frame.
+pub(crate) fn is_inline(byte: u8) -> bool {
+    !matches!(
+        byte,
+        b'+' | b'-' | b':' | b'$' | b'*' | b'_' | b'#' | b',' | b'(' | b'=' | b'%' | b'~' | b'>'
+    )
+}
+
+/// Decode the inline command the buffer starts with as the array of its arguments, `None` for an
+/// empty line, which is skipped. A line ends at a LF, with or without the CR before it.
+pub(crate) fn decode(buf: &mut BytesMut) -> Result<Option<RespArray>, RespError> {
+    let end = match buf.iter().position(|b| *b == b'\n') {
+        Some(end) if end <= MAX_INLINE_LEN => end,
+        None if buf.len() <= MAX_INLINE_LEN => return Err(RespError::NotComplete),
+        _ => {
+            return Err(RespError::InvalidFrame(
+                "too big inline request".to_string(),
+            ))
+        }
+    };
+    let line = buf.split_to(end + 1);
+    let line = &line[..end];
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let args = split_args(line)
+        .ok_or_else(|| RespError::InvalidFrame("unbalanced quotes in request".to_string()))?;
+    if args.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(RespArray::new(
+        args.into_iter()
+            .map(|arg| BulkString::new(arg).into())
+            .collect::<Vec<RespFrame>>(),
+    )))
+}
+
+/// Split a line into its arguments like redis does: separated by whitespace, in double quotes
+/// with backslash escapes or in single quotes. `None` if a quote is not closed.
+pub(crate) fn split_args(line: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut args = Vec::new();
+    let mut bytes = line.iter().copied().peekable();
+    loop {
+        while bytes.next_if(u8::is_ascii_whitespace).is_some() {}
+        let Some(first) = bytes.next() else {
+            return Some(args);
+        };
+        let mut arg = Vec::new();
+        match first {
+            b'"' => loop {
+                match bytes.next()? {
+                    b'"' => break,
+                    b'\\' => match bytes.next()? {
+                        b'n' => arg.push(b'\n'),
+                        b'r' => arg.push(b'\r'),
+                        b't' => arg.push(b'\t'),
+                        b'x' => {
+                            let hex = [bytes.next()?, bytes.next()?];
+                            let hex = std::str::from_utf8(&hex).ok()?;
+                            arg.push(u8::from_str_radix(hex, 16).ok()?);
+                        }
+                        b => arg.push(b),
+                    },
+                    b => arg.push(b),
+                }
+            },
+            b'\'' => loop {
+                match bytes.next()? {
+                    b'\'' => break,
+                    b'\\' if bytes.peek() == Some(&b'\'') => arg.push(bytes.next()?),
+                    b => arg.push(b),
+                }
+            },
+            b => {
+                arg.push(b);
+                while let Some(b) = bytes.next_if(|b| !b.is_ascii_whitespace()) {
+                    arg.push(b);
+                }
+            }
+        }
+        // a closing quote must end the argument
+        let quoted = matches!(first, b'"' | b'\'');
+        if quoted && bytes.peek().is_some_and(|b| !b.is_ascii_whitespace()) {
+            return None;
+        }
+        args.push(arg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_inline_command_decode() -> Result<()> {
+        let mut buf = BytesMut::from("\r\n\nset key \"a \\x00b\\n\" 'it\\'s'\r\nget key\n");
+        assert_eq!(decode(&mut buf)?, None);
+        assert_eq!(decode(&mut buf)?, None);
+        assert_eq!(
+            decode(&mut buf)?,
+            Some(RespArray::new(vec![
+                BulkString::new("set").into(),
+                BulkString::new("key").into(),
+                BulkString::new(b"a \x00b\n".to_vec()).into(),
+                BulkString::new("it's").into(),
+            ]))
+        );
+        assert_eq!(
+            decode(&mut buf)?,
+            Some(RespArray::new(vec![
+                BulkString::new("get").into(),
+                BulkString::new("key").into(),
+            ]))
+        );
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(b"get ke");
+        assert_eq!(decode(&mut buf), Err(RespError::NotComplete));
+        buf.extend_from_slice(b"y \"open\r\n");
+        assert_eq!(
+            decode(&mut buf),
+            Err(RespError::InvalidFrame(
+                "unbalanced quotes in request".to_string()
+            ))
+        );
+
+        let mut buf = BytesMut::from(&vec![b'a'; MAX_INLINE_LEN + 1][..]);
+        assert_eq!(
+            decode(&mut buf),
+            Err(RespError::InvalidFrame(
+                "too big inline request".to_string()
+            ))
+        );
+        Ok(())
+    }
+}
@@ -1,14 +1,18 @@
-use bytes::BytesMut;
+use bytes::{Buf, BytesMut};
 
 use crate::{RespDecode, RespEncode, RespError};
 
-use super::{extract_simple_frame_data, CRLF_LEN};
+use super::{encode_fmt, extract_simple_frame_data, formatted_len, parse_number, CRLF_LEN};
 
-// - integer: ":[<+|->]<value>\r\n"
+// - integer: ":[<+|->]<value>\r\n", the sign is optional and only written when negative, like
+//   redis does
 impl RespEncode for i64 {
-    fn encode(self) -> Vec<u8> {
-        let sign = if self < 0 { "" } else { "+" };
-        format!(":{}{}\r\n", sign, self).into_bytes()
+    fn encode_into(&self, buf: &mut BytesMut) {
+        encode_fmt(buf, format_args!(":{}\r\n", self));
+    }
+
+    fn encoded_len(&self) -> usize {
+        formatted_len(format_args!(":{}\r\n", self))
     }
 }
 
@@ -16,10 +20,9 @@ impl RespDecode for i64 {
     const PREFIX: &'static str = ":";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
         let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        // split the buffer
-        let data = buf.split_to(end + CRLF_LEN);
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-        Ok(s.parse()?)
+        let value = parse_number(&buf[Self::PREFIX.len()..end], "integer")?;
+        buf.advance(end + CRLF_LEN);
+        Ok(value)
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
@@ -37,7 +40,7 @@ mod tests {
     #[test]
     fn test_integer_encode() {
         let frame: RespFrame = 123.into();
-        assert_eq!(frame.encode(), b":+123\r\n");
+        assert_eq!(frame.encode(), b":123\r\n");
 
         let frame: RespFrame = (-123).into();
         assert_eq!(frame.encode(), b":-123\r\n");
@@ -58,4 +61,17 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_integer_with_garbage_is_invalid() {
+        for frame in [&b":12a3\r\n"[..], b":\r\n", b": 1\r\n", b":1.5\r\n"] {
+            let mut buf = BytesMut::from(frame);
+            assert!(matches!(
+                i64::decode(&mut buf),
+                Err(RespError::InvalidFrame(_))
+            ));
+            // nothing is consumed from the frame
+            assert_eq!(&buf[..], frame);
+        }
+    }
 }
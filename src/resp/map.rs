@@ -1,27 +1,37 @@
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 
-use crate::{RespDecode, RespEncode, RespError, RespFrame, SimpleString};
+use crate::{RespDecode, RespEncode, RespError, RespFrame};
 use std::{
     collections::BTreeMap,
     ops::{Deref, DerefMut},
 };
 
-use super::{calc_total_length, parse_length, BUF_CAP, CRLF_LEN};
+use super::{calc_total_length, encode_header, header_len, parse_length, preview, CRLF, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct RespMap(pub(crate) BTreeMap<String, RespFrame>);
+pub struct RespMap(pub(crate) BTreeMap<Bytes, RespFrame>);
 
 // - map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
-// we only support string key which encode to SimpleString
+// we only support string keys, which encode to bulk strings
 impl RespEncode for RespMap {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(BUF_CAP);
-        buf.extend_from_slice(&format!("%{}\r\n", self.len()).into_bytes());
-        for (key, value) in self.0 {
-            buf.extend_from_slice(&SimpleString::new(key).encode());
-            buf.extend_from_slice(&value.encode());
+    fn encode_into(&self, buf: &mut BytesMut) {
+        encode_header(buf, '%', self.len());
+        for (key, value) in &self.0 {
+            encode_header(buf, '$', key.len());
+            buf.extend_from_slice(key);
+            buf.extend_from_slice(CRLF);
+            value.encode_into(buf);
         }
-        buf
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len())
+            + self
+                .iter()
+                .map(|(key, value)| {
+                    header_len(key.len()) + key.len() + CRLF_LEN + value.encoded_len()
+                })
+                .sum::<usize>()
     }
 }
 
@@ -40,9 +50,18 @@ impl RespDecode for RespMap {
 
         let mut frames = RespMap::new();
         for _ in 0..len {
-            let key = SimpleString::decode(buf)?;
+            let key = match RespFrame::decode(buf)? {
+                RespFrame::BulkString(key) => key.0,
+                RespFrame::SimpleString(key) => Bytes::from(key.0),
+                key => {
+                    return Err(RespError::InvalidFrameType(format!(
+                        "expect: map key string, got: {:?}",
+                        preview(&key.encode())
+                    )))
+                }
+            };
             let value = RespFrame::decode(buf)?;
-            frames.insert(key.0, value);
+            frames.insert(key, value);
         }
 
         Ok(frames)
@@ -67,7 +86,7 @@ impl Default for RespMap {
 }
 
 impl Deref for RespMap {
-    type Target = BTreeMap<String, RespFrame>;
+    type Target = BTreeMap<Bytes, RespFrame>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -90,33 +109,35 @@ mod tests {
     #[test]
     fn test_map_encode() {
         let mut map = RespMap::new();
-        map.insert(
-            "hello".to_string(),
-            BulkString::new("world".to_string()).into(),
-        );
-        map.insert("foo".to_string(), (-123456.789).into());
+        map.insert("hello".into(), BulkString::new("world".to_string()).into());
+        map.insert("foo".into(), (-123456.789).into());
 
         let frame: RespFrame = map.into();
         assert_eq!(
             &frame.encode(),
-            b"%2\r\n+foo\r\n,-123456.789\r\n+hello\r\n$5\r\nworld\r\n"
+            b"%2\r\n$3\r\nfoo\r\n,-123456.789\r\n$5\r\nhello\r\n$5\r\nworld\r\n"
         );
     }
 
     #[test]
     fn test_map_decode() -> Result<()> {
         let mut buf = BytesMut::new();
-        buf.extend_from_slice(b"%2\r\n+hello\r\n$5\r\nworld\r\n+foo\r\n$3\r\nbar\r\n");
+        buf.extend_from_slice(b"%2\r\n+hello\r\n$5\r\nworld\r\n$3\r\nfoo\r\n$3\r\nbar\r\n");
 
         let frame = RespMap::decode(&mut buf)?;
         let mut map = RespMap::new();
-        map.insert(
-            "hello".to_string(),
-            BulkString::new(b"world".to_vec()).into(),
-        );
-        map.insert("foo".to_string(), BulkString::new(b"bar".to_vec()).into());
+        map.insert("hello".into(), BulkString::new(b"world".to_vec()).into());
+        map.insert("foo".into(), BulkString::new(b"bar".to_vec()).into());
         assert_eq!(frame, map);
 
+        buf.extend_from_slice(b"%1\r\n:1\r\n:2\r\n");
+        assert_eq!(
+            RespMap::decode(&mut buf),
+            Err(RespError::InvalidFrameType(
+                "expect: map key string, got: \":1\\r\\n\"".to_string()
+            ))
+        );
+
         Ok(())
     }
 }
@@ -1,31 +1,54 @@
 mod array;
+mod big_number;
 mod bool;
 mod bulk_string;
 mod double;
 mod frame;
+mod inline;
 mod integer;
 mod map;
 mod null;
+mod push;
 mod set;
 mod simple_error;
 mod simple_string;
+mod verbatim_string;
 
 use bytes::{Buf, BytesMut};
 use enum_dispatch::enum_dispatch;
+use std::cell::Cell;
+use std::fmt::{self, Write};
 use thiserror::Error;
 
-const BUF_CAP: usize = 4096;
 const CRLF: &[u8] = b"\r\n";
 const CRLF_LEN: usize = CRLF.len();
+// the longest a length header can be: a prefix, the digits of the largest length and the CRLF
+const MAX_LENGTH_HEADER: usize = 1 + 20 + CRLF_LEN;
 
+pub(crate) use self::inline::split_args;
 pub use self::{
-    array::RespArray, bulk_string::BulkString, frame::RespFrame, map::RespMap, null::RespNull,
-    set::RespSet, simple_error::SimpleError, simple_string::SimpleString,
+    array::RespArray, big_number::BigNumber, bulk_string::BulkString, frame::RespFrame,
+    map::RespMap, null::RespNull, push::RespPush, set::RespSet, simple_error::SimpleError,
+    simple_string::SimpleString, verbatim_string::VerbatimString,
 };
 
 #[enum_dispatch]
 pub trait RespEncode {
-    fn encode(self) -> Vec<u8>;
+    /// Append the frame to `buf`, which grows as needed.
+    fn encode_into(&self, buf: &mut BytesMut);
+
+    /// The number of bytes `encode_into` appends, to reserve them at once.
+    fn encoded_len(&self) -> usize;
+
+    /// The frame encoded into a buffer of its own.
+    fn encode(self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut buf = BytesMut::with_capacity(self.encoded_len());
+        self.encode_into(&mut buf);
+        buf.into()
+    }
 }
 
 pub trait RespDecode: Sized {
@@ -40,8 +63,9 @@ pub enum RespError {
     InvalidFrame(String),
     #[error("Invalid frame type: {0}")]
     InvalidFrameType(String),
+    /// A length header that is not a number, is negative or is over the limits, as it was sent.
     #[error("Invalid frame length: {0}")]
-    InvalidFrameLength(isize),
+    InvalidFrameLength(String),
     #[error("Frame is not complete")]
     NotComplete,
 
@@ -53,20 +77,162 @@ pub enum RespError {
     ParseFloatError(#[from] std::num::ParseFloatError),
 }
 
+/// The largest lengths a frame may announce, and how deep it may nest. A frame over them is
+/// refused right away, rather than waited for until that many bytes arrive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtoLimits {
+    /// The most bytes of a bulk string.
+    pub max_bulk_len: usize,
+    /// The most elements of an array, a set or a map.
+    pub max_multibulk_len: usize,
+    /// The most levels of aggregates nested in one another, the outermost included.
+    pub max_depth: usize,
+}
+
+impl Default for ProtoLimits {
+    // the defaults of redis
+    fn default() -> Self {
+        Self {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_multibulk_len: i32::MAX as usize,
+            max_depth: 128,
+        }
+    }
+}
+
+thread_local! {
+    // the limits of the decode running on this thread, see `ProtoLimits::decode`
+    static LIMITS: Cell<ProtoLimits> = Cell::new(ProtoLimits::default());
+    // how many aggregates the length being computed on this thread is nested in
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// How far the decode of a request that did not arrive whole yet got, so that the next decode
+/// of the same buffer, grown since, goes on from there instead of looking at every byte again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PartialRequest {
+    // the bytes of the array header and of the elements that arrived whole, and how many
+    // elements they are
+    len: usize,
+    elements: usize,
+}
+
+impl PartialRequest {
+    // look at the elements of the array `buf` starts with from where the last look stopped,
+    // until it is whole. The other frames are not sent in pieces big enough to bother.
+    fn scan(&mut self, buf: &[u8]) -> Result<(), RespError> {
+        if !buf.starts_with(RespArray::PREFIX.as_bytes()) {
+            return Ok(());
+        }
+        let (end, len) = parse_length(buf, RespArray::PREFIX)?;
+        let Ok(len) = usize::try_from(len) else {
+            return Ok(());
+        };
+        if self.len == 0 {
+            self.len = end + CRLF_LEN;
+        }
+        nested(|| {
+            while self.elements < len {
+                let data = buf.get(self.len..).ok_or(RespError::NotComplete)?;
+                let element = RespFrame::expect_length(data)?;
+                if data.len() < element {
+                    return Err(RespError::NotComplete);
+                }
+                self.len = add_length(self.len, element)?;
+                self.elements += 1;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ProtoLimits {
+    /// Decode a request like `RespFrame::decode`, under these limits instead of the default ones.
+    /// A request that doesn't start like a frame is an inline command, the way telnet sends them,
+    /// decoded as the array of its arguments.
+    pub fn decode(self, buf: &mut BytesMut) -> Result<RespFrame, RespError> {
+        self.decode_partial(buf, &mut PartialRequest::default())
+    }
+
+    /// Decode a request like `decode`, going on from `partial` for a buffer that grew since the
+    /// last decode found it not complete, as a connection reading a big request in pieces does.
+    pub fn decode_partial(
+        self,
+        buf: &mut BytesMut,
+        partial: &mut PartialRequest,
+    ) -> Result<RespFrame, RespError> {
+        loop {
+            match buf.first() {
+                Some(b) if inline::is_inline(*b) => {
+                    if let Some(args) = inline::decode(buf)? {
+                        return Ok(args.into());
+                    }
+                }
+                Some(_) => {
+                    let previous = LIMITS.replace(self);
+                    let result = partial.scan(buf).and_then(|()| RespFrame::decode(buf));
+                    LIMITS.set(previous);
+                    // what comes next is another request
+                    if result != Err(RespError::NotComplete) {
+                        *partial = PartialRequest::default();
+                    }
+                    return result;
+                }
+                None => return Err(RespError::NotComplete),
+            }
+        }
+    }
+}
+
 // utility functions
+
+// append `<prefix><len>\r\n`, how the aggregates and the bulk strings start
+fn encode_header(buf: &mut BytesMut, prefix: char, len: usize) {
+    encode_fmt(buf, format_args!("{}{}\r\n", prefix, len));
+}
+
+fn header_len(len: usize) -> usize {
+    formatted_len(format_args!("{}\r\n", len)) + 1
+}
+
+// writing into a `BytesMut` can't fail, it grows instead
+fn encode_fmt(buf: &mut BytesMut, args: fmt::Arguments) {
+    buf.write_fmt(args).expect("a BytesMut grows to fit");
+}
+
+// how long `args` are once formatted, without formatting them anywhere
+fn formatted_len(args: fmt::Arguments) -> usize {
+    struct Counter(usize);
+
+    impl Write for Counter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    counter
+        .write_fmt(args)
+        .expect("counting the bytes can't fail");
+    counter.0
+}
+
 fn extract_fixed_data(
     buf: &mut BytesMut,
     expect: &str,
     expect_type: &str,
 ) -> Result<(), RespError> {
-    if buf.len() < expect.len() {
+    // only waited for while what arrived may still be the frame
+    if buf.len() < expect.len() && expect.as_bytes().starts_with(buf) {
         return Err(RespError::NotComplete);
     }
 
     if !buf.starts_with(expect.as_bytes()) {
         return Err(RespError::InvalidFrameType(format!(
             "expect: {}, got: {:?}",
-            expect_type, buf
+            expect_type,
+            preview(buf)
         )));
     }
 
@@ -82,65 +248,142 @@ fn extract_simple_frame_data(buf: &[u8], prefix: &str) -> Result<usize, RespErro
     if !buf.starts_with(prefix.as_bytes()) {
         return Err(RespError::InvalidFrameType(format!(
             "expect: SimpleString({}), got: {:?}",
-            prefix, buf
+            prefix,
+            preview(buf)
         )));
     }
 
-    let end = find_crlf(buf, 1).ok_or(RespError::NotComplete)?;
-
-    println!("find_crlf: {}", end);
-    Ok(end)
+    find_crlf(buf)
 }
 
-// find nth CRLF in the buffer
-fn find_crlf(buf: &[u8], nth: usize) -> Option<usize> {
-    let mut count = 0;
-    for i in 1..buf.len() - 1 {
-        if buf[i] == b'\r' && buf[i + 1] == b'\n' {
-            count += 1;
-            if count == nth {
-                return Some(i);
-            }
-        }
+// the CRLF ending the line the buffer starts with. A line has no other CR or LF, a bare one is
+// an invalid frame rather than part of it.
+fn find_crlf(buf: &[u8]) -> Result<usize, RespError> {
+    let end = buf
+        .iter()
+        .skip(1)
+        .position(|b| *b == b'\r' || *b == b'\n')
+        .map(|i| i + 1)
+        .ok_or(RespError::NotComplete)?;
+    match &buf[end..] {
+        [b'\r', b'\n', ..] => Ok(end),
+        [b'\r'] => Err(RespError::NotComplete),
+        _ => Err(expected_crlf(&buf[end..])),
     }
+}
+
+// what the CRLF ending a frame was expected in place of
+fn expected_crlf(got: &[u8]) -> RespError {
+    RespError::InvalidFrame(format!(
+        "expect: CRLF, got: {:?}",
+        preview(&got[..got.len().min(CRLF_LEN)])
+    ))
+}
+
+// the start of a frame to show in an error, however big the frame is
+fn preview(buf: &[u8]) -> String {
+    String::from_utf8_lossy(&buf[..buf.len().min(16)]).into_owned()
+}
 
-    None
+// the number on the line of a frame, an invalid frame if it is not one
+fn parse_number<T: std::str::FromStr>(line: &[u8], expect: &str) -> Result<T, RespError> {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| {
+            RespError::InvalidFrame(format!("expect: {}, got: {:?}", expect, preview(line)))
+        })
 }
 
+// the end of the length header and the length, -1 for a null bulk string or array. Fails for a
+// length that is not a number, is negative otherwise or is over the limits of `ProtoLimits`.
 fn parse_length(buf: &[u8], prefix: &str) -> Result<(usize, isize), RespError> {
-    let end = extract_simple_frame_data(buf, prefix)?;
-    let s = String::from_utf8_lossy(&buf[prefix.len()..end]);
-    Ok((end, s.parse()?))
+    let end = match extract_simple_frame_data(buf, prefix) {
+        // a header this long can't be a length, whatever comes next
+        Err(RespError::NotComplete) if buf.len() > MAX_LENGTH_HEADER => {
+            return Err(invalid_length(&buf[..MAX_LENGTH_HEADER]));
+        }
+        result => result?,
+    };
+    let limits = LIMITS.get();
+    let (max, nullable) = match prefix {
+        "$" => (limits.max_bulk_len, true),
+        "=" => (limits.max_bulk_len, false),
+        "*" => (limits.max_multibulk_len, true),
+        _ => (limits.max_multibulk_len, false),
+    };
+    let len = std::str::from_utf8(&buf[prefix.len()..end])
+        .ok()
+        .and_then(|s| s.parse::<isize>().ok())
+        .filter(|len| match usize::try_from(*len) {
+            Ok(len) => len <= max,
+            Err(_) => *len == -1 && nullable,
+        })
+        .ok_or_else(|| invalid_length(&buf[..end]))?;
+    Ok((end, len))
+}
+
+// a null where a frame of `expect_type` was expected, which is a `RespNull` rather than one
+fn null_frame(expect_type: &str) -> RespError {
+    RespError::InvalidFrameType(format!("expect: {}, got: null", expect_type))
+}
+
+fn invalid_length(header: &[u8]) -> RespError {
+    RespError::InvalidFrameLength(String::from_utf8_lossy(header).into_owned())
+}
+
+// add up the lengths of a frame, which can't overflow for a frame that fits in memory, and only
+// does for a made up one
+fn add_length(total: usize, len: usize) -> Result<usize, RespError> {
+    total
+        .checked_add(len)
+        .ok_or_else(|| RespError::InvalidFrameLength(len.to_string()))
+}
+
+// run `f` one level of aggregates deeper, an invalid frame past `ProtoLimits::max_depth` so
+// that a frame nested without end can't exhaust the stack
+fn nested<T>(f: impl FnOnce() -> Result<T, RespError>) -> Result<T, RespError> {
+    let max_depth = LIMITS.get().max_depth;
+    let depth = DEPTH.get() + 1;
+    if depth > max_depth {
+        return Err(RespError::InvalidFrame(format!(
+            "nested deeper than {} levels",
+            max_depth
+        )));
+    }
+    DEPTH.set(depth);
+    let result = f();
+    DEPTH.set(depth - 1);
+    result
 }
 
 fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result<usize, RespError> {
     let mut total = end + CRLF_LEN;
     let mut data = &buf[total..];
     match prefix {
-        "*" | "~" => {
+        "*" | "~" | ">" => nested(|| {
             // find nth CRLF in the buffer, for array and set, we need to find 1 CRLF for each element
             for _ in 0..len {
                 let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
-                total += len;
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
+                total = add_length(total, len)?;
             }
             Ok(total)
-        }
-        "%" => {
+        }),
+        "%" => nested(|| {
             // find nth CRLF in the buffer. For map, we need to find 2 CRLF for each key-value pair
             for _ in 0..len {
-                let len = SimpleString::expect_length(data)?;
-
-                data = &data[len..];
-                total += len;
+                let len = RespFrame::expect_length(data)?;
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
+                total = add_length(total, len)?;
 
                 let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
-                total += len;
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
+                total = add_length(total, len)?;
             }
             Ok(total)
-        }
-        _ => Ok(len + CRLF_LEN),
+        }),
+        _ => add_length(len, CRLF_LEN),
     }
 }
 
@@ -161,6 +404,206 @@ mod tests {
         let ret = calc_total_length(buf, end, len as usize, "*");
         assert_eq!(ret.unwrap_err(), RespError::NotComplete);
 
+        // cut in the middle of an element
+        for (buf, prefix) in [
+            (&b"*2\r\n$3\r\nset\r\n$5\r\nhel"[..], "*"),
+            (b"%1\r\n+key\r\n$5\r\nval", "%"),
+        ] {
+            let (end, len) = parse_length(buf, prefix)?;
+            let ret = calc_total_length(buf, end, len as usize, prefix);
+            assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_lengths_are_refused() {
+        // none of them is waited for, whatever comes next
+        let corpus: [&[u8]; 14] = [
+            b"$99999999999999999999\r\n",
+            b"*4294967295\r\n",
+            b"$600000000\r\n",
+            b"$-2\r\n",
+            b"*-5\r\n",
+            b"%-1\r\n",
+            b"~-1\r\n",
+            b"$abc\r\n",
+            b"*\r\n",
+            b"$\xff\r\n",
+            b"$1 \r\n",
+            b"*1\r\n$-7\r\n",
+            b"*2\r\n*99999999999999999999\r\n",
+            b"$111111111111111111111111111111",
+        ];
+        for header in corpus {
+            assert!(
+                matches!(
+                    RespFrame::expect_length(header),
+                    Err(RespError::InvalidFrameLength(_))
+                ),
+                "{:?}",
+                header
+            );
+            assert!(
+                matches!(
+                    RespFrame::decode(&mut BytesMut::from(header)),
+                    Err(RespError::InvalidFrameLength(_))
+                ),
+                "{:?}",
+                header
+            );
+        }
+        assert_eq!(RespFrame::expect_length(b"$-1\r\n"), Ok(5));
+
+        // nor is a frame nested deeper than the limit, which would exhaust the stack
+        let max_depth = ProtoLimits::default().max_depth;
+        let deep = [&b"*1\r\n~1\r\n%1\r\n"[..], b">1\r\n"]
+            .concat()
+            .repeat(max_depth);
+        assert!(matches!(
+            RespFrame::expect_length(&deep),
+            Err(RespError::InvalidFrame(_))
+        ));
+        assert!(matches!(
+            RespFrame::decode(&mut BytesMut::from(&deep[..])),
+            Err(RespError::InvalidFrame(_))
+        ));
+        let mut nested = b"*1\r\n".repeat(max_depth);
+        nested.extend_from_slice(b":1\r\n");
+        assert_eq!(RespFrame::expect_length(&nested), Ok(nested.len()));
+    }
+
+    #[test]
+    fn test_partial_request_goes_on_from_the_last_decode() {
+        let limits = ProtoLimits::default();
+        let request = b"*3\r\n$3\r\nset\r\n$3\r\nkey\r\n$5\r\nvalue\r\n";
+        let mut partial = PartialRequest::default();
+        let mut buf = BytesMut::new();
+        for (i, byte) in request.iter().enumerate() {
+            buf.extend_from_slice(&[*byte]);
+            let result = limits.decode_partial(&mut buf, &mut partial);
+            if i + 1 < request.len() {
+                assert_eq!(result, Err(RespError::NotComplete));
+            } else {
+                assert_eq!(result, RespFrame::decode(&mut BytesMut::from(&request[..])));
+            }
+        }
+        // only the elements that arrived whole are behind
+        assert_eq!(partial, PartialRequest::default());
+        buf.extend_from_slice(b"*2\r\n$3\r\nget\r\n$3\r\nk");
+        assert_eq!(
+            limits.decode_partial(&mut buf, &mut partial),
+            Err(RespError::NotComplete)
+        );
+        assert_eq!(partial.elements, 1);
+        assert_eq!(partial.len, 4 + 9);
+        // a wrong element is found out without starting over
+        buf.extend_from_slice(b"eyXX");
+        assert!(limits.decode_partial(&mut buf, &mut partial).is_err());
+        assert_eq!(partial, PartialRequest::default());
+    }
+
+    #[test]
+    fn test_bare_line_endings_are_invalid() {
+        for frame in [
+            &b"+OK\n"[..],
+            b"-ERR oops\n+OK\r\n",
+            b":1\n",
+            b"$5\nhello\r\n",
+            b"*1\r\n$4\r\nping\n",
+            b"+O\rK\r\n",
+            b"_\n",
+        ] {
+            let result = RespFrame::decode(&mut BytesMut::from(frame));
+            assert!(
+                matches!(
+                    result,
+                    Err(RespError::InvalidFrame(_) | RespError::InvalidFrameType(_))
+                ),
+                "{:?}: {:?}",
+                frame,
+                result
+            );
+        }
+        // a CR at the end may be followed by its LF yet
+        assert_eq!(
+            RespFrame::decode(&mut BytesMut::from(&b"+OK\r"[..])),
+            Err(RespError::NotComplete)
+        );
+    }
+
+    #[test]
+    fn test_extra_bytes_between_frames() -> anyhow::Result<()> {
+        let mut buf = BytesMut::from(&b"+OK\r\nXX:1\r\n"[..]);
+        assert_eq!(RespFrame::decode(&mut buf)?, SimpleString::new("OK").into());
+        assert!(matches!(
+            RespFrame::decode(&mut buf),
+            Err(RespError::InvalidFrameType(_))
+        ));
+
+        let mut buf = BytesMut::from(&b"*1\r\n$2\r\nab\r\n\r\n+OK\r\n"[..]);
+        assert!(RespFrame::decode(&mut buf).is_ok());
+        assert!(RespFrame::decode(&mut buf).is_err());
         Ok(())
     }
+
+    #[test]
+    fn test_proto_limits() {
+        let limits = ProtoLimits {
+            max_bulk_len: 3,
+            max_multibulk_len: 1,
+            max_depth: 2,
+        };
+        let frame = |frame: &[u8], limits: ProtoLimits| limits.decode(&mut BytesMut::from(frame));
+        assert!(frame(b"$3\r\nabc\r\n", limits).is_ok());
+        assert_eq!(
+            frame(b"$4\r\nabcd\r\n", limits),
+            Err(RespError::InvalidFrameLength("$4".to_string()))
+        );
+        assert!(frame(b"*1\r\n$2\r\nab\r\n", limits).is_ok());
+        assert!(frame(b"*2\r\n$2\r\nab\r\n$2\r\nab\r\n", limits).is_err());
+        assert!(frame(b"*1\r\n*1\r\n:1\r\n", limits).is_ok());
+        assert!(matches!(
+            frame(b"*1\r\n*1\r\n*1\r\n:1\r\n", limits),
+            Err(RespError::InvalidFrame(_))
+        ));
+        // only for that decode
+        assert!(RespFrame::decode(&mut BytesMut::from(&b"$4\r\nabcd\r\n"[..])).is_ok());
+    }
+
+    #[test]
+    fn test_decode_random_input_does_not_panic() {
+        use rand::{seq::SliceRandom, Rng};
+
+        let valid: [&[u8]; 6] = [
+            b"*2\r\n$3\r\nget\r\n$5\r\nhello\r\n",
+            b"%1\r\n+key\r\n:-12\r\n",
+            b"~2\r\n#t\r\n,1.5\r\n",
+            b"$-1\r\n*-1\r\n_\r\n",
+            b"-ERR oops\r\n+OK\r\n",
+            b"*1\r\n*1\r\n$0\r\n\r\n",
+        ];
+        let bytes = b"*$%~:+-#,_\r\n-0123456789";
+        let mut rng = rand::thread_rng();
+        for _ in 0..20_000 {
+            let mut input = valid.choose(&mut rng).unwrap().to_vec();
+            for _ in 0..rng.gen_range(1..4) {
+                let at = rng.gen_range(0..input.len());
+                match rng.gen_range(0..3) {
+                    0 => input[at] = *bytes.choose(&mut rng).unwrap(),
+                    1 => input.insert(at, *bytes.choose(&mut rng).unwrap()),
+                    _ => input.truncate(at.max(1)),
+                }
+            }
+            let mut buf = BytesMut::from(&input[..]);
+            let _ = RespFrame::expect_length(&buf);
+            while !buf.is_empty() {
+                let len = buf.len();
+                if RespFrame::decode(&mut buf).is_err() || buf.len() == len {
+                    break;
+                }
+            }
+        }
+    }
 }
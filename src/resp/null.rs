@@ -1,16 +1,86 @@
 use bytes::BytesMut;
 
-use crate::{RespDecode, RespEncode, RespError};
+use crate::{RespDecode, RespEncode, RespError, RespFrame};
 
 use super::extract_fixed_data;
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
-pub struct RespNull;
+/// A missing value. RESP3 has a frame for it, RESP2 encodes it as a null bulk string or, when it
+/// stands for a missing array, as a null array. Whichever way it was encoded, it is the same null.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RespNull {
+    // stands for an array, which matters to RESP2 only
+    array: bool,
+    // encoded the RESP2 way
+    resp2: bool,
+}
+
+impl RespFrame {
+    /// The reply of a missing value.
+    pub const NULL: RespFrame = RespFrame::Null(RespNull {
+        array: false,
+        resp2: false,
+    });
+
+    /// The reply of a missing array, such as a blocking pop that timed out.
+    pub const NULL_ARRAY: RespFrame = RespFrame::Null(RespNull {
+        array: true,
+        resp2: false,
+    });
+}
+
+impl RespNull {
+    // the same null, encoded the RESP2 way
+    pub(super) fn into_resp2(self) -> Self {
+        RespNull {
+            resp2: true,
+            ..self
+        }
+    }
+
+    // the null a RESP2 frame the buffer starts with is, if it is one
+    pub(super) fn resp2(buf: &[u8]) -> Option<Self> {
+        let array = match buf.get(..5)? {
+            b"$-1\r\n" => false,
+            b"*-1\r\n" => true,
+            _ => return None,
+        };
+        Some(RespNull { array, resp2: true })
+    }
+
+    fn wire(&self) -> &'static [u8] {
+        match (self.resp2, self.array) {
+            (false, _) => b"_\r\n",
+            (true, false) => b"$-1\r\n",
+            (true, true) => b"*-1\r\n",
+        }
+    }
+}
+
+// every null is the same value
+impl PartialEq for RespNull {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for RespNull {}
+
+impl PartialOrd for RespNull {
+    fn partial_cmp(&self, _other: &Self) -> Option<std::cmp::Ordering> {
+        Some(std::cmp::Ordering::Equal)
+    }
+}
 
 // - null: "_\r\n"
+// - null bulk string: "$-1\r\n"
+// - null array: "*-1\r\n"
 impl RespEncode for RespNull {
-    fn encode(self) -> Vec<u8> {
-        b"_\r\n".to_vec()
+    fn encode_into(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(self.wire());
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.wire().len()
     }
 }
 
@@ -18,7 +88,7 @@ impl RespDecode for RespNull {
     const PREFIX: &'static str = "_";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
         extract_fixed_data(buf, "_\r\n", "Null")?;
-        Ok(RespNull)
+        Ok(RespNull::default())
     }
 
     fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
@@ -28,25 +98,34 @@ impl RespDecode for RespNull {
 
 #[cfg(test)]
 mod tests {
-    use crate::RespFrame;
-
     use super::*;
     use anyhow::Result;
 
     #[test]
     fn test_null_encode() {
-        let frame: RespFrame = RespNull.into();
-        assert_eq!(frame.encode(), b"_\r\n");
+        assert_eq!(RespFrame::NULL.encode(), b"_\r\n");
+        assert_eq!(RespFrame::NULL_ARRAY.encode(), b"_\r\n");
+        assert_eq!(RespFrame::NULL.into_resp2().encode(), b"$-1\r\n");
+        assert_eq!(RespFrame::NULL_ARRAY.into_resp2().encode(), b"*-1\r\n");
     }
 
     #[test]
     fn test_null_decode() -> Result<()> {
         let mut buf = BytesMut::new();
-        buf.extend_from_slice(b"_\r\n");
+        buf.extend_from_slice(b"_\r\n$-1\r\n*-1\r\n");
 
-        let frame = RespNull::decode(&mut buf)?;
-        assert_eq!(frame, RespNull);
+        // the same null, encoded back the way it came
+        for wire in [&b"_\r\n"[..], b"$-1\r\n", b"*-1\r\n"] {
+            assert_eq!(RespFrame::expect_length(&buf)?, wire.len());
+            let frame = RespFrame::decode(&mut buf)?;
+            assert_eq!(frame, RespFrame::NULL);
+            assert_eq!(frame, RespFrame::NULL_ARRAY);
+            assert_eq!(frame.encode(), wire);
+        }
+        assert!(buf.is_empty());
 
+        buf.extend_from_slice(b"$-1\r");
+        assert_eq!(RespFrame::decode(&mut buf), Err(RespError::NotComplete));
         Ok(())
     }
 }
@@ -3,20 +3,22 @@ use bytes::{Buf, BytesMut};
 use crate::{RespDecode, RespEncode, RespError, RespFrame};
 use std::ops::Deref;
 
-use super::{calc_total_length, parse_length, BUF_CAP, CRLF_LEN};
+use super::{calc_total_length, encode_header, header_len, parse_length, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespSet(pub(crate) Vec<RespFrame>);
 
 // - set: "~<number-of-elements>\r\n<element-1>...<element-n>"
 impl RespEncode for RespSet {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(BUF_CAP);
-        buf.extend_from_slice(&format!("~{}\r\n", self.len()).into_bytes());
-        for frame in self.0 {
-            buf.extend_from_slice(&frame.encode());
+    fn encode_into(&self, buf: &mut BytesMut) {
+        encode_header(buf, '~', self.len());
+        for frame in &self.0 {
+            frame.encode_into(buf);
         }
-        buf
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len()) + self.iter().map(RespEncode::encoded_len).sum::<usize>()
     }
 }
 
@@ -78,7 +80,7 @@ mod tests {
         .into();
         assert_eq!(
             frame.encode(),
-            b"~2\r\n*2\r\n:+1234\r\n#t\r\n$5\r\nworld\r\n"
+            b"~2\r\n*2\r\n:1234\r\n#t\r\n$5\r\nworld\r\n"
         );
     }
 
@@ -0,0 +1,121 @@
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::{RespDecode, RespEncode, RespError};
+
+use super::{
+    add_length, bulk_string::check_terminator, encode_header, header_len, parse_length, preview,
+    CRLF, CRLF_LEN,
+};
+
+/// A string along with how it is formatted, `txt` for plain text or `mkd` for markdown.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+pub struct VerbatimString {
+    pub(crate) format: [u8; 3],
+    pub(crate) data: Bytes,
+}
+
+impl VerbatimString {
+    pub fn new(format: [u8; 3], data: impl Into<Bytes>) -> Self {
+        VerbatimString {
+            format,
+            data: data.into(),
+        }
+    }
+}
+
+// the format and the `:` before the data
+const FORMAT_LEN: usize = 4;
+
+// - verbatim string: "=<length>\r\n<format>:<data>\r\n", the length counting the format
+impl RespEncode for VerbatimString {
+    fn encode_into(&self, buf: &mut BytesMut) {
+        encode_header(buf, '=', FORMAT_LEN + self.data.len());
+        buf.extend_from_slice(&self.format);
+        buf.extend_from_slice(b":");
+        buf.extend_from_slice(&self.data);
+        buf.extend_from_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        let len = FORMAT_LEN + self.data.len();
+        header_len(len) + len + CRLF_LEN
+    }
+}
+
+impl RespDecode for VerbatimString {
+    const PREFIX: &'static str = "=";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let (end, len) = parse_length(buf, Self::PREFIX)?;
+        let len = len as usize;
+        let remained = &buf[end + CRLF_LEN..];
+        check_terminator(remained, len)?;
+        if remained.len() < add_length(len, CRLF_LEN)? {
+            return Err(RespError::NotComplete);
+        }
+        let format = match remained[..len] {
+            [a, b, c, b':', ..] => [a, b, c],
+            _ => {
+                return Err(RespError::InvalidFrame(format!(
+                    "expect: verbatim string format, got: {:?}",
+                    preview(&remained[..len])
+                )))
+            }
+        };
+
+        buf.advance(end + CRLF_LEN);
+        let data = buf.split_to(len + CRLF_LEN).freeze();
+        Ok(VerbatimString::new(format, data.slice(FORMAT_LEN..len)))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        let (end, len) = parse_length(buf, Self::PREFIX)?;
+        check_terminator(&buf[end + CRLF_LEN..], len as usize)?;
+        add_length(end + 2 * CRLF_LEN, len as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_verbatim_string_encode() {
+        let frame: RespFrame = VerbatimString::new(*b"txt", "Some string").into();
+        assert_eq!(frame.encode(), b"=15\r\ntxt:Some string\r\n");
+    }
+
+    #[test]
+    fn test_verbatim_string_decode() -> Result<()> {
+        let mut buf = BytesMut::from(&b"=15\r\ntxt:Some string\r\n=4\r\nmkd:\r\n"[..]);
+        assert_eq!(
+            RespFrame::decode(&mut buf)?,
+            VerbatimString::new(*b"txt", "Some string").into()
+        );
+        assert_eq!(
+            RespFrame::decode(&mut buf)?,
+            VerbatimString::new(*b"mkd", "").into()
+        );
+
+        let mut buf = BytesMut::from(&b"=15\r\ntxt:Some"[..]);
+        assert_eq!(
+            VerbatimString::decode(&mut buf),
+            Err(RespError::NotComplete)
+        );
+        let mut buf = BytesMut::from(&b"=3\r\ntxt\r\n"[..]);
+        assert_eq!(
+            VerbatimString::decode(&mut buf),
+            Err(RespError::InvalidFrame(
+                "expect: verbatim string format, got: \"txt\"".to_string()
+            ))
+        );
+        // not a null like a bulk string
+        let mut buf = BytesMut::from(&b"=-1\r\n"[..]);
+        assert_eq!(
+            VerbatimString::decode(&mut buf),
+            Err(RespError::InvalidFrameLength("=-1".to_string()))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,1312 @@
+use anyhow::Result;
+use bytes::BytesMut;
+use simple_redis::{
+    network, Backend, BulkString, RespArray, RespDecode, RespEncode, RespError, RespFrame,
+    ServerConfig,
+};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+// serve a fresh backend on a random port from a runtime of its own
+fn start_server() -> Result<std::net::SocketAddr> {
+    start_server_with(Backend::new())
+}
+
+fn start_server_with(backend: Backend) -> Result<std::net::SocketAddr> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    listener.set_nonblocking(true)?;
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(network::stream_handler(stream, backend.clone()));
+            }
+        });
+    });
+    Ok(addr)
+}
+
+// send raw bytes and read back exactly one reply
+fn request(stream: &mut TcpStream, bytes: &[u8]) -> Result<Vec<u8>> {
+    stream.write_all(bytes)?;
+    read_reply(stream)
+}
+
+// read exactly one frame pushed by the server, a byte at a time so that the frames pushed after it
+// are left for the next read
+fn read_reply(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut buf = BytesMut::new();
+    let mut chunk = [0; 1];
+    loop {
+        let n = stream.read(&mut chunk)?;
+        anyhow::ensure!(n > 0, "the server closed the connection");
+        buf.extend_from_slice(&chunk[..n]);
+        match RespFrame::decode(&mut buf.clone()) {
+            Ok(frame) => return Ok(frame.encode()),
+            Err(RespError::NotComplete) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// the bytes of a command with the given arguments
+fn command(args: &[&str]) -> Vec<u8> {
+    let args = args
+        .iter()
+        .map(|arg| BulkString::from(*arg).into())
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(args).encode()
+}
+
+// the same with arguments of any bytes
+fn binary_command(args: &[&[u8]]) -> Vec<u8> {
+    let args = args
+        .iter()
+        .map(|arg| BulkString::from(*arg).into())
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(args).encode()
+}
+
+fn connect(addr: std::net::SocketAddr) -> Result<TcpStream> {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    Ok(stream)
+}
+
+#[test]
+fn test_connection_survives_bad_commands() -> Result<()> {
+    let addr = start_server()?;
+    let mut stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    assert_eq!(request(&mut stream, b"*1\r\n$4\r\nping\r\n")?, b"+PONG\r\n");
+    assert_eq!(
+        request(
+            &mut stream,
+            b"*3\r\n$7\r\nCOMMANS\r\n$4\r\nDOCS\r\n$3\r\nget\r\n"
+        )?,
+        b"-ERR unknown command 'COMMANS', with args beginning with: 'DOCS' 'get' \r\n"
+    );
+    assert_eq!(
+        request(&mut stream, b"*1\r\n$5\r\nnope!\r\n")?,
+        b"-ERR unknown command 'nope!', with args beginning with: \r\n"
+    );
+    // a wrong number of arguments and a frame that isn't a command are errors too
+    assert!(request(&mut stream, b"*3\r\n$4\r\necho\r\n$1\r\na\r\n$1\r\nb\r\n")?.starts_with(b"-"));
+    assert!(request(&mut stream, b"+hello\r\n")?.starts_with(b"-"));
+    assert!(request(&mut stream, b"*1\r\n:1\r\n")?.starts_with(b"-"));
+
+    // redis-cli starts with this, and reads the map as an array until it sends HELLO 3
+    assert!(request(&mut stream, b"*2\r\n$7\r\nCOMMAND\r\n$4\r\nDOCS\r\n")?.starts_with(b"*"));
+
+    // and the connection is still usable afterwards
+    assert_eq!(
+        request(&mut stream, b"*2\r\n$4\r\nping\r\n$5\r\nhello\r\n")?,
+        b"$5\r\nhello\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, b"*2\r\n$4\r\necho\r\n$3\r\nhey\r\n")?,
+        b"$3\r\nhey\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, b"*3\r\n$3\r\nset\r\n$1\r\nk\r\n$1\r\nv\r\n")?,
+        b"+OK\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n")?,
+        b"$1\r\nv\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_invalid_frames_close_the_connection() -> Result<()> {
+    let addr = start_server()?;
+    for (header, reply) in [
+        (
+            &b"*1\r\n$99999999999999999999\r\n"[..],
+            &b"-ERR Protocol error: invalid bulk length\r\n"[..],
+        ),
+        (
+            b"*4294967295\r\n",
+            b"-ERR Protocol error: invalid multibulk length\r\n",
+        ),
+        (b"$-5\r\n", b"-ERR Protocol error: invalid bulk length\r\n"),
+    ] {
+        let mut stream = connect(addr)?;
+        assert_eq!(request(&mut stream, header)?, reply);
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest)?;
+        assert!(rest.is_empty());
+    }
+
+    // as are the frames that don't end the way they should
+    for (frame, reply) in [
+        (
+            &b"*2\r\n$4\r\necho\r\n$5\r\nhelloXX\r\n"[..],
+            &b"-ERR Protocol error: expect: CRLF, got: \"XX\"\r\n"[..],
+        ),
+        (
+            b"*1\r\n$4\r\nping\n",
+            b"-ERR Protocol error: expect: CRLF, got: \"\\n\"\r\n",
+        ),
+    ] {
+        let mut stream = connect(addr)?;
+        assert_eq!(request(&mut stream, frame)?, reply);
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest)?;
+        assert!(rest.is_empty());
+    }
+
+    // a lower limit takes effect from the next command on
+    let mut stream = connect(addr)?;
+    let config_set = command(&["config", "set", "proto-max-bulk-len", "1mb"]);
+    assert_eq!(request(&mut stream, &config_set)?, b"+OK\r\n");
+    // refused as soon as the header arrives, before the value
+    assert_eq!(
+        request(&mut stream, b"*3\r\n$3\r\nset\r\n$3\r\nkey\r\n$1048577\r\n")?,
+        b"-ERR Protocol error: invalid bulk length\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_inline_commands() -> Result<()> {
+    let addr = start_server()?;
+    let mut stream = connect(addr)?;
+    assert_eq!(request(&mut stream, b"PING\r\n")?, b"+PONG\r\n");
+    assert_eq!(
+        request(&mut stream, b"SET greeting \"hello\\x21 world\"\r\n")?,
+        b"+OK\r\n"
+    );
+    // empty lines are skipped, and a bare LF ends a line too
+    assert_eq!(
+        request(&mut stream, b"\r\nGET greeting\n")?,
+        b"$12\r\nhello! world\r\n"
+    );
+    assert_eq!(request(&mut stream, b"SET quote 'it\\'s'\r\n")?, b"+OK\r\n");
+    // inline commands and frames mix on a connection
+    assert_eq!(
+        request(&mut stream, &command(&["get", "quote"]))?,
+        b"$4\r\nit's\r\n"
+    );
+
+    assert_eq!(
+        request(&mut stream, b"GET \"greeting\r\n")?,
+        b"-ERR Protocol error: unbalanced quotes in request\r\n"
+    );
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest)?;
+    assert!(rest.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_hello_switches_the_protocol() -> Result<()> {
+    let addr = start_server()?;
+    let mut stream = connect(addr)?;
+    let mut publisher = connect(addr)?;
+    request(
+        &mut publisher,
+        &command(&["hset", "h", "f1", "v1", "f2", "v2"]),
+    )?;
+
+    // RESP2 until the client asks for RESP3
+    assert_eq!(
+        request(&mut stream, &command(&["hgetall", "h"]))?,
+        b"*4\r\n$2\r\nf1\r\n$2\r\nv1\r\n$2\r\nf2\r\n$2\r\nv2\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &command(&["get", "nope"]))?,
+        b"$-1\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &command(&["hello", "4"]))?,
+        b"-NOPROTO unsupported protocol version\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &command(&["hello", "three"]))?,
+        b"-ERR Protocol version is not an integer or out of range\r\n"
+    );
+
+    // the reply to HELLO 3 is already in RESP3
+    let reply = request(&mut stream, &command(&["hello", "3", "setname", "app"]))?;
+    let reply = String::from_utf8(reply)?;
+    assert!(reply.starts_with("%6\r\n"));
+    assert!(reply.contains("$5\r\nproto\r\n:3\r\n"));
+    assert!(reply.contains("$6\r\nserver\r\n$5\r\nredis\r\n"));
+    assert_eq!(
+        request(&mut stream, &command(&["client", "getname"]))?,
+        b"$3\r\napp\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &command(&["hgetall", "h"]))?,
+        b"%2\r\n$2\r\nf1\r\n$2\r\nv1\r\n$2\r\nf2\r\n$2\r\nv2\r\n"
+    );
+    assert_eq!(request(&mut stream, &command(&["get", "nope"]))?, b"_\r\n");
+
+    // the confirmations and the messages are pushes
+    assert_eq!(
+        request(&mut stream, &command(&["subscribe", "news"]))?,
+        b">3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n"
+    );
+    request(&mut publisher, &command(&["publish", "news", "hi"]))?;
+    assert_eq!(
+        read_reply(&mut stream)?,
+        b">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n"
+    );
+
+    // RESET goes back to RESP2
+    assert_eq!(request(&mut stream, &command(&["reset"]))?, b"+RESET\r\n");
+    assert_eq!(
+        request(&mut stream, &command(&["get", "nope"]))?,
+        b"$-1\r\n"
+    );
+    let reply = request(&mut stream, &command(&["hello"]))?;
+    assert!(reply.starts_with(b"*12\r\n"));
+    assert!(String::from_utf8(reply)?.contains("$5\r\nproto\r\n:2\r\n"));
+    Ok(())
+}
+
+#[test]
+fn test_idle_clients_are_closed() -> Result<()> {
+    let addr = start_server()?;
+    let mut stream = connect(addr)?;
+    let mut subscriber = connect(addr)?;
+    request(&mut stream, &command(&["config", "set", "timeout", "1"]))?;
+    request(&mut subscriber, &command(&["subscribe", "news"]))?;
+
+    let start = std::time::Instant::now();
+    let mut buf = [0; 1];
+    assert_eq!(stream.read(&mut buf)?, 0);
+    assert!(start.elapsed() >= Duration::from_millis(900));
+
+    // a subscriber waits for its messages as long as it takes
+    let mut publisher = connect(addr)?;
+    request(&mut publisher, &command(&["publish", "news", "hi"]))?;
+    assert_eq!(
+        read_reply(&mut subscriber)?,
+        b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_null_replies_match_the_protocol() -> Result<()> {
+    let addr = start_server()?;
+    let mut stream = connect(addr)?;
+    request(&mut stream, &command(&["set", "empty", ""]))?;
+
+    // an empty string or array is not a null
+    assert_eq!(
+        request(&mut stream, &command(&["get", "empty"]))?,
+        b"$0\r\n\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &command(&["lrange", "nope", "0", "-1"]))?,
+        b"*0\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &command(&["blpop", "nope", "0.01"]))?,
+        b"*-1\r\n"
+    );
+
+    request(&mut stream, &command(&["hello", "3"]))?;
+    assert_eq!(
+        request(&mut stream, &command(&["get", "empty"]))?,
+        b"$0\r\n\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &command(&["blpop", "nope", "0.01"]))?,
+        b"_\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &command(&["mget", "nope", "empty"]))?,
+        b"*2\r\n_\r\n$0\r\n\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_info_counts_clients() -> Result<()> {
+    let addr = start_server()?;
+    let mut first = TcpStream::connect(addr)?;
+    first.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let info = |stream: &mut TcpStream| -> Result<String> {
+        let reply = request(stream, b"*2\r\n$4\r\ninfo\r\n$7\r\nclients\r\n")?;
+        Ok(String::from_utf8(reply)?)
+    };
+    assert!(info(&mut first)?.contains("connected_clients:1\r\n"));
+
+    let mut second = TcpStream::connect(addr)?;
+    second.set_read_timeout(Some(Duration::from_secs(5)))?;
+    assert!(info(&mut second)?.contains("connected_clients:2\r\n"));
+    drop(second);
+    // the server notices the closed connection on its own time
+    for _ in 0..100 {
+        if info(&mut first)?.contains("connected_clients:1\r\n") {
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    anyhow::bail!("the closed connection is still counted")
+}
+
+#[test]
+fn test_select_is_per_connection() -> Result<()> {
+    let addr = start_server()?;
+    let mut first = TcpStream::connect(addr)?;
+    let mut second = TcpStream::connect(addr)?;
+    for stream in [&first, &second] {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    }
+    let get = b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n";
+
+    assert_eq!(
+        request(&mut first, b"*2\r\n$6\r\nselect\r\n$1\r\n1\r\n")?,
+        b"+OK\r\n"
+    );
+    assert_eq!(
+        request(&mut first, b"*3\r\n$3\r\nset\r\n$1\r\nk\r\n$1\r\nv\r\n")?,
+        b"+OK\r\n"
+    );
+    assert_eq!(request(&mut first, get)?, b"$1\r\nv\r\n");
+    assert_eq!(request(&mut second, get)?, b"$-1\r\n");
+    // a failed SELECT keeps the database
+    assert!(request(&mut first, b"*2\r\n$6\r\nselect\r\n$2\r\n99\r\n")?.starts_with(b"-ERR"));
+    assert_eq!(request(&mut first, get)?, b"$1\r\nv\r\n");
+
+    // after a SWAPDB the second connection, still on db0, sees what was in db1
+    assert_eq!(
+        request(&mut first, b"*3\r\n$6\r\nswapdb\r\n$1\r\n0\r\n$1\r\n1\r\n")?,
+        b"+OK\r\n"
+    );
+    assert_eq!(request(&mut second, get)?, b"$1\r\nv\r\n");
+    assert_eq!(request(&mut first, get)?, b"$-1\r\n");
+    Ok(())
+}
+
+#[test]
+fn test_client_list_and_kill() -> Result<()> {
+    let addr = start_server()?;
+    let mut first = connect(addr)?;
+    let mut second = connect(addr)?;
+    assert_eq!(
+        request(&mut first, &command(&["client", "setname", "first"]))?,
+        b"+OK\r\n"
+    );
+    assert_eq!(
+        request(&mut second, &command(&["client", "id"]))?,
+        b":2\r\n"
+    );
+
+    let list = String::from_utf8(request(&mut first, &command(&["client", "list"]))?)?;
+    // past the length of the bulk string
+    let lines = list
+        .lines()
+        .skip(1)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    let first_addr = first.local_addr()?.to_string();
+    assert!(lines[0].starts_with(&format!(
+        "id=1 addr={} laddr={} name=first ",
+        first_addr, addr
+    )));
+    assert!(lines[0].ends_with(" db=0 cmd=client resp=2"));
+    assert!(lines[1].starts_with(&format!("id=2 addr={} ", second.local_addr()?)));
+
+    // the victim's socket is closed by the server
+    assert_eq!(
+        request(&mut first, &command(&["client", "kill", "id", "2"]))?,
+        b":1\r\n"
+    );
+    let mut buf = [0; 16];
+    assert_eq!(second.read(&mut buf)?, 0);
+
+    // killing itself still gets a reply first
+    assert_eq!(
+        request(&mut first, &command(&["client", "kill", &first_addr]))?,
+        b"+OK\r\n"
+    );
+    assert_eq!(first.read(&mut buf)?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_auth_with_requirepass() -> Result<()> {
+    let backend = Backend::new_with_server_config(ServerConfig {
+        requirepass: "s3cret".to_string(),
+        ..Default::default()
+    });
+    let addr = start_server_with(backend)?;
+    let mut stream = connect(addr)?;
+
+    let set = command(&["set", "k", "v"]);
+    assert_eq!(
+        request(&mut stream, &set)?,
+        b"-NOAUTH Authentication required.\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &command(&["ping"]))?,
+        b"-NOAUTH Authentication required.\r\n"
+    );
+    // a wrong password keeps the connection, unauthenticated
+    assert_eq!(
+        request(&mut stream, &command(&["auth", "nope"]))?,
+        b"-WRONGPASS invalid username-password pair or user is disabled.\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &command(&["auth", "someone", "s3cret"]))?,
+        b"-WRONGPASS invalid username-password pair or user is disabled.\r\n"
+    );
+    assert!(request(&mut stream, &set)?.starts_with(b"-NOAUTH"));
+
+    assert_eq!(
+        request(&mut stream, &command(&["auth", "default", "s3cret"]))?,
+        b"+OK\r\n"
+    );
+    assert_eq!(request(&mut stream, &set)?, b"+OK\r\n");
+
+    // every connection authenticates on its own
+    let mut other = connect(addr)?;
+    assert!(request(&mut other, &command(&["get", "k"]))?.starts_with(b"-NOAUTH"));
+    assert_eq!(
+        request(&mut other, &command(&["auth", "s3cret"]))?,
+        b"+OK\r\n"
+    );
+    assert_eq!(
+        request(&mut other, &command(&["get", "k"]))?,
+        b"$1\r\nv\r\n"
+    );
+
+    // so can HELLO, which authenticates along the way
+    let mut hello = connect(addr)?;
+    assert!(request(&mut hello, &command(&["hello", "3"]))?.starts_with(b"-NOAUTH"));
+    let reply = request(
+        &mut hello,
+        &command(&["hello", "3", "auth", "default", "s3cret"]),
+    )?;
+    assert!(reply.starts_with(b"%"));
+    assert_eq!(request(&mut hello, &command(&["get", "nope"]))?, b"_\r\n");
+
+    // QUIT is allowed before authenticating, and closes the connection
+    let mut quitter = connect(addr)?;
+    assert_eq!(request(&mut quitter, &command(&["quit"]))?, b"+OK\r\n");
+    let mut buf = [0; 16];
+    assert_eq!(quitter.read(&mut buf)?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_shutdown_stops_the_server() -> Result<()> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    listener.set_nonblocking(true)?;
+    let server = std::thread::spawn(move || -> Result<()> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            network::Server::new(listener, Backend::new()).run().await
+        })
+    });
+
+    let mut first = connect(addr)?;
+    let mut second = connect(addr)?;
+    assert_eq!(request(&mut second, &command(&["ping"]))?, b"+PONG\r\n");
+    assert!(request(&mut first, &command(&["shutdown", "now"]))?.starts_with(b"-ERR"));
+    assert_eq!(
+        request(&mut first, &command(&["shutdown", "nosave"]))?,
+        b"+OK\r\n"
+    );
+
+    // the server returns once every connection is closed
+    server.join().unwrap()?;
+    let mut buf = [0; 16];
+    assert_eq!(first.read(&mut buf)?, 0);
+    assert_eq!(second.read(&mut buf)?, 0);
+    assert!(TcpStream::connect(addr).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_shutdown_handle() -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let server = network::Server::new(listener, Backend::new());
+        let shutdown = server.shutdown_handle();
+        let task = tokio::spawn(server.run());
+        // a client blocked on a list doesn't hold the shutdown up
+        let blocked = std::thread::spawn(move || -> Result<Vec<u8>> {
+            let mut stream = connect(addr)?;
+            let mut buf = [0; 16];
+            stream.write_all(&command(&["blpop", "list", "0"]))?;
+            let n = stream.read(&mut buf)?;
+            Ok(buf[..n].to_vec())
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown.cancel();
+        task.await??;
+        assert_eq!(blocked.join().unwrap()?, b"");
+        anyhow::Ok(())
+    })
+}
+
+#[test]
+fn test_debug_sleep_only_blocks_its_connection() -> Result<()> {
+    let addr = start_server()?;
+    let mut sleeper = connect(addr)?;
+    let mut other = connect(addr)?;
+
+    let started = std::time::Instant::now();
+    sleeper.write_all(&command(&["debug", "sleep", "1"]))?;
+    assert_eq!(request(&mut other, &command(&["get", "k"]))?, b"$-1\r\n");
+    assert!(started.elapsed() < Duration::from_millis(500));
+
+    let mut buf = [0; 16];
+    let n = sleeper.read(&mut buf)?;
+    assert_eq!(&buf[..n], b"+OK\r\n");
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    Ok(())
+}
+
+#[test]
+fn test_monitor_sees_other_clients_commands() -> Result<()> {
+    let addr = start_server()?;
+    let mut monitor = connect(addr)?;
+    let mut client = connect(addr)?;
+    assert_eq!(request(&mut monitor, &command(&["monitor"]))?, b"+OK\r\n");
+
+    request(&mut client, &command(&["SET", "k", "v"]))?;
+    request(&mut client, &command(&["get", "k"]))?;
+    request(&mut client, &command(&["auth", "secret"]))?;
+    // the monitor's own commands are not shown back to it
+    monitor.write_all(&command(&["ping"]))?;
+    request(&mut client, &command(&["select", "1"]))?;
+    request(&mut client, &command(&["del", "k"]))?;
+
+    // the lines may come in a single read, with the PONG in between
+    let mut pushed = String::new();
+    let mut chunk = [0; 1024];
+    while pushed.matches("\r\n").count() < 6 {
+        let n = monitor.read(&mut chunk)?;
+        anyhow::ensure!(n > 0, "the server closed the connection");
+        pushed.push_str(std::str::from_utf8(&chunk[..n])?);
+    }
+    let lines = pushed
+        .lines()
+        .filter(|line| *line != "+PONG")
+        .map(|line| line.split_once(' ').map(|(_, rest)| rest.to_string()))
+        .collect::<Option<Vec<_>>>()
+        .unwrap();
+    let client_addr = client.local_addr()?;
+    assert_eq!(
+        lines,
+        [
+            format!(r#"[0 {}] "SET" "k" "v""#, client_addr),
+            format!(r#"[0 {}] "get" "k""#, client_addr),
+            format!(r#"[0 {}] "auth" "(redacted)""#, client_addr),
+            format!(r#"[0 {}] "select" "1""#, client_addr),
+            format!(r#"[1 {}] "del" "k""#, client_addr),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_slowlog_records_commands() -> Result<()> {
+    let backend = Backend::new();
+    let addr = start_server_with(backend.clone())?;
+    let mut client = connect(addr)?;
+    let local = client.local_addr()?.to_string();
+
+    // a threshold of 0 logs every command
+    request(
+        &mut client,
+        &command(&["config", "set", "slowlog-log-slower-than", "0"]),
+    )?;
+    request(&mut client, &command(&["client", "setname", "slow"]))?;
+    request(&mut client, &command(&["set", "k", "v"]))?;
+
+    let entries = backend.slowlog_get(None);
+    assert_eq!(entries.len(), 3);
+    assert_eq!(
+        entries[0].args,
+        [b"set".to_vec(), b"k".to_vec(), b"v".to_vec()]
+    );
+    assert_eq!(entries[0].client_addr, local);
+    assert_eq!(entries[0].client_name, "slow");
+    assert!(entries[0].id > entries[1].id);
+
+    let reply = request(&mut client, &command(&["slowlog", "get", "1"]))?;
+    assert!(reply.starts_with(b"*1\r\n*6\r\n:2\r\n:"));
+    assert!(reply.ends_with(
+        format!("$1\r\nv\r\n${}\r\n{}\r\n$4\r\nslow\r\n", local.len(), local).as_bytes()
+    ));
+    // the GET before is logged too
+    assert_eq!(
+        request(&mut client, &command(&["slowlog", "len"]))?,
+        b":4\r\n"
+    );
+    assert_eq!(
+        request(&mut client, &command(&["slowlog", "reset"]))?,
+        b"+OK\r\n"
+    );
+    assert_eq!(
+        request(&mut client, &command(&["slowlog", "len"]))?,
+        b":1\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_info_commandstats() -> Result<()> {
+    let addr = start_server()?;
+    let mut client = connect(addr)?;
+    request(&mut client, &command(&["set", "k", "v"]))?;
+    request(&mut client, &command(&["GET", "k"]))?;
+    request(&mut client, &command(&["get", "missing"]))?;
+    request(&mut client, &command(&["get"]))?;
+
+    let reply = request(&mut client, &command(&["info", "commandstats"]))?;
+    let info = String::from_utf8(reply)?;
+    assert!(info.contains("cmdstat_get:calls=2,usec="));
+    assert!(info.contains(",rejected_calls=1\r\n"));
+    assert!(info.contains("cmdstat_set:calls=1,"));
+    Ok(())
+}
+
+#[test]
+fn test_multi_exec_and_discard() -> Result<()> {
+    let addr = start_server()?;
+    let mut client = connect(addr)?;
+    assert_eq!(
+        request(&mut client, &command(&["exec"]))?,
+        b"-ERR EXEC without MULTI\r\n"
+    );
+    assert_eq!(request(&mut client, &command(&["multi"]))?, b"+OK\r\n");
+    assert_eq!(
+        request(&mut client, &command(&["multi"]))?,
+        b"-ERR MULTI calls can not be nested\r\n"
+    );
+    assert_eq!(
+        request(&mut client, &command(&["set", "k", "1"]))?,
+        b"+QUEUED\r\n"
+    );
+    assert_eq!(
+        request(&mut client, &command(&["incr", "k"]))?,
+        b"+QUEUED\r\n"
+    );
+    assert_eq!(
+        request(&mut client, &command(&["select", "1"]))?,
+        b"+QUEUED\r\n"
+    );
+    // a nested MULTI doesn't abort the transaction
+    assert_eq!(
+        request(&mut client, &command(&["exec"]))?,
+        b"*3\r\n+OK\r\n:2\r\n+OK\r\n"
+    );
+    // the database selected inside the transaction stays selected
+    assert_eq!(request(&mut client, &command(&["get", "k"]))?, b"$-1\r\n");
+
+    request(&mut client, &command(&["multi"]))?;
+    request(&mut client, &command(&["set", "k", "queued"]))?;
+    assert_eq!(request(&mut client, &command(&["discard"]))?, b"+OK\r\n");
+    assert_eq!(request(&mut client, &command(&["get", "k"]))?, b"$-1\r\n");
+    assert_eq!(
+        request(&mut client, &command(&["discard"]))?,
+        b"-ERR DISCARD without MULTI\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_exec_after_queueing_error_aborts() -> Result<()> {
+    let addr = start_server()?;
+    let mut client = connect(addr)?;
+    request(&mut client, &command(&["multi"]))?;
+    request(&mut client, &command(&["set", "k", "v"]))?;
+    let reply = request(&mut client, &command(&["set", "k"]))?;
+    assert!(reply.starts_with(b"-ERR wrong number of arguments"));
+    assert!(request(&mut client, &command(&["nope"]))?.starts_with(b"-ERR unknown command"));
+    assert_eq!(
+        request(&mut client, &command(&["exec"]))?,
+        b"-EXECABORT Transaction discarded because of previous errors.\r\n"
+    );
+    // nothing ran, and the connection is out of the transaction
+    assert_eq!(request(&mut client, &command(&["get", "k"]))?, b"$-1\r\n");
+    Ok(())
+}
+
+#[test]
+fn test_exec_is_not_interleaved() -> Result<()> {
+    let addr = start_server()?;
+    let mut client = connect(addr)?;
+    let mut writer = connect(addr)?;
+
+    const INCRS: usize = 500;
+    request(&mut client, &command(&["multi"]))?;
+    request(&mut client, &command(&["set", "counter", "0"]))?;
+    for _ in 0..INCRS {
+        request(&mut client, &command(&["incr", "counter"]))?;
+    }
+    request(&mut client, &command(&["get", "counter"]))?;
+
+    let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let writing = {
+        let stop = stop.clone();
+        std::thread::spawn(move || -> Result<()> {
+            while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                request(&mut writer, &command(&["set", "counter", "1000000"]))?;
+            }
+            Ok(())
+        })
+    };
+    let reply = request(&mut client, &command(&["exec"]))?;
+    stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    writing.join().unwrap()?;
+
+    let expected = format!("$3\r\n{}\r\n", INCRS);
+    assert!(reply.ends_with(expected.as_bytes()));
+    Ok(())
+}
+
+#[test]
+fn test_watch_lets_one_of_two_transactions_through() -> Result<()> {
+    let addr = start_server()?;
+    let mut a = connect(addr)?;
+    let mut b = connect(addr)?;
+    request(&mut a, &command(&["set", "counter", "0"]))?;
+
+    for client in [&mut a, &mut b] {
+        assert_eq!(
+            request(client, &command(&["watch", "counter"]))?,
+            b"+OK\r\n"
+        );
+        assert_eq!(
+            request(client, &command(&["get", "counter"]))?,
+            b"$1\r\n0\r\n"
+        );
+    }
+    for client in [&mut a, &mut b] {
+        request(client, &command(&["multi"]))?;
+        assert_eq!(
+            request(client, &command(&["incr", "counter"]))?,
+            b"+QUEUED\r\n"
+        );
+    }
+    assert_eq!(request(&mut a, &command(&["exec"]))?, b"*1\r\n:1\r\n");
+    // the EXEC of the first changed the counter under the second
+    assert_eq!(request(&mut b, &command(&["exec"]))?, b"*-1\r\n");
+    assert_eq!(
+        request(&mut b, &command(&["get", "counter"]))?,
+        b"$1\r\n1\r\n"
+    );
+
+    // EXEC stops the watching, and watching inside a transaction is refused
+    request(&mut b, &command(&["multi"]))?;
+    assert_eq!(
+        request(&mut b, &command(&["watch", "counter"]))?,
+        b"-ERR WATCH inside MULTI is not allowed\r\n"
+    );
+    request(&mut b, &command(&["incr", "counter"]))?;
+    request(&mut a, &command(&["set", "counter", "10"]))?;
+    assert_eq!(request(&mut b, &command(&["exec"]))?, b"*1\r\n:11\r\n");
+
+    // so does UNWATCH
+    request(&mut b, &command(&["watch", "counter"]))?;
+    request(&mut b, &command(&["unwatch"]))?;
+    request(&mut a, &command(&["set", "counter", "20"]))?;
+    request(&mut b, &command(&["multi"]))?;
+    request(&mut b, &command(&["incr", "counter"]))?;
+    assert_eq!(request(&mut b, &command(&["exec"]))?, b"*1\r\n:21\r\n");
+    Ok(())
+}
+
+#[test]
+fn test_publish_reaches_subscribers() -> Result<()> {
+    let addr = start_server()?;
+    let mut publisher = connect(addr)?;
+    let mut first = connect(addr)?;
+    let mut second = connect(addr)?;
+
+    // a confirmation per channel, each a reply of its own
+    first.write_all(&command(&["subscribe", "news", "sport"]))?;
+    assert_eq!(
+        read_reply(&mut first)?,
+        b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n"
+    );
+    assert_eq!(
+        read_reply(&mut first)?,
+        b"*3\r\n$9\r\nsubscribe\r\n$5\r\nsport\r\n:2\r\n"
+    );
+    request(&mut second, &command(&["subscribe", "news"]))?;
+
+    assert_eq!(
+        request(&mut publisher, &command(&["publish", "news", "hello"]))?,
+        b":2\r\n"
+    );
+    assert_eq!(
+        request(&mut publisher, &command(&["publish", "sport", "goal"]))?,
+        b":1\r\n"
+    );
+    assert_eq!(
+        request(&mut publisher, &command(&["publish", "other", "x"]))?,
+        b":0\r\n"
+    );
+    let message = |channel: &str, payload: &str| {
+        format!(
+            "*3\r\n$7\r\nmessage\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
+            channel.len(),
+            channel,
+            payload.len(),
+            payload
+        )
+        .into_bytes()
+    };
+    assert_eq!(read_reply(&mut first)?, message("news", "hello"));
+    assert_eq!(read_reply(&mut first)?, message("sport", "goal"));
+    assert_eq!(read_reply(&mut second)?, message("news", "hello"));
+
+    // a subscriber may only manage its subscriptions
+    let reply = request(&mut second, &command(&["get", "k"]))?;
+    assert!(reply.starts_with(b"-ERR Can't execute 'get'"));
+    assert_eq!(
+        request(&mut second, &command(&["ping", "hi"]))?,
+        b"*2\r\n$4\r\npong\r\n$2\r\nhi\r\n"
+    );
+    assert_eq!(
+        request(&mut second, &command(&["unsubscribe"]))?,
+        b"*3\r\n$11\r\nunsubscribe\r\n$4\r\nnews\r\n:0\r\n"
+    );
+    assert_eq!(request(&mut second, &command(&["get", "k"]))?, b"$-1\r\n");
+    assert_eq!(
+        request(&mut publisher, &command(&["publish", "news", "bye"]))?,
+        b":1\r\n"
+    );
+
+    // a closed subscriber is no receiver
+    drop(first);
+    let started = std::time::Instant::now();
+    while request(&mut publisher, &command(&["publish", "news", "?"]))? != b":0\r\n" {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_keys_fields_and_channels_are_binary_safe() -> Result<()> {
+    let addr = start_server()?;
+    let mut stream = connect(addr)?;
+    let mut subscriber = connect(addr)?;
+    // a NUL, and bytes that are no UTF-8
+    let (nul, invalid): (&[u8], &[u8]) = (b"a\0b", b"\xff\xfe");
+
+    for (key, value) in [(nul, b"1"), (invalid, b"2")] {
+        assert_eq!(
+            request(&mut stream, &binary_command(&[b"set", key, value]))?,
+            b"+OK\r\n"
+        );
+    }
+    assert_eq!(
+        request(&mut stream, &binary_command(&[b"get", nul]))?,
+        b"$1\r\n1\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &binary_command(&[b"get", invalid]))?,
+        b"$1\r\n2\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &binary_command(&[b"keys", b"\xff*"]))?,
+        b"*1\r\n$2\r\n\xff\xfe\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &binary_command(&[b"keys", b"a?b"]))?,
+        b"*1\r\n$3\r\na\0b\r\n"
+    );
+
+    // fields and members are any bytes as well
+    assert_eq!(
+        request(&mut stream, &binary_command(&[b"hset", b"h", invalid, nul]))?,
+        b":1\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &binary_command(&[b"hkeys", b"h"]))?,
+        b"*1\r\n$2\r\n\xff\xfe\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &binary_command(&[b"sadd", b"s", nul]))?,
+        b":1\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &binary_command(&[b"sismember", b"s", nul]))?,
+        b":1\r\n"
+    );
+
+    // and so are channels
+    assert_eq!(
+        request(&mut subscriber, &binary_command(&[b"subscribe", invalid]))?,
+        b"*3\r\n$9\r\nsubscribe\r\n$2\r\n\xff\xfe\r\n:1\r\n"
+    );
+    assert_eq!(
+        request(&mut stream, &binary_command(&[b"publish", invalid, nul]))?,
+        b":1\r\n"
+    );
+    assert_eq!(
+        read_reply(&mut subscriber)?,
+        b"*3\r\n$7\r\nmessage\r\n$2\r\n\xff\xfe\r\n$3\r\na\0b\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_keyspace_notification_of_expired_key() -> Result<()> {
+    let addr = start_server()?;
+    let mut client = connect(addr)?;
+    let mut subscriber = connect(addr)?;
+    assert_eq!(
+        request(
+            &mut client,
+            &command(&["config", "set", "notify-keyspace-events", "KEA"])
+        )?,
+        b"+OK\r\n"
+    );
+    assert_eq!(
+        request(
+            &mut client,
+            &command(&["config", "get", "notify-keyspace-events"])
+        )?,
+        b"*2\r\n$22\r\nnotify-keyspace-events\r\n$3\r\nAKE\r\n"
+    );
+    request(
+        &mut subscriber,
+        &command(&["subscribe", "__keyevent@0__:expired"]),
+    )?;
+
+    request(&mut client, &command(&["set", "key", "v", "px", "20"]))?;
+    std::thread::sleep(Duration::from_millis(30));
+    // the active expire cycle or the read, whichever comes first, publishes the event
+    request(&mut client, &command(&["get", "key"]))?;
+    assert_eq!(
+        read_reply(&mut subscriber)?,
+        b"*3\r\n$7\r\nmessage\r\n$22\r\n__keyevent@0__:expired\r\n$3\r\nkey\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_reset_discards_the_transaction() -> Result<()> {
+    let addr = start_server()?;
+    let mut client = connect(addr)?;
+    request(&mut client, &command(&["select", "1"]))?;
+    request(&mut client, &command(&["client", "setname", "mine"]))?;
+    request(&mut client, &command(&["multi"]))?;
+    request(&mut client, &command(&["set", "key", "v"]))?;
+    assert_eq!(request(&mut client, &command(&["reset"]))?, b"+RESET\r\n");
+    assert_eq!(
+        request(&mut client, &command(&["exec"]))?,
+        b"-ERR EXEC without MULTI\r\n"
+    );
+    // nothing of the transaction ran, and the connection is back on database 0 without a name
+    assert_eq!(request(&mut client, &command(&["get", "key"]))?, b"$-1\r\n");
+    request(&mut client, &command(&["select", "1"]))?;
+    assert_eq!(request(&mut client, &command(&["get", "key"]))?, b"$-1\r\n");
+    assert_eq!(
+        request(&mut client, &command(&["client", "getname"]))?,
+        b"$-1\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_reset_leaves_subscriber_mode() -> Result<()> {
+    let addr = start_server()?;
+    let mut publisher = connect(addr)?;
+    let mut subscriber = connect(addr)?;
+    request(&mut subscriber, &command(&["subscribe", "news"]))?;
+    request(&mut subscriber, &command(&["psubscribe", "n*"]))?;
+    assert_eq!(
+        request(&mut subscriber, &command(&["reset"]))?,
+        b"+RESET\r\n"
+    );
+    assert_eq!(
+        request(&mut publisher, &command(&["publish", "news", "hello"]))?,
+        b":0\r\n"
+    );
+    assert_eq!(
+        request(&mut subscriber, &command(&["get", "k"]))?,
+        b"$-1\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_replication_offset_and_wait() -> Result<()> {
+    let addr = start_server()?;
+    let mut client = connect(addr)?;
+    let offset = |client: &mut TcpStream| -> Result<u64> {
+        let info = request(client, &command(&["info", "replication"]))?;
+        let info = String::from_utf8(info)?;
+        assert!(info.contains("role:master\r\n"));
+        let offset = info
+            .split("master_repl_offset:")
+            .nth(1)
+            .and_then(|rest| rest.split("\r\n").next())
+            .ok_or_else(|| anyhow::anyhow!("no offset in {:?}", info))?;
+        Ok(offset.parse()?)
+    };
+    assert_eq!(offset(&mut client)?, 0);
+
+    // reads and failed writes don't move it
+    request(&mut client, &command(&["get", "key"]))?;
+    request(&mut client, &command(&["incr", "key", "extra"]))?;
+    assert_eq!(offset(&mut client)?, 0);
+    let set = command(&["set", "key", "value"]);
+    request(&mut client, &set)?;
+    assert_eq!(offset(&mut client)?, set.len() as u64);
+    request(&mut client, &command(&["multi"]))?;
+    request(&mut client, &set)?;
+    request(&mut client, &command(&["exec"]))?;
+    // a transaction goes between a MULTI and an EXEC
+    let wrapping = command(&["multi"]).len() + command(&["exec"]).len();
+    assert_eq!(offset(&mut client)?, (2 * set.len() + wrapping) as u64);
+
+    // with no replica there is nothing to wait for, not even forever
+    let started = std::time::Instant::now();
+    assert_eq!(
+        request(&mut client, &command(&["wait", "0", "0"]))?,
+        b":0\r\n"
+    );
+    assert_eq!(
+        request(&mut client, &command(&["wait", "1", "0"]))?,
+        b":0\r\n"
+    );
+    assert!(started.elapsed() < Duration::from_secs(1));
+    Ok(())
+}
+
+#[test]
+fn test_save_and_bgsave() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("simple-redis-save-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let addr = start_server()?;
+    let mut client = connect(addr)?;
+    let set_dir = command(&["config", "set", "dir", dir.to_str().unwrap()]);
+    assert_eq!(request(&mut client, &set_dir)?, b"+OK\r\n");
+    let dump = dir.join("dump.rdb");
+
+    request(&mut client, &command(&["set", "key", "saved"]))?;
+    request(&mut client, &command(&["rpush", "list", "a", "b"]))?;
+    assert_eq!(request(&mut client, &command(&["save"]))?, b"+OK\r\n");
+    let loaded = Backend::new();
+    assert_eq!(loaded.load_from(&dump)?, 2);
+    assert_eq!(loaded.get(b"key"), Some(BulkString::from("saved").into()));
+
+    request(&mut client, &command(&["set", "key", "in the background"]))?;
+    assert_eq!(
+        request(&mut client, &command(&["bgsave"]))?,
+        b"+Background saving started\r\n"
+    );
+    let started = std::time::Instant::now();
+    loop {
+        let info = request(&mut client, &command(&["info", "persistence"]))?;
+        if String::from_utf8(info)?.contains("rdb_bgsave_in_progress:0\r\n") {
+            break;
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let loaded = Backend::new();
+    loaded.load_from(&dump)?;
+    assert_eq!(
+        loaded.get(b"key"),
+        Some(BulkString::from("in the background").into())
+    );
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_appendonly_replays_the_writes() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("simple-redis-aof-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let addr = start_server()?;
+    let mut client = connect(addr)?;
+    let set_dir = command(&["config", "set", "dir", dir.to_str().unwrap()]);
+    request(&mut client, &set_dir)?;
+    for (name, value) in [("appendfsync", "always"), ("appendonly", "yes")] {
+        let set = command(&["config", "set", name, value]);
+        assert_eq!(request(&mut client, &set)?, b"+OK\r\n");
+    }
+    request(&mut client, &command(&["set", "key", "1"]))?;
+    request(&mut client, &command(&["incrby", "key", "41"]))?;
+    request(&mut client, &command(&["select", "1"]))?;
+    request(&mut client, &command(&["sadd", "set", "a", "b"]))?;
+    request(&mut client, &command(&["multi"]))?;
+    request(&mut client, &command(&["srem", "set", "a"]))?;
+    request(&mut client, &command(&["exec"]))?;
+    let stop = command(&["config", "set", "appendonly", "no"]);
+    assert_eq!(request(&mut client, &stop)?, b"+OK\r\n");
+
+    let restored = Backend::new();
+    restored.load_aof(&dir.join("appendonly.aof"))?;
+    assert_eq!(restored.get(b"key"), Some(BulkString::from("42").into()));
+    assert!(restored.select(1)?.sismember(b"set", b"b"));
+    assert!(!restored.select(1)?.sismember(b"set", b"a"));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_replica_follows_its_primary() -> Result<()> {
+    let (primary, replica) = (start_server()?, start_server()?);
+    let (mut to_primary, mut to_replica) = (connect(primary)?, connect(replica)?);
+    request(&mut to_primary, &command(&["set", "before", "snapshot"]))?;
+    request(&mut to_replica, &command(&["set", "stale", "gone"]))?;
+
+    let port = primary.port().to_string();
+    let replicaof = command(&["replicaof", "127.0.0.1", &port]);
+    assert_eq!(request(&mut to_replica, &replicaof)?, b"+OK\r\n");
+    // the replica gets the data of the primary as of the snapshot, and nothing else
+    let started = std::time::Instant::now();
+    while request(&mut to_replica, &command(&["get", "before"]))? != b"$8\r\nsnapshot\r\n" {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(
+        request(&mut to_replica, &command(&["exists", "stale"]))?,
+        b":0\r\n"
+    );
+
+    // then every write, on whatever database
+    request(&mut to_primary, &command(&["select", "2"]))?;
+    request(&mut to_primary, &command(&["rpush", "list", "a", "b"]))?;
+    request(&mut to_primary, &command(&["incr", "counter"]))?;
+    // a transaction runs at once there too, database switches included
+    for args in [
+        &["multi"][..],
+        &["incr", "counter"],
+        &["select", "3"],
+        &["set", "moved", "yes"],
+    ] {
+        request(&mut to_primary, &command(args))?;
+    }
+    assert_eq!(
+        request(&mut to_primary, &command(&["exec"]))?,
+        b"*3\r\n:2\r\n+OK\r\n+OK\r\n"
+    );
+    request(&mut to_primary, &command(&["select", "2"]))?;
+    assert_eq!(
+        request(&mut to_primary, &command(&["wait", "1", "5000"]))?,
+        b":1\r\n"
+    );
+    request(&mut to_replica, &command(&["select", "2"]))?;
+    assert_eq!(
+        request(&mut to_replica, &command(&["lrange", "list", "0", "-1"]))?,
+        b"*2\r\n$1\r\na\r\n$1\r\nb\r\n"
+    );
+    assert_eq!(
+        request(&mut to_replica, &command(&["get", "counter"]))?,
+        b"$1\r\n2\r\n"
+    );
+    request(&mut to_replica, &command(&["select", "3"]))?;
+    assert_eq!(
+        request(&mut to_replica, &command(&["get", "moved"]))?,
+        b"$3\r\nyes\r\n"
+    );
+    request(&mut to_replica, &command(&["select", "2"]))?;
+
+    assert_eq!(
+        request(&mut to_replica, &command(&["set", "key", "value"]))?,
+        b"-READONLY You can't write against a read only replica.\r\n"
+    );
+    let info = String::from_utf8(request(
+        &mut to_replica,
+        &command(&["info", "replication"]),
+    )?)?;
+    assert!(info.contains("role:slave\r\n"));
+    assert!(info.contains("master_link_status:up\r\n"));
+    let info = String::from_utf8(request(
+        &mut to_primary,
+        &command(&["info", "replication"]),
+    )?)?;
+    assert!(info.contains("role:master\r\nconnected_slaves:1\r\nslave0:ip=127.0.0.1,"));
+
+    // a replica let go of its primary keeps the data, and takes writes again
+    let no_one = command(&["replicaof", "no", "one"]);
+    assert_eq!(request(&mut to_replica, &no_one)?, b"+OK\r\n");
+    assert_eq!(
+        request(&mut to_replica, &command(&["set", "key", "value"]))?,
+        b"+OK\r\n"
+    );
+    assert_eq!(
+        request(&mut to_replica, &command(&["get", "counter"]))?,
+        b"$1\r\n2\r\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_replica_matches_concurrent_writes() -> Result<()> {
+    let (primary, replica) = (start_server()?, start_server()?);
+    let mut to_replica = connect(replica)?;
+    let port = primary.port().to_string();
+    let replicaof = command(&["replicaof", "127.0.0.1", &port]);
+    assert_eq!(request(&mut to_replica, &replicaof)?, b"+OK\r\n");
+    let mut to_primary = connect(primary)?;
+    let started = std::time::Instant::now();
+    while request(&mut to_primary, &command(&["info", "replication"]))?
+        .windows(18)
+        .all(|w| w != b"connected_slaves:1")
+    {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // a client blocked on the list takes some of what the writers push
+    let mut blocked = connect(primary)?;
+    blocked.write_all(&command(&["blpop", "list", "0"]))?;
+    let writers = (0..4)
+        .map(|writer| {
+            std::thread::spawn(move || -> Result<()> {
+                let mut client = connect(primary)?;
+                for i in 0..50 {
+                    let value = format!("{}-{}", writer, i);
+                    request(&mut client, &command(&["set", "key", &value]))?;
+                    request(&mut client, &command(&["append", "log", &value]))?;
+                    request(&mut client, &command(&["rpush", "list", &value]))?;
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    read_reply(&mut blocked)?;
+    for writer in writers {
+        writer.join().unwrap()?;
+    }
+
+    assert_eq!(
+        request(&mut to_primary, &command(&["wait", "1", "5000"]))?,
+        b":1\r\n"
+    );
+    for args in [
+        &["get", "key"][..],
+        &["get", "log"],
+        &["lrange", "list", "0", "-1"],
+    ] {
+        assert_eq!(
+            request(&mut to_replica, &command(args))?,
+            request(&mut to_primary, &command(args))?,
+            "{:?} differs",
+            args
+        );
+    }
+    Ok(())
+}